@@ -0,0 +1,52 @@
+//! Rope edits through `Buffer`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use wyv::buffer::{Buffer, Position};
+
+const LINES: [usize; 2] = [1_000, 100_000];
+
+fn text(lines: usize) -> String {
+    (0..lines)
+        .map(|i| format!("    let value_{} = compute({}, \"{}\");\n", i, i, i))
+        .collect()
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for lines in LINES {
+        let text = text(lines);
+        // Typing a word a character at a time in the middle of the file.
+        group.bench_with_input(BenchmarkId::new("typing", lines), &text, |b, text| {
+            b.iter_batched(
+                || {
+                    let mut buffer = Buffer::from_text(text);
+                    buffer.goto(Position::new(lines / 2, 4));
+                    buffer
+                },
+                |mut buffer| {
+                    for c in ["f", "o", "o", "b", "a", "r", "\n"] {
+                        buffer.insert(c).unwrap();
+                    }
+                    buffer
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        // Pasting a large block at the top.
+        let block = self::text(100);
+        group.bench_with_input(BenchmarkId::new("paste", lines), &text, |b, text| {
+            b.iter_batched(
+                || Buffer::from_text(text),
+                |mut buffer| {
+                    buffer.insert(&block).unwrap();
+                    buffer
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert);
+criterion_main!(benches);
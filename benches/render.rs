@@ -0,0 +1,32 @@
+//! Full frames drawn against ratatui's `TestBackend`.
+
+use std::{fs, path::Path};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use wyv::{buffer::Position, harness::Harness};
+
+fn frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+
+    let mut h = Harness::new(Path::new("./src"), 200, 60).unwrap();
+    group.bench_function("tree", |b| b.iter(|| h.render().unwrap().area));
+
+    h.app.tree.expand_all();
+    h.app.open(Path::new("./src/app.rs"), None).unwrap();
+    group.bench_function("tree_and_buffer", |b| b.iter(|| h.render().unwrap().area));
+
+    // A file much taller than the screen, scrolled to the middle.
+    let path = std::env::temp_dir().join(format!("wyv-bench-render-{}.rs", std::process::id()));
+    let text: String = (0..100_000)
+        .map(|i| format!("fn item_{}() -> u32 {{ {} }}\n", i, i))
+        .collect();
+    fs::write(&path, text).unwrap();
+    h.app.open(&path, Some(Position::new(50_000, 0))).unwrap();
+    group.bench_function("large_buffer", |b| b.iter(|| h.render().unwrap().area));
+    fs::remove_file(path).unwrap();
+
+    group.finish();
+}
+
+criterion_group!(benches, frame);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+//! File tree scanning and flattening on synthetic trees.
+
+use std::{fs, path::PathBuf};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use wyv::widgets::file_tree::{FileNode, FileTree, TreeOptions};
+
+const SIZES: [usize; 2] = [10_000, 100_000];
+const FILES_PER_DIR: usize = 100;
+
+/// A tree of `entries` files, `FILES_PER_DIR` to a directory.
+fn synthetic_node(root: &str, entries: usize) -> FileNode {
+    let dirs = (0..entries.div_ceil(FILES_PER_DIR))
+        .map(|d| {
+            let files = (0..FILES_PER_DIR.min(entries - d * FILES_PER_DIR))
+                .map(|f| FileNode::File(format!("file{:03}.rs", f)))
+                .collect();
+            FileNode::Directory(format!("dir{:04}", d), files)
+        })
+        .collect();
+    FileNode::Directory(root.to_string(), dirs)
+}
+
+/// Writes the same layout as [`synthetic_node`] to a temporary directory.
+fn synthetic_dir(entries: usize) -> PathBuf {
+    let root =
+        std::env::temp_dir().join(format!("wyv-bench-tree-{}-{}", entries, std::process::id()));
+    for d in 0..entries.div_ceil(FILES_PER_DIR) {
+        let dir = root.join(format!("dir{:04}", d));
+        fs::create_dir_all(&dir).unwrap();
+        for f in 0..FILES_PER_DIR.min(entries - d * FILES_PER_DIR) {
+            fs::write(dir.join(format!("file{:03}.rs", f)), "").unwrap();
+        }
+    }
+    root
+}
+
+fn scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+    group.sample_size(10);
+    for entries in SIZES {
+        let root = synthetic_dir(entries);
+        group.bench_with_input(BenchmarkId::from_parameter(entries), &root, |b, root| {
+            b.iter(|| FileNode::new_from_path_with(root, &TreeOptions::default()).unwrap())
+        });
+        fs::remove_dir_all(root).unwrap();
+    }
+    group.finish();
+}
+
+fn visible(c: &mut Criterion) {
+    let mut group = c.benchmark_group("visible");
+    let empty = std::env::temp_dir().join(format!("wyv-bench-list-{}", std::process::id()));
+    fs::create_dir_all(&empty).unwrap();
+    for entries in SIZES {
+        let mut tree = FileTree::new(&empty).unwrap();
+        tree.set_root_node(synthetic_node("root", entries));
+        group.bench_function(format!("{}/expand_all", entries), |b| {
+            b.iter(|| tree.expand_all())
+        });
+        // A screenful at the top, and one at the bottom as when the last
+        // entry is selected.
+        let rows = tree.row_count();
+        for offset in [0, rows - 50] {
+            group.bench_function(format!("{}/{}", entries, offset), |b| {
+                b.iter(|| tree.visible(offset..offset + 50).count())
+            });
+        }
+    }
+    fs::remove_dir_all(empty).unwrap();
+    group.finish();
+}
+
+criterion_group!(benches, scan, visible);
+criterion_main!(benches);
@@ -0,0 +1,4806 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent};
+use ratatui::layout;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    badges::{self, GitBadges},
+    buffer::{self, Buffer, Position},
+    cli, commands,
+    config::{self, Action, Config, ConfigSources, ConfigWatcher, LoadedConfig, Theme},
+    crash, data,
+    diff::Diff,
+    features,
+    follow::{Follow, Level},
+    format, git, harness,
+    history::{CommandHistory, HistorySearch, HistoryStore},
+    jobs::{self, CancelToken, Jobs},
+    jumps::{Jump, JumpList},
+    keymaps,
+    layers::{Layer, Layers},
+    lint,
+    logging::Logger,
+    lsp::{self, Diagnostic, FileEdit, Location, LspEvent, LspHost, SignatureHelp},
+    marks::{self, Marks},
+    merge::{Merge, Side},
+    metadata::MetadataCache,
+    mux::{self, Multiplexer},
+    paths,
+    perf::PerfStats,
+    plugins::{PluginConfig, PluginEvent, PluginHost},
+    project,
+    projects::ProjectStore,
+    pty::{Pty, TerminalPosition},
+    recent::RecentFiles,
+    remote::{self, Request, RpcError},
+    review::Review,
+    runtime::{Message, Spawner},
+    scripting::{Effect, KeyOutcome, Scripting},
+    search,
+    session::{Layout, Session, SessionStore},
+    shell::{self, LineRange},
+    snippet,
+    spell::{Misspelling, SpellChecker, Suggestions},
+    ssh::{Connection, SshEvent, SshRoot, SshUrl},
+    symbols::{self, Scanner, Symbol},
+    tasks::{self, TaskConfig, TaskRun},
+    tutor::{self, Tutor, TutorStore},
+    widgets::{
+        branch_picker::BranchPicker,
+        completion::Completion,
+        data_tree::DataTree,
+        file_tree::{Detail, FileTree},
+        git_log::GitLog,
+        git_status::GitStatus,
+        hex::{self, HexEditor},
+        locations::{self, Locations},
+        outline::{Outline, OutlineItem},
+        pane_cache::PaneCache,
+        popup_menu::MenuKey,
+        preview::{self, Preview},
+        project_picker::ProjectPicker,
+        recent_picker::RecentPicker,
+        search::{Replace, Search},
+        select_list::ListKey,
+        symbol_picker::SymbolPicker,
+        terminal::CopyMode,
+        text_input::{InputKey, TextInput},
+    },
+};
+use ropey::Rope;
+
+/// Rows beyond each edge of the tree's view whose metadata is fetched ahead
+/// of being scrolled to.
+pub const METADATA_LOOKAHEAD: usize = 20;
+/// How often open buffers' files are looked at to see if they've gone.
+pub const CHECK_FILES_EVERY: Duration = Duration::from_secs(1);
+
+/// What the status line says about a buffer whose file has gone.
+pub fn orphan_warning(buffer: &Buffer) -> String {
+    format!(
+        "{} was deleted or moved; :saveas <path> to keep it",
+        buffer.title()
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    /// Typing at the `:` prompt.
+    Command,
+    /// Typing into the active buffer.
+    Insert,
+    /// Asking what to do with unsaved buffers before quitting.
+    ConfirmQuit,
+    /// Asking whether to run the programs the project config lists.
+    ConfirmTrust,
+    /// Moving through the blame pane beside the active buffer.
+    Blame,
+    /// Staging changes in the git status panel.
+    GitStatus,
+    /// Browsing the git log panel.
+    GitLog,
+    /// Picking a branch in the branch overlay.
+    Branches,
+    /// Resolving conflicts in the merge view.
+    Merge,
+    /// Scrolling through the diff view.
+    Diff,
+    /// Moving through the locations panel.
+    Locations,
+    /// Confirming the edits a rename takes.
+    Rename,
+    /// Typing into the terminal pane.
+    Terminal,
+    /// Selecting lines of the terminal's screen and scrollback.
+    TerminalCopy,
+    /// Typing a pattern into the project search overlay.
+    Search,
+    /// Picking which of the changes a replace across files makes.
+    Replace,
+    /// Typing a query into the symbol overlay.
+    Symbols,
+    /// Moving through the outline sidebar.
+    Outline,
+    /// Picking a replacement for a misspelled word.
+    Spell,
+    /// Moving through, searching and editing a binary file's bytes.
+    Hex,
+    /// Moving through a JSON or YAML document's structure.
+    Structure,
+    /// Following a file as it grows, e.g. a build or server log.
+    Follow,
+    /// Picking a file to reopen in the recent files overlay.
+    Recent,
+    /// Picking a workspace to switch to in the project overlay.
+    Projects,
+    /// Picking a changed file of the branch under review.
+    Review,
+    /// Asking whether to restore the session a run that was cut short left.
+    ConfirmRestore,
+}
+
+impl Mode {
+    /// What the mode is called, as `insert` or `git_status`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Command => "command",
+            Mode::Insert => "insert",
+            Mode::ConfirmQuit => "confirm_quit",
+            Mode::ConfirmRestore => "confirm_restore",
+            Mode::ConfirmTrust => "confirm_trust",
+            Mode::Blame => "blame",
+            Mode::GitStatus => "git_status",
+            Mode::GitLog => "git_log",
+            Mode::Branches => "branches",
+            Mode::Merge => "merge",
+            Mode::Diff => "diff",
+            Mode::Locations => "locations",
+            Mode::Rename => "rename",
+            Mode::Terminal => "terminal",
+            Mode::TerminalCopy => "terminal_copy",
+            Mode::Search => "search",
+            Mode::Replace => "replace",
+            Mode::Symbols => "symbols",
+            Mode::Outline => "outline",
+            Mode::Spell => "spell",
+            Mode::Hex => "hex",
+            Mode::Structure => "structure",
+            Mode::Follow => "follow",
+            Mode::Review => "review",
+            Mode::Recent => "recent",
+            Mode::Projects => "projects",
+        }
+    }
+}
+
+/// Panels shown below the editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Panel {
+    Log,
+    Jobs,
+    Plugins,
+    Git,
+    GitLog,
+    Locations,
+    Tasks,
+    Terminal,
+}
+
+pub struct App {
+    pub config: Config,
+    pub tree: FileTree,
+    /// Sizes and modification times of files, for the tree's detail
+    /// columns.
+    pub metadata: MetadataCache,
+    /// The tree as last drawn, redrawn only when it changes.
+    pub tree_pane: PaneCache,
+    /// Git status letters for the tree's `git` column.
+    pub badges: GitBadges,
+    pub buffers: Vec<Buffer>,
+    /// How many scratch buffers have been opened, so that each gets a name
+    /// of its own even after earlier ones are saved.
+    scratches: usize,
+    pub active: Option<usize>,
+    pub status: Option<String>,
+    pub mode: Mode,
+    pub command_line: TextInput,
+    pub panel: Option<Panel>,
+    pub logger: Option<Logger>,
+    /// Runs I/O off the UI thread. Without one (e.g. in tests) the same work
+    /// happens synchronously.
+    pub spawner: Option<Spawner>,
+    pub jobs: Jobs,
+    pub plugins: PluginHost,
+    pub scripts: Option<Scripting>,
+    pub exit: bool,
+    /// Set to stop the process; the main loop does it, then clears this.
+    pub suspend: bool,
+    /// A buffer to save through `editor.elevate`. The main loop does it
+    /// with the terminal handed back, so the helper can ask for a password.
+    pub elevated_save: Option<usize>,
+    /// Opens every buffer read-only (`--readonly`).
+    pub readonly: bool,
+    /// Prints the active buffer on exit (`--stdout`), so quitting doesn't
+    /// ask about its changes.
+    pub to_stdout: bool,
+    /// Theme chosen on the command line, kept across config reloads.
+    pub theme_override: Option<Theme>,
+    /// The files the config was loaded from, for trusting the project.
+    pub config_sources: Option<ConfigSources>,
+    /// Stops the background watcher of those files.
+    pub config_watch: Option<CancelToken>,
+    /// Where sessions are saved, if anywhere.
+    pub sessions: Option<SessionStore>,
+    /// Where the workspace roots opened are listed, if anywhere.
+    pub projects: Option<ProjectStore>,
+    /// Where the command history is kept, if anywhere.
+    pub history_store: Option<HistoryStore>,
+    /// The lines run at the `:` prompt.
+    pub command_history: CommandHistory,
+    /// While Up and Down step through the history: the line shown, and
+    /// what had been typed before.
+    history_step: Option<(usize, String)>,
+    /// A ctrl-r search of the history, while one is under way.
+    pub history_search: Option<HistorySearch>,
+    /// The tutor, while `--tutor` has lessons left.
+    pub tutor: Option<Tutor>,
+    /// Plugins the project config asks for that wait on the user's trust.
+    pub untrusted: Vec<PluginConfig>,
+    /// The project config as last loaded: what trusting the project trusts.
+    project_text: Option<String>,
+    /// Options changed with `:set`, kept across config reloads.
+    pub overrides: Vec<(String, Value)>,
+    pub perf: PerfStats,
+    pub show_perf: bool,
+    /// Shows blame for the cursor line after its text.
+    pub blame_inline: bool,
+    /// Blame for files as last saved, keyed by path.
+    pub blame: HashMap<PathBuf, Vec<git::BlameLine>>,
+    /// What the git panel shows, once it has been opened.
+    pub git_status: Option<GitStatus>,
+    /// What the git log panel shows, once it has been opened.
+    pub git_log: Option<GitLog>,
+    /// The branch overlay's contents while it is open.
+    pub branches: Option<BranchPicker>,
+    /// The recent files overlay's contents while it is open.
+    pub recent_picker: Option<RecentPicker>,
+    /// The project overlay's contents while it is open.
+    pub project_picker: Option<ProjectPicker>,
+    /// The buffer being resolved in the merge view, and its conflicts.
+    pub merge: Option<(usize, Merge)>,
+    /// The diff view's contents while it is open.
+    pub diff: Option<Diff>,
+    /// The branch under review and the notes left on it, kept until
+    /// another review starts.
+    pub review: Option<Review>,
+    /// The multiplexer pane `:send` types into, once found.
+    pub mux_pane: Option<String>,
+    pub lsp: LspHost,
+    /// The completion menu while it is open.
+    pub completion: Option<Completion>,
+    /// Where each layer of the screen was last drawn, for the mouse.
+    pub layers: Layers,
+    /// Documentation shown at the cursor until the next key.
+    pub hover: Option<String>,
+    /// The signature of the call being typed, while in one.
+    pub signature: Option<SignatureHelp>,
+    /// Where the cursor was before each jump, for `ctrl-o` and `ctrl-i`.
+    pub jumps: JumpList,
+    /// Marks of files not open, and where the global ones are.
+    marks: Marks,
+    /// [`Action::SetMark`] or [`Action::GotoMark`] while waiting for the
+    /// key naming the mark.
+    pending_mark: Option<Action>,
+    /// The files opened lately and where their cursors were left.
+    recent: RecentFiles,
+    /// The location list: what the locations panel lists and `]` and `[`
+    /// step through, once search results, diagnostics, task problems or
+    /// references have filled it.
+    pub locations: Option<Locations>,
+    /// What the language servers last reported for each file.
+    pub diagnostics: BTreeMap<PathBuf, Vec<Diagnostic>>,
+    /// What the linters found in each file when it was last saved.
+    pub lints: BTreeMap<PathBuf, Vec<Diagnostic>>,
+    /// The edits of a rename waiting to be confirmed, and their preview.
+    pub rename: Option<(Vec<FileEdit>, Vec<String>)>,
+    /// The last task started, with its output.
+    pub task_run: Option<TaskRun>,
+    /// The integrated terminal's shell, once started.
+    pub pty: Option<Pty>,
+    /// Shows the terminal pane beside the editor; below it, it is a panel.
+    pub terminal_split: bool,
+    /// Copy mode's cursor and selection while in it.
+    pub copy_mode: Option<CopyMode>,
+    /// Text copied from the terminal, for pasting back into it or into a
+    /// buffer.
+    pub register: Option<String>,
+    /// The project search's pattern and results, kept after it closes.
+    pub search: Option<Search>,
+    /// The changes of a replace waiting to be made, while previewing them.
+    pub replace: Option<Replace>,
+    /// The symbol overlay's contents while it is open.
+    pub symbols: Option<SymbolPicker>,
+    /// The outline sidebar while it is shown.
+    pub outline: Option<Outline>,
+    /// The structural viewer's tree while it is open.
+    pub structure: Option<DataTree>,
+    /// The binary file open in the hex view.
+    pub hex: Option<HexEditor>,
+    /// The markdown preview pane while it is shown.
+    pub preview: Option<Preview>,
+    /// The file being followed, while it is.
+    pub follow: Option<Follow>,
+    /// The remote tree the file tree mirrors, once rooted at an ssh:// URL.
+    pub ssh: Option<SshRoot>,
+    /// The spell checker, loaded the first time it is needed.
+    spell: Option<SpellChecker>,
+    /// The replacements offered for a misspelled word while picking one.
+    pub suggestions: Option<Suggestions>,
+    /// When open buffers' files were last looked at.
+    files_checked: Option<Instant>,
+    /// When the session was last snapshotted.
+    session_snapshotted: Option<Instant>,
+    /// The snapshot of a run that was cut short, while asking whether to
+    /// restore it.
+    pub pending_restore: Option<Session>,
+    /// What last changed, for a screen reader to read out: the file shown
+    /// or the mode entered.
+    pub announcement: Option<String>,
+}
+
+impl App {
+    pub fn new(root: &Path, config: Config) -> Result<Self> {
+        let tree = FileTree::with_options(root, config.tree.clone())?;
+        Ok(App::with_tree(tree, config))
+    }
+
+    /// Like [`new`](Self::new), but lists only the root's own entries so
+    /// that a huge workspace opens at once. Set a spawner and call
+    /// [`rescan_tree`](Self::rescan_tree) to fill in the rest.
+    pub fn shallow(root: &Path, config: Config) -> Result<Self> {
+        let tree = FileTree::shallow(root, config.tree.clone())?;
+        Ok(App::with_tree(tree, config))
+    }
+
+    fn with_tree(tree: FileTree, config: Config) -> Self {
+        App {
+            config,
+            tree,
+            metadata: MetadataCache::default(),
+            tree_pane: PaneCache::default(),
+            badges: GitBadges::default(),
+            buffers: Vec::new(),
+            scratches: 0,
+            active: None,
+            status: None,
+            mode: Mode::Normal,
+            command_line: TextInput::default(),
+            panel: None,
+            logger: None,
+            spawner: None,
+            jobs: Jobs::default(),
+            plugins: PluginHost::default(),
+            scripts: None,
+            exit: false,
+            suspend: false,
+            elevated_save: None,
+            readonly: false,
+            to_stdout: false,
+            theme_override: None,
+            config_sources: None,
+            config_watch: None,
+            sessions: None,
+            projects: None,
+            history_store: None,
+            command_history: CommandHistory::default(),
+            history_step: None,
+            history_search: None,
+            tutor: None,
+            untrusted: Vec::new(),
+            project_text: None,
+            overrides: Vec::new(),
+            perf: PerfStats::default(),
+            show_perf: false,
+            blame_inline: false,
+            blame: HashMap::new(),
+            git_status: None,
+            git_log: None,
+            branches: None,
+            recent_picker: None,
+            project_picker: None,
+            merge: None,
+            diff: None,
+            lsp: LspHost::default(),
+            completion: None,
+            layers: Layers::default(),
+            hover: None,
+            signature: None,
+            jumps: JumpList::default(),
+            marks: Marks::default(),
+            pending_mark: None,
+            recent: RecentFiles::default(),
+            locations: None,
+            diagnostics: BTreeMap::new(),
+            lints: BTreeMap::new(),
+            rename: None,
+            task_run: None,
+            pty: None,
+            terminal_split: false,
+            copy_mode: None,
+            register: None,
+            search: None,
+            replace: None,
+            symbols: None,
+            outline: None,
+            structure: None,
+            hex: None,
+            preview: None,
+            follow: None,
+            review: None,
+            mux_pane: None,
+            ssh: None,
+            spell: None,
+            suggestions: None,
+            files_checked: None,
+            session_snapshotted: None,
+            pending_restore: None,
+            announcement: None,
+        }
+    }
+
+    /// Applies a reloaded config, restarting plugins if their list changed,
+    /// and asks about newly untrusted project plugins.
+    pub fn apply_loaded(&mut self, loaded: LoadedConfig) -> Result<()> {
+        let restart = loaded.config.plugins != self.config.plugins;
+        let restart_lsp = loaded.config.lsp != self.config.lsp;
+        self.apply_config(loaded.config)?;
+        if restart {
+            self.start_plugins();
+        }
+        if restart_lsp {
+            self.start_language_servers();
+        }
+        self.set_untrusted(loaded.untrusted, loaded.project_text);
+        Ok(())
+    }
+
+    /// Notes the plugins waiting on trust in the project config loaded as
+    /// `project_text`, and asks about them.
+    pub fn set_untrusted(&mut self, plugins: Vec<PluginConfig>, project_text: Option<String>) {
+        self.untrusted = plugins;
+        self.project_text = project_text;
+        if !self.untrusted.is_empty() && self.mode == Mode::Normal {
+            self.mode = Mode::ConfirmTrust;
+        }
+    }
+
+    /// Trusts the project config as it was last loaded, which is what the
+    /// user was asked about, and starts its plugins.
+    pub fn trust_project(&mut self) -> Result<()> {
+        let (sources, text) = match (&self.config_sources, &self.project_text) {
+            (Some(sources), Some(text)) => (sources, text),
+            _ => bail!("there is no project config to trust"),
+        };
+        sources.trust_project(text)?;
+        let loaded = sources.load()?;
+        tracing::info!("project config trusted");
+        self.apply_loaded(loaded)?;
+        self.status = Some("Project config trusted".to_string());
+        Ok(())
+    }
+
+    /// (Re)starts the configured plugins. Needs a spawner to deliver their
+    /// messages, so does nothing without one.
+    pub fn start_plugins(&mut self) {
+        let spawner = match &self.spawner {
+            Some(spawner) => spawner,
+            None => return,
+        };
+        // Stop the old ones first so a plugin never runs twice.
+        self.plugins = PluginHost::default();
+        let (plugins, errors) = PluginHost::start(&self.config.plugins, &spawner.sender());
+        for e in errors {
+            tracing::error!(error = %e, "plugin failed to start");
+            self.status = Some(e.to_string());
+        }
+        self.plugins = plugins;
+    }
+
+    /// (Re)starts language servers for the open buffers; others start as
+    /// files they handle are opened. Needs a spawner, like plugins.
+    pub fn start_language_servers(&mut self) {
+        let spawner = match &self.spawner {
+            Some(spawner) => spawner,
+            None => return,
+        };
+        self.completion = None;
+        // Stop the old ones first so a server never runs twice.
+        self.lsp = LspHost::default();
+        self.lsp = LspHost::new(&self.config.lsp, self.tree.root(), spawner.sender());
+        for buffer in self.buffers.iter().filter(|b| !b.is_large()) {
+            if let Some(path) = buffer.path() {
+                if let Err(e) = self.lsp.did_open(path, buffer.text()) {
+                    tracing::error!(error = %e, "language server failed to start");
+                    self.status = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Swaps in a reloaded config, applying the parts that need more than a
+    /// redraw to take effect.
+    pub fn apply_config(&mut self, mut config: Config) -> Result<()> {
+        let rescan = self.tree.needs_rescan(&config.tree);
+        self.tree.set_options(config.tree.clone());
+        if rescan {
+            self.rescan_tree()?;
+        }
+        if let Some(logger) = &self.logger {
+            logger.set_level(&config.log.level)?;
+        }
+        if let Some(theme) = &self.theme_override {
+            config.theme = theme.clone();
+        }
+        if let Some(scripts) = &self.scripts {
+            for (key, value) in scripts.overrides() {
+                config.set(&key, value)?;
+            }
+        }
+        for (key, value) in &self.overrides {
+            config.set(key, value.clone())?;
+        }
+        if let Some(spell) = &mut self.spell {
+            spell.set_config_words(&config.spell.words);
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Changes an option for the rest of the session, as `:set` does. A
+    /// string given for `theme` names a theme.
+    pub fn set_option(&mut self, name: &str, value: Value) -> Result<()> {
+        let key = config::resolve_option(name);
+        let value = match (key, value) {
+            ("theme", Value::String(name)) => serde_json::to_value(Theme::named(&name)?)?,
+            (_, value) => value,
+        };
+        let mut config = self.config.clone();
+        config.set(key, value.clone())?;
+        if key == "theme" || key.starts_with("theme.") {
+            self.theme_override = None;
+        }
+        self.overrides.retain(|(k, _)| k != key);
+        self.overrides.push((key.to_string(), value));
+        self.apply_config(config)
+    }
+
+    /// Takes on keymap profile `name`, a built-in one or a file, for the
+    /// rest of the session, keeping the bindings that set the keymap apart
+    /// from the defaults. Where those disagree with the profile, they're
+    /// listed in a buffer.
+    pub fn import_keymap(&mut self, name: &str) -> Result<()> {
+        let profile = keymaps::load(name, self.tree.root())?;
+        let (keymap, conflicts) = keymaps::apply(&self.config.keymap, &profile);
+        self.set_option("keymap", serde_json::to_value(keymap)?)?;
+        if conflicts.is_empty() {
+            self.status = Some(format!("Took on the {} keymap", name));
+            return Ok(());
+        }
+        let mut report = format!(
+            "Keys bound both by you and by the {} keymap; yours are kept.\n\n",
+            name
+        );
+        for conflict in &conflicts {
+            report.push_str(&format!(
+                "{:<16} yours: {:<20} {}: {}\n",
+                conflict.key,
+                conflict.yours.name(),
+                name,
+                conflict.profile.name()
+            ));
+        }
+        self.open_scratch("keymap conflicts", &report);
+        self.status = Some(format!(
+            "Took on the {} keymap; {} of your bindings differ from it",
+            name,
+            conflicts.len()
+        ));
+        Ok(())
+    }
+
+    /// Writes the keymap in effect to `path` as a profile to import.
+    pub fn export_keymap(&mut self, path: &Path) -> Result<()> {
+        let path = self.tree.root().join(path);
+        fs::write(&path, keymaps::export(&self.config.keymap)?)
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        self.status = Some(format!("Wrote the keymap to {}", path.display()));
+        Ok(())
+    }
+
+    /// Where the current value of an option comes from.
+    pub fn option_source(&self, name: &str) -> &'static str {
+        let key = config::resolve_option(name);
+        let overridden = |overrides: &[(String, Value)]| overrides.iter().any(|(k, _)| k == key);
+        if overridden(&self.overrides) {
+            "set"
+        } else if self.theme_override.is_some() && key.starts_with("theme") {
+            "command line"
+        } else if self
+            .scripts
+            .as_ref()
+            .is_some_and(|s| overridden(&s.overrides()))
+        {
+            "init.lua"
+        } else {
+            self.config_sources
+                .as_ref()
+                .and_then(|s| s.source_of(key))
+                .unwrap_or("default")
+        }
+    }
+
+    /// Applies what the last script call asked for. Effects can trigger
+    /// more script calls, so this repeats until the queue settles.
+    pub fn apply_script_effects(&mut self) {
+        for _ in 0..16 {
+            let effects = match &self.scripts {
+                Some(scripts) => scripts.take_effects(),
+                None => return,
+            };
+            if effects.is_empty() {
+                return;
+            }
+
+            for effect in effects {
+                let result = match effect {
+                    Effect::Status(message) => {
+                        self.status = Some(message);
+                        Ok(())
+                    }
+                    Effect::Open(path) => self.open(&path, None),
+                    Effect::Action(action) => {
+                        self.run(action);
+                        Ok(())
+                    }
+                    Effect::Command(line) => commands::execute(self, &line),
+                    Effect::Set(key, value) => self.config.set(&key, value),
+                };
+                if let Err(e) = result {
+                    self.status = Some(e.to_string());
+                }
+            }
+        }
+        tracing::warn!("script effects kept re-triggering; giving up");
+    }
+
+    pub fn rescan_tree(&mut self) -> Result<()> {
+        self.badges.changed_all();
+        match &self.spawner {
+            Some(spawner) => {
+                let root = self.tree.root().to_path_buf();
+                jobs::scan_tree(&mut self.jobs, spawner, root, self.tree.options().clone());
+                Ok(())
+            }
+            None => self.tree.rescan(),
+        }
+    }
+
+    /// Whether work is running whose results should show up soon: jobs,
+    /// a task, the terminal's shell or a followed file.
+    pub fn is_busy(&self) -> bool {
+        !self.jobs.is_empty()
+            || self.task_run.as_ref().is_some_and(|run| run.running())
+            || self.pty.is_some()
+            || self.follow.is_some()
+    }
+
+    /// Keeps the tree's git badges up to date while its `git` column is
+    /// shown, running the `git status` queries that are due.
+    pub fn refresh_badges(&mut self) {
+        if !self.tree.options().details.contains(&Detail::Git) {
+            return;
+        }
+        let root = self.tree.canonical_root().to_owned();
+        if self.badges.should_look_from(&root) {
+            match git::repo_root(&root) {
+                Ok(repo) => self.badges.track(&repo),
+                Err(e) => tracing::debug!(root = %root.display(), error = %e, "no badges"),
+            }
+        }
+        while let Some(query) = self.badges.next_query(Instant::now()) {
+            match &self.spawner {
+                Some(spawner) => {
+                    jobs::git_status(&mut self.jobs, spawner, query);
+                }
+                None => {
+                    let result = badges::run(&query);
+                    self.badges.finished(query, result);
+                }
+            }
+        }
+    }
+
+    /// Orphans the buffers whose files have been deleted or moved since
+    /// they were read or saved, warning about them, and adopts them again
+    /// if their files come back. Looks at most once every
+    /// [`CHECK_FILES_EVERY`].
+    pub fn check_files(&mut self, now: Instant) {
+        if self
+            .files_checked
+            .is_some_and(|at| now.duration_since(at) < CHECK_FILES_EVERY)
+        {
+            return;
+        }
+        self.files_checked = Some(now);
+        for buffer in &mut self.buffers {
+            if buffer.check_on_disk() && buffer.is_orphaned() {
+                tracing::warn!(path = ?buffer.path(), "open file deleted or moved");
+                self.status = Some(orphan_warning(buffer));
+            }
+        }
+    }
+
+    /// Snapshots the session every `editor.autosave_session` seconds, for
+    /// restoring should this run be cut short. Not while a snapshot left by
+    /// the last run waits to be restored.
+    pub fn autosave_session(&mut self, now: Instant) {
+        let every = Duration::from_secs(self.config.editor.autosave_session);
+        let due = self
+            .session_snapshotted
+            .is_none_or(|at| now.duration_since(at) >= every);
+        if every.is_zero() || !due || self.pending_restore.is_some() {
+            return;
+        }
+        self.session_snapshotted = Some(now);
+        if let Some(store) = &self.sessions {
+            if let Err(e) = store.snapshot(self.tree.root(), &self.session()) {
+                tracing::warn!(error = %e, "could not snapshot the session");
+            }
+        }
+    }
+
+    /// Queues the metadata for the tree's detail columns to be fetched: only
+    /// for the rows in a view `height` rows tall, then the
+    /// [`METADATA_LOOKAHEAD`] rows either side of them, nearest first.
+    /// Called before each frame is drawn.
+    pub fn fetch_metadata(&mut self, height: usize) {
+        if self
+            .tree
+            .options()
+            .details
+            .iter()
+            .all(|&d| d == Detail::Git)
+        {
+            return;
+        }
+        let offset = self.tree.scroll_offset(height);
+        let end = offset + height + METADATA_LOOKAHEAD;
+        let above = offset.saturating_sub(METADATA_LOOKAHEAD)..offset;
+        let rows = self
+            .tree
+            .visible(offset..end)
+            .chain(self.tree.visible(above).rev())
+            .filter(|node| node.more().is_none())
+            .map(|node| self.tree.node_path(node));
+        let paths = self.metadata.queue(rows);
+        if paths.is_empty() {
+            return;
+        }
+        match &self.spawner {
+            Some(spawner) => {
+                jobs::fetch_metadata(&mut self.jobs, spawner, self.metadata.clone(), paths);
+            }
+            None => self.metadata.fetch(&paths),
+        }
+    }
+
+    /// Applies a result sent back from the runtime.
+    pub fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::TreeScanned { root, result } => {
+                // Results for a root we have since moved away from are stale.
+                if root != self.tree.root() {
+                    return;
+                }
+                match result {
+                    Ok(node) => {
+                        self.metadata.clear();
+                        self.tree.set_root_node(node);
+                    }
+                    Err(e) => {
+                        tracing::error!(root = %root.display(), error = %e, "tree scan failed");
+                        self.status = Some(e.to_string());
+                    }
+                }
+            }
+            Message::FileChunk { job, path, text } => {
+                let buffer = self
+                    .buffers
+                    .iter_mut()
+                    .find(|b| b.path() == Some(&path) && b.loading_progress().is_some());
+                match (buffer, text) {
+                    (Some(buffer), Some(text)) => buffer.append_loaded(&text),
+                    (Some(buffer), None) => {
+                        buffer.finish_loading();
+                        buffer.set_readonly(self.readonly);
+                    }
+                    // The buffer was closed before the file was all read.
+                    (None, _) => {
+                        self.jobs.cancel(job);
+                    }
+                }
+            }
+            Message::Job(event) => {
+                if let Some((job, Err(e))) = self.jobs.handle(event) {
+                    self.status = Some(format!("{}: {}", job.name, e));
+                }
+            }
+            Message::Plugin {
+                generation,
+                index,
+                message,
+            } => {
+                if let Some(status) = self.plugins.handle((generation, index), message) {
+                    self.status = Some(status);
+                }
+            }
+            Message::PluginExited { generation, index } => {
+                if let Some(status) = self.plugins.exited((generation, index)) {
+                    self.status = Some(status);
+                }
+            }
+            Message::Lsp {
+                generation,
+                index,
+                message,
+            } => {
+                let buffers = &self.buffers;
+                let text_of = |path: &Path| {
+                    let buffer = buffers.iter().find(|b| b.path() == Some(path));
+                    buffer.map(|b| b.text().clone())
+                };
+                if let Some(event) = self.lsp.handle((generation, index), message, text_of) {
+                    self.handle_lsp_event(event);
+                }
+            }
+            Message::LspExited { generation, index } => {
+                if let Some(status) = self.lsp.exited((generation, index)) {
+                    self.status = Some(status);
+                }
+            }
+            Message::TaskOutput { id, line } => {
+                if let Some(run) = self.task_run.as_mut().filter(|r| r.id == id) {
+                    run.push_line(line);
+                }
+            }
+            Message::TaskExited { id, code } => {
+                if let Some(run) = self.task_run.as_mut().filter(|r| r.id == id) {
+                    run.finish(code);
+                    // A killed task reports why through its job.
+                    if code.is_some() {
+                        let outcome = match run.succeeded() {
+                            true => "finished",
+                            false => "failed",
+                        };
+                        let mut status = format!("{} {} ({})", run.name, outcome, run.state());
+                        match run.problems().len() {
+                            0 => (),
+                            1 => status += "; 1 problem, :problems shows it",
+                            n => status += &format!("; {} problems, :problems lists them", n),
+                        }
+                        self.status = Some(status);
+                    }
+                }
+            }
+            Message::SearchResult { id, file } => {
+                if let Some(search) = self.search.as_mut().filter(|s| s.job == Some(id)) {
+                    search.add(file);
+                }
+            }
+            Message::SearchDone { id, truncated } => {
+                if let Some(search) = self.search.as_mut().filter(|s| s.job == Some(id)) {
+                    search.finish(truncated);
+                }
+            }
+            Message::SymbolsIndexed { id, symbols } => {
+                if let Some(picker) = self.symbols.as_mut().filter(|p| p.job == Some(id)) {
+                    picker.set_symbols(symbols);
+                }
+            }
+            Message::TerminalOutput(bytes) => {
+                if let Some(pty) = &mut self.pty {
+                    pty.process(&bytes);
+                }
+            }
+            Message::Ssh(event) => self.handle_ssh_event(event),
+            Message::Linted { path, result } => self.linted(path, result),
+            Message::GitStatus { query, result } => self.badges.finished(query, result),
+            Message::FileChanged(path) => {
+                if let Err(e) = self.reload_followed(&path) {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Message::TerminalExited => {
+                if self.pty.take().is_some() {
+                    self.hide_terminal();
+                    self.status = Some("Terminal exited".to_string());
+                }
+            }
+            Message::ConfigReloaded(reloaded) => {
+                match (*reloaded).and_then(|loaded| self.apply_loaded(loaded)) {
+                    Ok(()) => {
+                        tracing::info!("config reloaded");
+                        self.status = Some("Config reloaded".to_string());
+                    }
+                    Err(e) => {
+                        tracing::error!(error = %e, "config reload failed");
+                        self.status = Some(format!("Config error: {}", e));
+                    }
+                }
+            }
+            Message::Remote { request, reply } => {
+                let response = self.handle_remote(&request);
+                // The client may have hung up already.
+                let _ = reply.send(response);
+            }
+        }
+    }
+
+    /// Runs a request from the control socket; see [`crate::remote`] for
+    /// the methods.
+    pub fn handle_remote(&mut self, request: &Request) -> Result<Value, RpcError> {
+        tracing::info!(method = %request.method, "remote request");
+        crash::record_event(format!("remote {}", request.method));
+        match request.method.as_str() {
+            "state" => Ok(self.remote_state()),
+            "open" => {
+                let files = request
+                    .params
+                    .get("files")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| RpcError::invalid_params("files"))?;
+                for file in files {
+                    let file = file
+                        .as_str()
+                        .ok_or_else(|| RpcError::invalid_params("files"))?;
+                    let target = cli::parse_target(file);
+                    self.open(&target.path, target.position).map_err(|e| {
+                        RpcError::failed(format!("{}: {}", target.path.display(), e))
+                    })?;
+                }
+                Ok(json!({ "opened": files.len() }))
+            }
+            "command" => {
+                commands::execute(self, request.str_param("line")?).map_err(RpcError::failed)?;
+                Ok(self
+                    .status
+                    .clone()
+                    .map_or(Value::Null, |s| json!({ "status": s })))
+            }
+            "keys" => {
+                let keys = harness::parse_script(request.str_param("keys")?)
+                    .map_err(|e| RpcError::new(remote::INVALID_PARAMS, e.to_string()))?;
+                for key in keys {
+                    self.handle_key(key);
+                }
+                Ok(Value::Null)
+            }
+            method => Err(RpcError::new(
+                remote::METHOD_NOT_FOUND,
+                format!("unknown method `{}`", method),
+            )),
+        }
+    }
+
+    fn remote_state(&self) -> Value {
+        let buffers: Vec<Value> = self
+            .buffers
+            .iter()
+            .map(|b| {
+                let cursor = b.cursor();
+                json!({
+                    "path": b.path(),
+                    "title": b.title(),
+                    "dirty": b.is_dirty(),
+                    "readonly": b.readonly(),
+                    "cursor": { "line": cursor.line, "col": cursor.col },
+                })
+            })
+            .collect();
+        let selected = self.tree.selected_node().map(|n| self.tree.node_path(n));
+        json!({
+            "root": self.tree.root(),
+            "selected": selected,
+            "buffers": buffers,
+            "active": self.active,
+            "mode": self.mode.name(),
+            "status": self.status,
+        })
+    }
+
+    pub fn set_theme_override(&mut self, theme: Theme) {
+        self.config.theme = theme.clone();
+        self.theme_override = Some(theme);
+    }
+
+    pub fn buffer(&self) -> Option<&Buffer> {
+        self.active.and_then(|i| self.buffers.get(i))
+    }
+
+    pub fn buffer_mut(&mut self) -> Option<&mut Buffer> {
+        self.active.and_then(move |i| self.buffers.get_mut(i))
+    }
+
+    /// Opens `path` (or switches to it if already open) and makes it the
+    /// active buffer, moving the cursor to `position` if given. Where the
+    /// cursor was goes in the jump list if it moved, and where it went is
+    /// centred if it's out of view.
+    pub fn open(&mut self, path: &Path, position: Option<Position>) -> Result<()> {
+        let from = self.here();
+        self.show(path, position)?;
+        if let Some(from) = from.filter(|from| self.here().as_ref() != Some(from)) {
+            self.jumps.push(from);
+            if let Some(buffer) = self.buffer_mut() {
+                buffer.center_cursor();
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens `path` at `position`, or in the place it was left, without
+    /// counting it as a jump. A file that isn't open picks up where it was
+    /// left the last time it was.
+    fn show(&mut self, path: &Path, position: Option<Position>) -> Result<()> {
+        let path = &paths::normalize(path);
+        if let Some(here) = self.here() {
+            self.recent.update(&here.path, here.position);
+        }
+        let position = match self.buffer_index(path) {
+            Some(_) => position,
+            None => position.or_else(|| self.recent.position(path)),
+        };
+        let idx = match self.buffer_index(path) {
+            Some(idx) => idx,
+            None if self.ssh.as_ref().is_some_and(|s| s.needs_fetch(path)) => {
+                return self.fetch(path, position);
+            }
+            None if hex::is_binary_file(path) => return self.open_hex(path),
+            None => {
+                tracing::info!(path = %path.display(), "opening file");
+                let buffer = match self.large_file_size(path) {
+                    Some(size) => {
+                        let mut buffer = self.stream(path, size)?;
+                        self.marks.restore(&mut buffer);
+                        buffer
+                    }
+                    None => {
+                        let mut buffer = Buffer::open(path)?;
+                        buffer.set_readonly(self.readonly);
+                        if let (false, Err(e)) = (self.readonly, paths::check_writable(path)) {
+                            self.status = Some(format!(
+                                "{}: {}; edits need :sudowrite to save",
+                                buffer.title(),
+                                e
+                            ));
+                        }
+                        if Merge::has_markers(&buffer.text().to_string()) {
+                            self.status = Some(format!(
+                                "{} has merge conflicts; :merge resolves them",
+                                buffer.title()
+                            ));
+                        }
+                        if let Err(e) = self.lsp.did_open(path, buffer.text()) {
+                            tracing::error!(error = %e, "language server failed to start");
+                            self.status = Some(e.to_string());
+                        }
+                        self.marks.restore(&mut buffer);
+                        buffer
+                    }
+                };
+                self.buffers.push(buffer);
+                crash::set_buffers(self.buffers.iter().filter_map(Buffer::path));
+                self.plugins.notify(&PluginEvent::FileOpened(path));
+                if let Some(scripts) = &self.scripts {
+                    if let Err(e) = scripts.fire("open", path) {
+                        self.status = Some(e.to_string());
+                    }
+                    self.apply_script_effects();
+                }
+                self.buffers.len() - 1
+            }
+        };
+
+        self.active = Some(idx);
+        if let Some(position) = position {
+            self.buffers[idx].goto(position);
+        }
+        self.recent.visit(path, self.buffers[idx].cursor());
+        if self.blame_inline && !self.buffers[idx].is_large() {
+            if let Err(e) = self.load_blame() {
+                tracing::debug!(path = %path.display(), error = %e, "no blame");
+            }
+        }
+        Ok(())
+    }
+
+    /// The buffer open for `path`, however it's spelled. Buffers keep their
+    /// paths [normalized](paths::normalize).
+    pub fn buffer_index(&self, path: &Path) -> Option<usize> {
+        let path = paths::normalize(path);
+        self.buffers.iter().position(|b| b.path() == Some(&path))
+    }
+
+    /// The size of the file at `path` if it's past `editor.large_file`.
+    fn large_file_size(&self, path: &Path) -> Option<u64> {
+        let limit = self.config.editor.large_file;
+        let size = fs::metadata(path).ok()?.len();
+        (limit > 0 && size > limit).then_some(size)
+    }
+
+    /// A buffer for a large file, which is streamed in by a job if there's
+    /// a spawner and read in here otherwise.
+    fn stream(&mut self, path: &Path, size: u64) -> Result<Buffer> {
+        tracing::info!(path = %path.display(), size, "streaming large file");
+        let mut buffer = Buffer::loading(path, size);
+        match &self.spawner {
+            Some(spawner) => {
+                jobs::load_file(&mut self.jobs, spawner, path.to_owned(), size);
+            }
+            None => {
+                buffer::read_chunks(fs::File::open(path)?, |text| {
+                    buffer.append_loaded(text);
+                    true
+                })?;
+                buffer.finish_loading();
+                buffer.set_readonly(self.readonly);
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Shows generated text in a read-only buffer called `name`, replacing
+    /// the text of an earlier one with the same name.
+    pub fn open_scratch(&mut self, name: &str, text: &str) {
+        let buffer = Buffer::scratch(name, text);
+        let existing = self
+            .buffers
+            .iter()
+            .position(|b| b.path().is_none() && b.title() == name);
+        match existing {
+            Some(idx) => {
+                self.buffers[idx] = buffer;
+                self.active = Some(idx);
+            }
+            None => {
+                self.buffers.push(buffer);
+                self.active = Some(self.buffers.len() - 1);
+            }
+        }
+    }
+
+    /// Opens a new untitled buffer holding `text`, named `scratch 1`,
+    /// `scratch 2` and so on. It's kept until quitting unless `:saveas`
+    /// gives it a file.
+    pub fn new_scratch(&mut self, text: &str) {
+        self.scratches += 1;
+        let name = format!("scratch {}", self.scratches);
+        self.buffers.push(Buffer::untitled(&name, text));
+        self.active = Some(self.buffers.len() - 1);
+        self.status = Some(format!("{}: :saveas <path> keeps it in a file", name));
+    }
+
+    /// Opens `text`, read from stdin, in a buffer of its own: read-only,
+    /// unless it's to be printed back out with `--stdout`.
+    pub fn open_stdin(&mut self, text: &str) {
+        let buffer = match self.to_stdout {
+            true => Buffer::untitled("stdin", text),
+            false => Buffer::scratch("stdin", text),
+        };
+        self.buffers.push(buffer);
+        self.active = Some(self.buffers.len() - 1);
+    }
+
+    /// Blame for the active buffer, if it has been loaded.
+    pub fn active_blame(&self) -> Option<&[git::BlameLine]> {
+        let path = self.buffer()?.path()?;
+        self.blame.get(path).map(Vec::as_slice)
+    }
+
+    /// Loads blame for the active buffer unless it is already cached.
+    fn load_blame(&mut self) -> Result<()> {
+        let buffer = self.buffer().ok_or_else(|| anyhow!("no file to blame"))?;
+        if buffer.is_large() {
+            bail!("{} is too large to blame", buffer.title());
+        }
+        let path = buffer
+            .path()
+            .ok_or_else(|| anyhow!("no file to blame"))?
+            .to_owned();
+        if let Entry::Vacant(entry) = self.blame.entry(path) {
+            let lines = git::blame(entry.key())?;
+            entry.insert(lines);
+        }
+        Ok(())
+    }
+
+    pub fn toggle_inline_blame(&mut self) -> Result<()> {
+        self.blame_inline = !self.blame_inline;
+        if self.blame_inline {
+            if let Err(e) = self.load_blame() {
+                self.blame_inline = false;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens or closes the pane annotating every line with its blame.
+    pub fn toggle_blame_pane(&mut self) -> Result<()> {
+        if self.mode == Mode::Blame {
+            self.mode = Mode::Normal;
+        } else {
+            self.load_blame()?;
+            self.mode = Mode::Blame;
+        }
+        Ok(())
+    }
+
+    /// Opens the git panel with a fresh status of the tree's repository.
+    pub fn open_git_status(&mut self) -> Result<()> {
+        match &mut self.git_status {
+            Some(status) => status.refresh()?,
+            None => self.git_status = Some(GitStatus::load(self.tree.root())?),
+        }
+        self.panel = Some(Panel::Git);
+        self.mode = Mode::GitStatus;
+        Ok(())
+    }
+
+    /// Opens the git log panel on the tree's repository, or with `file` on
+    /// the history of the active buffer's file.
+    pub fn open_git_log(&mut self, file: bool) -> Result<()> {
+        let log = match file {
+            true => {
+                let path = self
+                    .buffer()
+                    .and_then(Buffer::path)
+                    .ok_or_else(|| anyhow!("no file to show the history of"))?;
+                GitLog::file_history(path)?
+            }
+            false => GitLog::load(self.tree.root())?,
+        };
+        self.git_log = Some(log);
+        self.panel = Some(Panel::GitLog);
+        self.mode = Mode::GitLog;
+        Ok(())
+    }
+
+    fn handle_git_log_key(&mut self, key: KeyEvent) {
+        let log = match &mut self.git_log {
+            Some(log) => log,
+            None => return self.mode = Mode::Normal,
+        };
+        if log.filtering {
+            match key.code {
+                KeyCode::Enter => log.filtering = false,
+                KeyCode::Esc => {
+                    while !log.filter().is_empty() {
+                        log.pop_filter();
+                    }
+                    log.filtering = false;
+                }
+                KeyCode::Backspace => log.pop_filter(),
+                KeyCode::Char(c) => log.push_filter(c),
+                _ => (),
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => log.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => log.select_prev(),
+            KeyCode::Char('/') => log.filtering = true,
+            KeyCode::Enter => match log.show_selected() {
+                Ok((name, text)) => self.open_scratch(&name, &text),
+                Err(e) => self.status = Some(e.to_string()),
+            },
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.panel = None;
+            }
+            _ => (),
+        }
+    }
+
+    pub fn open_branches(&mut self) -> Result<()> {
+        self.branches = Some(BranchPicker::load(self.tree.root())?);
+        self.mode = Mode::Branches;
+        Ok(())
+    }
+
+    /// Checks out the branch `name`, unless that would change the file of a
+    /// buffer with unsaved changes.
+    pub fn switch_branch(&mut self, name: &str) -> Result<()> {
+        let root = git::repo_root(self.tree.root())?;
+        let changed = git::changed_between(&root, "HEAD", name)?;
+        let clobbered: Vec<String> = self
+            .dirty_buffers()
+            .filter(|b| {
+                b.path()
+                    .and_then(|p| fs::canonicalize(p).ok())
+                    .is_some_and(|p| changed.contains(&p))
+            })
+            .map(Buffer::title)
+            .collect();
+        if !clobbered.is_empty() {
+            bail!(
+                "{} differ on {}; save or discard the changes first",
+                clobbered.join(", "),
+                name
+            );
+        }
+        git::switch(&root, name)?;
+        tracing::info!(branch = name, "switched branch");
+        self.status = Some(format!("Switched to {}", name));
+        self.reload_after_checkout()
+    }
+
+    /// Creates a branch at the current commit and checks it out.
+    pub fn create_branch(&mut self, name: &str) -> Result<()> {
+        let root = git::repo_root(self.tree.root())?;
+        git::create_branch(&root, name)?;
+        tracing::info!(branch = name, "created branch");
+        self.status = Some(format!("Switched to new branch {}", name));
+        Ok(())
+    }
+
+    /// Brings buffers, blame and the tree in line with a new checkout.
+    fn reload_after_checkout(&mut self) -> Result<()> {
+        for idx in 0..self.buffers.len() {
+            let buffer = &mut self.buffers[idx];
+            if buffer.path().is_some() && !buffer.is_dirty() {
+                buffer.reload()?;
+                self.sync_lsp(idx)?;
+            }
+        }
+        self.blame.clear();
+        if self.blame_inline {
+            let _ = self.load_blame();
+        }
+        if let Some(status) = &mut self.git_status {
+            status.refresh()?;
+        }
+        self.rescan_tree()
+    }
+
+    fn handle_branches_key(&mut self, key: KeyEvent) {
+        let picker = match &mut self.branches {
+            Some(picker) => picker,
+            None => return self.mode = Mode::Normal,
+        };
+        let result = match (picker.list_mut().handle_key(key), key.code) {
+            (ListKey::Handled, _) => Ok(()),
+            (ListKey::Picked, _) => match picker.selected_branch().cloned() {
+                Some(branch) if !branch.current => self.switch_branch(&branch.name).map(|()| {
+                    self.branches = None;
+                    self.mode = Mode::Normal;
+                }),
+                _ => Ok(()),
+            },
+            (ListKey::Closed, _) => {
+                self.branches = None;
+                self.mode = Mode::Normal;
+                Ok(())
+            }
+            (ListKey::Ignored, KeyCode::Char('n')) => {
+                self.command_line.set("branch ");
+                self.status = None;
+                self.branches = None;
+                self.mode = Mode::Command;
+                Ok(())
+            }
+            (ListKey::Ignored, KeyCode::Char('d')) => picker
+                .delete_selected()
+                .map(|name| self.status = Some(format!("Deleted branch {}", name))),
+            (ListKey::Ignored, _) => Ok(()),
+        };
+        if let Err(e) = result {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    /// Opens the structural viewer on the active buffer's JSON or YAML.
+    pub fn open_structure(&mut self) -> Result<()> {
+        let buffer = self
+            .buffer()
+            .ok_or_else(|| anyhow!("no buffer to show the structure of"))?;
+        let path = match buffer.path().filter(|p| data::is_structured(p)) {
+            Some(path) => path,
+            None => bail!("{} isn't JSON or YAML", buffer.title()),
+        };
+        let root = data::parse(path, &buffer.text().to_string())
+            .map_err(|e| anyhow!("{}: {}", self.diff_title(path), e))?;
+        self.structure = Some(DataTree::new(path, root));
+        self.status = None;
+        self.mode = Mode::Structure;
+        Ok(())
+    }
+
+    fn handle_structure_key(&mut self, key: KeyEvent) {
+        let tree = match &mut self.structure {
+            Some(tree) => tree,
+            None => return self.mode = Mode::Normal,
+        };
+        let jump = match key.code {
+            KeyCode::Char('j') | KeyCode::Down => return tree.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => return tree.select_prev(),
+            KeyCode::Char('l') | KeyCode::Right => return tree.expand_selected(),
+            KeyCode::Char('h') | KeyCode::Left => return tree.collapse_selected(),
+            KeyCode::Enter if tree.selected_node().is_some_and(|n| n.has_children()) => {
+                return tree.toggle_selected();
+            }
+            KeyCode::Enter | KeyCode::Char('g') => tree.selected_node().map(|n| n.position),
+            KeyCode::Char('q') | KeyCode::Esc => None,
+            _ => return,
+        };
+        let source = tree.source().to_owned();
+        self.structure = None;
+        self.mode = Mode::Normal;
+        if let Some(position) = jump {
+            if let Err(e) = self.jump_to(&source, position) {
+                self.status = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Roots the tree at a directory on another host, mirroring it locally
+    /// once it has been listed in the background.
+    pub fn connect(&mut self, url: &str) -> Result<()> {
+        features::require("ssh")?;
+        let url = SshUrl::parse(url)?;
+        let root = SshRoot::new(url.clone(), &self.config.ssh)?;
+        tracing::info!(url = %url, "connecting");
+        self.tree = FileTree::with_options(root.mirror(), self.tree.options().clone())?;
+        let (job, options) = (root.clone(), self.tree.options().clone());
+        self.ssh = Some(root);
+        self.run_ssh(&format!("list {}", url), move || {
+            let result = job.list(&options).and_then(|e| job.populate(&e));
+            SshEvent::Listed { url, result }
+        });
+        Ok(())
+    }
+
+    /// Fetches the mirrored file `path` from the host, opening it at
+    /// `position` once it arrives.
+    fn fetch(&mut self, path: &Path, position: Option<Position>) -> Result<()> {
+        let root = self
+            .ssh
+            .as_mut()
+            .ok_or_else(|| anyhow!("no remote tree to fetch from"))?;
+        root.transfers += 1;
+        let (job, path) = (root.clone(), path.to_owned());
+        self.run_ssh(&format!("get {}", path.display()), move || {
+            let result = job.fetch(&path);
+            SshEvent::Fetched {
+                path,
+                position,
+                result,
+            }
+        });
+        Ok(())
+    }
+
+    /// Puts the saved file `path` back on the host if it is mirrored.
+    fn put(&mut self, path: &Path) {
+        let root = match self.ssh.as_mut() {
+            Some(root) if root.remote_path(path).is_some() => root,
+            _ => return,
+        };
+        root.transfers += 1;
+        let (job, path) = (root.clone(), path.to_owned());
+        self.run_ssh(&format!("put {}", path.display()), move || {
+            let result = job.put(&path);
+            SshEvent::Put { path, result }
+        });
+    }
+
+    /// Runs an sftp session as a job, or right away without a spawner.
+    fn run_ssh<F>(&mut self, name: &str, session: F)
+    where
+        F: FnOnce() -> SshEvent + Send + 'static,
+    {
+        match &self.spawner {
+            Some(spawner) => {
+                self.jobs.spawn(spawner, name, move |ctx| {
+                    ctx.send(Message::Ssh(session()));
+                    Ok(())
+                });
+            }
+            None => self.handle_ssh_event(session()),
+        }
+    }
+
+    fn handle_ssh_event(&mut self, event: SshEvent) {
+        let root = match &mut self.ssh {
+            Some(root) => root,
+            None => return,
+        };
+        let host = root.url().host.clone();
+        let result = match event {
+            // Results for a tree we have since moved away from are stale.
+            SshEvent::Listed { url, .. } if url != *root.url() => Ok(()),
+            SshEvent::Listed { result: Ok(()), .. } => {
+                root.connection = Connection::Connected;
+                self.status = Some(format!("Connected to {}", host));
+                self.rescan_tree()
+            }
+            SshEvent::Listed { result: Err(e), .. } => {
+                root.connection = Connection::Failed(e.to_string());
+                Err(e)
+            }
+            SshEvent::Fetched {
+                path,
+                position,
+                result,
+            } => {
+                root.transfers = root.transfers.saturating_sub(1);
+                match result {
+                    Ok(()) => {
+                        root.set_fetched(&path);
+                        self.open(&path, position)
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            SshEvent::Put { path, result } => {
+                root.transfers = root.transfers.saturating_sub(1);
+                result.map(|()| {
+                    root.set_fetched(&path);
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    self.status = Some(format!("Put {} on {}", name, host));
+                })
+            }
+        };
+        if let Err(e) = result {
+            tracing::error!(host, error = %e, "sftp failed");
+            self.status = Some(e.to_string());
+        }
+    }
+
+    /// Follows the active buffer's file: reloads it whenever the file
+    /// changes and keeps the cursor on its last line until paused. Without
+    /// a spawner nothing watches the file.
+    pub fn follow(&mut self) -> Result<()> {
+        let buffer = self
+            .buffer()
+            .ok_or_else(|| anyhow!("no buffer to follow"))?;
+        let path = match buffer.path() {
+            Some(path) => path.to_owned(),
+            None => bail!("{} has no file to follow", buffer.title()),
+        };
+        if buffer.is_dirty() {
+            bail!("{} has unsaved changes", buffer.title());
+        }
+        let (follow, watcher) = Follow::new(&path, &self.config.follow)?;
+        if let Some(spawner) = &self.spawner {
+            spawner.watch_file(watcher);
+        }
+        tracing::info!(path = %path.display(), "following file");
+        self.follow = Some(follow);
+        self.reload_followed(&path)?;
+        self.status = None;
+        self.mode = Mode::Follow;
+        Ok(())
+    }
+
+    /// Reloads the followed file's buffer after the file changed, moving to
+    /// its end unless following is paused.
+    pub fn reload_followed(&mut self, path: &Path) -> Result<()> {
+        let paused = match self.follow.as_ref().filter(|f| f.path() == path) {
+            Some(follow) => follow.paused,
+            None => return Ok(()),
+        };
+        let idx = match self.buffers.iter().position(|b| b.path() == Some(path)) {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let buffer = &mut self.buffers[idx];
+        if buffer.is_dirty() {
+            bail!("{} changed on disk but has unsaved changes", buffer.title());
+        }
+        buffer.reload()?;
+        if buffer.is_orphaned() {
+            bail!(orphan_warning(buffer));
+        }
+        if !paused {
+            buffer.goto_last_line();
+        }
+        self.sync_lsp(idx)
+    }
+
+    /// The level of each line in `lines` of the followed buffer that reports
+    /// an error or warning, to draw them in their own color.
+    pub fn log_levels(&self, lines: Range<usize>) -> Vec<(usize, Level)> {
+        let (follow, buffer) = match (&self.follow, self.buffer()) {
+            (Some(follow), Some(buffer)) if buffer.path() == Some(follow.path()) => {
+                (follow, buffer)
+            }
+            _ => return Vec::new(),
+        };
+        lines
+            .take_while(|&idx| idx < buffer.line_count())
+            .filter_map(|idx| {
+                let line = buffer.line(idx).to_string();
+                follow.level(&line).map(|level| (idx, level))
+            })
+            .collect()
+    }
+
+    fn handle_follow_key(&mut self, key: KeyEvent) {
+        let follow = match &mut self.follow {
+            Some(follow) => follow,
+            None => return self.mode = Mode::Normal,
+        };
+        let cursor = match self
+            .buffers
+            .iter()
+            .position(|b| b.path() == Some(follow.path()))
+        {
+            Some(idx) => self.buffers[idx].cursor(),
+            None => return self.stop_following(),
+        };
+        // Moving away from the end pauses; going back to it resumes, as
+        // `None` does.
+        let line = match key.code {
+            KeyCode::Char(' ') | KeyCode::Char('p') if !follow.paused => {
+                follow.paused = true;
+                return;
+            }
+            KeyCode::Char(' ') | KeyCode::Char('p') | KeyCode::Char('G') | KeyCode::End => None,
+            KeyCode::Char('j') | KeyCode::Down => Some(cursor.line + 1),
+            KeyCode::Char('k') | KeyCode::Up => Some(cursor.line.saturating_sub(1)),
+            KeyCode::PageDown => Some(cursor.line + 20),
+            KeyCode::PageUp => Some(cursor.line.saturating_sub(20)),
+            KeyCode::Char('g') | KeyCode::Home => Some(0),
+            KeyCode::Char('q') | KeyCode::Esc => return self.stop_following(),
+            _ => return,
+        };
+        follow.paused = line.is_some();
+        let path = follow.path().to_owned();
+        if let Some(buffer) = self.buffers.iter_mut().find(|b| b.path() == Some(&path)) {
+            match line {
+                Some(line) => buffer.goto(Position::new(line, 0)),
+                None => buffer.goto_last_line(),
+            }
+        }
+    }
+
+    fn stop_following(&mut self) {
+        self.follow = None;
+        if self.mode == Mode::Follow {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    /// Opens `path` in the hex view, as happens to binary files.
+    pub fn open_hex(&mut self, path: &Path) -> Result<()> {
+        if let Some(hex) = self.hex.as_ref().filter(|h| h.is_dirty()) {
+            bail!("{} has unwritten changes", self.diff_title(hex.path()));
+        }
+        tracing::info!(path = %path.display(), "opening file in the hex view");
+        self.hex = Some(HexEditor::open(path)?);
+        self.status = None;
+        self.mode = Mode::Hex;
+        Ok(())
+    }
+
+    fn handle_hex_key(&mut self, key: KeyEvent) {
+        let hex = match &mut self.hex {
+            Some(hex) => hex,
+            None => return self.mode = Mode::Normal,
+        };
+        if let Some(query) = &mut hex.query {
+            match key.code {
+                KeyCode::Enter => {
+                    let query = hex.query.take().map(|mut q| q.take()).unwrap_or_default();
+                    let found = hex::parse_pattern(&query).map(|p| hex.search(p));
+                    self.status = match found {
+                        Ok(true) => None,
+                        Ok(false) => Some(format!("{} not found", query.trim())),
+                        Err(e) => Some(e.to_string()),
+                    };
+                }
+                KeyCode::Esc => hex.query = None,
+                _ => {
+                    query.handle_key(key);
+                }
+            }
+            return;
+        }
+        let rows = |n: isize| n * hex::ROW_BYTES as isize;
+        match key.code {
+            KeyCode::Char('h') | KeyCode::Left => hex.move_by(-1),
+            KeyCode::Char('l') | KeyCode::Right => hex.move_by(1),
+            KeyCode::Char('j') | KeyCode::Down => hex.move_by(rows(1)),
+            KeyCode::Char('k') | KeyCode::Up => hex.move_by(rows(-1)),
+            KeyCode::PageDown => hex.move_by(rows(16)),
+            KeyCode::PageUp => hex.move_by(rows(-16)),
+            KeyCode::Esc if hex.editing => hex.editing = false,
+            KeyCode::Char(c) if hex.editing => {
+                if let Err(e) = hex.type_digit(c) {
+                    self.status = Some(e.to_string());
+                }
+            }
+            KeyCode::Char('g') => hex.goto(0),
+            KeyCode::Char('G') => hex.goto(usize::MAX),
+            KeyCode::Char('/') => hex.query = Some(TextInput::default()),
+            KeyCode::Char('n') if !hex.search_next() => {
+                self.status = Some("no match; / searches for bytes".to_string());
+            }
+            KeyCode::Char('i') if self.readonly => {
+                self.status = Some("read-only mode; bytes can't be edited".to_string());
+            }
+            KeyCode::Char('i') => hex.editing = true,
+            KeyCode::Char('w') => {
+                self.status = Some(match hex.save() {
+                    Ok(()) => format!("wrote {} bytes", hex.bytes().len()),
+                    Err(e) => e.to_string(),
+                });
+            }
+            KeyCode::Char('q') | KeyCode::Esc if hex.is_dirty() => {
+                self.status =
+                    Some("unwritten changes: [w]rite them, or [Q] discards them".to_string());
+            }
+            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
+                self.hex = None;
+                self.mode = Mode::Normal;
+            }
+            _ => (),
+        }
+    }
+
+    /// Opens the merge view on the conflicts in the active buffer.
+    pub fn open_merge(&mut self) -> Result<()> {
+        let idx = self.active.ok_or_else(|| anyhow!("no buffer"))?;
+        let merge = Merge::parse(&self.buffers[idx].text().to_string())?;
+        self.merge = Some((idx, merge));
+        self.mode = Mode::Merge;
+        Ok(())
+    }
+
+    /// Opens the diff view on the active buffer, as edited, against its
+    /// file as committed at `HEAD`.
+    pub fn diff_with_head(&mut self) -> Result<()> {
+        let buffer = self.buffer().ok_or_else(|| anyhow!("no buffer"))?;
+        let path = buffer.path().ok_or_else(|| anyhow!("buffer has no file"))?;
+        let committed = git::file_at(path, "HEAD")?;
+        let title = self.diff_title(path);
+        self.show_diff(Diff::new(
+            &format!("{} (HEAD)", title),
+            &committed,
+            &title,
+            &buffer.text().to_string(),
+        ));
+        Ok(())
+    }
+
+    /// Opens the diff view on two files, or on the active buffer and a file
+    /// if `left` is `None`. Relative paths are taken from the tree's root,
+    /// and files open in a buffer are compared as edited.
+    pub fn diff_files(&mut self, left: Option<&Path>, right: &Path) -> Result<()> {
+        let (left_title, left) = match left {
+            Some(path) => self.text_of(path)?,
+            None => {
+                let buffer = self.buffer().ok_or_else(|| anyhow!("no buffer"))?;
+                let title = match buffer.path() {
+                    Some(path) => self.diff_title(path),
+                    None => buffer.title(),
+                };
+                (title, buffer.text().to_string())
+            }
+        };
+        let (right_title, right) = self.text_of(right)?;
+        self.show_diff(Diff::new(&left_title, &left, &right_title, &right));
+        Ok(())
+    }
+
+    /// Opens the diff view on the two files marked in the tree, or on the
+    /// marked file and the selected one, then clears the marks. Needs no
+    /// git repository.
+    pub fn compare_marked(&mut self) -> Result<()> {
+        let mut marks = self.tree.marked();
+        if marks.len() == 1 {
+            let selected = self.tree.selected_node().filter(|n| !n.has_children());
+            if let Some(path) = selected.map(|n| self.tree.node_path(n)) {
+                if path != marks[0] {
+                    marks.push(path);
+                }
+            }
+        }
+        let (left, right) = match marks.as_slice() {
+            [left, right] => (left.clone(), right.clone()),
+            _ => bail!("mark two files with m to compare them"),
+        };
+        self.diff_files(Some(&left), &right)?;
+        self.tree.clear_marks();
+        Ok(())
+    }
+
+    fn text_of(&self, path: &Path) -> Result<(String, String)> {
+        let path = self.tree.root().join(path);
+        let buffer = self.buffer_index(&path).map(|idx| &self.buffers[idx]);
+        let text = match buffer {
+            Some(buffer) => buffer.text().to_string(),
+            None => fs::read_to_string(&path).map_err(|e| anyhow!("{}: {}", path.display(), e))?,
+        };
+        Ok((self.diff_title(&path), text))
+    }
+
+    /// `path` relative to the tree's root, if it is under it.
+    pub fn diff_title(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(self.tree.root()).unwrap_or(path);
+        relative.display().to_string()
+    }
+
+    fn show_diff(&mut self, diff: Diff) {
+        if diff.is_identical() {
+            self.status = Some("No differences".to_string());
+        }
+        self.diff = Some(diff);
+        self.mode = Mode::Diff;
+    }
+
+    fn handle_diff_key(&mut self, key: KeyEvent) {
+        let diff = match &mut self.diff {
+            Some(diff) => diff,
+            None => return self.mode = Mode::Normal,
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => diff.scroll_by(1),
+            KeyCode::Char('k') | KeyCode::Up => diff.scroll_by(-1),
+            KeyCode::PageDown | KeyCode::Char(' ') => diff.scroll_by(20),
+            KeyCode::PageUp => diff.scroll_by(-20),
+            KeyCode::Char('n') => diff.jump_change(false),
+            KeyCode::Char('p') => diff.jump_change(true),
+            KeyCode::Char('u') | KeyCode::Tab => diff.toggle_layout(),
+            KeyCode::Char('c') if self.review.as_ref().is_some_and(|r| r.open.is_some()) => {
+                self.command_line.set("note ");
+                self.status = None;
+                self.mode = Mode::Command;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.diff = None;
+                self.mode = Mode::Normal;
+                // A file's diff goes back to the review it was opened from.
+                if let Some(review) = self.review.as_mut().filter(|r| r.open.is_some()) {
+                    review.open = None;
+                    self.mode = Mode::Review;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Lists the files the current branch changed against `base`, or goes
+    /// back to the review under way if no base is given.
+    pub fn open_review(&mut self, base: Option<&str>) -> Result<()> {
+        if base.is_some() || self.review.is_none() {
+            self.review = Some(Review::load(self.tree.root(), base)?);
+        }
+        self.status = None;
+        self.mode = Mode::Review;
+        Ok(())
+    }
+
+    fn handle_review_key(&mut self, key: KeyEvent) {
+        let review = match &mut self.review {
+            Some(review) => review,
+            None => return self.mode = Mode::Normal,
+        };
+        let result = match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                review.select_next();
+                Ok(())
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                review.select_prev();
+                Ok(())
+            }
+            KeyCode::Enter => match review.diff_selected() {
+                Ok(diff) => {
+                    review.open = Some(review.selected());
+                    self.status = None;
+                    self.show_diff(diff);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            },
+            KeyCode::Char('w') => self.write_review_report(None),
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    /// Leaves a note on the line at the top of the review's open diff.
+    pub fn add_review_note(&mut self, text: &str) -> Result<()> {
+        if text.is_empty() {
+            bail!("note needs some text");
+        }
+        let line = self
+            .diff
+            .as_ref()
+            .and_then(Diff::top_line)
+            .ok_or_else(|| anyhow!("no line to leave a note on"))?;
+        let review = self
+            .review
+            .as_mut()
+            .ok_or_else(|| anyhow!("no review; start one with :review"))?;
+        let path = review.add_note(line, text)?;
+        self.status = Some(format!("Noted line {} of {}", line.0, path));
+        self.mode = Mode::Diff;
+        Ok(())
+    }
+
+    /// Writes the review's notes to `path`, `review.txt` by default, from
+    /// the repository's root.
+    pub fn write_review_report(&mut self, path: Option<&Path>) -> Result<()> {
+        let review = self
+            .review
+            .as_ref()
+            .ok_or_else(|| anyhow!("no review; start one with :review"))?;
+        let written = review.write_report(path.unwrap_or(Path::new("review.txt")))?;
+        let name = self.diff_title(&written);
+        self.status = Some(match review.notes().len() {
+            1 => format!("Wrote 1 note to {}", name),
+            n => format!("Wrote {} notes to {}", n, name),
+        });
+        Ok(())
+    }
+
+    /// Writes the merge view's result into its buffer and saves it, closing
+    /// the view once nothing is left to resolve.
+    fn save_merge(&mut self) -> Result<()> {
+        let (idx, merge) = match &self.merge {
+            Some((idx, merge)) => (*idx, merge),
+            None => return Ok(()),
+        };
+        let unresolved = merge.unresolved();
+        self.buffers[idx].set_text(&merge.result())?;
+        self.sync_lsp(idx)?;
+        self.save(idx)?;
+        if unresolved == 0 {
+            self.merge = None;
+            self.mode = Mode::Normal;
+            self.status = Some(format!("{} merged", self.buffers[idx].title()));
+        } else {
+            self.status = Some(format!("Saved with {} conflicts left", unresolved));
+        }
+        Ok(())
+    }
+
+    fn handle_merge_key(&mut self, key: KeyEvent) {
+        let merge = match &mut self.merge {
+            Some((_, merge)) => merge,
+            None => return self.mode = Mode::Normal,
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Char('n') | KeyCode::Down => merge.select_next(),
+            KeyCode::Char('k') | KeyCode::Char('p') | KeyCode::Up => merge.select_prev(),
+            KeyCode::Char('o') => merge.resolve(Side::Ours),
+            KeyCode::Char('t') => merge.resolve(Side::Theirs),
+            KeyCode::Char('b') => merge.resolve(Side::Both),
+            KeyCode::Char('s') | KeyCode::Char('w') => {
+                if let Err(e) = self.save_merge() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.merge = None;
+                self.mode = Mode::Normal;
+            }
+            _ => (),
+        }
+    }
+
+    /// Commits what is staged in the tree's repository.
+    pub fn commit(&mut self, message: &str) -> Result<()> {
+        let status = match &mut self.git_status {
+            Some(status) => status,
+            None => self.git_status.insert(GitStatus::load(self.tree.root())?),
+        };
+        let summary = status.commit(message)?;
+        tracing::info!(commit = %summary, "committed");
+        self.badges.changed_all();
+        // Lines now belong to the new commit.
+        self.blame.clear();
+        if self.blame_inline {
+            let _ = self.load_blame();
+        }
+        self.status = Some(format!("Committed {}", summary));
+        if self.panel == Some(Panel::Git) {
+            self.mode = Mode::GitStatus;
+        }
+        Ok(())
+    }
+
+    fn handle_git_status_key(&mut self, key: KeyEvent) {
+        let status = match &mut self.git_status {
+            Some(status) => status,
+            None => return self.mode = Mode::Normal,
+        };
+        let result = match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                status.select_next();
+                Ok(())
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                status.select_prev();
+                Ok(())
+            }
+            KeyCode::Enter | KeyCode::Tab => status.toggle_expanded(),
+            KeyCode::Char('s') => status.stage(),
+            KeyCode::Char('u') => status.unstage(),
+            KeyCode::Char('r') => status.refresh(),
+            KeyCode::Char('c') => {
+                self.command_line.set("commit ");
+                self.status = None;
+                self.mode = Mode::Command;
+                Ok(())
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.panel = None;
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            self.status = Some(e.to_string());
+        }
+        // Staging changes the letters in the tree too.
+        if matches!(key.code, KeyCode::Char('s' | 'u' | 'r')) {
+            self.badges.changed_all();
+        }
+    }
+
+    /// Opens the details of the commit that last changed the cursor line.
+    fn show_blamed_commit(&mut self) -> Result<()> {
+        let buffer = self.buffer().ok_or_else(|| anyhow!("no buffer"))?;
+        let line = buffer.cursor().line;
+        let dir = buffer
+            .path()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("."))
+            .to_owned();
+        let blame = self
+            .active_blame()
+            .and_then(|lines| lines.get(line))
+            .ok_or_else(|| anyhow!("no blame for line {}", line + 1))?;
+        if !blame.is_committed() {
+            return Err(anyhow!("line {} is not committed yet", line + 1));
+        }
+        let name = format!("commit {}", blame.short_commit());
+        let text = git::show(&dir, &blame.commit, None)?;
+        self.mode = Mode::Normal;
+        self.open_scratch(&name, &text);
+        Ok(())
+    }
+
+    pub fn toggle_panel(&mut self, panel: Panel) {
+        if self.panel == Some(panel) {
+            self.panel = None;
+        } else {
+            self.panel = Some(panel);
+        }
+    }
+
+    pub fn handle_event(&mut self, event: Event) {
+        match &event {
+            Event::Key(key) => {
+                if let Some(note) = self.key_note(key) {
+                    crash::record_event(note);
+                }
+            }
+            Event::Resize(w, h) => crash::record_event(format!("resize {}x{}", w, h)),
+            _ => (),
+        }
+        let before = self
+            .config
+            .editor
+            .screen_reader
+            .then(|| (self.mode, self.buffers.len(), self.shown()));
+        match event {
+            // Windows reports releases too; act on presses and repeats only.
+            Event::Key(key) if key.kind == KeyEventKind::Release => (),
+            Event::Key(key) => self.handle_key(key),
+            Event::Mouse(mouse) => self.handle_mouse(mouse),
+            // The next frame is laid out afresh for the new size; nothing
+            // drawn for the old one is reused.
+            Event::Resize(_, _) => self.tree_pane.invalidate(),
+            Event::FocusGained | Event::FocusLost | Event::Paste(_) => (),
+        }
+        self.check_tutor();
+        if let Some(before) = before.filter(|_| self.config.editor.screen_reader) {
+            self.announce_changes(before);
+        }
+    }
+
+    /// The file or buffer in front, as the announcements name it.
+    fn shown(&self) -> Option<String> {
+        let buffer = self.buffer()?;
+        Some(match buffer.path() {
+            Some(path) => {
+                let root = paths::normalize(self.tree.root());
+                let relative = path.strip_prefix(&root).unwrap_or(path);
+                relative.display().to_string()
+            }
+            None => buffer.title().trim_end_matches(" +").to_string(),
+        })
+    }
+
+    /// Announces what changed since `(mode, buffer count, shown)`: another
+    /// file in front or another mode.
+    fn announce_changes(&mut self, (mode, buffers, shown): (Mode, usize, Option<String>)) {
+        let now = self.shown();
+        if now != shown {
+            self.announcement = Some(match now {
+                Some(name) if self.buffers.len() > buffers => format!("Opened {}", name),
+                Some(name) => format!("Switched to {}", name),
+                None => "No file open".to_string(),
+            });
+        } else if self.mode != mode {
+            self.announcement = Some(format!("{} mode", self.mode.name().replace('_', " ")));
+        }
+    }
+
+    /// Copies the tutor's lessons into `dir` and opens them, to be watched
+    /// as they're worked through. Finishing them is noted in `dir` too.
+    pub fn start_tutor(&mut self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        let path = paths::normalize(&dir.join(tutor::LESSONS_FILE));
+        fs::write(&path, tutor::LESSONS)?;
+        // A copy left open from before would hide the fresh one.
+        if let Some(idx) = self.buffer_index(&path) {
+            self.buffers[idx].reload()?;
+        }
+        self.open(&path, Some(Position::new(0, 0)))?;
+        let store = TutorStore::new(dir.join(tutor::PROGRESS_FILE));
+        self.tutor = Some(Tutor::new(&path, self.tree.selected(), store));
+        self.status = Some("Tutor: lesson 1: the tree".to_string());
+        Ok(())
+    }
+
+    /// Notes the tutor's tasks done since the last key.
+    fn check_tutor(&mut self) {
+        let mut tutor = match self.tutor.take() {
+            Some(tutor) => tutor,
+            None => return,
+        };
+        if let Some(news) = tutor.check(self) {
+            self.status = Some(news);
+        }
+        if !tutor.is_done() {
+            self.tutor = Some(tutor);
+        }
+    }
+
+    /// Clicks and the wheel go to the layer on top where they are, and of
+    /// those only the popup menus do anything with them yet; a dialog up
+    /// keeps them from the panes underneath.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let at = layout::Position::new(mouse.column, mouse.row);
+        let area = match self.layers.route(at) {
+            Some((Layer::Float, area)) => area,
+            _ => return,
+        };
+        match (self.mode, &mut self.completion, &mut self.suggestions) {
+            (Mode::Insert, Some(completion), _) => match completion.handle_mouse(mouse, area) {
+                MenuKey::Picked => self.pick_completion(),
+                MenuKey::Closed => self.completion = None,
+                MenuKey::Moved | MenuKey::Ignored => (),
+            },
+            (Mode::Spell, _, Some(suggestions)) => match suggestions.handle_mouse(mouse, area) {
+                MenuKey::Picked => self.pick_suggestion(),
+                MenuKey::Closed => self.close_suggestions(),
+                MenuKey::Moved | MenuKey::Ignored => (),
+            },
+            _ => (),
+        }
+    }
+
+    /// What a key is noted as in crash reports: the action it runs in
+    /// Normal mode, or which key it was in the others. Characters typed in
+    /// any other mode are text, file contents or secrets, and aren't noted.
+    fn key_note(&self, key: &KeyEvent) -> Option<String> {
+        match (self.mode, key.code) {
+            (Mode::Normal, _) => match self.config.keymap.action(key) {
+                Some(action) => Some(format!("key in Normal: {:?}", action)),
+                None => Some(format!("key in Normal: {}", config::key_name(key))),
+            },
+            (_, KeyCode::Char(_)) => None,
+            (mode, _) => Some(format!("key in {:?}: {}", mode, config::key_name(key))),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        match self.mode {
+            Mode::Normal => {
+                // Any key closes the hover window; esc does nothing else.
+                if self.hover.take().is_some() && key.code == KeyCode::Esc {
+                    return;
+                }
+                if let Some(action) = self.pending_mark.take() {
+                    return self.handle_mark_key(action, key);
+                }
+                if let Some(scripts) = &self.scripts {
+                    match scripts.key(&config::key_name(&key)) {
+                        Ok(KeyOutcome::Unbound) => (),
+                        Ok(KeyOutcome::Action(action)) => return self.run(action),
+                        Ok(KeyOutcome::Handled) => return self.apply_script_effects(),
+                        Err(e) => {
+                            self.status = Some(e.to_string());
+                            return;
+                        }
+                    }
+                }
+                if let Some(action) = self.config.keymap.action(&key) {
+                    self.run(action);
+                }
+            }
+            Mode::Command => self.handle_command_key(key),
+            Mode::Insert => self.handle_insert_key(key),
+            Mode::ConfirmQuit => self.handle_confirm_quit_key(key),
+            Mode::ConfirmRestore => self.handle_confirm_restore_key(key),
+            Mode::ConfirmTrust => self.handle_confirm_trust_key(key),
+            Mode::Blame => self.handle_blame_key(key),
+            Mode::GitStatus => self.handle_git_status_key(key),
+            Mode::GitLog => self.handle_git_log_key(key),
+            Mode::Branches => self.handle_branches_key(key),
+            Mode::Recent => self.handle_recent_key(key),
+            Mode::Projects => self.handle_projects_key(key),
+            Mode::Merge => self.handle_merge_key(key),
+            Mode::Diff => self.handle_diff_key(key),
+            Mode::Review => self.handle_review_key(key),
+            Mode::Locations => self.handle_locations_key(key),
+            Mode::Rename => self.handle_rename_key(key),
+            Mode::Terminal => self.handle_terminal_key(key),
+            Mode::TerminalCopy => self.handle_terminal_copy_key(key),
+            Mode::Search => self.handle_search_key(key),
+            Mode::Symbols => self.handle_symbols_key(key),
+            Mode::Outline => self.handle_outline_key(key),
+            Mode::Spell => self.handle_spell_key(key),
+            Mode::Hex => self.handle_hex_key(key),
+            Mode::Structure => self.handle_structure_key(key),
+            Mode::Follow => self.handle_follow_key(key),
+            Mode::Replace => self.handle_replace_key(key),
+        }
+    }
+
+    fn handle_blame_key(&mut self, key: KeyEvent) {
+        let cursor = match self.buffer() {
+            Some(buffer) => buffer.cursor(),
+            None => return self.mode = Mode::Normal,
+        };
+        let line = match key.code {
+            KeyCode::Char('j') | KeyCode::Down => cursor.line + 1,
+            KeyCode::Char('k') | KeyCode::Up => cursor.line.saturating_sub(1),
+            KeyCode::Enter => {
+                if let Err(e) = self.show_blamed_commit() {
+                    self.status = Some(e.to_string());
+                }
+                return;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return self.mode = Mode::Normal,
+            _ => return,
+        };
+        if let Some(buffer) = self.buffer_mut() {
+            buffer.goto(Position::new(line, cursor.col));
+        }
+    }
+
+    fn handle_confirm_trust_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('t') | KeyCode::Char('T') => {
+                self.mode = Mode::Normal;
+                if let Err(e) = self.trust_project() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            KeyCode::Char('i') | KeyCode::Char('I') | KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.status = Some("Project plugins not started; :trust to allow them".to_string());
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_confirm_quit_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('s') | KeyCode::Char('S') => {
+                self.mode = Mode::Normal;
+                match self.save_all() {
+                    Ok(()) => self.exit = true,
+                    Err(e) => self.status = Some(e.to_string()),
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                self.mode = Mode::Normal;
+                self.exit = true;
+            }
+            KeyCode::Char('c') | KeyCode::Char('C') | KeyCode::Esc => self.mode = Mode::Normal,
+            _ => (),
+        }
+    }
+
+    /// Quits, first asking about unsaved buffers unless `force` is set.
+    pub fn quit(&mut self, force: bool) {
+        let printed = self.active.filter(|_| self.to_stdout);
+        let mut dirty = self
+            .buffers
+            .iter()
+            .enumerate()
+            .filter(|&(idx, b)| b.is_dirty() && Some(idx) != printed);
+        if force || dirty.next().is_none() {
+            self.exit = true;
+        } else {
+            self.mode = Mode::ConfirmQuit;
+        }
+    }
+
+    pub fn dirty_buffers(&self) -> impl Iterator<Item = &Buffer> {
+        self.buffers.iter().filter(|b| b.is_dirty())
+    }
+
+    /// Saves buffer `idx` and lets plugins and scripts know.
+    pub fn save(&mut self, idx: usize) -> Result<()> {
+        let buffer = &mut self.buffers[idx];
+        if let Some(path) = buffer.path().filter(|_| !buffer.readonly()) {
+            if let Err(e) = paths::check_writable(path) {
+                bail!(
+                    "{}: {}; :sudowrite saves it through {}",
+                    buffer.title(),
+                    e,
+                    self.config.editor.elevate
+                );
+            }
+        }
+        buffer
+            .save()
+            .map_err(|e| anyhow!("{}: {}", buffer.title(), e))?;
+        self.saved(idx)
+    }
+
+    /// Saves buffer `idx` through `editor.elevate`, for a file this user
+    /// can't write. Run with the terminal handed back, as the main loop
+    /// does for [`elevated_save`](Self::elevated_save), so that the helper
+    /// can ask for a password.
+    pub fn save_elevated(&mut self, idx: usize) -> Result<()> {
+        let helper = &self.config.editor.elevate;
+        let buffer = &mut self.buffers[idx];
+        buffer
+            .save_through(helper)
+            .map_err(|e| anyhow!("{}: {}", buffer.title(), e))?;
+        tracing::info!(path = ?buffer.path(), helper, "saved file through helper");
+        self.status = Some(format!("Saved {} through {}", buffer.title(), helper));
+        self.saved(idx)
+    }
+
+    /// Saves buffer `idx` to `path`, relative to the tree's root, and to
+    /// there from now on, e.g. once its own file was deleted or moved. A
+    /// file already there is only written over, and directories on the
+    /// way that don't exist yet only made, if `force` is set. A file open
+    /// in another buffer is never saved over.
+    pub fn save_as(&mut self, idx: usize, path: &Path, force: bool) -> Result<()> {
+        let path = paths::normalize(&self.tree.root().join(path));
+        let title = self.diff_title(&path);
+        if self.buffer_index(&path).is_some_and(|other| other != idx) {
+            bail!("{} is open in another buffer", title);
+        }
+        let moved = self.buffers[idx].path() != Some(path.as_path());
+        if moved && !force && path.exists() {
+            bail!("{} exists; :saveas! saves over it", title);
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+            if !force {
+                bail!(
+                    "{} doesn't exist; :saveas! creates it",
+                    self.diff_title(dir)
+                );
+            }
+            fs::create_dir_all(dir)
+                .map_err(|e| anyhow!("can't create {}: {}", self.diff_title(dir), e))?;
+        }
+        paths::check_writable(&path).map_err(|e| anyhow!("{}: {}", title, e))?;
+        let buffer = &mut self.buffers[idx];
+        buffer
+            .save_as(&path)
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        if moved && !buffer.is_large() {
+            if let Err(e) = self.lsp.did_open(&path, buffer.text()) {
+                tracing::error!(error = %e, "language server failed to start");
+            }
+        }
+        if moved {
+            crash::set_buffers(self.buffers.iter().filter_map(Buffer::path));
+        }
+        // A new file in the workspace shows up in the tree.
+        if path.starts_with(self.tree.root()) && self.tree.find_path(&path).is_none() {
+            self.rescan_tree()?;
+        }
+        self.saved(idx)
+    }
+
+    fn saved(&mut self, idx: usize) -> Result<()> {
+        let path = match self.buffers[idx].path() {
+            Some(path) => path.to_owned(),
+            None => return Ok(()),
+        };
+        tracing::info!(path = %path.display(), "saved file");
+        self.metadata.invalidate(&path);
+        self.badges.changed(&path);
+        self.put(&path);
+        if self.blame.remove(&path).is_some() && self.blame_inline {
+            let _ = self.load_blame();
+        }
+        self.plugins.notify(&PluginEvent::BufferSaved(&path));
+        if !self.buffers[idx].is_large() {
+            self.lsp.did_save(&path);
+        }
+        if self.config.editor.lint_on_save && self.config.linters.for_path(&path).is_some() {
+            self.lint(&path)?;
+        }
+        if let Some(scripts) = &self.scripts {
+            if let Err(e) = scripts.fire("save", &path) {
+                self.status = Some(e.to_string());
+            }
+            self.apply_script_effects();
+        }
+        Ok(())
+    }
+
+    /// Saves buffer `idx`, formatting it first if `editor.format_on_save`
+    /// is set. A formatter failing doesn't stop the save.
+    pub fn write(&mut self, idx: usize) -> Result<()> {
+        if self.config.editor.format_on_save {
+            match self.format(idx, true) {
+                // The language server's answer saves the buffer.
+                Ok(true) => return Ok(()),
+                Ok(false) => (),
+                Err(e) => {
+                    self.save(idx)?;
+                    self.status = Some(format!("Saved without formatting: {}", e));
+                    return Ok(());
+                }
+            }
+        }
+        self.save(idx)
+    }
+
+    /// Formats buffer `idx` with the external formatter for its file type,
+    /// else with its language server. Returns whether the server's edits
+    /// are still to come; with `save` the buffer is saved once they have
+    /// been applied, and having no formatter is no error.
+    pub fn format(&mut self, idx: usize, save: bool) -> Result<bool> {
+        let buffer = &self.buffers[idx];
+        let path = match buffer.path() {
+            Some(path) => path.to_owned(),
+            None if save => return Ok(false),
+            None => bail!("{} has no file to format", buffer.title()),
+        };
+        if let Some(formatter) = self.config.formatters.for_path(&path) {
+            let text = buffer.text().to_string();
+            let formatted = format::run(formatter, &path, &text)?;
+            self.apply_format(idx, &format::line_edits(&text, &formatted), false)?;
+            return Ok(false);
+        }
+        let editor = &self.config.editor;
+        let (tab_width, tabs) = (editor.tab_width, !editor.expand_tab);
+        if self.lsp.format(&path, tab_width, tabs, save) {
+            return Ok(true);
+        }
+        match save {
+            true => Ok(false),
+            false => bail!("no formatter for {}", self.buffers[idx].title()),
+        }
+    }
+
+    /// Makes formatting `edits` in buffer `idx`, saving it after with
+    /// `save`.
+    fn apply_format(
+        &mut self,
+        idx: usize,
+        edits: &[(Position, Position, String)],
+        save: bool,
+    ) -> Result<()> {
+        self.buffers[idx].apply_edits(edits)?;
+        self.sync_lsp(idx)?;
+        if save {
+            self.save(idx)?;
+        }
+        Ok(())
+    }
+
+    /// Saves every dirty buffer, stopping at the first failure.
+    pub fn save_all(&mut self) -> Result<()> {
+        for idx in 0..self.buffers.len() {
+            if self.buffers[idx].is_dirty() {
+                self.save(idx)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `edit` to the active buffer and tells its language server.
+    fn edit(&mut self, edit: impl FnOnce(&mut Buffer) -> Result<()>) -> Result<()> {
+        let idx = self.active.ok_or_else(|| anyhow!("no buffer"))?;
+        edit(&mut self.buffers[idx])?;
+        self.sync_lsp(idx)
+    }
+
+    /// Sends buffer `idx`'s text to its language server.
+    fn sync_lsp(&mut self, idx: usize) -> Result<()> {
+        let buffer = &self.buffers[idx];
+        match buffer.path() {
+            Some(path) if !buffer.is_large() => self.lsp.did_change(path, buffer.text()),
+            _ => Ok(()),
+        }
+    }
+
+    fn handle_insert_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if let Some(completion) = &mut self.completion {
+            let picked = match key.code {
+                KeyCode::Tab => MenuKey::Picked,
+                _ => completion.handle_key(key),
+            };
+            match picked {
+                MenuKey::Picked => return self.pick_completion(),
+                MenuKey::Moved => return,
+                MenuKey::Closed => return self.completion = None,
+                MenuKey::Ignored => (),
+            }
+        }
+
+        // Left and right move a glyph at a time, however many chars it is.
+        let (cursor, left, right) = match self.buffer() {
+            Some(buffer) => {
+                let cursor = buffer.cursor();
+                (cursor, buffer.prev_glyph(cursor), buffer.next_glyph(cursor))
+            }
+            None => return self.mode = Mode::Normal,
+        };
+        let moved = |line: usize, col: usize| Some(Position::new(line, col));
+        let goto = match key.code {
+            KeyCode::Left => Some(left),
+            KeyCode::Right => Some(right),
+            KeyCode::Up => moved(cursor.line.saturating_sub(1), cursor.col),
+            KeyCode::Down => moved(cursor.line + 1, cursor.col),
+            KeyCode::Home => moved(cursor.line, 0),
+            KeyCode::End => moved(cursor.line, usize::MAX),
+            _ => None,
+        };
+        if let (Some(position), Some(buffer)) = (goto, self.buffer_mut()) {
+            buffer.goto(position);
+            self.completion = None;
+            self.signature = None;
+            return;
+        }
+
+        let indent = match self.config.editor.expand_tab {
+            true => " ".repeat(self.config.editor.tab_width as usize),
+            false => "\t".to_string(),
+        };
+        let result = match key.code {
+            KeyCode::Esc => {
+                self.completion = None;
+                self.signature = None;
+                self.mode = Mode::Normal;
+                Ok(())
+            }
+            KeyCode::Char(' ') if ctrl => {
+                self.request_completion();
+                Ok(())
+            }
+            KeyCode::Char('v') if ctrl => match self.register.clone() {
+                Some(text) => self
+                    .edit(|b| b.insert(&text))
+                    .map(|()| self.completion = None),
+                None => Ok(()),
+            },
+            KeyCode::Char(c) if !ctrl => self
+                .edit(|b| b.insert(c.encode_utf8(&mut [0; 4])))
+                .map(|()| self.typed(c)),
+            KeyCode::Enter => self
+                .edit(|b| b.insert("\n"))
+                .map(|()| self.completion = None),
+            KeyCode::Tab => self
+                .edit(|b| b.insert(&indent))
+                .map(|()| self.completion = None),
+            KeyCode::Backspace => {
+                let glyph = (cursor.col - left.col).max(1);
+                self.edit(|b| b.delete_before(glyph)).map(|()| {
+                    self.filter_completion();
+                    self.update_signature(None);
+                })
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    /// Narrows the completion menu after `c` was typed, or asks for
+    /// completions if `c` starts a word or is one of the server's trigger
+    /// characters.
+    fn typed(&mut self, c: char) {
+        self.update_signature(Some(c));
+        let word = c.is_alphanumeric() || c == '_';
+        if word && self.completion.is_some() {
+            return self.filter_completion();
+        }
+        self.completion = None;
+        let path = self.buffer().and_then(Buffer::path);
+        if word || path.is_some_and(|p| self.lsp.is_trigger(p, c)) {
+            self.request_completion();
+        }
+    }
+
+    /// Asks for signature help after `c` if it is one of the server's
+    /// signature characters, or after any change while the help is shown
+    /// so that the active parameter follows the cursor.
+    fn update_signature(&mut self, c: Option<char>) {
+        let path = self.buffer().and_then(Buffer::path);
+        let trigger = match (path, c) {
+            (Some(path), Some(c)) => self.lsp.is_signature_trigger(path, c),
+            _ => false,
+        };
+        if trigger || self.signature.is_some() {
+            // Without a language server there is simply no help.
+            let _ = self.ask_lsp(LspHost::signature_help);
+        }
+    }
+
+    /// Asks the active buffer's language server for completions at the
+    /// cursor.
+    fn request_completion(&mut self) {
+        let buffer = match self.active.map(|idx| &self.buffers[idx]) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        if let Some(path) = buffer.path() {
+            self.lsp.completion(path, buffer.text(), buffer.cursor());
+        }
+    }
+
+    /// Filters the completion menu by what has been typed since the
+    /// completed word's start, closing it if the cursor left the word or
+    /// nothing matches.
+    fn filter_completion(&mut self) {
+        let (completion, buffer) = match (&mut self.completion, self.active) {
+            (Some(completion), Some(idx)) => (completion, &self.buffers[idx]),
+            _ => return,
+        };
+        let (start, cursor) = (completion.start(), buffer.cursor());
+        if cursor.line != start.line || cursor.col < start.col {
+            self.completion = None;
+            return;
+        }
+        let typed = buffer
+            .line(cursor.line)
+            .slice(start.col..cursor.col)
+            .to_string();
+        completion.set_filter(&typed);
+        if completion.visible().is_empty() {
+            self.completion = None;
+        }
+    }
+
+    fn handle_lsp_event(&mut self, event: LspEvent) {
+        match event {
+            LspEvent::Status(status) => self.status = Some(status),
+            LspEvent::Completion {
+                path,
+                position,
+                items,
+            } => {
+                let buffer = match self.buffer() {
+                    Some(buffer) if buffer.path() == Some(&path) => buffer,
+                    _ => return,
+                };
+                // The cursor may have moved on since the request.
+                let cursor = buffer.cursor();
+                if self.mode != Mode::Insert
+                    || cursor.line != position.line
+                    || cursor.col < position.col
+                {
+                    return;
+                }
+                let line = buffer.line(cursor.line);
+                let word = (0..cursor.col)
+                    .rev()
+                    .take_while(|&col| {
+                        let c = line.char(col);
+                        c.is_alphanumeric() || c == '_'
+                    })
+                    .last()
+                    .unwrap_or(cursor.col);
+                let col = items
+                    .iter()
+                    .find_map(|i| i.start_col)
+                    .filter(|&col| col <= cursor.col)
+                    .unwrap_or(word);
+                let start = Position::new(cursor.line, col);
+                self.completion = Some(Completion::new(&path, start, items));
+                self.filter_completion();
+            }
+            LspEvent::Definition(found) => match found.as_slice() {
+                [] => self.status = Some("No definition found".to_string()),
+                [location] => {
+                    let text = self.file_text(&location.path);
+                    let entry = self.location_entry(location, text.as_ref());
+                    if let Err(e) = self.jump_to(&entry.path, entry.position) {
+                        self.status = Some(e.to_string());
+                    }
+                }
+                _ => self.show_locations("Definitions", &found),
+            },
+            LspEvent::References(found) => match found.is_empty() {
+                true => self.status = Some("No references found".to_string()),
+                false => self.show_locations("References", &found),
+            },
+            LspEvent::Rename(files) => self.preview_rename(files),
+            LspEvent::Hover {
+                path,
+                position,
+                text,
+            } => {
+                let cursor = self
+                    .buffer()
+                    .filter(|b| b.path() == Some(&path))
+                    .map(Buffer::cursor);
+                if self.mode != Mode::Normal || cursor != Some(position) {
+                    return;
+                }
+                match text {
+                    Some(text) => self.hover = Some(text),
+                    None => self.status = Some("No information here".to_string()),
+                }
+            }
+            LspEvent::Format { path, edits, save } => {
+                let idx = match self.buffers.iter().position(|b| b.path() == Some(&path)) {
+                    Some(idx) => idx,
+                    None => return,
+                };
+                let edits = lsp::to_edits(self.buffers[idx].text(), &edits);
+                if let Err(e) = self.apply_format(idx, &edits, save) {
+                    self.status = Some(e.to_string());
+                }
+            }
+            LspEvent::Diagnostics { path, diagnostics } => {
+                match diagnostics.is_empty() {
+                    true => self.diagnostics.remove(&path),
+                    false => self.diagnostics.insert(path, diagnostics),
+                };
+            }
+            LspEvent::DocumentSymbols { path, symbols } => {
+                let outline = match self.outline.as_mut() {
+                    Some(outline) if outline.path() == Some(&path) => outline,
+                    _ => return,
+                };
+                let text = outline.text().cloned().unwrap_or_default();
+                let items = symbols
+                    .into_iter()
+                    .map(|s| OutlineItem {
+                        depth: s.depth,
+                        symbol: Symbol {
+                            name: s.name,
+                            kind: s.kind.to_string(),
+                            path: path.clone(),
+                            position: lsp::to_position(&text, s.start),
+                        },
+                    })
+                    .collect();
+                outline.set_items(items);
+            }
+            LspEvent::SignatureHelp { path, help } => {
+                let active = self.buffer().and_then(Buffer::path) == Some(&path);
+                if self.mode == Mode::Insert && active {
+                    self.signature = help;
+                }
+            }
+        }
+    }
+
+    /// Sends a request about the symbol at the cursor of the active buffer.
+    fn ask_lsp(
+        &mut self,
+        request: impl FnOnce(&mut LspHost, &Path, &Rope, Position) -> bool,
+    ) -> Result<()> {
+        let buffer = match self.active {
+            Some(idx) => &self.buffers[idx],
+            None => bail!("no buffer"),
+        };
+        let path = match buffer.path() {
+            Some(path) => path,
+            None => bail!("{} has no file", buffer.title()),
+        };
+        if !request(&mut self.lsp, path, buffer.text(), buffer.cursor()) {
+            bail!("no language server for {}", buffer.title());
+        }
+        Ok(())
+    }
+
+    /// Asks a language server where the symbol at the cursor is defined.
+    pub fn goto_definition(&mut self) -> Result<()> {
+        self.ask_lsp(LspHost::definition)
+    }
+
+    /// Asks a language server for documentation on the symbol at the
+    /// cursor.
+    pub fn show_hover(&mut self) -> Result<()> {
+        self.ask_lsp(LspHost::hover)
+    }
+
+    /// Asks a language server where the symbol at the cursor is used.
+    pub fn find_references(&mut self) -> Result<()> {
+        self.ask_lsp(LspHost::references)
+    }
+
+    /// Asks a language server to rename the symbol at the cursor, showing
+    /// the edits that takes before making them.
+    pub fn rename_symbol(&mut self, new_name: &str) -> Result<()> {
+        self.ask_lsp(|lsp, path, text, position| lsp.rename(path, text, position, new_name))
+    }
+
+    /// The text of `path`: its buffer's if it is open, else what is on disk.
+    fn file_text(&self, path: &Path) -> Option<Rope> {
+        match self.buffer_index(path).map(|idx| &self.buffers[idx]) {
+            Some(buffer) => Some(buffer.text().clone()),
+            None => fs::read_to_string(path).ok().map(|t| Rope::from_str(&t)),
+        }
+    }
+
+    /// An entry for the locations panel, labelled with the file, line and
+    /// text `location` points at. `text` is the file's text, if readable.
+    fn location_entry(&self, location: &Location, text: Option<&Rope>) -> locations::Entry {
+        let (line, character) = location.start;
+        let position = match text {
+            Some(text) => lsp::to_position(text, location.start),
+            None => Position::new(line, character),
+        };
+        let source = text
+            .filter(|t| line < t.len_lines())
+            .map(|t| t.line(line).to_string())
+            .unwrap_or_default();
+        let label = format!(
+            "{}:{}:{}  {}",
+            self.diff_title(&location.path),
+            position.line + 1,
+            position.col + 1,
+            source.trim()
+        );
+        locations::Entry {
+            path: location.path.clone(),
+            position,
+            label,
+        }
+    }
+
+    fn show_locations(&mut self, title: &str, found: &[Location]) {
+        let mut texts: HashMap<&Path, Option<Rope>> = HashMap::new();
+        let entries = found
+            .iter()
+            .map(|location| {
+                let text = texts
+                    .entry(&location.path)
+                    .or_insert_with(|| self.file_text(&location.path));
+                self.location_entry(location, text.as_ref())
+            })
+            .collect();
+        self.locations = Some(Locations::new(title, entries));
+        self.open_locations();
+    }
+
+    /// Focuses the locations panel again after jumping from it.
+    pub fn open_locations(&mut self) {
+        if self.locations.is_some() {
+            self.panel = Some(Panel::Locations);
+            self.mode = Mode::Locations;
+        } else {
+            self.status = Some("No locations to show".to_string());
+        }
+    }
+
+    fn handle_locations_key(&mut self, key: KeyEvent) {
+        let locations = match &mut self.locations {
+            Some(locations) => locations,
+            None => return self.mode = Mode::Normal,
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => locations.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => locations.select_prev(),
+            KeyCode::Char('l') | KeyCode::Right => locations.scroll_by(8),
+            KeyCode::Char('h') | KeyCode::Left => locations.scroll_by(-8),
+            KeyCode::Enter => {
+                if let Some(entry) = locations.selected().cloned() {
+                    self.mode = Mode::Normal;
+                    if let Err(e) = self.jump_to(&entry.path, entry.position) {
+                        self.status = Some(e.to_string());
+                    }
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.panel = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Moves the cursor to the next entry of the location list, or the
+    /// previous one with `back`. If the cursor isn't on the selected entry
+    /// yet, it goes there first.
+    pub fn step_location(&mut self, back: bool) -> Result<()> {
+        let here = self
+            .buffer()
+            .and_then(|b| Some((b.path()?.to_owned(), b.cursor())));
+        let locations = match &mut self.locations {
+            Some(locations) if !locations.entries().is_empty() => locations,
+            _ => bail!("the location list is empty"),
+        };
+        let at_selected = locations
+            .selected()
+            .is_some_and(|e| here == Some((e.path.clone(), e.position)));
+        if at_selected {
+            let before = locations.selected_index();
+            match back {
+                true => locations.select_prev(),
+                false => locations.select_next(),
+            }
+            if locations.selected_index() == before {
+                bail!(
+                    "already at the {} location",
+                    if back { "first" } else { "last" }
+                );
+            }
+        }
+        let entry = locations.selected().cloned().expect("the list isn't empty");
+        let status = format!(
+            "({} of {}) {}",
+            locations.selected_index() + 1,
+            locations.entries().len(),
+            entry.label
+        );
+        self.jump_to(&entry.path, entry.position)?;
+        self.status = Some(status);
+        Ok(())
+    }
+
+    /// Runs the linter for `path`'s file type in the background; what it
+    /// finds replaces the file's earlier lints.
+    pub fn lint(&mut self, path: &Path) -> Result<()> {
+        let linter = match self.config.linters.for_path(path) {
+            Some(linter) => linter.clone(),
+            None => bail!("no linter for {}", self.diff_title(path)),
+        };
+        let path = path.to_owned();
+        match &self.spawner {
+            Some(spawner) => {
+                let name = format!("lint {}", self.diff_title(&path));
+                self.jobs.spawn(spawner, &name, move |ctx| {
+                    let result = lint::run(&linter, &path);
+                    ctx.send(Message::Linted { path, result });
+                    Ok(())
+                });
+            }
+            None => {
+                let result = lint::run(&linter, &path);
+                self.linted(path, result);
+            }
+        }
+        Ok(())
+    }
+
+    fn linted(&mut self, path: PathBuf, result: Result<Vec<Diagnostic>>) {
+        let name = self.diff_title(&path);
+        let lints = match result {
+            Ok(lints) => lints,
+            Err(e) => {
+                self.status = Some(e.to_string());
+                return;
+            }
+        };
+        self.status = Some(match lints.len() {
+            0 => format!("{}: no problems", name),
+            1 => format!("{}: 1 problem, :diagnostics shows it", name),
+            n => format!("{}: {} problems, :diagnostics lists them", name, n),
+        });
+        match lints.is_empty() {
+            true => self.lints.remove(&path),
+            false => self.lints.insert(path, lints),
+        };
+    }
+
+    /// Everything the language servers and linters report for `path`.
+    pub fn diagnostics_for<'a>(&'a self, path: &Path) -> impl Iterator<Item = &'a Diagnostic> {
+        let servers = self.diagnostics.get(path).into_iter().flatten();
+        servers.chain(self.lints.get(path).into_iter().flatten())
+    }
+
+    /// Fills the location list with the language servers' diagnostics and
+    /// the linters' findings.
+    pub fn open_diagnostics(&mut self) -> Result<()> {
+        let (mut entries, mut rows) = (Vec::new(), Vec::new());
+        for (path, diagnostics) in self.diagnostics.iter().chain(&self.lints) {
+            let text = self.file_text(path);
+            for diagnostic in diagnostics {
+                let (line, character) = diagnostic.start;
+                let position = match &text {
+                    Some(text) => lsp::to_position(text, diagnostic.start),
+                    None => Position::new(line, character),
+                };
+                let message = diagnostic.message.lines().next().unwrap_or_default();
+                let (file, at) = (
+                    self.diff_title(path),
+                    format!("{}:{}", position.line + 1, position.col + 1),
+                );
+                let severity = diagnostic.severity_name();
+                entries.push(locations::Entry {
+                    path: path.clone(),
+                    position,
+                    label: format!("{}:{}: {}: {}", file, at, severity, message),
+                });
+                rows.push(vec![file, at, severity.to_string(), message.to_string()]);
+            }
+        }
+        if entries.is_empty() {
+            bail!("no diagnostics");
+        }
+        let header = &["File", "Line", "Severity", "Message"];
+        self.locations = Some(Locations::new("Diagnostics", entries).with_table(header, rows));
+        self.open_locations();
+        Ok(())
+    }
+
+    /// Opens `path` at `position`, as a jump.
+    fn jump_to(&mut self, path: &Path, position: Position) -> Result<()> {
+        self.open(path, Some(position))
+    }
+
+    /// Where the cursor is, as a place in the jump list.
+    fn here(&self) -> Option<Jump> {
+        let buffer = self.buffer()?;
+        Some(Jump::new(buffer.path()?, buffer.cursor()))
+    }
+
+    /// Steps back through the jump list, to where the cursor was before
+    /// the last jump.
+    pub fn jump_back(&mut self) -> Result<()> {
+        let here = self
+            .here()
+            .ok_or_else(|| anyhow!("no jumps to go back to"))?;
+        match self.jumps.back(here).cloned() {
+            Some(jump) => self.show(&jump.path, Some(jump.position)),
+            None => bail!("at the start of the jump list"),
+        }
+    }
+
+    /// Steps forward through the jump list after [`jump_back`](Self::jump_back).
+    pub fn jump_forward(&mut self) -> Result<()> {
+        match self.jumps.forward().cloned() {
+            Some(jump) => self.show(&jump.path, Some(jump.position)),
+            None => bail!("at the end of the jump list"),
+        }
+    }
+
+    /// What's remembered of this workspace for the next run.
+    pub fn session(&self) -> Session {
+        Session {
+            jumps: self.jumps.clone(),
+            marks: match self.config.editor.save_marks {
+                true => self.all_marks(),
+                false => Marks::default(),
+            },
+            recent: self.recent_files(),
+            buffers: self
+                .buffers
+                .iter()
+                .filter_map(Buffer::path)
+                .map(Path::to_owned)
+                .collect(),
+            active: self.buffer().and_then(Buffer::path).map(Path::to_owned),
+            tree: Some(self.tree.snapshot()),
+            layout: Layout {
+                panel: self
+                    .panel
+                    .filter(|p| matches!(p, Panel::Log | Panel::Jobs | Panel::Plugins)),
+                outline: self.outline.is_some(),
+                preview: self.preview.is_some(),
+            },
+        }
+    }
+
+    /// Saves this workspace's session, if there's somewhere to, and drops
+    /// its snapshot as this run is done with it.
+    pub fn save_session(&self) -> Result<()> {
+        match &self.sessions {
+            Some(store) => {
+                store.save(self.tree.root(), &self.session())?;
+                store.clear_snapshot(self.tree.root())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Asks whether to restore the snapshot the last run here left, if it
+    /// was cut short with files open.
+    pub fn offer_restore(&mut self) {
+        let snapshot = match &self.sessions {
+            Some(store) => store.last_snapshot(self.tree.root()),
+            None => None,
+        };
+        if let Some(snapshot) = snapshot.filter(|s| s.buffers.iter().any(|p| p.is_file())) {
+            self.pending_restore = Some(snapshot);
+            self.mode = Mode::ConfirmRestore;
+        }
+    }
+
+    fn handle_confirm_restore_key(&mut self, key: KeyEvent) {
+        let restore = match key.code {
+            KeyCode::Char('r') | KeyCode::Char('y') | KeyCode::Enter => true,
+            KeyCode::Char('d') | KeyCode::Char('n') | KeyCode::Esc => false,
+            _ => return,
+        };
+        self.mode = Mode::Normal;
+        let snapshot = match self.pending_restore.take() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        if restore {
+            self.restore_session(snapshot.clone());
+            self.reopen(&snapshot);
+            crash::set_buffers(self.buffers.iter().filter_map(Buffer::path));
+            self.status = Some(format!("Restored {} files", self.buffers.len()));
+        }
+    }
+
+    /// Picks up this workspace's saved session, returning it, and lists
+    /// its root among the projects.
+    pub fn load_session(&mut self) -> Session {
+        if let Some(projects) = &self.projects {
+            if let Err(e) = projects.visit(self.tree.root()) {
+                tracing::warn!(error = %e, "could not list the project");
+            }
+        }
+        let session = match &self.sessions {
+            Some(store) => store.load(self.tree.root()),
+            None => Session::default(),
+        };
+        self.restore_session(session.clone());
+        session
+    }
+
+    /// Reopens the buffers and panes a session had open, as switching back
+    /// to its workspace does. Files that have gone are left out.
+    fn reopen(&mut self, session: &Session) {
+        if let Some(state) = &session.tree {
+            self.tree.set_state(state.clone());
+        }
+        let active = session.active.iter();
+        for path in session.buffers.iter().chain(active).filter(|p| p.is_file()) {
+            if let Err(e) = self.show(path, None) {
+                tracing::warn!(path = %path.display(), error = %e, "could not reopen file");
+            }
+        }
+        self.panel = session.layout.panel;
+        if session.layout.outline {
+            self.outline = Some(Outline::default());
+        }
+        if session.layout.preview {
+            let _ = self.toggle_preview();
+        }
+    }
+
+    /// Switches the workspace to `root` without restarting. This one's
+    /// session is saved, and the new root's project config, session and
+    /// buffers take over. Refused while a buffer has unsaved changes.
+    pub fn switch_project(&mut self, root: &Path) -> Result<()> {
+        let root = paths::normalize(&self.tree.root().join(root));
+        if !root.is_dir() {
+            bail!("{} isn't a directory", root.display());
+        }
+        if root == self.tree.root() {
+            bail!("{} is open already", root.display());
+        }
+        match self.dirty_buffers().count() {
+            0 => (),
+            1 => bail!("a buffer has unsaved changes; :w saves it"),
+            n => bail!("{} buffers have unsaved changes; :wa saves them", n),
+        }
+        let sources = self.config_sources.clone().map(|mut sources| {
+            sources.project = Some(project::config_path(&root));
+            sources
+        });
+        let loaded = match &sources {
+            Some(sources) => sources.load()?,
+            None => LoadedConfig {
+                config: self.config.clone(),
+                untrusted: Vec::new(),
+                project_text: None,
+            },
+        };
+        if let Err(e) = self.save_session() {
+            tracing::warn!(error = %e, "could not save the session");
+        }
+        tracing::info!(root = %root.display(), "switching project");
+
+        let fresh = match self.spawner {
+            Some(_) => App::shallow(&root, self.config.clone())?,
+            None => App::new(&root, self.config.clone())?,
+        };
+        {
+            // What belongs to this run rather than to a workspace comes
+            // along; the rest, plugins and language servers included, stops
+            // as the old app drops.
+            let old = std::mem::replace(self, fresh);
+            if let Some(stop) = &old.config_watch {
+                stop.cancel();
+            }
+            self.logger = old.logger;
+            self.spawner = old.spawner;
+            self.jobs = old.jobs;
+            self.scripts = old.scripts;
+            self.readonly = old.readonly;
+            self.to_stdout = old.to_stdout;
+            self.theme_override = old.theme_override;
+            self.overrides = old.overrides;
+            self.perf = old.perf;
+            self.show_perf = old.show_perf;
+            self.blame_inline = old.blame_inline;
+            self.mux_pane = old.mux_pane;
+            self.pty = old.pty;
+            self.terminal_split = old.terminal_split;
+            self.register = old.register;
+            self.sessions = old.sessions;
+            self.projects = old.projects;
+            self.history_store = old.history_store;
+            self.command_history = old.command_history;
+            self.tutor = old.tutor;
+        }
+        self.config_sources = sources;
+        self.apply_config(loaded.config)?;
+        if let (Some(spawner), Some(sources)) = (&self.spawner, &self.config_sources) {
+            let watcher = ConfigWatcher::new(sources.clone());
+            self.config_watch = Some(watcher.stopper());
+            spawner.watch_config(watcher);
+            self.rescan_tree()?;
+        }
+        self.start_plugins();
+        self.start_language_servers();
+        let session = self.load_session();
+        self.reopen(&session);
+        crash::set_buffers(self.buffers.iter().filter_map(Buffer::path));
+        self.status = Some(format!("Switched to {}", root.display()));
+        self.set_untrusted(loaded.untrusted, loaded.project_text);
+        Ok(())
+    }
+
+    /// Opens the project overlay, listing the other workspace roots opened
+    /// before.
+    pub fn open_projects(&mut self) -> Result<()> {
+        let roots = self
+            .projects
+            .as_ref()
+            .map(ProjectStore::load)
+            .unwrap_or_default();
+        let picker = ProjectPicker::new(roots, self.tree.root());
+        if picker.roots().is_empty() {
+            bail!("no other projects yet; :project <dir> switches to one");
+        }
+        self.project_picker = Some(picker);
+        self.mode = Mode::Projects;
+        Ok(())
+    }
+
+    fn handle_projects_key(&mut self, key: KeyEvent) {
+        let picker = match &mut self.project_picker {
+            Some(picker) => picker,
+            None => return self.mode = Mode::Normal,
+        };
+        match picker.list_mut().handle_key(key) {
+            ListKey::Picked => {
+                let root = picker.selected_root().map(Path::to_owned);
+                self.project_picker = None;
+                self.mode = Mode::Normal;
+                if let Some(root) = root {
+                    if let Err(e) = self.switch_project(&root) {
+                        self.status = Some(e.to_string());
+                    }
+                }
+            }
+            ListKey::Closed => {
+                self.project_picker = None;
+                self.mode = Mode::Normal;
+            }
+            ListKey::Handled | ListKey::Ignored => (),
+        }
+    }
+
+    /// Picks up from a session saved by an earlier run.
+    pub fn restore_session(&mut self, session: Session) {
+        self.jumps = session.jumps;
+        self.recent = session.recent;
+        if self.config.editor.save_marks {
+            self.marks = session.marks;
+            for buffer in &mut self.buffers {
+                self.marks.restore(buffer);
+            }
+        }
+    }
+
+    /// The recent files, with the cursors of those open where they are.
+    fn recent_files(&self) -> RecentFiles {
+        let mut recent = self.recent.clone();
+        for buffer in &self.buffers {
+            if let Some(path) = buffer.path() {
+                recent.update(path, buffer.cursor());
+            }
+        }
+        recent
+    }
+
+    /// Opens the recent files overlay, leaving out the active buffer's file
+    /// and files that have gone.
+    pub fn open_recent(&mut self) -> Result<()> {
+        let current = self.buffer().and_then(Buffer::path).map(Path::to_owned);
+        let label =
+            |file: &Jump| format!("{}:{}", self.diff_title(&file.path), file.position.line + 1);
+        let picker = RecentPicker::new(&self.recent_files(), current.as_deref(), label);
+        if picker.files().is_empty() {
+            bail!("no recent files");
+        }
+        self.recent_picker = Some(picker);
+        self.mode = Mode::Recent;
+        Ok(())
+    }
+
+    fn handle_recent_key(&mut self, key: KeyEvent) {
+        let picker = match &mut self.recent_picker {
+            Some(picker) => picker,
+            None => return self.mode = Mode::Normal,
+        };
+        match picker.list_mut().handle_key(key) {
+            ListKey::Picked => {
+                let file = picker.selected_file().cloned();
+                self.recent_picker = None;
+                self.mode = Mode::Normal;
+                if let Some(file) = file {
+                    if let Err(e) = self.open(&file.path, None) {
+                        self.status = Some(format!("{}: {}", file.path.display(), e));
+                    }
+                }
+            }
+            ListKey::Closed => {
+                self.recent_picker = None;
+                self.mode = Mode::Normal;
+            }
+            ListKey::Handled | ListKey::Ignored => (),
+        }
+    }
+
+    /// Every mark, with those of open files as their edits have left them.
+    fn all_marks(&self) -> Marks {
+        let mut marks = self.marks.clone();
+        for buffer in &self.buffers {
+            marks.record(buffer);
+        }
+        marks
+    }
+
+    fn handle_mark_key(&mut self, action: Action, key: KeyEvent) {
+        let name = match key.code {
+            KeyCode::Char(c) if marks::is_valid(c) => c,
+            KeyCode::Esc => return self.status = None,
+            _ => return self.status = Some(marks::NAMING.to_string()),
+        };
+        let result = match action {
+            Action::SetMark => self.set_mark(name),
+            _ => self.goto_mark(name),
+        };
+        if let Err(e) = result {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    /// Sets mark `name` at the cursor. A global mark moves here from
+    /// whichever file it was in.
+    pub fn set_mark(&mut self, name: char) -> Result<()> {
+        let idx = self.active.ok_or_else(|| anyhow!("no buffer to mark"))?;
+        let cursor = self.buffers[idx].cursor();
+        if marks::is_global(name) {
+            let buffer = &self.buffers[idx];
+            let path = buffer
+                .path()
+                .ok_or_else(|| anyhow!("{} has no file to keep mark {} in", buffer.title(), name))?
+                .to_owned();
+            for buffer in &mut self.buffers {
+                buffer.remove_mark(name);
+            }
+            self.marks.set_global(name, Jump::new(&path, cursor));
+        }
+        self.buffers[idx].set_mark(name, cursor);
+        self.status = Some(format!("Set mark {}", name));
+        Ok(())
+    }
+
+    /// Goes to mark `name`, as a jump.
+    pub fn goto_mark(&mut self, name: char) -> Result<()> {
+        let unset = || anyhow!("mark {} isn't set", name);
+        let (path, position) = match marks::is_global(name) {
+            true => {
+                let open = self
+                    .buffers
+                    .iter()
+                    .find_map(|b| Some((b.path()?.to_owned(), b.mark(name)?)));
+                match open {
+                    Some(found) => found,
+                    None => {
+                        let jump = self.marks.global(name).ok_or_else(unset)?;
+                        (jump.path.clone(), jump.position)
+                    }
+                }
+            }
+            false => {
+                let buffer = self.buffer().ok_or_else(unset)?;
+                let position = buffer.mark(name).ok_or_else(unset)?;
+                match buffer.path().map(Path::to_owned) {
+                    Some(path) => (path, position),
+                    None => {
+                        self.buffer_mut().ok_or_else(unset)?.goto(position);
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        self.open(&path, Some(position))
+    }
+
+    /// Deletes the marks named in `names`: the active buffer's own, or
+    /// global ones wherever they are.
+    pub fn delete_marks(&mut self, names: &str) -> Result<()> {
+        let mut deleted = 0;
+        for name in names.chars().filter(|c| !c.is_whitespace()) {
+            if !marks::is_valid(name) {
+                bail!(marks::NAMING);
+            }
+            let found = match marks::is_global(name) {
+                true => {
+                    let held = self.buffers.iter_mut().any(|b| b.remove_mark(name));
+                    self.marks.remove_global(name) || held
+                }
+                false => self.buffer_mut().is_some_and(|b| b.remove_mark(name)),
+            };
+            deleted += found as usize;
+        }
+        self.status = Some(match deleted {
+            0 => bail!("no such marks"),
+            1 => "Deleted 1 mark".to_string(),
+            n => format!("Deleted {} marks", n),
+        });
+        Ok(())
+    }
+
+    /// Fills the location list with the active buffer's marks and the
+    /// global ones.
+    pub fn open_marks(&mut self) -> Result<()> {
+        let active = self.buffer().and_then(Buffer::path).map(Path::to_owned);
+        let all = self.all_marks();
+        let mut shown: Vec<_> = all
+            .iter()
+            .filter(|&(name, path, _)| marks::is_global(name) || Some(path) == active.as_deref())
+            .collect();
+        if shown.is_empty() {
+            bail!("no marks set");
+        }
+        shown.sort_by_key(|&(name, _, _)| (marks::is_global(name), name));
+        let mut texts: HashMap<&Path, Option<Rope>> = HashMap::new();
+        let entries = shown
+            .into_iter()
+            .map(|(name, path, position)| {
+                let text = texts.entry(path).or_insert_with(|| self.file_text(path));
+                let source = text
+                    .as_ref()
+                    .filter(|t| position.line < t.len_lines())
+                    .map(|t| t.line(position.line).to_string())
+                    .unwrap_or_default();
+                locations::Entry {
+                    path: path.to_owned(),
+                    position,
+                    label: format!(
+                        "{}  {}:{}:{}  {}",
+                        name,
+                        self.diff_title(path),
+                        position.line + 1,
+                        position.col + 1,
+                        source.trim()
+                    ),
+                }
+            })
+            .collect();
+        self.locations = Some(Locations::new("Marks", entries));
+        self.open_locations();
+        Ok(())
+    }
+
+    /// Shows the edits of a rename for confirmation, each with the text it
+    /// replaces.
+    fn preview_rename(&mut self, files: Vec<FileEdit>) {
+        if files.is_empty() {
+            return self.status = Some("Nothing to rename".to_string());
+        }
+        let mut preview = Vec::new();
+        for file in &files {
+            let text = self.file_text(&file.path);
+            let title = self.diff_title(&file.path);
+            preview.push(format!("{} ({} edits)", title, file.edits.len()));
+            for (start, end, new) in &file.edits {
+                let (start, old) = match &text {
+                    Some(text) => {
+                        let (start, end) =
+                            (lsp::to_position(text, *start), lsp::to_position(text, *end));
+                        let old = match start.line == end.line && start.col <= end.col {
+                            true => text.line(start.line).slice(start.col..end.col).to_string(),
+                            false => "…".to_string(),
+                        };
+                        (start, old)
+                    }
+                    None => (Position::new(start.0, start.1), "?".to_string()),
+                };
+                preview.push(format!(
+                    "  {}:{}  {} → {}",
+                    start.line + 1,
+                    start.col + 1,
+                    old,
+                    new
+                ));
+            }
+        }
+        self.rename = Some((files, preview));
+        self.mode = Mode::Rename;
+    }
+
+    fn handle_rename_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('a') | KeyCode::Char('A') | KeyCode::Enter => {
+                self.mode = Mode::Normal;
+                if let Err(e) = self.apply_rename() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            KeyCode::Char('c') | KeyCode::Char('C') | KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.rename = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Makes the edits of the previewed rename: in the buffer of files that
+    /// are open, else in the file on disk. If a file that isn't open can't
+    /// be written, nothing is renamed.
+    fn apply_rename(&mut self) -> Result<()> {
+        let files = match self.rename.take() {
+            Some((files, _)) => files,
+            None => return Ok(()),
+        };
+        for file in files
+            .iter()
+            .filter(|f| self.buffer_index(&f.path).is_none())
+        {
+            if let Err(e) = paths::check_writable(&file.path) {
+                bail!(
+                    "{}: {}; nothing was renamed",
+                    self.diff_title(&file.path),
+                    e
+                );
+            }
+        }
+        let mut count = 0;
+        for file in &files {
+            let open = self.buffer_index(&file.path);
+            let mut closed = None;
+            let buffer = match open {
+                Some(idx) => &mut self.buffers[idx],
+                None => closed.insert(Buffer::open(&file.path)?),
+            };
+            let edits = lsp::to_edits(buffer.text(), &file.edits);
+            buffer.apply_edits(&edits)?;
+            count += edits.len();
+            match open {
+                Some(idx) => self.sync_lsp(idx)?,
+                None => buffer.save()?,
+            }
+        }
+        self.status = Some(format!(
+            "Renamed {} occurrences in {} files",
+            count,
+            files.len()
+        ));
+        Ok(())
+    }
+
+    /// The configured tasks and those found in the project's build files.
+    pub fn tasks(&self) -> Vec<TaskConfig> {
+        tasks::available(&self.config.tasks, self.tree.root())
+    }
+
+    /// Starts the task `name` in the background, stopping the one before
+    /// it, and shows its output. Needs a spawner, like plugins.
+    pub fn run_task(&mut self, name: &str) -> Result<()> {
+        let task = self
+            .tasks()
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| anyhow!("no task `{}`", name))?;
+        self.start_task(task)
+    }
+
+    /// Runs a shell command line as a task, with its output in the tasks
+    /// panel (`:!cmd`).
+    pub fn run_shell(&mut self, command: &str) -> Result<()> {
+        if command.is_empty() {
+            bail!("no command to run");
+        }
+        self.start_task(TaskConfig::new(
+            format!("!{}", command),
+            command.to_string(),
+        ))
+    }
+
+    fn start_task(&mut self, task: TaskConfig) -> Result<()> {
+        let spawner = match &self.spawner {
+            Some(spawner) => spawner,
+            None => bail!("no runtime to run tasks on"),
+        };
+        if let Some(run) = self.task_run.as_ref().filter(|r| r.running()) {
+            self.jobs.cancel(run.id);
+        }
+        let root = self.tree.root().to_path_buf();
+        let id = tasks::spawn(&mut self.jobs, spawner, &task, &root);
+        tracing::info!(task = %task.name, command = %task.command, "task started");
+        self.task_run = Some(TaskRun::new(id, &task, &root));
+        self.panel = Some(Panel::Tasks);
+        self.status = Some(format!("Running {}", task.command));
+        Ok(())
+    }
+
+    /// Pipes `range` of the active buffer's lines through `command`,
+    /// replacing them with its output.
+    pub fn filter_lines(&mut self, range: LineRange, command: &str) -> Result<()> {
+        if command.is_empty() {
+            bail!("no command to filter through");
+        }
+        let buffer = self
+            .buffer()
+            .ok_or_else(|| anyhow!("no buffer to filter"))?;
+        if buffer.readonly() {
+            bail!("{} is read-only", buffer.title());
+        }
+        let text = buffer.text();
+        let (start, end) = (
+            text.line_to_char(range.first),
+            text.line_to_char(range.last + 1),
+        );
+        let input = text.slice(start..end).to_string();
+        let mut output = shell::filter(command, self.tree.root(), &input)?;
+        // Keep the line break after the last line, or the lack of one.
+        if input.ends_with('\n') && !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+        let mut filtered = text.slice(..start).to_string();
+        filtered.push_str(&output);
+        filtered.push_str(&text.slice(end..).to_string());
+        let edits = format::line_edits(&text.to_string(), &filtered);
+        self.edit(|buffer| buffer.apply_edits(&edits))?;
+        let lines = range.last - range.first + 1;
+        self.status = Some(match lines {
+            1 => format!("Filtered 1 line through {}", command),
+            n => format!("Filtered {} lines through {}", n, command),
+        });
+        Ok(())
+    }
+
+    /// Types `range` of the active buffer's lines into the multiplexer pane
+    /// beside wyv's, e.g. a REPL, pressing enter after each.
+    pub fn send_lines(&mut self, range: LineRange) -> Result<()> {
+        let buffer = self
+            .buffer()
+            .ok_or_else(|| anyhow!("no buffer to send from"))?;
+        let text = buffer.text();
+        let (start, end) = (
+            text.line_to_char(range.first),
+            text.line_to_char(range.last + 1),
+        );
+        let lines = text.slice(start..end).to_string();
+        let (mux, pane) = self.send_to_pane(&lines, true)?;
+        self.status = Some(match range.last - range.first + 1 {
+            1 => format!("Sent 1 line to {} pane {}", mux, pane),
+            n => format!("Sent {} lines to {} pane {}", n, mux, pane),
+        });
+        Ok(())
+    }
+
+    /// Types the active buffer's path, or the selected tree entry's, into
+    /// the multiplexer pane beside wyv's.
+    pub fn send_path(&mut self) -> Result<()> {
+        let path = match self.buffer().and_then(Buffer::path) {
+            Some(path) => path.to_owned(),
+            None => {
+                let node = self
+                    .tree
+                    .selected_node()
+                    .ok_or_else(|| anyhow!("no file to send"))?;
+                self.tree.node_path(node)
+            }
+        };
+        let quoted = mux::shell_quote(&path.to_string_lossy());
+        let (mux, pane) = self.send_to_pane(&quoted, false)?;
+        self.status = Some(format!(
+            "Sent {} to {} pane {}",
+            self.diff_title(&path),
+            mux,
+            pane
+        ));
+        Ok(())
+    }
+
+    /// Sends `text` to the pane beside wyv's, finding or splitting one off
+    /// the first time. Returns the multiplexer's name and the pane.
+    fn send_to_pane(&mut self, text: &str, enter: bool) -> Result<(&'static str, String)> {
+        let mux = Multiplexer::detect().ok_or_else(|| anyhow!("not running in tmux or WezTerm"))?;
+        let pane = match self.mux_pane.take() {
+            Some(pane) => pane,
+            None => mux.neighbour()?,
+        };
+        // A pane that has since closed is looked for again next time.
+        mux.send(&pane, text, enter)?;
+        self.mux_pane = Some(pane.clone());
+        Ok((mux.name(), pane))
+    }
+
+    /// Lists the problems the last task's output pointed at.
+    pub fn open_problems(&mut self) -> Result<()> {
+        let run = self
+            .task_run
+            .as_ref()
+            .ok_or_else(|| anyhow!("no task has run"))?;
+        if run.problems().is_empty() {
+            bail!("{} reported no problems", run.name);
+        }
+        let title = format!("Problems from {}", run.name);
+        self.locations = Some(Locations::new(&title, run.problems().to_vec()));
+        self.open_locations();
+        Ok(())
+    }
+
+    /// Opens the project search overlay as it was left, or searching for
+    /// `pattern` if given.
+    pub fn open_search(&mut self, pattern: Option<&str>) {
+        let root = self.tree.root();
+        let search = self.search.get_or_insert_with(|| Search::new(root));
+        self.status = None;
+        self.mode = Mode::Search;
+        if let Some(pattern) = pattern {
+            search.query.set(pattern);
+            self.restart_search();
+        }
+    }
+
+    /// Searches for the overlay's pattern again, stopping the search that
+    /// was running. Without a spawner the search runs to the end first.
+    fn restart_search(&mut self) {
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return,
+        };
+        if let Some(id) = search.job {
+            self.jobs.cancel(id);
+        }
+        if search.query.is_empty() {
+            return search.start(None);
+        }
+        let regex = match search::regex(search.query.text()) {
+            Ok(regex) => regex,
+            Err(e) => return search.fail(format!("{:#}", e)),
+        };
+        let root = self.tree.root().to_path_buf();
+        match &self.spawner {
+            Some(spawner) => {
+                let id = search::spawn(&mut self.jobs, spawner, &root, regex, self.tree.options());
+                search.start(Some(id));
+            }
+            None => {
+                search.start(None);
+                let truncated =
+                    search::run(&root, &regex, self.tree.options(), &|| false, &mut |file| {
+                        search.add(file)
+                    });
+                search.finish(truncated);
+            }
+        }
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) {
+        self.status = None;
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return self.mode = Mode::Normal,
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if let Some(replacement) = &mut search.replacement {
+            // Typing goes to the replacement; moving works as before.
+            match key.code {
+                KeyCode::Char('r') if ctrl => return search.replacement = None,
+                KeyCode::Esc => return search.replacement = None,
+                KeyCode::Enter => {
+                    let replacement = replacement.text().to_string();
+                    if let Err(e) = self.preview_replace(&replacement) {
+                        self.status = Some(e.to_string());
+                    }
+                    return;
+                }
+                _ if replacement.handle_key(key) != InputKey::Ignored => return,
+                _ => (),
+            }
+        }
+        match key.code {
+            KeyCode::Char('r') if ctrl => search.replacement = Some(TextInput::default()),
+            KeyCode::Down => search.select_next(),
+            KeyCode::Up => search.select_prev(),
+            KeyCode::Char('n') if ctrl => search.select_next(),
+            KeyCode::Char('p') if ctrl => search.select_prev(),
+            KeyCode::Char('q') if ctrl => {
+                let title = format!("Search: {}", search.query.text());
+                let entries = search.entries();
+                if entries.is_empty() {
+                    self.status = Some("No matches to keep".to_string());
+                    return;
+                }
+                self.locations = Some(Locations::new(&title, entries));
+                self.open_locations();
+            }
+            KeyCode::Enter => {
+                if let Some((path, position)) = search.selected_match() {
+                    let path = path.to_owned();
+                    self.mode = Mode::Normal;
+                    if let Err(e) = self.jump_to(&path, position) {
+                        self.status = Some(e.to_string());
+                    }
+                }
+            }
+            KeyCode::Esc => self.mode = Mode::Normal,
+            _ => {
+                if search.query.handle_key(key) == InputKey::Edited {
+                    self.restart_search();
+                }
+            }
+        }
+    }
+
+    /// Works out what replacing the project search's matches with
+    /// `replacement` would change, in open buffers or else on disk, and shows
+    /// it for confirmation.
+    pub fn preview_replace(&mut self, replacement: &str) -> Result<()> {
+        let search = match &self.search {
+            Some(search) if !search.query.is_empty() => search,
+            _ => bail!("search for something to replace first"),
+        };
+        if search.searching() {
+            bail!("the search is still running");
+        }
+        let regex = search::regex(search.query.text())?;
+        let mut files = Vec::new();
+        for file in search.files() {
+            let text = match self.file_text(&file.path) {
+                Some(text) => text.to_string(),
+                None => continue,
+            };
+            let changes = search::changes(&text, &regex, replacement);
+            if !changes.is_empty() {
+                files.push((file.path.clone(), changes));
+            }
+        }
+        if files.is_empty() {
+            bail!("nothing matches `{}` any more", search.query.text());
+        }
+        let root = self.tree.root();
+        self.replace = Some(Replace::new(root, search.query.text(), replacement, files));
+        self.mode = Mode::Replace;
+        Ok(())
+    }
+
+    fn handle_replace_key(&mut self, key: KeyEvent) {
+        let replace = match &mut self.replace {
+            Some(replace) => replace,
+            None => return self.mode = Mode::Normal,
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => replace.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => replace.select_prev(),
+            KeyCode::Char(' ') => replace.toggle_selected(),
+            KeyCode::Char('t') => replace.toggle_all(),
+            KeyCode::Char('a') | KeyCode::Enter => {
+                self.mode = Mode::Normal;
+                if let Err(e) = self.apply_replace() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            KeyCode::Char('c') | KeyCode::Esc => {
+                self.replace = None;
+                self.mode = Mode::Search;
+            }
+            _ => (),
+        }
+    }
+
+    /// Makes the changes of the previewed replace that weren't left out:
+    /// in the buffers of files that are open, else in the files on disk.
+    /// Nothing is changed if any file has changed since the preview or
+    /// can't be written.
+    fn apply_replace(&mut self) -> Result<()> {
+        let files = match self.replace.take() {
+            Some(replace) => replace.included(),
+            None => return Ok(()),
+        };
+        if files.is_empty() {
+            bail!("every change was left out");
+        }
+
+        let mut on_disk = Vec::new();
+        let mut in_buffers = Vec::new();
+        for (path, changes) in &files {
+            let open = self.buffer_index(path);
+            if let Some(buffer) = open.map(|idx| &self.buffers[idx]).filter(|b| b.readonly()) {
+                bail!("{} is read-only", buffer.title());
+            }
+            let text = self
+                .file_text(path)
+                .ok_or_else(|| anyhow!("could not read {}", path.display()))?;
+            let stale = changes.iter().any(|c| {
+                c.line >= text.len_lines()
+                    || text.line(c.line).to_string().trim_end_matches(['\n', '\r']) != c.text
+            });
+            if stale {
+                bail!(
+                    "{} changed since the preview; search again",
+                    self.diff_title(path)
+                );
+            }
+            let edits: Vec<(Position, Position, String)> = changes
+                .iter()
+                .map(|c| {
+                    let start = Position::new(c.line, c.range.0);
+                    let end = Position::new(c.line, c.range.1);
+                    (start, end, c.replacement.clone())
+                })
+                .collect();
+            match open {
+                Some(idx) => in_buffers.push((idx, edits)),
+                None => {
+                    if let Err(e) = paths::check_writable(path) {
+                        bail!("{}: {}; nothing was replaced", self.diff_title(path), e);
+                    }
+                    let mut buffer = Buffer::from_text(&text.to_string());
+                    buffer.apply_edits(&edits)?;
+                    on_disk.push((path.clone(), buffer.text().to_string()));
+                }
+            }
+        }
+
+        search::write_all(&on_disk)?;
+        for (idx, edits) in in_buffers {
+            self.buffers[idx].apply_edits(&edits)?;
+            self.sync_lsp(idx)?;
+        }
+        let count: usize = files.iter().map(|(_, changes)| changes.len()).sum();
+        tracing::info!(count, files = files.len(), "replaced across files");
+        self.status = Some(format!(
+            "Replaced {} matches in {} files",
+            count,
+            files.len()
+        ));
+        if let Some(search) = &mut self.search {
+            search.replacement = None;
+        }
+        self.restart_search();
+        Ok(())
+    }
+
+    /// Opens the symbol overlay on the active buffer's symbols, or on those
+    /// of every file in the project, which are gathered in the background
+    /// when there is a spawner.
+    pub fn open_symbols(&mut self, workspace: bool) -> Result<()> {
+        let root = self.tree.root().to_path_buf();
+        let picker = match workspace {
+            true => match &self.spawner {
+                Some(spawner) => {
+                    let id = symbols::spawn(&mut self.jobs, spawner, &root, self.tree.options());
+                    let mut picker = SymbolPicker::new(&root, true, Vec::new());
+                    picker.job = Some(id);
+                    picker
+                }
+                None => {
+                    let found = symbols::workspace(&root, self.tree.options(), &|| false);
+                    SymbolPicker::new(&root, true, found)
+                }
+            },
+            false => {
+                let buffer = self
+                    .buffer()
+                    .ok_or_else(|| anyhow!("no buffer to list the symbols of"))?;
+                let path = buffer
+                    .path()
+                    .ok_or_else(|| anyhow!("{} isn't a file", buffer.title()))?;
+                let found = Scanner::default().scan(path, &buffer.text().to_string());
+                if found.is_empty() {
+                    bail!("no symbols found in {}", self.diff_title(path));
+                }
+                SymbolPicker::new(&root, false, found)
+            }
+        };
+        self.close_symbols();
+        self.symbols = Some(picker);
+        self.status = None;
+        self.mode = Mode::Symbols;
+        Ok(())
+    }
+
+    /// Closes the symbol overlay, stopping the job gathering its symbols.
+    fn close_symbols(&mut self) {
+        if let Some(id) = self.symbols.take().and_then(|p| p.job) {
+            self.jobs.cancel(id);
+        }
+        if self.mode == Mode::Symbols {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    fn handle_symbols_key(&mut self, key: KeyEvent) {
+        let picker = match &mut self.symbols {
+            Some(picker) => picker,
+            None => return self.mode = Mode::Normal,
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Down => picker.select_next(),
+            KeyCode::Up => picker.select_prev(),
+            KeyCode::Char('n') if ctrl => picker.select_next(),
+            KeyCode::Char('p') if ctrl => picker.select_prev(),
+            KeyCode::Char(c) if !ctrl => picker.push_query(c),
+            KeyCode::Backspace => picker.pop_query(),
+            KeyCode::Enter => {
+                if let Some(symbol) = picker.selected_symbol() {
+                    let (path, position) = (symbol.path.clone(), symbol.position);
+                    self.close_symbols();
+                    if let Err(e) = self.jump_to(&path, position) {
+                        self.status = Some(e.to_string());
+                    }
+                }
+            }
+            KeyCode::Esc => self.close_symbols(),
+            _ => (),
+        }
+    }
+
+    /// Shows the outline sidebar, or hides it if it is shown.
+    pub fn toggle_outline(&mut self) {
+        match self.outline {
+            Some(_) => {
+                self.outline = None;
+                if self.mode == Mode::Outline {
+                    self.mode = Mode::Normal;
+                }
+            }
+            None => self.outline = Some(Outline::default()),
+        }
+    }
+
+    /// Shows the outline sidebar if it is hidden and moves into it, with
+    /// the symbol around the cursor selected.
+    pub fn focus_outline(&mut self) {
+        self.outline.get_or_insert_with(Outline::default);
+        self.refresh_outline();
+        self.status = None;
+        self.mode = Mode::Outline;
+    }
+
+    /// Works the outline out again if the focused buffer has changed since
+    /// it was, from its language server or else with patterns, and selects
+    /// the symbol around the cursor unless the outline is focused. Called
+    /// before each frame is drawn.
+    pub fn refresh_outline(&mut self) {
+        let outline = match &mut self.outline {
+            Some(outline) => outline,
+            None => return,
+        };
+        let buffer = self.active.and_then(|i| self.buffers.get(i));
+        let path = buffer.and_then(Buffer::path);
+        let text = buffer.map(Buffer::text);
+        if !outline.is_current(path, text) {
+            outline.set_source(path.zip(text).map(|(p, t)| (p.to_owned(), t.clone())));
+            // The server's answer replaces the outline when it comes.
+            if !path.is_some_and(|path| self.lsp.document_symbols(path)) {
+                outline.scan();
+            }
+        }
+        if let (Some(buffer), false) = (buffer, self.mode == Mode::Outline) {
+            outline.follow(buffer.cursor().line);
+        }
+    }
+
+    fn handle_outline_key(&mut self, key: KeyEvent) {
+        let outline = match &mut self.outline {
+            Some(outline) => outline,
+            None => return self.mode = Mode::Normal,
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => outline.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => outline.select_prev(),
+            KeyCode::Enter => {
+                if let Some(item) = outline.selected_item() {
+                    let symbol = item.symbol.clone();
+                    self.mode = Mode::Normal;
+                    if let Err(e) = self.jump_to(&symbol.path, symbol.position) {
+                        self.status = Some(e.to_string());
+                    }
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Char('o') => self.toggle_outline(),
+            KeyCode::Esc => self.mode = Mode::Normal,
+            _ => (),
+        }
+    }
+
+    /// Shows the markdown preview pane for the active buffer, or hides it
+    /// if it is shown.
+    pub fn toggle_preview(&mut self) -> Result<()> {
+        if self.preview.take().is_some() {
+            return Ok(());
+        }
+        let buffer = self
+            .buffer()
+            .ok_or_else(|| anyhow!("no buffer to preview"))?;
+        if !buffer.path().is_some_and(preview::is_markdown) {
+            bail!("{} isn't markdown", buffer.title());
+        }
+        self.preview = Some(Preview::default());
+        self.refresh_preview();
+        Ok(())
+    }
+
+    /// Lays the preview out again if the focused buffer has changed since
+    /// it was. Called before each frame is drawn.
+    pub fn refresh_preview(&mut self) {
+        let preview = match &mut self.preview {
+            Some(preview) => preview,
+            None => return,
+        };
+        let buffer = self.active.and_then(|i| self.buffers.get(i));
+        let path = buffer
+            .and_then(Buffer::path)
+            .filter(|path| preview::is_markdown(path));
+        let text = path.and(buffer).map(Buffer::text);
+        if !preview.is_current(path, text) {
+            preview.set_source(path.zip(text).map(|(p, t)| (p.to_owned(), t.clone())));
+        }
+    }
+
+    fn spell_checker(&mut self) -> &mut SpellChecker {
+        let (root, words) = (self.tree.root(), &self.config.spell.words);
+        self.spell
+            .get_or_insert_with(|| SpellChecker::new(root, words))
+    }
+
+    /// The misspelled words on `lines` of the active buffer, to underline
+    /// when spell checking is on.
+    pub fn misspellings(&mut self, lines: Range<usize>) -> Vec<Misspelling> {
+        if !self.config.spell.enabled {
+            return Vec::new();
+        }
+        self.buffer_misspellings(lines)
+    }
+
+    fn buffer_misspellings(&mut self, lines: Range<usize>) -> Vec<Misspelling> {
+        let (path, text) = match self.buffer().filter(|b| !b.is_large()) {
+            Some(buffer) => match buffer.path() {
+                Some(path) => (path.to_owned(), buffer.text().clone()),
+                None => return Vec::new(),
+            },
+            None => return Vec::new(),
+        };
+        self.spell_checker().misspellings(&path, &text, lines)
+    }
+
+    /// Offers replacements for the first misspelled word at or after the
+    /// cursor, whether or not spell checking is on, moving the cursor to it.
+    pub fn suggest_spelling(&mut self) -> Result<()> {
+        let buffer = self
+            .buffer()
+            .ok_or_else(|| anyhow!("no buffer to check the spelling of"))?;
+        let (cursor, count) = (buffer.cursor(), buffer.line_count());
+        let misspelling = self
+            .buffer_misspellings(cursor.line..count)
+            .into_iter()
+            .find(|m| m.line > cursor.line || m.end > cursor.col)
+            .ok_or_else(|| anyhow!("no misspelled words after the cursor"))?;
+        let words = self.spell_checker().suggest(&misspelling.word);
+        if let Some(buffer) = self.buffer_mut() {
+            buffer.goto(Position::new(misspelling.line, misspelling.start));
+        }
+        self.suggestions = Some(Suggestions::new(misspelling, words));
+        self.status = None;
+        self.mode = Mode::Spell;
+        Ok(())
+    }
+
+    /// Accepts `word` from now on in this project, adding it to its list.
+    pub fn add_spelling(&mut self, word: &str) -> Result<()> {
+        self.spell_checker().add_word(word)
+    }
+
+    fn handle_spell_key(&mut self, key: KeyEvent) {
+        let suggestions = match &mut self.suggestions {
+            Some(suggestions) => suggestions,
+            None => return self.mode = Mode::Normal,
+        };
+        match key.code {
+            KeyCode::Char('j') => suggestions.select_next(),
+            KeyCode::Char('k') => suggestions.select_prev(),
+            KeyCode::Char('q') => self.close_suggestions(),
+            KeyCode::Char('a') => {
+                let word = suggestions.misspelling.word.clone();
+                self.close_suggestions();
+                self.status = Some(match self.add_spelling(&word) {
+                    Ok(()) => format!("added `{}` to the project's words", word),
+                    Err(e) => e.to_string(),
+                });
+            }
+            _ => match suggestions.handle_key(key) {
+                MenuKey::Picked => self.pick_suggestion(),
+                MenuKey::Closed => self.close_suggestions(),
+                MenuKey::Moved | MenuKey::Ignored => (),
+            },
+        }
+    }
+
+    /// Replaces the misspelled word with the selected suggestion.
+    fn pick_suggestion(&mut self) {
+        let suggestions = match &self.suggestions {
+            Some(suggestions) => suggestions,
+            None => return,
+        };
+        let word = match suggestions.selected_word() {
+            Some(word) => word.to_string(),
+            None => return,
+        };
+        let m = suggestions.misspelling.clone();
+        self.close_suggestions();
+        let (start, end) = (Position::new(m.line, m.start), Position::new(m.line, m.end));
+        if let Err(e) = self.edit(|b| b.replace(start, end, &word)) {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    fn close_suggestions(&mut self) {
+        self.suggestions = None;
+        if self.mode == Mode::Spell {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    pub fn terminal_visible(&self) -> bool {
+        match self.config.terminal.position {
+            TerminalPosition::Bottom => self.panel == Some(Panel::Terminal),
+            TerminalPosition::Right => self.terminal_split,
+        }
+    }
+
+    /// Shows the terminal pane where the config puts it and focuses it,
+    /// starting the shell if it isn't running. Needs a spawner to deliver
+    /// the shell's output.
+    pub fn show_terminal(&mut self) -> Result<()> {
+        if self.pty.is_none() {
+            let sender = match &self.spawner {
+                Some(spawner) => spawner.sender(),
+                None => bail!("no runtime to run a terminal on"),
+            };
+            // The first draw resizes it to fit the pane.
+            let pty = Pty::spawn(&self.config.terminal, self.tree.root(), (24, 80), sender)?;
+            self.pty = Some(pty);
+        }
+        match self.config.terminal.position {
+            TerminalPosition::Bottom => {
+                self.panel = Some(Panel::Terminal);
+                self.terminal_split = false;
+            }
+            TerminalPosition::Right => {
+                if self.panel == Some(Panel::Terminal) {
+                    self.panel = None;
+                }
+                self.terminal_split = true;
+            }
+        }
+        self.mode = Mode::Terminal;
+        Ok(())
+    }
+
+    /// Hides the terminal pane if it has focus, else shows and focuses it.
+    /// The shell keeps running while hidden.
+    pub fn toggle_terminal(&mut self) -> Result<()> {
+        match self.mode == Mode::Terminal && self.terminal_visible() {
+            true => {
+                self.hide_terminal();
+                Ok(())
+            }
+            false => self.show_terminal(),
+        }
+    }
+
+    fn hide_terminal(&mut self) {
+        if self.panel == Some(Panel::Terminal) {
+            self.panel = None;
+        }
+        self.terminal_split = false;
+        self.copy_mode = None;
+        if matches!(self.mode, Mode::Terminal | Mode::TerminalCopy) {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    fn handle_terminal_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        if self.config.keymap.action(&key) == Some(Action::ToggleTerminal) {
+            return self.run(Action::ToggleTerminal);
+        }
+        let result = match (key.code, &mut self.pty) {
+            (_, None) => return self.mode = Mode::Normal,
+            // Back to the rest of the app, leaving the pane open.
+            (KeyCode::Char('\\'), _) if ctrl => return self.mode = Mode::Normal,
+            (KeyCode::PageUp, _) if shift => {
+                self.start_copy_mode();
+                return self.handle_terminal_copy_key(KeyEvent::from(KeyCode::PageUp));
+            }
+            (_, Some(pty)) => pty.send_key(&key),
+        };
+        if let Err(e) = result {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    /// Starts copy mode on the line of the shell's cursor.
+    fn start_copy_mode(&mut self) {
+        let pty = match &self.pty {
+            Some(pty) => pty,
+            None => return,
+        };
+        let cursor = match pty.cursor() {
+            Some((row, _)) => row as isize,
+            None => pty.size().0 as isize - 1,
+        };
+        self.copy_mode = Some(CopyMode {
+            cursor,
+            anchor: None,
+        });
+        self.mode = Mode::TerminalCopy;
+    }
+
+    fn leave_copy_mode(&mut self) {
+        if let Some(pty) = &mut self.pty {
+            pty.set_scrollback(0);
+        }
+        self.copy_mode = None;
+        self.mode = Mode::Terminal;
+    }
+
+    fn handle_terminal_copy_key(&mut self, key: KeyEvent) {
+        let (pty, copy) = match (&mut self.pty, &mut self.copy_mode) {
+            (Some(pty), Some(copy)) => (pty, copy),
+            _ => return self.mode = Mode::Normal,
+        };
+        let rows = pty.size().0 as isize;
+        let cursor = match key.code {
+            KeyCode::Char('k') | KeyCode::Up => copy.cursor - 1,
+            KeyCode::Char('j') | KeyCode::Down => copy.cursor + 1,
+            KeyCode::PageUp => copy.cursor - rows,
+            KeyCode::PageDown => copy.cursor + rows,
+            KeyCode::Char('g') => isize::MIN,
+            KeyCode::Char('G') => isize::MAX,
+            KeyCode::Char('v') => {
+                copy.anchor = match copy.anchor {
+                    Some(_) => None,
+                    None => Some(copy.cursor),
+                };
+                return;
+            }
+            KeyCode::Char('y') | KeyCode::Enter => {
+                let (first, last) = copy.selection();
+                self.register = Some(pty.text(first, last));
+                self.status = Some(match last - first {
+                    0 => "Copied 1 line".to_string(),
+                    n => format!("Copied {} lines", n + 1),
+                });
+                return self.leave_copy_mode();
+            }
+            KeyCode::Char('p') => {
+                if let Some(text) = &self.register {
+                    if let Err(e) = pty.paste(text) {
+                        self.status = Some(e.to_string());
+                    }
+                }
+                return self.leave_copy_mode();
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return self.leave_copy_mode(),
+            _ => return,
+        };
+        copy.cursor = cursor.clamp(-(pty.history() as isize), rows - 1);
+        // Scroll just enough to keep the cursor in view.
+        let top = -(pty.scrollback() as isize);
+        if copy.cursor < top {
+            pty.set_scrollback(copy.cursor.unsigned_abs());
+        } else if copy.cursor >= top + rows {
+            pty.set_scrollback((rows - 1 - copy.cursor) as usize);
+        }
+    }
+
+    fn pick_completion(&mut self) {
+        if let Err(e) = self.accept_completion() {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    /// Replaces the completed word with the selected item, expanding it if
+    /// it is a snippet.
+    fn accept_completion(&mut self) -> Result<()> {
+        let completion = match self.completion.take() {
+            Some(completion) => completion,
+            None => return Ok(()),
+        };
+        let item = match completion.selected() {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+        let (text, offset) = match item.snippet {
+            true => snippet::expand(&item.insert_text),
+            false => (item.insert_text.clone(), item.insert_text.chars().count()),
+        };
+        let start = completion.start();
+        // Where the cursor ends up: `offset` chars into the inserted text.
+        let before: String = text.chars().take(offset).collect();
+        let cursor = match before.rsplit_once('\n') {
+            Some((above, last)) => Position::new(
+                start.line + above.matches('\n').count() + 1,
+                last.chars().count(),
+            ),
+            None => Position::new(start.line, start.col + before.chars().count()),
+        };
+        self.edit(|buffer| {
+            buffer.delete_before(buffer.cursor().col.saturating_sub(start.col))?;
+            buffer.insert(&text)?;
+            buffer.goto(cursor);
+            Ok(())
+        })
+    }
+
+    fn handle_command_key(&mut self, key: KeyEvent) {
+        if self.history_search.is_some() {
+            return self.handle_history_search_key(key);
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if !matches!(key.code, KeyCode::Up | KeyCode::Down) {
+            self.history_step = None;
+        }
+        match key.code {
+            KeyCode::Esc => {
+                self.command_line.clear();
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Enter => self.run_command_line(),
+            KeyCode::Backspace if self.command_line.is_empty() => self.mode = Mode::Normal,
+            KeyCode::Tab => self.complete_command_line(),
+            KeyCode::Up => self.step_history(true),
+            KeyCode::Down => self.step_history(false),
+            KeyCode::Char('r') if ctrl => {
+                self.status = None;
+                self.history_search = Some(HistorySearch::default());
+            }
+            _ => {
+                self.command_line.handle_key(key);
+            }
+        }
+    }
+
+    /// Runs what's on the command line, adding it to the history.
+    fn run_command_line(&mut self) {
+        let line = self.command_line.take();
+        self.mode = Mode::Normal;
+        self.command_history.push(&line);
+        if let Some(store) = &self.history_store {
+            if let Err(e) = store.save(&self.command_history) {
+                tracing::warn!(error = %e, "could not save the command history");
+            }
+        }
+        if let Err(e) = commands::execute(self, &line) {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    /// Shows the next line of the history, `older` or newer, that starts
+    /// with what was typed before stepping. Past the newest, what was typed
+    /// comes back.
+    fn step_history(&mut self, older: bool) {
+        let (at, typed) = match self.history_step.take() {
+            Some(step) => step,
+            None if older => (
+                self.command_history.len(),
+                self.command_line.text().to_string(),
+            ),
+            None => return,
+        };
+        let next = match older {
+            true => self.command_history.older(at, &typed).or(Some(at)),
+            false => self.command_history.newer(at, &typed),
+        };
+        match next.and_then(|idx| Some((idx, self.command_history.get(idx)?))) {
+            Some((idx, line)) => {
+                self.command_line.set(line);
+                self.history_step = Some((idx, typed));
+            }
+            None => self.command_line.set(&typed),
+        }
+    }
+
+    /// Ctrl-r searches back through the history for lines with what's
+    /// typed in them, again for the one before. Enter runs the line found
+    /// and Esc leaves the command line as it was; other keys take the line
+    /// to edit.
+    fn handle_history_search_key(&mut self, key: KeyEvent) {
+        let search = match &mut self.history_search {
+            Some(search) => search,
+            None => return,
+        };
+        let history = &self.command_history;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('r') if ctrl => {
+                let before = search.found.unwrap_or(history.len());
+                if let Some(found) = history.search(&search.query, before) {
+                    search.found = Some(found);
+                }
+            }
+            KeyCode::Esc => self.history_search = None,
+            KeyCode::Char('g') if ctrl => self.history_search = None,
+            KeyCode::Char(c) => {
+                search.query.push(c);
+                let from = search.found.map_or(history.len(), |i| i + 1);
+                search.found = history.search(&search.query, from);
+            }
+            KeyCode::Backspace => {
+                search.query.pop();
+                search.found = match search.query.is_empty() {
+                    true => None,
+                    false => history.search(&search.query, history.len()),
+                };
+            }
+            code => {
+                if let Some(line) = search.found.and_then(|i| history.get(i)) {
+                    self.command_line.set(line);
+                }
+                self.history_search = None;
+                if code == KeyCode::Enter {
+                    self.run_command_line();
+                }
+            }
+        }
+    }
+
+    /// Extends the word before the cursor as far as the candidates agree,
+    /// listing them in the status bar if there are several.
+    fn complete_command_line(&mut self) {
+        let tree = &self.tree;
+        let candidates = self
+            .command_line
+            .complete(|typed| commands::complete(typed, tree));
+        if candidates.len() > 1 {
+            self.status = Some(candidates.join(" "));
+        }
+    }
+
+    pub fn run(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.quit(false),
+            Action::CommandMode => {
+                self.command_line.clear();
+                self.status = None;
+                self.mode = Mode::Command;
+            }
+            Action::TreeUp => self.tree.select_prev(),
+            Action::TreeDown => self.tree.select_next(),
+            Action::TreeExpand => self.tree.expand_selected(),
+            Action::TreeCollapse => self.tree.collapse_selected(),
+            Action::TreeToggle => self.open_selected(),
+            Action::TreeLoadMore => self.load_more(),
+            Action::TreeMark => {
+                if self.tree.toggle_mark_selected() {
+                    let name = self.tree.selected_node().map_or("", |n| n.name());
+                    let hint = match self.tree.marked().len() {
+                        1 => "mark or select another file and press = to compare",
+                        _ => "= compares the marked files",
+                    };
+                    self.status = Some(format!("Marked {}; {}", name, hint));
+                }
+            }
+            Action::CompareMarked => {
+                if let Err(e) = self.compare_marked() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::TogglePerf => self.show_perf = !self.show_perf,
+            Action::InsertMode => match self.buffer() {
+                Some(buffer) if buffer.readonly() => {
+                    self.status = Some(format!("{} is read-only", buffer.title()));
+                }
+                Some(_) => {
+                    self.status = None;
+                    self.mode = Mode::Insert;
+                }
+                None => self.status = Some("no buffer".to_string()),
+            },
+            Action::ToggleBlame => {
+                if let Err(e) = self.toggle_inline_blame() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::GotoDefinition => {
+                if let Err(e) = self.goto_definition() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::FindReferences => {
+                if let Err(e) = self.find_references() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::Hover => {
+                if let Err(e) = self.show_hover() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::JumpBack => {
+                if let Err(e) = self.jump_back() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::JumpForward => {
+                if let Err(e) = self.jump_forward() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::ToggleTerminal => {
+                if let Err(e) = self.toggle_terminal() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::Search => self.open_search(None),
+            Action::Outline => self.focus_outline(),
+            Action::FileSymbols | Action::WorkspaceSymbols => {
+                if let Err(e) = self.open_symbols(action == Action::WorkspaceSymbols) {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::Structure => {
+                if let Err(e) = self.open_structure() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::TogglePreview => {
+                if let Err(e) = self.toggle_preview() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::SpellSuggest => {
+                if let Err(e) = self.suggest_spelling() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::Follow => {
+                if let Err(e) = self.follow() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::NextLocation | Action::PrevLocation => {
+                if let Err(e) = self.step_location(action == Action::PrevLocation) {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::Suspend => self.suspend = true,
+            Action::SetMark | Action::GotoMark => {
+                self.pending_mark = Some(action);
+                self.status = Some(
+                    match action {
+                        Action::SetMark => "Mark: type its name, A to Z for one across files",
+                        _ => "Go to mark: type its name",
+                    }
+                    .to_string(),
+                );
+            }
+            Action::RecentFiles => {
+                if let Err(e) = self.open_recent() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::SaveAs => {
+                let dir = self
+                    .buffer()
+                    .and_then(Buffer::path)
+                    .and_then(Path::parent)
+                    .and_then(|dir| dir.strip_prefix(self.tree.root()).ok())
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .map(|dir| format!("{}/", dir.display()));
+                self.command_line
+                    .set(&format!("saveas {}", dir.unwrap_or_default()));
+                self.status = None;
+                self.mode = Mode::Command;
+            }
+            Action::Projects => {
+                if let Err(e) = self.open_projects() {
+                    self.status = Some(e.to_string());
+                }
+            }
+            Action::None => (),
+        }
+    }
+
+    /// Toggles the selected directory, or opens the selected file.
+    fn open_selected(&mut self) {
+        let path = match self.tree.selected_node() {
+            Some(node) if node.more().is_some() => return self.load_more(),
+            Some(node) if !node.has_children() => self.tree.node_path(node),
+            _ => return self.tree.toggle_selected(),
+        };
+
+        if let Err(e) = self.open(&path, None) {
+            tracing::warn!(path = %path.display(), error = %e, "failed to open file");
+            self.status = Some(format!("{}: {}", path.display(), e));
+        }
+    }
+
+    /// Lists the entries of a directory left out by `tree.max_entries`,
+    /// when its “more” row is selected.
+    fn load_more(&mut self) {
+        match self.tree.load_selected() {
+            Ok(Some(1)) => self.status = Some("Loaded 1 more entry".to_string()),
+            Ok(Some(count)) => self.status = Some(format!("Loaded {} more entries", count)),
+            Ok(None) => (),
+            Err(e) => self.status = Some(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Git status letters for the tree's `git` column, kept per repository and
+//! refreshed off the UI thread. Files saved or otherwise known to have
+//! changed are looked at again on their own; the whole repository is
+//! queried at most once every [`FULL_EVERY`], however often it's asked to.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::git::{self, StatusEntry};
+
+/// The shortest time between two full `git status` runs in a repository.
+pub const FULL_EVERY: Duration = Duration::from_secs(10);
+/// Shown beside directories with changes somewhere under them.
+pub const CHANGED_BELOW: char = '•';
+
+/// A `git status` to run: of the whole repository, or only of `paths`,
+/// relative to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub repo: PathBuf,
+    pub paths: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default)]
+struct Repo {
+    /// Status letter by path, relative to the repository.
+    files: HashMap<String, char>,
+    /// Directories with changes under them, relative to the repository.
+    dirs: HashSet<String>,
+    /// Paths to query once the running query is done.
+    dirty: HashSet<String>,
+    full_wanted: bool,
+    full_at: Option<Instant>,
+    running: bool,
+}
+
+/// The status of each repository the tree shows files from.
+#[derive(Debug, Default)]
+pub struct GitBadges {
+    repos: HashMap<PathBuf, Repo>,
+    /// Directories the repository containing them was looked for from.
+    looked_from: HashSet<PathBuf>,
+    generation: u64,
+}
+
+impl GitBadges {
+    /// Starts keeping the status of the repository at `repo`, queried in
+    /// full on the next [`next_query`](Self::next_query).
+    pub fn track(&mut self, repo: &Path) {
+        self.repos.entry(repo.to_owned()).or_default().full_wanted = true;
+    }
+
+    /// Whether to look for the repository containing `dir`: once for each
+    /// directory, unless it's in a repository already tracked.
+    pub fn should_look_from(&mut self, dir: &Path) -> bool {
+        self.find(dir).is_none() && self.looked_from.insert(dir.to_owned())
+    }
+
+    /// A number that changes whenever a badge may have.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The letter to show beside `path`: git's for a changed file, and
+    /// [`CHANGED_BELOW`] for a directory with changed files in it.
+    pub fn badge(&self, path: &Path) -> Option<char> {
+        let (repo, relative) = self.find(path)?;
+        let relative = relative.to_str()?;
+        match repo.files.get(relative) {
+            Some(&letter) => Some(letter),
+            None => repo.dirs.contains(relative).then_some(CHANGED_BELOW),
+        }
+    }
+
+    fn find<'a>(&self, path: &'a Path) -> Option<(&Repo, &'a Path)> {
+        self.repos
+            .iter()
+            .find_map(|(root, repo)| Some((repo, path.strip_prefix(root).ok()?)))
+    }
+
+    /// `path` changed, e.g. it was saved: its status is queried again.
+    pub fn changed(&mut self, path: &Path) {
+        for (root, repo) in &mut self.repos {
+            if let Some(relative) = path.strip_prefix(root).ok().and_then(Path::to_str) {
+                repo.dirty.insert(relative.to_string());
+            }
+        }
+    }
+
+    /// Anything may have changed, e.g. after a commit: every repository is
+    /// queried in full again, as soon as [`FULL_EVERY`] allows.
+    pub fn changed_all(&mut self) {
+        for repo in self.repos.values_mut() {
+            repo.full_wanted = true;
+        }
+    }
+
+    /// The next query to run at `now`, if one is due. Each repository runs
+    /// one query at a time.
+    pub fn next_query(&mut self, now: Instant) -> Option<Query> {
+        self.repos.iter_mut().find_map(|(root, repo)| {
+            if repo.running {
+                return None;
+            }
+            let full_due = repo
+                .full_at
+                .is_none_or(|at| now.duration_since(at) >= FULL_EVERY);
+            let paths = match (repo.full_wanted && full_due, repo.dirty.is_empty()) {
+                (true, _) => {
+                    repo.full_wanted = false;
+                    repo.full_at = Some(now);
+                    repo.dirty.clear();
+                    None
+                }
+                (false, false) => Some(repo.dirty.drain().collect()),
+                (false, true) => return None,
+            };
+            repo.running = true;
+            Some(Query {
+                repo: root.clone(),
+                paths,
+            })
+        })
+    }
+
+    /// Applies the result of `query`. On failure the old letters are kept.
+    pub fn finished(&mut self, query: Query, result: Result<Vec<StatusEntry>>) {
+        let repo = match self.repos.get_mut(&query.repo) {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.running = false;
+        let entries = match result {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!(repo = %query.repo.display(), error = %e, "git status failed");
+                return;
+            }
+        };
+        match &query.paths {
+            Some(paths) => {
+                for path in paths {
+                    repo.files.remove(path);
+                }
+            }
+            None => repo.files.clear(),
+        }
+        for entry in entries {
+            let letter = match entry.unstaged {
+                ' ' => entry.staged,
+                letter => letter,
+            };
+            // Untracked directories are listed with a `/` after them.
+            let path = entry.path.strip_suffix('/').unwrap_or(&entry.path);
+            repo.files.insert(path.to_string(), letter);
+        }
+        repo.dirs = repo
+            .files
+            .keys()
+            .flat_map(|path| Path::new(path).ancestors().skip(1))
+            .filter_map(|dir| dir.to_str().filter(|d| !d.is_empty()))
+            .map(str::to_string)
+            .collect();
+        self.generation += 1;
+    }
+}
+
+/// Runs `query`'s `git status`.
+pub fn run(query: &Query) -> Result<Vec<StatusEntry>> {
+    match &query.paths {
+        Some(paths) => git::status_of(&query.repo, paths),
+        None => git::status(&query.repo),
+    }
+}
+
+#[test]
+fn test_saved_files_are_queried_alone_and_full_runs_are_rate_limited() {
+    let repo = Path::new("/work/repo");
+    let mut badges = GitBadges::default();
+    badges.track(repo);
+    let start = Instant::now();
+
+    let full = badges.next_query(start).unwrap();
+    assert_eq!(full.paths, None);
+    assert_eq!(badges.next_query(start), None);
+    let entry = |path: &str, staged, unstaged| StatusEntry {
+        path: path.to_string(),
+        staged,
+        unstaged,
+    };
+    badges.finished(
+        full,
+        Ok(vec![
+            entry("src/app.rs", ' ', 'M'),
+            entry("new.txt", '?', '?'),
+        ]),
+    );
+    assert_eq!(badges.badge(&repo.join("src/app.rs")), Some('M'));
+    assert_eq!(badges.badge(&repo.join("src")), Some(CHANGED_BELOW));
+    assert_eq!(badges.badge(&repo.join("new.txt")), Some('?'));
+    assert_eq!(badges.badge(&repo.join("README")), None);
+    assert_eq!(badges.badge(Path::new("/elsewhere/src")), None);
+
+    // A save only asks about the saved file.
+    badges.changed(&repo.join("src/app.rs"));
+    badges.changed_all();
+    let saved = badges.next_query(start + Duration::from_secs(1)).unwrap();
+    assert_eq!(saved.paths, Some(vec!["src/app.rs".to_string()]));
+    badges.finished(saved, Ok(vec![entry("src/app.rs", 'M', ' ')]));
+    assert_eq!(badges.badge(&repo.join("src/app.rs")), Some('M'));
+
+    // The full run asked for waits until FULL_EVERY has passed.
+    assert_eq!(badges.next_query(start + Duration::from_secs(2)), None);
+    let full = badges.next_query(start + FULL_EVERY).unwrap();
+    assert_eq!(full.paths, None);
+    badges.finished(full, Ok(Vec::new()));
+    assert_eq!(badges.badge(&repo.join("src")), None);
+}
@@ -0,0 +1,931 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use ropey::{Rope, RopeSlice};
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete, UnicodeSegmentation};
+use unicode_width::UnicodeWidthStr;
+
+use crate::widgets::viewport::{Policy, Viewport};
+
+/// A zero-based line/column position in a buffer. Columns count chars, and
+/// the cursor's always sits at the start of a glyph: a grapheme cluster,
+/// such as a letter and its accents or an emoji sequence.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, col: usize) -> Self {
+        Position { line, col }
+    }
+}
+
+/// Bytes read at a time when a large file is streamed in.
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+/// How far a file being streamed into a buffer has got.
+#[derive(Debug, Clone, Copy)]
+struct Loading {
+    read: u64,
+    size: u64,
+    /// Where the cursor was sent before the text reached it.
+    goto: Option<Position>,
+}
+
+#[derive(Debug)]
+pub struct Buffer {
+    path: Option<PathBuf>,
+    /// Title of a buffer without a file, e.g. `commit 1a2b3c4d`.
+    name: Option<String>,
+    text: Rope,
+    cursor: Position,
+    readonly: bool,
+    dirty: bool,
+    /// Whether the file was there when last read or saved.
+    on_disk: bool,
+    /// The file was there but has since been deleted or moved, or its
+    /// directory removed. Saving then takes [`save_as`](Self::save_as).
+    orphaned: bool,
+    /// Too big for language servers, blame and spell checking.
+    large: bool,
+    loading: Option<Loading>,
+    /// Marks set in this buffer, moved along by edits before them.
+    marks: BTreeMap<char, Position>,
+    /// The lines the editor view shows.
+    pub view: Viewport,
+}
+
+impl Buffer {
+    /// Opens `path`, or starts an empty buffer for it if the file does not
+    /// exist yet.
+    pub fn open(path: &Path) -> Result<Self> {
+        let (text, on_disk) = match fs::File::open(path) {
+            Ok(file) => (Rope::from_reader(io::BufReader::new(file))?, true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Rope::new(), false),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Buffer {
+            path: Some(path.to_owned()),
+            on_disk,
+            ..Buffer::from_rope(text)
+        })
+    }
+
+    /// Reads the file again, dropping unsaved changes. The cursor stays put
+    /// as far as the new text allows. If the file has gone, the buffer is
+    /// orphaned instead and keeps its text.
+    pub fn reload(&mut self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => bail!("buffer has no file"),
+        };
+        self.check_on_disk();
+        if self.orphaned {
+            return Ok(());
+        }
+        let reloaded = Buffer::open(&path)?;
+        self.text = reloaded.text;
+        self.dirty = false;
+        self.goto(self.cursor);
+        Ok(())
+    }
+
+    /// An empty, read-only buffer for a large file of `size` bytes, whose
+    /// text is then streamed in with [`append_loaded`](Self::append_loaded).
+    pub fn loading(path: &Path, size: u64) -> Self {
+        Buffer {
+            path: Some(path.to_owned()),
+            readonly: true,
+            on_disk: true,
+            large: true,
+            loading: Some(Loading {
+                read: 0,
+                size,
+                goto: None,
+            }),
+            ..Buffer::from_rope(Rope::new())
+        }
+    }
+
+    /// Adds the next part of a file being streamed in.
+    pub fn append_loaded(&mut self, text: &str) {
+        self.text.insert(self.text.len_chars(), text);
+        if let Some(loading) = &mut self.loading {
+            loading.read += text.len() as u64;
+        }
+    }
+
+    /// Marks a streamed file as all read, moving the cursor where it was
+    /// sent meanwhile. The buffer stays read-only until told otherwise.
+    pub fn finish_loading(&mut self) {
+        if let Some(goto) = self.loading.take().and_then(|l| l.goto) {
+            self.goto(goto);
+        }
+    }
+
+    /// Bytes read so far and the file's size, while it is streamed in.
+    pub fn loading_progress(&self) -> Option<(u64, u64)> {
+        self.loading.map(|l| (l.read, l.size))
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.large
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        Buffer::from_rope(Rope::from_str(text))
+    }
+
+    /// An editable buffer without a file, for notes or a command's output,
+    /// until [`save_as`](Self::save_as) gives it one.
+    pub fn untitled(name: &str, text: &str) -> Self {
+        Buffer {
+            name: Some(name.to_string()),
+            ..Buffer::from_text(text)
+        }
+    }
+
+    /// A read-only buffer without a file, showing generated text.
+    pub fn scratch(name: &str, text: &str) -> Self {
+        Buffer {
+            name: Some(name.to_string()),
+            readonly: true,
+            ..Buffer::from_text(text)
+        }
+    }
+
+    fn from_rope(text: Rope) -> Self {
+        Buffer {
+            path: None,
+            name: None,
+            text,
+            cursor: Position::default(),
+            readonly: false,
+            dirty: false,
+            on_disk: false,
+            orphaned: false,
+            large: false,
+            loading: None,
+            marks: BTreeMap::new(),
+            view: Viewport::default(),
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn is_orphaned(&self) -> bool {
+        self.orphaned
+    }
+
+    /// Looks whether the file is still there: a buffer whose file has gone
+    /// is orphaned, and adopted again if it comes back. Returns whether
+    /// that changed.
+    pub fn check_on_disk(&mut self) -> bool {
+        let exists = match &self.path {
+            Some(path) if self.on_disk || self.orphaned => path.exists(),
+            _ => return false,
+        };
+        let orphaned = !exists;
+        if orphaned == self.orphaned {
+            return false;
+        }
+        self.orphaned = orphaned;
+        self.on_disk = exists;
+        true
+    }
+
+    pub fn title(&self) -> String {
+        let name = self
+            .path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .or_else(|| self.name.clone())
+            .unwrap_or_else(|| "[scratch]".to_string());
+        if self.dirty {
+            name + " +"
+        } else {
+            name
+        }
+    }
+
+    pub fn text(&self) -> &Rope {
+        &self.text
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// Sets whether the buffer can be edited. It can't while it's still
+    /// being streamed in.
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.readonly = readonly || self.loading.is_some();
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Inserts `text` at the cursor and moves the cursor past it.
+    pub fn insert(&mut self, text: &str) -> Result<()> {
+        if self.readonly {
+            bail!("buffer is read-only");
+        }
+        let idx = self.char_index(self.cursor);
+        self.shift_marks(self.cursor, self.cursor, text);
+        self.text.insert(idx, text);
+        self.cursor = self.position_at(idx + text.chars().count());
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Deletes up to `count` chars before the cursor, joining lines when it
+    /// crosses a line break.
+    pub fn delete_before(&mut self, count: usize) -> Result<()> {
+        if self.readonly {
+            bail!("buffer is read-only");
+        }
+        let end = self.char_index(self.cursor);
+        let start = end.saturating_sub(count);
+        if start == end {
+            return Ok(());
+        }
+        let from = self.position_at(start);
+        self.shift_marks(from, self.cursor, "");
+        self.text.remove(start..end);
+        self.cursor = from;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Replaces the text from `start` up to `end` with `text`. The cursor
+    /// keeps its position as far as the new text allows.
+    pub fn replace(&mut self, start: Position, end: Position, text: &str) -> Result<()> {
+        if self.readonly {
+            bail!("buffer is read-only");
+        }
+        let clamp = |pos: Position| {
+            let line = pos.line.min(self.line_count().saturating_sub(1));
+            Position::new(line, pos.col.min(self.line(line).len_chars()))
+        };
+        let (start, end) = (clamp(start), clamp(end).max(clamp(start)));
+        self.shift_marks(start, end, text);
+        let (start, end) = (self.char_index(start), self.char_index(end));
+        self.text.remove(start..end);
+        self.text.insert(start, text);
+        self.dirty = true;
+        self.goto(self.cursor);
+        Ok(())
+    }
+
+    /// Makes several replacements, given as ranges of the current text,
+    /// keeping the cursor on the text it was on.
+    pub fn apply_edits(&mut self, edits: &[(Position, Position, String)]) -> Result<()> {
+        let mut edits: Vec<_> = edits.iter().collect();
+        // Last first, so the ranges of the others stay put.
+        edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+        let mut cursor = self.cursor;
+        for (start, end, text) in edits {
+            self.replace(*start, *end, text)?;
+            cursor = shift(cursor, *start, *end, text);
+        }
+        self.goto(cursor);
+        Ok(())
+    }
+
+    /// Replaces the whole text, keeping the cursor where it can.
+    pub fn set_text(&mut self, text: &str) -> Result<()> {
+        if self.readonly {
+            bail!("buffer is read-only");
+        }
+        self.text = Rope::from_str(text);
+        self.dirty = true;
+        self.goto(self.cursor);
+        Ok(())
+    }
+
+    /// Writes the buffer to its file.
+    pub fn save(&mut self) -> Result<()> {
+        if self.readonly {
+            bail!("buffer is read-only");
+        }
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => bail!("buffer has no file name; :saveas <path> to save it"),
+        };
+        if self.orphaned {
+            bail!("the file was deleted or moved; :saveas <path> to save it");
+        }
+        self.write(&path)
+    }
+
+    /// Saves to `path`, and to there from now on.
+    pub fn save_as(&mut self, path: &Path) -> Result<()> {
+        if self.readonly {
+            bail!("buffer is read-only");
+        }
+        self.write(path)?;
+        self.path = Some(path.to_owned());
+        self.name = None;
+        Ok(())
+    }
+
+    /// Saves by piping the text into `tee` run by `helper`, such as `sudo`
+    /// or `pkexec`, for a file this user can't write. `helper` may carry
+    /// arguments of its own.
+    pub fn save_through(&mut self, helper: &str) -> Result<()> {
+        if self.readonly {
+            bail!("buffer is read-only");
+        }
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => bail!("buffer has no file name"),
+        };
+        let mut words = helper.split_whitespace();
+        let program = words.next().context("no program to save through")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .args(["tee", "--"])
+            .arg(&path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("could not run {}", program))?;
+        // A helper that fails early stops reading; its status says more
+        // than the broken pipe.
+        let mut stdin = io::BufWriter::new(child.stdin.take().context("no stdin")?);
+        let written = self
+            .text
+            .write_to(&mut stdin)
+            .and_then(|()| io::Write::flush(&mut stdin));
+        drop(stdin);
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.lines().find(|l| !l.trim().is_empty()) {
+                Some(reason) => bail!("{}: {}", program, reason.trim()),
+                None => bail!("{} failed ({})", program, output.status),
+            }
+        }
+        written?;
+        self.saved();
+        Ok(())
+    }
+
+    /// Writes the text beside `path` and moves it over the file, so that a
+    /// crash or a full disk partway through leaves the file as it was. The
+    /// new file gets the old one's permissions, and a link is written
+    /// through rather than replaced. Only a file in a directory that can't
+    /// take another is written in place.
+    fn write(&mut self, path: &Path) -> Result<()> {
+        let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        let temp = target.with_file_name(format!(".{}.wyv-save", name));
+        let text = &self.text;
+        let write_to = |file: fs::File| {
+            let mut file = io::BufWriter::new(file);
+            text.write_to(&mut file)?;
+            io::Write::flush(&mut file)?;
+            file.get_ref().sync_all()
+        };
+        let written = match fs::File::create(&temp) {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                fs::File::create(&target).and_then(write_to)
+            }
+            file => file
+                .and_then(write_to)
+                .and_then(|()| match fs::metadata(&target) {
+                    Ok(meta) => fs::set_permissions(&temp, meta.permissions()),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                    Err(e) => Err(e),
+                })
+                .and_then(|()| fs::rename(&temp, &target))
+                .inspect_err(|_| {
+                    let _ = fs::remove_file(&temp);
+                }),
+        };
+        written?;
+        self.saved();
+        Ok(())
+    }
+
+    fn saved(&mut self) {
+        self.dirty = false;
+        self.on_disk = true;
+        self.orphaned = false;
+    }
+
+    fn char_index(&self, pos: Position) -> usize {
+        self.text.line_to_char(pos.line) + pos.col
+    }
+
+    fn position_at(&self, idx: usize) -> Position {
+        let line = self.text.char_to_line(idx);
+        Position::new(line, idx - self.text.line_to_char(line))
+    }
+
+    /// Sets mark `name` at `pos`, in place of wherever it was.
+    pub fn set_mark(&mut self, name: char, pos: Position) {
+        self.marks.insert(name, pos);
+    }
+
+    pub fn remove_mark(&mut self, name: char) -> bool {
+        self.marks.remove(&name).is_some()
+    }
+
+    /// Where mark `name` is now, kept within the text once it's all read.
+    pub fn mark(&self, name: char) -> Option<Position> {
+        let pos = *self.marks.get(&name)?;
+        if self.loading.is_some() {
+            return Some(pos);
+        }
+        let line = pos.line.min(self.line_count().saturating_sub(1));
+        Some(Position::new(
+            line,
+            pos.col.min(self.line(line).len_chars()),
+        ))
+    }
+
+    /// The marks set in this buffer, in order of their names.
+    pub fn marks(&self) -> impl Iterator<Item = (char, Position)> + '_ {
+        self.marks
+            .keys()
+            .filter_map(|&name| Some((name, self.mark(name)?)))
+    }
+
+    /// Moves the marks past an edit replacing `start..end` with `text`.
+    fn shift_marks(&mut self, start: Position, end: Position, text: &str) {
+        for pos in self.marks.values_mut() {
+            *pos = shift(*pos, start, end, text);
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.text.len_lines()
+    }
+
+    /// Line `idx` without its trailing line break.
+    pub fn line(&self, idx: usize) -> RopeSlice<'_> {
+        let line = self.text.line(idx);
+        let mut end = line.len_chars();
+        while end > 0 && matches!(line.char(end - 1), '\n' | '\r') {
+            end -= 1;
+        }
+        line.slice(..end)
+    }
+
+    pub fn cursor(&self) -> Position {
+        self.cursor
+    }
+
+    /// Moves the cursor to `pos`, clamped to the buffer contents and moved
+    /// back to the start of the glyph it's in.
+    pub fn goto(&mut self, pos: Position) {
+        let line = pos.line.min(self.line_count().saturating_sub(1));
+        let text = self.line(line);
+        let col = pos.col.min(text.len_chars());
+        let col = match is_boundary(text, col) {
+            true => col,
+            false => prev_boundary(text, col),
+        };
+        self.cursor = Position::new(line, col);
+        if let Some(loading) = &mut self.loading {
+            loading.goto = Some(pos);
+        }
+    }
+
+    /// The start of the glyph before `pos` on its line, or `pos` at the
+    /// start of the line.
+    pub fn prev_glyph(&self, pos: Position) -> Position {
+        Position::new(pos.line, prev_boundary(self.line(pos.line), pos.col))
+    }
+
+    /// The start of the glyph after `pos` on its line, or the end of the
+    /// line.
+    pub fn next_glyph(&self, pos: Position) -> Position {
+        Position::new(pos.line, next_boundary(self.line(pos.line), pos.col))
+    }
+
+    /// How many columns the first `pos.col` chars of its line take on
+    /// screen.
+    pub fn display_col(&self, pos: Position) -> usize {
+        let line = self.line(pos.line);
+        let mut width = 0;
+        each_chunk(line.slice(..pos.col.min(line.len_chars())), |chunk| {
+            width += chunk.graphemes(true).map(glyph_width).sum::<usize>();
+        });
+        width
+    }
+
+    /// Moves the cursor to the start of the last line, not counting the
+    /// empty one after a final line break.
+    pub fn goto_last_line(&mut self) {
+        let mut line = self.line_count().saturating_sub(1);
+        if line > 0 && self.line(line).len_chars() == 0 {
+            line -= 1;
+        }
+        self.goto(Position::new(line, 0));
+    }
+
+    /// Scrolls so the cursor is visible in a view `height` lines tall.
+    pub fn scroll_to_cursor(&mut self, height: usize) {
+        let len = self.line_count();
+        self.view.resize(height, len);
+        self.view.scroll_into_view(self.cursor.line, len);
+    }
+
+    /// Puts the cursor in the middle of the view the next time it's drawn,
+    /// if it's out of view by then, as after a jump.
+    pub fn center_cursor(&mut self) {
+        self.view.set_policy(Policy::Center);
+    }
+}
+
+/// How many columns `glyph`, a grapheme cluster, takes on screen. Control
+/// chars such as tabs take none, as the terminal buffer leaves them out.
+pub fn glyph_width(glyph: &str) -> usize {
+    match glyph.contains(char::is_control) {
+        true => 0,
+        false => glyph.width(),
+    }
+}
+
+/// Calls `f` with each of `text`'s chunks in turn. Looked up by offset, as
+/// the rope's chunk iterator allocates for text across two.
+pub fn each_chunk(text: RopeSlice, mut f: impl FnMut(&str)) {
+    let mut byte = 0;
+    while byte < text.len_bytes() {
+        let (chunk, start, _, _) = text.chunk_at_byte(byte);
+        let chunk = &chunk[byte - start..];
+        f(chunk);
+        byte += chunk.len();
+    }
+}
+
+/// The char index of the glyph boundary before char `col` of `text`.
+fn prev_boundary(text: RopeSlice, col: usize) -> usize {
+    let byte = text.char_to_byte(col);
+    let (mut chunk, mut chunk_start, _, _) = text.chunk_at_byte(byte);
+    let mut cursor = GraphemeCursor::new(byte, text.len_bytes(), true);
+    loop {
+        match cursor.prev_boundary(chunk, chunk_start) {
+            Ok(None) => return 0,
+            Ok(Some(byte)) => return text.byte_to_char(byte),
+            Err(GraphemeIncomplete::PrevChunk) => {
+                (chunk, chunk_start, _, _) = text.chunk_at_byte(chunk_start - 1);
+            }
+            Err(GraphemeIncomplete::PreContext(end)) => {
+                let context = text.chunk_at_byte(end - 1).0;
+                cursor.provide_context(context, end - context.len());
+            }
+            Err(_) => unreachable!("only earlier text is asked for"),
+        }
+    }
+}
+
+/// The char index of the glyph boundary after char `col` of `text`.
+fn next_boundary(text: RopeSlice, col: usize) -> usize {
+    let byte = text.char_to_byte(col);
+    let (mut chunk, mut chunk_start, _, _) = text.chunk_at_byte(byte);
+    let mut cursor = GraphemeCursor::new(byte, text.len_bytes(), true);
+    loop {
+        match cursor.next_boundary(chunk, chunk_start) {
+            Ok(None) => return text.len_chars(),
+            Ok(Some(byte)) => return text.byte_to_char(byte),
+            Err(GraphemeIncomplete::NextChunk) => {
+                chunk_start += chunk.len();
+                chunk = text.chunk_at_byte(chunk_start).0;
+            }
+            Err(GraphemeIncomplete::PreContext(end)) => {
+                let context = text.chunk_at_byte(end - 1).0;
+                cursor.provide_context(context, end - context.len());
+            }
+            Err(_) => unreachable!("only neighbouring text is asked for"),
+        }
+    }
+}
+
+/// Whether char `col` of `text` starts a glyph, or is its end.
+fn is_boundary(text: RopeSlice, col: usize) -> bool {
+    let byte = text.char_to_byte(col);
+    let (chunk, chunk_start, _, _) = text.chunk_at_byte(byte);
+    let mut cursor = GraphemeCursor::new(byte, text.len_bytes(), true);
+    loop {
+        match cursor.is_boundary(chunk, chunk_start) {
+            Ok(boundary) => return boundary,
+            Err(GraphemeIncomplete::PreContext(end)) => {
+                let context = text.chunk_at_byte(end - 1).0;
+                cursor.provide_context(context, end - context.len());
+            }
+            Err(_) => unreachable!("only earlier text is asked for"),
+        }
+    }
+}
+
+/// Reads `reader` in parts of up to [`CHUNK_SIZE`] bytes, handing each to
+/// `chunk` as text. Parts end between chars; invalid UTF-8 is replaced.
+/// Stops early if `chunk` returns `false`.
+pub fn read_chunks(
+    mut reader: impl io::Read,
+    mut chunk: impl FnMut(&str) -> bool,
+) -> io::Result<()> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    // Bytes of a char cut off at the end of the last read.
+    let mut pending = 0;
+    loop {
+        let read = match reader.read(&mut buf[pending..]) {
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let end = pending + read;
+        let whole = match std::str::from_utf8(&buf[..end]) {
+            Err(e) if read > 0 && e.error_len().is_none() => e.valid_up_to(),
+            _ => end,
+        };
+        if whole > 0 && !chunk(&String::from_utf8_lossy(&buf[..whole])) {
+            return Ok(());
+        }
+        if read == 0 {
+            return Ok(());
+        }
+        buf.copy_within(whole..end, 0);
+        pending = end - whole;
+    }
+}
+
+#[test]
+fn test_goto_clamps_to_contents() {
+    let mut buffer = Buffer::from_text("first\nsecond line\n");
+    buffer.goto(Position::new(1, 3));
+    assert_eq!(buffer.cursor(), Position::new(1, 3));
+
+    buffer.goto(Position::new(1, 99));
+    assert_eq!(buffer.cursor(), Position::new(1, 11));
+
+    // The empty line after the final newline is still a valid position.
+    buffer.goto(Position::new(99, 0));
+    assert_eq!(buffer.cursor(), Position::new(2, 0));
+    assert_eq!(buffer.line(0).to_string(), "first");
+}
+
+#[test]
+fn test_insert_marks_dirty_until_saved() {
+    let path = std::env::temp_dir().join(format!("wyv-save-test-{}.txt", std::process::id()));
+    let mut buffer = Buffer::open(&path).unwrap();
+    buffer.insert("one\ntwo").unwrap();
+    assert!(buffer.is_dirty());
+    assert_eq!(buffer.cursor(), Position::new(1, 3));
+    assert_eq!(
+        buffer.title(),
+        format!("{} +", path.file_name().unwrap().to_string_lossy())
+    );
+
+    buffer.save().unwrap();
+    assert!(!buffer.is_dirty());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo");
+    fs::remove_file(&path).unwrap();
+
+    buffer.set_readonly(true);
+    assert!(buffer.insert("x").is_err());
+    assert!(Buffer::from_text("").save().is_err());
+}
+
+#[test]
+fn test_buffers_whose_file_goes_are_orphaned_until_saved_as() {
+    let dir = std::env::temp_dir().join(format!("wyv-orphan-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (path, moved) = (dir.join("a.txt"), dir.join("b.txt"));
+    fs::write(&path, "kept").unwrap();
+    let mut buffer = Buffer::open(&path).unwrap();
+    assert!(!buffer.check_on_disk());
+
+    fs::rename(&path, &moved).unwrap();
+    buffer.reload().unwrap();
+    assert!(buffer.is_orphaned());
+    assert_eq!(buffer.text().to_string(), "kept");
+    assert!(buffer.save().is_err());
+    assert!(!path.exists());
+
+    // It's adopted again if the file comes back.
+    fs::rename(&moved, &path).unwrap();
+    assert!(buffer.check_on_disk());
+    assert!(!buffer.is_orphaned());
+
+    fs::remove_file(&path).unwrap();
+    assert!(buffer.check_on_disk());
+    buffer.save_as(&moved).unwrap();
+    assert!(!buffer.is_orphaned());
+    assert_eq!(buffer.path(), Some(moved.as_path()));
+    assert_eq!(fs::read_to_string(&moved).unwrap(), "kept");
+    fs::remove_dir_all(dir).unwrap();
+
+    // A buffer for a file not yet written is no orphan.
+    let mut new = Buffer::open(&path).unwrap();
+    assert!(!new.check_on_disk());
+    assert!(!new.is_orphaned());
+}
+
+#[cfg(unix)]
+#[cfg(unix)]
+#[test]
+fn test_saves_replace_the_file_whole_and_keep_its_mode_and_links() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let dir = std::env::temp_dir().join(format!("wyv-save-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (path, link) = (dir.join("run.sh"), dir.join("link.sh"));
+    fs::write(&path, "old").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+    symlink(&path, &link).unwrap();
+
+    let mut buffer = Buffer::open(&link).unwrap();
+    buffer.insert("new ").unwrap();
+    buffer.save().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new old");
+    assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o750);
+    // Nothing's left beside it.
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_saving_through_a_helper_pipes_into_tee() {
+    let path = std::env::temp_dir().join(format!("wyv-tee-test-{}.txt", std::process::id()));
+    let mut buffer = Buffer::open(&path).unwrap();
+    buffer.insert("through tee\n").unwrap();
+    buffer.save_through("env").unwrap();
+    assert!(!buffer.is_dirty());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "through tee\n");
+    fs::remove_file(&path).unwrap();
+
+    let error = buffer.save_through("false").unwrap_err();
+    assert!(error.to_string().starts_with("false failed"), "{}", error);
+    assert!(buffer.save_through("no-such-helper-for-wyv").is_err());
+}
+
+#[test]
+fn test_delete_before_joins_lines() {
+    let mut buffer = Buffer::from_text("ab\ncd");
+    buffer.goto(Position::new(1, 1));
+    buffer.delete_before(2).unwrap();
+    assert_eq!(buffer.text().to_string(), "abd");
+    assert_eq!(buffer.cursor(), Position::new(0, 2));
+    buffer.delete_before(5).unwrap();
+    assert_eq!(buffer.text().to_string(), "d");
+    assert_eq!(buffer.cursor(), Position::new(0, 0));
+}
+
+/// Where `pos` ends up once `start..end` is replaced by `text`.
+fn shift(pos: Position, start: Position, end: Position, text: &str) -> Position {
+    if pos < start {
+        return pos;
+    }
+    let lines = text.matches('\n').count();
+    let last = text.rsplit('\n').next().unwrap_or_default().chars().count();
+    if pos < end {
+        // Inside the replaced text: keep to the same line of it if there is one.
+        return Position::new(pos.line.min(start.line + lines), pos.col);
+    }
+    let new_end = match lines {
+        0 => Position::new(start.line, start.col + last),
+        _ => Position::new(start.line + lines, last),
+    };
+    match pos.line == end.line {
+        true => Position::new(new_end.line, pos.col - end.col + new_end.col),
+        false => Position::new(pos.line - end.line + new_end.line, pos.col),
+    }
+}
+
+#[test]
+fn test_replace_range() {
+    let mut buffer = Buffer::from_text("let foo = foo + 1;\n");
+    buffer
+        .replace(Position::new(0, 10), Position::new(0, 13), "bar")
+        .unwrap();
+    buffer
+        .replace(Position::new(0, 4), Position::new(0, 7), "bar")
+        .unwrap();
+    assert_eq!(buffer.text().to_string(), "let bar = bar + 1;\n");
+    buffer
+        .replace(Position::new(0, 99), Position::new(0, 99), "!")
+        .unwrap();
+    assert_eq!(buffer.text().to_string(), "let bar = bar + 1;!\n");
+}
+
+#[test]
+fn test_apply_edits_keeps_cursor_on_its_text() {
+    let mut buffer = Buffer::from_text("a\nb\nc x\n");
+    buffer.goto(Position::new(2, 2));
+    let edits = [
+        (
+            Position::new(0, 0),
+            Position::new(1, 0),
+            "a1\na2\n".to_string(),
+        ),
+        (Position::new(2, 0), Position::new(2, 1), "cc".to_string()),
+    ];
+    buffer.apply_edits(&edits).unwrap();
+    assert_eq!(buffer.text().to_string(), "a1\na2\nb\ncc x\n");
+    assert_eq!(buffer.cursor(), Position::new(3, 3));
+}
+
+#[test]
+fn test_marks_move_with_edits_before_them() {
+    let mut buffer = Buffer::from_text("fn main() {\n    run();\n}\n");
+    buffer.set_mark('a', Position::new(1, 4));
+    buffer.set_mark('b', Position::new(0, 0));
+    buffer.insert("// entry\n").unwrap();
+    assert_eq!(buffer.mark('a'), Some(Position::new(2, 4)));
+    assert_eq!(buffer.mark('b'), Some(Position::new(1, 0)));
+
+    buffer.goto(Position::new(2, 4));
+    buffer.delete_before(4).unwrap();
+    assert_eq!(buffer.mark('a'), Some(Position::new(2, 0)));
+    buffer
+        .replace(Position::new(0, 0), Position::new(1, 0), "")
+        .unwrap();
+    assert_eq!(
+        buffer.marks().collect::<Vec<_>>(),
+        [('a', Position::new(1, 0)), ('b', Position::new(0, 0)),]
+    );
+
+    // Marks past the end of new text stay within it.
+    buffer.set_text("x").unwrap();
+    assert_eq!(buffer.mark('a'), Some(Position::new(0, 0)));
+    assert!(buffer.remove_mark('a'));
+    assert_eq!(buffer.mark('a'), None);
+}
+
+#[test]
+fn test_large_files_stream_in_between_chars() {
+    // Hands out one byte per read, so chars arrive cut in half.
+    struct Trickle<'a>(&'a [u8]);
+    impl io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let text = "héllo\nwörld ✓\n";
+    let mut buffer = Buffer::loading(Path::new("big.log"), text.len() as u64);
+    assert!(buffer.readonly() && buffer.is_large());
+    buffer.goto(Position::new(1, 2));
+    read_chunks(Trickle(text.as_bytes()), |chunk| {
+        buffer.append_loaded(chunk);
+        true
+    })
+    .unwrap();
+    assert_eq!(buffer.loading_progress(), Some((18, 18)));
+    buffer.finish_loading();
+    assert_eq!(buffer.text().to_string(), text);
+    assert_eq!(buffer.cursor(), Position::new(1, 2));
+    assert_eq!(buffer.loading_progress(), None);
+
+    let mut parts = Vec::new();
+    read_chunks(&b"ok \xff"[..], |chunk| {
+        parts.push(chunk.to_string());
+        true
+    })
+    .unwrap();
+    assert_eq!(parts, ["ok \u{fffd}"]);
+}
+
+#[test]
+fn test_cursor_moves_by_glyph_and_measures_columns() {
+    // A wide char, a family emoji of five chars and an accent of its own.
+    let mut buffer = Buffer::from_text("a漢👨‍👩‍👧e\u{301}b\n");
+    let mut pos = Position::new(0, 0);
+    let mut stops = vec![(pos.col, buffer.display_col(pos))];
+    while buffer.next_glyph(pos) != pos {
+        pos = buffer.next_glyph(pos);
+        stops.push((pos.col, buffer.display_col(pos)));
+    }
+    assert_eq!(stops, [(0, 0), (1, 1), (2, 3), (7, 5), (9, 6), (10, 7)]);
+    assert_eq!(buffer.prev_glyph(Position::new(0, 7)).col, 2);
+    assert_eq!(buffer.prev_glyph(Position::new(0, 0)).col, 0);
+
+    // Up and down can land inside a glyph; the cursor goes to its start.
+    buffer.goto(Position::new(0, 4));
+    assert_eq!(buffer.cursor(), Position::new(0, 2));
+    buffer.goto(Position::new(0, 8));
+    assert_eq!(buffer.cursor(), Position::new(0, 7));
+}
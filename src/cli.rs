@@ -0,0 +1,205 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+
+use crate::{buffer::Position, paths, ssh};
+
+#[derive(Debug, Default, Parser)]
+#[command(name = "wyv", version, about = "A terminal file tree and editor")]
+pub struct Args {
+    /// Files to open, or a directory to root the tree at, which may be on
+    /// another host as `ssh://user@host/path`. `+LINE` before a file jumps
+    /// to that line; `FILE:LINE:COL` is accepted too. `-` reads what's piped
+    /// in into a read-only buffer.
+    #[arg(value_name = "PATH")]
+    pub paths: Vec<String>,
+
+    /// Use this config file instead of the default one.
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Theme to use, overriding the config.
+    #[arg(long, value_name = "NAME")]
+    pub theme: Option<String>,
+
+    /// Draw without colours, in bold, underline and reverse only. Setting
+    /// `NO_COLOR` does the same.
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Open all files read-only.
+    #[arg(long)]
+    pub readonly: bool,
+
+    /// Work through a short lesson in moving around and editing.
+    #[arg(long)]
+    pub tutor: bool,
+
+    /// Print the active buffer to stdout on exit, for use as a filter. The
+    /// buffer read from `-` can then be edited.
+    #[arg(long)]
+    pub stdout: bool,
+
+    /// Listen on the control socket so `--remote` can reach this instance.
+    #[arg(long, hide = !cfg!(feature = "remote"))]
+    pub daemon: bool,
+
+    /// Send a request to the running `--daemon` instance and exit: `state`,
+    /// `open FILE..`, `keys SCRIPT` or a `:` command. `open` starts a new
+    /// instance if none is running.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        num_args = 1..,
+        allow_hyphen_values = true,
+        hide = !cfg!(feature = "remote")
+    )]
+    pub remote: Option<Vec<String>>,
+
+    /// Control socket to listen on or connect to.
+    #[arg(long, value_name = "PATH", hide = !cfg!(feature = "remote"))]
+    pub socket: Option<PathBuf>,
+}
+
+/// A file named on the command line, with the position to jump to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OpenTarget {
+    pub path: PathBuf,
+    pub position: Option<Position>,
+}
+
+impl Args {
+    /// The directory the tree should be rooted at: the first directory
+    /// argument, or the current directory, [normalized](paths::normalize).
+    pub fn root(&self) -> PathBuf {
+        let root = self
+            .paths
+            .iter()
+            .map(Path::new)
+            .find(|p| p.is_dir())
+            .unwrap_or(Path::new("."));
+        paths::normalize(root)
+    }
+
+    /// Whether to draw without colours: `--no-color`, or `NO_COLOR` set to
+    /// anything but nothing, as <https://no-color.org> has it.
+    pub fn monochrome(&self) -> bool {
+        self.no_color || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+    }
+
+    /// Whether `-` asks for stdin to be read into a buffer.
+    pub fn reads_stdin(&self) -> bool {
+        self.paths.iter().any(|p| p == "-")
+    }
+
+    /// The remote directory to root the tree at, if one is given.
+    pub fn ssh_url(&self) -> Option<&str> {
+        self.paths
+            .iter()
+            .map(String::as_str)
+            .find(|p| ssh::is_url(p))
+    }
+
+    /// The files to open, in argument order, their paths
+    /// [normalized](paths::normalize).
+    pub fn files(&self) -> Vec<OpenTarget> {
+        let mut files = Vec::new();
+        let mut pending_line = None;
+        for arg in &self.paths {
+            if let Some(line) = arg.strip_prefix('+') {
+                // A bare `+` jumps to the end, like vim.
+                pending_line = Some(line.parse::<usize>().unwrap_or(usize::MAX));
+                continue;
+            }
+            if arg == "-" || Path::new(arg).is_dir() || ssh::is_url(arg) {
+                continue;
+            }
+
+            let mut target = parse_target(arg);
+            target.path = paths::normalize(&target.path);
+            if let Some(line) = pending_line.take() {
+                target.position = Some(Position::new(line.saturating_sub(1), 0));
+            }
+            files.push(target);
+        }
+        files
+    }
+}
+
+/// Splits an optional `:line[:col]` suffix off `arg`. Paths that exist as
+/// written are taken literally, so files with colons in their names open.
+pub fn parse_target(arg: &str) -> OpenTarget {
+    let literal = OpenTarget {
+        path: PathBuf::from(arg),
+        position: None,
+    };
+    if Path::new(arg).exists() {
+        return literal;
+    }
+
+    let (path, line, col) = match split_number(arg) {
+        Some((head, last)) => match split_number(head) {
+            Some((path, line)) => (path, line, Some(last)),
+            None => (head, last, None),
+        },
+        None => return literal,
+    };
+
+    OpenTarget {
+        path: PathBuf::from(path),
+        position: Some(Position::new(line - 1, col.map_or(0, |c| c - 1))),
+    }
+}
+
+/// Splits a trailing `:N` off `s`, where N is a positive number.
+fn split_number(s: &str) -> Option<(&str, usize)> {
+    let (head, n) = s.rsplit_once(':')?;
+    let n = n.parse::<usize>().ok().filter(|n| *n > 0)?;
+    Some((head, n))
+}
+
+#[test]
+fn test_file_positions() {
+    let args = Args::parse_from([
+        "wyv",
+        "src",
+        "missing.rs:12:5",
+        "+3",
+        "other.rs",
+        "notes.txt:7",
+        "plain.txt",
+        "a:b:7",
+    ]);
+    assert_eq!(args.root(), paths::normalize(Path::new("src")));
+
+    let target = |path: &str, position| OpenTarget {
+        path: paths::normalize(Path::new(path)),
+        position,
+    };
+    assert_eq!(
+        args.files(),
+        vec![
+            target("missing.rs", Some(Position::new(11, 4))),
+            target("other.rs", Some(Position::new(2, 0))),
+            target("notes.txt", Some(Position::new(6, 0))),
+            target("plain.txt", None),
+            target("a:b", Some(Position::new(6, 0))),
+        ]
+    );
+}
+
+#[test]
+fn test_stdin_is_not_a_file() {
+    let args = Args::parse_from(["wyv", "-", "--stdout"]);
+    assert!(args.reads_stdin() && args.stdout);
+    assert!(args.files().is_empty());
+    assert!(!Args::parse_from(["wyv", "notes.txt"]).reads_stdin());
+}
+
+#[test]
+fn test_no_color_flag() {
+    assert!(Args::parse_from(["wyv", "--no-color"]).monochrome());
+}
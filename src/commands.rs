@@ -0,0 +1,599 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+use crate::{
+    app::{App, Panel},
+    buffer::Buffer,
+    cli, config, features, keymaps, marks,
+    shell::{self, LineRange},
+    widgets::file_tree::FileTree,
+};
+
+/// Runs a line typed at the `:` prompt.
+pub fn execute(app: &mut App, line: &str) -> Result<()> {
+    let mut words = line.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    tracing::debug!(command = line, "running command");
+
+    if let Some(command) = line.trim_start().strip_prefix('!') {
+        return app.run_shell(command.trim());
+    }
+    let filter = app.buffer().and_then(|buffer| {
+        let lines = line_count(buffer);
+        let (range, command) = shell::parse_filter(line, buffer.cursor().line, lines)?;
+        Some((range, command.to_string()))
+    });
+    if let Some((range, command)) = filter {
+        return app.filter_lines(range, &command);
+    }
+    if let Some(feature) = needs(name) {
+        features::require(feature)?;
+    }
+
+    match name {
+        "q" | "quit" => app.quit(false),
+        "q!" | "quit!" => app.quit(true),
+        "w" | "write" => match app.active {
+            Some(idx) => app.write(idx)?,
+            None => bail!("no buffer to save"),
+        },
+        "wa" | "wall" => app.save_all()?,
+        "sudow" | "sudowrite" | "w!!" => match app.active {
+            Some(idx) => app.elevated_save = Some(idx),
+            None => bail!("no buffer to save"),
+        },
+        // `:saveas!` creates the directories the path names.
+        "sav" | "saveas" | "sav!" | "saveas!" => {
+            // The path is the rest of the line, spaces and all.
+            let path = line.trim_start()[name.len()..].trim();
+            match (app.active, path) {
+                (None, _) => bail!("no buffer to save"),
+                (Some(_), "") => bail!("saveas needs a path"),
+                (Some(idx), path) => app.save_as(idx, Path::new(path), name.ends_with('!'))?,
+            }
+        }
+        "new" | "enew" => {
+            // `:new !cmd` fills the buffer with what `cmd` prints.
+            let rest = line.trim_start()[name.len()..].trim_start();
+            let text = match rest.strip_prefix('!') {
+                Some(command) => shell::filter(command.trim(), app.tree.root(), "")?,
+                None => String::new(),
+            };
+            app.new_scratch(&text);
+        }
+        "sus" | "suspend" | "stop" => app.suspend = true,
+        "log" => app.toggle_panel(Panel::Log),
+        "jobs" => app.toggle_panel(Panel::Jobs),
+        "cancel" => match words.next() {
+            Some(id) => {
+                let id = id.parse()?;
+                if !app.jobs.cancel(id) {
+                    bail!("no job {}", id);
+                }
+            }
+            None => app.jobs.cancel_all(),
+        },
+        "plugins" => app.toggle_panel(Panel::Plugins),
+        "perf" => app.show_perf = !app.show_perf,
+        "trust" => app.trust_project()?,
+        "blame" => app.toggle_blame_pane()?,
+        "git" => app.open_git_status()?,
+        "gitlog" => app.open_git_log(false)?,
+        "history" => app.open_git_log(true)?,
+        "branches" => app.open_branches()?,
+        "merge" => app.open_merge()?,
+        "diff" => match (words.next(), words.next()) {
+            (None, _) => app.diff_with_head()?,
+            (Some(right), None) => app.diff_files(None, Path::new(right))?,
+            (Some(left), Some(right)) => app.diff_files(Some(Path::new(left)), Path::new(right))?,
+        },
+        "compare" => match (words.next(), words.next()) {
+            (None, _) => app.compare_marked()?,
+            (Some(_), None) => bail!("compare needs two files, or none to compare the marked ones"),
+            (Some(left), Some(right)) => app.diff_files(Some(Path::new(left)), Path::new(right))?,
+        },
+        "branch" => match words.next() {
+            Some(name) => app.create_branch(name)?,
+            None => app.open_branches()?,
+        },
+        "definition" => app.goto_definition()?,
+        "references" => app.find_references()?,
+        "hover" => app.show_hover()?,
+        "format" | "fmt" => match app.active {
+            Some(idx) => {
+                app.format(idx, false)?;
+            }
+            None => bail!("no buffer to format"),
+        },
+        "locations" | "copen" => app.open_locations(),
+        "cnext" | "cn" => app.step_location(false)?,
+        "cprev" | "cp" => app.step_location(true)?,
+        "diagnostics" => app.open_diagnostics()?,
+        "lint" => {
+            let path = match app.buffer().and_then(|b| b.path()) {
+                Some(path) => path.to_owned(),
+                None => bail!("no file to lint"),
+            };
+            app.lint(&path)?;
+        }
+        "outline" => app.toggle_outline(),
+        "preview" => app.toggle_preview()?,
+        "structure" => app.open_structure()?,
+        "ssh" => match words.next() {
+            Some(url) => app.connect(url)?,
+            None => bail!("ssh needs a URL such as ssh://user@host/path"),
+        },
+        "follow" | "tail" => {
+            if let Some(path) = words.next() {
+                app.open(&app.tree.root().join(path), None)?;
+            }
+            app.follow()?
+        }
+        "hex" => match words.next() {
+            Some(path) => app.open_hex(&app.tree.root().join(path))?,
+            None => {
+                let buffer = app
+                    .buffer()
+                    .ok_or_else(|| anyhow!("no buffer to show in hex"))?;
+                let path = buffer
+                    .path()
+                    .ok_or_else(|| anyhow!("{} isn't a file", buffer.title()))?
+                    .to_path_buf();
+                app.open_hex(&path)?
+            }
+        },
+        "symbols" => app.open_symbols(false)?,
+        "wsymbols" | "workspace-symbols" => app.open_symbols(true)?,
+        "spell" => app.suggest_spelling()?,
+        "spellgood" => match words.next() {
+            Some(word) => app.add_spelling(word)?,
+            None => bail!("spellgood needs a word"),
+        },
+        "back" => app.jump_back()?,
+        "mark" | "k" => {
+            let mut name = words.next().unwrap_or_default().chars();
+            match (name.next(), name.next()) {
+                (Some(name), None) if marks::is_valid(name) => app.set_mark(name)?,
+                _ => bail!(marks::NAMING),
+            }
+        }
+        "marks" => app.open_marks()?,
+        "recent" => app.open_recent()?,
+        "project" => match words.next() {
+            Some(root) => app.switch_project(Path::new(root))?,
+            None => app.open_projects()?,
+        },
+        "projects" => app.open_projects()?,
+        "keymap" => match (words.next(), words.next()) {
+            (Some("export"), Some(path)) => app.export_keymap(Path::new(path))?,
+            (Some("import"), Some(profile)) => app.import_keymap(profile)?,
+            (Some("export"), None) => bail!("keymap export needs a file to write"),
+            (Some("import"), None) => bail!(
+                "keymap import needs a profile: {} or a file",
+                keymaps::names().join(", ")
+            ),
+            _ => bail!("keymap export <file> or keymap import <profile>"),
+        },
+        "tutor" => {
+            let dir = config::state_dir().unwrap_or_else(std::env::temp_dir);
+            app.start_tutor(&dir)?
+        }
+        "delm" | "delmarks" => app.delete_marks(&words.collect::<String>())?,
+        "rename" => match words.next() {
+            Some(name) => app.rename_symbol(name)?,
+            None => bail!("rename needs a new name"),
+        },
+        "task" => match words.next() {
+            Some(name) => app.run_task(name)?,
+            None => {
+                let names: Vec<String> = app.tasks().into_iter().map(|t| t.name).collect();
+                app.status = Some(match names.is_empty() {
+                    true => "No tasks; add [[tasks]] to the config".to_string(),
+                    false => format!("Tasks: {}", names.join(" ")),
+                });
+            }
+        },
+        "tasks" => app.toggle_panel(Panel::Tasks),
+        "problems" => app.open_problems()?,
+        "terminal" | "term" => match words.next() {
+            Some(position) => {
+                app.set_option("terminal.position", Value::String(position.to_string()))?;
+                app.show_terminal()?;
+            }
+            None => app.toggle_terminal()?,
+        },
+        "grep" | "search" => {
+            // The pattern is the rest of the line, spacing and all.
+            let pattern = line.trim_start()[name.len()..].trim_start();
+            app.open_search(Some(pattern).filter(|p| !p.is_empty()));
+        }
+        "replace" => {
+            let replacement = line.trim_start()[name.len()..].trim_start();
+            app.preview_replace(replacement)?;
+        }
+        "open" => match words.next() {
+            Some(file) => {
+                let target = cli::parse_target(file);
+                let path = app.tree.root().join(&target.path);
+                app.open(&path, target.position)?;
+            }
+            None => bail!("open needs a file, e.g. src/main.rs:12"),
+        },
+        "send" => {
+            let buffer = app
+                .buffer()
+                .ok_or_else(|| anyhow!("no buffer to send from"))?;
+            let cursor = buffer.cursor().line;
+            let range = match words.next() {
+                Some(range) => shell::parse_range(range, cursor, line_count(buffer))
+                    .ok_or_else(|| anyhow!("invalid range `{}`", range))?,
+                None => LineRange {
+                    first: cursor,
+                    last: cursor,
+                },
+            };
+            app.send_lines(range)?;
+        }
+        "send-path" => app.send_path()?,
+        "review" => app.open_review(words.next())?,
+        "note" => {
+            let text = line.trim_start()[name.len()..].trim();
+            app.add_review_note(text)?;
+        }
+        "review-report" => app.write_review_report(words.next().map(Path::new))?,
+        "commit" => {
+            // The message is the rest of the line, spacing and all.
+            let message = line.trim_start()[name.len()..].trim();
+            app.commit(message)?;
+        }
+        "set" | "set?" => {
+            let args: Vec<&str> = words.collect();
+            app.status = Some(set(app, name == "set?", &args.join(" "))?);
+        }
+        _ => {
+            let args: Vec<&str> = words.collect();
+            if let Some(scripts) = &app.scripts {
+                if scripts.run_command(name, &args)? {
+                    app.apply_script_effects();
+                    return Ok(());
+                }
+            }
+            if !app.plugins.run_command(name, &args) {
+                bail!("unknown command `{}`", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lines in `buffer`, not counting the empty one after a final line break.
+fn line_count(buffer: &Buffer) -> usize {
+    let text = buffer.text();
+    match text.len_chars().checked_sub(1).map(|i| text.char(i)) {
+        Some('\n') => text.len_lines() - 1,
+        _ => text.len_lines(),
+    }
+}
+
+/// `:set name=value` (or `name value`) changes an option, `:set name` turns
+/// a flag on and `:set noname` off. `:set? name` shows an option's value and
+/// where it comes from; with no name, every option not at its default.
+fn set(app: &mut App, inspect: bool, args: &str) -> Result<String> {
+    let describe = |app: &App, name: &str| -> Result<String> {
+        let key = config::resolve_option(name);
+        let value = app
+            .config
+            .get(key)
+            .ok_or_else(|| anyhow!("unknown option `{}`", name))?;
+        Ok(format!("{} = {} ({})", key, value, app.option_source(key)))
+    };
+
+    if args.is_empty() {
+        let changed: Vec<String> = config::option_names()
+            .iter()
+            .filter(|n| config::resolve_option(n) == n.as_str())
+            .filter(|n| app.option_source(n) != "default")
+            .map(|n| describe(app, n))
+            .collect::<Result<_>>()?;
+        return Ok(match changed.is_empty() {
+            true => "All options are at their defaults".to_string(),
+            false => changed.join(", "),
+        });
+    }
+    if inspect {
+        let described: Vec<String> = args
+            .split_whitespace()
+            .map(|n| describe(app, n))
+            .collect::<Result<_>>()?;
+        return Ok(described.join(", "));
+    }
+
+    let (name, value) = match args.split_once('=').or_else(|| args.split_once(' ')) {
+        Some((name, value)) => {
+            let value = value.trim();
+            let parsed =
+                serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+            (name.trim(), parsed)
+        }
+        None => {
+            let is_flag = |name: &str| {
+                app.config
+                    .get(config::resolve_option(name))
+                    .is_some_and(|v| v.is_boolean())
+            };
+            match args.strip_prefix("no") {
+                Some(flag) if !is_flag(args) && is_flag(flag) => (flag, Value::Bool(false)),
+                _ if is_flag(args) => (args, Value::Bool(true)),
+                _ => return describe(app, args),
+            }
+        }
+    };
+    app.set_option(name, value)?;
+    describe(app, name)
+}
+
+/// The commands `execute` knows, by their long names, for completing the
+/// first word of a line, each with the feature it needs, if any.
+const COMMANDS: &[(&str, Option<&str>)] = &[
+    ("back", None),
+    ("blame", Some("git")),
+    ("branch", Some("git")),
+    ("branches", Some("git")),
+    ("cancel", None),
+    ("cnext", None),
+    ("commit", Some("git")),
+    ("compare", None),
+    ("copen", None),
+    ("cprev", None),
+    ("definition", Some("lsp")),
+    ("delmarks", None),
+    ("diagnostics", None),
+    ("diff", None),
+    ("enew", None),
+    ("follow", None),
+    ("format", None),
+    ("git", Some("git")),
+    ("gitlog", Some("git")),
+    ("grep", None),
+    ("hex", None),
+    ("history", Some("git")),
+    ("hover", Some("lsp")),
+    ("jobs", None),
+    ("keymap", None),
+    ("lint", None),
+    ("locations", None),
+    ("log", None),
+    ("mark", None),
+    ("marks", None),
+    ("merge", None),
+    ("new", None),
+    ("note", None),
+    ("open", None),
+    ("outline", None),
+    ("perf", None),
+    ("plugins", None),
+    ("preview", None),
+    ("problems", None),
+    ("project", None),
+    ("projects", None),
+    ("quit", None),
+    ("recent", None),
+    ("references", Some("lsp")),
+    ("rename", Some("lsp")),
+    ("replace", None),
+    ("review", Some("git")),
+    ("review-report", Some("git")),
+    ("saveas", None),
+    ("search", None),
+    ("send", None),
+    ("send-path", None),
+    ("set", None),
+    ("spell", None),
+    ("spellgood", None),
+    ("ssh", Some("ssh")),
+    ("structure", None),
+    ("sudowrite", None),
+    ("suspend", None),
+    ("symbols", None),
+    ("tail", None),
+    ("task", None),
+    ("tasks", None),
+    ("terminal", Some("terminal")),
+    ("trust", None),
+    ("tutor", None),
+    ("wall", None),
+    ("workspace-symbols", None),
+    ("write", None),
+    ("wsymbols", None),
+];
+
+/// The feature command `name`, or the one it's short for, needs, if any.
+fn needs(name: &str) -> Option<&'static str> {
+    let name = match name {
+        "term" => "terminal",
+        name => name,
+    };
+    COMMANDS.iter().find(|(c, _)| *c == name)?.1
+}
+
+/// The commands this build can run: those needing a feature it was built
+/// without are left out.
+fn command_names() -> impl Iterator<Item = &'static str> {
+    COMMANDS
+        .iter()
+        .filter(|(_, feature)| feature.is_none_or(features::enabled))
+        .map(|(name, _)| *name)
+}
+
+/// Candidates for the word being typed at the end of `line`: command names
+/// for the first word, then option names after `set` and `set?` (theme
+/// names after `set theme`), files after the commands that open one,
+/// directories after those that save or switch to one, and keymap profiles.
+pub fn complete(line: &str, tree: &FileTree) -> Vec<String> {
+    let (command, rest) = match line.split_once(' ') {
+        Some(split) => split,
+        None => {
+            return command_names()
+                .filter(|c| c.starts_with(line))
+                .map(|c| c.to_string())
+                .collect()
+        }
+    };
+    let word = rest.rsplit(' ').next().unwrap_or_default();
+    let themes = |prefix: &str| -> Vec<String> {
+        config::Theme::names()
+            .into_iter()
+            .filter(|n| n.starts_with(prefix))
+            .collect()
+    };
+    match command {
+        "set" | "set?" => match (rest.split_once(' '), word.strip_prefix("theme=")) {
+            (Some(("theme", _)), _) => themes(word),
+            (None, Some(prefix)) => themes(prefix)
+                .into_iter()
+                .map(|n| format!("theme={}", n))
+                .collect(),
+            // `:set?` takes several names, `:set` a name and its value.
+            (Some(_), _) if command == "set" => Vec::new(),
+            _ => config::option_names()
+                .into_iter()
+                .filter(|n| n.starts_with(word))
+                .collect(),
+        },
+        "sav" | "saveas" | "sav!" | "saveas!" | "project" => complete_path(tree, word, false),
+        "open" | "diff" | "compare" | "hex" | "follow" | "tail" => complete_path(tree, word, true),
+        "keymap" => match rest.split_once(' ') {
+            None => ["export", "import"]
+                .into_iter()
+                .filter(|c| c.starts_with(word))
+                .map(String::from)
+                .collect(),
+            Some(("import", _)) => {
+                let mut candidates: Vec<String> = keymaps::names()
+                    .into_iter()
+                    .filter(|n| n.starts_with(word))
+                    .map(String::from)
+                    .collect();
+                candidates.extend(complete_path(tree, word, true));
+                candidates
+            }
+            Some(("export", _)) => complete_path(tree, word, true),
+            Some(_) => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// The tree's paths `word`, a path from its root, could go on to name:
+/// directories, each ending in `/`, and with `files`, files too.
+fn complete_path(tree: &FileTree, word: &str, files: bool) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word),
+    };
+    let node = match dir.is_empty() {
+        true => tree.find_path(tree.root()),
+        false => tree.find_path(&tree.root().join(dir)),
+    };
+    node.into_iter()
+        .flat_map(|node| node.children())
+        .filter(|child| (files || child.has_children()) && child.name().starts_with(prefix))
+        .map(|child| match child.has_children() {
+            true => format!("{}{}/", dir, child.name()),
+            false => format!("{}{}", dir, child.name()),
+        })
+        .collect()
+}
+
+#[test]
+fn test_complete_option_names() {
+    let tree = FileTree::new(Path::new("src")).unwrap();
+    let complete = |line| complete(line, &tree);
+    assert!(complete("set edi").iter().all(|n| n.starts_with("editor.")));
+    assert_eq!(complete("set? editor.wr"), ["editor.wrap"]);
+    assert_eq!(complete("set theme li"), ["light"]);
+    assert_eq!(complete("set theme=li"), ["theme=light"]);
+    assert!(complete("set wrap x").is_empty());
+    assert!(complete("log x").is_empty());
+}
+
+#[test]
+fn test_complete_directories_to_save_in() {
+    let tree = FileTree::new(Path::new(".")).unwrap();
+    assert_eq!(complete("saveas sr", &tree), ["src/"]);
+    assert_eq!(complete("sav! src/wid", &tree), ["src/widgets/"]);
+    // Files aren't offered, only where to put one.
+    assert!(complete("saveas src/widgets/file", &tree).is_empty());
+    assert!(complete("saveas nowhere/x", &tree).is_empty());
+}
+
+#[test]
+fn test_complete_command_names_and_files() {
+    let tree = FileTree::new(Path::new(".")).unwrap();
+    assert_eq!(complete("proj", &tree), ["project", "projects"]);
+    assert!(complete("zz", &tree).is_empty());
+    assert_eq!(
+        complete("open src/widgets/file_t", &tree),
+        ["src/widgets/file_tree.rs"]
+    );
+    assert_eq!(complete("diff Cargo.toml sr", &tree), ["src/"]);
+    assert_eq!(complete("keymap im", &tree), ["import"]);
+    assert_eq!(complete("keymap import v", &tree), ["vim", "vscode"]);
+}
+
+#[test]
+fn test_complete_hides_commands_the_build_leaves_out() {
+    let tree = FileTree::new(Path::new(".")).unwrap();
+    let expect = |on: bool, names: &[&str]| match on {
+        true => names.iter().map(|n| n.to_string()).collect(),
+        false => Vec::<String>::new(),
+    };
+    assert_eq!(
+        complete("bla", &tree),
+        expect(cfg!(feature = "git"), &["blame"])
+    );
+    assert_eq!(
+        complete("defin", &tree),
+        expect(cfg!(feature = "lsp"), &["definition"])
+    );
+    assert_eq!(
+        complete("term", &tree),
+        expect(cfg!(feature = "terminal"), &["terminal"])
+    );
+    assert_eq!(
+        complete("ss", &tree),
+        expect(cfg!(feature = "ssh"), &["ssh"])
+    );
+    // Those that need nothing are always there.
+    assert!(command_names().any(|c| c == "merge"));
+    assert!(command_names().any(|c| c == "send"));
+}
+
+#[test]
+fn test_commands_the_build_leaves_out_say_so() {
+    use crate::config::Config;
+
+    let mut app = App::new(Path::new("."), Config::default()).unwrap();
+    for (line, feature) in [
+        ("blame", "git"),
+        ("definition", "lsp"),
+        ("term right", "terminal"),
+        ("ssh host:/srv", "ssh"),
+    ] {
+        assert_eq!(needs(line.split(' ').next().unwrap()), Some(feature));
+        // Run only where they'd fail, not start a shell or connect.
+        if !features::enabled(feature) {
+            assert_eq!(
+                execute(&mut app, line).unwrap_err().to_string(),
+                format!("wyv was built without the `{}` feature", feature)
+            );
+        }
+    }
+    // Sending goes through tmux or WezTerm, not the built-in terminal.
+    assert_eq!(needs("send-path"), None);
+}
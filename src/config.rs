@@ -0,0 +1,1320 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    follow::FollowOptions,
+    format::Formatters,
+    jobs::CancelToken,
+    keymaps,
+    lint::Linters,
+    logging::LogOptions,
+    lsp::LspServerConfig,
+    plugins::PluginConfig,
+    project::{self, TrustStore},
+    pty::TerminalOptions,
+    spell::SpellOptions,
+    ssh::SshOptions,
+    tasks::TaskConfig,
+    widgets::file_tree::TreeOptions,
+};
+
+const APP_NAME: &str = "wyv";
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub theme: Theme,
+    pub keymap: Keymap,
+    pub tree: TreeOptions,
+    pub editor: EditorOptions,
+    pub log: LogOptions,
+    pub plugins: Vec<PluginConfig>,
+    pub lsp: Vec<LspServerConfig>,
+    pub formatters: Formatters,
+    pub linters: Linters,
+    pub tasks: Vec<TaskConfig>,
+    pub terminal: TerminalOptions,
+    pub spell: SpellOptions,
+    pub follow: FollowOptions,
+    pub ssh: SshOptions,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(toml::from_str(&text)?)
+    }
+
+    /// Sets the option at a dotted path such as `editor.tab_width`, checking
+    /// that the value fits the option's type.
+    pub fn set(&mut self, key: &str, value: serde_json::Value) -> Result<()> {
+        let mut root = serde_json::to_value(&*self)?;
+        let mut slot = &mut root;
+        for part in key.split('.') {
+            slot = slot
+                .as_object_mut()
+                .and_then(|o| o.get_mut(part))
+                .ok_or_else(|| anyhow::anyhow!("unknown option `{}`", key))?;
+        }
+        *slot = value;
+
+        *self = serde_json::from_value(root)
+            .map_err(|e| anyhow::anyhow!("invalid value for `{}`: {}", key, e))?;
+        Ok(())
+    }
+
+    /// The value of the option at a dotted path, as JSON.
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        let mut value = serde_json::to_value(self).ok()?;
+        for part in key.split('.') {
+            value = value.as_object_mut()?.remove(part)?;
+        }
+        Some(value)
+    }
+
+    /// Loads the config at `path`, falling back to the defaults when the file
+    /// does not exist yet.
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if path.exists() {
+            Config::load(path)
+        } else {
+            Ok(Config::default())
+        }
+    }
+}
+
+/// Platform config directory for wyv: `$XDG_CONFIG_HOME/wyv` (or
+/// `~/.config/wyv`) on unix-likes and `%APPDATA%\wyv` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    app_dir("APPDATA", "XDG_CONFIG_HOME", ".config")
+}
+
+/// Platform directory for logs and other state: `$XDG_STATE_HOME/wyv` (or
+/// `~/.local/state/wyv`) on unix-likes and `%LOCALAPPDATA%\wyv` on Windows.
+pub fn state_dir() -> Option<PathBuf> {
+    app_dir("LOCALAPPDATA", "XDG_STATE_HOME", ".local/state")
+}
+
+fn app_dir(windows_var: &str, xdg_var: &str, home_fallback: &str) -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os(windows_var).map(|d| PathBuf::from(d).join(APP_NAME));
+    }
+
+    env::var_os(xdg_var)
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(home_fallback)))
+        .map(|d| d.join(APP_NAME))
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join(CONFIG_FILE))
+}
+
+/// Where the effective config comes from: the global config file, overlaid
+/// with the project's `.wyv.toml`. Tables merge key by key; anything else in
+/// the project file replaces the global value, except `plugins`, which are
+/// added to the global ones once the project is trusted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigSources {
+    pub global: Option<PathBuf>,
+    pub project: Option<PathBuf>,
+    pub trust: Option<TrustStore>,
+}
+
+/// A merged config, and the plugins the project config asked for that were
+/// left out because the project isn't trusted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadedConfig {
+    pub config: Config,
+    pub untrusted: Vec<PluginConfig>,
+    /// The project config as it was read, for trusting that text rather
+    /// than whatever the file holds by the time the user answers.
+    pub project_text: Option<String>,
+}
+
+impl ConfigSources {
+    /// The global config at `global` and the project config in `root`.
+    pub fn new(global: Option<PathBuf>, root: &Path) -> Self {
+        ConfigSources {
+            global,
+            project: Some(project::config_path(root)),
+            trust: TrustStore::default_location(),
+        }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.global
+            .iter()
+            .chain(&self.project)
+            .map(PathBuf::as_path)
+    }
+
+    /// Loads and merges the files. Missing files count as empty.
+    pub fn load(&self) -> Result<LoadedConfig> {
+        let mut merged = match self.global.as_deref().filter(|p| p.exists()) {
+            Some(path) => read_table(path)?,
+            None => toml::Value::Table(Default::default()),
+        };
+
+        let mut project_plugins = Vec::new();
+        let mut project_text = None;
+        let mut trusted = false;
+        if let Some(path) = self.project.as_deref().filter(|p| p.exists()) {
+            let text = fs::read_to_string(path)?;
+            let mut overlay: toml::Value =
+                toml::from_str(&text).with_context(|| path.display().to_string())?;
+            if let Some(plugins) = overlay.as_table_mut().and_then(|t| t.remove("plugins")) {
+                project_plugins = plugins
+                    .try_into()
+                    .with_context(|| format!("{}: plugins", path.display()))?;
+            }
+            trusted = self
+                .trust
+                .as_ref()
+                .is_some_and(|t| t.is_trusted(path, &text));
+            // Language servers, formatters, linters, tasks, sftp, the
+            // terminal's shell and what `:sudowrite` runs are programs too,
+            // so they wait for trust.
+            if let Some(table) = overlay.as_table_mut().filter(|_| !trusted) {
+                for key in ["lsp", "formatters", "linters", "tasks", "ssh"] {
+                    if table.remove(key).is_some() {
+                        tracing::warn!(key, "ignoring programs in the untrusted project config");
+                    }
+                }
+                for (name, key) in [("terminal", "shell"), ("editor", "elevate")] {
+                    let section = table.get_mut(name).and_then(|t| t.as_table_mut());
+                    if section.and_then(|t| t.remove(key)).is_some() {
+                        tracing::warn!(
+                            key = format!("{}.{}", name, key),
+                            "ignoring programs in the untrusted project config"
+                        );
+                    }
+                }
+            }
+            merge(&mut merged, overlay);
+            project_text = Some(text);
+        }
+
+        if let Some(theme) = merged.get_mut("theme") {
+            if let Some(palette) = theme.get("palette").and_then(|p| p.as_str()) {
+                let mut base = toml::Value::try_from(Theme::named(palette)?)?;
+                merge(&mut base, theme.clone());
+                *theme = base;
+            }
+        }
+        let mut config: Config = merged.try_into()?;
+        if !config.editor.keymap_profile.is_empty() {
+            let dir = config_dir().unwrap_or_default();
+            let profile = keymaps::load(&config.editor.keymap_profile, &dir)?;
+            let (keymap, conflicts) = keymaps::apply(&config.keymap, &profile);
+            for conflict in conflicts {
+                tracing::info!(key = conflict.key, "keymap binding kept over the profile's");
+            }
+            config.keymap = keymap;
+        }
+        if trusted {
+            config.plugins.extend(project_plugins);
+            project_plugins = Vec::new();
+        }
+        Ok(LoadedConfig {
+            config,
+            untrusted: project_plugins,
+            project_text,
+        })
+    }
+
+    /// Which file sets the option at `key`: `project`, `global`, or `None`
+    /// when it keeps its default.
+    pub fn source_of(&self, key: &str) -> Option<&'static str> {
+        let sets = |path: Option<&Path>| {
+            let table = path.filter(|p| p.exists()).and_then(|p| read_table(p).ok());
+            table.is_some_and(|table| {
+                key.split('.')
+                    .try_fold(&table, |value, part| value.get(part))
+                    .is_some()
+            })
+        };
+        if sets(self.project.as_deref()) {
+            Some("project")
+        } else if sets(self.global.as_deref()) {
+            Some("global")
+        } else {
+            None
+        }
+    }
+
+    /// Trusts the project config as `contents`, the text it was loaded
+    /// with.
+    pub fn trust_project(&self, contents: &str) -> Result<()> {
+        match (&self.trust, &self.project) {
+            (Some(store), Some(project)) => store.trust(project, contents),
+            _ => anyhow::bail!("there is no project config to trust"),
+        }
+    }
+}
+
+/// Short names `:set` accepts for common options.
+const OPTION_ALIASES: &[(&str, &str)] = &[
+    ("wrap", "editor.wrap"),
+    ("numbers", "editor.line_numbers"),
+    ("hidden", "tree.show_hidden"),
+    ("tabwidth", "editor.tab_width"),
+    ("spell", "spell.enabled"),
+    ("screenreader", "editor.screen_reader"),
+    ("mouse", "editor.mouse"),
+];
+
+/// The full dotted name for an option or its alias.
+pub fn resolve_option(name: &str) -> &str {
+    OPTION_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, key)| key)
+}
+
+/// Every option `:set` can change, aliases included, sorted. Keys, plugins,
+/// language servers, formatters, linters and tasks are left out since they're maps
+/// and lists rather than options.
+pub fn option_names() -> Vec<String> {
+    fn walk(prefix: &str, value: &serde_json::Value, names: &mut Vec<String>) {
+        match value.as_object() {
+            Some(object) => {
+                for (key, value) in object {
+                    walk(&format!("{}{}.", prefix, key), value, names);
+                }
+            }
+            None => names.push(prefix.trim_end_matches('.').to_string()),
+        }
+    }
+
+    let mut names = vec!["theme".to_string()];
+    names.extend(OPTION_ALIASES.iter().map(|(alias, _)| alias.to_string()));
+    if let Ok(serde_json::Value::Object(config)) = serde_json::to_value(Config::default()) {
+        for (key, value) in &config {
+            if !["keymap", "plugins", "lsp", "formatters", "linters", "tasks"]
+                .contains(&key.as_str())
+            {
+                walk(&format!("{}.", key), value, &mut names);
+            }
+        }
+    }
+    names.sort();
+    names
+}
+
+fn read_table(path: &Path) -> Result<toml::Value> {
+    let text = fs::read_to_string(path)?;
+    toml::from_str(&text).with_context(|| path.display().to_string())
+}
+
+/// Merges `overlay` into `base`: tables key by key, anything else replaced.
+fn merge(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(slot) => merge(slot, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Polls the config files' modification times so edits can be applied while
+/// the app is running.
+pub struct ConfigWatcher {
+    sources: ConfigSources,
+    modified: Vec<Option<SystemTime>>,
+    /// Stops the watcher, e.g. once another project's config replaces
+    /// these.
+    stop: CancelToken,
+}
+
+impl ConfigWatcher {
+    pub fn new(sources: ConfigSources) -> Self {
+        let modified = sources.paths().map(modified_time).collect();
+        ConfigWatcher {
+            sources,
+            modified,
+            stop: CancelToken::default(),
+        }
+    }
+
+    pub fn sources(&self) -> &ConfigSources {
+        &self.sources
+    }
+
+    /// A token that stops the watcher when cancelled.
+    pub fn stopper(&self) -> CancelToken {
+        self.stop.clone()
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.is_cancelled()
+    }
+
+    /// Returns the freshly loaded config if any file changed since the last
+    /// call. Removed files no longer contribute.
+    pub fn poll(&mut self) -> Option<Result<LoadedConfig>> {
+        let modified: Vec<_> = self.sources.paths().map(modified_time).collect();
+        if modified == self.modified {
+            return None;
+        }
+
+        self.modified = modified;
+        Some(self.sources.load())
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorOptions {
+    pub tab_width: u16,
+    pub expand_tab: bool,
+    pub line_numbers: bool,
+    pub wrap: bool,
+    /// Formats buffers before `:w` writes them.
+    pub format_on_save: bool,
+    /// Runs the file type's linter in the background after each save.
+    pub lint_on_save: bool,
+    /// Files bigger than this many bytes are streamed in, and language
+    /// servers, blame and spell checking leave them alone. 0 means no limit.
+    pub large_file: u64,
+    /// Uses the kitty keyboard protocol where the terminal supports it, so
+    /// that e.g. `ctrl-i` can be bound apart from `tab`.
+    pub enhanced_keys: bool,
+    /// Input events handled before the next frame is drawn, however many
+    /// are waiting, so that holding a key or pasting a lot still shows
+    /// progress. 0 means no limit.
+    pub max_events_per_frame: usize,
+    /// Frames drawn a second at most. Lower it to spare the CPU, a
+    /// battery or a slow ssh link. 0 means no cap.
+    pub max_fps: u32,
+    /// Runs `tee` for `:sudowrite`, to save files this user can't write:
+    /// `sudo`, `doas` or `pkexec`, say.
+    pub elevate: String,
+    /// Remembers marks from one run to the next, along with the jump list.
+    pub save_marks: bool,
+    /// Keymap profile to take on beneath `[keymap]` as the config loads:
+    /// `vim`, `emacs`, `vscode` or a profile file in the config directory.
+    pub keymap_profile: String,
+    /// Seconds between snapshots of the session, which are offered for
+    /// restoring if wyv doesn't exit cleanly. 0 takes none.
+    pub autosave_session: u64,
+    /// Suits terminal screen readers: plain ASCII in place of decorative
+    /// glyphs, a line announcing files opened and modes entered, and
+    /// frames drawn only when something changed.
+    pub screen_reader: bool,
+    /// Takes the mouse from the terminal. Turned off, the terminal's own
+    /// text selection and middle-click paste keep working.
+    pub mouse: bool,
+}
+
+impl Default for EditorOptions {
+    fn default() -> Self {
+        EditorOptions {
+            tab_width: 4,
+            expand_tab: true,
+            line_numbers: true,
+            wrap: false,
+            format_on_save: false,
+            lint_on_save: false,
+            large_file: 64 << 20,
+            enhanced_keys: true,
+            max_events_per_frame: 256,
+            max_fps: 60,
+            elevate: "sudo".to_string(),
+            save_marks: true,
+            keymap_profile: String::new(),
+            autosave_session: 30,
+            screen_reader: false,
+            mouse: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Built-in or saved theme that the colours set beside it are laid
+    /// over: `high-contrast` or `colorblind`, say.
+    pub palette: String,
+    pub foreground: ThemeColor,
+    pub background: ThemeColor,
+    pub selection_fg: ThemeColor,
+    pub selection_bg: ThemeColor,
+    pub status_fg: ThemeColor,
+    pub status_bg: ThemeColor,
+    pub accent: ThemeColor,
+    /// Lines and words only in the newer side of a diff.
+    pub added: ThemeColor,
+    /// Lines and words only in the older side of a diff.
+    pub removed: ThemeColor,
+    /// Errors: in logs, diagnostics and failed tasks.
+    pub error: ThemeColor,
+    pub warning: ThemeColor,
+    /// Line numbers, hints and anything else kept in the background.
+    pub muted: ThemeColor,
+    /// Leaves the colours out, telling things apart by bold, underline and
+    /// reverse alone, for monochrome terminals and `NO_COLOR`.
+    pub monochrome: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            palette: String::new(),
+            foreground: ThemeColor(Color::White),
+            background: ThemeColor(Color::Reset),
+            selection_fg: ThemeColor(Color::Black),
+            selection_bg: ThemeColor(Color::LightBlue),
+            status_fg: ThemeColor(Color::White),
+            status_bg: ThemeColor(Color::DarkGray),
+            accent: ThemeColor(Color::LightBlue),
+            added: ThemeColor(Color::Green),
+            removed: ThemeColor(Color::Red),
+            error: ThemeColor(Color::Red),
+            warning: ThemeColor(Color::Yellow),
+            muted: ThemeColor(Color::DarkGray),
+            monochrome: false,
+        }
+    }
+}
+
+impl Theme {
+    /// Looks `name` up among the built-in themes, then as
+    /// `<config dir>/themes/<name>.toml`.
+    pub fn named(name: &str) -> Result<Theme> {
+        let builtin = match name {
+            "default" | "dark" => Some(Theme::default()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            "colorblind" | "deuteranopia" | "protanopia" => Some(Theme::colorblind()),
+            "monochrome" => Some(Theme::monochrome()),
+            _ => None,
+        };
+        if let Some(theme) = builtin {
+            return Ok(Theme {
+                palette: name.to_string(),
+                ..theme
+            });
+        }
+
+        let path = config_dir()
+            .map(|d| d.join("themes").join(format!("{}.toml", name)))
+            .filter(|p| p.exists())
+            .ok_or_else(|| anyhow::anyhow!("unknown theme `{}`", name))?;
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// The names [`Theme::named`] knows: the built-in themes, then those
+    /// in `<config dir>/themes`.
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = [
+            "colorblind",
+            "dark",
+            "default",
+            "deuteranopia",
+            "high-contrast",
+            "light",
+            "monochrome",
+            "protanopia",
+        ]
+        .map(String::from)
+        .into();
+        let files = config_dir()
+            .and_then(|d| fs::read_dir(d.join("themes")).ok())
+            .into_iter()
+            .flatten()
+            .flatten();
+        let mut custom: Vec<String> = files
+            .filter_map(|entry| {
+                let path = entry.path();
+                match path.extension().and_then(|e| e.to_str()) {
+                    Some("toml") => Some(path.file_stem()?.to_str()?.to_string()),
+                    _ => None,
+                }
+            })
+            .collect();
+        custom.sort();
+        names.extend(custom);
+        names
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            foreground: ThemeColor(Color::Black),
+            background: ThemeColor(Color::White),
+            selection_fg: ThemeColor(Color::White),
+            selection_bg: ThemeColor(Color::Blue),
+            status_fg: ThemeColor(Color::Black),
+            status_bg: ThemeColor(Color::Gray),
+            accent: ThemeColor(Color::Blue),
+            added: ThemeColor(Color::Green),
+            removed: ThemeColor(Color::Red),
+            error: ThemeColor(Color::Red),
+            warning: ThemeColor(Color::Rgb(0xa0, 0x60, 0)),
+            muted: ThemeColor(Color::DarkGray),
+            ..Theme::default()
+        }
+    }
+
+    /// Bright on black, with nothing told apart by a dim colour.
+    pub fn high_contrast() -> Self {
+        Theme {
+            foreground: ThemeColor(Color::White),
+            background: ThemeColor(Color::Black),
+            selection_fg: ThemeColor(Color::Black),
+            selection_bg: ThemeColor(Color::LightYellow),
+            status_fg: ThemeColor(Color::Black),
+            status_bg: ThemeColor(Color::White),
+            accent: ThemeColor(Color::LightYellow),
+            added: ThemeColor(Color::LightCyan),
+            removed: ThemeColor(Color::LightMagenta),
+            error: ThemeColor(Color::LightRed),
+            warning: ThemeColor(Color::LightYellow),
+            muted: ThemeColor(Color::Gray),
+            ..Theme::default()
+        }
+    }
+
+    /// No colours at all: selections and the status bar reversed, accents
+    /// and additions bold, removals underlined.
+    pub fn monochrome() -> Self {
+        Theme {
+            monochrome: true,
+            ..Theme::default()
+        }
+    }
+
+    /// Blue and orange in place of green and red, from the Okabe-Ito
+    /// palette, which people with deuteranopia or protanopia tell apart.
+    pub fn colorblind() -> Self {
+        Theme {
+            selection_bg: ThemeColor(Color::Rgb(0x56, 0xb4, 0xe9)),
+            accent: ThemeColor(Color::Rgb(0x56, 0xb4, 0xe9)),
+            added: ThemeColor(Color::Rgb(0x00, 0x72, 0xb2)),
+            removed: ThemeColor(Color::Rgb(0xe6, 0x9f, 0x00)),
+            error: ThemeColor(Color::Rgb(0xd5, 0x5e, 0x00)),
+            warning: ThemeColor(Color::Rgb(0xf0, 0xe4, 0x42)),
+            ..Theme::default()
+        }
+    }
+
+    pub fn base(&self) -> Style {
+        let style = Style::default().fg(self.foreground.0).bg(self.background.0);
+        self.colored(style, Modifier::empty())
+    }
+
+    pub fn selection(&self) -> Style {
+        let style = Style::default()
+            .fg(self.selection_fg.0)
+            .bg(self.selection_bg.0);
+        self.colored(style, Modifier::REVERSED)
+    }
+
+    pub fn status(&self) -> Style {
+        let style = Style::default().fg(self.status_fg.0).bg(self.status_bg.0);
+        self.colored(style, Modifier::REVERSED)
+    }
+
+    pub fn accent(&self) -> Style {
+        self.colored(Style::default().fg(self.accent.0), Modifier::BOLD)
+    }
+
+    pub fn gutter(&self) -> Style {
+        let style = Style::default().fg(self.muted.0).bg(self.background.0);
+        self.colored(style, Modifier::empty())
+    }
+
+    pub fn added(&self) -> Style {
+        let style = Style::default().fg(self.added.0).bg(self.background.0);
+        self.colored(style, Modifier::BOLD)
+    }
+
+    pub fn removed(&self) -> Style {
+        let style = Style::default().fg(self.removed.0).bg(self.background.0);
+        self.colored(style, Modifier::UNDERLINED)
+    }
+
+    pub fn misspelled(&self) -> Style {
+        Style::default().add_modifier(Modifier::UNDERLINED)
+    }
+
+    /// Lines of a followed log that report errors.
+    pub fn log_error(&self) -> Style {
+        let style = Style::default().fg(self.error.0);
+        self.colored(style, Modifier::BOLD | Modifier::UNDERLINED)
+    }
+
+    /// Lines of a followed log that report warnings.
+    pub fn log_warning(&self) -> Style {
+        self.colored(Style::default().fg(self.warning.0), Modifier::BOLD)
+    }
+
+    /// Gutter signs for diagnostics of `severity`, 1 being errors.
+    pub fn diagnostic(&self, severity: u64) -> Style {
+        match severity {
+            1 => self.log_error(),
+            2 => self.log_warning(),
+            _ => self.gutter(),
+        }
+    }
+
+    /// `style`, or in monochrome just `modifier`.
+    fn colored(&self, style: Style, modifier: Modifier) -> Style {
+        match self.monochrome {
+            true => Style::default().add_modifier(modifier),
+            false => style,
+        }
+    }
+}
+
+/// A color written in the config either by name (`"lightblue"`) or as
+/// `"#rrggbb"`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ThemeColor(pub Color);
+
+impl TryFrom<String> for ThemeColor {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        parse_color(&s)
+            .map(ThemeColor)
+            .ok_or_else(|| format!("unknown color `{}`", s))
+    }
+}
+
+impl From<ThemeColor> for String {
+    fn from(c: ThemeColor) -> Self {
+        match c.0 {
+            Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            Color::Indexed(i) => i.to_string(),
+            other => format!("{:?}", other).to_lowercase(),
+        }
+    }
+}
+
+pub fn parse_color(s: &str) -> Option<Color> {
+    let s = s.trim().to_lowercase();
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Ok(i) = s.parse::<u8>() {
+        return Some(Color::Indexed(i));
+    }
+
+    let color = match s.replace(['-', '_', ' '], "").as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    };
+    Some(color)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    CommandMode,
+    TreeUp,
+    TreeDown,
+    TreeExpand,
+    TreeCollapse,
+    TreeToggle,
+    /// Lists the rest of a directory's entries, past `tree.max_entries`.
+    TreeLoadMore,
+    /// Marks the selected file for comparing, or unmarks it.
+    TreeMark,
+    /// Compares the two marked files, or the marked and selected ones.
+    CompareMarked,
+    TogglePerf,
+    /// Typing into the active buffer.
+    InsertMode,
+    /// Blame for the cursor line, shown after its text.
+    ToggleBlame,
+    /// Jumps to where the symbol at the cursor is defined.
+    GotoDefinition,
+    /// Lists the uses of the symbol at the cursor.
+    FindReferences,
+    /// Shows documentation for the symbol at the cursor.
+    Hover,
+    /// Steps back through the jump list, to where the cursor was before
+    /// the last jump.
+    JumpBack,
+    /// Steps forward through the jump list again.
+    JumpForward,
+    /// Shows and focuses the terminal pane, or hides it once focused.
+    ToggleTerminal,
+    /// Opens the project search overlay.
+    Search,
+    /// Shows the outline sidebar and moves into it.
+    Outline,
+    /// Lists the active buffer's functions and types to jump to.
+    FileSymbols,
+    /// Lists the functions and types of every file in the project.
+    WorkspaceSymbols,
+    /// Moves the cursor to the next entry of the location list.
+    NextLocation,
+    /// Moves the cursor to the previous entry of the location list.
+    PrevLocation,
+    /// Shows the active JSON or YAML buffer as a tree of keys and values.
+    Structure,
+    /// Shows the rendered markdown beside the editor, or hides it.
+    TogglePreview,
+    /// Suggests replacements for the misspelled word at or after the cursor.
+    SpellSuggest,
+    /// Follows the active buffer's file, keeping to its end as it grows.
+    Follow,
+    /// Stops wyv and hands the terminal back to the shell, as ctrl-z does
+    /// elsewhere; `fg` brings it back.
+    Suspend,
+    /// Sets a mark at the cursor, named by the next key: `a` to `z` for
+    /// one in this file, `A` to `Z` for one across files.
+    SetMark,
+    /// Goes to the mark named by the next key.
+    GotoMark,
+    /// Lists the files opened lately to reopen one.
+    RecentFiles,
+    /// Starts `:saveas` at the prompt, in the active buffer's directory.
+    SaveAs,
+    /// Lists the workspaces opened before to switch to one. Not bound by
+    /// default.
+    Projects,
+    /// Unbinds a key inherited from the defaults.
+    None,
+}
+
+impl Action {
+    /// The name the config spells this action with, e.g. `tree_down`.
+    pub fn name(self) -> String {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => format!("{:?}", self),
+        }
+    }
+}
+
+/// Key bindings, written in the config as `"ctrl-c" = "quit"`. User entries
+/// are merged over the defaults rather than replacing them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "HashMap<String, Action>", into = "HashMap<String, Action>")]
+pub struct Keymap {
+    bindings: HashMap<String, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = [
+            ("ctrl-c", Action::Quit),
+            (":", Action::CommandMode),
+            ("k", Action::TreeUp),
+            ("up", Action::TreeUp),
+            ("j", Action::TreeDown),
+            ("down", Action::TreeDown),
+            ("l", Action::TreeExpand),
+            ("right", Action::TreeExpand),
+            ("h", Action::TreeCollapse),
+            ("left", Action::TreeCollapse),
+            ("enter", Action::TreeToggle),
+            ("L", Action::TreeLoadMore),
+            ("m", Action::TreeMark),
+            ("=", Action::CompareMarked),
+            ("f12", Action::TogglePerf),
+            ("b", Action::ToggleBlame),
+            ("i", Action::InsertMode),
+            ("g", Action::GotoDefinition),
+            ("r", Action::FindReferences),
+            ("K", Action::Hover),
+            ("ctrl-o", Action::JumpBack),
+            // Without the kitty keyboard protocol, ctrl-i arrives as tab.
+            ("ctrl-i", Action::JumpForward),
+            ("tab", Action::JumpForward),
+            ("ctrl-t", Action::ToggleTerminal),
+            ("ctrl-f", Action::Search),
+            ("o", Action::Outline),
+            ("s", Action::FileSymbols),
+            ("S", Action::WorkspaceSymbols),
+            ("]", Action::NextLocation),
+            ("[", Action::PrevLocation),
+            ("z", Action::SpellSuggest),
+            ("P", Action::TogglePreview),
+            ("T", Action::Structure),
+            ("F", Action::Follow),
+            ("ctrl-z", Action::Suspend),
+            // `m` marks files in the tree for comparing.
+            ("M", Action::SetMark),
+            ("'", Action::GotoMark),
+            ("R", Action::RecentFiles),
+            ("W", Action::SaveAs),
+        ]
+        .into_iter()
+        .map(|(k, a)| (k.to_string(), a))
+        .collect();
+
+        Keymap { bindings }
+    }
+}
+
+impl From<HashMap<String, Action>> for Keymap {
+    fn from(overrides: HashMap<String, Action>) -> Self {
+        let mut keymap = Keymap::default();
+        for (key, action) in overrides {
+            keymap.bindings.insert(normalize_key(&key), action);
+        }
+        keymap
+    }
+}
+
+impl From<Keymap> for HashMap<String, Action> {
+    fn from(keymap: Keymap) -> Self {
+        keymap.bindings
+    }
+}
+
+impl Keymap {
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        match self.bindings.get(&key_name(key)) {
+            Some(Action::None) | None => None,
+            Some(action) => Some(*action),
+        }
+    }
+
+    /// The bindings that differ from the defaults, unbound keys included.
+    pub fn overrides(&self) -> HashMap<String, Action> {
+        let defaults = Keymap::default().bindings;
+        let mut overrides: HashMap<String, Action> = self
+            .bindings
+            .iter()
+            .filter(|&(key, action)| defaults.get(key) != Some(action))
+            .map(|(key, &action)| (key.clone(), action))
+            .collect();
+        for key in defaults.keys().filter(|k| !self.bindings.contains_key(*k)) {
+            overrides.insert(key.clone(), Action::None);
+        }
+        overrides
+    }
+}
+
+/// Modifiers as written in key names, in the order [`key_name`] puts them,
+/// shift aside. Super, hyper and meta only arrive with the kitty keyboard
+/// protocol.
+const MODIFIERS: [(&str, KeyModifiers); 5] = [
+    ("ctrl", KeyModifiers::CONTROL),
+    ("alt", KeyModifiers::ALT),
+    ("super", KeyModifiers::SUPER),
+    ("hyper", KeyModifiers::HYPER),
+    ("meta", KeyModifiers::META),
+];
+
+/// Canonical name of a key event, e.g. `ctrl-alt-x`, `shift-tab`, `f5`.
+pub fn key_name(key: &KeyEvent) -> String {
+    let mut name = String::new();
+    for (modifier, flag) in MODIFIERS {
+        if key.modifiers.contains(flag) {
+            name.push_str(modifier);
+            name.push('-');
+        }
+    }
+
+    let base = match key.code {
+        KeyCode::Char(' ') => "space".to_string(),
+        // Shifted characters arrive in their uppercase form; with ctrl or alt
+        // held they are spelled `ctrl-shift-x` instead.
+        KeyCode::Char(c) if name.is_empty() => return c.to_string(),
+        KeyCode::Char(c) if c.is_uppercase() => {
+            return name + "shift-" + &c.to_lowercase().to_string();
+        }
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "backtab".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Insert => "insert".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Null => "null".to_string(),
+        // Media, lock and lone modifier keys only arrive with the kitty
+        // keyboard protocol.
+        other => format!("{:?}", other).to_lowercase(),
+    };
+    if key.modifiers.contains(KeyModifiers::SHIFT) {
+        name.push_str("shift-");
+    }
+    name + &base
+}
+
+/// Parses a key written the way [`key_name`] spells it (modifier order and
+/// case are forgiving), producing the event crossterm would report.
+pub fn parse_key(name: &str) -> Option<KeyEvent> {
+    let normalized = normalize_key(name);
+    let mut rest = normalized.as_str();
+    let mut modifiers = KeyModifiers::NONE;
+    for (modifier, flag) in MODIFIERS
+        .into_iter()
+        .chain([("shift", KeyModifiers::SHIFT)])
+    {
+        if let Some(r) = rest
+            .strip_prefix(modifier)
+            .and_then(|r| r.strip_prefix('-'))
+        {
+            modifiers |= flag;
+            rest = r;
+        }
+    }
+
+    let code = match rest {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        f if f.len() > 1 && f.starts_with('f') => KeyCode::F(f[1..].parse().ok()?),
+        c if c.chars().count() == 1 => {
+            let c = c.chars().next()?;
+            // Shifted characters are reported in uppercase with SHIFT set.
+            if modifiers.contains(KeyModifiers::SHIFT) {
+                KeyCode::Char(c.to_ascii_uppercase())
+            } else {
+                if c.is_uppercase() {
+                    modifiers |= KeyModifiers::SHIFT;
+                }
+                KeyCode::Char(c)
+            }
+        }
+        _ => return None,
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// Puts modifiers of a user-written key into the order `key_name` produces.
+pub(crate) fn normalize_key(key: &str) -> String {
+    let (modifiers, base) = match key.rsplit_once('-') {
+        // A trailing `-` means the minus key itself, e.g. `ctrl--`.
+        Some((modifiers, "")) => (modifiers.strip_suffix('-').unwrap_or(modifiers), "-"),
+        Some((modifiers, base)) => (modifiers, base),
+        None => ("", key),
+    };
+    let mut modifiers = modifiers.to_lowercase();
+    // Single characters keep their case so `G` and `g` stay distinct, except
+    // under ctrl/alt where `ctrl-C` means `ctrl-shift-c`.
+    let base = if base.chars().count() != 1 {
+        base.to_lowercase()
+    } else if modifiers.is_empty() {
+        base.to_string()
+    } else {
+        if base.chars().any(char::is_uppercase) {
+            modifiers.push_str("-shift");
+        }
+        base.to_lowercase()
+    };
+
+    let mut name = String::new();
+    for modifier in MODIFIERS.iter().map(|(m, _)| *m).chain(["shift"]) {
+        if modifiers.split('-').any(|m| m == modifier) {
+            name.push_str(modifier);
+            name.push('-');
+        }
+    }
+    name + &base
+}
+
+#[test]
+fn test_partial_config_keeps_defaults() {
+    let config: Config = toml::from_str(
+        r##"
+        [theme]
+        accent = "#ff8000"
+
+        [keymap]
+        "q" = "quit"
+        "Ctrl-c" = "none"
+        "alt-J" = "tree_down"
+
+        [tree]
+        show_hidden = true
+        "##,
+    )
+    .unwrap();
+
+    assert_eq!(config.theme.accent, ThemeColor(Color::Rgb(0xff, 0x80, 0)));
+    assert_eq!(config.theme.foreground, Theme::default().foreground);
+    assert!(config.tree.show_hidden);
+    assert_eq!(config.editor, EditorOptions::default());
+
+    let key = |code, modifiers| KeyEvent::new(code, modifiers);
+    let keymap = &config.keymap;
+    assert_eq!(
+        keymap.action(&key(KeyCode::Char('q'), KeyModifiers::NONE)),
+        Some(Action::Quit)
+    );
+    assert_eq!(
+        keymap.action(&key(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+        None
+    );
+    assert_eq!(
+        keymap.action(&key(KeyCode::Char('j'), KeyModifiers::NONE)),
+        Some(Action::TreeDown)
+    );
+    assert_eq!(
+        keymap.action(&key(
+            KeyCode::Char('J'),
+            KeyModifiers::ALT | KeyModifiers::SHIFT
+        )),
+        Some(Action::TreeDown)
+    );
+}
+
+#[test]
+fn test_watcher_picks_up_changes() {
+    let dir = env::temp_dir().join(format!("wyv-config-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(CONFIG_FILE);
+    let _ = fs::remove_file(&path);
+
+    let sources = ConfigSources {
+        global: Some(path.clone()),
+        ..Default::default()
+    };
+    let mut watcher = ConfigWatcher::new(sources);
+    assert!(watcher.poll().is_none());
+
+    fs::write(&path, "[editor]\ntab_width = 2\n").unwrap();
+    let loaded = watcher.poll().unwrap().unwrap();
+    assert_eq!(loaded.config.editor.tab_width, 2);
+    assert!(watcher.poll().is_none());
+
+    fs::remove_file(&path).unwrap();
+    assert_eq!(watcher.poll().unwrap().unwrap().config, Config::default());
+    let _ = fs::remove_dir(&dir);
+}
+
+#[test]
+fn test_project_config_overlays_global() {
+    let dir = env::temp_dir().join(format!("wyv-project-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let global = dir.join(CONFIG_FILE);
+    fs::write(
+        &global,
+        "[editor]\ntab_width = 8\nwrap = true\n[[plugins]]\nname = \"a\"\ncommand = \"a\"\n",
+    )
+    .unwrap();
+    fs::write(
+        project::config_path(&dir),
+        "[editor]\ntab_width = 2\nelevate = \"./x\"\n[[plugins]]\nname = \"b\"\ncommand = \"b\"\n\
+         [[lsp]]\nname = \"c\"\ncommand = \"c\"\nextensions = [\"rs\"]\n\
+         [formatters.rs]\ncommand = \"evil\"\n[linters.sh]\ncommand = \"evil\"\n\
+         [terminal]\nshell = \"evil\"\nposition = \"right\"\n\
+         [[tasks]]\nname = \"d\"\ncommand = \"make d\"\n",
+    )
+    .unwrap();
+    let sources = ConfigSources {
+        trust: Some(TrustStore::new(dir.join("trusted.json"))),
+        ..ConfigSources::new(Some(global), &dir)
+    };
+
+    let loaded = sources.load().unwrap();
+    assert_eq!(loaded.config.editor.tab_width, 2);
+    assert!(loaded.config.editor.wrap);
+    let names =
+        |plugins: &[PluginConfig]| plugins.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&loaded.config.plugins), ["a"]);
+    assert_eq!(names(&loaded.untrusted), ["b"]);
+    assert!(loaded.config.lsp.is_empty());
+    assert!(loaded.config.tasks.is_empty());
+    let rustfmt = loaded.config.formatters.for_path(Path::new("a.rs"));
+    assert_eq!(rustfmt.unwrap().command, "rustfmt");
+    let shellcheck = loaded.config.linters.for_path(Path::new("a.sh"));
+    assert_eq!(shellcheck.unwrap().command, "shellcheck");
+    assert_eq!(loaded.config.terminal.shell, None);
+    assert_eq!(loaded.config.editor.elevate, "sudo");
+    assert_eq!(
+        loaded.config.terminal.position,
+        crate::pty::TerminalPosition::Right
+    );
+    assert_eq!(sources.source_of("editor.tab_width"), Some("project"));
+    assert_eq!(sources.source_of("editor.wrap"), Some("global"));
+    assert_eq!(sources.source_of("tree.indent"), None);
+
+    sources
+        .trust_project(loaded.project_text.as_deref().unwrap())
+        .unwrap();
+    let loaded = sources.load().unwrap();
+    assert_eq!(names(&loaded.config.plugins), ["a", "b"]);
+    assert!(loaded.untrusted.is_empty());
+    assert_eq!(loaded.config.lsp[0].name, "c");
+    assert_eq!(loaded.config.tasks[0].command, "make d");
+    let evil = loaded.config.formatters.for_path(Path::new("a.rs"));
+    assert_eq!(evil.unwrap().command, "evil");
+    let evil = loaded.config.linters.for_path(Path::new("a.sh"));
+    assert_eq!(evil.unwrap().command, "evil");
+    assert_eq!(loaded.config.terminal.shell.as_deref(), Some("evil"));
+    assert_eq!(loaded.config.editor.elevate, "./x");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_option_names_resolve() {
+    let names = option_names();
+    assert!(names.contains(&"editor.wrap".to_string()));
+    assert!(names.contains(&"wrap".to_string()));
+    assert!(!names.iter().any(|n| n.starts_with("keymap")));
+    assert_eq!(resolve_option("numbers"), "editor.line_numbers");
+    assert_eq!(resolve_option("editor.wrap"), "editor.wrap");
+    assert_eq!(
+        Config::default().get("editor.tab_width"),
+        Some(serde_json::json!(4))
+    );
+    assert_eq!(Config::default().get("editor.bogus"), None);
+}
+
+#[test]
+fn test_parse_key_round_trips() {
+    for name in [
+        "j",
+        "G",
+        "ctrl-c",
+        "ctrl-shift-c",
+        "alt-enter",
+        "f5",
+        "shift-tab",
+        ":",
+        "ctrl-i",
+        "tab",
+        "super-s",
+        "ctrl-hyper-meta-x",
+    ] {
+        let key = parse_key(name).unwrap();
+        assert_eq!(key_name(&key), name);
+    }
+    assert_eq!(parse_key("nonsense"), None);
+    assert_eq!(normalize_key("Shift-Super-ctrl-K"), "ctrl-super-shift-k");
+    // Only the kitty keyboard protocol tells these apart.
+    assert_ne!(parse_key("ctrl-i"), parse_key("tab"));
+}
+
+#[test]
+fn test_set_by_dotted_path() {
+    let mut config = Config::default();
+    config
+        .set("editor.tab_width", serde_json::json!(8))
+        .unwrap();
+    assert_eq!(config.editor.tab_width, 8);
+    config
+        .set("theme.accent", serde_json::json!("red"))
+        .unwrap();
+    assert_eq!(config.theme.accent, ThemeColor(Color::Red));
+
+    assert!(config.set("editor.nope", serde_json::json!(1)).is_err());
+    assert!(config.set("editor.wrap", serde_json::json!("yes")).is_err());
+    assert_eq!(config.editor.tab_width, 8);
+}
+
+#[test]
+fn test_keymap_profile_loads_beneath_the_keymap() {
+    let dir = env::temp_dir().join(format!("wyv-profile-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let global = dir.join(CONFIG_FILE);
+    fs::write(
+        &global,
+        "[editor]\nkeymap_profile = \"vim\"\n[keymap]\n\"/\" = \"outline\"\n",
+    )
+    .unwrap();
+    let sources = ConfigSources {
+        project: None,
+        ..ConfigSources::new(Some(global), &dir)
+    };
+    let keymap = sources.load().unwrap().config.keymap;
+    let key = |name| keymap.action(&parse_key(name).unwrap());
+    assert_eq!(key("m"), Some(Action::SetMark));
+    assert_eq!(key("/"), Some(Action::Outline));
+    assert_eq!(key("j"), Some(Action::TreeDown));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_theme_palette_loads_beneath_the_theme() {
+    let dir = env::temp_dir().join(format!("wyv-palette-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let global = dir.join(CONFIG_FILE);
+    fs::write(
+        &global,
+        "[theme]\npalette = \"deuteranopia\"\naccent = \"red\"\n",
+    )
+    .unwrap();
+    let sources = ConfigSources {
+        project: None,
+        ..ConfigSources::new(Some(global), &dir)
+    };
+    let theme = sources.load().unwrap().config.theme;
+    assert_eq!(theme.accent, ThemeColor(Color::Red));
+    assert_eq!(theme.added, Theme::colorblind().added);
+    assert_ne!(theme.added, Theme::default().added);
+    assert_eq!(theme.log_error().fg, Some(theme.error.0));
+    fs::remove_dir_all(dir).unwrap();
+
+    for name in Theme::names() {
+        let theme = Theme::named(&name).unwrap();
+        assert_ne!(theme.added(), theme.removed(), "{}", name);
+        assert_ne!(theme.log_error(), theme.log_warning(), "{}", name);
+    }
+}
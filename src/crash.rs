@@ -0,0 +1,141 @@
+//! Crash reports. A panic writes the panic message, a backtrace, version
+//! info, the last few input events (keys by the action they ran, never the
+//! text typed) and the paths of open buffers (never their contents) to a
+//! file in the state directory. The next start points the user at it.
+
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fmt::Write as _,
+    fs, io, panic,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{config, features, logging::RecentLogs};
+
+const EVENT_CAPACITY: usize = 50;
+const LOG_LINES: usize = 30;
+/// Holds the path of the newest report until the next start shows it.
+const LAST_CRASH_FILE: &str = "last-crash";
+
+struct CrashContext {
+    events: VecDeque<String>,
+    buffers: Vec<PathBuf>,
+    logs: Option<RecentLogs>,
+}
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    events: VecDeque::new(),
+    buffers: Vec::new(),
+    logs: None,
+});
+
+fn context() -> std::sync::MutexGuard<'static, CrashContext> {
+    // A panic while the lock was held must not stop the report being written.
+    CONTEXT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn default_dir() -> Option<PathBuf> {
+    config::state_dir()
+}
+
+/// Remembers an input event for the next report.
+pub fn record_event(event: impl Into<String>) {
+    let mut context = context();
+    if context.events.len() == EVENT_CAPACITY {
+        context.events.pop_front();
+    }
+    context.events.push_back(event.into());
+}
+
+/// Replaces the list of open buffers included in reports.
+pub fn set_buffers<'a>(paths: impl IntoIterator<Item = &'a Path>) {
+    context().buffers = paths.into_iter().map(Path::to_owned).collect();
+}
+
+/// Writes a report into `dir` on panic, before the previously installed
+/// hook runs, then tells the user where it went.
+pub fn install_hook(dir: PathBuf, logs: Option<RecentLogs>) {
+    context().logs = logs;
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let backtrace = Backtrace::force_capture();
+        let written = write_report(&dir, &info.to_string(), &backtrace.to_string());
+        previous(info);
+        match written {
+            Ok(path) => eprintln!("wyv crashed; a report was written to {}", path.display()),
+            Err(e) => eprintln!("wyv crashed; writing a report failed: {}", e),
+        }
+    }));
+}
+
+/// Writes a report to a new file in `dir` and marks it as the latest one.
+pub fn write_report(dir: &Path, message: &str, backtrace: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = dir.join(format!("crash-{}-{}.txt", stamp, std::process::id()));
+    fs::write(&path, render_report(message, backtrace))?;
+    fs::write(dir.join(LAST_CRASH_FILE), path.to_string_lossy().as_bytes())?;
+    Ok(path)
+}
+
+/// The report written by the last crash, if it hasn't been shown yet.
+pub fn take_last_report(dir: &Path) -> Option<PathBuf> {
+    let marker = dir.join(LAST_CRASH_FILE);
+    let path = fs::read_to_string(&marker).ok()?;
+    let _ = fs::remove_file(marker);
+    Some(PathBuf::from(path.trim()))
+}
+
+fn render_report(message: &str, backtrace: &str) -> String {
+    let context = context();
+    let mut report = String::new();
+    let _ = writeln!(report, "wyv {} crash report", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        report,
+        "os: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report, "features: {}", features::list().join(", "));
+    let _ = writeln!(report, "\n{}", message);
+
+    let _ = writeln!(report, "\nOpen buffers:");
+    for path in &context.buffers {
+        let _ = writeln!(report, "  {}", path.display());
+    }
+    let _ = writeln!(report, "\nLast events, oldest first:");
+    for event in &context.events {
+        let _ = writeln!(report, "  {}", event);
+    }
+    if let Some(logs) = &context.logs {
+        let _ = writeln!(report, "\nRecent log:");
+        for line in logs.last(LOG_LINES) {
+            let _ = writeln!(report, "  {}", line.trim_end());
+        }
+    }
+    let _ = writeln!(report, "\nBacktrace:\n{}", backtrace);
+    report
+}
+
+#[test]
+fn test_report_written_and_taken_once() {
+    let dir = std::env::temp_dir().join(format!("wyv-crash-test-{}", std::process::id()));
+    record_event("key ctrl-x");
+    let path = write_report(&dir, "panicked at src/ui.rs:1:1", "0: main").unwrap();
+
+    let report = fs::read_to_string(&path).unwrap();
+    assert!(report.starts_with("wyv "));
+    assert!(report.contains("panicked at src/ui.rs:1:1"));
+    assert!(report.contains("  key ctrl-x"));
+    assert!(report.contains("Backtrace:\n0: main"));
+
+    assert_eq!(take_last_report(&dir), Some(path));
+    assert_eq!(take_last_report(&dir), None);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,601 @@
+//! Parses JSON and YAML into a tree of keys and values that remembers where
+//! each value starts, for the structural viewer.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::buffer::Position;
+
+/// Arrays longer than this are folded into ranges of this many items.
+pub const ARRAY_CHUNK: usize = 100;
+
+/// How a node is reached from its parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    Root,
+    Name(String),
+    Index(usize),
+    /// A fold of array items, first and last.
+    Range(usize, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Object(Vec<DataNode>),
+    Array(Vec<DataNode>),
+    /// A string, number, boolean or null, as written.
+    Scalar(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataNode {
+    pub key: Key,
+    /// Where it is in the document, e.g. `$.servers[0].name`; unique, so
+    /// that it can name the node.
+    pub path: String,
+    pub value: Value,
+    /// Where the value starts in the source.
+    pub position: Position,
+}
+
+impl DataNode {
+    pub fn children(&self) -> &[DataNode] {
+        match &self.value {
+            Value::Object(children) | Value::Array(children) => children,
+            Value::Scalar(_) => &[],
+        }
+    }
+
+    pub fn has_children(&self) -> bool {
+        !self.children().is_empty()
+    }
+
+    /// The node's key and a summary of its value, e.g. `name: "wyv"`,
+    /// `servers: [3]` or `[0 … 99]`.
+    pub fn label(&self) -> String {
+        let value = match &self.value {
+            Value::Object(children) => format!("{{{}}}", children.len()),
+            Value::Array(children) => format!("[{}]", count(children)),
+            Value::Scalar(text) => text.clone(),
+        };
+        match &self.key {
+            Key::Root => value,
+            Key::Name(name) => format!("{}: {}", name, value),
+            Key::Index(i) => format!("{}: {}", i, value),
+            Key::Range(first, last) => format!("[{} … {}]", first, last),
+        }
+    }
+}
+
+/// How many items an array holds, looking through the folds of long ones.
+fn count(children: &[DataNode]) -> usize {
+    children
+        .iter()
+        .map(|c| match c.key {
+            Key::Range(first, last) => last - first + 1,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Whether `path` is a JSON or YAML file the viewer can show.
+pub fn is_structured(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("json" | "yaml" | "yml")
+    )
+}
+
+/// Parses `text`, as JSON or YAML going by the extension of `path`.
+pub fn parse(path: &Path, text: &str) -> Result<DataNode> {
+    let (value, position) = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => {
+            let mut parser = FlowParser::new(text, Position::default(), false);
+            let parsed = parser.value()?;
+            parser.end()?;
+            parsed
+        }
+        Some("yaml" | "yml") => yaml(text)?,
+        _ => bail!("not JSON or YAML"),
+    };
+    Ok(node(Key::Root, "$".to_string(), value, position))
+}
+
+/// A value before the nodes for it are named by their paths.
+#[derive(Debug)]
+enum Raw {
+    Object(Vec<(String, Raw, Position)>),
+    Array(Vec<(Raw, Position)>),
+    Scalar(String),
+}
+
+/// Names the nodes for `raw` and everything in it by their paths, folding
+/// long arrays.
+fn node(key: Key, path: String, raw: Raw, position: Position) -> DataNode {
+    let value = match raw {
+        Raw::Scalar(text) => Value::Scalar(text),
+        Raw::Object(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(name, raw, position)| {
+                    let path = child_path(&path, &name);
+                    node(Key::Name(name), path, raw, position)
+                })
+                .collect(),
+        ),
+        Raw::Array(items) => {
+            let mut nodes: Vec<DataNode> = items
+                .into_iter()
+                .enumerate()
+                .map(|(i, (raw, position))| {
+                    let path = format!("{}[{}]", path, i);
+                    node(Key::Index(i), path, raw, position)
+                })
+                .collect();
+            if nodes.len() > ARRAY_CHUNK {
+                let mut folds = Vec::new();
+                while !nodes.is_empty() {
+                    let rest = nodes.split_off(nodes.len().min(ARRAY_CHUNK));
+                    let first = folds.len() * ARRAY_CHUNK;
+                    let last = first + nodes.len() - 1;
+                    folds.push(DataNode {
+                        key: Key::Range(first, last),
+                        path: format!("{}[{}…{}]", path, first, last),
+                        position: nodes[0].position,
+                        value: Value::Array(std::mem::replace(&mut nodes, rest)),
+                    });
+                }
+                nodes = folds;
+            }
+            Value::Array(nodes)
+        }
+    };
+    DataNode {
+        key,
+        path,
+        value,
+        position,
+    }
+}
+
+/// The path of `name` in the node at `parent`: `.name` if it looks like an
+/// identifier, else quoted in brackets.
+fn child_path(parent: &str, name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    match plain {
+        true => format!("{}.{}", parent, name),
+        false => format!("{}[{:?}]", parent, name),
+    }
+}
+
+/// Parses JSON, and YAML's flow collections when `lenient`, where strings
+/// needn't be quoted.
+struct FlowParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    position: Position,
+    lenient: bool,
+}
+
+impl<'a> FlowParser<'a> {
+    /// A parser of `text`, which starts at `position` in the source.
+    fn new(text: &'a str, position: Position, lenient: bool) -> Self {
+        FlowParser {
+            chars: text.chars().peekable(),
+            position,
+            lenient,
+        }
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!(
+            "line {}, column {}: {}",
+            self.position.line + 1,
+            self.position.col + 1,
+            message
+        )
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        match c {
+            '\n' => self.position = Position::new(self.position.line + 1, 0),
+            _ => self.position.col += 1,
+        }
+        Some(c)
+    }
+
+    fn skip_space(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_space();
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(&format!("expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(&format!("expected `{}`", expected))),
+        }
+    }
+
+    /// Checks that only whitespace is left.
+    fn end(&mut self) -> Result<()> {
+        self.skip_space();
+        match self.chars.peek().copied() {
+            Some(c) => Err(self.error(&format!("unexpected `{}`", c))),
+            None => Ok(()),
+        }
+    }
+
+    fn value(&mut self) -> Result<(Raw, Position)> {
+        self.skip_space();
+        let start = self.position;
+        let raw = match self.chars.peek() {
+            Some('{') => self.object()?,
+            Some('[') => self.array()?,
+            Some('"') => Raw::Scalar(format!("{:?}", self.string()?)),
+            Some(_) => Raw::Scalar(self.word()?),
+            None => return Err(self.error("expected a value")),
+        };
+        Ok((raw, start))
+    }
+
+    fn object(&mut self) -> Result<Raw> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_space();
+        if self.chars.peek() == Some(&'}') {
+            self.bump();
+            return Ok(Raw::Object(entries));
+        }
+        loop {
+            self.skip_space();
+            let key = match self.chars.peek() {
+                Some('"') => self.string()?,
+                Some(_) if self.lenient => self.word()?,
+                _ => return Err(self.error("expected a key")),
+            };
+            self.expect(':')?;
+            let (value, position) = self.value()?;
+            entries.push((key, value, position));
+            self.skip_space();
+            match self.bump() {
+                Some(',') => continue,
+                Some('}') => return Ok(Raw::Object(entries)),
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Raw> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_space();
+        if self.chars.peek() == Some(&']') {
+            self.bump();
+            return Ok(Raw::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_space();
+            match self.bump() {
+                Some(',') => continue,
+                Some(']') => return Ok(Raw::Array(items)),
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => {
+                            let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        Some(c) => c,
+                        None => return Err(self.error("unterminated string")),
+                    };
+                    text.push(c);
+                }
+                Some(c) => text.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// A number, `true`, `false` or `null`, or any bare string when lenient.
+    fn word(&mut self) -> Result<String> {
+        let stops: &[char] = match self.lenient {
+            true => &[',', ']', '}', ':'],
+            false => &[',', ']', '}', ':', ' ', '\t', '\n', '\r'],
+        };
+        let mut word = String::new();
+        while let Some(&c) = self.chars.peek().filter(|c| !stops.contains(c)) {
+            word.push(c);
+            self.bump();
+        }
+        let word = word.trim().to_string();
+        let valid = self.lenient
+            || ["true", "false", "null"].contains(&word.as_str())
+            || word.parse::<f64>().is_ok();
+        match (word.is_empty(), valid) {
+            (true, _) => Err(self.error("expected a value")),
+            (false, true) => Ok(word),
+            (false, false) => Err(self.error(&format!("unexpected `{}`", word))),
+        }
+    }
+}
+
+/// A line of YAML that has content.
+#[derive(Debug, Clone)]
+struct YamlLine {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+/// Parses the block style YAML is mostly written in: mappings, sequences,
+/// scalars, `|` and `>` blocks and flow collections. Anchors, tags and
+/// multiple documents aren't understood.
+fn yaml(text: &str) -> Result<(Raw, Position)> {
+    let mut lines: Vec<YamlLine> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let content = strip_comment(line);
+        let trimmed = content.trim_start();
+        let directive = trimmed.starts_with('%') || trimmed == "---" || trimmed == "...";
+        if trimmed.is_empty() || directive {
+            continue;
+        }
+        lines.push(YamlLine {
+            number,
+            indent: content.len() - trimmed.len(),
+            text: trimmed.trim_end().to_string(),
+        });
+    }
+    if lines.is_empty() {
+        return Ok((Raw::Scalar("null".to_string()), Position::default()));
+    }
+    let mut i = 0;
+    let indent = lines[0].indent;
+    let parsed = block(&mut lines, &mut i, indent)?;
+    match lines.get(i) {
+        Some(line) => bail!("line {}: unexpected `{}`", line.number + 1, line.text),
+        None => Ok(parsed),
+    }
+}
+
+/// `line` up to a `#` comment that isn't in quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') if previous.is_whitespace() => return &line[..i],
+            _ => (),
+        }
+        previous = c;
+    }
+    line
+}
+
+/// The `key` and `value` of a mapping entry, split at the first `: ` (or
+/// trailing `:`) outside quotes.
+fn split_entry(text: &str) -> Option<(String, &str)> {
+    let mut quote = None;
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    for (j, &(i, c)) in chars.iter().enumerate() {
+        match (quote, c) {
+            (None, '"' | '\'') if j == 0 => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, ':') => {
+                let next = chars.get(j + 1).map(|&(_, c)| c);
+                if next.is_none() || next == Some(' ') {
+                    let key = unquote(text[..i].trim());
+                    return Some((key, text[i + 1..].trim()));
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn unquote(text: &str) -> String {
+    for q in ['"', '\''] {
+        if let Some(inner) = text.strip_prefix(q).and_then(|t| t.strip_suffix(q)) {
+            return inner.to_string();
+        }
+    }
+    text.to_string()
+}
+
+/// Parses the block starting at line `i`, whose lines are indented by
+/// `indent`.
+fn block(lines: &mut [YamlLine], i: &mut usize, indent: usize) -> Result<(Raw, Position)> {
+    let first = lines[*i].clone();
+    let position = Position::new(first.number, first.indent);
+    if first.text == "-" || first.text.starts_with("- ") {
+        let mut items = Vec::new();
+        while let Some(line) = lines.get(*i).filter(|l| l.indent == indent) {
+            let rest = match line.text.strip_prefix('-') {
+                Some(rest) if rest.is_empty() || rest.starts_with(' ') => rest.to_string(),
+                _ => break,
+            };
+            let position = Position::new(line.number, line.indent);
+            let trimmed = rest.trim_start();
+            if trimmed.is_empty() {
+                *i += 1;
+                items.push(nested(lines, i, indent, position)?);
+            } else {
+                // The item's content is a block of its own, indented to where
+                // it starts: `- a: 1` goes on with `  b: 2`.
+                let offset = indent + 1 + rest.len() - trimmed.len();
+                lines[*i].indent = offset;
+                lines[*i].text = trimmed.to_string();
+                items.push(block(lines, i, offset)?);
+            }
+        }
+        return Ok((Raw::Array(items), position));
+    }
+    if split_entry(&first.text).is_none() {
+        *i += 1;
+        return Ok((scalar(&first.text, position)?, position));
+    }
+
+    let mut entries = Vec::new();
+    while let Some(line) = lines.get(*i).filter(|l| l.indent == indent) {
+        let (key, value) = match split_entry(&line.text) {
+            Some(entry) => entry,
+            None => bail!("line {}: expected `key: value`", line.number + 1),
+        };
+        let value = value.to_string();
+        let start = Position::new(line.number, line.indent);
+        let value_col = line.indent + line.text.len() - value.len();
+        *i += 1;
+        let parsed = match value.as_str() {
+            "" => nested(lines, i, indent, start)?,
+            v if v.starts_with('|') || v.starts_with('>') => {
+                let mut text = Vec::new();
+                while let Some(line) = lines.get(*i).filter(|l| l.indent > indent) {
+                    text.push(line.text.clone());
+                    *i += 1;
+                }
+                let joined = text.join(if v.starts_with('|') { "\n" } else { " " });
+                (Raw::Scalar(format!("{:?}", joined)), start)
+            }
+            v => {
+                let position = Position::new(start.line, value_col);
+                (scalar(v, position)?, position)
+            }
+        };
+        entries.push((key, parsed.0, parsed.1));
+    }
+    Ok((Raw::Object(entries), position))
+}
+
+/// The value of a key or item with nothing after it: the block indented
+/// under it, a sequence at its own indentation, or null.
+fn nested(
+    lines: &mut [YamlLine],
+    i: &mut usize,
+    indent: usize,
+    position: Position,
+) -> Result<(Raw, Position)> {
+    match lines.get(*i) {
+        Some(next) if next.indent > indent => {
+            let indent = next.indent;
+            block(lines, i, indent)
+        }
+        Some(next) if next.indent == indent && next.text.starts_with("- ") => {
+            block(lines, i, indent)
+        }
+        _ => Ok((Raw::Scalar("null".to_string()), position)),
+    }
+}
+
+/// A scalar or flow collection written on one line.
+fn scalar(text: &str, position: Position) -> Result<Raw> {
+    if text.starts_with('[') || text.starts_with('{') {
+        let mut parser = FlowParser::new(text, position, true);
+        let (raw, _) = parser.value()?;
+        parser.end()?;
+        return Ok(raw);
+    }
+    Ok(match text.starts_with('"') || text.starts_with('\'') {
+        true => Raw::Scalar(format!("{:?}", unquote(text))),
+        false => Raw::Scalar(text.to_string()),
+    })
+}
+
+#[test]
+fn test_parse_json() {
+    let text = "{\n  \"name\": \"wyv\",\n  \"tags\": [1, true, null],\n  \"a b\": {}\n}\n";
+    let root = parse(Path::new("a.json"), text).unwrap();
+    assert_eq!(root.label(), "{3}");
+    let children = root.children();
+    assert_eq!(children[0].label(), "name: \"wyv\"");
+    assert_eq!(children[0].position, Position::new(1, 10));
+    assert_eq!(children[1].label(), "tags: [3]");
+    assert_eq!(children[1].children()[2].path, "$.tags[2]");
+    assert_eq!(children[1].children()[2].label(), "2: null");
+    assert_eq!(children[2].path, "$[\"a b\"]");
+
+    let err = parse(Path::new("a.json"), "{\"a\": tru}").unwrap_err();
+    assert_eq!(err.to_string(), "line 1, column 10: unexpected `tru`");
+    assert!(parse(Path::new("a.json"), "[1] 2").is_err());
+}
+
+#[test]
+fn test_parse_yaml() {
+    let text = "\
+# A comment
+name: wyv  # trailing
+servers:
+  - host: a.example
+    port: 80
+  - host: 'b'
+empty:
+list:
+- x
+- [1, two]
+script: |
+  echo hi
+  echo bye
+";
+    let root = parse(Path::new("a.yaml"), text).unwrap();
+    let labels: Vec<String> = root.children().iter().map(DataNode::label).collect();
+    assert_eq!(
+        labels,
+        [
+            "name: wyv",
+            "servers: [2]",
+            "empty: null",
+            "list: [2]",
+            "script: \"echo hi\\necho bye\"",
+        ]
+    );
+    let servers = &root.children()[1];
+    let first = &servers.children()[0];
+    assert_eq!(first.label(), "0: {2}");
+    assert_eq!(first.children()[1].label(), "port: 80");
+    assert_eq!(first.children()[1].path, "$.servers[0].port");
+    assert_eq!(first.children()[1].position, Position::new(4, 10));
+    assert_eq!(servers.children()[1].children()[0].label(), "host: \"b\"");
+    let flow = &root.children()[3].children()[1];
+    assert_eq!(flow.children()[1].label(), "1: two");
+    assert_eq!(flow.position, Position::new(9, 2));
+}
+
+#[test]
+fn test_long_arrays_fold() {
+    let items: Vec<String> = (0..250).map(|i| i.to_string()).collect();
+    let root = parse(Path::new("a.json"), &format!("[{}]", items.join(","))).unwrap();
+    assert_eq!(root.label(), "[250]");
+    let folds: Vec<String> = root.children().iter().map(DataNode::label).collect();
+    assert_eq!(folds, ["[0 … 99]", "[100 … 199]", "[200 … 249]"]);
+    assert_eq!(root.children()[2].children()[0].label(), "200: 200");
+}
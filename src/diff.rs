@@ -0,0 +1,452 @@
+//! Line diffs with word-level detail for changed lines, shown side by side
+//! or as a unified diff by [`crate::widgets::diff::DiffView`].
+
+use crate::widgets::viewport::Viewport;
+
+/// One step of an edit script turning `a` into `b`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// `a[i]` and `b[j]` are equal.
+    Equal(usize, usize),
+    /// `a[i]` is removed.
+    Delete(usize),
+    /// `b[j]` is inserted.
+    Insert(usize),
+}
+
+/// The shortest edit script turning `a` into `b`, from Myers' O(ND)
+/// algorithm.
+pub fn edits<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    // Common ends are cheap to strip and keep the search small.
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Equal(i, i)).collect();
+    edits.extend(myers(a_mid, b_mid).into_iter().map(|e| match e {
+        Edit::Equal(i, j) => Edit::Equal(i + prefix, j + prefix),
+        Edit::Delete(i) => Edit::Delete(i + prefix),
+        Edit::Insert(j) => Edit::Insert(j + prefix),
+    }));
+    edits.extend((0..suffix).map(|i| Edit::Equal(a.len() - suffix + i, b.len() - suffix + i)));
+    edits
+}
+
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let offset = max + 1;
+    // Furthest x reached on each diagonal k = x - y, indexed by k + offset.
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    // The part of `v` each round read, for walking back: round d reads
+    // diagonals -d-1..=d+1.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                y -= 1;
+                edits.push(Edit::Insert(y as usize));
+            } else {
+                x -= 1;
+                edits.push(Edit::Delete(x as usize));
+            }
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+/// Runs of text, marked when they differ from the other side.
+pub type Segments = Vec<(String, bool)>;
+
+/// A line on one side of a diff, split into runs that are marked when they
+/// differ from the paired line on the other side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    /// One-based line number.
+    pub number: usize,
+    pub segments: Segments,
+}
+
+impl DiffLine {
+    fn plain(number: usize, text: &str) -> Self {
+        DiffLine {
+            number: number + 1,
+            segments: vec![(text.to_string(), false)],
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.segments.iter().map(|(s, _)| s.as_str()).collect()
+    }
+}
+
+/// A row of the side-by-side view. Unchanged rows have the same text on
+/// both sides; a changed line has both; removed and added lines only one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub left: Option<DiffLine>,
+    pub right: Option<DiffLine>,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLayout {
+    SideBySide,
+    Unified,
+}
+
+/// Two texts compared line by line, with a scroll position shared by both
+/// sides.
+#[derive(Debug, Clone)]
+pub struct Diff {
+    pub left_title: String,
+    pub right_title: String,
+    rows: Vec<Row>,
+    pub layout: DiffLayout,
+    /// The rows (side by side) or lines (unified) shown. Any of them can
+    /// be scrolled to the top, the last included.
+    pub view: Viewport,
+}
+
+impl Diff {
+    pub fn new(left_title: &str, left: &str, right_title: &str, right: &str) -> Self {
+        let a: Vec<&str> = left.lines().collect();
+        let b: Vec<&str> = right.lines().collect();
+        Diff {
+            left_title: left_title.to_string(),
+            right_title: right_title.to_string(),
+            rows: rows(&a, &b),
+            layout: DiffLayout::SideBySide,
+            view: Viewport::default().past_end(true),
+        }
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.rows.iter().all(|r| !r.changed)
+    }
+
+    /// The unified form: unchanged lines marked ` `, then each run of
+    /// changes as its removed lines (`-`) followed by its added ones (`+`).
+    pub fn unified(&self) -> Vec<(char, &DiffLine)> {
+        let mut lines = Vec::new();
+        let mut added = Vec::new();
+        for row in &self.rows {
+            if !row.changed {
+                lines.append(&mut added);
+                lines.extend(row.left.as_ref().map(|l| (' ', l)));
+                continue;
+            }
+            lines.extend(row.left.as_ref().map(|l| ('-', l)));
+            added.extend(row.right.as_ref().map(|l| ('+', l)));
+        }
+        lines.append(&mut added);
+        lines
+    }
+
+    /// The line at the top of the view, by its one-based number in the
+    /// right text, or in the left one with `true` if only the left has it.
+    pub fn top_line(&self) -> Option<(usize, bool)> {
+        let row = match self.layout {
+            DiffLayout::SideBySide => self.rows.get(self.scroll())?,
+            DiffLayout::Unified => {
+                let lines = self.unified();
+                let (mark, line) = lines.get(self.scroll())?;
+                match mark {
+                    '-' => return Some((line.number, true)),
+                    '+' => return Some((line.number, false)),
+                    // Unchanged lines are listed by their left number.
+                    _ => self.rows.iter().find(|r| {
+                        !r.changed && r.left.as_ref().map(|l| l.number) == Some(line.number)
+                    })?,
+                }
+            }
+        };
+        match (&row.right, &row.left) {
+            (Some(right), _) => Some((right.number, false)),
+            (None, Some(left)) => Some((left.number, true)),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether each line of the current layout is changed.
+    fn changed(&self) -> Vec<bool> {
+        match self.layout {
+            DiffLayout::SideBySide => self.rows.iter().map(|r| r.changed).collect(),
+            DiffLayout::Unified => self.unified().iter().map(|(c, _)| *c != ' ').collect(),
+        }
+    }
+
+    pub fn toggle_layout(&mut self) {
+        // Keep roughly the same place: map through the side-by-side rows.
+        let changed = self.changed();
+        self.layout = match self.layout {
+            DiffLayout::SideBySide => DiffLayout::Unified,
+            DiffLayout::Unified => DiffLayout::SideBySide,
+        };
+        let ratio = self.scroll() as f64 / changed.len().max(1) as f64;
+        self.scroll_to((ratio * self.changed().len() as f64) as usize);
+    }
+
+    /// The first row or line shown.
+    pub fn scroll(&self) -> usize {
+        self.view.offset()
+    }
+
+    pub fn scroll_to(&mut self, line: usize) {
+        let len = self.changed().len();
+        self.view.scroll_to(line, len);
+    }
+
+    pub fn scroll_by(&mut self, lines: isize) {
+        let len = self.changed().len();
+        self.view.scroll_by(lines, len);
+    }
+
+    /// Scrolls to the start of the next run of changes, or the previous one
+    /// with `back`.
+    pub fn jump_change(&mut self, back: bool) {
+        let changed = self.changed();
+        let mut starts = (0..changed.len()).filter(|&i| changed[i] && (i == 0 || !changed[i - 1]));
+        let target = match back {
+            true => starts.rfind(|&i| i < self.scroll()),
+            false => starts.find(|&i| i > self.scroll()),
+        };
+        if let Some(target) = target {
+            self.scroll_to(target);
+        }
+    }
+}
+
+fn rows(a: &[&str], b: &[&str]) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let flush = |rows: &mut Vec<Row>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+        for i in 0..removed.len().max(added.len()) {
+            let (left, right) = match (removed.get(i), added.get(i)) {
+                (Some(&l), Some(&r)) => {
+                    let (left, right) = word_diff(a[l], b[r]);
+                    (
+                        Some(DiffLine {
+                            number: l + 1,
+                            segments: left,
+                        }),
+                        Some(DiffLine {
+                            number: r + 1,
+                            segments: right,
+                        }),
+                    )
+                }
+                (l, r) => (
+                    l.map(|&l| DiffLine::plain(l, a[l])),
+                    r.map(|&r| DiffLine::plain(r, b[r])),
+                ),
+            };
+            rows.push(Row {
+                left,
+                right,
+                changed: true,
+            });
+        }
+        removed.clear();
+        added.clear();
+    };
+    for edit in edits(a, b) {
+        match edit {
+            Edit::Equal(i, j) => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push(Row {
+                    left: Some(DiffLine::plain(i, a[i])),
+                    right: Some(DiffLine::plain(j, b[j])),
+                    changed: false,
+                });
+            }
+            Edit::Delete(i) => removed.push(i),
+            Edit::Insert(j) => added.push(j),
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+/// Splits a line into words, runs of spaces and single punctuation marks.
+fn words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, n)| n);
+        if class(c) == 2 || next.is_none_or(|n| class(n) != class(c)) {
+            let end = i + c.len_utf8();
+            words.push(&line[start..end]);
+            start = end;
+        }
+    }
+    words
+}
+
+/// Both lines as runs, marking the words that differ.
+fn word_diff(left: &str, right: &str) -> (Segments, Segments) {
+    let (a, b) = (words(left), words(right));
+    let (mut l, mut r) = (Segments::new(), Segments::new());
+    let push = |runs: &mut Segments, word: &str, changed: bool| match runs.last_mut() {
+        Some((text, c)) if *c == changed => text.push_str(word),
+        _ => runs.push((word.to_string(), changed)),
+    };
+    for edit in edits(&a, &b) {
+        match edit {
+            Edit::Equal(i, j) => {
+                push(&mut l, a[i], false);
+                push(&mut r, b[j], false);
+            }
+            Edit::Delete(i) => push(&mut l, a[i], true),
+            Edit::Insert(j) => push(&mut r, b[j], true),
+        }
+    }
+    (l, r)
+}
+
+#[test]
+fn test_edits_are_minimal() {
+    let a: Vec<char> = "ABCABBA".chars().collect();
+    let b: Vec<char> = "CBABAC".chars().collect();
+    let script = edits(&a, &b);
+    let changes = script
+        .iter()
+        .filter(|e| !matches!(e, Edit::Equal(..)))
+        .count();
+    assert_eq!(changes, 5);
+
+    // Applying the script to `a` gives `b`.
+    let rebuilt: Vec<char> = script
+        .iter()
+        .filter_map(|e| match *e {
+            Edit::Equal(i, _) => Some(a[i]),
+            Edit::Insert(j) => Some(b[j]),
+            Edit::Delete(_) => None,
+        })
+        .collect();
+    assert_eq!(rebuilt, b);
+    assert!(edits::<char>(&[], &[]).is_empty());
+}
+
+#[test]
+fn test_rows_pair_changed_lines_with_word_detail() {
+    let diff = Diff::new(
+        "a",
+        "one\nlet x = 1;\nthree\nfour\n",
+        "b",
+        "one\nlet y = 1;\nthree\nadded\nfour\n",
+    );
+    let rows = diff.rows();
+    assert_eq!(rows.len(), 5);
+    assert!(!rows[0].changed && rows[1].changed && rows[3].changed);
+    assert_eq!(
+        rows[1].left.as_ref().unwrap().segments,
+        [
+            ("let ".to_string(), false),
+            ("x".to_string(), true),
+            (" = 1;".to_string(), false)
+        ]
+    );
+    assert!(rows[3].left.is_none());
+    assert_eq!(rows[3].right.as_ref().unwrap().number, 4);
+
+    let unified: Vec<String> = diff
+        .unified()
+        .iter()
+        .map(|(c, l)| format!("{}{}", c, l.text()))
+        .collect();
+    assert_eq!(
+        unified,
+        [
+            " one",
+            "-let x = 1;",
+            "+let y = 1;",
+            " three",
+            "+added",
+            " four"
+        ]
+    );
+}
+
+#[test]
+fn test_jump_between_changes() {
+    let mut diff = Diff::new("a", "1\n2\n3\n4\n5\n", "b", "1\nx\n3\n4\ny\n");
+    diff.jump_change(false);
+    assert_eq!(diff.scroll(), 1);
+    diff.jump_change(false);
+    assert_eq!(diff.scroll(), 4);
+    diff.jump_change(true);
+    assert_eq!(diff.scroll(), 1);
+}
+
+#[test]
+fn test_top_line_prefers_the_right_side() {
+    let mut diff = Diff::new("a", "gone\nkept\n", "b", "kept\nnew\n");
+    assert_eq!(diff.top_line(), Some((1, true)));
+    diff.scroll_by(1);
+    assert_eq!(diff.top_line(), Some((1, false)));
+    diff.toggle_layout();
+    diff.scroll_to(1);
+    assert_eq!(diff.top_line(), Some((1, false)));
+    diff.scroll_to(2);
+    assert_eq!(diff.top_line(), Some((2, false)));
+}
@@ -0,0 +1,57 @@
+//! Errors worth telling the user about, each naming what was being done and
+//! to which path. They end up in the status line rather than ending the
+//! session; anything else stays an [`anyhow::Error`].
+
+use std::{io, path::PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("can't {operation} {}: {source}", .path.display())]
+    Io {
+        /// What was being done, e.g. "read directory".
+        operation: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{}: the name isn't valid UTF-8", .path.display())]
+    NotUtf8 { path: PathBuf },
+    #[error("{}: not a file, directory or link", .path.display())]
+    Unsupported { path: PathBuf },
+    #[error("saved by a newer wyv: format {found}, where this one reads up to {supported}")]
+    NewerFormat { found: u32, supported: u32 },
+    #[error("couldn't draw the screen: {0}")]
+    Draw(#[source] io::Error),
+    #[error("cancelled")]
+    Cancelled,
+}
+
+impl Error {
+    /// Wraps an [`io::Error`] from doing `operation` to `path`, for
+    /// `map_err`.
+    pub fn io(operation: &'static str, path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| Error::Io {
+            operation,
+            path,
+            source,
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[test]
+fn test_messages_name_the_operation_and_path() {
+    let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+    let e = Error::io("read directory", "/srv/private")(denied);
+    assert_eq!(
+        e.to_string(),
+        "can't read directory /srv/private: permission denied"
+    );
+    assert!(std::error::Error::source(&e).is_some());
+    let e = Error::Unsupported {
+        path: "/dev/null".into(),
+    };
+    assert_eq!(e.to_string(), "/dev/null: not a file, directory or link");
+}
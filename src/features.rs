@@ -0,0 +1,48 @@
+//! Which optional subsystems this build includes. Minimal builds leave out
+//! the heavy ones with `--no-default-features`; the UI checks here to hide
+//! what isn't available.
+
+/// Every Cargo feature wyv knows about, and whether it was compiled in.
+pub const FEATURES: &[(&str, bool)] = &[
+    ("lua", cfg!(feature = "lua")),
+    ("remote", cfg!(feature = "remote")),
+    ("git", cfg!(feature = "git")),
+    ("lsp", cfg!(feature = "lsp")),
+    ("terminal", cfg!(feature = "terminal")),
+    ("ssh", cfg!(feature = "ssh")),
+];
+
+pub fn enabled(name: &str) -> bool {
+    FEATURES.iter().any(|&(n, on)| n == name && on)
+}
+
+/// Names of the features compiled in.
+pub fn list() -> Vec<&'static str> {
+    FEATURES
+        .iter()
+        .filter(|(_, on)| *on)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Fails with a message naming the missing feature if `name` is disabled.
+pub fn require(name: &str) -> anyhow::Result<()> {
+    match enabled(name) {
+        true => Ok(()),
+        false => Err(missing(name)),
+    }
+}
+
+pub fn missing(name: &str) -> anyhow::Error {
+    anyhow::anyhow!("wyv was built without the `{}` feature", name)
+}
+
+#[test]
+fn test_require_names_missing_feature() {
+    assert_eq!(require("lua").is_ok(), cfg!(feature = "lua"));
+    assert!(!enabled("bogus"));
+    assert_eq!(
+        require("bogus").unwrap_err().to_string(),
+        "wyv was built without the `bogus` feature"
+    );
+}
@@ -0,0 +1,166 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::CancelToken;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FollowOptions {
+    /// Lines matching this are shown as errors while following a file.
+    pub error: String,
+    /// Lines matching this are shown as warnings.
+    pub warning: String,
+}
+
+impl Default for FollowOptions {
+    fn default() -> Self {
+        FollowOptions {
+            error: r"\b(ERROR|FATAL|CRITICAL|PANIC)\b|^error(\[\w+\])?:".to_string(),
+            warning: r"\b(WARN|WARNING)\b|^warning:".to_string(),
+        }
+    }
+}
+
+/// How serious a line of a log is, by the patterns it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+/// A file being followed: its buffer is reloaded as the file grows and,
+/// unless paused, its cursor kept on the last line.
+#[derive(Debug)]
+pub struct Follow {
+    path: PathBuf,
+    pub paused: bool,
+    error: Regex,
+    warning: Regex,
+    /// Stops the watcher once the file is no longer followed.
+    stop: CancelToken,
+}
+
+impl Follow {
+    /// Follows `path`, returning the watcher to poll it with.
+    pub fn new(path: &Path, options: &FollowOptions) -> Result<(Self, FileWatcher)> {
+        let compile = |key, pattern: &str| {
+            Regex::new(pattern).map_err(|e| anyhow!("invalid `follow.{}`: {}", key, e))
+        };
+        let stop = CancelToken::default();
+        let follow = Follow {
+            path: path.to_owned(),
+            paused: false,
+            error: compile("error", &options.error)?,
+            warning: compile("warning", &options.warning)?,
+            stop: stop.clone(),
+        };
+        Ok((follow, FileWatcher::new(path, stop)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The level of `line`, errors winning over warnings.
+    pub fn level(&self, line: &str) -> Option<Level> {
+        if self.error.is_match(line) {
+            Some(Level::Error)
+        } else if self.warning.is_match(line) {
+            Some(Level::Warning)
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Follow {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+/// Watches a followed file for changes by polling its size and modified
+/// time, until the [`Follow`] it belongs to is dropped.
+#[derive(Debug)]
+pub struct FileWatcher {
+    path: PathBuf,
+    seen: Option<(u64, SystemTime)>,
+    stop: CancelToken,
+}
+
+impl FileWatcher {
+    fn new(path: &Path, stop: CancelToken) -> Self {
+        FileWatcher {
+            path: path.to_owned(),
+            seen: stat(path),
+            stop,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.is_cancelled()
+    }
+
+    /// Whether the file changed since the last call.
+    pub fn poll(&mut self) -> bool {
+        let seen = stat(&self.path);
+        if seen == self.seen {
+            return false;
+        }
+        self.seen = seen;
+        true
+    }
+}
+
+fn stat(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+#[test]
+fn test_levels_and_watching() {
+    let dir = std::env::temp_dir().join(format!("wyv-follow-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("server.log");
+    fs::write(&path, "started\n").unwrap();
+
+    let (follow, mut watcher) = Follow::new(&path, &FollowOptions::default()).unwrap();
+    assert_eq!(follow.level("12:00 ERROR disk full"), Some(Level::Error));
+    assert_eq!(
+        follow.level("error[E0308]: mismatched types"),
+        Some(Level::Error)
+    );
+    assert_eq!(follow.level("12:00 WARN slow query"), Some(Level::Warning));
+    assert_eq!(
+        follow.level("warning: unused variable"),
+        Some(Level::Warning)
+    );
+    assert_eq!(follow.level("no errors found"), None);
+
+    assert!(!watcher.poll());
+    fs::write(&path, "started\nlistening\n").unwrap();
+    assert!(watcher.poll());
+    assert!(!watcher.poll());
+
+    assert!(!watcher.is_stopped());
+    drop(follow);
+    assert!(watcher.is_stopped());
+
+    let options = FollowOptions {
+        error: "(".to_string(),
+        ..FollowOptions::default()
+    };
+    assert!(Follow::new(&path, &options).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
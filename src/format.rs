@@ -0,0 +1,209 @@
+//! Formatting buffers with external programs such as rustfmt, which read
+//! the text on stdin and write it formatted to stdout. Files without one
+//! are left to their language server.
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buffer::Position,
+    diff::{self, Edit},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormatterConfig {
+    pub command: String,
+    /// Arguments, with `{path}` replaced by the file's path.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Formatters by file extension, written in the config as
+/// `[formatters.rs]` tables. User entries are merged over the defaults; one
+/// with an empty command leaves the extension to its language server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    from = "HashMap<String, FormatterConfig>",
+    into = "HashMap<String, FormatterConfig>"
+)]
+pub struct Formatters {
+    by_extension: HashMap<String, FormatterConfig>,
+}
+
+impl Default for Formatters {
+    fn default() -> Self {
+        let formatter = |command: &str, args: &[&str]| FormatterConfig {
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        };
+        let prettier = formatter("prettier", &["--stdin-filepath", "{path}"]);
+        let mut by_extension = HashMap::new();
+        by_extension.insert(
+            "rs".to_string(),
+            formatter("rustfmt", &["--edition", "2021"]),
+        );
+        by_extension.insert("py".to_string(), formatter("black", &["--quiet", "-"]));
+        for extension in [
+            "js", "jsx", "ts", "tsx", "css", "scss", "html", "json", "md", "yaml",
+        ] {
+            by_extension.insert(extension.to_string(), prettier.clone());
+        }
+        Formatters { by_extension }
+    }
+}
+
+impl From<HashMap<String, FormatterConfig>> for Formatters {
+    fn from(overrides: HashMap<String, FormatterConfig>) -> Self {
+        let mut formatters = Formatters::default();
+        formatters.by_extension.extend(overrides);
+        formatters
+    }
+}
+
+impl From<Formatters> for HashMap<String, FormatterConfig> {
+    fn from(formatters: Formatters) -> Self {
+        formatters.by_extension
+    }
+}
+
+impl Formatters {
+    /// The formatter for `path`'s file type, if it has one.
+    pub fn for_path(&self, path: &Path) -> Option<&FormatterConfig> {
+        let extension = path.extension()?.to_str()?;
+        self.by_extension
+            .get(extension)
+            .filter(|f| !f.command.is_empty())
+    }
+}
+
+/// Runs `formatter` on `text`, the contents of `path`, returning the
+/// formatted text.
+pub fn run(formatter: &FormatterConfig, path: &Path, text: &str) -> Result<String> {
+    let path_arg = path.to_string_lossy();
+    let args = formatter
+        .args
+        .iter()
+        .map(|a| a.replace("{path}", &path_arg));
+    let mut child = Command::new(&formatter.command)
+        .args(args)
+        .current_dir(path.parent().unwrap_or(Path::new(".")))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("could not run {}", formatter.command))?;
+    // Written from a thread so a formatter that answers as it reads can't
+    // fill its stdout while we wait to finish writing.
+    let mut stdin = child.stdin.take().context("no stdin")?;
+    let input = text.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or("failed");
+        bail!("{}: {}", formatter.command, reason.trim());
+    }
+    String::from_utf8(output.stdout).with_context(|| format!("{}: output", formatter.command))
+}
+
+/// The fewest whole-line replacements turning `old` into `new`, as ranges
+/// of `old` and their new text.
+pub fn line_edits(old: &str, new: &str) -> Vec<(Position, Position, String)> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let mut edits = Vec::new();
+    let mut line = 0;
+    // The hunk being built: where it starts, how many lines it removes and
+    // what it puts in their place.
+    let mut hunk: Option<(usize, usize, String)> = None;
+    let mut flush = |hunk: &mut Option<(usize, usize, String)>| {
+        if let Some((start, removed, text)) = hunk.take() {
+            let end = Position::new(start + removed, 0);
+            edits.push((Position::new(start, 0), end, text));
+        }
+    };
+    for edit in diff::edits(&old_lines, &new_lines) {
+        match edit {
+            Edit::Equal(_, _) => {
+                flush(&mut hunk);
+                line += 1;
+            }
+            Edit::Delete(_) => {
+                hunk.get_or_insert((line, 0, String::new())).1 += 1;
+                line += 1;
+            }
+            Edit::Insert(j) => hunk
+                .get_or_insert((line, 0, String::new()))
+                .2
+                .push_str(new_lines[j]),
+        }
+    }
+    flush(&mut hunk);
+    edits
+}
+
+#[test]
+fn test_line_edits() {
+    let old = "fn main(){\nlet x=1;\n}\n";
+    let new = "fn main() {\n    let x = 1;\n}\n";
+    assert_eq!(
+        line_edits(old, new),
+        [(
+            Position::new(0, 0),
+            Position::new(2, 0),
+            "fn main() {\n    let x = 1;\n".to_string()
+        )]
+    );
+    assert!(line_edits(new, new).is_empty());
+    assert_eq!(
+        line_edits("a\nb", "a\nb\nc\n"),
+        [(
+            Position::new(1, 0),
+            Position::new(2, 0),
+            "b\nc\n".to_string()
+        )]
+    );
+}
+
+#[test]
+fn test_formatters_merge_over_defaults() {
+    let overrides = HashMap::from([
+        (
+            "py".to_string(),
+            FormatterConfig {
+                command: String::new(),
+                args: Vec::new(),
+            },
+        ),
+        (
+            "go".to_string(),
+            FormatterConfig {
+                command: "gofmt".to_string(),
+                args: Vec::new(),
+            },
+        ),
+    ]);
+    let formatters = Formatters::from(overrides);
+    assert_eq!(
+        formatters.for_path(Path::new("a/b.rs")).unwrap().command,
+        "rustfmt"
+    );
+    assert_eq!(
+        formatters.for_path(Path::new("b.go")).unwrap().command,
+        "gofmt"
+    );
+    assert!(formatters.for_path(Path::new("c.py")).is_none());
+    assert!(formatters.for_path(Path::new("Makefile")).is_none());
+}
@@ -0,0 +1,60 @@
+//! Loose matching for filters and pickers: a pattern matches when its
+//! characters appear in the text in order, so `fxtr` finds `fix tree`.
+
+/// Whether every character of `pattern` occurs in `text`, in order,
+/// ignoring case. An empty pattern matches everything.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+        .all(|p| text.any(|t| t == p))
+}
+
+/// How well `pattern` matches `text`, higher being better, or `None` if it
+/// doesn't. Characters starting a word or following the last match count
+/// for more, so `gl` ranks `git_log` above `angle`.
+pub fn score(pattern: &str, text: &str) -> Option<usize> {
+    let text: Vec<char> = text.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    for p in pattern.chars().filter(|c| !c.is_whitespace()) {
+        let p = p.to_lowercase().next().unwrap_or(p);
+        let idx = next + text[next..].iter().position(|t| t.to_lowercase().eq([p]))?;
+        let prev = idx.checked_sub(1).map(|i| text[i]);
+        let word_start = match prev {
+            None => true,
+            Some(prev) => {
+                !prev.is_alphanumeric() || prev.is_lowercase() && text[idx].is_uppercase()
+            }
+        };
+        score += 1;
+        if word_start {
+            score += 2;
+        }
+        if idx == next && idx > 0 {
+            score += 3;
+        }
+        next = idx + 1;
+    }
+    Some(score)
+}
+
+#[test]
+fn test_matches_in_order_ignoring_case() {
+    assert!(matches("", "anything"));
+    assert!(matches("fxtr", "Fix tree"));
+    assert!(matches("fix tree", "Fix the tree"));
+    assert!(!matches("trfx", "Fix tree"));
+    assert!(!matches("fixx", "Fix tree"));
+}
+
+#[test]
+fn test_score_prefers_word_starts() {
+    assert_eq!(score("", "anything"), Some(0));
+    assert_eq!(score("gl", "log"), None);
+    assert!(score("gl", "git_log") > score("gl", "angle"));
+    assert!(score("gl", "GitLog") > score("gl", "angle"));
+    assert!(score("log", "log") > score("log", "lo_g"));
+}
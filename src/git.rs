@@ -0,0 +1,595 @@
+//! Git integration through the `git` command line, so no library has to
+//! agree with the user's git version or config.
+
+use std::path::{Path, PathBuf};
+#[cfg(feature = "git")]
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "git")]
+use anyhow::{bail, Context};
+
+#[cfg(not(feature = "git"))]
+use crate::features;
+
+/// The hash git uses for lines that are not committed yet.
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+
+/// Who last changed one line of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameLine {
+    pub commit: String,
+    pub author: String,
+    /// `YYYY-MM-DD`, in the author's time zone.
+    pub date: String,
+    pub summary: String,
+}
+
+impl BlameLine {
+    pub fn short_commit(&self) -> &str {
+        &self.commit[..self.commit.len().min(8)]
+    }
+
+    pub fn is_committed(&self) -> bool {
+        self.commit != UNCOMMITTED
+    }
+
+    /// One-line form for the blame column and inline annotations.
+    pub fn describe(&self) -> String {
+        match self.is_committed() {
+            true => format!("{} {} {}", self.short_commit(), self.author, self.date),
+            false => "not committed yet".to_string(),
+        }
+    }
+}
+
+/// A commit as listed by [`log`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    pub hash: String,
+    pub author: String,
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    pub subject: String,
+}
+
+impl Commit {
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..self.hash.len().min(8)]
+    }
+
+    /// One-line form for the log panel, which filters on it too.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.short_hash(),
+            self.date,
+            self.author,
+            self.subject
+        )
+    }
+}
+
+/// A local branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    pub name: String,
+    /// Whether it is checked out.
+    pub current: bool,
+}
+
+/// A changed file in `git status`, with its path relative to the repository
+/// root and the two status letters git prints for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub path: String,
+    /// Status in the index: `M`, `A`, `D`, `R`, ... or a space.
+    pub staged: char,
+    /// Status in the working tree, or `?` for untracked files.
+    pub unstaged: char,
+}
+
+impl StatusEntry {
+    pub fn is_staged(&self) -> bool {
+        !matches!(self.staged, ' ' | '?')
+    }
+
+    pub fn is_untracked(&self) -> bool {
+        self.unstaged == '?'
+    }
+}
+
+/// One hunk of a diff, with the header of the file it belongs to so it can
+/// be applied on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    file_header: String,
+    /// The `@@ -a,b +c,d @@` line.
+    pub header: String,
+    pub lines: Vec<String>,
+}
+
+impl Hunk {
+    /// A patch containing just this hunk.
+    pub fn patch(&self) -> String {
+        let mut patch = self.file_header.clone();
+        patch.push_str(&self.header);
+        patch.push('\n');
+        for line in &self.lines {
+            patch.push_str(line);
+            patch.push('\n');
+        }
+        patch
+    }
+}
+
+/// Runs git in `dir` and returns its output, failing with git's own message.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    git_with_input(dir, args, None)
+}
+
+/// Like [`git`], writing `input` to its stdin.
+#[cfg(feature = "git")]
+fn git_with_input(dir: &Path, args: &[&str], input: Option<&str>) -> Result<String> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("could not run git")?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git {}: {}", args[0], stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(not(feature = "git"))]
+fn git_with_input(_dir: &Path, _args: &[&str], _input: Option<&str>) -> Result<String> {
+    Err(features::missing("git"))
+}
+
+/// The top directory of the repository containing `dir`.
+pub fn repo_root(dir: &Path) -> Result<PathBuf> {
+    let output = git(dir, &["rev-parse", "--show-toplevel"])?;
+    Ok(PathBuf::from(output.trim_end()))
+}
+
+/// The files under `dir` that git doesn't ignore, tracked or not, relative
+/// to `dir`.
+pub fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let output = git(
+        dir,
+        &[
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ],
+    )?;
+    Ok(output
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Changed and untracked files in the repository at `root`.
+pub fn status(root: &Path) -> Result<Vec<StatusEntry>> {
+    let output = git(root, &["status", "--porcelain=v1", "-z"])?;
+    Ok(parse_status(&output))
+}
+
+/// Like [`status`], but only of `paths`, relative to `root`.
+pub fn status_of(root: &Path, paths: &[String]) -> Result<Vec<StatusEntry>> {
+    let mut args = vec!["status", "--porcelain=v1", "-z", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    let output = git(root, &args)?;
+    Ok(parse_status(&output))
+}
+
+/// Hunks of `path`'s changes: those staged in the index if `staged`, else
+/// those not staged yet.
+pub fn hunks(root: &Path, path: &str, staged: bool) -> Result<Vec<Hunk>> {
+    let mut args = vec!["diff", "--no-color", "--no-ext-diff"];
+    if staged {
+        args.push("--cached");
+    }
+    args.extend(["--", path]);
+    Ok(parse_hunks(&git(root, &args)?))
+}
+
+pub fn stage(root: &Path, path: &str) -> Result<()> {
+    git(root, &["add", "--", path]).map(drop)
+}
+
+pub fn unstage(root: &Path, path: &str) -> Result<()> {
+    git(root, &["restore", "--staged", "--", path]).map(drop)
+}
+
+/// Stages a hunk of unstaged changes, or with `reverse` unstages a hunk of
+/// staged ones.
+pub fn apply_to_index(root: &Path, hunk: &Hunk, reverse: bool) -> Result<()> {
+    let mut args = vec!["apply", "--cached"];
+    if reverse {
+        args.push("--reverse");
+    }
+    args.push("-");
+    git_with_input(root, &args, Some(&hunk.patch())).map(drop)
+}
+
+/// Local branches, sorted by name.
+pub fn branches(root: &Path) -> Result<Vec<Branch>> {
+    let output = git(root, &["branch", "--format=%(HEAD)%(refname:short)"])?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let (head, name) = line.split_at_checked(1)?;
+            Some(Branch {
+                name: name.to_string(),
+                current: head == "*",
+            })
+        })
+        .collect())
+}
+
+/// Files that differ between two commits, as absolute paths.
+pub fn changed_between(root: &Path, from: &str, to: &str) -> Result<Vec<PathBuf>> {
+    let output = git(root, &["diff", "--name-only", "-z", from, to, "--"])?;
+    Ok(output
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(|p| root.join(p))
+        .collect())
+}
+
+/// The commit where `HEAD` and `base` last shared history.
+pub fn merge_base(root: &Path, base: &str) -> Result<String> {
+    let output = git(root, &["merge-base", "HEAD", base])?;
+    Ok(output.trim().to_string())
+}
+
+/// The name of the checked out branch, or `HEAD` if detached.
+pub fn current_branch(root: &Path) -> Result<String> {
+    let output = git(root, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    Ok(output.trim().to_string())
+}
+
+/// Tracked files whose work tree contents differ from `rev`, with git's
+/// letter for the change (`A`, `M`, `D`...) and their path from `root`.
+pub fn changed_since(root: &Path, rev: &str) -> Result<Vec<(char, String)>> {
+    let output = git(
+        root,
+        &["diff", "--name-status", "--no-renames", "-z", rev, "--"],
+    )?;
+    Ok(parse_name_status(&output))
+}
+
+/// Checks out the branch `name`.
+pub fn switch(root: &Path, name: &str) -> Result<()> {
+    git(root, &["switch", "--quiet", name]).map(drop)
+}
+
+/// Creates a branch `name` at the current commit and checks it out.
+pub fn create_branch(root: &Path, name: &str) -> Result<()> {
+    git(root, &["switch", "--quiet", "--create", name]).map(drop)
+}
+
+/// Deletes the branch `name`, which has to be merged.
+pub fn delete_branch(root: &Path, name: &str) -> Result<()> {
+    git(root, &["branch", "--delete", name]).map(drop)
+}
+
+/// Commits what is staged, returning git's one-line summary of the commit.
+pub fn commit(root: &Path, message: &str) -> Result<String> {
+    let output = git_with_input(root, &["commit", "--quiet", "--file", "-"], Some(message))?;
+    let summary = git(root, &["log", "-1", "--format=%h %s"])?;
+    Ok(match output.trim() {
+        "" => summary.trim().to_string(),
+        output => output.to_string(),
+    })
+}
+
+/// Splits `path` into the directory to run git in and the file name.
+fn locate(path: &Path) -> Result<(&Path, String)> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", path.display()))?;
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+    Ok((
+        dir.unwrap_or(Path::new(".")),
+        name.to_string_lossy().into_owned(),
+    ))
+}
+
+/// Blame for every line of the file at `path`, as last saved.
+pub fn blame(path: &Path) -> Result<Vec<BlameLine>> {
+    let (dir, name) = locate(path)?;
+    let output = git(dir, &["blame", "--line-porcelain", "--", &name])?;
+    Ok(parse_blame(&output))
+}
+
+/// The contents of the file at `path` as of `rev`, e.g. `HEAD`.
+pub fn file_at(path: &Path, rev: &str) -> Result<String> {
+    let (dir, name) = locate(path)?;
+    git(dir, &["show", &format!("{}:./{}", rev, name)])
+}
+
+/// The contents of `path`, relative to the repository at `root`, as of
+/// `rev`. Unlike [`file_at`] the file needn't exist any more.
+pub fn file_in(root: &Path, rev: &str, path: &str) -> Result<String> {
+    git(root, &["show", &format!("{}:{}", rev, path)])
+}
+
+/// The commit message and patch of `commit`, as `git show` prints them,
+/// limited to the changes to `path` if given.
+pub fn show(dir: &Path, commit: &str, path: Option<&str>) -> Result<String> {
+    let mut args = vec!["show", "--stat", "--patch", "--no-color", commit];
+    if let Some(path) = path {
+        args.extend(["--", path]);
+    }
+    git(dir, &args)
+}
+
+/// Most recent commits first, at most `limit` of them, only those touching
+/// `path` (following renames) if given.
+pub fn log(root: &Path, path: Option<&str>, limit: usize) -> Result<Vec<Commit>> {
+    let limit = format!("--max-count={}", limit);
+    let mut args = vec![
+        "log",
+        "--date=short",
+        "--format=%H%x1f%an%x1f%ad%x1f%s",
+        &limit,
+    ];
+    if let Some(path) = path {
+        args.extend(["--follow", "--", path]);
+    }
+    Ok(parse_log(&git(root, &args)?))
+}
+
+fn parse_name_status(output: &str) -> Vec<(char, String)> {
+    let mut fields = output.split('\0').filter(|f| !f.is_empty());
+    let mut changes = Vec::new();
+    while let (Some(status), Some(path)) = (fields.next(), fields.next()) {
+        changes.push((status.chars().next().unwrap_or('M'), path.to_string()));
+    }
+    changes
+}
+
+fn parse_blame(output: &str) -> Vec<BlameLine> {
+    let mut lines = Vec::new();
+    let mut current: Option<BlameLine> = None;
+    let mut time = 0i64;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            // The line's contents end each entry.
+            if let Some(mut entry) = current.take() {
+                entry.date = format_date(time, &entry.date);
+                lines.push(entry);
+            }
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match current.as_mut() {
+            None => {
+                current = Some(BlameLine {
+                    commit: key.to_string(),
+                    author: String::new(),
+                    date: String::new(),
+                    summary: String::new(),
+                })
+            }
+            Some(entry) => match key {
+                "author" => entry.author = value.to_string(),
+                "author-time" => time = value.parse().unwrap_or_default(),
+                // Kept in `date` until the entry is complete.
+                "author-tz" => entry.date = value.to_string(),
+                "summary" => entry.summary = value.to_string(),
+                _ => (),
+            },
+        }
+    }
+    lines
+}
+
+fn parse_log(output: &str) -> Vec<Commit> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\x1f');
+            Some(Commit {
+                hash: fields.next()?.to_string(),
+                author: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                subject: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_status(output: &str) -> Vec<StatusEntry> {
+    let mut entries = Vec::new();
+    let mut fields = output.split('\0').filter(|f| !f.is_empty());
+    while let Some(field) = fields.next() {
+        let mut chars = field.chars();
+        let (staged, unstaged) = match (chars.next(), chars.next()) {
+            (Some(staged), Some(unstaged)) => (staged, unstaged),
+            _ => continue,
+        };
+        // Renames and copies are followed by the original path.
+        if matches!(staged, 'R' | 'C') {
+            fields.next();
+        }
+        entries.push(StatusEntry {
+            path: field.get(3..).unwrap_or_default().to_string(),
+            staged,
+            unstaged,
+        });
+    }
+    entries
+}
+
+fn parse_hunks(output: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut file_header = String::new();
+    let mut in_hunk = false;
+    for line in output.lines() {
+        if line.starts_with("diff --git ") {
+            file_header.clear();
+            in_hunk = false;
+        }
+        if line.starts_with("@@") {
+            in_hunk = true;
+            hunks.push(Hunk {
+                file_header: file_header.clone(),
+                header: line.to_string(),
+                lines: Vec::new(),
+            });
+        } else if in_hunk {
+            if let Some(hunk) = hunks.last_mut() {
+                hunk.lines.push(line.to_string());
+            }
+        } else {
+            file_header.push_str(line);
+            file_header.push('\n');
+        }
+    }
+    hunks
+}
+
+/// Formats a unix time as a `YYYY-MM-DD` date in the zone given as `+hhmm`.
+fn format_date(time: i64, tz: &str) -> String {
+    let offset = tz
+        .get(1..)
+        .and_then(|hhmm| hhmm.parse::<i64>().ok())
+        .map(|hhmm| (hhmm / 100 * 60 + hhmm % 100) * 60)
+        .map(|secs| if tz.starts_with('-') { -secs } else { secs })
+        .unwrap_or_default();
+    // Howard Hinnant's days-to-civil algorithm.
+    let days = (time + offset).div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[test]
+fn test_parse_line_porcelain() {
+    let output = "\
+1a2b3c4d5e6f7a8b9c0d1a2b3c4d5e6f7a8b9c0d 1 1 2
+author Ada Lovelace
+author-mail <ada@example.com>
+author-time 1700000000
+author-tz +0100
+summary Add the engine
+filename src/main.rs
+\tfn main() {
+1a2b3c4d5e6f7a8b9c0d1a2b3c4d5e6f7a8b9c0d 2 2
+author Ada Lovelace
+author-time 1700000000
+author-tz -1200
+summary Add the engine
+filename src/main.rs
+\t}
+0000000000000000000000000000000000000000 3 3 1
+author Not Committed Yet
+author-time 1700000000
+author-tz +0000
+summary Version of src/main.rs from src/main.rs
+filename src/main.rs
+\t
+";
+    let lines = parse_blame(output);
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0].describe(), "1a2b3c4d Ada Lovelace 2023-11-14");
+    assert_eq!(lines[0].summary, "Add the engine");
+    assert_eq!(lines[1].date, "2023-11-14");
+    assert!(!lines[2].is_committed());
+    assert_eq!(lines[2].describe(), "not committed yet");
+}
+
+#[test]
+fn test_parse_log() {
+    let output = "1a2b3c4d5e6f\x1fAda\x1f2024-02-29\x1fAdd the engine\nbroken line\n";
+    let commits = parse_log(output);
+    assert_eq!(commits.len(), 1);
+    assert_eq!(
+        commits[0].describe(),
+        "1a2b3c4d 2024-02-29 Ada Add the engine"
+    );
+}
+
+#[test]
+fn test_format_date_applies_zone() {
+    assert_eq!(format_date(0, "+0000"), "1970-01-01");
+    assert_eq!(format_date(1_709_164_800, "+0000"), "2024-02-29");
+    assert_eq!(format_date(1_709_164_800, "-0100"), "2024-02-28");
+}
+
+#[test]
+fn test_parse_status_and_hunks() {
+    let status = parse_status("M  src/app.rs\0 M README.md\0R  new.rs\0old.rs\0?? notes.txt\0");
+    let paths: Vec<&str> = status.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, ["src/app.rs", "README.md", "new.rs", "notes.txt"]);
+    assert!(status[0].is_staged() && !status[1].is_staged());
+    assert!(status[3].is_untracked());
+
+    let diff = "\
+diff --git a/notes.txt b/notes.txt
+index 1111111..2222222 100644
+--- a/notes.txt
++++ b/notes.txt
+@@ -1,2 +1,2 @@
+-first
++First
+ second
+@@ -9 +9,2 @@ fn tail()
+ ninth
++tenth
+";
+    let hunks = parse_hunks(diff);
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(hunks[1].header, "@@ -9 +9,2 @@ fn tail()");
+    assert_eq!(hunks[1].lines, [" ninth", "+tenth"]);
+    assert!(hunks[0]
+        .patch()
+        .starts_with("diff --git a/notes.txt b/notes.txt\n"));
+    assert!(hunks[0]
+        .patch()
+        .ends_with("@@ -1,2 +1,2 @@\n-first\n+First\n second\n"));
+}
+
+#[test]
+fn test_parse_name_status() {
+    let changes = parse_name_status("M\0src/app.rs\0A\0src/review.rs\0D\0old.txt\0");
+    assert_eq!(
+        changes,
+        [
+            ('M', "src/app.rs".to_string()),
+            ('A', "src/review.rs".to_string()),
+            ('D', "old.txt".to_string()),
+        ]
+    );
+    assert!(parse_name_status("").is_empty());
+}
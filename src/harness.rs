@@ -0,0 +1,134 @@
+//! Drives an [`App`] against ratatui's `TestBackend`, so features can be
+//! tested by feeding scripted key presses and asserting on the rendered
+//! screen.
+
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    backend::{Backend, TestBackend},
+    buffer::Buffer,
+    Terminal,
+};
+
+use crate::{
+    app::App,
+    config::{self, Config},
+    ui,
+};
+
+pub struct Harness {
+    pub app: App,
+    terminal: Terminal<TestBackend>,
+}
+
+impl Harness {
+    /// An app rooted at `root` with the default config, on a `width` x
+    /// `height` screen.
+    pub fn new(root: &Path, width: u16, height: u16) -> Result<Self> {
+        Harness::with_app(App::new(root, Config::default())?, width, height)
+    }
+
+    pub fn with_app(app: App, width: u16, height: u16) -> Result<Self> {
+        let terminal = Terminal::new(TestBackend::new(width, height))?;
+        Ok(Harness { app, terminal })
+    }
+
+    pub fn event(&mut self, event: Event) -> &mut Self {
+        self.app.handle_event(event);
+        self
+    }
+
+    /// Feeds a key script: plain characters are typed as-is and `<name>`
+    /// sends a named key such as `<enter>`, `<esc>` or `<ctrl-c>`. Use `<lt>`
+    /// for a literal `<`.
+    pub fn keys(&mut self, script: &str) -> Result<&mut Self> {
+        for key in parse_script(script)? {
+            self.event(Event::Key(key));
+        }
+        Ok(self)
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) -> &mut Self {
+        self.terminal.backend_mut().resize(width, height);
+        self.event(Event::Resize(width, height))
+    }
+
+    /// Draws a frame and returns the resulting screen contents.
+    pub fn render(&mut self) -> Result<&Buffer> {
+        ui::draw(&mut self.terminal, &mut self.app)?;
+        Ok(self.terminal.backend().buffer())
+    }
+
+    /// Draws a frame and returns where it left the cursor.
+    pub fn cursor(&mut self) -> Result<(u16, u16)> {
+        self.render()?;
+        let position = self.terminal.backend_mut().get_cursor_position()?;
+        Ok((position.x, position.y))
+    }
+
+    /// Draws a frame and returns its rows as strings, trailing spaces
+    /// trimmed.
+    pub fn screen(&mut self) -> Result<Vec<String>> {
+        let buffer = self.render()?;
+        let width = buffer.area.width as usize;
+        let rows = buffer
+            .content
+            .chunks(width.max(1))
+            .map(|row| {
+                let line: String = row.iter().map(|c| c.symbol()).collect();
+                line.trim_end().to_string()
+            })
+            .collect();
+        Ok(rows)
+    }
+
+    /// Whether any row of a freshly drawn frame contains `text`.
+    pub fn screen_contains(&mut self, text: &str) -> Result<bool> {
+        Ok(self.screen()?.iter().any(|row| row.contains(text)))
+    }
+}
+
+/// Splits a key script into key events; see [`Harness::keys`].
+pub fn parse_script(script: &str) -> Result<Vec<KeyEvent>> {
+    let mut keys = Vec::new();
+    let mut chars = script.chars();
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            let modifiers = if c.is_uppercase() {
+                KeyModifiers::SHIFT
+            } else {
+                KeyModifiers::NONE
+            };
+            keys.push(KeyEvent::new(KeyCode::Char(c), modifiers));
+            continue;
+        }
+
+        let name: String = chars.by_ref().take_while(|c| *c != '>').collect();
+        let key = match name.as_str() {
+            "lt" => Some(KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE)),
+            name => config::parse_key(name),
+        };
+        keys.push(key.ok_or_else(|| anyhow!("unknown key `<{}>` in script", name))?);
+    }
+    Ok(keys)
+}
+
+#[test]
+fn test_parse_script() {
+    let keys = parse_script("jG<enter><ctrl-c><lt>").unwrap();
+    let codes: Vec<KeyCode> = keys.iter().map(|k| k.code).collect();
+    assert_eq!(
+        codes,
+        [
+            KeyCode::Char('j'),
+            KeyCode::Char('G'),
+            KeyCode::Enter,
+            KeyCode::Char('c'),
+            KeyCode::Char('<'),
+        ]
+    );
+    assert_eq!(keys[3].modifiers, KeyModifiers::CONTROL);
+    assert!(parse_script("<bogus>").is_err());
+}
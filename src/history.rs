@@ -0,0 +1,144 @@
+//! The lines run at the `:` prompt, oldest first, for Up and Down to step
+//! back through and ctrl-r to search. Kept in the state directory, shared
+//! by every workspace.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+const HISTORY_FILE: &str = "history.json";
+
+/// How many lines the history remembers.
+pub const MAX_HISTORY: usize = 200;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CommandHistory {
+    lines: Vec<String>,
+}
+
+impl CommandHistory {
+    /// Adds `line` as the newest, dropping an earlier copy of it.
+    pub fn push(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        self.lines.retain(|l| l != line);
+        self.lines.push(line.to_string());
+        if self.lines.len() > MAX_HISTORY {
+            self.lines.remove(0);
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.lines.get(idx).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The newest line before `before` that starts with `prefix`.
+    pub fn older(&self, before: usize, prefix: &str) -> Option<usize> {
+        let before = before.min(self.lines.len());
+        self.lines[..before]
+            .iter()
+            .rposition(|l| l.starts_with(prefix))
+    }
+
+    /// The oldest line after `after` that starts with `prefix`.
+    pub fn newer(&self, after: usize, prefix: &str) -> Option<usize> {
+        let from = (after + 1).min(self.lines.len());
+        self.lines[from..]
+            .iter()
+            .position(|l| l.starts_with(prefix))
+            .map(|i| from + i)
+    }
+
+    /// The newest line before `before` that has `query` in it.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        let before = before.min(self.lines.len());
+        self.lines[..before].iter().rposition(|l| l.contains(query))
+    }
+}
+
+/// A ctrl-r search under way: what's been typed, and the line it found.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HistorySearch {
+    pub query: String,
+    pub found: Option<usize>,
+}
+
+/// The file the history is kept in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(path: PathBuf) -> Self {
+        HistoryStore { path }
+    }
+
+    pub fn default_location() -> Option<Self> {
+        config::state_dir().map(|d| HistoryStore::new(d.join(HISTORY_FILE)))
+    }
+
+    /// The history saved last, or an empty one if it can't be read.
+    pub fn load(&self) -> CommandHistory {
+        let read = fs::read_to_string(&self.path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Ok(serde_json::from_str(&text)?));
+        match read {
+            Ok(history) => history,
+            Err(e) => {
+                tracing::debug!(path = %self.path.display(), error = %e, "no saved history");
+                CommandHistory::default()
+            }
+        }
+    }
+
+    pub fn save(&self, history: &CommandHistory) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(history)?)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_history_steps_by_prefix_and_searches_back() {
+    let mut history = CommandHistory::default();
+    for line in ["set wrap", "grep todo", "set nowrap", "w", "grep todo "] {
+        history.push(line);
+    }
+    // Running a line again moves it to the end rather than repeating it.
+    assert_eq!(history.len(), 4);
+    assert_eq!(history.get(3), Some("grep todo"));
+
+    assert_eq!(history.older(history.len(), "set"), Some(1));
+    assert_eq!(history.older(1, "set"), Some(0));
+    assert_eq!(history.older(0, "set"), None);
+    assert_eq!(history.newer(0, "set"), Some(1));
+    assert_eq!(history.newer(1, "set"), None);
+
+    assert_eq!(history.search("wrap", history.len()), Some(1));
+    assert_eq!(history.search("wrap", 1), Some(0));
+    assert_eq!(history.search("nothing", history.len()), None);
+
+    let dir = std::env::temp_dir().join(format!("wyv-history-test-{}", std::process::id()));
+    let store = HistoryStore::new(dir.join("history.json"));
+    assert!(store.load().is_empty());
+    store.save(&history).unwrap();
+    assert_eq!(store.load(), history);
+    fs::remove_dir_all(dir).unwrap();
+}
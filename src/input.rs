@@ -0,0 +1,126 @@
+//! Reading terminal events in batches. Everything already waiting is read
+//! at once and thinned out before it's handled, so a burst of resizes from
+//! dragging the window or a flung mouse wheel costs one frame rather than
+//! one per event. Held keys arrive as many repeats, all of which are
+//! handled in the frame they arrive in, up to a limit that keeps frames
+//! coming through a flood of input.
+
+use std::{io, time::Duration};
+
+use crossterm::event::{self, Event, MouseEventKind};
+
+/// Wheel events kept from a run in one direction; a fling sends dozens.
+const MAX_SCROLL_RUN: usize = 3;
+
+/// Waits up to `timeout` for an event, then reads every other one already
+/// waiting, up to `max` in all; 0 means no limit. Events past `max` stay
+/// queued for the next frame. Empty if none came.
+pub fn read_batch(timeout: Duration, max: usize) -> io::Result<Vec<Event>> {
+    if !event::poll(timeout)? {
+        return Ok(Vec::new());
+    }
+    let events = read_up_to(max, || match event::poll(Duration::ZERO)? {
+        true => event::read().map(Some),
+        false => Ok(None),
+    })?;
+    Ok(coalesce(events))
+}
+
+/// Reads events with `next` until it has no more or `max` have been read,
+/// 0 meaning no limit. Reads at least one.
+fn read_up_to(
+    max: usize,
+    mut next: impl FnMut() -> io::Result<Option<Event>>,
+) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    while max == 0 || events.len() < max.max(1) {
+        match next()? {
+            Some(event) => events.push(event),
+            None => break,
+        }
+    }
+    Ok(events)
+}
+
+/// Drops events a later one in the batch makes pointless: every resize but
+/// the last, mouse moves but the last of a run, and wheel events past
+/// [`MAX_SCROLL_RUN`] in a row in one direction.
+pub fn coalesce(events: Vec<Event>) -> Vec<Event> {
+    let last_resize = events
+        .iter()
+        .rposition(|e| matches!(e, Event::Resize(_, _)));
+    let mut kept: Vec<Event> = Vec::with_capacity(events.len());
+    // How many mouse events of the same kind came in a row, this one too.
+    let mut run = 0;
+    for (i, event) in events.into_iter().enumerate() {
+        if matches!(event, Event::Resize(_, _)) && Some(i) != last_resize {
+            continue;
+        }
+        let kind = match &event {
+            Event::Mouse(mouse) => Some(mouse.kind),
+            _ => None,
+        };
+        let repeat = kind.is_some()
+            && matches!(kept.last(), Some(Event::Mouse(last)) if Some(last.kind) == kind);
+        run = if repeat { run + 1 } else { 1 };
+        match kind {
+            Some(MouseEventKind::Moved) if repeat => *kept.last_mut().unwrap() = event,
+            Some(kind) if is_scroll(kind) && run > MAX_SCROLL_RUN => (),
+            _ => kept.push(event),
+        }
+    }
+    kept
+}
+
+fn is_scroll(kind: MouseEventKind) -> bool {
+    matches!(
+        kind,
+        MouseEventKind::ScrollUp
+            | MouseEventKind::ScrollDown
+            | MouseEventKind::ScrollLeft
+            | MouseEventKind::ScrollRight
+    )
+}
+
+#[test]
+fn test_coalesce_thins_out_bursts() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent};
+
+    let mouse = |kind, column| {
+        Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row: 0,
+            modifiers: KeyModifiers::NONE,
+        })
+    };
+    let key = Event::Key(KeyEvent::from(KeyCode::Char('j')));
+    let mut events = vec![Event::Resize(80, 24), key.clone(), Event::Resize(90, 30)];
+    events.extend((0..3).map(|x| mouse(MouseEventKind::Moved, x)));
+    events.extend((0..20).map(|_| mouse(MouseEventKind::ScrollDown, 0)));
+    events.push(mouse(MouseEventKind::ScrollUp, 0));
+    events.push(key.clone());
+    events.push(key.clone());
+
+    let kept = coalesce(events);
+    assert_eq!(kept[..2], [key.clone(), Event::Resize(90, 30)]);
+    assert_eq!(kept[2], mouse(MouseEventKind::Moved, 2));
+    let scrolls = |kind| kept.iter().filter(|e| **e == mouse(kind, 0)).count();
+    assert_eq!(scrolls(MouseEventKind::ScrollDown), MAX_SCROLL_RUN);
+    assert_eq!(scrolls(MouseEventKind::ScrollUp), 1);
+    // Keys are never dropped, even repeated ones.
+    assert_eq!(kept.iter().filter(|e| **e == key).count(), 3);
+}
+
+#[test]
+fn test_reads_stop_at_the_limit_and_leave_the_rest_queued() {
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    let key = Event::Key(KeyEvent::from(KeyCode::Char('j')));
+    let mut queued = vec![key; 10].into_iter();
+    let read = read_up_to(4, || Ok(queued.next())).unwrap();
+    assert_eq!(read.len(), 4);
+    assert_eq!(queued.len(), 6);
+    assert_eq!(read_up_to(0, || Ok(queued.next())).unwrap().len(), 6);
+    assert!(read_up_to(0, || Ok(queued.next())).unwrap().is_empty());
+}
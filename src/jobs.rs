@@ -0,0 +1,309 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    badges::{self, Query},
+    buffer,
+    metadata::MetadataCache,
+    runtime::{Message, Spawner},
+    widgets::file_tree::{FileNode, TreeOptions},
+};
+
+pub type JobId = u64;
+
+/// Shared flag a job checks to find out it should stop.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Updates a job sends back to the UI thread.
+#[derive(Debug)]
+pub enum JobEvent {
+    Progress {
+        id: JobId,
+        done: u64,
+        total: Option<u64>,
+    },
+    Finished {
+        id: JobId,
+        result: Result<(), String>,
+    },
+}
+
+/// Handed to a running job for reporting progress and checking cancellation.
+pub struct JobContext {
+    id: JobId,
+    token: CancelToken,
+    tx: UnboundedSender<Message>,
+}
+
+impl JobContext {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn progress(&self, done: u64, total: Option<u64>) {
+        let _ = self.tx.send(Message::Job(JobEvent::Progress {
+            id: self.id,
+            done,
+            total,
+        }));
+    }
+
+    /// Sends a job's result to the UI thread.
+    pub fn send(&self, message: Message) {
+        let _ = self.tx.send(message);
+    }
+
+    /// For sending results from threads the job starts.
+    pub fn sender(&self) -> UnboundedSender<Message> {
+        self.tx.clone()
+    }
+}
+
+/// A job as seen by the UI: what it is and how far along it is.
+#[derive(Debug)]
+pub struct JobInfo {
+    pub name: String,
+    pub done: u64,
+    pub total: Option<u64>,
+    pub started: Instant,
+    token: CancelToken,
+}
+
+impl JobInfo {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+/// Tracks background jobs running on the runtime's blocking pool.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    next_id: JobId,
+    active: BTreeMap<JobId, JobInfo>,
+}
+
+impl Jobs {
+    /// Starts `job` on a worker thread. Its result is reported as a
+    /// [`JobEvent::Finished`]; cancelled jobs should return early with any
+    /// result.
+    pub fn spawn<F>(&mut self, spawner: &Spawner, name: &str, job: F) -> JobId
+    where
+        F: FnOnce(&JobContext) -> Result<()> + Send + 'static,
+    {
+        self.next_id += 1;
+        let id = self.next_id;
+        let token = CancelToken::default();
+        self.active.insert(
+            id,
+            JobInfo {
+                name: name.to_string(),
+                done: 0,
+                total: None,
+                started: Instant::now(),
+                token: token.clone(),
+            },
+        );
+        tracing::debug!(id, name, "job started");
+
+        let ctx = JobContext {
+            id,
+            token,
+            tx: spawner.sender(),
+        };
+        spawner.run_blocking(move || {
+            let result = job(&ctx).map_err(|e| e.to_string());
+            ctx.send(Message::Job(JobEvent::Finished { id, result }));
+        });
+        id
+    }
+
+    pub fn cancel(&mut self, id: JobId) -> bool {
+        match self.active.get(&id) {
+            Some(job) => {
+                job.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cancel_all(&mut self) {
+        for job in self.active.values() {
+            job.token.cancel();
+        }
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = (JobId, &JobInfo)> {
+        self.active.iter().map(|(id, job)| (*id, job))
+    }
+
+    pub fn get(&self, id: JobId) -> Option<&JobInfo> {
+        self.active.get(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Applies a job update. Returns the finished job and its result when a
+    /// job completes.
+    pub fn handle(&mut self, event: JobEvent) -> Option<(JobInfo, Result<(), String>)> {
+        match event {
+            JobEvent::Progress { id, done, total } => {
+                if let Some(job) = self.active.get_mut(&id) {
+                    job.done = done;
+                    job.total = total;
+                }
+                None
+            }
+            JobEvent::Finished { id, result } => {
+                let job = self.active.remove(&id)?;
+                tracing::debug!(id, name = %job.name, ?result, "job finished");
+                Some((job, result))
+            }
+        }
+    }
+}
+
+/// Entries scanned between progress reports.
+const SCAN_PROGRESS_STEP: u64 = 256;
+
+/// Scans `root` as a cancellable job, sending the nodes back as a
+/// [`Message::TreeScanned`].
+pub fn scan_tree(jobs: &mut Jobs, spawner: &Spawner, root: PathBuf, options: TreeOptions) -> JobId {
+    let name = format!("scan {}", root.display());
+    jobs.spawn(spawner, &name, move |ctx| {
+        let scanned = AtomicU64::new(0);
+        let result = FileNode::scan(&root, &options, &|_| {
+            let scanned = scanned.fetch_add(1, Ordering::Relaxed) + 1;
+            if scanned.is_multiple_of(SCAN_PROGRESS_STEP) {
+                ctx.progress(scanned, None);
+            }
+            !ctx.is_cancelled()
+        });
+        if ctx.is_cancelled() {
+            anyhow::bail!("cancelled");
+        }
+
+        ctx.send(Message::TreeScanned { root, result });
+        Ok(())
+    })
+}
+
+/// Runs `query` for the tree's git badges as a job, sending the result
+/// back as a [`Message::GitStatus`].
+pub fn git_status(jobs: &mut Jobs, spawner: &Spawner, query: Query) -> JobId {
+    let name = format!("git status {}", query.repo.display());
+    jobs.spawn(spawner, &name, move |ctx| {
+        let result = badges::run(&query);
+        ctx.send(Message::GitStatus { query, result });
+        Ok(())
+    })
+}
+
+/// Stats `paths` into `cache` as a job. Its finishing wakes the UI, which
+/// draws what was fetched.
+pub fn fetch_metadata(
+    jobs: &mut Jobs,
+    spawner: &Spawner,
+    cache: MetadataCache,
+    paths: Vec<PathBuf>,
+) -> JobId {
+    let name = format!("stat {} files", paths.len());
+    jobs.spawn(spawner, &name, move |_| {
+        cache.fetch(&paths);
+        Ok(())
+    })
+}
+
+/// Streams the large file at `path` into its buffer as a cancellable job,
+/// sending it in parts as [`Message::FileChunk`]s.
+pub fn load_file(jobs: &mut Jobs, spawner: &Spawner, path: PathBuf, size: u64) -> JobId {
+    let name = format!("load {}", path.display());
+    jobs.spawn(spawner, &name, move |ctx| {
+        let file = fs::File::open(&path)?;
+        let mut read = 0;
+        let result = buffer::read_chunks(file, |text| {
+            read += text.len() as u64;
+            ctx.progress(read, Some(size));
+            ctx.send(Message::FileChunk {
+                job: ctx.id(),
+                path: path.clone(),
+                text: Some(text.to_string()),
+            });
+            !ctx.is_cancelled()
+        });
+        // A failed read still ends the loading, with the text read so far.
+        ctx.send(Message::FileChunk {
+            job: ctx.id(),
+            path,
+            text: None,
+        });
+        Ok(result?)
+    })
+}
+
+#[test]
+fn test_cancelled_job_reports_finish() {
+    let mut runtime = crate::runtime::Runtime::new().unwrap();
+    let spawner = runtime.spawner();
+    let mut jobs = Jobs::default();
+
+    let id = jobs.spawn(&spawner, "spin", |ctx| {
+        let mut ticks = 0;
+        while !ctx.is_cancelled() {
+            ticks += 1;
+            ctx.progress(ticks, None);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        anyhow::bail!("cancelled")
+    });
+    assert_eq!(jobs.active().count(), 1);
+    assert!(jobs.cancel(id));
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        assert!(Instant::now() < deadline, "job never finished");
+        for message in runtime.drain() {
+            if let Message::Job(event) = message {
+                if let Some((job, result)) = jobs.handle(event) {
+                    assert_eq!(job.name, "spin");
+                    assert_eq!(result, Err("cancelled".to_string()));
+                    assert!(jobs.is_empty());
+                    return;
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,144 @@
+//! Where the cursor has jumped from, across files, for `ctrl-o` and
+//! `ctrl-i` to step back and forth through as vim's do. Following a
+//! definition, a search hit, a location or opening a file all count as
+//! jumps; moving the cursor within a file doesn't.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::Position;
+
+/// How many positions the jump list remembers.
+pub const MAX_JUMPS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jump {
+    pub path: PathBuf,
+    pub position: Position,
+}
+
+impl Jump {
+    pub fn new(path: &Path, position: Position) -> Self {
+        Jump {
+            path: path.to_owned(),
+            position,
+        }
+    }
+
+    /// Whether `other` is on the same line of the same file, which a jump
+    /// list needn't tell apart.
+    fn same_line(&self, other: &Jump) -> bool {
+        self.path == other.path && self.position.line == other.position.line
+    }
+}
+
+/// The jumps made, oldest first, and how far back through them
+/// [`back`](Self::back) has gone.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<Jump>", into = "Vec<Jump>")]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    /// The jump [`forward`](Self::forward) goes on from; `jumps.len()`
+    /// when not stepping through them.
+    index: usize,
+}
+
+impl From<Vec<Jump>> for JumpList {
+    fn from(mut jumps: Vec<Jump>) -> Self {
+        let excess = jumps.len().saturating_sub(MAX_JUMPS);
+        jumps.drain(..excess);
+        JumpList {
+            index: jumps.len(),
+            jumps,
+        }
+    }
+}
+
+impl From<JumpList> for Vec<Jump> {
+    fn from(list: JumpList) -> Self {
+        list.jumps
+    }
+}
+
+impl JumpList {
+    /// Records jumping away from `from`. Jumps stepped back past are
+    /// forgotten, and an older jump from the same line is dropped.
+    pub fn push(&mut self, from: Jump) {
+        self.jumps.truncate(self.index);
+        self.jumps.retain(|jump| !jump.same_line(&from));
+        if self.jumps.len() == MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.jumps.push(from);
+        self.index = self.jumps.len();
+    }
+
+    /// Steps back to the jump before, from `here`, which is remembered
+    /// first so that [`forward`](Self::forward) can come back to it.
+    pub fn back(&mut self, here: Jump) -> Option<&Jump> {
+        if self.index == self.jumps.len() {
+            match self.jumps.last() {
+                Some(last) if last.same_line(&here) => self.index -= 1,
+                _ => {
+                    if self.index == 0 {
+                        return None;
+                    }
+                    self.push(here);
+                    self.index -= 1;
+                }
+            }
+        }
+        self.index = self.index.checked_sub(1)?;
+        self.jumps.get(self.index)
+    }
+
+    /// Steps forward again after [`back`](Self::back).
+    pub fn forward(&mut self) -> Option<&Jump> {
+        if self.index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.index += 1;
+        self.jumps.get(self.index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.jumps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jumps.is_empty()
+    }
+}
+
+#[test]
+fn test_jumps_step_back_and_forth_like_vim() {
+    let at = |path: &str, line| Jump::new(Path::new(path), Position::new(line, 0));
+    let mut list = JumpList::default();
+    assert_eq!(list.back(at("a", 0)), None);
+    assert!(list.is_empty());
+
+    list.push(at("a", 1));
+    list.push(at("b", 2));
+    // Back from c:3 remembers it, so forward can return there.
+    assert_eq!(list.back(at("c", 3)), Some(&at("b", 2)));
+    assert_eq!(list.back(at("b", 2)), Some(&at("a", 1)));
+    assert_eq!(list.back(at("a", 1)), None);
+    assert_eq!(list.forward(), Some(&at("b", 2)));
+    assert_eq!(list.forward(), Some(&at("c", 3)));
+    assert_eq!(list.forward(), None);
+
+    // Jumping from partway back forgets the jumps ahead.
+    list.back(at("c", 3));
+    list.push(at("b", 2));
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.forward(), None);
+
+    // One entry per line, however often it's jumped from.
+    list.push(at("a", 1));
+    assert_eq!(list.len(), 2);
+
+    let saved = serde_json::to_string(&list).unwrap();
+    let loaded: JumpList = serde_json::from_str(&saved).unwrap();
+    assert_eq!(loaded, list);
+}
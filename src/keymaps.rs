@@ -0,0 +1,138 @@
+//! Keymap profiles: sets of bindings to take on at once, either the
+//! built-in vim, emacs and vscode ones or a file someone shared. A profile
+//! is written like the config's `[keymap]` table, which is what `:keymap
+//! export` writes. The user's own bindings stay on top of a profile's.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+use crate::config::{self, Action, Keymap};
+
+/// The profiles that come with wyv, by name.
+pub const PROFILES: [(&str, &str); 3] = [
+    ("emacs", include_str!("../assets/keymaps/emacs.toml")),
+    ("vim", include_str!("../assets/keymaps/vim.toml")),
+    ("vscode", include_str!("../assets/keymaps/vscode.toml")),
+];
+
+/// A key the user bound one way and a profile another. The user's binding
+/// is the one kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: String,
+    pub yours: Action,
+    pub profile: Action,
+}
+
+/// The bindings of the built-in profile `name`, or else of the profile
+/// file `name` names, relative to `dir`.
+pub fn load(name: &str, dir: &Path) -> Result<HashMap<String, Action>> {
+    let text = match PROFILES.iter().find(|(n, _)| *n == name) {
+        Some((_, text)) => text.to_string(),
+        None => {
+            let path = dir.join(name);
+            fs::read_to_string(&path).map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => anyhow!(
+                    "no keymap profile `{}`; there's {} or a file",
+                    name,
+                    names().join(", ")
+                ),
+                _ => anyhow!("{}: {}", path.display(), e),
+            })?
+        }
+    };
+    parse(&text).with_context(|| format!("keymap profile {}", name))
+}
+
+/// The names of the built-in profiles.
+pub fn names() -> Vec<&'static str> {
+    PROFILES.iter().map(|(name, _)| *name).collect()
+}
+
+/// Reads a profile: a `[keymap]` table, or bindings on their own.
+fn parse(text: &str) -> Result<HashMap<String, Action>> {
+    let mut table: toml::Value = toml::from_str(text)?;
+    let bindings = match table.as_table_mut().and_then(|t| t.remove("keymap")) {
+        Some(keymap) => keymap,
+        None => table,
+    };
+    let bindings: HashMap<String, Action> = bindings.try_into()?;
+    Ok(bindings
+        .into_iter()
+        .map(|(key, action)| (config::normalize_key(&key), action))
+        .collect())
+}
+
+/// `keymap` with `profile` taken on beneath the bindings that set it apart
+/// from the defaults, and the keys where the two disagree.
+pub fn apply(keymap: &Keymap, profile: &HashMap<String, Action>) -> (Keymap, Vec<Conflict>) {
+    let yours = keymap.overrides();
+    let mut conflicts: Vec<Conflict> = yours
+        .iter()
+        .filter_map(|(key, &action)| match profile.get(key) {
+            Some(&theirs) if theirs != action => Some(Conflict {
+                key: key.clone(),
+                yours: action,
+                profile: theirs,
+            }),
+            _ => None,
+        })
+        .collect();
+    conflicts.sort_by(|a, b| a.key.cmp(&b.key));
+    let mut bindings = profile.clone();
+    bindings.extend(yours);
+    (Keymap::from(bindings), conflicts)
+}
+
+/// `keymap` written as a profile, keys in order.
+pub fn export(keymap: &Keymap) -> Result<String> {
+    #[derive(Serialize)]
+    struct Profile {
+        keymap: BTreeMap<String, Action>,
+    }
+
+    let keymap = HashMap::from(keymap.clone()).into_iter().collect();
+    Ok(toml::to_string(&Profile { keymap })?)
+}
+
+#[test]
+fn test_profiles_load_and_keep_your_bindings() {
+    for name in names() {
+        assert!(!load(name, Path::new(".")).unwrap().is_empty(), "{}", name);
+    }
+    let emacs = load("emacs", Path::new(".")).unwrap();
+    assert_eq!(emacs["ctrl-n"], Action::TreeDown);
+
+    let yours = Keymap::from(HashMap::from([
+        ("ctrl-n".to_string(), Action::Search),
+        ("ctrl-p".to_string(), Action::TreeUp),
+    ]));
+    let (keymap, conflicts) = apply(&yours, &emacs);
+    let key = |name| keymap.action(&config::parse_key(name).unwrap());
+    assert_eq!(key("ctrl-n"), Some(Action::Search));
+    assert_eq!(key("alt-x"), Some(Action::CommandMode));
+    // Defaults the profile leaves alone stay.
+    assert_eq!(key("j"), Some(Action::TreeDown));
+    assert_eq!(
+        conflicts,
+        [Conflict {
+            key: "ctrl-n".to_string(),
+            yours: Action::Search,
+            profile: Action::TreeDown,
+        }]
+    );
+
+    let exported = export(&keymap).unwrap();
+    assert!(exported.starts_with("[keymap]"));
+    assert_eq!(Keymap::from(parse(&exported).unwrap()), keymap);
+    assert!(load("nano", Path::new("/nowhere"))
+        .unwrap_err()
+        .to_string()
+        .contains("emacs, vim, vscode"));
+}
@@ -0,0 +1,129 @@
+//! What's on screen, bottom to top: the panes, windows floating by the
+//! cursor, dialogs and toasts. `ui::draw` draws the layers in that order
+//! and notes where the mouse can reach each, and mouse events go to the
+//! layer on top at the pointer, or to a float or dialog up anywhere, which
+//! keeps the mouse to itself the way it keeps the keys.
+
+use ratatui::layout::{Position, Rect};
+
+/// A layer of the screen. Later ones are drawn over earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    /// The tree, tabs, editor, panels and the bar.
+    Base,
+    /// Windows by the cursor: hover, signature help and the completion and
+    /// spelling menus.
+    Float,
+    /// Dialogs, pickers and the search overlay.
+    Dialog,
+    /// Notices that take no input, like the perf overlay.
+    Toast,
+}
+
+impl Layer {
+    /// Every layer, in the order they're drawn.
+    pub const ALL: [Layer; 4] = [Layer::Base, Layer::Float, Layer::Dialog, Layer::Toast];
+
+    /// Whether the mouse can reach it.
+    pub fn takes_input(self) -> bool {
+        self != Layer::Toast
+    }
+
+    /// Whether it keeps the mouse while it's up, wherever the pointer is:
+    /// a click outside a menu closes it rather than landing underneath.
+    pub fn is_modal(self) -> bool {
+        matches!(self, Layer::Float | Layer::Dialog)
+    }
+}
+
+/// Where the mouse could reach each layer in the last frame. Windows that
+/// are only there to be read, like hover, aren't noted.
+#[derive(Debug, Default, Clone)]
+pub struct Layers {
+    drawn: Vec<(Layer, Rect)>,
+}
+
+impl Layers {
+    /// Forgets the last frame, before drawing the next.
+    pub fn clear(&mut self) {
+        self.drawn.clear();
+    }
+
+    /// Notes that `layer` drew over `area` and wants the mouse there.
+    pub fn push(&mut self, layer: Layer, area: Rect) {
+        self.drawn.push((layer, area));
+    }
+
+    /// The areas `layer` drew over, in the order it drew them.
+    pub fn areas(&self, layer: Layer) -> impl Iterator<Item = Rect> + '_ {
+        self.drawn
+            .iter()
+            .filter(move |(l, _)| *l == layer)
+            .map(|(_, area)| *area)
+    }
+
+    pub fn is_up(&self, layer: Layer) -> bool {
+        self.areas(layer).next().is_some()
+    }
+
+    /// The layer a mouse event at `at` goes to, with the area of it that's
+    /// there: the highest one under the pointer that takes input, unless a
+    /// modal one above it is up.
+    pub fn route(&self, at: Position) -> Option<(Layer, Rect)> {
+        let mut drawn: Vec<&(Layer, Rect)> = self.drawn.iter().collect();
+        // Top first: the highest layer, and within it the last drawn.
+        drawn.sort_by_key(|(layer, _)| *layer);
+        let mut modal = None;
+        for &(layer, area) in drawn.into_iter().rev() {
+            if !layer.takes_input() {
+                continue;
+            }
+            if modal.is_some_and(|(top, _)| top != layer) {
+                break;
+            }
+            if area.contains(at) {
+                return Some((layer, area));
+            }
+            if layer.is_modal() && modal.is_none() {
+                modal = Some((layer, area));
+            }
+        }
+        modal
+    }
+}
+
+#[test]
+fn test_layers_route_the_mouse_to_the_top() {
+    let screen = Rect::new(0, 0, 80, 24);
+    let menu = Rect::new(10, 5, 20, 6);
+    let perf = Rect::new(60, 0, 20, 5);
+    let mut layers = Layers::default();
+    layers.push(Layer::Base, screen);
+    layers.push(Layer::Toast, perf);
+    assert_eq!(
+        layers.route(Position::new(70, 2)),
+        Some((Layer::Base, screen))
+    );
+
+    // Drawn after the toast, but still beneath it.
+    layers.push(Layer::Float, menu);
+    assert_eq!(
+        layers.route(Position::new(12, 6)),
+        Some((Layer::Float, menu))
+    );
+    // Clicks outside the menu are its to close it on.
+    assert_eq!(
+        layers.route(Position::new(0, 0)),
+        Some((Layer::Float, menu))
+    );
+
+    let dialog = Rect::new(20, 8, 40, 8);
+    layers.push(Layer::Dialog, dialog);
+    assert_eq!(
+        layers.route(Position::new(12, 6)),
+        Some((Layer::Dialog, dialog))
+    );
+    layers.clear();
+    assert!(!layers.is_up(Layer::Base));
+    assert_eq!(layers.route(Position::new(0, 0)), None);
+}
@@ -0,0 +1,58 @@
+//! wyv's file tree, editor buffers and app logic, usable without the
+//! terminal entry point in `main.rs`.
+
+pub mod app;
+pub mod badges;
+pub mod buffer;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod crash;
+pub mod data;
+pub mod diff;
+pub mod error;
+pub mod features;
+pub mod follow;
+pub mod format;
+pub mod fuzzy;
+pub mod git;
+pub mod harness;
+pub mod history;
+pub mod input;
+pub mod jobs;
+pub mod jumps;
+pub mod keymaps;
+pub mod layers;
+pub mod lint;
+pub mod logging;
+pub mod lsp;
+pub mod markdown;
+pub mod marks;
+pub mod merge;
+pub mod metadata;
+pub mod mux;
+pub mod pacing;
+pub mod paths;
+pub mod perf;
+pub mod plugins;
+pub mod project;
+pub mod projects;
+pub mod pty;
+pub mod recent;
+pub mod remote;
+pub mod review;
+pub mod runtime;
+pub mod scripting;
+pub mod search;
+pub mod session;
+pub mod shell;
+pub mod snippet;
+pub mod sort;
+pub mod spell;
+pub mod ssh;
+pub mod symbols;
+pub mod tasks;
+pub mod terminal;
+pub mod tutor;
+pub mod ui;
+pub mod widgets;
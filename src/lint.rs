@@ -0,0 +1,340 @@
+//! Linting saved files with external programs such as clippy, eslint and
+//! shellcheck. Their output is read by a regex over each line or by an
+//! adapter for the program's JSON, and turned into [`Diagnostic`]s.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::lsp::Diagnostic;
+
+/// How a linter's output is read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintFormat {
+    /// Each line of output matched against the linter's `pattern`.
+    #[default]
+    Regex,
+    /// `cargo ... --message-format=json`, e.g. clippy.
+    Cargo,
+    /// `eslint --format json`.
+    Eslint,
+    /// `shellcheck --format=json`.
+    Shellcheck,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinterConfig {
+    pub command: String,
+    /// Arguments, with `{path}` replaced by the file's path.
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub format: LintFormat,
+    /// For the `regex` format: a pattern with `line` and `message` groups,
+    /// and optionally `file`, `col` and `severity`.
+    #[serde(default = "default_pattern")]
+    pub pattern: String,
+}
+
+/// Matches `path:line:col: severity: message` lines, as compilers print.
+pub fn default_pattern() -> String {
+    r"^(?P<file>[^:\s]+):(?P<line>\d+):(?:(?P<col>\d+):)?\s*(?:(?P<severity>error|warning|info|note|hint)\w*:)?\s*(?P<message>.+)$"
+        .to_string()
+}
+
+/// Linters by file extension, written in the config as `[linters.sh]`
+/// tables. User entries are merged over the defaults; one with an empty
+/// command turns linting off for the extension.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    from = "HashMap<String, LinterConfig>",
+    into = "HashMap<String, LinterConfig>"
+)]
+pub struct Linters {
+    by_extension: HashMap<String, LinterConfig>,
+}
+
+impl Default for Linters {
+    fn default() -> Self {
+        let linter = |command: &str, args: &[&str], format| LinterConfig {
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            format,
+            pattern: default_pattern(),
+        };
+        let eslint = linter(
+            "eslint",
+            &["--format", "json", "{path}"],
+            LintFormat::Eslint,
+        );
+        let shellcheck = linter(
+            "shellcheck",
+            &["--format=json", "{path}"],
+            LintFormat::Shellcheck,
+        );
+        let mut by_extension = HashMap::new();
+        by_extension.insert(
+            "rs".to_string(),
+            linter(
+                "cargo",
+                &["clippy", "--quiet", "--message-format=json"],
+                LintFormat::Cargo,
+            ),
+        );
+        for extension in ["js", "jsx", "ts", "tsx"] {
+            by_extension.insert(extension.to_string(), eslint.clone());
+        }
+        for extension in ["sh", "bash"] {
+            by_extension.insert(extension.to_string(), shellcheck.clone());
+        }
+        Linters { by_extension }
+    }
+}
+
+impl From<HashMap<String, LinterConfig>> for Linters {
+    fn from(overrides: HashMap<String, LinterConfig>) -> Self {
+        let mut linters = Linters::default();
+        linters.by_extension.extend(overrides);
+        linters
+    }
+}
+
+impl From<Linters> for HashMap<String, LinterConfig> {
+    fn from(linters: Linters) -> Self {
+        linters.by_extension
+    }
+}
+
+impl Linters {
+    /// The linter for `path`'s file type, if it has one.
+    pub fn for_path(&self, path: &Path) -> Option<&LinterConfig> {
+        let extension = path.extension()?.to_str()?;
+        self.by_extension
+            .get(extension)
+            .filter(|l| !l.command.is_empty())
+    }
+}
+
+/// Runs `linter` on the saved file at `path`, returning what it found
+/// there. Problems it reports in other files are left out.
+pub fn run(linter: &LinterConfig, path: &Path) -> Result<Vec<Diagnostic>> {
+    let pattern = match linter.format {
+        LintFormat::Regex => Some(
+            Regex::new(&linter.pattern)
+                .map_err(|e| anyhow!("invalid pattern for {}: {}", linter.command, e))?,
+        ),
+        _ => None,
+    };
+    let path_arg = path.to_string_lossy();
+    let args = linter.args.iter().map(|a| a.replace("{path}", &path_arg));
+    let output = Command::new(&linter.command)
+        .args(args)
+        .current_dir(path.parent().unwrap_or(Path::new(".")))
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("could not run {}", linter.command))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let diagnostics = match (&pattern, linter.format) {
+        // Compilers print their errors to stderr, so both are read.
+        (Some(pattern), _) => parse_lines(pattern, &format!("{}{}", stdout, stderr), path),
+        (None, LintFormat::Cargo) => parse_cargo(&stdout, path),
+        (None, LintFormat::Eslint) => parse_eslint(&stdout, path),
+        (None, _) => parse_shellcheck(&stdout, path),
+    };
+    // Linters exit with an error when they find problems; it only means
+    // the linter failed if there are none to show for it.
+    if diagnostics.is_empty() && !output.status.success() && stdout.trim().is_empty() {
+        let reason = stderr
+            .lines()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or("failed");
+        bail!("{}: {}", linter.command, reason.trim());
+    }
+    Ok(diagnostics)
+}
+
+/// Whether `file`, as a linter printed it, names `path`.
+fn is_file(path: &Path, file: &str) -> bool {
+    let file = Path::new(file);
+    path == file || path.ends_with(file.strip_prefix("./").unwrap_or(file))
+}
+
+/// A diagnostic at one-based `line` and `col`, with a severity named by
+/// the linter.
+fn diagnostic(line: u64, col: u64, severity: &str, message: &str) -> Diagnostic {
+    let severity = match severity.to_ascii_lowercase().as_str() {
+        "error" | "fatal" => 1,
+        "warning" | "warn" => 2,
+        "info" | "note" => 3,
+        _ => 4,
+    };
+    Diagnostic {
+        start: (
+            line.saturating_sub(1) as usize,
+            col.saturating_sub(1) as usize,
+        ),
+        severity,
+        message: message.to_string(),
+    }
+}
+
+fn parse_lines(pattern: &Regex, output: &str, path: &Path) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let captures = pattern.captures(line)?;
+            let group = |name| captures.name(name).map(|m| m.as_str());
+            if group("file").is_some_and(|file| !is_file(path, file)) {
+                return None;
+            }
+            let number = |name| group(name).and_then(|n| n.parse().ok());
+            Some(diagnostic(
+                number("line")?,
+                number("col").unwrap_or(1),
+                group("severity").unwrap_or("error"),
+                group("message")?.trim(),
+            ))
+        })
+        .collect()
+}
+
+/// Reads cargo's JSON messages, one per line, at their primary span.
+fn parse_cargo(output: &str, path: &Path) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let value: Value = serde_json::from_str(line).ok()?;
+            if value["reason"] != "compiler-message" {
+                return None;
+            }
+            let message = &value["message"];
+            let span = message["spans"]
+                .as_array()?
+                .iter()
+                .find(|s| s["is_primary"] == true)?;
+            if !is_file(path, span["file_name"].as_str()?) {
+                return None;
+            }
+            Some(diagnostic(
+                span["line_start"].as_u64()?,
+                span["column_start"].as_u64()?,
+                message["level"].as_str()?,
+                message["message"].as_str()?,
+            ))
+        })
+        .collect()
+}
+
+fn parse_eslint(output: &str, path: &Path) -> Vec<Diagnostic> {
+    let results: Value = serde_json::from_str(output).unwrap_or_default();
+    let files = results.as_array().map(Vec::as_slice).unwrap_or_default();
+    files
+        .iter()
+        .filter(|f| {
+            f["filePath"]
+                .as_str()
+                .is_some_and(|file| is_file(path, file))
+        })
+        .flat_map(|f| f["messages"].as_array().into_iter().flatten())
+        .filter_map(|m| {
+            let message = match m["ruleId"].as_str() {
+                Some(rule) => format!("{} ({})", m["message"].as_str()?, rule),
+                None => m["message"].as_str()?.to_string(),
+            };
+            let severity = match m["severity"].as_u64()? {
+                2 => "error",
+                _ => "warning",
+            };
+            Some(diagnostic(
+                m["line"].as_u64()?,
+                m["column"].as_u64().unwrap_or(1),
+                severity,
+                &message,
+            ))
+        })
+        .collect()
+}
+
+fn parse_shellcheck(output: &str, path: &Path) -> Vec<Diagnostic> {
+    let comments: Value = serde_json::from_str(output).unwrap_or_default();
+    let comments = comments.as_array().map(Vec::as_slice).unwrap_or_default();
+    comments
+        .iter()
+        .filter(|c| c["file"].as_str().is_some_and(|file| is_file(path, file)))
+        .filter_map(|c| {
+            Some(diagnostic(
+                c["line"].as_u64()?,
+                c["column"].as_u64().unwrap_or(1),
+                c["level"].as_str()?,
+                &format!("{} (SC{})", c["message"].as_str()?, c["code"].as_u64()?),
+            ))
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_linter_output() {
+    let path = Path::new("/work/src/main.rs");
+    let pattern = Regex::new(&default_pattern()).unwrap();
+    let lines = parse_lines(
+        &pattern,
+        "src/main.rs:3:5: warning: unused variable\n\
+         other.rs:1:1: error: elsewhere\n\
+         ./src/main.rs:7: missing semicolon\n\
+         compiling...\n",
+        path,
+    );
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].start, (2, 4));
+    assert_eq!(lines[0].severity_name(), "warning");
+    assert_eq!(lines[0].message, "unused variable");
+    assert_eq!(lines[1].start, (6, 0));
+    assert_eq!(lines[1].severity_name(), "error");
+
+    let cargo = parse_cargo(
+        "{\"reason\":\"compiler-artifact\"}\n\
+         {\"reason\":\"compiler-message\",\"message\":{\"level\":\"warning\",\
+         \"message\":\"this loop never loops\",\"spans\":[{\"file_name\":\"src/main.rs\",\
+         \"line_start\":10,\"column_start\":2,\"is_primary\":true}]}}\n",
+        path,
+    );
+    assert_eq!(cargo.len(), 1);
+    assert_eq!(cargo[0].start, (9, 1));
+    assert_eq!(cargo[0].message, "this loop never loops");
+
+    let path = Path::new("/work/app.js");
+    let eslint = parse_eslint(
+        r#"[{"filePath":"/work/app.js","messages":[
+            {"ruleId":"no-unused-vars","severity":2,"message":"'x' is unused","line":1,"column":7},
+            {"ruleId":null,"severity":1,"message":"Parsing hint","line":4}]}]"#,
+        path,
+    );
+    assert_eq!(eslint.len(), 2);
+    assert_eq!(eslint[0].message, "'x' is unused (no-unused-vars)");
+    assert_eq!(eslint[0].severity_name(), "error");
+    assert_eq!(eslint[1].start, (3, 0));
+    assert_eq!(eslint[1].severity_name(), "warning");
+
+    let path = Path::new("/work/run.sh");
+    let shellcheck = parse_shellcheck(
+        r#"[{"file":"/work/run.sh","line":3,"column":6,"level":"info",
+            "code":2086,"message":"Double quote to prevent globbing."}]"#,
+        path,
+    );
+    assert_eq!(shellcheck[0].start, (2, 5));
+    assert_eq!(shellcheck[0].severity_name(), "info");
+    assert_eq!(
+        shellcheck[0].message,
+        "Double quote to prevent globbing. (SC2086)"
+    );
+    assert!(parse_shellcheck("not json", path).is_empty());
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{
+    fmt::MakeWriter, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+use crate::config;
+
+const LOG_FILE: &str = "wyv.log";
+/// Environment variable overriding the configured level, in `EnvFilter`
+/// syntax (e.g. `WYV_LOG=wyv=trace`).
+const LOG_ENV: &str = "WYV_LOG";
+const RECENT_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogOptions {
+    pub level: String,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            level: "info".to_string(),
+        }
+    }
+}
+
+pub fn default_log_path() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join(LOG_FILE))
+}
+
+/// The most recent log lines, kept in memory for the log panel.
+#[derive(Debug, Clone, Default)]
+pub struct RecentLogs {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl RecentLogs {
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == RECENT_CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Up to `count` of the newest lines, oldest first.
+    pub fn last(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Handle to the installed subscriber, used to change the level when the
+/// config is reloaded.
+pub struct Logger {
+    filter: reload::Handle<EnvFilter, Registry>,
+    recent: RecentLogs,
+    env_override: bool,
+}
+
+impl Logger {
+    pub fn recent(&self) -> &RecentLogs {
+        &self.recent
+    }
+
+    /// Applies a new configured level, unless `WYV_LOG` takes precedence.
+    pub fn set_level(&self, level: &str) -> Result<()> {
+        if self.env_override {
+            return Ok(());
+        }
+        self.filter.reload(EnvFilter::try_new(level)?)?;
+        Ok(())
+    }
+}
+
+/// Installs the global subscriber, writing to `path` (if any) and to the
+/// in-memory recent log.
+pub fn init(path: Option<&Path>, options: &LogOptions) -> Result<Logger> {
+    let env_filter = EnvFilter::try_from_env(LOG_ENV).ok();
+    let env_override = env_filter.is_some();
+    let filter = match env_filter {
+        Some(filter) => filter,
+        None => EnvFilter::try_new(&options.level)?,
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let file = match path {
+        Some(path) => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            Some(OpenOptions::new().create(true).append(true).open(path)?)
+        }
+        None => None,
+    };
+    let recent = RecentLogs::default();
+    let writer = LogWriter {
+        file: file.map(|f| Arc::new(Mutex::new(f))),
+        recent: recent.clone(),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer),
+        )
+        .try_init()?;
+
+    Ok(Logger {
+        filter: handle,
+        recent,
+        env_override,
+    })
+}
+
+#[derive(Clone)]
+struct LogWriter {
+    file: Option<Arc<Mutex<File>>>,
+    recent: RecentLogs,
+}
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The fmt layer writes each event with a single call.
+        for line in String::from_utf8_lossy(buf).lines() {
+            self.recent.push(line.to_string());
+        }
+        if let Some(file) = &self.file {
+            file.lock().unwrap().write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.file {
+            Some(file) => file.lock().unwrap().flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_recent_logs_are_bounded() {
+    let recent = RecentLogs::default();
+    for i in 0..RECENT_CAPACITY + 10 {
+        recent.push(i.to_string());
+    }
+
+    let last = recent.last(3);
+    assert_eq!(last, ["507", "508", "509"]);
+    assert_eq!(recent.last(usize::MAX).len(), RECENT_CAPACITY);
+}
@@ -0,0 +1,1347 @@
+//! Language servers, spoken to with LSP's `Content-Length` framed JSON-RPC
+//! over stdin/stdout.
+//!
+//! Servers are configured per file extension and started the first time a
+//! matching file is opened:
+//!
+//! ```toml
+//! [[lsp]]
+//! name = "rust-analyzer"
+//! command = "rust-analyzer"
+//! extensions = ["rs"]
+//! ```
+//!
+//! Documents are synced in full on every change.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::Child,
+    sync::mpsc,
+    thread,
+};
+
+use anyhow::{anyhow, Result};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{buffer::Position, runtime::Message};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LspServerConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// File extensions the server handles, without the dot.
+    pub extensions: Vec<String>,
+    /// The `languageId` documents are opened with; the file's extension if
+    /// not set.
+    #[serde(default)]
+    pub language_id: Option<String>,
+}
+
+/// What a server's completion item kind looks like in the menu.
+pub fn kind_icon(kind: Option<u64>) -> &'static str {
+    match kind {
+        Some(2) => "mth",
+        Some(3) => "fn",
+        Some(4) => "new",
+        Some(5) => "fld",
+        Some(6) => "var",
+        Some(7) | Some(22) => "typ",
+        Some(8) => "trt",
+        Some(9) => "mod",
+        Some(10) => "prp",
+        Some(13) => "enm",
+        Some(14) => "kw",
+        Some(15) => "snp",
+        Some(17) => "file",
+        Some(19) => "dir",
+        Some(20) => "var",
+        Some(21) => "cst",
+        Some(_) | None => "txt",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: Option<u64>,
+    pub detail: Option<String>,
+    pub documentation: Option<String>,
+    /// What to insert in place of the word being completed.
+    pub insert_text: String,
+    /// Whether `insert_text` is a snippet, see [`crate::snippet`].
+    pub snippet: bool,
+    /// What the typed word is matched against.
+    pub filter_text: String,
+    /// Where the server wants the replaced text to start on the cursor
+    /// line, in chars.
+    pub start_col: Option<usize>,
+}
+
+/// A line and UTF-16 column, as servers give positions.
+pub type LspPosition = (usize, usize);
+
+/// A span of a file a server pointed at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+/// Replacements a server wants made in one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    /// Ranges to replace and their new text.
+    pub edits: Vec<(LspPosition, LspPosition, String)>,
+}
+
+/// A problem a server found in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub start: LspPosition,
+    /// 1 for errors, 2 warnings, 3 information and 4 hints.
+    pub severity: u64,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn severity_name(&self) -> &'static str {
+        match self.severity {
+            1 => "error",
+            2 => "warning",
+            3 => "info",
+            _ => "hint",
+        }
+    }
+}
+
+/// An entry of a file's outline, as a `textDocument/documentSymbol`
+/// response gives it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub kind: &'static str,
+    /// How many symbols it is nested in.
+    pub depth: usize,
+    /// Where its name starts.
+    pub start: LspPosition,
+}
+
+/// The signature of the call being typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureHelp {
+    pub label: String,
+    pub documentation: Option<String>,
+    /// The char range of the parameter being typed, within `label`.
+    pub active: Option<(usize, usize)>,
+}
+
+/// Something a server sent that the app has to act on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspEvent {
+    /// Completions for `position` in the file at `path`.
+    Completion {
+        path: PathBuf,
+        position: Position,
+        items: Vec<CompletionItem>,
+    },
+    /// Where the symbol at the cursor is defined.
+    Definition(Vec<Location>),
+    /// Where the symbol at the cursor is used.
+    References(Vec<Location>),
+    /// The edits renaming a symbol takes.
+    Rename(Vec<FileEdit>),
+    /// Documentation for the symbol at `position` in the file at `path`.
+    Hover {
+        path: PathBuf,
+        position: Position,
+        text: Option<String>,
+    },
+    /// The signature of the call around the cursor in the file at `path`,
+    /// if it is still in one.
+    SignatureHelp {
+        path: PathBuf,
+        help: Option<SignatureHelp>,
+    },
+    /// The edits formatting the file at `path` takes, to be followed by a
+    /// save with `save`.
+    Format {
+        path: PathBuf,
+        edits: Vec<(LspPosition, LspPosition, String)>,
+        save: bool,
+    },
+    /// The problems a server now finds in the file at `path`.
+    Diagnostics {
+        path: PathBuf,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// The outline of the file at `path`.
+    DocumentSymbols {
+        path: PathBuf,
+        symbols: Vec<DocumentSymbol>,
+    },
+    /// Text for the status bar.
+    Status(String),
+}
+
+/// What a request we sent was for, so its response can be routed.
+#[derive(Debug, Clone)]
+enum Pending {
+    Initialize,
+    Completion { path: PathBuf, position: Position },
+    Definition,
+    References,
+    Rename,
+    Hover { path: PathBuf, position: Position },
+    SignatureHelp { path: PathBuf },
+    Format { path: PathBuf, save: bool },
+    DocumentSymbols { path: PathBuf },
+}
+
+/// Reads one framed message from a server. `None` at the end of the
+/// stream; `Null` for a body that isn't JSON.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body).unwrap_or(Value::Null)))
+}
+
+/// `message` with its `Content-Length` header.
+pub fn frame(message: &Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+/// A `file://` URI for `path`.
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// `pos` as an LSP position, whose columns count UTF-16 units.
+pub fn lsp_position(text: &Rope, pos: Position) -> Value {
+    let character: usize = match pos.line < text.len_lines() {
+        true => text
+            .line(pos.line)
+            .chars()
+            .take(pos.col)
+            .map(char::len_utf16)
+            .sum(),
+        false => 0,
+    };
+    json!({ "line": pos.line, "character": character })
+}
+
+/// A server's position as a buffer position in `text`.
+pub fn to_position(text: &Rope, (line, character): LspPosition) -> Position {
+    let col = match line < text.len_lines() {
+        true => char_col(&text.line(line).to_string(), character as u64),
+        false => 0,
+    };
+    Position::new(line, col)
+}
+
+/// A server's text edits as buffer ranges in `text`.
+pub fn to_edits(
+    text: &Rope,
+    edits: &[(LspPosition, LspPosition, String)],
+) -> Vec<(Position, Position, String)> {
+    edits
+        .iter()
+        .map(|(start, end, new)| {
+            (
+                to_position(text, *start),
+                to_position(text, *end),
+                new.clone(),
+            )
+        })
+        .collect()
+}
+
+/// The path a `file://` URI names.
+pub fn uri_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let hex = encoded
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match (encoded[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                bytes.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+fn parse_position(value: &Value) -> Option<LspPosition> {
+    let line = value.get("line")?.as_u64()?;
+    let character = value.get("character")?.as_u64()?;
+    Some((line as usize, character as usize))
+}
+
+fn parse_range(value: &Value) -> Option<(LspPosition, LspPosition)> {
+    Some((
+        parse_position(value.get("start")?)?,
+        parse_position(value.get("end")?)?,
+    ))
+}
+
+/// Locations out of a definition or references response: a `Location`,
+/// a list of them, or a list of `LocationLink`s.
+pub fn parse_locations(result: &Value) -> Vec<Location> {
+    let items = match result {
+        Value::Array(items) => items.as_slice(),
+        Value::Null => &[],
+        result => std::slice::from_ref(result),
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let (uri, range) = match item.get("targetUri") {
+                Some(uri) => (uri, item.get("targetSelectionRange")?),
+                None => (item.get("uri")?, item.get("range")?),
+            };
+            let (start, end) = parse_range(range)?;
+            Some(Location {
+                path: uri_path(uri.as_str()?)?,
+                start,
+                end,
+            })
+        })
+        .collect()
+}
+
+/// The file and diagnostics of a `textDocument/publishDiagnostics`
+/// notification. Diagnostics without a severity count as errors.
+pub fn parse_diagnostics(params: &Value) -> Option<(PathBuf, Vec<Diagnostic>)> {
+    let path = uri_path(params.get("uri")?.as_str()?)?;
+    let items = params.get("diagnostics")?.as_array()?;
+    let diagnostics = items
+        .iter()
+        .filter_map(|item| {
+            Some(Diagnostic {
+                start: parse_range(item.get("range")?)?.0,
+                severity: item.get("severity").and_then(Value::as_u64).unwrap_or(1),
+                message: item.get("message")?.as_str()?.to_string(),
+            })
+        })
+        .collect();
+    Some((path, diagnostics))
+}
+
+/// What a `SymbolKind` is called in the outline.
+fn symbol_kind(kind: u64) -> &'static str {
+    const KINDS: [&str; 26] = [
+        "file",
+        "module",
+        "namespace",
+        "package",
+        "class",
+        "method",
+        "property",
+        "field",
+        "constructor",
+        "enum",
+        "interface",
+        "function",
+        "variable",
+        "constant",
+        "string",
+        "number",
+        "boolean",
+        "array",
+        "object",
+        "key",
+        "null",
+        "member",
+        "struct",
+        "event",
+        "operator",
+        "type",
+    ];
+    (kind as usize)
+        .checked_sub(1)
+        .and_then(|i| KINDS.get(i))
+        .unwrap_or(&"symbol")
+}
+
+/// The outline in a `textDocument/documentSymbol` response, in order.
+/// Nested `DocumentSymbol`s keep their nesting; a flat list of
+/// `SymbolInformation` is nested by which ranges hold which.
+pub fn parse_document_symbols(result: &Value) -> Vec<DocumentSymbol> {
+    fn nested(items: &[Value], depth: usize, symbols: &mut Vec<DocumentSymbol>) {
+        for item in items {
+            let range = item.get("selectionRange").or_else(|| item.get("range"));
+            let (name, start) = match (item.get("name"), range.and_then(parse_range)) {
+                (Some(Value::String(name)), Some((start, _))) => (name, start),
+                _ => continue,
+            };
+            let kind = item.get("kind").and_then(Value::as_u64).unwrap_or(0);
+            symbols.push(DocumentSymbol {
+                name: name.clone(),
+                kind: symbol_kind(kind),
+                depth,
+                start,
+            });
+            if let Some(Value::Array(children)) = item.get("children") {
+                nested(children, depth + 1, symbols);
+            }
+        }
+    }
+
+    let items = result.as_array().map(Vec::as_slice).unwrap_or_default();
+    if items.iter().all(|item| item.get("location").is_none()) {
+        let mut symbols = Vec::new();
+        nested(items, 0, &mut symbols);
+        return symbols;
+    }
+    let mut flat: Vec<(LspPosition, LspPosition, &Value)> = items
+        .iter()
+        .filter_map(|item| {
+            let (start, end) = parse_range(item.pointer("/location/range")?)?;
+            Some((start, end, item))
+        })
+        .collect();
+    flat.sort_by_key(|&(start, end, _)| (start, std::cmp::Reverse(end)));
+    let mut open: Vec<LspPosition> = Vec::new();
+    let mut symbols = Vec::new();
+    for (start, end, item) in flat {
+        let name = match item.get("name").and_then(Value::as_str) {
+            Some(name) => name,
+            None => continue,
+        };
+        while open.last().is_some_and(|&holder_end| holder_end < end) {
+            open.pop();
+        }
+        let kind = item.get("kind").and_then(Value::as_u64).unwrap_or(0);
+        symbols.push(DocumentSymbol {
+            name: name.to_string(),
+            kind: symbol_kind(kind),
+            depth: open.len(),
+            start,
+        });
+        open.push(end);
+    }
+    symbols
+}
+
+/// A list of `TextEdit`s, as a formatting response has.
+pub fn parse_text_edits(edits: &Value) -> Vec<(LspPosition, LspPosition, String)> {
+    let edits = edits.as_array().map(Vec::as_slice).unwrap_or_default();
+    edits
+        .iter()
+        .filter_map(|edit| {
+            let (start, end) = parse_range(edit.get("range")?)?;
+            Some((start, end, edit.get("newText")?.as_str()?.to_string()))
+        })
+        .collect()
+}
+
+/// The text edits in a `WorkspaceEdit`, from either its `changes` map or
+/// its `documentChanges`. File creations, renames and deletions are left
+/// out.
+pub fn parse_workspace_edit(result: &Value) -> Vec<FileEdit> {
+    let mut files: Vec<FileEdit> = Vec::new();
+    let mut add = |uri: Option<&str>, edits: Vec<_>| {
+        let path = match uri.and_then(uri_path) {
+            Some(path) if !edits.is_empty() => path,
+            _ => return,
+        };
+        match files.iter_mut().find(|f| f.path == path) {
+            Some(file) => file.edits.extend(edits),
+            None => files.push(FileEdit { path, edits }),
+        }
+    };
+    if let Some(changes) = result.get("documentChanges").and_then(Value::as_array) {
+        for change in changes {
+            let uri = change.pointer("/textDocument/uri").and_then(Value::as_str);
+            add(
+                uri,
+                parse_text_edits(change.get("edits").unwrap_or(&Value::Null)),
+            );
+        }
+    } else if let Some(changes) = result.get("changes").and_then(Value::as_object) {
+        for (uri, edits) in changes {
+            add(Some(uri), parse_text_edits(edits));
+        }
+    }
+    files
+}
+
+/// The char column of a UTF-16 `character` offset into `line`.
+fn char_col(line: &str, character: u64) -> usize {
+    let mut units = 0;
+    line.chars()
+        .take_while(|c| {
+            units += c.len_utf16() as u64;
+            units <= character
+        })
+        .count()
+}
+
+/// Text out of a `string | MarkupContent` value.
+fn markup(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(text) => text.as_str(),
+        value => value.get("value")?.as_str()?,
+    };
+    Some(text.to_string()).filter(|t| !t.is_empty())
+}
+
+/// The text of a hover response, whose contents are `MarkupContent`, a
+/// `MarkedString` or a list of them. Code fences are dropped since the text
+/// is shown as is.
+pub fn parse_hover(result: &Value) -> Option<String> {
+    let contents = result.get("contents")?;
+    let parts = match contents {
+        Value::Array(parts) => parts.as_slice(),
+        contents => std::slice::from_ref(contents),
+    };
+    let text = parts
+        .iter()
+        .filter_map(markup)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("```"))
+        .collect();
+    Some(lines.join("\n").trim().to_string()).filter(|t| !t.is_empty())
+}
+
+/// The active signature out of a signature help response. A parameter's
+/// label is either a substring of the signature's or a UTF-16 range in it.
+pub fn parse_signature_help(result: &Value) -> Option<SignatureHelp> {
+    let signatures = result.get("signatures")?.as_array()?;
+    let index = |value: Option<&Value>| value.and_then(Value::as_u64).map(|i| i as usize);
+    let signature = signatures
+        .get(index(result.get("activeSignature")).unwrap_or(0))
+        .or_else(|| signatures.first())?;
+    let label = signature.get("label")?.as_str()?.to_string();
+    let parameter = index(signature.get("activeParameter"))
+        .or_else(|| index(result.get("activeParameter")))
+        .and_then(|i| signature.get("parameters")?.as_array()?.get(i))
+        .and_then(|p| p.get("label"));
+    let active = match parameter {
+        Some(Value::String(name)) => label.find(name.as_str()).map(|start| {
+            let start = label[..start].chars().count();
+            (start, start + name.chars().count())
+        }),
+        Some(Value::Array(range)) => match (index(range.first()), index(range.get(1))) {
+            (Some(start), Some(end)) => {
+                Some((char_col(&label, start as u64), char_col(&label, end as u64)))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    Some(SignatureHelp {
+        documentation: signature.get("documentation").and_then(markup),
+        label,
+        active,
+    })
+}
+
+/// Items out of a completion response, which is either a list or a
+/// `CompletionList`. `line` is the cursor line, to place text edits.
+pub fn parse_completion(result: &Value, line: &str) -> Vec<CompletionItem> {
+    let items = match result {
+        Value::Array(items) => items,
+        result => match result.get("items").and_then(Value::as_array) {
+            Some(items) => items,
+            None => return Vec::new(),
+        },
+    };
+    let mut items: Vec<(String, CompletionItem)> = items
+        .iter()
+        .filter_map(|item| {
+            let text = |key: &str| item.get(key).and_then(Value::as_str).map(str::to_string);
+            let label = text("label")?;
+            let edit = item.get("textEdit");
+            // Either a TextEdit's `range` or an InsertReplaceEdit's `insert`.
+            let start = edit
+                .and_then(|e| e.get("range").or_else(|| e.get("insert")))
+                .and_then(|r| r.get("start")?.get("character")?.as_u64());
+            let insert_text = edit
+                .and_then(|e| e.get("newText")?.as_str().map(str::to_string))
+                .or_else(|| text("insertText"))
+                .unwrap_or_else(|| label.clone());
+            let sort = text("sortText").unwrap_or_else(|| label.clone());
+            Some((
+                sort,
+                CompletionItem {
+                    kind: item.get("kind").and_then(Value::as_u64),
+                    detail: text("detail").filter(|d| !d.is_empty()),
+                    documentation: item.get("documentation").and_then(markup),
+                    insert_text,
+                    snippet: item.get("insertTextFormat").and_then(Value::as_u64) == Some(2),
+                    filter_text: text("filterText").unwrap_or_else(|| label.clone()),
+                    start_col: start.map(|c| char_col(line, c)),
+                    label,
+                },
+            ))
+        })
+        .collect();
+    items.sort_by(|(a, _), (b, _)| a.cmp(b));
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(feature = "lsp")]
+fn spawn(config: &LspServerConfig, root: &Path) -> Result<Child> {
+    use std::process::{Command, Stdio};
+
+    use anyhow::Context;
+
+    Command::new(&config.command)
+        .args(&config.args)
+        .current_dir(root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("starting language server `{}`", config.name))
+}
+
+#[cfg(not(feature = "lsp"))]
+fn spawn(_config: &LspServerConfig, _root: &Path) -> Result<Child> {
+    Err(crate::features::missing("lsp"))
+}
+
+struct Server {
+    config: LspServerConfig,
+    child: Child,
+    stdin: mpsc::Sender<String>,
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+    /// Messages held back until the server has answered `initialize`.
+    queued: Vec<String>,
+    initialized: bool,
+    capabilities: Value,
+    /// Open documents and their versions.
+    documents: HashMap<PathBuf, i64>,
+    alive: bool,
+}
+
+impl Server {
+    fn start(
+        config: &LspServerConfig,
+        root: &Path,
+        (generation, index): (u64, usize),
+        tx: UnboundedSender<Message>,
+    ) -> Result<Self> {
+        let mut child = spawn(config, root)?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                let message = Message::Lsp {
+                    generation,
+                    index,
+                    message,
+                };
+                if tx.send(message).is_err() {
+                    return;
+                }
+            }
+            let _ = tx.send(Message::LspExited { generation, index });
+        });
+
+        // As with plugins, writes go through a thread so a stuck server
+        // can't block the UI.
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("no stdin"))?;
+        let (stdin_tx, stdin_rx) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for message in stdin_rx {
+                if stdin
+                    .write_all(message.as_bytes())
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        let mut server = Server {
+            config: config.clone(),
+            child,
+            stdin: stdin_tx,
+            next_id: 0,
+            pending: HashMap::new(),
+            queued: Vec::new(),
+            initialized: false,
+            capabilities: Value::Null,
+            documents: HashMap::new(),
+            alive: true,
+        };
+        let params = json!({
+            "processId": std::process::id(),
+            "clientInfo": { "name": "wyv", "version": env!("CARGO_PKG_VERSION") },
+            "rootUri": file_uri(root),
+            "capabilities": {
+                "textDocument": {
+                    "synchronization": { "didSave": true },
+                    "completion": {
+                        "completionItem": {
+                            "snippetSupport": true,
+                            "documentationFormat": ["plaintext", "markdown"],
+                        },
+                    },
+                    "hover": { "contentFormat": ["plaintext", "markdown"] },
+                    "signatureHelp": {
+                        "signatureInformation": {
+                            "documentationFormat": ["plaintext", "markdown"],
+                            "parameterInformation": { "labelOffsetSupport": true },
+                        },
+                    },
+                    "definition": { "linkSupport": true },
+                    "references": {},
+                    "rename": {},
+                    "formatting": {},
+                    "documentSymbol": { "hierarchicalDocumentSymbolSupport": true },
+                },
+                "workspace": { "workspaceEdit": { "documentChanges": true } },
+            },
+        });
+        server.request(Pending::Initialize, "initialize", params);
+        Ok(server)
+    }
+
+    fn request(&mut self, pending: Pending, method: &str, params: Value) {
+        self.next_id += 1;
+        self.pending.insert(self.next_id, pending);
+        self.send(
+            json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params }),
+        );
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn send(&mut self, message: Value) {
+        let is_initialize = message.get("method").and_then(Value::as_str) == Some("initialize");
+        if !self.initialized && !is_initialize {
+            self.queued.push(frame(&message));
+            return;
+        }
+        if self.alive && self.stdin.send(frame(&message)).is_err() {
+            self.alive = false;
+        }
+    }
+
+    fn handles(&self, path: &Path) -> bool {
+        let extension = path.extension().and_then(|e| e.to_str());
+        self.config
+            .extensions
+            .iter()
+            .any(|e| Some(e.as_str()) == extension)
+    }
+
+    fn did_open(&mut self, path: &Path, text: &Rope) {
+        self.documents.insert(path.to_owned(), 0);
+        let language = self.config.language_id.clone().unwrap_or_else(|| {
+            let extension = path.extension().unwrap_or_default();
+            extension.to_string_lossy().into_owned()
+        });
+        let document = json!({
+            "uri": file_uri(path),
+            "languageId": language,
+            "version": 0,
+            "text": text.to_string(),
+        });
+        self.notify("textDocument/didOpen", json!({ "textDocument": document }));
+    }
+
+    /// Characters that ask for completions straight away, such as `.`.
+    fn trigger_characters(&self) -> impl Iterator<Item = &str> {
+        self.capabilities
+            .pointer("/completionProvider/triggerCharacters")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+    }
+
+    /// Characters that ask for signature help, such as `(` and `,`.
+    fn signature_characters(&self) -> impl Iterator<Item = &str> {
+        let provider = self.capabilities.get("signatureHelpProvider");
+        ["triggerCharacters", "retriggerCharacters"]
+            .into_iter()
+            .filter_map(move |key| provider?.get(key)?.as_array())
+            .flatten()
+            .filter_map(Value::as_str)
+    }
+}
+
+/// The running language servers.
+#[derive(Default)]
+pub struct LspHost {
+    configs: Vec<LspServerConfig>,
+    root: PathBuf,
+    tx: Option<UnboundedSender<Message>>,
+    /// Tags its servers' messages, so those from an earlier host's are
+    /// left alone.
+    generation: u64,
+    servers: Vec<Server>,
+    /// Servers that failed to start, by name, so they aren't retried on
+    /// every change.
+    failed: Vec<String>,
+}
+
+impl LspHost {
+    /// A host that starts servers from `configs` in `root` as files they
+    /// handle are opened, sending their messages on `tx`.
+    pub fn new(configs: &[LspServerConfig], root: &Path, tx: UnboundedSender<Message>) -> Self {
+        LspHost {
+            configs: configs.to_vec(),
+            root: root.to_owned(),
+            tx: Some(tx),
+            generation: crate::runtime::next_generation(),
+            servers: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    /// The server handling `path`, started if it isn't running yet.
+    fn server_for(&mut self, path: &Path) -> Result<Option<&mut Server>> {
+        if let Some(idx) = self.servers.iter().position(|s| s.handles(path)) {
+            let server = &mut self.servers[idx];
+            return Ok(Some(server).filter(|s| s.alive));
+        }
+        let extension = path.extension().and_then(|e| e.to_str());
+        let config = self.configs.iter().find(|c| {
+            c.extensions.iter().any(|e| Some(e.as_str()) == extension)
+                && !self.failed.contains(&c.name)
+        });
+        let (config, tx) = match (config, &self.tx) {
+            (Some(config), Some(tx)) => (config, tx.clone()),
+            _ => return Ok(None),
+        };
+        tracing::info!(server = %config.name, "starting language server");
+        let id = (self.generation, self.servers.len());
+        match Server::start(config, &self.root, id, tx) {
+            Ok(server) => self.servers.push(server),
+            Err(e) => {
+                self.failed.push(config.name.clone());
+                return Err(e);
+            }
+        }
+        Ok(self.servers.last_mut())
+    }
+
+    /// Tells the server for `path` that it was opened with `text`.
+    pub fn did_open(&mut self, path: &Path, text: &Rope) -> Result<()> {
+        if let Some(server) = self.server_for(path)? {
+            server.did_open(path, text);
+        }
+        Ok(())
+    }
+
+    /// Sends the new `text` of `path` to its server.
+    pub fn did_change(&mut self, path: &Path, text: &Rope) -> Result<()> {
+        let server = match self.server_for(path)? {
+            Some(server) => server,
+            None => return Ok(()),
+        };
+        let version = match server.documents.get_mut(path) {
+            Some(version) => {
+                *version += 1;
+                *version
+            }
+            None => {
+                server.did_open(path, text);
+                return Ok(());
+            }
+        };
+        let params = json!({
+            "textDocument": { "uri": file_uri(path), "version": version },
+            "contentChanges": [{ "text": text.to_string() }],
+        });
+        server.notify("textDocument/didChange", params);
+        Ok(())
+    }
+
+    pub fn did_save(&mut self, path: &Path) {
+        if let Ok(Some(server)) = self.server_for(path) {
+            let params = json!({ "textDocument": { "uri": file_uri(path) } });
+            server.notify("textDocument/didSave", params);
+        }
+    }
+
+    /// Sends `method` for `position` in `path`, with `params` added to the
+    /// document and position. Returns `false` if no server handles the
+    /// file.
+    fn request_at(
+        &mut self,
+        (path, text, position): (&Path, &Rope, Position),
+        pending: Pending,
+        method: &str,
+        params: Value,
+    ) -> bool {
+        let server = match self.server_for(path) {
+            Ok(Some(server)) => server,
+            _ => return false,
+        };
+        let mut params = params;
+        params["textDocument"] = json!({ "uri": file_uri(path) });
+        params["position"] = lsp_position(text, position);
+        server.request(pending, method, params);
+        true
+    }
+
+    /// Asks for completions at `position` in `path`.
+    pub fn completion(&mut self, path: &Path, text: &Rope, position: Position) -> bool {
+        let pending = Pending::Completion {
+            path: path.to_owned(),
+            position,
+        };
+        let at = (path, text, position);
+        self.request_at(at, pending, "textDocument/completion", json!({}))
+    }
+
+    /// Asks where the symbol at `position` in `path` is defined.
+    pub fn definition(&mut self, path: &Path, text: &Rope, position: Position) -> bool {
+        let at = (path, text, position);
+        self.request_at(
+            at,
+            Pending::Definition,
+            "textDocument/definition",
+            json!({}),
+        )
+    }
+
+    /// Asks where the symbol at `position` in `path` is used, declaration
+    /// included.
+    pub fn references(&mut self, path: &Path, text: &Rope, position: Position) -> bool {
+        let at = (path, text, position);
+        let params = json!({ "context": { "includeDeclaration": true } });
+        self.request_at(at, Pending::References, "textDocument/references", params)
+    }
+
+    /// Asks for the edits renaming the symbol at `position` in `path` to
+    /// `new_name` takes.
+    pub fn rename(&mut self, path: &Path, text: &Rope, position: Position, new_name: &str) -> bool {
+        let at = (path, text, position);
+        let params = json!({ "newName": new_name });
+        self.request_at(at, Pending::Rename, "textDocument/rename", params)
+    }
+
+    /// Asks for documentation on the symbol at `position` in `path`.
+    pub fn hover(&mut self, path: &Path, text: &Rope, position: Position) -> bool {
+        let pending = Pending::Hover {
+            path: path.to_owned(),
+            position,
+        };
+        let at = (path, text, position);
+        self.request_at(at, pending, "textDocument/hover", json!({}))
+    }
+
+    /// Asks for the signature of the call around `position` in `path`.
+    pub fn signature_help(&mut self, path: &Path, text: &Rope, position: Position) -> bool {
+        let pending = Pending::SignatureHelp {
+            path: path.to_owned(),
+        };
+        let at = (path, text, position);
+        self.request_at(at, pending, "textDocument/signatureHelp", json!({}))
+    }
+
+    /// Asks for the edits formatting `path` with `tab_width` wide indents
+    /// of spaces, or with `tabs` of tabs. False if no server formats the
+    /// file.
+    pub fn format(&mut self, path: &Path, tab_width: u16, tabs: bool, save: bool) -> bool {
+        let server = match self.server_for(path) {
+            Ok(Some(server)) => server,
+            _ => return false,
+        };
+        // Until the server has said what it can do, assume it formats; a
+        // failure doesn't stop a save.
+        let provider = server.capabilities.get("documentFormattingProvider");
+        if server.initialized && provider.is_none_or(|p| p.as_bool() == Some(false)) {
+            return false;
+        }
+        let params = json!({
+            "textDocument": { "uri": file_uri(path) },
+            "options": { "tabSize": tab_width, "insertSpaces": !tabs },
+        });
+        let pending = Pending::Format {
+            path: path.to_owned(),
+            save,
+        };
+        server.request(pending, "textDocument/formatting", params);
+        true
+    }
+
+    /// Asks for the outline of `path`. False if no server gives one.
+    pub fn document_symbols(&mut self, path: &Path) -> bool {
+        let server = match self.server_for(path) {
+            Ok(Some(server)) => server,
+            _ => return false,
+        };
+        let provider = server.capabilities.get("documentSymbolProvider");
+        if !server.initialized || provider.is_none_or(|p| p.as_bool() == Some(false)) {
+            return false;
+        }
+        let params = json!({ "textDocument": { "uri": file_uri(path) } });
+        let pending = Pending::DocumentSymbols {
+            path: path.to_owned(),
+        };
+        server.request(pending, "textDocument/documentSymbol", params);
+        true
+    }
+
+    /// Whether typing `c` in `path` should ask for signature help.
+    pub fn is_signature_trigger(&self, path: &Path, c: char) -> bool {
+        let mut buf = [0; 4];
+        let c = c.encode_utf8(&mut buf);
+        self.servers
+            .iter()
+            .filter(|s| s.handles(path))
+            .any(|s| s.signature_characters().any(|t| t == c))
+    }
+
+    /// Whether typing `c` in `path` should ask for completions even
+    /// though it doesn't continue a word.
+    pub fn is_trigger(&self, path: &Path, c: char) -> bool {
+        let mut buf = [0; 4];
+        let c = c.encode_utf8(&mut buf);
+        self.servers
+            .iter()
+            .filter(|s| s.handles(path))
+            .any(|s| s.trigger_characters().any(|t| t == c))
+    }
+
+    /// Handles a message a server sent. `text_of` gives the current text
+    /// of an open file, to make sense of positions in the response.
+    pub fn handle(
+        &mut self,
+        (generation, index): (u64, usize),
+        message: Value,
+        text_of: impl Fn(&Path) -> Option<Rope>,
+    ) -> Option<LspEvent> {
+        if generation != self.generation {
+            return None;
+        }
+        let server = self.servers.get_mut(index)?;
+        if message.is_null() {
+            return Some(LspEvent::Status(format!(
+                "{}: invalid message",
+                server.config.name
+            )));
+        }
+        let id = message.get("id").cloned();
+        match (message.get("method").and_then(Value::as_str), id) {
+            // Requests from the server. None of them need more than an
+            // empty answer, but some servers wait for one.
+            (Some(method), Some(id)) => {
+                let result = match method {
+                    "workspace/configuration" => {
+                        let items = message.pointer("/params/items").and_then(Value::as_array);
+                        Value::Array(vec![Value::Null; items.map_or(0, Vec::len)])
+                    }
+                    _ => Value::Null,
+                };
+                server.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+                None
+            }
+            (Some("window/showMessage"), None) => message
+                .pointer("/params/message")
+                .and_then(Value::as_str)
+                .map(|m| LspEvent::Status(format!("{}: {}", server.config.name, m))),
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let (path, diagnostics) = parse_diagnostics(message.get("params")?)?;
+                Some(LspEvent::Diagnostics { path, diagnostics })
+            }
+            (Some(_), None) => None,
+            (None, Some(id)) => {
+                let pending = server.pending.remove(&id.as_u64()?)?;
+                let error = message.get("error");
+                if let (Some(error), Pending::Format { path, save: true }) = (error, &pending) {
+                    // The save still happens, just without formatting.
+                    tracing::warn!(server = %server.config.name, %error, "formatting failed");
+                    return Some(LspEvent::Format {
+                        path: path.clone(),
+                        edits: Vec::new(),
+                        save: true,
+                    });
+                }
+                if let Some(error) = error {
+                    let text = error
+                        .get("message")
+                        .and_then(Value::as_str)
+                        .unwrap_or("error");
+                    return Some(LspEvent::Status(format!(
+                        "{}: {}",
+                        server.config.name, text
+                    )));
+                }
+                let result = message.get("result").cloned().unwrap_or(Value::Null);
+                match pending {
+                    Pending::Initialize => {
+                        tracing::info!(server = %server.config.name, "language server initialized");
+                        server.capabilities =
+                            result.get("capabilities").cloned().unwrap_or_default();
+                        server.initialized = true;
+                        server.notify("initialized", json!({}));
+                        for message in std::mem::take(&mut server.queued) {
+                            if server.stdin.send(message).is_err() {
+                                server.alive = false;
+                            }
+                        }
+                        None
+                    }
+                    Pending::Completion { path, position } => {
+                        let line = text_of(&path)
+                            .filter(|t| position.line < t.len_lines())
+                            .map(|t| t.line(position.line).to_string())
+                            .unwrap_or_default();
+                        Some(LspEvent::Completion {
+                            items: parse_completion(&result, &line),
+                            path,
+                            position,
+                        })
+                    }
+                    Pending::Definition => Some(LspEvent::Definition(parse_locations(&result))),
+                    Pending::References => Some(LspEvent::References(parse_locations(&result))),
+                    Pending::Rename => Some(LspEvent::Rename(parse_workspace_edit(&result))),
+                    Pending::Hover { path, position } => Some(LspEvent::Hover {
+                        text: parse_hover(&result),
+                        path,
+                        position,
+                    }),
+                    Pending::Format { path, save } => Some(LspEvent::Format {
+                        edits: parse_text_edits(&result),
+                        path,
+                        save,
+                    }),
+                    Pending::SignatureHelp { path } => Some(LspEvent::SignatureHelp {
+                        help: parse_signature_help(&result),
+                        path,
+                    }),
+                    Pending::DocumentSymbols { path } => Some(LspEvent::DocumentSymbols {
+                        symbols: parse_document_symbols(&result),
+                        path,
+                    }),
+                }
+            }
+            (None, None) => None,
+        }
+    }
+
+    pub fn exited(&mut self, (generation, index): (u64, usize)) -> Option<String> {
+        if generation != self.generation {
+            return None;
+        }
+        let server = self.servers.get_mut(index)?;
+        server.alive = false;
+        tracing::warn!(server = %server.config.name, "language server exited");
+        Some(format!("language server {} exited", server.config.name))
+    }
+}
+
+impl Drop for LspHost {
+    fn drop(&mut self) {
+        for server in &mut self.servers {
+            let _ = server.child.kill();
+            let _ = server.child.wait();
+        }
+    }
+}
+
+#[test]
+fn test_framing_round_trip() {
+    let message = json!({ "jsonrpc": "2.0", "id": 1, "result": "é" });
+    let framed = frame(&message) + &frame(&json!(null)) + "Garbage: 1\r\n\r\n";
+    let mut reader = io::Cursor::new(framed.into_bytes());
+    assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+    assert_eq!(read_message(&mut reader).unwrap(), Some(Value::Null));
+    assert!(read_message(&mut reader).is_err());
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+    assert_eq!(file_uri(Path::new("/a b/c.rs")), "file:///a%20b/c.rs");
+}
+
+#[test]
+fn test_parse_completion_items() {
+    let result = json!({ "isIncomplete": false, "items": [
+        { "label": "push", "kind": 2, "sortText": "b", "detail": "fn(&mut self, T)",
+          "documentation": { "kind": "markdown", "value": "Appends an element." },
+          "insertTextFormat": 2,
+          "textEdit": { "range": { "start": { "line": 0, "character": 5 },
+                                   "end": { "line": 0, "character": 6 } },
+                        "newText": "push(${1:value})" } },
+        { "label": "pop", "sortText": "a" },
+    ]});
+    let items = parse_completion(&result, "v.pu");
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].label, "pop");
+    assert_eq!(items[0].insert_text, "pop");
+    assert_eq!(items[0].start_col, None);
+    let push = &items[1];
+    assert!(push.snippet);
+    assert_eq!(push.insert_text, "push(${1:value})");
+    assert_eq!(push.documentation.as_deref(), Some("Appends an element."));
+    assert_eq!(kind_icon(push.kind), "mth");
+
+    // Columns count UTF-16 units on the wire and chars in the buffer.
+    let items = parse_completion(
+        &json!([{ "label": "x", "textEdit": { "range": { "start": { "character": 3 } }, "newText": "x" } }]),
+        "😀.",
+    );
+    assert_eq!(items[0].start_col, Some(2));
+    let text = Rope::from_str("😀.x\n");
+    assert_eq!(lsp_position(&text, Position::new(0, 2))["character"], 3);
+}
+
+#[test]
+fn test_parse_locations_and_edits() {
+    let range =
+        json!({ "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 7 } });
+    let location = json!({ "uri": "file:///src/a%20b.rs", "range": range });
+    let link = json!({ "targetUri": "file:///src/c.rs", "targetRange": range,
+                       "targetSelectionRange": range });
+    let locations = parse_locations(&json!([location, link]));
+    assert_eq!(locations.len(), 2);
+    assert_eq!(locations[0].path, Path::new("/src/a b.rs"));
+    assert_eq!((locations[1].start, locations[1].end), ((1, 4), (1, 7)));
+    assert_eq!(parse_locations(&location).len(), 1);
+    assert!(parse_locations(&Value::Null).is_empty());
+
+    let edit = json!({ "range": range, "newText": "new" });
+    let changes = json!({ "changes": { "file:///a.rs": [edit, edit], "file:///b.rs": [] } });
+    let files = parse_workspace_edit(&changes);
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].edits.len(), 2);
+    let document_changes = json!({ "documentChanges": [
+        { "textDocument": { "uri": "file:///a.rs", "version": 1 }, "edits": [edit] },
+        { "kind": "create", "uri": "file:///new.rs" },
+    ]});
+    let files = parse_workspace_edit(&document_changes);
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].edits[0].2, "new");
+
+    let published = json!({ "uri": "file:///a.rs", "diagnostics": [
+        { "range": range, "severity": 2, "message": "unused" },
+        { "range": range, "message": "mismatched types" },
+    ]});
+    let (path, diagnostics) = parse_diagnostics(&published).unwrap();
+    assert_eq!(path, Path::new("/a.rs"));
+    assert_eq!(diagnostics[0].start, (1, 4));
+    assert_eq!(diagnostics[0].severity_name(), "warning");
+    assert_eq!(diagnostics[1].severity_name(), "error");
+
+    let text = Rope::from_str("x\n😀ab\n");
+    assert_eq!(to_position(&text, (1, 3)), Position::new(1, 2));
+}
+
+#[test]
+fn test_parse_hover_and_signature_help() {
+    let hover = json!({ "contents": { "kind": "markdown",
+        "value": "```rust\nfn len(&self) -> usize\n```\n\nThe length." } });
+    assert_eq!(
+        parse_hover(&hover).as_deref(),
+        Some("fn len(&self) -> usize\n\nThe length.")
+    );
+    let hover = json!({ "contents": ["a", { "language": "rust", "value": "b" }] });
+    assert_eq!(parse_hover(&hover).as_deref(), Some("a\n\nb"));
+    assert_eq!(parse_hover(&json!({ "contents": [] })), None);
+
+    let help = json!({ "activeSignature": 0, "activeParameter": 1, "signatures": [
+        { "label": "insert(é: usize, item: T)", "documentation": "Inserts.",
+          "parameters": [{ "label": "é: usize" }, { "label": [17, 24] }] },
+    ]});
+    let help = parse_signature_help(&help).unwrap();
+    assert_eq!(help.documentation.as_deref(), Some("Inserts."));
+    assert_eq!(help.active, Some((17, 24)));
+    let name = json!({ "signatures": [
+        { "label": "f(é: u8)", "activeParameter": 0, "parameters": [{ "label": "é: u8" }] },
+    ]});
+    assert_eq!(parse_signature_help(&name).unwrap().active, Some((2, 7)));
+    assert_eq!(parse_signature_help(&json!({ "signatures": [] })), None);
+}
+
+#[test]
+fn test_parse_document_symbols() {
+    let range = |line: u64, end: u64| json!({ "start": { "line": line, "character": 4 }, "end": { "line": end, "character": 1 } });
+    let nested = json!([
+        { "name": "Tree", "kind": 23, "range": range(0, 9), "selectionRange": range(0, 0),
+          "children": [{ "name": "render", "kind": 6, "range": range(2, 4),
+                         "selectionRange": range(2, 2) }] },
+        { "name": "main", "kind": 12, "range": range(10, 12), "selectionRange": range(10, 10) },
+    ]);
+    let symbols = parse_document_symbols(&nested);
+    let found: Vec<(&str, &str, usize)> = symbols
+        .iter()
+        .map(|s| (s.name.as_str(), s.kind, s.depth))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("Tree", "struct", 0),
+            ("render", "method", 1),
+            ("main", "function", 0)
+        ]
+    );
+    assert_eq!(symbols[1].start, (2, 4));
+
+    let location = |line, end| json!({ "uri": "file:///a.rs", "range": range(line, end) });
+    let flat = json!([
+        { "name": "main", "kind": 12, "location": location(10, 12) },
+        { "name": "render", "kind": 6, "location": location(2, 4) },
+        { "name": "Tree", "kind": 5, "location": location(0, 9) },
+    ]);
+    let depths: Vec<(String, usize)> = parse_document_symbols(&flat)
+        .into_iter()
+        .map(|s| (s.name, s.depth))
+        .collect();
+    assert_eq!(
+        depths,
+        [
+            ("Tree".to_string(), 0),
+            ("render".to_string(), 1),
+            ("main".to_string(), 0)
+        ]
+    );
+    assert!(parse_document_symbols(&Value::Null).is_empty());
+}
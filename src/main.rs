@@ -1,56 +1,309 @@
-pub mod ui;
-pub mod widgets;
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::{atomic::AtomicBool, Arc};
+use std::time::Instant;
 
-use std::thread;
-use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use crossterm::event::Event;
 
-use crossterm::{
-    event::{poll, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode},
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+use wyv::{
+    app::App,
+    cli::{self, Args},
+    config::{self, ConfigSources, ConfigWatcher, Theme},
+    crash, features,
+    history::HistoryStore,
+    input, logging,
+    pacing::Pacer,
+    projects::ProjectStore,
+    remote,
+    runtime::Runtime,
+    scripting::{self, Scripting},
+    session::SessionStore,
+    terminal::{self, Term},
+    tutor::{self, TutorStore},
+    ui,
+    widgets::file_tree::FileNode,
 };
 
-use anyhow::Result;
-use tui::{backend::CrosstermBackend, Terminal};
-
 fn main() -> Result<()> {
-    enable_raw_mode()?;
-    let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    let mut exit = false;
-    while !exit {
-        ui::draw(&mut terminal)?;
-
-        if poll(Duration::from_secs(0))? {
-            let event = read()?;
-
-            match event {
-                Event::Key(ke) => {
-                    if ke.code == KeyCode::Char('c') && ke.modifiers.contains(KeyModifiers::CONTROL)
-                    {
-                        exit = true;
-                    }
+    let args = Args::parse();
+    let socket = args.socket.clone().or_else(remote::default_socket_path);
+
+    // `--remote open` falls back to starting an instance with the files.
+    let mut fallback_files: Vec<String> = Vec::new();
+    if let Some(words) = &args.remote {
+        features::require("remote")?;
+        let mut request = remote::request_from_args(words)?;
+        let path = socket
+            .as_deref()
+            .ok_or_else(|| anyhow!("no control socket path; pass --socket"))?;
+        if request.method == "open" && !remote::is_listening(path) {
+            fallback_files = serde_json::from_value(request.params["files"].take())?;
+        } else {
+            let result = remote::send(path, &request)
+                .with_context(|| format!("remote {} via {}", request.method, path.display()))?;
+            if !result.is_null() {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            }
+            return Ok(());
+        }
+    }
+
+    // An explicitly requested config file has to exist.
+    if let Some(path) = args.config.as_ref().filter(|p| !p.exists()) {
+        return Err(anyhow!("config file {} not found", path.display()));
+    }
+    // `-` reads what's piped in; keys then come from the terminal itself.
+    let stdin = match args.reads_stdin() {
+        true if io::stdin().is_terminal() => return Err(anyhow!("nothing is piped in for `-`")),
+        true => {
+            let mut bytes = Vec::new();
+            io::stdin().read_to_end(&mut bytes)?;
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        false => None,
+    };
+    if args.stdout && !io::stdout().is_terminal() {
+        terminal::draw_on_tty();
+    }
+    let root = args.root();
+    let sources = ConfigSources::new(
+        args.config.clone().or_else(config::default_config_path),
+        &root,
+    );
+    let loaded = sources.load()?;
+    let config = loaded.config;
+
+    // Piped output gets the tree as plain text rather than escape codes.
+    if let Some(reason) = terminal::unsupported(stdin.is_some()) {
+        if io::stdout().is_terminal() {
+            return Err(anyhow!("{}; wyv needs an interactive terminal", reason));
+        }
+        let tree = FileNode::new_from_path_with(&root, &config.tree)?;
+        let mut out = io::stdout().lock();
+        return match tree.write_text(&mut out, config.tree.indent) {
+            // The reader went away, e.g. `wyv | head`.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => Ok(result?),
+        };
+    }
+    let mut runtime = Runtime::new()?;
+
+    let logger = logging::init(logging::default_log_path().as_deref(), &config.log)?;
+    tracing::info!(
+        version = env!("CARGO_PKG_VERSION"),
+        features = ?features::list(),
+        "starting wyv"
+    );
+
+    let crash_dir = crash::default_dir();
+    let last_crash = crash_dir.as_deref().and_then(crash::take_last_report);
+
+    let mut app = App::shallow(&root, config)?;
+    if let Some(report) = last_crash {
+        app.status = Some(format!(
+            "wyv crashed last time; report saved to {}",
+            report.display()
+        ));
+    }
+    app.logger = Some(logger);
+    app.spawner = Some(runtime.spawner());
+    // Deeper levels of the tree stream in while the UI is already up.
+    app.rescan_tree()?;
+    app.config_sources = Some(sources.clone());
+    app.start_plugins();
+    app.start_language_servers();
+    app.set_untrusted(loaded.untrusted, loaded.project_text);
+    if let Some(init) = scripting::default_init_path().filter(|p| p.exists()) {
+        match Scripting::new() {
+            Ok(scripts) => {
+                if let Err(e) = scripts.load_file(&init) {
+                    tracing::error!(error = %e, "init.lua failed");
+                    app.status = Some(e.to_string());
                 }
-                Event::Mouse(_) => (),
-                Event::Resize(_, _) => (),
+                app.scripts = Some(scripts);
+                app.apply_script_effects();
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "not loading init.lua");
+                app.status = Some(format!("init.lua: {}", e));
             }
         }
+    }
+    let watcher = ConfigWatcher::new(sources);
+    app.config_watch = Some(watcher.stopper());
+    runtime.spawner().watch_config(watcher);
+    let _server = match (&socket, args.daemon) {
+        (Some(path), true) => Some(remote::serve(&runtime.spawner(), path)?),
+        (None, true) => return Err(anyhow!("no control socket path; pass --socket")),
+        (_, false) => None,
+    };
+    app.readonly = args.readonly;
+    app.to_stdout = args.stdout;
+    app.sessions = SessionStore::default_location();
+    app.projects = ProjectStore::default_location();
+    app.history_store = HistoryStore::default_location();
+    app.command_history = app
+        .history_store
+        .as_ref()
+        .map(HistoryStore::load)
+        .unwrap_or_default();
+    app.load_session();
+    app.offer_restore();
+    if let Some(name) = &args.theme {
+        app.set_theme_override(Theme::named(name)?);
+    }
+    if args.monochrome() {
+        let theme = Theme {
+            monochrome: true,
+            ..app.config.theme.clone()
+        };
+        app.set_theme_override(theme);
+    }
+    let targets = args
+        .files()
+        .into_iter()
+        .chain(fallback_files.iter().map(|f| cli::parse_target(f)));
+    if let Some(url) = args.ssh_url() {
+        if let Err(e) = app.connect(url) {
+            app.status = Some(format!("{}: {}", url, e));
+        }
+    }
+    for target in targets {
+        if let Err(e) = app.open(&target.path, target.position) {
+            app.status = Some(format!("{}: {}", target.path.display(), e));
+        }
+    }
+    if let Some(text) = &stdin {
+        app.open_stdin(text);
+    }
+    let state_dir = config::state_dir().unwrap_or_else(std::env::temp_dir);
+    if args.tutor {
+        app.start_tutor(&state_dir)?;
+    } else if app.status.is_none() {
+        // Offered once, to whoever hasn't taken it yet.
+        let store = TutorStore::new(state_dir.join(tutor::PROGRESS_FILE));
+        if store.suggest().unwrap_or(false) {
+            app.status = Some("New to wyv? :tutor walks through the basics".to_string());
+        }
+    }
+
+    let signalled = terminal::termination_flag()?;
+    let job_control = terminal::job_control_flags()?;
+    terminal::install_panic_hook();
+    if let Some(dir) = crash_dir {
+        crash::install_hook(dir, app.logger.as_ref().map(|l| l.recent().clone()));
+    }
+    let editor = &app.config.editor;
+    let mut terminal = terminal::setup(editor.enhanced_keys, editor.mouse)?;
 
-        thread::sleep(Duration::from_millis(100));
+    let result = run(
+        &mut terminal,
+        &mut app,
+        &mut runtime,
+        &signalled,
+        &job_control,
+    );
+    terminal::restore()?;
+    if let Err(e) = app.save_session() {
+        tracing::warn!(error = %e, "could not save the session");
     }
+    if args.stdout {
+        match print_active(&app) {
+            // The reader went away, e.g. `wyv --stdout | head`.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+            printed => printed?,
+        }
+    }
+    result
+}
+
+/// Writes the active buffer to stdout, for `--stdout`.
+fn print_active(app: &App) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    if let Some(buffer) = app.buffer() {
+        for chunk in buffer.text().chunks() {
+            out.write_all(chunk.as_bytes())?;
+        }
+    }
+    out.flush()
+}
+
+fn run(
+    terminal: &mut Term,
+    app: &mut App,
+    runtime: &mut Runtime,
+    signalled: &AtomicBool,
+    (stopped, continued): &(Arc<AtomicBool>, Arc<AtomicBool>),
+) -> Result<()> {
+    let mut pacer = Pacer::default();
+    while !app.exit && !terminal::terminated(signalled) {
+        if std::mem::take(&mut app.suspend) | terminal::take(stopped) {
+            if let Err(e) = terminal::suspend(terminal) {
+                app.status = Some(e.to_string());
+            }
+            pacer.active();
+        }
+        if let Some(idx) = app.elevated_save.take() {
+            if let Err(e) = terminal::outside(terminal, || app.save_elevated(idx))? {
+                app.status = Some(e.to_string());
+            }
+            pacer.active();
+        }
+        // Stopped by something wyv can't catch: whatever was drawn over
+        // the screen meanwhile has to go.
+        if terminal::take(continued) {
+            terminal.clear()?;
+            app.tree_pane.invalidate();
+            pacer.active();
+        }
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+        let start = Instant::now();
+        let messages = runtime.drain();
+        app.perf.set_queue_depth(messages.len());
+        if !messages.is_empty() {
+            pacer.active();
+        }
+        for message in messages {
+            app.handle_message(message);
+        }
+        app.refresh_badges();
+        app.check_files(start);
+        app.autosave_session(start);
+        app.perf.record("messages", start.elapsed());
+
+        let start = Instant::now();
+        pacer.set_max_fps(app.config.editor.max_fps);
+        pacer.set_on_change(app.config.editor.screen_reader);
+        terminal::capture_mouse(app.config.editor.mouse)?;
+        if pacer.should_draw(start) {
+            // A frame that fails to draw is reported and drawn again later,
+            // rather than taking the session down with it.
+            if let Err(e) = ui::draw(terminal, app) {
+                tracing::error!(error = %e, "draw failed");
+                app.status = Some(e.to_string());
+            }
+            app.perf.frame(start.elapsed());
+            pacer.drawn(start);
+        }
+
+        // Waits for input, but only as long as the pacer says; results from
+        // the runtime are picked up on the next frame.
+        let events = input::read_batch(pacer.timeout(), app.config.editor.max_events_per_frame)?;
+        if events.is_empty() {
+            pacer.quiet(app.is_busy());
+            continue;
+        }
+        let start = Instant::now();
+        if events.iter().any(|e| matches!(e, Event::Resize(_, _))) {
+            pacer.resized(start);
+        }
+        for event in events {
+            app.handle_event(event);
+        }
+        app.perf.record("input", start.elapsed());
+        pacer.active();
+    }
 
     Ok(())
 }
@@ -0,0 +1,595 @@
+//! Lays markdown out as styled lines for the preview pane, each knowing the
+//! source line it comes from so that the preview can scroll with the editor.
+
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use ratatui::style::Modifier;
+use unicode_width::UnicodeWidthStr;
+
+/// What a piece of text is, which picks its color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Text,
+    Heading,
+    /// Inline code and code blocks.
+    Code,
+    Link,
+    /// Bullets, quote bars, rules and table borders.
+    Marker,
+    Keyword,
+    String,
+    Comment,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    pub text: String,
+    pub tone: Tone,
+    pub modifier: Modifier,
+}
+
+impl Piece {
+    fn new(text: impl Into<String>, tone: Tone) -> Self {
+        Piece {
+            text: text.into(),
+            tone,
+            modifier: Modifier::empty(),
+        }
+    }
+}
+
+/// A line of the preview, before it is wrapped to the pane's width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// The source line it comes from.
+    pub source: usize,
+    /// Goes before the first row, e.g. a bullet.
+    pub prefix: String,
+    /// Goes before the rows it wraps onto.
+    pub hang: String,
+    pub pieces: Vec<Piece>,
+    /// Code and tables are cut off at the pane's edge rather than wrapped.
+    pub wrap: bool,
+}
+
+impl Line {
+    /// The line's text, without its prefix.
+    pub fn text(&self) -> String {
+        self.pieces.iter().map(|p| p.text.as_str()).collect()
+    }
+}
+
+/// Lays `text` out, with headings, emphasis, lists, quotes, highlighted
+/// code blocks and tables.
+pub fn render(text: &str) -> Vec<Line> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let mut renderer = Renderer {
+        line_of: |byte: usize| line_starts.partition_point(|&start| start <= byte) - 1,
+        lines: Vec::new(),
+        current: Vec::new(),
+        source: None,
+        modifiers: Vec::new(),
+        tones: Vec::new(),
+        indents: Vec::new(),
+        marker: None,
+        lists: Vec::new(),
+        code: None,
+        table: None,
+    };
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    for (event, range) in Parser::new_ext(text, options).into_offset_iter() {
+        renderer.event(event, (renderer.line_of)(range.start));
+    }
+    renderer.flush();
+    while renderer.lines.last().is_some_and(|l| l.pieces.is_empty()) {
+        renderer.lines.pop();
+    }
+    renderer.lines
+}
+
+/// A table's rows, laid out once it has all of them.
+#[derive(Default)]
+struct Table {
+    rows: Vec<(usize, Vec<Vec<Piece>>)>,
+    /// How many rows the header has.
+    head: usize,
+}
+
+struct Renderer<F> {
+    line_of: F,
+    lines: Vec<Line>,
+    /// The pieces of the line being built.
+    current: Vec<Piece>,
+    /// Where the line being built starts.
+    source: Option<usize>,
+    modifiers: Vec<Modifier>,
+    tones: Vec<Tone>,
+    /// Indentation of the quotes and list items being built, outermost
+    /// first.
+    indents: Vec<String>,
+    /// A bullet or number replacing the innermost indentation on the next
+    /// line.
+    marker: Option<String>,
+    /// The next number of each list being built, or none if unordered.
+    lists: Vec<Option<u64>>,
+    /// The language of the code block being built.
+    code: Option<String>,
+    table: Option<Table>,
+}
+
+impl<F: Fn(usize) -> usize> Renderer<F> {
+    fn event(&mut self, event: Event, line: usize) {
+        match event {
+            Event::Start(tag) => self.start(tag, line),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &self.code {
+                Some(lang) => {
+                    for (i, code) in text.lines().enumerate() {
+                        let hang = self.hang();
+                        self.lines.push(Line {
+                            source: line + i,
+                            prefix: hang.clone() + "  ",
+                            hang: hang + "  ",
+                            pieces: highlight(lang, code),
+                            wrap: false,
+                        });
+                    }
+                }
+                None => self.push(&text, line),
+            },
+            Event::Code(code) => {
+                self.tones.push(Tone::Code);
+                self.push(&code, line);
+                self.tones.pop();
+            }
+            Event::SoftBreak => self.push(" ", line),
+            Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.flush();
+                self.lines.push(Line {
+                    source: line,
+                    prefix: self.hang(),
+                    hang: self.hang(),
+                    pieces: vec![Piece::new("─".repeat(40), Tone::Marker)],
+                    wrap: false,
+                });
+                self.blank();
+            }
+            Event::TaskListMarker(done) => {
+                let text = if done { "[x] " } else { "[ ] " };
+                self.current.push(Piece::new(text, Tone::Marker));
+                self.source.get_or_insert(line);
+            }
+            _ => (),
+        }
+    }
+
+    fn start(&mut self, tag: Tag, line: usize) {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.flush();
+                self.tones.push(Tone::Heading);
+                self.modifiers.push(match level {
+                    HeadingLevel::H1 => Modifier::BOLD | Modifier::UNDERLINED,
+                    _ => Modifier::BOLD,
+                });
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.indents.push("│ ".to_string());
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                self.code = Some(match kind {
+                    CodeBlockKind::Fenced(lang) => {
+                        lang.split(',').next().unwrap_or("").trim().to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                });
+            }
+            Tag::List(first) => {
+                self.flush();
+                self.lists.push(first);
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.indents.push(" ".repeat(marker.width()));
+                self.marker = Some(marker);
+            }
+            Tag::Table(_) => {
+                self.flush();
+                self.table = Some(Table::default());
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push((line, Vec::new()));
+                }
+            }
+            Tag::Emphasis => self.modifiers.push(Modifier::ITALIC),
+            Tag::Strong => self.modifiers.push(Modifier::BOLD),
+            Tag::Strikethrough => self.modifiers.push(Modifier::CROSSED_OUT),
+            Tag::Link { .. } => {
+                self.tones.push(Tone::Link);
+                self.modifiers.push(Modifier::UNDERLINED);
+            }
+            _ => (),
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.flush();
+                self.blank();
+            }
+            TagEnd::Heading(_) => {
+                self.flush();
+                self.tones.pop();
+                self.modifiers.pop();
+                self.blank();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                if self.lines.last().is_some_and(|l| l.pieces.is_empty()) {
+                    self.lines.pop();
+                }
+                self.indents.pop();
+                self.blank();
+            }
+            TagEnd::CodeBlock => {
+                self.code = None;
+                self.blank();
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+            }
+            TagEnd::Item => {
+                self.flush();
+                self.indents.pop();
+                self.marker = None;
+            }
+            TagEnd::TableCell => {
+                let cell = std::mem::take(&mut self.current);
+                self.source = None;
+                if let Some((_, cells)) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    cells.push(cell);
+                }
+            }
+            TagEnd::TableHead => {
+                if let Some(table) = &mut self.table {
+                    table.head = table.rows.len();
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.lay_out_table(table);
+                }
+                self.blank();
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.modifiers.pop();
+            }
+            TagEnd::Link => {
+                self.tones.pop();
+                self.modifiers.pop();
+            }
+            _ => (),
+        }
+    }
+
+    /// Adds inline text to the line being built.
+    fn push(&mut self, text: &str, line: usize) {
+        let mut piece = Piece::new(text, self.tones.last().copied().unwrap_or(Tone::Text));
+        piece.modifier = self
+            .modifiers
+            .iter()
+            .fold(Modifier::empty(), |all, &m| all | m);
+        self.current.push(piece);
+        self.source.get_or_insert(line);
+    }
+
+    /// Indentation for lines of the innermost quote or list item.
+    fn hang(&self) -> String {
+        self.indents.concat()
+    }
+
+    /// Ends the line being built, if there is one.
+    fn flush(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+        let hang = self.hang();
+        let prefix = match (self.marker.take(), self.indents.split_last()) {
+            (Some(marker), Some((_, outer))) => outer.concat() + &marker,
+            _ => hang.clone(),
+        };
+        self.lines.push(Line {
+            source: self.source.take().unwrap_or(0),
+            prefix,
+            hang,
+            pieces: std::mem::take(&mut self.current),
+            wrap: true,
+        });
+    }
+
+    /// Separates blocks with an empty line, or a bare quote bar in a quote.
+    fn blank(&mut self) {
+        let line = match self.lines.last() {
+            Some(last) if !last.pieces.is_empty() => last.source,
+            _ => return,
+        };
+        let hang = self.hang().trim_end().to_string();
+        self.lines.push(Line {
+            source: line,
+            prefix: hang.clone(),
+            hang,
+            pieces: Vec::new(),
+            wrap: false,
+        });
+    }
+
+    fn lay_out_table(&mut self, table: Table) {
+        let width = |cell: &[Piece]| cell.iter().map(|p| p.text.width()).sum::<usize>();
+        let mut widths: Vec<usize> = Vec::new();
+        for (_, cells) in &table.rows {
+            for (i, cell) in cells.iter().enumerate() {
+                match widths.get_mut(i) {
+                    Some(w) => *w = (*w).max(width(cell)),
+                    None => widths.push(width(cell)),
+                }
+            }
+        }
+        let hang = self.hang();
+        for (r, (source, cells)) in table.rows.into_iter().enumerate() {
+            let mut pieces = Vec::new();
+            for (i, mut cell) in cells.into_iter().enumerate() {
+                if i > 0 {
+                    pieces.push(Piece::new(" │ ", Tone::Marker));
+                }
+                let pad = widths[i] - width(&cell);
+                if r < table.head {
+                    for piece in &mut cell {
+                        piece.modifier |= Modifier::BOLD;
+                    }
+                }
+                pieces.extend(cell);
+                pieces.push(Piece::new(" ".repeat(pad), Tone::Text));
+            }
+            self.lines.push(Line {
+                source,
+                prefix: hang.clone(),
+                hang: hang.clone(),
+                pieces,
+                wrap: false,
+            });
+            if r + 1 == table.head {
+                let rule: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
+                self.lines.push(Line {
+                    source,
+                    prefix: hang.clone(),
+                    hang: hang.clone(),
+                    pieces: vec![Piece::new(rule.join("─┼─"), Tone::Marker)],
+                    wrap: false,
+                });
+            }
+        }
+    }
+}
+
+/// Words picked out as keywords in code blocks, across common languages.
+const KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "def",
+    "default",
+    "defer",
+    "do",
+    "elif",
+    "else",
+    "end",
+    "enum",
+    "except",
+    "export",
+    "false",
+    "finally",
+    "fn",
+    "for",
+    "from",
+    "func",
+    "function",
+    "go",
+    "if",
+    "impl",
+    "import",
+    "in",
+    "interface",
+    "let",
+    "local",
+    "loop",
+    "match",
+    "mod",
+    "mut",
+    "new",
+    "nil",
+    "None",
+    "null",
+    "package",
+    "pub",
+    "raise",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "switch",
+    "then",
+    "this",
+    "trait",
+    "True",
+    "False",
+    "true",
+    "try",
+    "type",
+    "use",
+    "var",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Splits a line of code in `lang` into keywords, strings, comments and
+/// the rest. Code in no language given is left plain.
+fn highlight(lang: &str, line: &str) -> Vec<Piece> {
+    if lang.is_empty() {
+        return vec![Piece::new(line, Tone::Code)];
+    }
+    let comment = match lang {
+        "python" | "py" | "sh" | "bash" | "shell" | "console" | "toml" | "yaml" | "yml"
+        | "ruby" | "rb" => "#",
+        "lua" | "sql" | "haskell" | "hs" => "--",
+        _ => "//",
+    };
+    // Rust's lifetimes and char literals both start with a single quote.
+    let quotes = match lang {
+        "rust" | "rs" => "\"",
+        _ => "\"'`",
+    };
+
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut add = |text: &str, tone: Tone| match pieces.last_mut() {
+        Some(last) if last.tone == tone => last.text.push_str(text),
+        _ => pieces.push(Piece::new(text, tone)),
+    };
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with(comment) {
+            add(rest, Tone::Comment);
+            break;
+        }
+        let len = if quotes.contains(c) {
+            let mut escaped = false;
+            let end = rest[1..]
+                .char_indices()
+                .find(|&(_, d)| {
+                    let close = !escaped && d == c;
+                    escaped = !escaped && d == '\\';
+                    close
+                })
+                .map_or(rest.len(), |(i, _)| i + 2);
+            add(&rest[..end], Tone::String);
+            end
+        } else if c.is_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|d: char| !d.is_alphanumeric() && d != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            let tone = match KEYWORDS.contains(&word) {
+                true => Tone::Keyword,
+                false => Tone::Code,
+            };
+            add(word, tone);
+            end
+        } else {
+            add(&rest[..c.len_utf8()], Tone::Code);
+            c.len_utf8()
+        };
+        rest = &rest[len..];
+    }
+    pieces
+}
+
+#[test]
+fn test_render_blocks() {
+    let text = "\
+# Title
+
+Some *very* **bold** `code`
+and more.
+
+- one
+- two
+  1. nested
+> quoted
+
+```rust
+let s = \"fn\"; // note
+```
+
+| a | bb |
+|---|----|
+| ccc | d |
+";
+    let lines = render(text);
+    let show: Vec<(usize, String)> = lines
+        .iter()
+        .map(|l| (l.source, format!("{}{}", l.prefix, l.text())))
+        .collect();
+    let expected = [
+        (0, "Title"),
+        (0, ""),
+        (2, "Some very bold code and more."),
+        (2, ""),
+        (5, "• one"),
+        (6, "• two"),
+        (7, "  1. nested"),
+        (7, ""),
+        (8, "│ quoted"),
+        (8, ""),
+        (11, "  let s = \"fn\"; // note"),
+        (11, ""),
+        (14, "a   │ bb"),
+        (14, "────┼───"),
+        (16, "ccc │ d "),
+    ];
+    let expected: Vec<(usize, String)> = expected
+        .iter()
+        .map(|(line, text)| (*line, text.to_string()))
+        .collect();
+    assert_eq!(show, expected);
+
+    let heading = &lines[0].pieces[0];
+    assert_eq!(heading.tone, Tone::Heading);
+    assert!(heading.modifier.contains(Modifier::BOLD));
+    let words: Vec<(&str, Tone, Modifier)> = lines[2]
+        .pieces
+        .iter()
+        .map(|p| (p.text.as_str(), p.tone, p.modifier))
+        .collect();
+    assert_eq!(words[1], ("very", Tone::Text, Modifier::ITALIC));
+    assert_eq!(words[3], ("bold", Tone::Text, Modifier::BOLD));
+    assert_eq!(words[5], ("code", Tone::Code, Modifier::empty()));
+
+    let code: Vec<(&str, Tone)> = lines[10]
+        .pieces
+        .iter()
+        .map(|p| (p.text.as_str(), p.tone))
+        .collect();
+    assert_eq!(
+        code,
+        [
+            ("let", Tone::Keyword),
+            (" s = ", Tone::Code),
+            ("\"fn\"", Tone::String),
+            ("; ", Tone::Code),
+            ("// note", Tone::Comment),
+        ]
+    );
+}
@@ -0,0 +1,136 @@
+//! Named places to come back to, set with `M` and a letter and gone to with
+//! `'` and the letter. Lowercase marks belong to one file; uppercase ones
+//! are global, each in whichever file it was last set. While a file is open
+//! its marks live in its buffer, where edits move them along; [`Marks`]
+//! keeps them otherwise, and from one run to the next.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buffer::{Buffer, Position},
+    jumps::Jump,
+};
+
+/// Says how marks are named, for a key or word that doesn't name one.
+pub const NAMING: &str = "marks are named a to z, or A to Z across files";
+
+/// Whether `name` can name a mark: a letter from `a` to `z` or `A` to `Z`.
+pub fn is_valid(name: char) -> bool {
+    name.is_ascii_alphabetic()
+}
+
+/// Whether mark `name` is global rather than kept to one file.
+pub fn is_global(name: char) -> bool {
+    name.is_ascii_uppercase()
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Marks {
+    global: BTreeMap<char, Jump>,
+    local: BTreeMap<PathBuf, BTreeMap<char, Position>>,
+}
+
+impl Marks {
+    /// Takes the marks of `buffer` in place of those kept for its file.
+    pub fn record(&mut self, buffer: &Buffer) {
+        let path = match buffer.path() {
+            Some(path) => path,
+            None => return,
+        };
+        let mut local = BTreeMap::new();
+        for (name, position) in buffer.marks() {
+            match is_global(name) {
+                true => {
+                    self.global.insert(name, Jump::new(path, position));
+                }
+                false => {
+                    local.insert(name, position);
+                }
+            }
+        }
+        match local.is_empty() {
+            true => self.local.remove(path),
+            false => self.local.insert(path.to_owned(), local),
+        };
+    }
+
+    /// Sets the marks kept for the file of `buffer` in it, as it's opened.
+    pub fn restore(&self, buffer: &mut Buffer) {
+        let path = match buffer.path() {
+            Some(path) => path.to_owned(),
+            None => return,
+        };
+        for (&name, &position) in self.local.get(&path).into_iter().flatten() {
+            buffer.set_mark(name, position);
+        }
+        for (&name, jump) in &self.global {
+            if jump.path == path {
+                buffer.set_mark(name, jump.position);
+            }
+        }
+    }
+
+    pub fn global(&self, name: char) -> Option<&Jump> {
+        self.global.get(&name)
+    }
+
+    pub fn set_global(&mut self, name: char, jump: Jump) {
+        self.global.insert(name, jump);
+    }
+
+    pub fn remove_global(&mut self, name: char) -> bool {
+        self.global.remove(&name).is_some()
+    }
+
+    /// Every mark kept: the global ones, then each file's own.
+    pub fn iter(&self) -> impl Iterator<Item = (char, &Path, Position)> {
+        let global = self
+            .global
+            .iter()
+            .map(|(&name, jump)| (name, jump.path.as_path(), jump.position));
+        let local = self.local.iter().flat_map(|(path, marks)| {
+            marks
+                .iter()
+                .map(move |(&name, &position)| (name, path.as_path(), position))
+        });
+        global.chain(local)
+    }
+}
+
+#[test]
+fn test_marks_are_kept_for_files_not_open() {
+    let path = Path::new("/work/src/main.rs");
+    let mut buffer = Buffer::from_text("fn main() {}\n");
+    buffer.set_mark('a', Position::new(0, 3));
+    let mut marks = Marks::default();
+    // Buffers without a file have nothing to keep them for.
+    marks.record(&buffer);
+    assert_eq!(marks, Marks::default());
+
+    let mut buffer = Buffer::loading(path, 0);
+    buffer.set_mark('a', Position::new(4, 0));
+    buffer.set_mark('M', Position::new(9, 2));
+    marks.record(&buffer);
+    marks.set_global(
+        'T',
+        Jump::new(Path::new("/work/tests/ui.rs"), Position::new(1, 0)),
+    );
+    let kept: Vec<_> = marks.iter().map(|(name, _, _)| name).collect();
+    assert_eq!(kept, ['M', 'T', 'a']);
+
+    let saved = serde_json::to_string(&marks).unwrap();
+    let loaded: Marks = serde_json::from_str(&saved).unwrap();
+    assert_eq!(loaded, marks);
+
+    // Reopened, the file gets its own marks and the global ones in it.
+    let mut reopened = Buffer::loading(path, 0);
+    loaded.restore(&mut reopened);
+    let names: Vec<_> = reopened.marks().map(|(name, _)| name).collect();
+    assert_eq!(names, ['M', 'a']);
+}
@@ -0,0 +1,273 @@
+//! Resolving merge conflicts left in a file as `<<<<<<<`, `=======` and
+//! `>>>>>>>` markers (with an optional `|||||||` base section, as
+//! `merge.conflictStyle = diff3` writes them).
+
+use anyhow::{bail, Result};
+
+const OURS_MARKER: &str = "<<<<<<<";
+const BASE_MARKER: &str = "|||||||";
+const SPLIT_MARKER: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>>";
+
+/// Which side of a conflict to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Ours,
+    Theirs,
+    /// Ours followed by theirs.
+    Both,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// What follows the `<<<<<<<` marker, usually `HEAD`.
+    pub ours_label: String,
+    pub ours: Vec<String>,
+    /// The base section's label and lines, if the markers include it.
+    pub base: Option<(String, Vec<String>)>,
+    /// What follows the `>>>>>>>` marker, usually the merged branch.
+    pub theirs_label: String,
+    pub theirs: Vec<String>,
+    pub resolution: Option<Side>,
+}
+
+impl Conflict {
+    /// The lines this conflict contributes to the result: the chosen side,
+    /// or the conflict with its markers while unresolved.
+    pub fn result(&self) -> Vec<String> {
+        match self.resolution {
+            Some(Side::Ours) => self.ours.clone(),
+            Some(Side::Theirs) => self.theirs.clone(),
+            Some(Side::Both) => [self.ours.clone(), self.theirs.clone()].concat(),
+            None => {
+                let mut lines = vec![marker(OURS_MARKER, &self.ours_label)];
+                lines.extend(self.ours.iter().cloned());
+                if let Some((label, base)) = &self.base {
+                    lines.push(marker(BASE_MARKER, label));
+                    lines.extend(base.iter().cloned());
+                }
+                lines.push(SPLIT_MARKER.to_string());
+                lines.extend(self.theirs.iter().cloned());
+                lines.push(marker(THEIRS_MARKER, &self.theirs_label));
+                lines
+            }
+        }
+    }
+}
+
+fn marker(marker: &str, label: &str) -> String {
+    match label {
+        "" => marker.to_string(),
+        label => format!("{} {}", marker, label),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(Vec<String>),
+    Conflict(Conflict),
+}
+
+/// A file with conflicts, one of which is selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merge {
+    segments: Vec<Segment>,
+    /// Whether the file ended with a line break.
+    trailing_newline: bool,
+    selected: usize,
+}
+
+impl Merge {
+    /// Splits `text` into plain runs and conflicts. Fails if it has no
+    /// conflicts or a conflict isn't closed.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut plain = Vec::new();
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let ours_label = match line.strip_prefix(OURS_MARKER) {
+                Some(label) => label.trim().to_string(),
+                None => {
+                    plain.push(line.to_string());
+                    continue;
+                }
+            };
+            if !plain.is_empty() {
+                segments.push(Segment::Text(std::mem::take(&mut plain)));
+            }
+
+            let mut conflict = Conflict {
+                ours_label,
+                ours: Vec::new(),
+                base: None,
+                theirs_label: String::new(),
+                theirs: Vec::new(),
+                resolution: None,
+            };
+            let mut section = &mut conflict.ours;
+            let mut closed = false;
+            for line in lines.by_ref() {
+                if let Some(label) = line.strip_prefix(BASE_MARKER) {
+                    section = &mut conflict
+                        .base
+                        .insert((label.trim().to_string(), Vec::new()))
+                        .1;
+                } else if line == SPLIT_MARKER {
+                    section = &mut conflict.theirs;
+                } else if let Some(label) = line.strip_prefix(THEIRS_MARKER) {
+                    conflict.theirs_label = label.trim().to_string();
+                    closed = true;
+                    break;
+                } else {
+                    section.push(line.to_string());
+                }
+            }
+            if !closed {
+                bail!("conflict {} is not closed", segments.len() + 1);
+            }
+            segments.push(Segment::Conflict(conflict));
+        }
+        if !plain.is_empty() {
+            segments.push(Segment::Text(plain));
+        }
+
+        let merge = Merge {
+            segments,
+            trailing_newline: text.ends_with('\n'),
+            selected: 0,
+        };
+        if merge.conflicts().next().is_none() {
+            bail!("no conflict markers");
+        }
+        Ok(merge)
+    }
+
+    /// Whether `text` looks like it has conflicts in it.
+    pub fn has_markers(text: &str) -> bool {
+        let mut lines = text.lines();
+        lines.any(|l| l.starts_with(OURS_MARKER))
+            && lines.any(|l| l == SPLIT_MARKER)
+            && lines.any(|l| l.starts_with(THEIRS_MARKER))
+    }
+
+    pub fn conflicts(&self) -> impl Iterator<Item = &Conflict> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Conflict(c) => Some(c),
+            Segment::Text(_) => None,
+        })
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_conflict(&self) -> Option<&Conflict> {
+        self.conflicts().nth(self.selected)
+    }
+
+    pub fn unresolved(&self) -> usize {
+        self.conflicts().filter(|c| c.resolution.is_none()).count()
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.conflicts().count() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Resolves the selected conflict and moves on to the next unresolved
+    /// one, if there is one.
+    pub fn resolve(&mut self, side: Side) {
+        let selected = self.selected;
+        let conflict = self
+            .segments
+            .iter_mut()
+            .filter_map(|s| match s {
+                Segment::Conflict(c) => Some(c),
+                Segment::Text(_) => None,
+            })
+            .nth(selected);
+        if let Some(conflict) = conflict {
+            conflict.resolution = Some(side);
+        }
+        let next = self
+            .conflicts()
+            .enumerate()
+            .skip(selected)
+            .chain(self.conflicts().enumerate().take(selected))
+            .find(|(_, c)| c.resolution.is_none());
+        if let Some((idx, _)) = next {
+            self.selected = idx;
+        }
+    }
+
+    /// The merged file's lines, each with the index of the conflict it
+    /// comes from, if any.
+    pub fn result_lines(&self) -> Vec<(String, Option<usize>)> {
+        let mut lines = Vec::new();
+        let mut conflict = 0;
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => lines.extend(text.iter().map(|l| (l.clone(), None))),
+                Segment::Conflict(c) => {
+                    lines.extend(c.result().into_iter().map(|l| (l, Some(conflict))));
+                    conflict += 1;
+                }
+            }
+        }
+        lines
+    }
+
+    /// The merged file, with markers left around unresolved conflicts.
+    pub fn result(&self) -> String {
+        let lines: Vec<String> = self.result_lines().into_iter().map(|(l, _)| l).collect();
+        let mut text = lines.join("\n");
+        if self.trailing_newline {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[test]
+fn test_parse_and_resolve_conflicts() {
+    let text = "\
+fn main() {
+<<<<<<< HEAD
+    println!(\"ours\");
+=======
+    println!(\"theirs\");
+>>>>>>> feature
+    let x = 1;
+<<<<<<< HEAD
+    one();
+||||||| base
+    zero();
+=======
+    two();
+>>>>>>> feature
+}
+";
+    assert!(Merge::has_markers(text));
+    let mut merge = Merge::parse(text).unwrap();
+    assert_eq!(merge.conflicts().count(), 2);
+    assert_eq!(merge.result(), text);
+    assert_eq!(merge.selected_conflict().unwrap().theirs_label, "feature");
+
+    merge.resolve(Side::Theirs);
+    assert_eq!(merge.selected(), 1);
+    assert_eq!(merge.unresolved(), 1);
+    merge.resolve(Side::Both);
+    assert_eq!(merge.unresolved(), 0);
+    assert_eq!(
+        merge.result(),
+        "fn main() {\n    println!(\"theirs\");\n    let x = 1;\n    one();\n    two();\n}\n"
+    );
+
+    assert!(Merge::parse("no conflicts\n").is_err());
+    assert!(Merge::parse("<<<<<<< HEAD\nours\n=======\n").is_err());
+}
@@ -0,0 +1,224 @@
+//! File metadata cached by path, so the tree's detail columns and anything
+//! else showing a file's size or age don't stat it again every frame. The
+//! tree only [peeks](MetadataCache::peek) at what's cached while drawing;
+//! the rows in view are [queued](MetadataCache::queue) and statted off the
+//! UI thread.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How long a stat is trusted before the file is looked at again.
+const FRESH_FOR: Duration = Duration::from_secs(2);
+
+/// What's kept of a file's metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileInfo {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub is_dir: bool,
+}
+
+#[derive(Debug)]
+struct Cached {
+    /// `None` if the file couldn't be read.
+    info: Option<FileInfo>,
+    checked: Instant,
+}
+
+/// Metadata by path, shared by clones of the cache. Each file is statted at
+/// most once every couple of seconds, and at once after it's
+/// [invalidated](Self::invalidate), e.g. by a save.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataCache {
+    entries: Arc<Mutex<HashMap<PathBuf, Cached>>>,
+    /// Paths queued to be statted and not statted yet.
+    queued: Arc<Mutex<HashSet<PathBuf>>>,
+    generation: Arc<AtomicU64>,
+}
+
+impl MetadataCache {
+    /// `path`'s metadata, or `None` if it doesn't exist or can't be read.
+    pub fn get(&self, path: &Path) -> Option<FileInfo> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = entries.get(path) {
+            if cached.checked.elapsed() < FRESH_FOR {
+                return cached.info;
+            }
+        }
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        let info = fs::metadata(path).ok().map(|m| FileInfo {
+            len: m.len(),
+            modified: m.modified().ok(),
+            is_dir: m.is_dir(),
+        });
+        let checked = Instant::now();
+        entries.insert(path.to_path_buf(), Cached { info, checked });
+        info
+    }
+
+    /// `path`'s metadata as last statted, however long ago, without statting
+    /// it now. `None` if it hasn't been yet, or couldn't be read.
+    pub fn peek(&self, path: &Path) -> Option<FileInfo> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.get(path).and_then(|cached| cached.info)
+    }
+
+    /// Of `paths`, the ones to stat: those not statted lately and not
+    /// queued already, in the order given. They stay queued until they're
+    /// [fetched](Self::fetch).
+    pub fn queue(&self, paths: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut queued = self.queued.lock().unwrap_or_else(|e| e.into_inner());
+        paths
+            .into_iter()
+            .filter(|path| {
+                entries
+                    .get(path)
+                    .is_none_or(|cached| cached.checked.elapsed() >= FRESH_FOR)
+            })
+            .filter(|path| queued.insert(path.clone()))
+            .collect()
+    }
+
+    /// Stats `paths`, e.g. ones [queued](Self::queue), on whichever thread
+    /// calls it.
+    pub fn fetch(&self, paths: &[PathBuf]) {
+        for path in paths {
+            self.get(path);
+            let mut queued = self.queued.lock().unwrap_or_else(|e| e.into_inner());
+            queued.remove(path);
+        }
+    }
+
+    /// A number that changes whenever a file is statted, so that what shows
+    /// metadata is drawn again then.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Whether `path` was modified since `modified`, as last seen: anything
+    /// worked out from the file then is out of date.
+    pub fn changed_since(&self, path: &Path, modified: Option<SystemTime>) -> bool {
+        self.get(path).and_then(|info| info.modified) != modified
+    }
+
+    /// Forgets `path`, so the next look at it stats it again.
+    pub fn invalidate(&self, path: &Path) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(path);
+    }
+
+    /// Forgets everything, e.g. when the tree is scanned again.
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+/// `len` bytes in at most four characters, e.g. `812B`, `4.2K` or `31M`.
+/// Formatted where it's written, so drawing a size allocates nothing.
+pub struct HumanSize(pub u64);
+
+impl fmt::Display for HumanSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut size = self.0 as f64;
+        for unit in ["B", "K", "M", "G", "T"] {
+            if size < 1024.0 || unit == "T" {
+                return match (unit, size < 10.0) {
+                    ("B", _) => write!(f, "{}B", self.0),
+                    (_, true) => write!(f, "{:.1}{}", size, unit),
+                    (_, false) => write!(f, "{:.0}{}", size, unit),
+                };
+            }
+            size /= 1024.0;
+        }
+        unreachable!()
+    }
+}
+
+/// How long before `now` a time was, in at most three characters, e.g.
+/// `5m` or `3d`.
+pub struct HumanAge {
+    pub time: SystemTime,
+    pub now: SystemTime,
+}
+
+impl fmt::Display for HumanAge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self
+            .now
+            .duration_since(self.time)
+            .unwrap_or_default()
+            .as_secs();
+        match secs {
+            0..60 => f.write_str("now"),
+            60..3600 => write!(f, "{}m", secs / 60),
+            3600..86400 => write!(f, "{}h", secs / 3600),
+            86400..604800 => write!(f, "{}d", secs / 86400),
+            604800..31536000 => write!(f, "{}w", secs / 604800),
+            _ => write!(f, "{}y", secs / 31536000),
+        }
+    }
+}
+
+#[test]
+fn test_cache_stats_once_until_invalidated() {
+    let dir = std::env::temp_dir().join(format!("wyv-metadata-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.txt");
+    fs::write(&path, "hello").unwrap();
+
+    let cache = MetadataCache::default();
+    let info = cache.get(&path).unwrap();
+    assert_eq!(info.len, 5);
+    assert!(!cache.changed_since(&path, info.modified));
+
+    // A write goes unseen until the path is invalidated.
+    fs::write(&path, "hello, world").unwrap();
+    assert_eq!(cache.clone().get(&path).unwrap().len, 5);
+    cache.invalidate(&path);
+    assert_eq!(cache.get(&path).unwrap().len, 12);
+    assert!(cache.get(&dir.join("missing")).is_none());
+
+    assert_eq!(HumanSize(812).to_string(), "812B");
+    assert_eq!(HumanSize(4300).to_string(), "4.2K");
+    assert_eq!(HumanSize(31 * 1024 * 1024).to_string(), "31M");
+    let now = SystemTime::now();
+    assert_eq!(HumanAge { time: now, now }.to_string(), "now");
+    let time = now - Duration::from_secs(3 * 86400);
+    assert_eq!(HumanAge { time, now }.to_string(), "3d");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_queued_paths_are_statted_once_fetched() {
+    let dir = std::env::temp_dir().join(format!("wyv-metadata-queue-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+    fs::write(&a, "a").unwrap();
+    fs::write(&b, "bb").unwrap();
+
+    let cache = MetadataCache::default();
+    assert_eq!(cache.queue([a.clone(), b.clone()]), [a.clone(), b.clone()]);
+    // Already queued, so not handed out twice.
+    assert!(cache.queue([a.clone()]).is_empty());
+    assert!(cache.peek(&a).is_none());
+
+    let generation = cache.generation();
+    cache.clone().fetch(&[a.clone(), b.clone()]);
+    assert!(cache.generation() > generation);
+    assert_eq!(cache.peek(&b).unwrap().len, 2);
+    // Fresh, so there's nothing to stat again yet.
+    assert!(cache.queue([a, b]).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,170 @@
+//! Working alongside terminal multiplexers. `:send` and `:send-path` type
+//! lines of the buffer or a file's path into a pane beside wyv's in tmux or
+//! WezTerm, splitting one off if wyv is alone in its window. Panes split
+//! off get `WYV_PANE` set to wyv's own pane, so programs there can type
+//! `:open` commands into it for files they report:
+//!
+//! ```sh
+//! tmux send-keys -t "$WYV_PANE" ":open src/app.rs:120" Enter
+//! wezterm cli send-text --pane-id "$WYV_PANE" --no-paste $':open src/app.rs:120\r'
+//! ```
+
+use std::{env, process::Command};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+
+/// The multiplexer wyv runs in, and the pane it runs in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux { pane: String },
+    WezTerm { pane: String },
+}
+
+impl Multiplexer {
+    /// The multiplexer the environment says wyv runs in, if any.
+    pub fn detect() -> Option<Self> {
+        Self::from_env(|name| env::var(name).ok())
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        if let (Some(_), Some(pane)) = (var("TMUX"), var("TMUX_PANE")) {
+            return Some(Multiplexer::Tmux { pane });
+        }
+        var("WEZTERM_PANE").map(|pane| Multiplexer::WezTerm { pane })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Multiplexer::Tmux { .. } => "tmux",
+            Multiplexer::WezTerm { .. } => "WezTerm",
+        }
+    }
+
+    /// Another pane in wyv's window, or a new one split off to its right.
+    pub fn neighbour(&self) -> Result<String> {
+        match self {
+            Multiplexer::Tmux { pane } => {
+                let panes = run("tmux", &["list-panes", "-t", pane, "-F", "#{pane_id}"])?;
+                if let Some(other) = panes.lines().find(|p| *p != pane) {
+                    return Ok(other.to_string());
+                }
+                let env = format!("WYV_PANE={}", pane);
+                let args = ["split-window", "-h", "-d", "-P", "-F", "#{pane_id}"];
+                let split = run("tmux", &[&args[..], &["-t", pane, "-e", &env]].concat())?;
+                Ok(split.trim().to_string())
+            }
+            Multiplexer::WezTerm { pane } => {
+                let panes = run("wezterm", &["cli", "list", "--format", "json"])?;
+                if let Some(other) = wezterm_neighbour(&panes, pane) {
+                    return Ok(other);
+                }
+                let env = format!("WYV_PANE={}", pane);
+                let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+                let args = ["cli", "split-pane", "--right", "--pane-id", pane, "--"];
+                let split = run("wezterm", &[&args[..], &["env", &env, &shell]].concat())?;
+                Ok(split.trim().to_string())
+            }
+        }
+    }
+
+    /// Types `text` into `pane`, pressing enter after each line if `enter`.
+    pub fn send(&self, pane: &str, text: &str, enter: bool) -> Result<()> {
+        match self {
+            Multiplexer::Tmux { .. } => {
+                for line in text.lines() {
+                    run("tmux", &["send-keys", "-t", pane, "-l", line])?;
+                    if enter {
+                        run("tmux", &["send-keys", "-t", pane, "Enter"])?;
+                    }
+                }
+            }
+            Multiplexer::WezTerm { .. } => {
+                let text = match enter {
+                    true => text.lines().map(|l| format!("{}\r", l)).collect(),
+                    false => text.to_string(),
+                };
+                let args = ["cli", "send-text", "--pane-id", pane, "--no-paste", &text];
+                run("wezterm", &args)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Another pane in the same tab as `own`, from `wezterm cli list`'s JSON.
+fn wezterm_neighbour(list: &str, own: &str) -> Option<String> {
+    let panes: Value = serde_json::from_str(list).ok()?;
+    let panes = panes.as_array()?;
+    let id = |pane: &Value| pane["pane_id"].as_u64().map(|id| id.to_string());
+    let tab = panes
+        .iter()
+        .find(|p| id(p).as_deref() == Some(own))?
+        .get("tab_id")?;
+    panes
+        .iter()
+        .filter(|p| p.get("tab_id") == Some(tab))
+        .filter_map(id)
+        .find(|id| id != own)
+}
+
+/// Quotes `path` for a shell if it has anything a shell would split or
+/// expand.
+pub fn shell_quote(path: &str) -> String {
+    let plain = |c: char| c.is_alphanumeric() || "/._-+:,@%".contains(c);
+    match !path.is_empty() && path.chars().all(plain) {
+        true => path.to_string(),
+        false => format!("'{}'", path.replace('\'', r"'\''")),
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("could not run {}", program))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{} {}: {}", program, args[0], stderr.trim());
+    }
+    String::from_utf8(output.stdout).map_err(|_| anyhow!("{}: output isn't UTF-8", program))
+}
+
+#[test]
+fn test_detect_and_find_neighbours() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        }
+    };
+    assert_eq!(
+        Multiplexer::from_env(env(&[
+            ("TMUX", "/tmp/tmux-1/default,1,0"),
+            ("TMUX_PANE", "%3")
+        ])),
+        Some(Multiplexer::Tmux {
+            pane: "%3".to_string()
+        })
+    );
+    assert_eq!(
+        Multiplexer::from_env(env(&[("WEZTERM_PANE", "7")])).map(|m| m.name()),
+        Some("WezTerm")
+    );
+    assert_eq!(Multiplexer::from_env(env(&[("TMUX_PANE", "%3")])), None);
+
+    let list = r#"[
+        {"window_id":0,"tab_id":0,"pane_id":0},
+        {"window_id":0,"tab_id":1,"pane_id":7},
+        {"window_id":0,"tab_id":1,"pane_id":9}
+    ]"#;
+    assert_eq!(wezterm_neighbour(list, "7").as_deref(), Some("9"));
+    assert_eq!(wezterm_neighbour(list, "0"), None);
+
+    assert_eq!(shell_quote("/src/app.rs"), "/src/app.rs");
+    assert_eq!(
+        shell_quote("/my notes/it's.txt"),
+        r"'/my notes/it'\''s.txt'"
+    );
+}
@@ -0,0 +1,236 @@
+//! How long the main loop waits for input before drawing again. Input
+//! wakes it at once; the wait only decides how soon results from the
+//! runtime show up when nothing is typed. While the window is being
+//! resized, frames are also held back until the size settles, and they
+//! never come faster than `editor.max_fps` allows. For screen readers,
+//! quiet waits draw nothing at all.
+
+use std::time::{Duration, Instant};
+
+/// The wait right after a key, a click or a message from the runtime.
+pub const MIN_WAIT: Duration = Duration::from_millis(16);
+/// The longest wait while jobs, tasks or the terminal are running.
+pub const BUSY_WAIT: Duration = Duration::from_millis(100);
+/// The longest wait once nothing has happened for a while.
+pub const IDLE_WAIT: Duration = Duration::from_secs(1);
+/// How long the size has to stay put after a resize before the layout is
+/// worked out for good.
+pub const RESIZE_SETTLE: Duration = Duration::from_millis(50);
+/// The time between frames while resizes keep coming, so the window
+/// doesn't look frozen while it's dragged.
+pub const RESIZE_FRAME: Duration = Duration::from_millis(100);
+
+/// Stretches the wait for input while nothing happens: it starts at
+/// [`MIN_WAIT`] after any activity and doubles with every quiet frame, up
+/// to [`BUSY_WAIT`] while work is running and [`IDLE_WAIT`] otherwise.
+#[derive(Debug)]
+pub struct Pacer {
+    wait: Duration,
+    /// When the last resize came, while they're still coming.
+    resized: Option<Instant>,
+    drawn: Option<Instant>,
+    /// The shortest time between two frames; zero without a cap.
+    frame: Duration,
+    /// A frame was held back to keep to `frame`, and is still to be drawn.
+    held: bool,
+    /// Whether frames wait for something to happen.
+    on_change: bool,
+    /// Something happened since the last frame.
+    changed: bool,
+}
+
+impl Default for Pacer {
+    fn default() -> Self {
+        Pacer {
+            wait: MIN_WAIT,
+            resized: None,
+            drawn: None,
+            frame: Duration::ZERO,
+            held: false,
+            on_change: false,
+            changed: true,
+        }
+    }
+}
+
+impl Pacer {
+    /// Draws at most `max_fps` frames a second, or as many as there's
+    /// call for with 0.
+    pub fn set_max_fps(&mut self, max_fps: u32) {
+        self.frame = match max_fps {
+            0 => Duration::ZERO,
+            fps => Duration::from_secs(1) / fps,
+        };
+        self.wait = self.wait.max(self.frame);
+    }
+
+    /// Draws only once something has happened rather than after every
+    /// wait, since screen readers may read out what's drawn again.
+    pub fn set_on_change(&mut self, on_change: bool) {
+        self.on_change = on_change;
+    }
+
+    /// How long to wait for input before the next frame. While resizing,
+    /// no longer than it takes the size to settle, and with a frame held
+    /// back, no longer than until it can be drawn.
+    pub fn timeout(&self) -> Duration {
+        let mut wait = self.wait;
+        if let Some(resized) = self.resized {
+            wait = wait.min(RESIZE_SETTLE.saturating_sub(resized.elapsed()));
+        }
+        if let (true, Some(drawn)) = (self.held, self.drawn) {
+            wait = wait.min(self.frame.saturating_sub(drawn.elapsed()));
+        }
+        match wait == self.wait {
+            true => wait,
+            false => wait.max(Duration::from_millis(1)),
+        }
+    }
+
+    /// The terminal was resized at `now`.
+    pub fn resized(&mut self, now: Instant) {
+        self.resized = Some(now);
+        self.changed = true;
+    }
+
+    /// Whether to draw at `now`: always, except while resizes are still
+    /// coming, when only every [`RESIZE_FRAME`] and once they settle, and
+    /// sooner after the last frame than the frame rate allows. Waiting
+    /// for changes, never while nothing has happened.
+    pub fn should_draw(&mut self, now: Instant) -> bool {
+        let due = self.resize_allows(now) && (self.changed || !self.on_change);
+        let allowed = self
+            .drawn
+            .is_none_or(|drawn| now.duration_since(drawn) >= self.frame);
+        self.held = due && !allowed;
+        due && allowed
+    }
+
+    fn resize_allows(&mut self, now: Instant) -> bool {
+        let resized = match self.resized {
+            Some(resized) => resized,
+            None => return true,
+        };
+        if now.duration_since(resized) >= RESIZE_SETTLE {
+            self.resized = None;
+            self.changed = true;
+            return true;
+        }
+        self.drawn
+            .is_none_or(|drawn| now.duration_since(drawn) >= RESIZE_FRAME)
+    }
+
+    /// A frame was drawn at `now`.
+    pub fn drawn(&mut self, now: Instant) {
+        self.drawn = Some(now);
+        self.changed = false;
+    }
+
+    /// Something happened: the next frames come quickly.
+    pub fn active(&mut self) {
+        self.wait = MIN_WAIT.max(self.frame);
+        self.changed = true;
+    }
+
+    /// A frame passed without input or messages.
+    pub fn quiet(&mut self, busy: bool) {
+        let limit = if busy { BUSY_WAIT } else { IDLE_WAIT };
+        self.wait = (self.wait * 2).min(limit.max(self.frame));
+    }
+}
+
+#[test]
+fn test_wait_stretches_while_quiet() {
+    let mut pacer = Pacer::default();
+    assert_eq!(pacer.timeout(), MIN_WAIT);
+    for _ in 0..10 {
+        pacer.quiet(true);
+    }
+    assert_eq!(pacer.timeout(), BUSY_WAIT);
+    for _ in 0..10 {
+        pacer.quiet(false);
+    }
+    assert_eq!(pacer.timeout(), IDLE_WAIT);
+    // Work starting again pulls the wait back down on the next frame.
+    pacer.quiet(true);
+    assert_eq!(pacer.timeout(), BUSY_WAIT);
+    pacer.active();
+    assert_eq!(pacer.timeout(), MIN_WAIT);
+}
+
+#[test]
+fn test_resize_storms_draw_coarsely_then_once_settled() {
+    let mut pacer = Pacer::default();
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    pacer.drawn(start);
+
+    // A resize every 10ms for 300ms draws every RESIZE_FRAME at most.
+    let mut frames = 0;
+    for ms in (10..300).step_by(10) {
+        pacer.resized(at(ms));
+        if pacer.should_draw(at(ms + 5)) {
+            pacer.drawn(at(ms + 5));
+            frames += 1;
+        }
+    }
+    assert_eq!(frames, 2);
+    assert!(pacer.timeout() <= RESIZE_SETTLE);
+
+    // Then the size settles and the final layout is drawn.
+    pacer.drawn(at(300));
+    assert!(!pacer.should_draw(at(330)));
+    assert!(pacer.should_draw(at(340)));
+    assert_eq!(pacer.timeout(), MIN_WAIT);
+    pacer.drawn(at(340));
+    assert!(pacer.should_draw(at(341)));
+}
+
+#[test]
+fn test_frames_keep_to_the_frame_rate_cap() {
+    let mut pacer = Pacer::default();
+    pacer.set_max_fps(10);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    // A key every 20ms draws a frame every 100ms, holding the rest back.
+    let mut frames = 0;
+    for ms in (0..500).step_by(20) {
+        pacer.active();
+        if pacer.should_draw(at(ms)) {
+            pacer.drawn(at(ms));
+            frames += 1;
+        }
+    }
+    assert_eq!(frames, 5);
+    assert!(pacer.timeout() <= Duration::from_millis(100));
+    // Quiet or not, the loop never wakes more often than it may draw.
+    pacer.active();
+    assert_eq!(pacer.wait, Duration::from_millis(100));
+
+    pacer.set_max_fps(0);
+    pacer.active();
+    assert!(pacer.should_draw(at(481)));
+    assert_eq!(pacer.timeout(), MIN_WAIT);
+}
+
+#[test]
+fn test_waiting_for_changes_skips_quiet_frames() {
+    let mut pacer = Pacer::default();
+    pacer.set_on_change(true);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    assert!(pacer.should_draw(at(0)));
+    pacer.drawn(at(0));
+    pacer.quiet(false);
+    assert!(!pacer.should_draw(at(50)));
+    pacer.active();
+    assert!(pacer.should_draw(at(60)));
+    pacer.drawn(at(60));
+
+    // The layout a resize settles on is still drawn.
+    pacer.resized(at(100));
+    assert!(pacer.should_draw(at(200)));
+    pacer.drawn(at(200));
+    assert!(!pacer.should_draw(at(300)));
+}
@@ -0,0 +1,129 @@
+//! One spelling for each file, so that `./src/../src/main.rs` and
+//! `src/main.rs` open the same buffer and find the same tree node. Buffers,
+//! the tree, command line arguments and saved trees all go through
+//! [`normalize`]. Also whether a path can be written, asked before saving
+//! rather than found out from a failed write.
+
+use std::{
+    env, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// `path` made absolute with links resolved, as far as it exists. The part
+/// that doesn't exist yet, e.g. a file about to be created, is added on
+/// with `.` and `..` worked out by name.
+pub fn normalize(path: &Path) -> PathBuf {
+    let absolute = match path.is_absolute() {
+        true => path.to_owned(),
+        false => env::current_dir().unwrap_or_default().join(path),
+    };
+    let mut existing = absolute.as_path();
+    loop {
+        if let Ok(resolved) = fs::canonicalize(existing) {
+            return match absolute.strip_prefix(existing) {
+                Ok(rest) if !rest.as_os_str().is_empty() => lexical(&resolved.join(rest)),
+                _ => resolved,
+            };
+        }
+        existing = match existing.parent() {
+            Some(parent) => parent,
+            None => return lexical(&absolute),
+        };
+    }
+}
+
+/// `path` with `.` dropped and each `..` taking off the name before it,
+/// without looking at the filesystem. A `..` at the start stays.
+pub fn lexical(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                out.pop();
+            }
+            Component::ParentDir if out.has_root() => (),
+            component => out.push(component),
+        }
+    }
+    if out.as_os_str().is_empty() {
+        out.push(".");
+    }
+    out
+}
+
+/// Whether this user can write the file at `path`, or create it if it
+/// doesn't exist, found out without changing anything. A file is opened
+/// for writing and closed again; in the directory to create one in, an
+/// empty file is made and removed, since only the system knows what its
+/// permission bits, ownership and mount options add up to.
+pub fn check_writable(path: &Path) -> io::Result<()> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_file() => fs::OpenOptions::new().write(true).open(path).map(drop),
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let dir = path.ancestors().skip(1).find(|dir| dir.is_dir());
+            match dir {
+                Some(dir) => {
+                    let probe = dir.join(format!(".wyv-writable-{}", std::process::id()));
+                    fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&probe)?;
+                    fs::remove_file(&probe)
+                }
+                None => Ok(()),
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[test]
+fn test_paths_are_spelled_one_way() {
+    assert_eq!(
+        lexical(Path::new("./src/../src/main.rs")),
+        Path::new("src/main.rs")
+    );
+    assert_eq!(lexical(Path::new("../a/./b/..")), Path::new("../a"));
+    assert_eq!(lexical(Path::new("/../etc")), Path::new("/etc"));
+    assert_eq!(lexical(Path::new("a/..")), Path::new("."));
+
+    let main = normalize(Path::new("src/main.rs"));
+    assert!(main.is_absolute());
+    assert_eq!(normalize(Path::new("./src/../src/main.rs")), main);
+
+    // Files that don't exist yet are placed under their real directory.
+    let new = normalize(Path::new("./src/new/../not-yet.rs"));
+    assert_eq!(new, main.with_file_name("not-yet.rs"));
+}
+
+#[test]
+fn test_writable_files_and_new_files_pass() {
+    let path = env::temp_dir().join(format!("wyv-writable-test-{}", std::process::id()));
+    check_writable(&path).unwrap();
+    fs::write(&path, "").unwrap();
+    check_writable(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_new_files_in_read_only_directories_fail() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = env::temp_dir().join(format!("wyv-read-only-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+    // Root writes anywhere, whatever the bits say.
+    let root = fs::write(dir.join("probe"), "").is_ok();
+    let result = check_writable(&dir.join("new.rs"));
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    match root {
+        true => result.unwrap(),
+        false => assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied),
+    }
+}
@@ -0,0 +1,93 @@
+//! Frame timing for the performance overlay (`f12` or `:perf`).
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How far back frames count towards the frame rate.
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+pub struct PerfStats {
+    frames: VecDeque<Instant>,
+    last_draw: Duration,
+    queue_depth: usize,
+    /// Time the last frame spent in each part of the loop, in loop order.
+    timings: Vec<(&'static str, Duration)>,
+}
+
+impl PerfStats {
+    /// Runs `f`, recording how long it took under `subsystem`.
+    pub fn time<T>(&mut self, subsystem: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(subsystem, start.elapsed());
+        result
+    }
+
+    pub fn record(&mut self, subsystem: &'static str, duration: Duration) {
+        match self.timings.iter_mut().find(|(name, _)| *name == subsystem) {
+            Some((_, slot)) => *slot = duration,
+            None => self.timings.push((subsystem, duration)),
+        }
+    }
+
+    /// Marks the end of a frame whose draw took `draw`.
+    pub fn frame(&mut self, draw: Duration) {
+        let now = Instant::now();
+        self.last_draw = draw;
+        self.frames.push_back(now);
+        while self
+            .frames
+            .front()
+            .is_some_and(|&t| now.duration_since(t) > FPS_WINDOW)
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Number of messages waiting at the start of the last frame.
+    pub fn set_queue_depth(&mut self, depth: usize) {
+        self.queue_depth = depth;
+    }
+
+    pub fn fps(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The overlay's lines.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("fps     {}", self.fps()),
+            format!("draw    {}", format_duration(self.last_draw)),
+            format!("queue   {}", self.queue_depth),
+        ];
+        lines.extend(
+            self.timings
+                .iter()
+                .map(|(name, d)| format!("{:<7} {}", name, format_duration(*d))),
+        );
+        lines
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    format!("{:.2}ms", d.as_secs_f64() * 1000.0)
+}
+
+#[test]
+fn test_stats_lines() {
+    let mut stats = PerfStats::default();
+    stats.record("input", Duration::from_micros(1500));
+    stats.record("input", Duration::from_micros(250));
+    stats.set_queue_depth(3);
+    stats.frame(Duration::from_millis(2));
+    stats.frame(Duration::from_millis(4));
+
+    assert_eq!(stats.fps(), 2);
+    assert_eq!(
+        stats.lines(),
+        ["fps     2", "draw    4.00ms", "queue   3", "input   0.25ms"]
+    );
+}
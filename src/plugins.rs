@@ -0,0 +1,371 @@
+//! Plugins are external executables speaking line-delimited JSON-RPC 2.0 on
+//! stdin/stdout.
+//!
+//! On start wyv sends `initialize` and the plugin answers with the commands it
+//! provides and the events it wants:
+//!
+//! ```json
+//! {"jsonrpc":"2.0","id":1,"result":{"commands":[{"name":"hello","description":"Say hi"}],"events":["file_opened"]}}
+//! ```
+//!
+//! wyv then sends `event` notifications (`{"event":"file_opened","path":..}`)
+//! and `command` requests (`{"name":"hello","args":[..]}`). A plugin can send
+//! `status` (`{"message":..}`) and `render` (`{"lines":[..]}`) notifications at
+//! any time; `render` replaces the plugin's content in the plugins panel.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::runtime::Message;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PluginCommand {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Things that happen in the app which plugins can subscribe to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginEvent<'a> {
+    FileOpened(&'a Path),
+    BufferSaved(&'a Path),
+}
+
+impl PluginEvent<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            PluginEvent::FileOpened(_) => "file_opened",
+            PluginEvent::BufferSaved(_) => "buffer_saved",
+        }
+    }
+
+    fn params(&self) -> Value {
+        match self {
+            PluginEvent::FileOpened(path) | PluginEvent::BufferSaved(path) => {
+                json!({ "event": self.name(), "path": path })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct InitializeResult {
+    #[serde(default)]
+    commands: Vec<PluginCommand>,
+    #[serde(default)]
+    events: HashSet<String>,
+}
+
+struct Plugin {
+    name: String,
+    child: Child,
+    stdin: mpsc::Sender<String>,
+    next_id: u64,
+    commands: Vec<PluginCommand>,
+    events: HashSet<String>,
+    /// Requests awaiting a response, by id, with the method they called.
+    pending: HashMap<u64, String>,
+    panel: Vec<String>,
+    alive: bool,
+}
+
+impl Plugin {
+    fn start(
+        config: &PluginConfig,
+        (generation, index): (u64, usize),
+        tx: UnboundedSender<Message>,
+    ) -> Result<Self> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("starting plugin `{}`", config.name))?;
+
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                let message = serde_json::from_str(&line).unwrap_or(Value::Null);
+                let message = Message::Plugin {
+                    generation,
+                    index,
+                    message,
+                };
+                if tx.send(message).is_err() {
+                    return;
+                }
+            }
+            let _ = tx.send(Message::PluginExited { generation, index });
+        });
+
+        // Writes go through a thread so a plugin that stops reading can't
+        // block the UI.
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("no stdin"))?;
+        let (stdin_tx, stdin_rx) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for line in stdin_rx {
+                if writeln!(stdin, "{}", line)
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        let mut plugin = Plugin {
+            name: config.name.clone(),
+            child,
+            stdin: stdin_tx,
+            next_id: 0,
+            commands: Vec::new(),
+            events: HashSet::new(),
+            pending: HashMap::new(),
+            panel: Vec::new(),
+            alive: true,
+        };
+        plugin.request(
+            "initialize",
+            json!({ "version": env!("CARGO_PKG_VERSION") }),
+        );
+        Ok(plugin)
+    }
+
+    fn request(&mut self, method: &str, params: Value) {
+        self.next_id += 1;
+        self.pending.insert(self.next_id, method.to_string());
+        self.send(
+            json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params }),
+        );
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn send(&mut self, message: Value) {
+        if self.alive && self.stdin.send(message.to_string()).is_err() {
+            self.alive = false;
+        }
+    }
+}
+
+/// The running plugins.
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<Plugin>,
+    /// Tags its plugins' messages, so those from an earlier host's are
+    /// left alone.
+    generation: u64,
+}
+
+impl PluginHost {
+    /// Starts every configured plugin. Plugins that fail to start are
+    /// skipped and their errors returned.
+    pub fn start(
+        configs: &[PluginConfig],
+        tx: &UnboundedSender<Message>,
+    ) -> (Self, Vec<anyhow::Error>) {
+        let mut host = PluginHost {
+            plugins: Vec::new(),
+            generation: crate::runtime::next_generation(),
+        };
+        let mut errors = Vec::new();
+        for config in configs {
+            let id = (host.generation, host.plugins.len());
+            match Plugin::start(config, id, tx.clone()) {
+                Ok(plugin) => host.plugins.push(plugin),
+                Err(e) => errors.push(e),
+            }
+        }
+        (host, errors)
+    }
+
+    /// Commands registered by running plugins, with the plugin's name.
+    pub fn commands(&self) -> impl Iterator<Item = (&str, &PluginCommand)> {
+        self.plugins
+            .iter()
+            .filter(|p| p.alive)
+            .flat_map(|p| p.commands.iter().map(move |c| (p.name.as_str(), c)))
+    }
+
+    /// Sends `name` to the plugin that registered it. Returns `false` if no
+    /// plugin did.
+    pub fn run_command(&mut self, name: &str, args: &[&str]) -> bool {
+        let plugin = self
+            .plugins
+            .iter_mut()
+            .find(|p| p.alive && p.commands.iter().any(|c| c.name == name));
+        match plugin {
+            Some(plugin) => {
+                plugin.request("command", json!({ "name": name, "args": args }));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn notify(&mut self, event: &PluginEvent) {
+        for plugin in &mut self.plugins {
+            if plugin.events.contains(event.name()) {
+                plugin.notify("event", event.params());
+            }
+        }
+    }
+
+    /// Content plugins rendered for the plugins panel, by plugin name.
+    pub fn panels(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.plugins
+            .iter()
+            .filter(|p| !p.panel.is_empty())
+            .map(|p| (p.name.as_str(), p.panel.as_slice()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Handles a line a plugin sent. Returns a message for the status bar,
+    /// if the plugin asked for one or something went wrong.
+    pub fn handle(&mut self, (generation, index): (u64, usize), message: Value) -> Option<String> {
+        if generation != self.generation {
+            return None;
+        }
+        let plugin = self.plugins.get_mut(index)?;
+        if message.is_null() {
+            return Some(format!("plugin {}: invalid message", plugin.name));
+        }
+
+        // Responses to our requests.
+        if let Some(id) = message.get("id").and_then(Value::as_u64) {
+            let method = plugin.pending.remove(&id)?;
+            if let Some(error) = message.get("error") {
+                let text = error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("error");
+                return Some(format!("plugin {}: {}", plugin.name, text));
+            }
+            let result = message.get("result").cloned().unwrap_or(Value::Null);
+            if method == "initialize" {
+                let init: InitializeResult = serde_json::from_value(result).unwrap_or_default();
+                tracing::info!(plugin = %plugin.name, commands = init.commands.len(), "plugin initialized");
+                plugin.commands = init.commands;
+                plugin.events = init.events;
+            }
+            return None;
+        }
+
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        match message.get("method").and_then(Value::as_str) {
+            Some("status") => params
+                .get("message")
+                .and_then(Value::as_str)
+                .map(|m| format!("{}: {}", plugin.name, m)),
+            Some("render") => {
+                plugin.panel = params
+                    .get("lines")
+                    .and_then(Value::as_array)
+                    .map(|lines| {
+                        lines
+                            .iter()
+                            .map(|l| l.as_str().unwrap_or_default().to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub fn exited(&mut self, (generation, index): (u64, usize)) -> Option<String> {
+        if generation != self.generation {
+            return None;
+        }
+        let plugin = self.plugins.get_mut(index)?;
+        plugin.alive = false;
+        tracing::warn!(plugin = %plugin.name, "plugin exited");
+        Some(format!("plugin {} exited", plugin.name))
+    }
+}
+
+impl Drop for PluginHost {
+    fn drop(&mut self) {
+        for plugin in &mut self.plugins {
+            let _ = plugin.child.kill();
+            let _ = plugin.child.wait();
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_plugin_round_trip() {
+    use std::time::{Duration, Instant};
+
+    // Answers `initialize`, then turns every command into a render.
+    let script = r#"
+        read init
+        echo '{"jsonrpc":"2.0","id":1,"result":{"commands":[{"name":"hello"}],"events":["file_opened"]}}'
+        while read line; do
+            echo '{"jsonrpc":"2.0","method":"render","params":{"lines":["hi there"]}}'
+        done
+    "#;
+    let config = PluginConfig {
+        name: "echo".to_string(),
+        command: "sh".to_string(),
+        args: vec!["-c".to_string(), script.to_string()],
+    };
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let (mut host, errors) = PluginHost::start(&[config], &tx);
+    assert!(errors.is_empty());
+
+    let mut pump = |host: &mut PluginHost, done: &dyn Fn(&PluginHost) -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(host) {
+            assert!(Instant::now() < deadline, "plugin never answered");
+            while let Ok(Message::Plugin {
+                generation,
+                index,
+                message,
+            }) = rx.try_recv()
+            {
+                host.handle((generation, index), message);
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    };
+
+    pump(&mut host, &|h| h.commands().count() == 1);
+    assert!(host.run_command("hello", &[]));
+    assert!(!host.run_command("missing", &[]));
+    pump(&mut host, &|h| h.panels().count() == 1);
+    let (name, lines) = host.panels().next().unwrap();
+    assert_eq!((name, lines), ("echo", &["hi there".to_string()][..]));
+}
@@ -0,0 +1,105 @@
+//! Per-project settings in a `.wyv.toml` at the workspace root. Anything in
+//! it that runs programs only takes effect once the user trusts that exact
+//! version of the file; the trusted versions are remembered by their SHA-256
+//! in the state directory, so editing the file asks again.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::config;
+
+pub const PROJECT_FILE: &str = ".wyv.toml";
+const TRUST_FILE: &str = "trusted.json";
+
+pub fn config_path(root: &Path) -> PathBuf {
+    root.join(PROJECT_FILE)
+}
+
+/// Project config files the user trusted, keyed by absolute path, with the
+/// hash of the contents they agreed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustStore {
+    path: PathBuf,
+}
+
+impl TrustStore {
+    pub fn new(path: PathBuf) -> Self {
+        TrustStore { path }
+    }
+
+    pub fn default_location() -> Option<Self> {
+        config::state_dir().map(|d| TrustStore::new(d.join(TRUST_FILE)))
+    }
+
+    pub fn is_trusted(&self, project: &Path, contents: &str) -> bool {
+        let key = key(project);
+        self.entries()
+            .map(|entries| entries.get(&key) == Some(&fingerprint(contents)))
+            .unwrap_or(false)
+    }
+
+    /// Trusts the project config at `project` as `contents`, the text the
+    /// user was shown. What's on disk by now may differ, and stays
+    /// untrusted if it does.
+    pub fn trust(&self, project: &Path, contents: &str) -> Result<()> {
+        let mut entries = self.entries().unwrap_or_default();
+        entries.insert(key(project), fingerprint(contents));
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&entries)?)?;
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<BTreeMap<String, String>> {
+        Ok(serde_json::from_str(&fs::read_to_string(&self.path)?)?)
+    }
+}
+
+fn key(project: &Path) -> String {
+    fs::canonicalize(project)
+        .unwrap_or_else(|_| project.to_owned())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// A hash no one can make a second file to match, since trusting one file
+/// lets it run programs.
+fn fingerprint(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[test]
+fn test_trust_follows_file_contents() {
+    let dir = std::env::temp_dir().join(format!("wyv-trust-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let project = config_path(&dir);
+    fs::write(&project, "[editor]\ntab_width = 2\n").unwrap();
+    let store = TrustStore::new(dir.join(TRUST_FILE));
+
+    let contents = fs::read_to_string(&project).unwrap();
+    assert!(!store.is_trusted(&project, &contents));
+    store.trust(&project, &contents).unwrap();
+    assert!(store.is_trusted(&project, &contents));
+    assert!(!store.is_trusted(&project, "[editor]\ntab_width = 8\n"));
+
+    // What was shown is trusted, not what the file was changed to since.
+    let shown = "[editor]\ntab_width = 4\n";
+    fs::write(&project, "[[lsp]]\nname = \"x\"\ncommand = \"evil\"\n").unwrap();
+    store.trust(&project, shown).unwrap();
+    assert!(store.is_trusted(&project, shown));
+    let now = fs::read_to_string(&project).unwrap();
+    assert!(!store.is_trusted(&project, &now));
+    assert_eq!(fingerprint("").len(), 64);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
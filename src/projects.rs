@@ -0,0 +1,76 @@
+//! The workspace roots wyv has been opened on, most recent first, for the
+//! project picker to switch between. Kept in the state directory; what's
+//! remembered of each one is its [session](crate::session).
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+
+use crate::{config, paths};
+
+const PROJECTS_FILE: &str = "projects.json";
+
+/// How many roots the list remembers.
+pub const MAX_PROJECTS: usize = 30;
+
+/// The file the roots are listed in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectStore {
+    path: PathBuf,
+}
+
+impl ProjectStore {
+    pub fn new(path: PathBuf) -> Self {
+        ProjectStore { path }
+    }
+
+    pub fn default_location() -> Option<Self> {
+        config::state_dir().map(|d| ProjectStore::new(d.join(PROJECTS_FILE)))
+    }
+
+    /// The roots opened so far, most recent first; none if the list can't
+    /// be read.
+    pub fn load(&self) -> Vec<PathBuf> {
+        let read = fs::read_to_string(&self.path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Ok(serde_json::from_str(&text)?));
+        match read {
+            Ok(roots) => roots,
+            Err(e) => {
+                tracing::debug!(path = %self.path.display(), error = %e, "no saved projects");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Puts `root` first.
+    pub fn visit(&self, root: &Path) -> Result<()> {
+        let root = paths::normalize(root);
+        let mut roots = self.load();
+        roots.retain(|r| *r != root);
+        roots.insert(0, root);
+        roots.truncate(MAX_PROJECTS);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&roots)?)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_projects_are_listed_most_recent_first() {
+    let dir = std::env::temp_dir().join(format!("wyv-projects-test-{}", std::process::id()));
+    let store = ProjectStore::new(dir.join("projects.json"));
+    assert!(store.load().is_empty());
+    store.visit(Path::new("src")).unwrap();
+    store.visit(Path::new("tests")).unwrap();
+    store.visit(Path::new("./src")).unwrap();
+    let roots = store.load();
+    assert_eq!(roots.len(), 2);
+    assert!(roots[0].ends_with("src") && roots[1].ends_with("tests"));
+    fs::remove_dir_all(dir).unwrap();
+}
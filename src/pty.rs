@@ -0,0 +1,415 @@
+//! The integrated terminal: the user's shell running on a pseudo-terminal,
+//! with its output fed through a terminal emulator whose screen the
+//! terminal pane draws.
+
+use std::path::Path;
+#[cfg(feature = "terminal")]
+use std::{
+    io::{Read, Write},
+    thread,
+};
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+#[cfg(feature = "terminal")]
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use ratatui::style::Style;
+#[cfg(feature = "terminal")]
+use ratatui::style::{Color, Modifier};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::runtime::Message;
+
+/// Where the terminal pane opens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminalPosition {
+    /// In the panel below the editor.
+    #[default]
+    Bottom,
+    /// Beside the editor.
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerminalOptions {
+    /// The program to run; `$SHELL` if unset.
+    pub shell: Option<String>,
+    pub position: TerminalPosition,
+    /// Lines kept after they scroll off the top.
+    pub scrollback: usize,
+}
+
+impl Default for TerminalOptions {
+    fn default() -> Self {
+        TerminalOptions {
+            shell: None,
+            position: TerminalPosition::Bottom,
+            scrollback: 1000,
+        }
+    }
+}
+
+impl TerminalOptions {
+    pub fn shell(&self) -> String {
+        self.shell
+            .clone()
+            .or_else(|| std::env::var("SHELL").ok())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| default_shell().to_string())
+    }
+}
+
+fn default_shell() -> &'static str {
+    match cfg!(windows) {
+        true => "cmd.exe",
+        false => "/bin/sh",
+    }
+}
+
+/// A shell on a pseudo-terminal. Its output arrives as
+/// [`Message::TerminalOutput`] and must be passed to [`Pty::process`];
+/// [`Message::TerminalExited`] follows once the shell is gone.
+#[cfg(feature = "terminal")]
+pub struct Pty {
+    parser: vt100::Parser,
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+}
+
+#[cfg(feature = "terminal")]
+impl Pty {
+    pub fn spawn(
+        options: &TerminalOptions,
+        cwd: &Path,
+        (rows, cols): (u16, u16),
+        sender: UnboundedSender<Message>,
+    ) -> Result<Self> {
+        let pair = native_pty_system().openpty(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+        let mut command = CommandBuilder::new(options.shell());
+        command.cwd(cwd);
+        command.env("TERM", "xterm-256color");
+        let child = pair.slave.spawn_command(command)?;
+        // Only the shell should hold the slave side, so that reads see EOF
+        // once it exits.
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if sender
+                            .send(Message::TerminalOutput(buf[..n].to_vec()))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+            }
+            let _ = sender.send(Message::TerminalExited);
+        });
+        tracing::info!(shell = %options.shell(), "terminal started");
+        Ok(Pty {
+            parser: vt100::Parser::new(rows, cols, options.scrollback),
+            master: pair.master,
+            writer,
+            child,
+        })
+    }
+
+    /// Feeds output from the shell to the emulator.
+    pub fn process(&mut self, bytes: &[u8]) {
+        self.parser.process(bytes);
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Sends a key press to the shell as the bytes a terminal would.
+    pub fn send_key(&mut self, key: &KeyEvent) -> Result<()> {
+        let application_cursor = self.parser.screen().application_cursor();
+        match key_bytes(key, application_cursor) {
+            Some(bytes) => self.write(&bytes),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends pasted text, bracketed if the program asked for that.
+    pub fn paste(&mut self, text: &str) -> Result<()> {
+        match self.parser.screen().bracketed_paste() {
+            true => self.write(format!("\x1b[200~{}\x1b[201~", text).as_bytes()),
+            false => self.write(text.as_bytes()),
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        self.parser.screen().size()
+    }
+
+    /// Resizes the screen and tells the shell, if the size changed.
+    pub fn resize(&mut self, (rows, cols): (u16, u16)) -> Result<()> {
+        if (rows, cols) == self.size() || rows == 0 || cols == 0 {
+            return Ok(());
+        }
+        self.parser.set_size(rows, cols);
+        self.master.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+    }
+
+    /// How many lines the view is scrolled back.
+    pub fn scrollback(&self) -> usize {
+        self.parser.screen().scrollback()
+    }
+
+    /// Scrolls the view back `lines` from the live screen, as far as there
+    /// is history.
+    pub fn set_scrollback(&mut self, lines: usize) {
+        self.parser.set_scrollback(lines);
+    }
+
+    /// How many lines of history there are above the live screen.
+    pub fn history(&mut self) -> usize {
+        let scrollback = self.scrollback();
+        self.set_scrollback(usize::MAX);
+        let history = self.scrollback();
+        self.set_scrollback(scrollback);
+        history
+    }
+
+    /// The text and style of the cell at `row`, `col` of the view, or
+    /// `None` for the second half of a wide character.
+    pub fn cell(&self, row: u16, col: u16) -> Option<(String, Style)> {
+        let cell = self.parser.screen().cell(row, col)?;
+        if cell.is_wide_continuation() {
+            return None;
+        }
+        let mut style = Style {
+            fg: color(cell.fgcolor()),
+            bg: color(cell.bgcolor()),
+            ..Style::default()
+        };
+        for (on, modifier) in [
+            (cell.bold(), Modifier::BOLD),
+            (cell.italic(), Modifier::ITALIC),
+            (cell.underline(), Modifier::UNDERLINED),
+            (cell.inverse(), Modifier::REVERSED),
+        ] {
+            if on {
+                style = style.add_modifier(modifier);
+            }
+        }
+        let text = match cell.has_contents() {
+            true => cell.contents(),
+            false => " ".to_string(),
+        };
+        Some((text, style))
+    }
+
+    /// Where the shell's cursor is in the view, unless it is hidden or
+    /// scrolled out of sight.
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        let screen = self.parser.screen();
+        match screen.hide_cursor() || screen.scrollback() > 0 {
+            true => None,
+            false => Some(screen.cursor_position()),
+        }
+    }
+
+    /// The text of lines `from` to `to`, counted from the top of the live
+    /// screen with history lines negative, one per line.
+    pub fn text(&mut self, from: isize, to: isize) -> String {
+        let scrollback = self.scrollback();
+        let (rows, cols) = self.size();
+        let mut lines = Vec::new();
+        for line in from..=to {
+            self.set_scrollback(line.min(0).unsigned_abs());
+            let row = line + self.scrollback() as isize;
+            if row < 0 || row >= rows as isize {
+                continue;
+            }
+            let screen = self.parser.screen();
+            let text = screen.contents_between(row as u16, 0, row as u16, cols);
+            lines.push(text.trim_end().to_string());
+        }
+        self.set_scrollback(scrollback);
+        lines.join("\n")
+    }
+}
+
+#[cfg(feature = "terminal")]
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+#[cfg(feature = "terminal")]
+/// `None` for the default colour, which the pane's style decides.
+fn color(color: vt100::Color) -> Option<Color> {
+    match color {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(i) => Some(Color::Indexed(i)),
+        vt100::Color::Rgb(r, g, b) => Some(Color::Rgb(r, g, b)),
+    }
+}
+
+/// Without the `terminal` feature there is never a shell to show.
+#[cfg(not(feature = "terminal"))]
+pub enum Pty {}
+
+#[cfg(not(feature = "terminal"))]
+impl Pty {
+    pub fn spawn(
+        _options: &TerminalOptions,
+        _cwd: &Path,
+        _size: (u16, u16),
+        _sender: UnboundedSender<Message>,
+    ) -> Result<Self> {
+        Err(crate::features::missing("terminal"))
+    }
+
+    pub fn process(&mut self, _bytes: &[u8]) {
+        match *self {}
+    }
+
+    pub fn write(&mut self, _bytes: &[u8]) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn send_key(&mut self, _key: &KeyEvent) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn paste(&mut self, _text: &str) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        match *self {}
+    }
+
+    pub fn resize(&mut self, _size: (u16, u16)) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn scrollback(&self) -> usize {
+        match *self {}
+    }
+
+    pub fn set_scrollback(&mut self, _lines: usize) {
+        match *self {}
+    }
+
+    pub fn history(&mut self) -> usize {
+        match *self {}
+    }
+
+    pub fn cell(&self, _row: u16, _col: u16) -> Option<(String, Style)> {
+        match *self {}
+    }
+
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        match *self {}
+    }
+
+    pub fn text(&mut self, _from: isize, _to: isize) -> String {
+        match *self {}
+    }
+}
+
+/// The bytes a terminal sends for `key`. With `application_cursor` the
+/// arrows use the form full-screen programs ask for.
+pub fn key_bytes(key: &KeyEvent, application_cursor: bool) -> Option<Vec<u8>> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let csi = |s: &str| format!("\x1b[{}", s).into_bytes();
+    let cursor = |c: char| match application_cursor {
+        true => format!("\x1bO{}", c).into_bytes(),
+        false => format!("\x1b[{}", c).into_bytes(),
+    };
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if ctrl && c.is_ascii_alphabetic() => {
+            vec![c.to_ascii_lowercase() as u8 & 0x1f]
+        }
+        KeyCode::Char(c @ ('@' | '[' | '\\' | ']' | '^' | '_')) if ctrl => vec![c as u8 & 0x1f],
+        KeyCode::Char(' ') if ctrl => vec![0],
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => csi("Z"),
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => cursor('A'),
+        KeyCode::Down => cursor('B'),
+        KeyCode::Right => cursor('C'),
+        KeyCode::Left => cursor('D'),
+        KeyCode::Home => cursor('H'),
+        KeyCode::End => cursor('F'),
+        KeyCode::Insert => csi("2~"),
+        KeyCode::Delete => csi("3~"),
+        KeyCode::PageUp => csi("5~"),
+        KeyCode::PageDown => csi("6~"),
+        KeyCode::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char).into_bytes(),
+        KeyCode::F(n @ 5..=12) => {
+            let code = [15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5];
+            csi(&format!("{}~", code))
+        }
+        _ => return None,
+    };
+    if alt {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
+
+#[test]
+fn test_key_bytes() {
+    let key = |code, modifiers| key_bytes(&KeyEvent::new(code, modifiers), false);
+    assert_eq!(
+        key(KeyCode::Char('a'), KeyModifiers::NONE),
+        Some(b"a".to_vec())
+    );
+    assert_eq!(
+        key(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        Some(vec![3])
+    );
+    assert_eq!(
+        key(KeyCode::Char('b'), KeyModifiers::ALT),
+        Some(b"\x1bb".to_vec())
+    );
+    assert_eq!(
+        key(KeyCode::Enter, KeyModifiers::NONE),
+        Some(b"\r".to_vec())
+    );
+    assert_eq!(
+        key(KeyCode::Up, KeyModifiers::NONE),
+        Some(b"\x1b[A".to_vec())
+    );
+    assert_eq!(
+        key(KeyCode::F(5), KeyModifiers::NONE),
+        Some(b"\x1b[15~".to_vec())
+    );
+    let up = KeyEvent::new(KeyCode::Up, KeyModifiers::NONE);
+    assert_eq!(key_bytes(&up, true), Some(b"\x1bOA".to_vec()));
+    assert_eq!(key(KeyCode::CapsLock, KeyModifiers::NONE), None);
+}
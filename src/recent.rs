@@ -0,0 +1,65 @@
+//! The files opened lately, most recent first, each with where its cursor
+//! was left so that reopening it picks up there. Kept with the workspace's
+//! session and listed by `:recent`.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{buffer::Position, jumps::Jump};
+
+/// How many files the list remembers.
+pub const MAX_RECENT: usize = 50;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RecentFiles {
+    files: Vec<Jump>,
+}
+
+impl RecentFiles {
+    /// Puts `path` first, with the cursor at `position`.
+    pub fn visit(&mut self, path: &Path, position: Position) {
+        self.files.retain(|file| file.path != path);
+        self.files.insert(0, Jump::new(path, position));
+        self.files.truncate(MAX_RECENT);
+    }
+
+    /// Notes that the cursor of `path` is at `position`, without moving it
+    /// up the list.
+    pub fn update(&mut self, path: &Path, position: Position) {
+        if let Some(file) = self.files.iter_mut().find(|file| file.path == path) {
+            file.position = position;
+        }
+    }
+
+    /// Where the cursor of `path` was left.
+    pub fn position(&self, path: &Path) -> Option<Position> {
+        let file = self.files.iter().find(|file| file.path == path)?;
+        Some(file.position)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Jump> {
+        self.files.iter()
+    }
+}
+
+#[test]
+fn test_recent_files_come_first_with_their_cursors() {
+    let (a, b) = (Path::new("/work/a.rs"), Path::new("/work/b.rs"));
+    let mut recent = RecentFiles::default();
+    recent.visit(a, Position::new(0, 0));
+    recent.visit(b, Position::new(0, 0));
+    recent.update(a, Position::new(7, 2));
+    assert_eq!(recent.iter().next().unwrap().path, b);
+    recent.visit(a, Position::new(7, 2));
+    let order: Vec<_> = recent.iter().map(|file| file.path.as_path()).collect();
+    assert_eq!(order, [a, b]);
+    assert_eq!(recent.position(a), Some(Position::new(7, 2)));
+
+    for n in 0..MAX_RECENT {
+        recent.visit(&Path::new("/work").join(n.to_string()), Position::default());
+    }
+    assert_eq!(recent.iter().count(), MAX_RECENT);
+    assert_eq!(recent.position(a), None);
+}
@@ -0,0 +1,323 @@
+//! Control socket letting other tools drive a running wyv, speaking
+//! line-delimited JSON-RPC 2.0 like plugins do. Methods:
+//!
+//! - `state` returns the tree root and selection, the open buffers and the
+//!   status line.
+//! - `open` (`{"files":[..]}`) opens files, each optionally `path:line[:col]`.
+//! - `command` (`{"line":"log"}`) runs a `:` command.
+//! - `keys` (`{"keys":"jj<enter>"}`) feeds key presses, in the harness's
+//!   script syntax.
+//!
+//! ```json
+//! {"jsonrpc":"2.0","id":1,"method":"command","params":{"line":"jobs"}}
+//! ```
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+
+#[cfg(all(unix, feature = "remote"))]
+use crate::runtime::Message;
+use crate::{config, runtime::Spawner};
+
+const SOCKET_FILE: &str = "wyv.sock";
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The method ran and failed, e.g. a file couldn't be opened.
+pub const FAILED: i64 = -32000;
+
+/// A call from a client, without the JSON-RPC envelope.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl Request {
+    /// A string parameter, or an invalid params error.
+    pub fn str_param(&self, name: &str) -> Result<&str, RpcError> {
+        self.params
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError::invalid_params(name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn failed(message: impl ToString) -> Self {
+        RpcError::new(FAILED, message.to_string())
+    }
+
+    pub fn invalid_params(name: &str) -> Self {
+        RpcError::new(INVALID_PARAMS, format!("missing or invalid `{}`", name))
+    }
+}
+
+pub type Reply = oneshot::Sender<Result<Value, RpcError>>;
+
+/// `$XDG_RUNTIME_DIR/wyv/wyv.sock`, or the state dir when there is no
+/// runtime dir.
+pub fn default_socket_path() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|d| !d.is_empty())
+        .map(|d| PathBuf::from(d).join("wyv"))
+        .or_else(config::state_dir)
+        .map(|d| d.join(SOCKET_FILE))
+}
+
+/// Turns the `--remote` arguments into a request: `state`, `open FILE..`,
+/// `keys SCRIPT`, or anything else as a `:` command line. Relative paths are
+/// resolved against the client's working directory since the server's
+/// differs.
+pub fn request_from_args(words: &[String]) -> Result<Request> {
+    let (command, args) = match words.split_first() {
+        Some(split) => split,
+        None => bail!("no remote command given"),
+    };
+    let (method, params) = match command.as_str() {
+        "state" => ("state", Value::Null),
+        "open" => {
+            let cwd = env::current_dir()?;
+            let files: Vec<String> = args
+                .iter()
+                .map(|a| cwd.join(a).to_string_lossy().into_owned())
+                .collect();
+            ("open", json!({ "files": files }))
+        }
+        "keys" => ("keys", json!({ "keys": args.join(" ") })),
+        _ => ("command", json!({ "line": words.join(" ") })),
+    };
+    Ok(Request {
+        method: method.to_string(),
+        params,
+    })
+}
+
+/// Removes the socket file when the server shuts down.
+pub struct ServerGuard {
+    path: PathBuf,
+}
+
+impl Drop for ServerGuard {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Listens on `path`, forwarding requests to the UI thread as
+/// [`Message::Remote`].
+#[cfg(all(unix, feature = "remote"))]
+pub fn serve(spawner: &Spawner, path: &Path) -> Result<ServerGuard> {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixListener,
+    };
+
+    if path.exists() {
+        if is_listening(path) {
+            bail!("another wyv is already listening on {}", path.display());
+        }
+        // Left behind by an instance that didn't shut down cleanly.
+        std::fs::remove_file(path)?;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let listener = {
+        let _runtime = spawner.enter();
+        UnixListener::bind(path)?
+    };
+    tracing::info!(socket = %path.display(), "listening for remote commands");
+
+    let tx = spawner.sender();
+    spawner.spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let response = match handle_line(&line, &tx).await {
+                        Some(response) => response,
+                        None => continue,
+                    };
+                    let mut out = response.to_string();
+                    out.push('\n');
+                    if write.write_all(out.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    Ok(ServerGuard {
+        path: path.to_owned(),
+    })
+}
+
+/// Parses one line, has the UI thread run it and builds the response.
+/// Notifications (requests without an id) get none.
+#[cfg(all(unix, feature = "remote"))]
+async fn handle_line(
+    line: &str,
+    tx: &tokio::sync::mpsc::UnboundedSender<Message>,
+) -> Option<Value> {
+    let message: Value = match serde_json::from_str(line) {
+        Ok(message) => message,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, e.to_string()),
+            ))
+        }
+    };
+    let id = message.get("id").cloned();
+    let request = match serde_json::from_value::<Request>(message) {
+        Ok(request) => request,
+        Err(e) => {
+            let error = RpcError::new(INVALID_REQUEST, e.to_string());
+            return Some(error_response(id.unwrap_or(Value::Null), error));
+        }
+    };
+
+    let (reply, rx) = oneshot::channel();
+    tx.send(Message::Remote { request, reply }).ok()?;
+    let result = rx
+        .await
+        .unwrap_or_else(|_| Err(RpcError::failed("wyv is shutting down")));
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+#[cfg(all(unix, feature = "remote"))]
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+#[cfg(not(all(unix, feature = "remote")))]
+pub fn serve(_spawner: &Spawner, _path: &Path) -> Result<ServerGuard> {
+    crate::features::require("remote")?;
+    bail!("the control socket is only supported on unix")
+}
+
+/// Whether an instance is accepting connections on `path`.
+#[cfg(unix)]
+pub fn is_listening(path: &Path) -> bool {
+    std::os::unix::net::UnixStream::connect(path).is_ok()
+}
+
+#[cfg(not(unix))]
+pub fn is_listening(_path: &Path) -> bool {
+    false
+}
+
+/// Calls `request` on the instance listening on `path` and returns its
+/// result.
+#[cfg(unix)]
+pub fn send(path: &Path, request: &Request) -> Result<Value> {
+    use std::io::{BufRead, BufReader, Write};
+
+    let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+    let mut line = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": request.method,
+        "params": request.params,
+    })
+    .to_string();
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    let mut response: Value = serde_json::from_str(&response)?;
+    if let Some(error) = response.get("error") {
+        let error: RpcError = serde_json::from_value(error.clone())?;
+        return Err(anyhow!(error.message));
+    }
+    Ok(response
+        .get_mut("result")
+        .map(Value::take)
+        .unwrap_or(Value::Null))
+}
+
+#[cfg(not(unix))]
+pub fn send(_path: &Path, _request: &Request) -> Result<Value> {
+    bail!("the control socket is only supported on unix")
+}
+
+#[cfg(all(unix, feature = "remote"))]
+#[test]
+fn test_request_round_trip() {
+    use std::time::{Duration, Instant};
+
+    let path = env::temp_dir().join(format!("wyv-remote-test-{}.sock", std::process::id()));
+    let mut runtime = crate::runtime::Runtime::new().unwrap();
+    let guard = serve(&runtime.spawner(), &path).unwrap();
+    assert!(serve(&runtime.spawner(), &path).is_err());
+
+    let client_path = path.clone();
+    let client = std::thread::spawn(move || {
+        let request = Request {
+            method: "ping".to_string(),
+            params: json!({ "n": 1 }),
+        };
+        let pong = send(&client_path, &request).unwrap();
+        let request = Request {
+            method: "bogus".to_string(),
+            params: Value::Null,
+        };
+        (pong, send(&client_path, &request).unwrap_err().to_string())
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut answered = 0;
+    while answered < 2 {
+        assert!(Instant::now() < deadline, "request never arrived");
+        for message in runtime.drain() {
+            if let Message::Remote { request, reply } = message {
+                let result = match request.method.as_str() {
+                    "ping" => Ok(request.params),
+                    _ => Err(RpcError::new(METHOD_NOT_FOUND, "no such method")),
+                };
+                reply.send(result).unwrap();
+                answered += 1;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let (pong, error) = client.join().unwrap();
+    assert_eq!(pong, json!({ "n": 1 }));
+    assert_eq!(error, "no such method");
+
+    drop(guard);
+    assert!(!path.exists());
+}
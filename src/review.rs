@@ -0,0 +1,180 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{diff::Diff, git};
+
+/// Bases tried, in order, when a review isn't given one.
+const DEFAULT_BASES: [&str; 3] = ["origin/HEAD", "main", "master"];
+
+/// A file the branch changed, with git's letter for the change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewFile {
+    pub change: char,
+    /// Relative to the repository's root.
+    pub path: String,
+}
+
+/// A note left on a line of a changed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    pub path: String,
+    /// One-based line number in the file as changed, or as it was at the
+    /// merge base if `removed`.
+    pub line: usize,
+    pub removed: bool,
+    pub text: String,
+}
+
+/// The files the current branch changed since it left `base`, compared
+/// from their merge base to the work tree, and the notes left on them.
+#[derive(Debug)]
+pub struct Review {
+    root: PathBuf,
+    base: String,
+    merge_base: String,
+    files: Vec<ReviewFile>,
+    selected: usize,
+    notes: Vec<Note>,
+    /// The file whose diff is open, if any.
+    pub open: Option<usize>,
+}
+
+impl Review {
+    /// The changes of the repository containing `dir` against `base`, or
+    /// against the first of `origin/HEAD`, `main` and `master` there is.
+    pub fn load(dir: &Path, base: Option<&str>) -> Result<Self> {
+        let root = git::repo_root(dir)?;
+        let (base, merge_base) = match base {
+            Some(base) => (base.to_string(), git::merge_base(&root, base)?),
+            None => DEFAULT_BASES
+                .iter()
+                .find_map(|base| Some((base.to_string(), git::merge_base(&root, base).ok()?)))
+                .ok_or_else(|| {
+                    anyhow!("no main or master branch; give a base to review against")
+                })?,
+        };
+        let files: Vec<ReviewFile> = git::changed_since(&root, &merge_base)?
+            .into_iter()
+            .map(|(change, path)| ReviewFile { change, path })
+            .collect();
+        if files.is_empty() {
+            bail!("no changes against {}", base);
+        }
+        Ok(Review {
+            root,
+            base,
+            merge_base,
+            files,
+            selected: 0,
+            notes: Vec::new(),
+            open: None,
+        })
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    pub fn files(&self) -> &[ReviewFile] {
+        &self.files
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.files.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// The selected file as it was at the merge base and as it is now,
+    /// either empty if the branch added or deleted it.
+    pub fn diff_selected(&self) -> Result<Diff> {
+        let file = &self.files[self.selected];
+        let before = match file.change {
+            'A' => String::new(),
+            _ => git::file_in(&self.root, &self.merge_base, &file.path)?,
+        };
+        let after = match file.change {
+            'D' => String::new(),
+            _ => fs::read_to_string(self.root.join(&file.path))
+                .map_err(|e| anyhow!("{}: {}", file.path, e))?,
+        };
+        Ok(Diff::new(
+            &format!("{} ({})", file.path, self.base),
+            &before,
+            &file.path,
+            &after,
+        ))
+    }
+
+    /// Leaves a note on `line` of the open file, returning the file's path.
+    pub fn add_note(&mut self, (line, removed): (usize, bool), text: &str) -> Result<&str> {
+        let file = match self.open {
+            Some(idx) => &self.files[idx],
+            None => bail!("open a file of the review to leave notes on it"),
+        };
+        self.notes.push(Note {
+            path: file.path.clone(),
+            line,
+            removed,
+            text: text.to_string(),
+        });
+        Ok(&file.path)
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    /// How many notes were left on `file`.
+    pub fn note_count(&self, file: &ReviewFile) -> usize {
+        self.notes.iter().filter(|n| n.path == file.path).count()
+    }
+
+    /// The notes as plain text, by file in the order they're listed and
+    /// then by line.
+    pub fn report(&self) -> String {
+        let branch = git::current_branch(&self.root).unwrap_or_else(|_| "HEAD".to_string());
+        let mut report = format!(
+            "Review of {} against {} (merge base {})\n",
+            branch,
+            self.base,
+            &self.merge_base[..self.merge_base.len().min(7)]
+        );
+        for file in &self.files {
+            let mut notes: Vec<&Note> = self.notes.iter().filter(|n| n.path == file.path).collect();
+            if notes.is_empty() {
+                continue;
+            }
+            notes.sort_by_key(|n| n.line);
+            let _ = write!(report, "\n{}\n", file.path);
+            for note in notes {
+                let removed = if note.removed { " (removed)" } else { "" };
+                let _ = writeln!(report, "  line {}{}: {}", note.line, removed, note.text);
+            }
+        }
+        report
+    }
+
+    /// Writes the report to `path`, relative to the repository's root.
+    pub fn write_report(&self, path: &Path) -> Result<PathBuf> {
+        if self.notes.is_empty() {
+            bail!("no notes to report; press c in a file's diff to leave one");
+        }
+        let path = self.root.join(path);
+        fs::write(&path, self.report()).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    future::Future,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use anyhow::Result;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    badges::Query,
+    config::{ConfigWatcher, LoadedConfig},
+    error,
+    follow::FileWatcher,
+    git::StatusEntry,
+    jobs::{JobEvent, JobId},
+    lsp::Diagnostic,
+    remote::{Reply, Request},
+    search::FileMatches,
+    ssh::SshEvent,
+    symbols::Symbol,
+    widgets::file_tree::FileNode,
+};
+
+/// A number no earlier call gave, for a plugin or language server host to
+/// tell its children's messages from those of the host before it, which
+/// may still be on their way after it stopped them.
+pub fn next_generation() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Results sent back to the UI thread by work running on the runtime.
+#[derive(Debug)]
+pub enum Message {
+    TreeScanned {
+        root: PathBuf,
+        result: error::Result<FileNode>,
+    },
+    /// Part of a large file being streamed into its buffer, or `None` once
+    /// it has all been read.
+    FileChunk {
+        job: JobId,
+        path: PathBuf,
+        text: Option<String>,
+    },
+    ConfigReloaded(Box<Result<LoadedConfig>>),
+    Job(JobEvent),
+    /// A line sent by a plugin; `Null` if it wasn't valid JSON. Plugins
+    /// and language servers are told apart by their host's
+    /// [`generation`](next_generation) as well as their index, since a
+    /// new host reuses the indices of the one it replaced.
+    Plugin {
+        generation: u64,
+        index: usize,
+        message: serde_json::Value,
+    },
+    PluginExited {
+        generation: u64,
+        index: usize,
+    },
+    /// A message from a language server; `Null` if it wasn't valid JSON.
+    Lsp {
+        generation: u64,
+        index: usize,
+        message: serde_json::Value,
+    },
+    LspExited {
+        generation: u64,
+        index: usize,
+    },
+    /// A line a task printed.
+    TaskOutput {
+        id: JobId,
+        line: String,
+    },
+    /// A task finished, with its exit code unless it was killed.
+    TaskExited {
+        id: JobId,
+        code: Option<i32>,
+    },
+    /// A file with matches for a project search.
+    SearchResult {
+        id: JobId,
+        file: FileMatches,
+    },
+    /// A project search finished; `truncated` if it hit the match limit.
+    SearchDone {
+        id: JobId,
+        truncated: bool,
+    },
+    /// The symbols found in the workspace for the symbol overlay.
+    SymbolsIndexed {
+        id: JobId,
+        symbols: Vec<Symbol>,
+    },
+    /// Output from the integrated terminal's shell.
+    TerminalOutput(Vec<u8>),
+    TerminalExited,
+    /// The file being followed changed on disk.
+    FileChanged(PathBuf),
+    Ssh(SshEvent),
+    /// The result of a `git status` for the tree's badges.
+    GitStatus {
+        query: Query,
+        result: Result<Vec<StatusEntry>>,
+    },
+    /// What a linter found in a saved file.
+    Linted {
+        path: PathBuf,
+        result: Result<Vec<Diagnostic>>,
+    },
+    /// A request from the control socket; the answer goes back on `reply`.
+    Remote {
+        request: Request,
+        reply: Reply,
+    },
+}
+
+/// Owns the tokio runtime that I/O-bound work runs on. The UI thread never
+/// awaits anything; it drains finished results with [`Runtime::drain`] once
+/// per frame.
+pub struct Runtime {
+    runtime: tokio::runtime::Runtime,
+    tx: UnboundedSender<Message>,
+    rx: UnboundedReceiver<Message>,
+}
+
+impl Runtime {
+    pub fn new() -> Result<Self> {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.thread_name("wyv-io").enable_time();
+        #[cfg(feature = "remote")]
+        builder.enable_io();
+        let runtime = builder.build()?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        Ok(Runtime { runtime, tx, rx })
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        Spawner {
+            handle: self.runtime.handle().clone(),
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Messages that arrived since the last call, without blocking.
+    pub fn drain(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        while let Ok(message) = self.rx.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+}
+
+/// Cheap handle for starting work on the runtime from anywhere in the app.
+#[derive(Debug, Clone)]
+pub struct Spawner {
+    handle: tokio::runtime::Handle,
+    tx: UnboundedSender<Message>,
+}
+
+impl Spawner {
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.handle.spawn(future);
+    }
+
+    /// Runs blocking I/O on the runtime's blocking pool.
+    pub fn run_blocking<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.handle.spawn_blocking(f);
+    }
+
+    /// Runs blocking I/O on the runtime's blocking pool and sends its result
+    /// to the UI thread.
+    pub fn spawn_blocking<F>(&self, f: F)
+    where
+        F: FnOnce() -> Message + Send + 'static,
+    {
+        let tx = self.tx.clone();
+        self.run_blocking(move || {
+            // The receiver only goes away when the app is shutting down.
+            let _ = tx.send(f());
+        });
+    }
+
+    /// Enters the runtime's context, for creating I/O resources outside a
+    /// task.
+    pub fn enter(&self) -> tokio::runtime::EnterGuard<'_> {
+        self.handle.enter()
+    }
+
+    pub fn sender(&self) -> UnboundedSender<Message> {
+        self.tx.clone()
+    }
+
+    /// Polls the config files in the background, sending every change
+    /// until the watcher is stopped.
+    pub fn watch_config(&self, mut watcher: ConfigWatcher) {
+        let tx = self.sender();
+        self.spawn(async move {
+            let mut ticker = tokio::time::interval(CONFIG_POLL_INTERVAL);
+            while !watcher.is_stopped() {
+                ticker.tick().await;
+                let polled = tokio::task::spawn_blocking(move || {
+                    let change = watcher.poll();
+                    (watcher, change)
+                })
+                .await;
+                let change = match polled {
+                    Ok((returned, change)) => {
+                        watcher = returned;
+                        change
+                    }
+                    Err(_) => return,
+                };
+
+                if let Some(reloaded) = change {
+                    if tx
+                        .send(Message::ConfigReloaded(Box::new(reloaded)))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Polls a followed file in the background, sending every change until
+    /// the file is no longer followed.
+    pub fn watch_file(&self, mut watcher: FileWatcher) {
+        let tx = self.sender();
+        self.spawn(async move {
+            let mut ticker = tokio::time::interval(FOLLOW_POLL_INTERVAL);
+            while !watcher.is_stopped() {
+                ticker.tick().await;
+                let polled = tokio::task::spawn_blocking(move || {
+                    let changed = watcher.poll();
+                    (watcher, changed)
+                })
+                .await;
+                let changed = match polled {
+                    Ok((returned, changed)) => {
+                        watcher = returned;
+                        changed
+                    }
+                    Err(_) => return,
+                };
+
+                if changed && !watcher.is_stopped() {
+                    let path = watcher.path().to_owned();
+                    if tx.send(Message::FileChanged(path)).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[test]
+fn test_blocking_result_reaches_ui_thread() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime.spawner().spawn_blocking(|| {
+        let root = PathBuf::from("./src/widgets");
+        let result = FileNode::new_from_path(&root);
+        Message::TreeScanned { root, result }
+    });
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    let message = loop {
+        if let Some(message) = runtime.drain().pop() {
+            break message;
+        }
+        assert!(std::time::Instant::now() < deadline, "scan never finished");
+        std::thread::sleep(Duration::from_millis(10));
+    };
+
+    match message {
+        Message::TreeScanned { root, result } => {
+            assert_eq!(root, PathBuf::from("./src/widgets"));
+            assert_eq!(result.unwrap().name(), "widgets");
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
@@ -0,0 +1,370 @@
+//! Lua scripting through `init.lua` in the config directory. Scripts get a
+//! global `wyv` table:
+//!
+//! ```lua
+//! wyv.set("editor.tab_width", 2)           -- any config option
+//! wyv.map("ctrl-q", "quit")                -- bind a key to an action...
+//! wyv.map("ctrl-g", function() wyv.status("hi") end) -- ...or a function
+//! wyv.command("greet", function(args) wyv.status("hello " .. (args[1] or "")) end)
+//! wyv.on("open", function(path) wyv.status("opened " .. path) end)
+//! wyv.statusline(function() return os.date("%H:%M") end)
+//! ```
+//!
+//! Functions can also call `wyv.open(path)`, `wyv.action(name)` and
+//! `wyv.exec(":command")`. These are queued and applied by the app once the
+//! script returns.
+
+use std::path::{Path, PathBuf};
+#[cfg(feature = "lua")]
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+#[cfg(feature = "lua")]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(feature = "lua")]
+use mlua::{Function, Lua, LuaSerdeExt, RegistryKey, Value};
+
+use crate::config::{self, Action};
+
+const INIT_FILE: &str = "init.lua";
+
+pub fn default_init_path() -> Option<PathBuf> {
+    config::config_dir().map(|d| d.join(INIT_FILE))
+}
+
+/// Something a script asked the app to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    Status(String),
+    Open(PathBuf),
+    Action(Action),
+    Command(String),
+    Set(String, serde_json::Value),
+}
+
+/// What a key press resolved to in the script's bindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOutcome {
+    Unbound,
+    Action(Action),
+    /// A Lua function ran; its effects are queued.
+    Handled,
+}
+
+#[cfg(feature = "lua")]
+enum Binding {
+    Action(Action),
+    Function(RegistryKey),
+}
+
+#[cfg(feature = "lua")]
+#[derive(Default)]
+struct ScriptState {
+    keys: HashMap<String, Binding>,
+    commands: HashMap<String, RegistryKey>,
+    hooks: HashMap<String, Vec<RegistryKey>>,
+    statusline: Option<RegistryKey>,
+    effects: Vec<Effect>,
+    /// Options set with `wyv.set`, re-applied when the config file reloads.
+    overrides: Vec<(String, serde_json::Value)>,
+}
+
+#[cfg(feature = "lua")]
+pub struct Scripting {
+    lua: Lua,
+    state: Rc<RefCell<ScriptState>>,
+}
+
+#[cfg(feature = "lua")]
+impl Scripting {
+    pub fn new() -> Result<Self> {
+        let scripting = Scripting {
+            lua: Lua::new(),
+            state: Rc::default(),
+        };
+        scripting.install_api().map_err(lua_error)?;
+        Ok(scripting)
+    }
+
+    pub fn load_file(&self, path: &Path) -> Result<()> {
+        let source = std::fs::read_to_string(path)?;
+        self.exec(&source, &path.display().to_string())
+    }
+
+    pub fn exec(&self, source: &str, name: &str) -> Result<()> {
+        self.lua
+            .load(source)
+            .set_name(name)
+            .exec()
+            .map_err(lua_error)
+    }
+
+    pub fn key(&self, key: &str) -> Result<KeyOutcome> {
+        let function = match self.state.borrow().keys.get(key) {
+            None => return Ok(KeyOutcome::Unbound),
+            Some(Binding::Action(action)) => return Ok(KeyOutcome::Action(*action)),
+            Some(Binding::Function(f)) => self.function(f)?,
+        };
+        function.call::<_, ()>(()).map_err(lua_error)?;
+        Ok(KeyOutcome::Handled)
+    }
+
+    /// Runs a command registered with `wyv.command`. Returns `false` if there
+    /// is none by that name.
+    pub fn run_command(&self, name: &str, args: &[&str]) -> Result<bool> {
+        let function = match self.state.borrow().commands.get(name) {
+            Some(f) => self.function(f)?,
+            None => return Ok(false),
+        };
+        function.call::<_, ()>(args.to_vec()).map_err(lua_error)?;
+        Ok(true)
+    }
+
+    pub fn commands(&self) -> Vec<String> {
+        self.state.borrow().commands.keys().cloned().collect()
+    }
+
+    /// Runs the hooks registered for `event` (`open`, `save`).
+    pub fn fire(&self, event: &str, path: &Path) -> Result<()> {
+        let functions = match self.state.borrow().hooks.get(event) {
+            Some(hooks) => hooks
+                .iter()
+                .map(|k| self.function(k))
+                .collect::<Result<Vec<_>>>()?,
+            None => return Ok(()),
+        };
+        let path = path.to_string_lossy().into_owned();
+        for function in functions {
+            function.call::<_, ()>(path.clone()).map_err(lua_error)?;
+        }
+        Ok(())
+    }
+
+    /// Text for the script-provided part of the status bar.
+    pub fn statusline(&self) -> Option<String> {
+        let function = self
+            .state
+            .borrow()
+            .statusline
+            .as_ref()
+            .and_then(|k| self.function(k).ok())?;
+        function.call::<_, Option<String>>(()).ok().flatten()
+    }
+
+    pub fn take_effects(&self) -> Vec<Effect> {
+        std::mem::take(&mut self.state.borrow_mut().effects)
+    }
+
+    pub fn overrides(&self) -> Vec<(String, serde_json::Value)> {
+        self.state.borrow().overrides.clone()
+    }
+
+    fn function(&self, key: &RegistryKey) -> Result<Function<'_>> {
+        self.lua.registry_value(key).map_err(lua_error)
+    }
+
+    fn install_api(&self) -> mlua::Result<()> {
+        let lua = &self.lua;
+        let wyv = lua.create_table()?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "set",
+            lua.create_function(move |lua, (key, value): (String, Value)| {
+                let value: serde_json::Value = lua.from_value(value)?;
+                let mut state = state.borrow_mut();
+                state.overrides.push((key.clone(), value.clone()));
+                state.effects.push(Effect::Set(key, value));
+                Ok(())
+            })?,
+        )?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "map",
+            lua.create_function(move |lua, (key, target): (String, Value)| {
+                let key = config::parse_key(&key)
+                    .map(|k| config::key_name(&k))
+                    .ok_or_else(|| mlua::Error::RuntimeError(format!("unknown key `{}`", key)))?;
+                let binding = match target {
+                    Value::Function(f) => Binding::Function(lua.create_registry_value(f)?),
+                    other => Binding::Action(lua.from_value(other)?),
+                };
+                state.borrow_mut().keys.insert(key, binding);
+                Ok(())
+            })?,
+        )?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "command",
+            lua.create_function(move |lua, (name, f): (String, Function)| {
+                let key = lua.create_registry_value(f)?;
+                state.borrow_mut().commands.insert(name, key);
+                Ok(())
+            })?,
+        )?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "on",
+            lua.create_function(move |lua, (event, f): (String, Function)| {
+                if !matches!(event.as_str(), "open" | "save") {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "unknown event `{}`",
+                        event
+                    )));
+                }
+                let key = lua.create_registry_value(f)?;
+                state.borrow_mut().hooks.entry(event).or_default().push(key);
+                Ok(())
+            })?,
+        )?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "statusline",
+            lua.create_function(move |lua, f: Function| {
+                state.borrow_mut().statusline = Some(lua.create_registry_value(f)?);
+                Ok(())
+            })?,
+        )?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "status",
+            lua.create_function(move |_, message: String| {
+                state.borrow_mut().effects.push(Effect::Status(message));
+                Ok(())
+            })?,
+        )?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "open",
+            lua.create_function(move |_, path: String| {
+                state.borrow_mut().effects.push(Effect::Open(path.into()));
+                Ok(())
+            })?,
+        )?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "action",
+            lua.create_function(move |lua, action: Value| {
+                let action = lua.from_value(action)?;
+                state.borrow_mut().effects.push(Effect::Action(action));
+                Ok(())
+            })?,
+        )?;
+
+        let state = Rc::clone(&self.state);
+        wyv.set(
+            "exec",
+            lua.create_function(move |_, line: String| {
+                let line = line.strip_prefix(':').unwrap_or(&line).to_string();
+                state.borrow_mut().effects.push(Effect::Command(line));
+                Ok(())
+            })?,
+        )?;
+
+        lua.globals().set("wyv", wyv)
+    }
+}
+
+#[cfg(feature = "lua")]
+fn lua_error(e: mlua::Error) -> anyhow::Error {
+    anyhow!("lua: {}", e)
+}
+
+/// Stand-in for builds without the `lua` feature. It can't be created, so
+/// `App::scripts` stays `None` and none of these ever run.
+#[cfg(not(feature = "lua"))]
+pub enum Scripting {}
+
+#[cfg(not(feature = "lua"))]
+impl Scripting {
+    pub fn new() -> Result<Self> {
+        Err(crate::features::missing("lua"))
+    }
+
+    pub fn load_file(&self, _path: &Path) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn exec(&self, _source: &str, _name: &str) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn key(&self, _key: &str) -> Result<KeyOutcome> {
+        match *self {}
+    }
+
+    pub fn run_command(&self, _name: &str, _args: &[&str]) -> Result<bool> {
+        match *self {}
+    }
+
+    pub fn commands(&self) -> Vec<String> {
+        match *self {}
+    }
+
+    pub fn fire(&self, _event: &str, _path: &Path) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn statusline(&self) -> Option<String> {
+        match *self {}
+    }
+
+    pub fn take_effects(&self) -> Vec<Effect> {
+        match *self {}
+    }
+
+    pub fn overrides(&self) -> Vec<(String, serde_json::Value)> {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "lua")]
+#[test]
+fn test_script_registers_bindings_and_hooks() {
+    let scripting = Scripting::new().unwrap();
+    scripting
+        .exec(
+            r#"
+            wyv.set("editor.tab_width", 2)
+            wyv.map("ctrl-q", "quit")
+            wyv.map("g", function() wyv.action("tree_down") end)
+            wyv.command("greet", function(args) wyv.status("hello " .. args[1]) end)
+            wyv.on("open", function(path) wyv.exec(":log") end)
+            wyv.statusline(function() return "lua!" end)
+            "#,
+            "test",
+        )
+        .unwrap();
+
+    assert_eq!(
+        scripting.take_effects(),
+        [Effect::Set("editor.tab_width".into(), serde_json::json!(2))]
+    );
+    assert_eq!(
+        scripting.key("ctrl-q").unwrap(),
+        KeyOutcome::Action(Action::Quit)
+    );
+    assert_eq!(scripting.key("x").unwrap(), KeyOutcome::Unbound);
+    assert_eq!(scripting.key("g").unwrap(), KeyOutcome::Handled);
+    assert!(scripting.run_command("greet", &["bob"]).unwrap());
+    assert!(!scripting.run_command("nope", &[]).unwrap());
+    scripting.fire("open", Path::new("a.txt")).unwrap();
+
+    assert_eq!(
+        scripting.take_effects(),
+        [
+            Effect::Action(Action::TreeDown),
+            Effect::Status("hello bob".into()),
+            Effect::Command("log".into()),
+        ]
+    );
+    assert_eq!(scripting.statusline().as_deref(), Some("lua!"));
+    assert!(scripting.exec("wyv.on('bogus', print)", "bad").is_err());
+}
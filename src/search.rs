@@ -0,0 +1,395 @@
+//! Searching the project's files for a regular expression, ripgrep-style:
+//! case-insensitive unless the pattern has capitals, hidden files and those
+//! git ignores skipped, and each match shown with the lines around it.
+//! Matches can then be replaced across files.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    git,
+    jobs::{JobId, Jobs},
+    runtime::{Message, Spawner},
+    widgets::file_tree::TreeOptions,
+};
+
+/// Lines shown before and after each matching line.
+pub const CONTEXT_LINES: usize = 1;
+/// Matching lines a search stops after.
+const MAX_MATCHES: usize = 10_000;
+/// Files bigger than this are skipped.
+const MAX_FILE_SIZE: u64 = 4 << 20;
+
+/// A line of a file in the results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchLine {
+    /// Zero-based line number.
+    pub line: usize,
+    pub text: String,
+    /// Char ranges of the matches in `text`; empty for a context line.
+    pub matches: Vec<(usize, usize)>,
+}
+
+/// The matching lines of one file, with their context, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub lines: Vec<SearchLine>,
+}
+
+/// One match and the text to replace it with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Zero-based line number.
+    pub line: usize,
+    /// The line as it was when the change was worked out.
+    pub text: String,
+    /// Char range of the match in `text`.
+    pub range: (usize, usize),
+    /// What replaces the match, with `$1`-style groups filled in.
+    pub replacement: String,
+}
+
+impl Change {
+    /// The line with the match replaced.
+    pub fn replaced(&self) -> String {
+        let (start, end) = self.range;
+        let mut line: String = self.text.chars().take(start).collect();
+        line.push_str(&self.replacement);
+        line.extend(self.text.chars().skip(end));
+        line
+    }
+}
+
+/// Compiles `pattern`, ignoring case unless it has an uppercase letter.
+pub fn regex(pattern: &str) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(!pattern.chars().any(char::is_uppercase))
+        .build()
+        .with_context(|| format!("invalid pattern `{}`", pattern))
+}
+
+/// The lines of `text` matching `regex`, with [`CONTEXT_LINES`] around each.
+pub fn search_text(text: &str, regex: &Regex) -> Vec<SearchLine> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut found = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let matches: Vec<(usize, usize)> = regex
+            .find_iter(line)
+            .filter(|m| !m.is_empty())
+            .map(|m| {
+                let start = line[..m.start()].chars().count();
+                (start, start + m.as_str().chars().count())
+            })
+            .collect();
+        if !matches.is_empty() {
+            found.push((idx, matches));
+        }
+    }
+
+    let shown: BTreeSet<usize> = found
+        .iter()
+        .flat_map(|(idx, _)| idx.saturating_sub(CONTEXT_LINES)..=idx + CONTEXT_LINES)
+        .filter(|idx| *idx < lines.len())
+        .collect();
+    let mut found = found.into_iter().peekable();
+    shown
+        .into_iter()
+        .map(|idx| SearchLine {
+            line: idx,
+            text: lines[idx].to_string(),
+            matches: match found.next_if(|(i, _)| *i == idx) {
+                Some((_, matches)) => matches,
+                None => Vec::new(),
+            },
+        })
+        .collect()
+}
+
+/// Every match of `regex` in `text`, replaced by `replacement`, which can
+/// refer to groups as `$1` or `${name}`.
+pub fn changes(text: &str, regex: &Regex, replacement: &str) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        for captures in regex.captures_iter(line) {
+            let m = captures.get(0).expect("group 0 is the whole match");
+            if m.is_empty() {
+                continue;
+            }
+            let mut expanded = String::new();
+            captures.expand(replacement, &mut expanded);
+            let start = line[..m.start()].chars().count();
+            changes.push(Change {
+                line: idx,
+                text: line.to_string(),
+                range: (start, start + m.as_str().chars().count()),
+                replacement: expanded,
+            });
+        }
+    }
+    changes
+}
+
+/// Writes new contents to each file. Each is written beside its original
+/// first, so one that can't be written leaves them all as they were; only
+/// then are they moved over the originals, one at a time. Those moves
+/// aren't atomic together: if one fails, the files already replaced stay
+/// replaced, the error names them, and the rest are left untouched with no
+/// copies lying about.
+pub fn write_all(files: &[(PathBuf, String)]) -> Result<()> {
+    let mut written: Vec<PathBuf> = Vec::new();
+    let result = files.iter().try_for_each(|(path, text)| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = path.with_file_name(format!(".{}.wyv-replace", name));
+        written.push(temp.clone());
+        fs::write(&temp, text)
+            .and_then(|()| fs::set_permissions(&temp, fs::metadata(path)?.permissions()))
+            .with_context(|| format!("could not write {}", path.display()))
+    });
+    if let Err(e) = result {
+        for temp in written {
+            let _ = fs::remove_file(temp);
+        }
+        return Err(e);
+    }
+    for (done, ((path, _), temp)) in files.iter().zip(&written).enumerate() {
+        if let Err(e) = fs::rename(temp, path) {
+            for temp in &written[done..] {
+                let _ = fs::remove_file(temp);
+            }
+            let changed: Vec<String> = files[..done]
+                .iter()
+                .map(|(path, _)| path.display().to_string())
+                .collect();
+            let e = anyhow::Error::new(e).context(format!("could not write {}", path.display()));
+            return Err(match changed.is_empty() {
+                true => e,
+                false => e.context(format!(
+                    "replaced in {} before failing; the rest are unchanged",
+                    changed.join(", ")
+                )),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The files under `root` to search: those git doesn't ignore, or every
+/// file in a directory outside a repository, less the ones `options` hides,
+/// in its [`sort`](TreeOptions::sort) order.
+pub fn files(root: &Path, options: &TreeOptions) -> Vec<PathBuf> {
+    let hidden = |path: &Path| {
+        path.components()
+            .any(|c| options.hides(&c.as_os_str().to_string_lossy()))
+    };
+    let mut files: Vec<PathBuf> = match git::files(root) {
+        Ok(files) => files
+            .into_iter()
+            .filter(|p| !hidden(p))
+            .map(|p| root.join(p))
+            .collect(),
+        Err(_) => {
+            let mut files = Vec::new();
+            walk(
+                root,
+                &mut |path| {
+                    let relative = path.strip_prefix(root).unwrap_or(path);
+                    !hidden(relative)
+                },
+                &mut files,
+            );
+            files
+        }
+    };
+    // In the tree's order, so results come in the order the files are
+    // listed there.
+    files.sort_by(|a, b| options.sort.compare_paths(a, b));
+    files
+}
+
+fn walk(dir: &Path, keep: &mut dyn FnMut(&Path) -> bool, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+        if !keep(&path) {
+            continue;
+        }
+        // Links are left out so that a loop can't catch the walk.
+        if file_type.is_dir() {
+            walk(&path, keep, files);
+        } else if file_type.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+/// Searches the text files under `root`, passing each one with matches to
+/// `found` until `cancelled` says to stop. Returns whether it stopped early
+/// because of too many matches.
+pub fn run(
+    root: &Path,
+    regex: &Regex,
+    options: &TreeOptions,
+    cancelled: &dyn Fn() -> bool,
+    found: &mut dyn FnMut(FileMatches),
+) -> bool {
+    let mut count = 0;
+    for path in files(root, options) {
+        if cancelled() {
+            return false;
+        }
+        let text = match read_text(&path) {
+            Some(text) => text,
+            None => continue,
+        };
+        let lines = search_text(&text, regex);
+        if lines.is_empty() {
+            continue;
+        }
+        count += lines.iter().filter(|l| !l.matches.is_empty()).count();
+        found(FileMatches { path, lines });
+        if count >= MAX_MATCHES {
+            return true;
+        }
+    }
+    false
+}
+
+/// The contents of `path`, unless it is too big or not text.
+fn read_text(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let bytes = fs::read(path).ok()?;
+    if bytes[..bytes.len().min(8000)].contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Runs a search as a job, sending each file with matches as a
+/// [`Message::SearchResult`] and finishing with a [`Message::SearchDone`].
+pub fn spawn(
+    jobs: &mut Jobs,
+    spawner: &Spawner,
+    root: &Path,
+    regex: Regex,
+    options: &TreeOptions,
+) -> JobId {
+    let root = root.to_owned();
+    let options = options.clone();
+    jobs.spawn(spawner, &format!("search {}", regex), move |ctx| {
+        let id = ctx.id();
+        let truncated = run(
+            &root,
+            &regex,
+            &options,
+            &|| ctx.is_cancelled(),
+            &mut |file| ctx.send(Message::SearchResult { id, file }),
+        );
+        ctx.send(Message::SearchDone { id, truncated });
+        match ctx.is_cancelled() {
+            true => anyhow::bail!("cancelled"),
+            false => Ok(()),
+        }
+    })
+}
+
+#[test]
+fn test_search_text_with_context() {
+    let text = "one\ntwo Foo\nthree\nfour\nfive\nsix foo foo\n";
+    let lines = search_text(text, &regex("foo").unwrap());
+    let numbers: Vec<usize> = lines.iter().map(|l| l.line).collect();
+    assert_eq!(numbers, [0, 1, 2, 4, 5]);
+    assert_eq!(lines[1].matches, [(4, 7)]);
+    assert!(lines[2].matches.is_empty());
+    assert_eq!(lines[4].matches, [(4, 7), (8, 11)]);
+
+    // Capitals make the search case-sensitive.
+    let lines = search_text(text, &regex("Foo").unwrap());
+    assert_eq!(lines.len(), 3);
+    assert!(search_text("ééx", &regex("x").unwrap())[0].matches == [(2, 3)]);
+    assert!(regex("(").is_err());
+}
+
+#[test]
+fn test_changes_expand_groups() {
+    let regex = regex(r"(\w+)\.unwrap\(\)").unwrap();
+    let changes = changes("a.unwrap() + b\nbé.unwrap()\n", &regex, "${1}?");
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].range, (0, 10));
+    assert_eq!(changes[0].replaced(), "a? + b");
+    assert_eq!(changes[1].line, 1);
+    assert_eq!(changes[1].replacement, "bé?");
+    assert_eq!(changes[1].replaced(), "bé?");
+}
+
+#[test]
+fn test_search_files_skips_hidden() {
+    let dir = std::env::temp_dir().join(format!("wyv-search-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join(".hidden")).unwrap();
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("a.txt"), "needle\n").unwrap();
+    fs::write(dir.join("sub/b.txt"), "hay\nneedle\n").unwrap();
+    fs::write(dir.join(".hidden/c.txt"), "needle\n").unwrap();
+    fs::write(dir.join("d.bin"), b"needle\0").unwrap();
+
+    let mut found = Vec::new();
+    let regex = regex("needle").unwrap();
+    let options = TreeOptions::default();
+    run(&dir, &regex, &options, &|| false, &mut |f| found.push(f));
+    let names: Vec<&Path> = found
+        .iter()
+        .map(|f| f.path.strip_prefix(&dir).unwrap())
+        .collect();
+    assert_eq!(names, [Path::new("a.txt"), Path::new("sub/b.txt")]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_write_all_reports_what_a_failed_move_left_changed() {
+    let dir = std::env::temp_dir().join(format!("wyv-write-all-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("a.txt"), "old").unwrap();
+    fs::write(dir.join("sub/b.txt"), "old").unwrap();
+    fs::write(dir.join("c.txt"), "old").unwrap();
+    // A file can't be moved over a directory with things in it.
+    let files = [
+        (dir.join("a.txt"), "new".to_string()),
+        (dir.join("sub"), "new".to_string()),
+        (dir.join("c.txt"), "new".to_string()),
+    ];
+    let message = format!("{:#}", write_all(&files).unwrap_err());
+    assert!(message.starts_with(&format!(
+        "replaced in {} before failing",
+        dir.join("a.txt").display()
+    )));
+    assert!(message.contains(&format!("could not write {}", dir.join("sub").display())));
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "new");
+    assert_eq!(fs::read_to_string(dir.join("c.txt")).unwrap(), "old");
+    let leftovers = fs::read_dir(&dir)
+        .unwrap()
+        .filter(|e| {
+            e.as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .ends_with(".wyv-replace")
+        })
+        .count();
+    assert_eq!(leftovers, 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,288 @@
+//! What's remembered of a workspace from one run to the next: its jump
+//! list, marks and recent files, and the buffers, panes and tree left open
+//! for switching back to it. Sessions are kept together in the state
+//! directory, keyed by the workspace's root, in a file tagged with the
+//! version of its format. Each workspace's session is read on its own, and
+//! any part of one that no longer reads falls back to its default, so that
+//! a damaged or outdated entry never costs the others theirs.
+//!
+//! While wyv runs it also snapshots the session now and then, into a file
+//! of its own that a clean exit clears. A snapshot still there at startup
+//! means the last run was cut short, and it's offered for restoring.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::{
+    app::Panel, config, error::Error, jumps::JumpList, marks::Marks, paths, recent::RecentFiles,
+    widgets::file_tree::FileTreeState,
+};
+
+const SESSIONS_FILE: &str = "sessions.json";
+const SNAPSHOTS_FILE: &str = "autosave.json";
+
+/// The version of the format sessions are saved in. Files without one
+/// predate it and are read as version 1, the first.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    #[serde(deserialize_with = "lenient")]
+    pub jumps: JumpList,
+    #[serde(deserialize_with = "lenient")]
+    pub marks: Marks,
+    #[serde(deserialize_with = "lenient")]
+    pub recent: RecentFiles,
+    /// The files open, in the order they were opened.
+    #[serde(deserialize_with = "lenient")]
+    pub buffers: Vec<PathBuf>,
+    #[serde(deserialize_with = "lenient")]
+    pub active: Option<PathBuf>,
+    #[serde(deserialize_with = "lenient")]
+    pub layout: Layout,
+    /// Which directories were expanded and which row was selected.
+    #[serde(deserialize_with = "lenient")]
+    pub tree: Option<FileTreeState>,
+}
+
+/// Which panes were shown around the editor.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    /// The panel below the editor, if it shows nothing that has to be
+    /// worked out again.
+    pub panel: Option<Panel>,
+    pub outline: bool,
+    pub preview: bool,
+}
+
+/// The file sessions are saved in, with the snapshots beside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionStore {
+    path: PathBuf,
+    snapshots: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(path: PathBuf) -> Self {
+        let snapshots = path.with_file_name(SNAPSHOTS_FILE);
+        SessionStore { path, snapshots }
+    }
+
+    pub fn default_location() -> Option<Self> {
+        config::state_dir().map(|d| SessionStore::new(d.join(SESSIONS_FILE)))
+    }
+
+    /// The session last saved for the workspace at `root`, or an empty one
+    /// if there's none or it can't be read.
+    pub fn load(&self, root: &Path) -> Session {
+        let mut sessions = match read(&self.path) {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                tracing::debug!(path = %self.path.display(), error = %e, "no saved sessions");
+                return Session::default();
+            }
+        };
+        sessions
+            .remove(&key(root))
+            .and_then(session)
+            .unwrap_or_default()
+    }
+
+    /// Saves `session` for the workspace at `root`, keeping the others as
+    /// they were read. A file that can't be read isn't written over.
+    pub fn save(&self, root: &Path, session: &Session) -> Result<()> {
+        update(&self.path, root, session)
+    }
+
+    /// Snapshots `session` for the workspace at `root`, for restoring
+    /// should this run not end cleanly.
+    pub fn snapshot(&self, root: &Path, session: &Session) -> Result<()> {
+        update(&self.snapshots, root, session)
+    }
+
+    /// The snapshot left by a run in `root` that didn't end cleanly.
+    pub fn last_snapshot(&self, root: &Path) -> Option<Session> {
+        session(read(&self.snapshots).ok()?.remove(&key(root))?)
+    }
+
+    /// Drops the snapshot for `root`, as the run there ends cleanly.
+    pub fn clear_snapshot(&self, root: &Path) -> Result<()> {
+        let mut snapshots = match read(&self.snapshots) {
+            Ok(snapshots) => snapshots,
+            Err(_) => return Ok(()),
+        };
+        match snapshots.remove(&key(root)) {
+            Some(_) => write(&self.snapshots, snapshots),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The sessions in the file at `path` by workspace, each as it was saved,
+/// to be read on its own with [`session`].
+fn read(path: &Path) -> Result<BTreeMap<String, Value>> {
+    let mut file: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    // Files from before the version tag are the map of sessions itself.
+    let (version, sessions) = match file.get("version") {
+        Some(version) => (
+            version.as_u64().context("the version isn't a number")? as u32,
+            file["sessions"].take(),
+        ),
+        None => (1, file),
+    };
+    // Each version that changes the format reads the one before it here.
+    if version > FORMAT_VERSION {
+        return Err(Error::NewerFormat {
+            found: version,
+            supported: FORMAT_VERSION,
+        }
+        .into());
+    }
+    Ok(serde_json::from_value(sessions)?)
+}
+
+/// A session as saved, or `None` if it isn't one at all.
+fn session(saved: Value) -> Option<Session> {
+    match serde_json::from_value(saved) {
+        Ok(session) => Some(session),
+        Err(e) => {
+            tracing::warn!(error = %e, "saved session dropped");
+            None
+        }
+    }
+}
+
+/// Sets the session for `root` in the file at `path`, creating it if it
+/// isn't there yet but leaving it alone if it can't be read.
+fn update(path: &Path, root: &Path, session: &Session) -> Result<()> {
+    let mut sessions = match read(path) {
+        Ok(sessions) => sessions,
+        Err(e) if is_missing(&e) => BTreeMap::new(),
+        Err(e) => return Err(e.context(format!("{}: not saving over it", path.display()))),
+    };
+    sessions.insert(key(root), serde_json::to_value(session)?);
+    write(path, sessions)
+}
+
+fn is_missing(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+fn write(path: &Path, sessions: BTreeMap<String, Value>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = serde_json::json!({ "version": FORMAT_VERSION, "sessions": sessions });
+    // Written beside it and moved into place, so a crash or a full disk
+    // partway through leaves the old file rather than half of the new one.
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    fs::write(&temp, serde_json::to_string_pretty(&file)?)
+        .and_then(|()| fs::rename(&temp, path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })?;
+    Ok(())
+}
+
+/// Reads a `T`, or gives its default if what's there doesn't fit.
+pub(crate) fn lenient<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Lenient<T> {
+        Fits(T),
+        Doesnt(serde::de::IgnoredAny),
+    }
+    Ok(match Lenient::deserialize(deserializer)? {
+        Lenient::Fits(value) => value,
+        Lenient::Doesnt(_) => {
+            tracing::warn!(type_name = std::any::type_name::<T>(), "saved part dropped");
+            T::default()
+        }
+    })
+}
+
+fn key(root: &Path) -> String {
+    paths::normalize(root).to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_sessions_are_kept_per_workspace() {
+    use crate::{buffer::Position, jumps::Jump};
+
+    let dir = std::env::temp_dir().join(format!("wyv-session-test-{}", std::process::id()));
+    let store = SessionStore::new(dir.join("sessions.json"));
+    assert_eq!(store.load(Path::new("src")), Session::default());
+
+    let mut session = Session::default();
+    let main = Jump::new(Path::new("/work/src/main.rs"), Position::new(3, 1));
+    session.jumps.push(main);
+    store.save(Path::new("src"), &session).unwrap();
+    store.save(Path::new("tests"), &Session::default()).unwrap();
+    assert_eq!(store.load(Path::new("./src/../src")), session);
+    assert!(store.load(Path::new("tests")).jumps.is_empty());
+
+    // Snapshots are kept apart, until a clean exit clears them.
+    assert_eq!(store.last_snapshot(Path::new("src")), None);
+    store.snapshot(Path::new("src"), &session).unwrap();
+    assert_eq!(store.last_snapshot(Path::new("src")), Some(session));
+    assert_eq!(store.last_snapshot(Path::new("tests")), None);
+    store.clear_snapshot(Path::new("src")).unwrap();
+    assert_eq!(store.last_snapshot(Path::new("src")), None);
+    // Each write goes through a copy beside the file, which doesn't stay.
+    let names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert!(names.iter().all(|n| !n.ends_with(".tmp")), "{:?}", names);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_damaged_sessions_cost_only_their_own_workspace() {
+    let dir = std::env::temp_dir().join(format!("wyv-session-damaged-{}", std::process::id()));
+    let store = SessionStore::new(dir.join("sessions.json"));
+    let mut session = Session::default();
+    session.buffers.push(PathBuf::from("/work/a.rs"));
+    store.save(Path::new("src"), &session).unwrap();
+    let mut file: Value = serde_json::from_str(&fs::read_to_string(&store.path).unwrap()).unwrap();
+    assert_eq!(file["version"], FORMAT_VERSION);
+
+    // A part of one workspace's session that no longer reads leaves the
+    // rest of it, and one that isn't a session at all leaves the others.
+    let src = key(Path::new("src"));
+    file["sessions"][&src]["layout"] = serde_json::json!("sideways");
+    file["sessions"]["/elsewhere"] = serde_json::json!(42);
+    fs::write(&store.path, file.to_string()).unwrap();
+    assert_eq!(store.load(Path::new("src")), session);
+    assert_eq!(store.load(Path::new("/elsewhere")), Session::default());
+    store.save(Path::new("tests"), &Session::default()).unwrap();
+    assert_eq!(store.load(Path::new("src")), session);
+    let file: Value = serde_json::from_str(&fs::read_to_string(&store.path).unwrap()).unwrap();
+    assert_eq!(file["sessions"]["/elsewhere"], 42);
+
+    // Files from before the version tag still read.
+    let old = serde_json::json!({ &src: session });
+    fs::write(&store.path, old.to_string()).unwrap();
+    assert_eq!(store.load(Path::new("src")), session);
+
+    // A file that doesn't read at all isn't saved over.
+    fs::write(&store.path, "{ not json").unwrap();
+    assert!(store.save(Path::new("src"), &session).is_err());
+    assert_eq!(fs::read_to_string(&store.path).unwrap(), "{ not json");
+    fs::remove_dir_all(dir).unwrap();
+}
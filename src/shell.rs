@@ -0,0 +1,116 @@
+//! Shell commands typed at the prompt: `:!cmd` runs one with its output in
+//! the tasks panel, and `:%!cmd` or `:2,5!cmd` filter lines of the buffer
+//! through one, replacing them with what it prints.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{bail, Context, Result};
+
+/// Lines a filter reads, both zero-based and inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub first: usize,
+    pub last: usize,
+}
+
+/// Splits a filter command such as `%!sort` or `2,5!jq .` into its lines
+/// and command, as [`parse_range`] reads them. `None` if `line` isn't a
+/// filter.
+pub fn parse_filter(line: &str, cursor: usize, line_count: usize) -> Option<(LineRange, &str)> {
+    let (range, command) = line.trim_start().split_once('!')?;
+    Some((parse_range(range, cursor, line_count)?, command.trim()))
+}
+
+/// Reads a range of lines such as `2,5`: `%` is the whole buffer, `.` the
+/// cursor's line and `$` the last; line numbers count from one.
+pub fn parse_range(range: &str, cursor: usize, line_count: usize) -> Option<LineRange> {
+    let last_line = line_count.saturating_sub(1);
+    let address = |text: &str| -> Option<usize> {
+        match text.trim() {
+            "." => Some(cursor),
+            "$" => Some(last_line),
+            n => n.parse::<usize>().ok()?.checked_sub(1),
+        }
+    };
+    let (first, last) = match range.trim() {
+        "%" => (0, last_line),
+        range => match range.split_once(',') {
+            Some((first, last)) => (address(first)?, address(last)?),
+            None => {
+                let line = address(range)?;
+                (line, line)
+            }
+        },
+    };
+    Some(LineRange {
+        first: first.min(last),
+        last: first.max(last).min(last_line),
+    })
+}
+
+/// Runs `command` with the shell in `dir`, feeding it `input` and returning
+/// what it prints. Fails with its first line of errors if it does.
+pub fn filter(command: &str, dir: &Path, input: &str) -> Result<String> {
+    let (shell, flag) = match cfg!(windows) {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("could not run {}", command))?;
+    // Written from a thread, as for formatters, so that a command printing
+    // as it reads can't block on a full pipe.
+    let mut stdin = child.stdin.take().context("no stdin")?;
+    let input = input.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.lines().find(|l| !l.trim().is_empty()) {
+            Some(reason) => bail!("{}: {}", command, reason.trim()),
+            None => bail!("{} failed ({})", command, output.status),
+        }
+    }
+    String::from_utf8(output.stdout).with_context(|| format!("{}: output", command))
+}
+
+#[test]
+fn test_parse_filter() {
+    let range = |first, last| LineRange { first, last };
+    assert_eq!(parse_filter("%!sort", 3, 10), Some((range(0, 9), "sort")));
+    assert_eq!(
+        parse_filter("2,5! jq .", 0, 10),
+        Some((range(1, 4), "jq ."))
+    );
+    assert_eq!(
+        parse_filter(".!tr a b", 3, 10),
+        Some((range(3, 3), "tr a b"))
+    );
+    assert_eq!(parse_filter(".,$!sort", 3, 10), Some((range(3, 9), "sort")));
+    assert_eq!(parse_filter("8,2!sort", 0, 5), Some((range(1, 4), "sort")));
+    assert_eq!(parse_filter("!ls", 0, 5), None);
+    assert_eq!(parse_filter("0!ls", 0, 5), None);
+    assert_eq!(parse_filter("w", 0, 5), None);
+    assert_eq!(parse_range("3", 0, 5), Some(range(2, 2)));
+    assert_eq!(parse_range("2,x", 0, 5), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_filter_pipes_through_command() {
+    let dir = Path::new(".");
+    assert_eq!(filter("sort", dir, "b\na\n").unwrap(), "a\nb\n");
+    let error = filter("echo oops >&2; exit 3", dir, "").unwrap_err();
+    assert_eq!(error.to_string(), "echo oops >&2; exit 3: oops");
+}
@@ -0,0 +1,135 @@
+//! Expanding LSP snippets (`for ${1:i} in $2 {$0}`) into plain text.
+//! Placeholders become their default text, choices their first option and
+//! variables their default, since the editor has no tab stops to jump
+//! between.
+
+/// The text of `snippet`, and the char offset into it where the cursor
+/// goes: the first tab stop, else `$0`, else the end.
+pub fn expand(snippet: &str) -> (String, usize) {
+    let chars: Vec<char> = snippet.chars().collect();
+    let mut out = String::new();
+    let mut stops = Vec::new();
+    let mut i = 0;
+    parse(&chars, &mut i, false, &mut out, &mut stops);
+
+    let len = out.chars().count();
+    let cursor = stops
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .min_by_key(|(n, _)| *n)
+        .or_else(|| stops.iter().find(|(n, _)| *n == 0))
+        .map_or(len, |&(_, at)| at);
+    (out, cursor)
+}
+
+/// Copies text from `chars[*i..]` into `out` up to the end, or with
+/// `nested` up to the `}` closing a placeholder. Tab stops are recorded as
+/// their number and char offset in `out`.
+fn parse(
+    chars: &[char],
+    i: &mut usize,
+    nested: bool,
+    out: &mut String,
+    stops: &mut Vec<(u32, usize)>,
+) {
+    while let Some(&c) = chars.get(*i) {
+        match c {
+            '\\' if matches!(chars.get(*i + 1), Some('$' | '}' | '\\' | ',' | '|')) => {
+                out.push(chars[*i + 1]);
+                *i += 2;
+            }
+            '}' if nested => {
+                *i += 1;
+                return;
+            }
+            '$' => {
+                *i += 1;
+                dollar(chars, i, out, stops);
+            }
+            c => {
+                out.push(c);
+                *i += 1;
+            }
+        }
+    }
+}
+
+/// Handles what follows a `$`: `$1`, `${1}`, `${1:default}`, `${1|a,b|}`,
+/// `$NAME` or `${NAME:default}`.
+fn dollar(chars: &[char], i: &mut usize, out: &mut String, stops: &mut Vec<(u32, usize)>) {
+    let at = out.chars().count();
+    let braced = chars.get(*i) == Some(&'{');
+    if braced {
+        *i += 1;
+    }
+    let start = *i;
+    while chars
+        .get(*i)
+        .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_')
+    {
+        *i += 1;
+    }
+    let name: String = chars[start..*i].iter().collect();
+    if name.is_empty() {
+        // Not a snippet construct after all.
+        out.push('$');
+        if braced {
+            out.push('{');
+        }
+        return;
+    }
+    if let Ok(n) = name.parse() {
+        stops.push((n, at));
+    }
+    if !braced {
+        return;
+    }
+    match chars.get(*i) {
+        Some(':') => {
+            *i += 1;
+            parse(chars, i, true, out, stops);
+        }
+        Some('|') => {
+            *i += 1;
+            let mut first = true;
+            while let Some(&c) = chars.get(*i) {
+                *i += 1;
+                match c {
+                    '|' => {
+                        if chars.get(*i) == Some(&'}') {
+                            *i += 1;
+                        }
+                        return;
+                    }
+                    ',' => first = false,
+                    '\\' => {
+                        if let (true, Some(&next)) = (first, chars.get(*i)) {
+                            out.push(next);
+                        }
+                        *i += 1;
+                    }
+                    c if first => out.push(c),
+                    _ => (),
+                }
+            }
+        }
+        Some('}') => *i += 1,
+        _ => (),
+    }
+}
+
+#[test]
+fn test_expand_snippets() {
+    assert_eq!(expand("println!($0)"), ("println!()".to_string(), 9));
+    assert_eq!(
+        expand("for ${1:i} in ${2:iter} {\n\t$0\n}"),
+        ("for i in iter {\n\t\n}".to_string(), 4)
+    );
+    assert_eq!(expand("${1|one,two|} \\$x"), ("one $x".to_string(), 0));
+    assert_eq!(
+        expand("${1:outer ${2:inner}} ${TM_FILENAME:file}"),
+        ("outer inner file".to_string(), 0)
+    );
+    assert_eq!(expand("plain"), ("plain".to_string(), 5));
+    assert_eq!(expand("cost: $ 5"), ("cost: $ 5".to_string(), 9));
+}
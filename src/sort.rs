@@ -0,0 +1,126 @@
+//! The order names are listed in, shared by the tree, search results and
+//! the symbol picker built from them. Runs of digits compare by their
+//! value, so `file2` comes before `file10`, and case is folded the Unicode
+//! way rather than by a locale's collation rules.
+
+use std::{cmp::Ordering, iter::Peekable, path::Path, str::CharIndices};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SortOptions {
+    /// Compares runs of digits by their value rather than char by char.
+    pub natural: bool,
+    /// Puts `B` before `a`, as plain byte order does.
+    pub case_sensitive: bool,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        SortOptions {
+            natural: true,
+            case_sensitive: false,
+        }
+    }
+}
+
+impl SortOptions {
+    /// Orders two names. Names only equal once case is folded or leading
+    /// zeros dropped are still told apart, by their bytes.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        let (mut a_chars, mut b_chars) = (a.char_indices().peekable(), b.char_indices().peekable());
+        loop {
+            let (x, y) = match (a_chars.peek(), b_chars.peek()) {
+                (None, None) => return a.cmp(b),
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(&(_, x)), Some(&(_, y))) => (x, y),
+            };
+            let order = match self.natural && x.is_ascii_digit() && y.is_ascii_digit() {
+                true => compare_numbers(digits(a, &mut a_chars), digits(b, &mut b_chars)),
+                false => {
+                    a_chars.next();
+                    b_chars.next();
+                    match self.case_sensitive {
+                        true => x.cmp(&y),
+                        false => x.to_lowercase().cmp(y.to_lowercase()),
+                    }
+                }
+            };
+            if order != Ordering::Equal {
+                return order;
+            }
+        }
+    }
+
+    /// Orders two paths a component at a time, so that a directory's
+    /// files stay together.
+    pub fn compare_paths(&self, a: &Path, b: &Path) -> Ordering {
+        let names = |path: &Path| {
+            path.components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let (a, b) = (names(a), names(b));
+        a.iter()
+            .zip(&b)
+            .map(|(x, y)| self.compare(x, y))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    }
+}
+
+/// The run of ASCII digits `chars` is at, taken from `text`.
+fn digits<'a>(text: &'a str, chars: &mut Peekable<CharIndices>) -> &'a str {
+    let start = chars.peek().map_or(text.len(), |&(i, _)| i);
+    let mut end = start;
+    while let Some(&(i, c)) = chars.peek() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        end = i + c.len_utf8();
+        chars.next();
+    }
+    &text[start..end]
+}
+
+/// Orders two runs of digits by value, however long, then the one with
+/// fewer leading zeros first.
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let (x, y) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    x.len()
+        .cmp(&y.len())
+        .then_with(|| x.cmp(y))
+        .then_with(|| a.len().cmp(&b.len()))
+}
+
+#[test]
+fn test_numbers_sort_by_value_and_case_is_folded() {
+    let mut names = vec![
+        "file10", "File2", "file1", "file02", "b", "a10b", "a9z", "B", "a",
+    ];
+    names.sort_by(|a, b| SortOptions::default().compare(a, b));
+    assert_eq!(
+        names,
+        ["a", "a9z", "a10b", "B", "b", "file1", "File2", "file02", "file10"]
+    );
+
+    let bytes = SortOptions {
+        natural: false,
+        case_sensitive: true,
+    };
+    names.sort_by(|a, b| bytes.compare(a, b));
+    assert_eq!(
+        names,
+        ["B", "File2", "a", "a10b", "a9z", "b", "file02", "file1", "file10"]
+    );
+
+    let paths = SortOptions::default();
+    let order = paths.compare_paths(Path::new("src/item2/z.rs"), Path::new("src/item10/a.rs"));
+    assert_eq!(order, Ordering::Less);
+    assert_eq!(
+        paths.compare_paths(Path::new("a"), Path::new("a/b")),
+        Ordering::Less
+    );
+}
@@ -0,0 +1,569 @@
+//! Spell checking of prose files, and of the comments and strings in code,
+//! against a bundled list of English words, the config's words and the
+//! project's own list in `.wyv-words`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use crossterm::event::{KeyEvent, MouseEvent};
+use ratatui::layout::Rect;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use crate::widgets::popup_menu::{MenuKey, MenuState};
+
+/// English words, most common first, one per line.
+const WORDS: &str = include_str!("../assets/dictionary/en.txt");
+/// The project's own words, one per line, at its root.
+pub const WORDS_FILE: &str = ".wyv-words";
+/// Words common in comments about code that aren't in the English list.
+const CODE_WORDS: &[&str] = &[
+    "api",
+    "apis",
+    "args",
+    "async",
+    "backend",
+    "bool",
+    "boolean",
+    "booleans",
+    "config",
+    "configs",
+    "const",
+    "enum",
+    "enums",
+    "eof",
+    "fixme",
+    "frontend",
+    "github",
+    "impl",
+    "init",
+    "json",
+    "lifetimes",
+    "lookup",
+    "lookups",
+    "metadata",
+    "mutex",
+    "namespace",
+    "namespaces",
+    "newline",
+    "newlines",
+    "param",
+    "params",
+    "parsers",
+    "regex",
+    "repo",
+    "repos",
+    "runtime",
+    "stderr",
+    "stdin",
+    "stdout",
+    "struct",
+    "structs",
+    "todo",
+    "toml",
+    "tuple",
+    "tuples",
+    "txt",
+    "unicode",
+    "url",
+    "urls",
+    "usize",
+    "utf",
+    "whitespace",
+    "workspace",
+    "yaml",
+];
+/// Suggestions offered for a misspelling at most.
+const MAX_SUGGESTIONS: usize = 8;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpellOptions {
+    /// Underlines misspelled words.
+    pub enabled: bool,
+    /// Words to accept besides the dictionary's and the project's.
+    pub words: Vec<String>,
+}
+
+/// A misspelled word in a buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    pub line: usize,
+    /// Char range of the word in the line.
+    pub start: usize,
+    pub end: usize,
+    pub word: String,
+}
+
+/// Which parts of a file are checked: all of it outside code blocks for
+/// prose, only comments and strings for code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Prose,
+    Code {
+        line_comment: &'static str,
+        block_comment: Option<(&'static str, &'static str)>,
+        quotes: &'static str,
+    },
+}
+
+fn scope(path: &Path) -> Option<Scope> {
+    let code = |line_comment, block_comment, quotes| Scope::Code {
+        line_comment,
+        block_comment,
+        quotes,
+    };
+    let extension = match path.extension() {
+        Some(extension) => extension.to_str()?,
+        None => return Some(Scope::Prose),
+    };
+    Some(match extension {
+        "md" | "markdown" | "txt" | "text" | "rst" | "adoc" | "org" => Scope::Prose,
+        "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "java" | "kt" | "swift" | "cs" | "scala" => {
+            code("//", Some(("/*", "*/")), "\"")
+        }
+        "go" => code("//", Some(("/*", "*/")), "\"`"),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => code("//", Some(("/*", "*/")), "\"'`"),
+        "py" | "sh" | "bash" | "zsh" | "rb" | "toml" | "yaml" | "yml" => code("#", None, "\"'"),
+        "lua" | "sql" | "hs" => code("--", None, "\"'"),
+        _ => return None,
+    })
+}
+
+/// Where a line starts: in a block comment, a string, or a fenced block
+/// of code in prose.
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    comment: bool,
+    quote: Option<char>,
+    fence: bool,
+}
+
+/// The char ranges of `line` to check, given the state it starts in, which
+/// is left as the next line starts.
+fn checked_ranges(line: &[char], scope: Scope, state: &mut State) -> Vec<Range<usize>> {
+    let (line_comment, block_comment, quotes) = match scope {
+        Scope::Prose => {
+            let text: String = line.iter().collect();
+            if text.trim_start().starts_with("```") {
+                state.fence = !state.fence;
+                return Vec::new();
+            }
+            return std::iter::once(0..line.len())
+                .filter(|_| !state.fence)
+                .collect();
+        }
+        Scope::Code {
+            line_comment,
+            block_comment,
+            quotes,
+        } => (line_comment, block_comment, quotes),
+    };
+    let at = |i: usize, s: &str| {
+        s.chars()
+            .enumerate()
+            .all(|(j, c)| line.get(i + j) == Some(&c))
+    };
+
+    let mut ranges = Vec::new();
+    let mut start = (state.comment || state.quote.is_some()).then_some(0);
+    let mut i = 0;
+    while i < line.len() {
+        if state.comment {
+            let close = block_comment.map_or("", |(_, close)| close);
+            if at(i, close) {
+                ranges.extend(start.take().map(|s| s..i));
+                state.comment = false;
+                i += close.chars().count();
+            } else {
+                i += 1;
+            }
+            continue;
+        }
+        if let Some(quote) = state.quote {
+            match line[i] {
+                '\\' => i += 2,
+                c if c == quote => {
+                    ranges.extend(start.take().map(|s| s..i));
+                    state.quote = None;
+                    i += 1;
+                }
+                _ => i += 1,
+            }
+            continue;
+        }
+        if at(i, line_comment) {
+            ranges.push(i + line_comment.chars().count()..line.len());
+            return ranges;
+        }
+        if let Some((open, _)) = block_comment.filter(|(open, _)| at(i, open)) {
+            state.comment = true;
+            i += open.chars().count();
+            start = Some(i);
+        } else if quotes.contains(line[i]) {
+            state.quote = Some(line[i]);
+            i += 1;
+            start = Some(i);
+        } else if line[i] == '\'' {
+            // A char literal, so that `'"'` doesn't start a string.
+            i += match (line.get(i + 1), line.get(i + 2)) {
+                (Some('\\'), _) => line[i + 2..]
+                    .iter()
+                    .position(|&c| c == '\'')
+                    .map_or(1, |p| p + 3),
+                (Some(_), Some('\'')) => 3,
+                _ => 1,
+            };
+        } else {
+            i += 1;
+        }
+    }
+    ranges.extend(start.map(|s| s..line.len()));
+    // Only double quotes and backticks make strings that go on.
+    if state.quote == Some('\'') {
+        state.quote = None;
+    }
+    ranges
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\'' || c == '’'
+}
+
+/// The words in `range` of `line` worth checking: not in backticks, and not
+/// looking like identifiers, paths or addresses.
+fn words(line: &[char], range: Range<usize>) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut code = false;
+    let mut i = range.start;
+    while i < range.end {
+        let c = line[i];
+        if c == '`' {
+            code = !code;
+        }
+        if code || !is_word_char(c) {
+            i += 1;
+            continue;
+        }
+        let run = i;
+        while i < range.end && is_word_char(line[i]) {
+            i += 1;
+        }
+        let (mut start, mut end) = (run, i);
+        while start < end && matches!(line[start], '\'' | '’') {
+            start += 1;
+        }
+        while end > start && matches!(line[end - 1], '\'' | '’') {
+            end -= 1;
+        }
+        let word = &line[start..end];
+        let before = run.checked_sub(1).map(|p| line[p]);
+        let after = line.get(i).copied();
+        let identifier = word
+            .iter()
+            .enumerate()
+            .any(|(j, c)| c.is_numeric() || *c == '_' || (j > 0 && c.is_uppercase()));
+        let joined = before.is_some_and(|c| ".:/\\@$#&%~".contains(c))
+            || after.is_some_and(|c| ":/\\@(".contains(c))
+            || (after == Some('.') && line.get(i + 1).is_some_and(|c| c.is_alphanumeric()));
+        if word.len() >= 3 && !identifier && !joined {
+            words.push(start..end);
+        }
+    }
+    words
+}
+
+/// The bundled words and the extra ones accepted, with suggestions for
+/// those that aren't.
+#[derive(Debug)]
+pub struct SpellChecker {
+    /// Each known word and how common it is, lower meaning more common.
+    words: HashMap<&'static str, usize>,
+    /// Words from the config, lowercased.
+    config_words: HashSet<String>,
+    /// Words from the project's list, lowercased.
+    project_words: HashSet<String>,
+    /// Where the project's list is.
+    words_file: PathBuf,
+}
+
+impl SpellChecker {
+    /// A checker for the project at `root`, accepting `words` besides the
+    /// dictionary's and the project's.
+    pub fn new(root: &Path, words: &[String]) -> Self {
+        let words_file = root.join(WORDS_FILE);
+        let project_words = fs::read_to_string(&words_file)
+            .map(|text| lowercase(text.lines().map(str::trim)))
+            .unwrap_or_default();
+        let mut checker = SpellChecker {
+            words: WORDS
+                .lines()
+                .chain(CODE_WORDS.iter().copied())
+                .enumerate()
+                .map(|(rank, word)| (word, rank))
+                .collect(),
+            config_words: HashSet::new(),
+            project_words,
+            words_file,
+        };
+        checker.set_config_words(words);
+        checker
+    }
+
+    pub fn set_config_words(&mut self, words: &[String]) {
+        self.config_words = lowercase(words.iter().map(String::as_str));
+    }
+
+    /// Whether `word` is spelled right, in any case and with or without a
+    /// possessive `'s`.
+    pub fn check(&self, word: &str) -> bool {
+        let word = word.replace('’', "'").to_lowercase();
+        let known = |w: &str| {
+            self.words.contains_key(w)
+                || self.config_words.contains(w)
+                || self.project_words.contains(w)
+        };
+        known(&word) || word.strip_suffix("'s").is_some_and(known)
+    }
+
+    /// Known words close to `word`, the most likely first, in its case.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let mut found: HashMap<String, (usize, usize)> = HashMap::new();
+        let add = |found: &mut HashMap<String, (usize, usize)>, distance, candidate: String| {
+            if let Some(&rank) = self.words.get(candidate.as_str()) {
+                found.entry(candidate).or_insert((distance, rank));
+            }
+        };
+        let near = edits(&lower);
+        for candidate in &near {
+            add(&mut found, 1, candidate.clone());
+        }
+        // Two edits away only if one finds too little; there are many more.
+        if found.len() < MAX_SUGGESTIONS {
+            for candidate in &near {
+                for further in edits(candidate) {
+                    add(&mut found, 2, further);
+                }
+            }
+        }
+        let mut found: Vec<(usize, usize, String)> = found
+            .into_iter()
+            .map(|(word, (distance, rank))| (distance, rank, word))
+            .collect();
+        found.sort();
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let upper = word.chars().all(|c| !c.is_lowercase());
+        found
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, _, w)| match (upper, capitalized) {
+                (true, _) => w.to_uppercase(),
+                (false, true) => {
+                    let mut chars = w.chars();
+                    chars
+                        .next()
+                        .map_or(String::new(), |c| c.to_uppercase().chain(chars).collect())
+                }
+                (false, false) => w,
+            })
+            .collect()
+    }
+
+    /// Adds `word` to the project's list, creating it if need be.
+    pub fn add_word(&mut self, word: &str) -> Result<()> {
+        let word = word.trim();
+        if word.is_empty() || word.contains(char::is_whitespace) {
+            bail!("`{}` isn't a word", word);
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.words_file)
+            .with_context(|| format!("could not open {}", self.words_file.display()))?;
+        writeln!(file, "{}", word)?;
+        self.project_words.insert(word.to_lowercase());
+        Ok(())
+    }
+
+    /// The misspelled words on `lines` of `text`, the contents of `path`:
+    /// anywhere in prose, else in comments and strings. None if wyv doesn't
+    /// know what kind of file it is.
+    pub fn misspellings(&self, path: &Path, text: &Rope, lines: Range<usize>) -> Vec<Misspelling> {
+        let scope = match scope(path) {
+            Some(scope) => scope,
+            None => return Vec::new(),
+        };
+        let mut state = State::default();
+        let mut found = Vec::new();
+        let end = lines.end.min(text.len_lines());
+        for idx in 0..end {
+            let line: Vec<char> = text
+                .line(idx)
+                .chars()
+                .filter(|c| *c != '\n' && *c != '\r')
+                .collect();
+            let ranges = checked_ranges(&line, scope, &mut state);
+            if idx < lines.start {
+                continue;
+            }
+            for range in ranges {
+                for word in words(&line, range) {
+                    let text: String = line[word.clone()].iter().collect();
+                    if !self.check(&text) {
+                        found.push(Misspelling {
+                            line: idx,
+                            start: word.start,
+                            end: word.end,
+                            word: text,
+                        });
+                    }
+                }
+            }
+        }
+        found
+    }
+}
+
+fn lowercase<'a>(words: impl Iterator<Item = &'a str>) -> HashSet<String> {
+    words
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Every string one deletion, swap, replacement or insertion from `word`.
+fn edits(word: &str) -> Vec<String> {
+    const LETTERS: &str = "abcdefghijklmnopqrstuvwxyz'";
+    let chars: Vec<char> = word.chars().collect();
+    let joined = |parts: &[&[char]]| parts.iter().flat_map(|p| p.iter()).collect::<String>();
+    let mut edits = Vec::new();
+    for i in 0..=chars.len() {
+        let (left, right) = chars.split_at(i);
+        if let Some((_, rest)) = right.split_first() {
+            edits.push(joined(&[left, rest]));
+        }
+        if right.len() > 1 {
+            edits.push(joined(&[left, &[right[1], right[0]], &right[2..]]));
+        }
+        for c in LETTERS.chars() {
+            if let Some((_, rest)) = right.split_first() {
+                edits.push(joined(&[left, &[c], rest]));
+            }
+            edits.push(joined(&[left, &[c], right]));
+        }
+    }
+    edits
+}
+
+/// The suggestions popup: replacements for one misspelling, with one
+/// selected.
+#[derive(Debug)]
+pub struct Suggestions {
+    pub misspelling: Misspelling,
+    pub words: Vec<String>,
+    state: MenuState,
+}
+
+impl Suggestions {
+    pub fn new(misspelling: Misspelling, words: Vec<String>) -> Self {
+        Suggestions {
+            misspelling,
+            words,
+            state: MenuState::default(),
+        }
+    }
+
+    pub fn state(&self) -> &MenuState {
+        &self.state
+    }
+
+    pub fn selected_word(&self) -> Option<&str> {
+        self.words.get(self.state.selected()).map(String::as_str)
+    }
+
+    pub fn select_next(&mut self) {
+        self.state.select_next(self.words.len());
+    }
+
+    pub fn select_prev(&mut self) {
+        self.state.select_prev(self.words.len());
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> MenuKey {
+        self.state.handle_key(key, self.words.len())
+    }
+
+    /// Handles `mouse` for the popup as drawn in `area`.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent, area: Rect) -> MenuKey {
+        self.state.handle_mouse(mouse, area, self.words.len())
+    }
+}
+
+#[test]
+fn test_misspellings_in_comments_and_prose() {
+    let checker = SpellChecker::new(Path::new("/nonexistent"), &["wyv".to_string()]);
+    let found = |path: &str, text: &str| -> Vec<(usize, String)> {
+        let text = Rope::from_str(text);
+        checker
+            .misspellings(Path::new(path), &text, 0..usize::MAX)
+            .into_iter()
+            .map(|m| (m.line, m.word))
+            .collect()
+    };
+
+    let code = "\
+// Teh tree's nodes, see `fooo` and self.widht.
+let quoted = '\"'; let s = \"recieve\";
+/* a multi
+   line commnet */ let barr = 1;
+fn parse_thing(HTTPServer: u8) {} // wyv's camelCase https://exmaple.com/pth
+";
+    assert_eq!(
+        found("a.rs", code),
+        [
+            (0, "Teh".to_string()),
+            (1, "recieve".to_string()),
+            (3, "commnet".to_string()),
+        ]
+    );
+
+    let prose = "Some definately wrong words.\n```\nlet codde = 1;\n```\nThe ende.\n";
+    assert_eq!(
+        found("notes.md", prose),
+        [(0, "definately".to_string()), (4, "ende".to_string())]
+    );
+    assert!(found("image.png", prose).is_empty());
+}
+
+#[test]
+fn test_suggestions_keep_case() {
+    let checker = SpellChecker::new(Path::new("/nonexistent"), &[]);
+    assert!(checker.check("English"));
+    assert!(checker.check("tree's"));
+    assert_eq!(checker.suggest("teh")[0], "the");
+    assert_eq!(checker.suggest("Recieve")[0], "Receive");
+    assert_eq!(checker.suggest("WROD")[0], "WORD");
+    assert!(checker
+        .suggest("definately")
+        .contains(&"definitely".to_string()));
+}
+
+#[test]
+fn test_project_words_are_kept() {
+    let dir = std::env::temp_dir().join(format!("wyv-spell-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut checker = SpellChecker::new(&dir, &[]);
+    assert!(!checker.check("frobnicate"));
+    checker.add_word("frobnicate").unwrap();
+    assert!(checker.check("Frobnicate"));
+    assert!(checker.add_word("two words").is_err());
+    assert!(SpellChecker::new(&dir, &[]).check("frobnicate"));
+    fs::remove_dir_all(&dir).unwrap();
+}
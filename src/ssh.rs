@@ -0,0 +1,478 @@
+//! Remote editing over SSH through the `sftp` command line. A tree rooted
+//! at `ssh://user@host/path` is listed into a local mirror of empty files;
+//! opening one fetches it and saving it puts it back.
+
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "ssh")]
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "ssh"))]
+use crate::features;
+use crate::{buffer::Position, config, widgets::file_tree::TreeOptions};
+
+const SCHEME: &str = "ssh://";
+/// Levels of directories listed below the root.
+const MAX_DEPTH: usize = 8;
+/// Entries listed before the rest of the tree is left out.
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshOptions {
+    /// The sftp program to run.
+    pub sftp: String,
+    /// Arguments passed before the ones wyv adds, e.g. `-i ~/.ssh/work`.
+    pub args: Vec<String>,
+    /// Where remote trees are mirrored; the state directory by default.
+    pub mirror: Option<PathBuf>,
+}
+
+impl Default for SshOptions {
+    fn default() -> Self {
+        SshOptions {
+            sftp: "sftp".to_string(),
+            args: Vec::new(),
+            mirror: None,
+        }
+    }
+}
+
+/// Whether `arg` names a remote tree rather than a local path.
+pub fn is_url(arg: &str) -> bool {
+    arg.starts_with(SCHEME)
+}
+
+/// A remote tree, written `ssh://[user@]host[:port]/path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshUrl {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    /// Absolute path on the host; `/` when none is given.
+    pub path: String,
+}
+
+impl SshUrl {
+    pub fn parse(url: &str) -> Result<Self> {
+        let rest = url
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| anyhow!("`{}` isn't an ssh:// URL", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let (user, host) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host),
+            None => (None, authority),
+        };
+        let (host, port) = match host.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| anyhow!("`{}` isn't a port", port))?;
+                (host, Some(port))
+            }
+            None => (host, None),
+        };
+        if host.is_empty() {
+            bail!("`{}` has no host", url);
+        }
+        let path = match path.trim_end_matches('/') {
+            "" => "/".to_string(),
+            path => path.to_string(),
+        };
+        Ok(SshUrl {
+            user: user.filter(|u| !u.is_empty()),
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// `user@host`, or the host alone, as sftp takes it.
+    pub fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+}
+
+impl fmt::Display for SshUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", SCHEME, self.destination())?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        write!(f, "{}", self.path)
+    }
+}
+
+/// Something listed on the host, by its path below the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    Dir(String),
+    /// A file or a link, which is fetched like the file it points to.
+    File(String),
+}
+
+/// Results of sftp sessions run in the background.
+#[derive(Debug)]
+pub enum SshEvent {
+    /// The remote tree at `url` was listed into its mirror.
+    Listed { url: SshUrl, result: Result<()> },
+    /// A mirrored file was fetched, to be opened at `position`.
+    Fetched {
+        path: PathBuf,
+        position: Option<Position>,
+        result: Result<()>,
+    },
+    /// A saved file was put back on the host.
+    Put { path: PathBuf, result: Result<()> },
+}
+
+/// How the connection to the host is doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Connection {
+    Connecting,
+    Connected,
+    Failed(String),
+}
+
+/// A remote tree and its local mirror.
+#[derive(Debug, Clone)]
+pub struct SshRoot {
+    url: SshUrl,
+    #[cfg_attr(not(feature = "ssh"), allow(dead_code))]
+    options: SshOptions,
+    mirror: PathBuf,
+    pub connection: Connection,
+    /// Mirrored files fetched since connecting, which hold the remote text.
+    fetched: HashSet<PathBuf>,
+    /// Fetches and puts still running.
+    pub transfers: usize,
+}
+
+impl SshRoot {
+    /// The tree at `url`, mirrored under the configured directory, which is
+    /// created if need be.
+    pub fn new(url: SshUrl, options: &SshOptions) -> Result<Self> {
+        let base = match &options.mirror {
+            Some(mirror) => mirror.clone(),
+            None => config::state_dir()
+                .ok_or_else(|| anyhow!("no state directory to mirror remote files in"))?
+                .join("ssh"),
+        };
+        let mut host = url.destination();
+        if let Some(port) = url.port {
+            host = format!("{}:{}", host, port);
+        }
+        let mirror = base.join(host).join(url.path.trim_start_matches('/'));
+        fs::create_dir_all(&mirror)?;
+        // The tree names files by their canonical paths.
+        let mirror = fs::canonicalize(mirror)?;
+        Ok(SshRoot {
+            url,
+            options: options.clone(),
+            mirror,
+            connection: Connection::Connecting,
+            fetched: HashSet::new(),
+            transfers: 0,
+        })
+    }
+
+    pub fn url(&self) -> &SshUrl {
+        &self.url
+    }
+
+    /// The local directory standing in for the remote root.
+    pub fn mirror(&self) -> &Path {
+        &self.mirror
+    }
+
+    /// Where the mirrored file `local` is on the host, if it is mirrored.
+    pub fn remote_path(&self, local: &Path) -> Option<String> {
+        let relative = local.strip_prefix(&self.mirror).ok()?;
+        let mut path = self.url.path.trim_end_matches('/').to_string();
+        for part in relative.iter() {
+            path.push('/');
+            path.push_str(part.to_str()?);
+        }
+        Some(path)
+    }
+
+    /// Whether `local` is a mirrored file whose text hasn't been fetched
+    /// yet.
+    pub fn needs_fetch(&self, local: &Path) -> bool {
+        self.remote_path(local).is_some() && !self.fetched.contains(local) && local.is_file()
+    }
+
+    pub fn set_fetched(&mut self, local: &Path) {
+        self.fetched.insert(local.to_owned());
+    }
+
+    /// One line on the connection for the status bar.
+    pub fn indicator(&self) -> String {
+        let host = &self.url.host;
+        match (&self.connection, self.transfers) {
+            (Connection::Connecting, _) => format!("ssh: connecting to {}", host),
+            (Connection::Failed(_), _) => format!("ssh: {} disconnected", host),
+            (Connection::Connected, 0) => format!("ssh: {}", host),
+            (Connection::Connected, n) => format!("ssh: {} ({} transferring)", host, n),
+        }
+    }
+
+    /// Lists the remote tree a level at a time, leaving out what the tree
+    /// hides.
+    pub fn list(&self, options: &TreeOptions) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        let mut level = vec![String::new()];
+        for _ in 0..=MAX_DEPTH {
+            if level.is_empty() || entries.len() >= MAX_ENTRIES {
+                break;
+            }
+            // A `-` keeps going past directories that can't be read.
+            let commands: Vec<String> = level
+                .iter()
+                .map(|dir| format!("-ls -la {}", quote(&self.join(dir))))
+                .collect();
+            let output = self.batch(&commands)?;
+            let mut next = Vec::new();
+            for (dir, listing) in level.iter().zip(parse_listings(&output)) {
+                for (name, is_dir) in listing {
+                    if options.hides(&name) {
+                        continue;
+                    }
+                    let path = match dir.is_empty() {
+                        true => name,
+                        false => format!("{}/{}", dir, name),
+                    };
+                    match is_dir {
+                        true => {
+                            next.push(path.clone());
+                            entries.push(Entry::Dir(path));
+                        }
+                        false => entries.push(Entry::File(path)),
+                    }
+                }
+            }
+            level = next;
+        }
+        entries.truncate(MAX_ENTRIES);
+        Ok(entries)
+    }
+
+    /// Replaces the mirror with empty stand-ins for `entries`.
+    pub fn populate(&self, entries: &[Entry]) -> Result<()> {
+        if self.mirror.exists() {
+            fs::remove_dir_all(&self.mirror)?;
+        }
+        fs::create_dir_all(&self.mirror)?;
+        for entry in entries {
+            match entry {
+                Entry::Dir(path) => fs::create_dir_all(self.mirror.join(path))?,
+                Entry::File(path) => {
+                    fs::File::create(self.mirror.join(path))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the remote file behind `local` over it.
+    pub fn fetch(&self, local: &Path) -> Result<()> {
+        let remote = self.remote_path_of(local)?;
+        self.batch(&[format!("get {} {}", quote(&remote), quote_path(local)?)])?;
+        Ok(())
+    }
+
+    /// Copies `local` over its remote file.
+    pub fn put(&self, local: &Path) -> Result<()> {
+        let remote = self.remote_path_of(local)?;
+        self.batch(&[format!("put {} {}", quote_path(local)?, quote(&remote))])?;
+        Ok(())
+    }
+
+    fn remote_path_of(&self, local: &Path) -> Result<String> {
+        self.remote_path(local)
+            .ok_or_else(|| anyhow!("{} isn't in the remote tree", local.display()))
+    }
+
+    /// The remote path of `relative`, below the root.
+    fn join(&self, relative: &str) -> String {
+        match relative.is_empty() {
+            true => self.url.path.clone(),
+            false => format!("{}/{}", self.url.path.trim_end_matches('/'), relative),
+        }
+    }
+
+    /// Runs `commands` in one sftp session, returning what it printed.
+    #[cfg(feature = "ssh")]
+    fn batch(&self, commands: &[String]) -> Result<String> {
+        let mut command = Command::new(&self.options.sftp);
+        command.args(&self.options.args).args(["-q", "-b", "-"]);
+        if let Some(port) = self.url.port {
+            command.args(["-P", &port.to_string()]);
+        }
+        let mut child = command
+            .arg(self.url.destination())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("{}: {}", self.options.sftp, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            for command in commands {
+                writeln!(stdin, "{}", command)?;
+            }
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().rfind(|l| !l.trim().is_empty());
+            bail!(
+                "sftp to {} failed: {}",
+                self.url.host,
+                reason.unwrap_or("no reason given").trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    #[cfg(not(feature = "ssh"))]
+    fn batch(&self, _commands: &[String]) -> Result<String> {
+        Err(features::missing("ssh"))
+    }
+}
+
+/// Quotes `path` for an sftp batch command.
+fn quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn quote_path(path: &Path) -> Result<String> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| anyhow!("{} isn't UTF-8", path.display()))?;
+    Ok(quote(path))
+}
+
+/// Splits the output of a batch of `ls -la` commands into one listing per
+/// command, each the names in it and whether they are directories. sftp
+/// echoes every command before its output.
+fn parse_listings(output: &str) -> Vec<Vec<(String, bool)>> {
+    let mut listings: Vec<Vec<(String, bool)>> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with("sftp>") {
+            listings.push(Vec::new());
+            continue;
+        }
+        let listing = match listings.last_mut() {
+            Some(listing) => listing,
+            None => continue,
+        };
+        if let Some((name, is_dir)) = parse_long_entry(line) {
+            listing.push((name, is_dir));
+        }
+    }
+    listings
+}
+
+/// Reads the name and kind from one line of `ls -l` output, skipping `.`
+/// and `..`.
+fn parse_long_entry(line: &str) -> Option<(String, bool)> {
+    let kind = line.chars().next()?;
+    if !matches!(kind, 'd' | '-' | 'l') {
+        return None;
+    }
+    // The name is whatever follows the eight columns before it.
+    let mut rest = line;
+    for _ in 0..8 {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace)?..];
+    }
+    let mut name = rest.trim_start();
+    if kind == 'l' {
+        name = name.split(" -> ").next().unwrap_or(name);
+    }
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return None;
+    }
+    Some((name.to_string(), kind == 'd'))
+}
+
+#[test]
+fn test_parse_url() {
+    let url = SshUrl::parse("ssh://ada@build.example:2222/srv/app/").unwrap();
+    assert_eq!(url.user.as_deref(), Some("ada"));
+    assert_eq!(url.host, "build.example");
+    assert_eq!(url.port, Some(2222));
+    assert_eq!(url.path, "/srv/app");
+    assert_eq!(url.destination(), "ada@build.example");
+    assert_eq!(url.to_string(), "ssh://ada@build.example:2222/srv/app");
+
+    let url = SshUrl::parse("ssh://host").unwrap();
+    assert_eq!((url.user, url.port, url.path.as_str()), (None, None, "/"));
+    assert!(SshUrl::parse("ssh://host:port/").is_err());
+    assert!(SshUrl::parse("ssh:///srv").is_err());
+    assert!(SshUrl::parse("/srv").is_err());
+}
+
+#[test]
+fn test_parse_listings() {
+    let output = "\
+sftp> -ls -la \"/srv/app\"
+drwxr-xr-x    4 ada      ada          4096 Oct 15 10:00 .
+drwxr-xr-x    9 root     root         4096 Oct  1 09:12 ..
+-rw-r--r--    1 ada      ada           120 Oct 15 10:00 Cargo.toml
+drwxr-xr-x    2 ada      ada          4096 Oct 15 10:00 src
+-rw-r--r--    1 ada      ada             0 Oct 15 10:00 two  spaces.txt
+lrwxrwxrwx    1 ada      ada             9 Oct 15 10:00 latest -> build/v2
+sftp> -ls -la \"/srv/app/src\"
+total 8
+-rw-r--r--    1 ada      ada            42 Oct 15 10:00 main.rs
+";
+    assert_eq!(
+        parse_listings(output),
+        [
+            vec![
+                ("Cargo.toml".to_string(), false),
+                ("src".to_string(), true),
+                ("two  spaces.txt".to_string(), false),
+                ("latest".to_string(), false),
+            ],
+            vec![("main.rs".to_string(), false)],
+        ]
+    );
+}
+
+#[test]
+fn test_mirror_paths() {
+    let dir = std::env::temp_dir().join(format!("wyv-ssh-test-{}", std::process::id()));
+    let options = SshOptions {
+        mirror: Some(dir.clone()),
+        ..SshOptions::default()
+    };
+    let url = SshUrl::parse("ssh://ada@host/srv/app").unwrap();
+    let root = SshRoot::new(url, &options).unwrap();
+    let mirror = fs::canonicalize(&dir).unwrap().join("ada@host/srv/app");
+    assert_eq!(root.mirror(), mirror);
+    let local = root.mirror().join("src/main.rs");
+    assert_eq!(
+        root.remote_path(&local).as_deref(),
+        Some("/srv/app/src/main.rs")
+    );
+    assert_eq!(root.remote_path(&dir), None);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(quote("a \"b\"\\c"), r#""a \"b\"\\c""#);
+}
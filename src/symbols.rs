@@ -0,0 +1,350 @@
+//! The functions, types and other definitions in files, for the symbol
+//! pickers. They come from a ctags `tags` file at the project root when
+//! there is one, else from patterns for the languages wyv knows.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+
+use crate::{
+    buffer::Position,
+    jobs::{JobId, Jobs},
+    runtime::{Message, Spawner},
+    search,
+    widgets::file_tree::TreeOptions,
+};
+
+/// Files bigger than this aren't scanned for a workspace's symbols.
+const MAX_FILE_SIZE: u64 = 1 << 20;
+
+/// A definition in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// What it is, such as `function` or `struct`.
+    pub kind: String,
+    pub path: PathBuf,
+    /// Where its name is.
+    pub position: Position,
+}
+
+/// Patterns for one language's definitions: the kind each finds and a
+/// regex matching a line, with the name in its `name` group.
+type Patterns = &'static [(&'static str, &'static str)];
+
+const RUST: Patterns = &[
+    (
+        "function",
+        r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|extern\s+\S+)\s+)*fn\s+(?P<name>\w+)",
+    ),
+    (
+        "struct",
+        r"^\s*(?:pub(?:\([^)]*\))?\s+)?struct\s+(?P<name>\w+)",
+    ),
+    ("enum", r"^\s*(?:pub(?:\([^)]*\))?\s+)?enum\s+(?P<name>\w+)"),
+    (
+        "trait",
+        r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:unsafe\s+)?trait\s+(?P<name>\w+)",
+    ),
+    ("type", r"^\s*(?:pub(?:\([^)]*\))?\s+)?type\s+(?P<name>\w+)"),
+    (
+        "module",
+        r"^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(?P<name>\w+)",
+    ),
+    (
+        "constant",
+        r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:const|static)\s+(?:mut\s+)?(?P<name>\w+)\s*:",
+    ),
+    ("macro", r"^\s*macro_rules!\s*(?P<name>\w+)"),
+    (
+        "impl",
+        r"^\s*(?:unsafe\s+)?impl(?:<.*?>)?\s+(?P<name>[^{]+?)\s*(?:where\b.*|\{.*)?$",
+    ),
+];
+
+const PYTHON: Patterns = &[
+    ("function", r"^\s*(?:async\s+)?def\s+(?P<name>\w+)"),
+    ("class", r"^\s*class\s+(?P<name>\w+)"),
+];
+
+const JAVASCRIPT: Patterns = &[
+    (
+        "function",
+        r"^\s*(?:export\s+)?(?:default\s+)?(?:async\s+)?function\*?\s+(?P<name>[\w$]+)",
+    ),
+    (
+        "function",
+        r"^\s*(?:export\s+)?(?:const|let|var)\s+(?P<name>[\w$]+)\s*=\s*(?:async\s*)?(?:\([^)]*\)|[\w$]+)\s*=>",
+    ),
+    (
+        "class",
+        r"^\s*(?:export\s+)?(?:default\s+)?(?:abstract\s+)?class\s+(?P<name>[\w$]+)",
+    ),
+    (
+        "interface",
+        r"^\s*(?:export\s+)?interface\s+(?P<name>[\w$]+)",
+    ),
+    (
+        "type",
+        r"^\s*(?:export\s+)?type\s+(?P<name>[\w$]+)\s*(?:<.*>)?\s*=",
+    ),
+    (
+        "enum",
+        r"^\s*(?:export\s+)?(?:const\s+)?enum\s+(?P<name>[\w$]+)",
+    ),
+];
+
+const GO: Patterns = &[
+    ("function", r"^func\s+(?:\([^)]*\)\s*)?(?P<name>\w+)"),
+    ("type", r"^type\s+(?P<name>\w+)"),
+];
+
+fn patterns(extension: &str) -> Option<Patterns> {
+    Some(match extension {
+        "rs" => RUST,
+        "py" => PYTHON,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => JAVASCRIPT,
+        "go" => GO,
+        _ => return None,
+    })
+}
+
+/// Finds symbols with each language's patterns, compiling them the first
+/// time a file of that language comes up.
+#[derive(Debug, Default)]
+pub struct Scanner {
+    compiled: HashMap<&'static str, Vec<(&'static str, Regex)>>,
+}
+
+impl Scanner {
+    /// The symbols in `text`, the contents of `path`, in order. None for
+    /// languages without patterns.
+    pub fn scan(&mut self, path: &Path, text: &str) -> Vec<Symbol> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let patterns = match patterns(extension) {
+            Some(patterns) => patterns,
+            None => return Vec::new(),
+        };
+        let key = patterns[0].1;
+        let compiled = self.compiled.entry(key).or_insert_with(|| {
+            patterns
+                .iter()
+                .map(|(kind, pattern)| (*kind, Regex::new(pattern).expect("valid pattern")))
+                .collect()
+        });
+
+        let mut symbols = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let found = compiled
+                .iter()
+                .find_map(|(kind, regex)| Some((kind, regex.captures(line)?.name("name")?)));
+            if let Some((kind, name)) = found {
+                symbols.push(Symbol {
+                    name: name.as_str().to_string(),
+                    kind: kind.to_string(),
+                    path: path.to_owned(),
+                    position: Position::new(idx, line[..name.start()].chars().count()),
+                });
+            }
+        }
+        symbols
+    }
+}
+
+/// The symbols of the files under `root`: those its `tags` file lists if it
+/// has one, else those found in the files a search would look at.
+pub fn workspace(root: &Path, options: &TreeOptions, cancelled: &dyn Fn() -> bool) -> Vec<Symbol> {
+    if let Ok(tags) = fs::read_to_string(root.join("tags")) {
+        return parse_tags(&tags, root);
+    }
+    let mut scanner = Scanner::default();
+    let mut symbols = Vec::new();
+    for path in search::files(root, options) {
+        if cancelled() {
+            break;
+        }
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if patterns(extension).is_none()
+            || fs::metadata(&path).map_or(true, |m| m.len() > MAX_FILE_SIZE)
+        {
+            continue;
+        }
+        if let Ok(text) = fs::read_to_string(&path) {
+            symbols.extend(scanner.scan(&path, &text));
+        }
+    }
+    symbols
+}
+
+/// The symbols in a ctags `tags` file, whose paths are relative to `dir`.
+/// Addresses given as search patterns are looked up in the files.
+pub fn parse_tags(tags: &str, dir: &Path) -> Vec<Symbol> {
+    let mut files: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let mut symbols = Vec::new();
+    for line in tags.lines().filter(|l| !l.starts_with("!_TAG_")) {
+        let mut fields = line.split('\t');
+        let (name, file, rest) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(name), Some(file), Some(rest)) => (name, file, rest),
+            _ => continue,
+        };
+        // The address may hold tabs, so it runs to the `;"` before the
+        // extension fields.
+        let rest = std::iter::once(rest)
+            .chain(fields)
+            .collect::<Vec<_>>()
+            .join("\t");
+        let (address, extensions) = rest.split_once(";\"").unwrap_or((&rest, ""));
+        let path = dir.join(file);
+        let lines = files.entry(path.clone()).or_insert_with(|| {
+            let text = fs::read_to_string(&path).unwrap_or_default();
+            text.lines().map(str::to_string).collect()
+        });
+        let line = match address.parse::<usize>() {
+            Ok(number) => number.saturating_sub(1),
+            Err(_) => match find_pattern(address, lines) {
+                Some(line) => line,
+                None => continue,
+            },
+        };
+        let col = lines
+            .get(line)
+            .and_then(|text| Some(text[..text.find(name)?].chars().count()))
+            .unwrap_or(0);
+        symbols.push(Symbol {
+            name: name.to_string(),
+            kind: tag_kind(extensions).to_string(),
+            path,
+            position: Position::new(line, col),
+        });
+    }
+    symbols
+}
+
+/// The line a tags search address such as `/^fn main() {$/` points at.
+fn find_pattern(address: &str, lines: &[String]) -> Option<usize> {
+    let pattern = address
+        .strip_prefix('/')
+        .and_then(|a| a.strip_suffix('/'))
+        .or_else(|| address.strip_prefix('?')?.strip_suffix('?'))?;
+    let (pattern, start) = match pattern.strip_prefix('^') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let (pattern, end) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let pattern = pattern.replace("\\/", "/").replace("\\\\", "\\");
+    lines.iter().position(|line| match (start, end) {
+        (true, true) => *line == pattern,
+        (true, false) => line.starts_with(&pattern),
+        (false, true) => line.ends_with(&pattern),
+        (false, false) => line.contains(&pattern),
+    })
+}
+
+/// The kind named by a tag's extension fields, either `kind:function` or
+/// ctags' single letters.
+fn tag_kind(extensions: &str) -> &str {
+    let field = extensions
+        .split('\t')
+        .map(str::trim)
+        .find(|f| f.len() == 1 || f.starts_with("kind:"));
+    match field {
+        Some(field) if field.starts_with("kind:") => &field[5..],
+        Some("f") => "function",
+        Some("c") => "class",
+        Some("s") => "struct",
+        Some("g") => "enum",
+        Some("m") => "member",
+        Some("t") => "type",
+        Some("v") => "variable",
+        Some("d") => "macro",
+        Some("i") => "interface",
+        Some("n") => "module",
+        _ => "symbol",
+    }
+}
+
+/// Gathers a workspace's symbols as a job, sending them as a
+/// [`Message::SymbolsIndexed`].
+pub fn spawn(jobs: &mut Jobs, spawner: &Spawner, root: &Path, options: &TreeOptions) -> JobId {
+    let root = root.to_owned();
+    let options = options.clone();
+    jobs.spawn(spawner, "index symbols", move |ctx| {
+        let symbols = workspace(&root, &options, &|| ctx.is_cancelled());
+        if ctx.is_cancelled() {
+            anyhow::bail!("cancelled");
+        }
+        ctx.send(Message::SymbolsIndexed {
+            id: ctx.id(),
+            symbols,
+        });
+        Ok(())
+    })
+}
+
+#[test]
+fn test_scan_rust_and_python() {
+    let mut scanner = Scanner::default();
+    let rust = "\
+pub struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl<T> Display for Tree<T> where T: Debug {
+    pub(crate) async fn render(&self) {}
+    // fn commented_out() {}
+}
+macro_rules! here { () => {} }
+";
+    let symbols = scanner.scan(Path::new("a.rs"), rust);
+    let found: Vec<(&str, &str)> = symbols
+        .iter()
+        .map(|s| (s.kind.as_str(), s.name.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("struct", "Tree"),
+            ("impl", "Display for Tree<T>"),
+            ("function", "render"),
+            ("macro", "here"),
+        ]
+    );
+    assert_eq!(symbols[2].position, Position::new(5, 24));
+
+    let python = "class Tree:\n    async def walk(self):\n        pass\n";
+    let names: Vec<String> = scanner
+        .scan(Path::new("a.py"), python)
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    assert_eq!(names, ["Tree", "walk"]);
+    assert!(scanner.scan(Path::new("a.txt"), python).is_empty());
+}
+
+#[test]
+fn test_parse_tags() {
+    let dir = std::env::temp_dir().join(format!("wyv-tags-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.rs"), "use std::io;\n\nfn main() {\n}\n").unwrap();
+    let tags = "\
+!_TAG_FILE_FORMAT\t2\t/extended format/
+main\tmain.rs\t/^fn main() {$/;\"\tf
+App\tapp.rs\t120;\"\tkind:struct\tline:120
+gone\tmain.rs\t/^fn gone$/;\"\tf
+";
+    let symbols = parse_tags(tags, &dir);
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols[0].kind, "function");
+    assert_eq!(symbols[0].path, dir.join("main.rs"));
+    assert_eq!(symbols[0].position, Position::new(2, 3));
+    assert_eq!(symbols[1].kind, "struct");
+    assert_eq!(symbols[1].position, Position::new(119, 0));
+    fs::remove_dir_all(&dir).unwrap();
+}
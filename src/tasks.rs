@@ -0,0 +1,358 @@
+//! Tasks: shell commands such as `cargo build` or `make test`, run as
+//! background jobs with their output streamed to the tasks panel. Places
+//! the output points at, like compiler errors, are collected as problems.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buffer::Position,
+    jobs::{JobContext, JobId, Jobs},
+    runtime::{Message, Spawner},
+    widgets::locations::Entry,
+};
+
+/// Output lines a task run keeps; older ones are dropped.
+const MAX_LINES: usize = 5000;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskConfig {
+    pub name: String,
+    /// A shell command line.
+    pub command: String,
+    /// Where it runs, relative to the project root; the root if unset.
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+}
+
+impl TaskConfig {
+    pub fn new(name: String, command: String) -> Self {
+        TaskConfig {
+            name,
+            command,
+            cwd: None,
+        }
+    }
+
+    /// The directory the task runs in.
+    pub fn dir(&self, root: &Path) -> PathBuf {
+        match &self.cwd {
+            Some(cwd) => root.join(cwd),
+            None => root.to_owned(),
+        }
+    }
+}
+
+/// The configured tasks, then those found in the project's build files
+/// (`cargo:test`, `npm:lint`, `make:all`...) unless a configured one has the
+/// same name.
+pub fn available(configured: &[TaskConfig], root: &Path) -> Vec<TaskConfig> {
+    let mut tasks = configured.to_vec();
+    for task in detect(root) {
+        if !tasks.iter().any(|t| t.name == task.name) {
+            tasks.push(task);
+        }
+    }
+    tasks
+}
+
+/// Tasks for the build files in `root`: cargo's usual commands, npm scripts
+/// and make targets.
+pub fn detect(root: &Path) -> Vec<TaskConfig> {
+    let mut tasks = Vec::new();
+    if root.join("Cargo.toml").is_file() {
+        for command in ["build", "check", "test", "clippy"] {
+            tasks.push(TaskConfig::new(
+                format!("cargo:{}", command),
+                format!("cargo {}", command),
+            ));
+        }
+    }
+    let scripts = fs::read_to_string(root.join("package.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|package| package.get("scripts")?.as_object().cloned());
+    for script in scripts.iter().flat_map(|s| s.keys()) {
+        tasks.push(TaskConfig::new(
+            format!("npm:{}", script),
+            format!("npm run {}", script),
+        ));
+    }
+    let makefile = ["GNUmakefile", "makefile", "Makefile"]
+        .iter()
+        .find_map(|name| fs::read_to_string(root.join(name)).ok());
+    for target in makefile.as_deref().map(make_targets).unwrap_or_default() {
+        tasks.push(TaskConfig::new(
+            format!("make:{}", target),
+            format!("make {}", target),
+        ));
+    }
+    tasks
+}
+
+/// The explicit targets of a makefile, skipping special and pattern ones.
+fn make_targets(makefile: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    for line in makefile.lines() {
+        if line.starts_with(|c: char| c.is_whitespace() || c == '#') {
+            continue;
+        }
+        let (names, rest) = match line.split_once(':') {
+            Some(split) => split,
+            None => continue,
+        };
+        // `x := y` and `x ::= y` are assignments.
+        if names.contains('=') || rest.starts_with('=') || rest.starts_with(":=") {
+            continue;
+        }
+        for name in names.split_whitespace() {
+            let special = name.starts_with('.') || name.contains(['%', '$']);
+            if !special && !targets.iter().any(|t| t == name) {
+                targets.push(name.to_string());
+            }
+        }
+    }
+    targets
+}
+
+/// Runs `task` as a job, streaming its output as [`Message::TaskOutput`]
+/// and finishing with a [`Message::TaskExited`].
+pub fn spawn(jobs: &mut Jobs, spawner: &Spawner, task: &TaskConfig, root: &Path) -> JobId {
+    let command = task.command.clone();
+    let dir = task.dir(root);
+    jobs.spawn(spawner, &format!("task {}", task.name), move |ctx| {
+        let result = run(ctx, &command, &dir);
+        let code = result.as_ref().ok().copied().flatten();
+        ctx.send(Message::TaskExited { id: ctx.id(), code });
+        result.map(|_| ())
+    })
+}
+
+fn run(ctx: &JobContext, command: &str, dir: &Path) -> Result<Option<i32>> {
+    let (shell, flag) = match cfg!(windows) {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("could not run {}", command))?;
+
+    let outputs: [Option<Box<dyn Read + Send>>; 2] = [
+        child.stdout.take().map(|o| Box::new(o) as _),
+        child.stderr.take().map(|e| Box::new(e) as _),
+    ];
+    let readers: Vec<_> = outputs
+        .into_iter()
+        .flatten()
+        .map(|output| {
+            let (id, sender) = (ctx.id(), ctx.sender());
+            thread::spawn(move || {
+                let mut output = BufReader::new(output);
+                let mut line = Vec::new();
+                while matches!(output.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                    let text = String::from_utf8_lossy(&line);
+                    let text = text.trim_end_matches(['\n', '\r']).to_string();
+                    if sender.send(Message::TaskOutput { id, line: text }).is_err() {
+                        return;
+                    }
+                    line.clear();
+                }
+            })
+        })
+        .collect();
+
+    loop {
+        if ctx.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("cancelled");
+        }
+        if let Some(status) = child.try_wait()? {
+            for reader in readers {
+                let _ = reader.join();
+            }
+            return Ok(status.code());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// A run of a task: its output so far and the problems found in it.
+#[derive(Debug)]
+pub struct TaskRun {
+    pub id: JobId,
+    pub name: String,
+    lines: VecDeque<String>,
+    problems: Vec<Entry>,
+    parser: ProblemParser,
+    dir: PathBuf,
+    /// The exit code once it has finished; `None` inside if it was killed.
+    exit: Option<Option<i32>>,
+}
+
+impl TaskRun {
+    pub fn new(id: JobId, task: &TaskConfig, root: &Path) -> Self {
+        TaskRun {
+            id,
+            name: task.name.clone(),
+            lines: VecDeque::new(),
+            problems: Vec::new(),
+            parser: ProblemParser::default(),
+            dir: task.dir(root),
+            exit: None,
+        }
+    }
+
+    pub fn push_line(&mut self, line: String) {
+        if let Some(problem) = self.parser.parse(&line, &self.dir) {
+            self.problems.push(problem);
+        }
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    pub fn finish(&mut self, code: Option<i32>) {
+        self.exit = Some(code);
+    }
+
+    pub fn lines(&self) -> &VecDeque<String> {
+        &self.lines
+    }
+
+    pub fn problems(&self) -> &[Entry] {
+        &self.problems
+    }
+
+    pub fn running(&self) -> bool {
+        self.exit.is_none()
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.exit == Some(Some(0))
+    }
+
+    /// How the run is going, e.g. `running` or `exit 1`.
+    pub fn state(&self) -> String {
+        match self.exit {
+            None => "running".to_string(),
+            Some(Some(code)) => format!("exit {}", code),
+            Some(None) => "killed".to_string(),
+        }
+    }
+}
+
+/// Picks out the places compiler and test output points at: `path:line:col:`
+/// prefixes, rustc's `--> path:line:col` under its message, and Python's
+/// `File "path", line N`. Only paths of existing files count.
+#[derive(Debug, Default)]
+pub struct ProblemParser {
+    /// rustc's last `error: ...` line, waiting for the location below it.
+    message: Option<String>,
+}
+
+impl ProblemParser {
+    pub fn parse(&mut self, line: &str, dir: &Path) -> Option<Entry> {
+        let trimmed = line.trim();
+        if let Some(location) = trimmed.strip_prefix("--> ") {
+            let (path, position) = parse_location(location)?;
+            let message = self.message.take().unwrap_or_default();
+            return entry(dir, path, position, format!("{}: {}", location, message));
+        }
+        if let Some(rest) = trimmed.strip_prefix("File \"") {
+            let (path, rest) = rest.split_once("\", line ")?;
+            let digits = rest.split(|c: char| !c.is_ascii_digit()).next()?;
+            let line = digits.parse::<usize>().ok()?;
+            let position = Position::new(line.saturating_sub(1), 0);
+            return entry(dir, path, position, trimmed.to_string());
+        }
+        if let Some((path, position)) = parse_location(trimmed) {
+            return entry(dir, path, position, trimmed.to_string());
+        }
+        if ["error", "warning"].iter().any(|p| trimmed.starts_with(p)) && trimmed.contains(':') {
+            self.message = Some(trimmed.to_string());
+        }
+        None
+    }
+}
+
+/// Splits `path:line[:col]...` into the path and a zero-based position.
+fn parse_location(text: &str) -> Option<(&str, Position)> {
+    let mut parts = text.splitn(4, ':');
+    let path = parts.next().filter(|p| !p.is_empty() && !p.contains(' '))?;
+    let line: usize = parts.next()?.parse().ok()?;
+    let col: usize = parts
+        .next()
+        .and_then(|c| c.trim_end().parse().ok())
+        .unwrap_or(1);
+    Some((
+        path,
+        Position::new(line.checked_sub(1)?, col.saturating_sub(1)),
+    ))
+}
+
+fn entry(dir: &Path, path: &str, position: Position, label: String) -> Option<Entry> {
+    let path = dir.join(path);
+    path.is_file().then_some(Entry {
+        path,
+        position,
+        label,
+    })
+}
+
+#[test]
+fn test_parse_problems() {
+    let dir = Path::new(".");
+    let mut parser = ProblemParser::default();
+    let mut parse = |line: &str| parser.parse(line, dir);
+    assert_eq!(parse("error[E0308]: mismatched types"), None);
+    let rust = parse("  --> src/app.rs:12:5").unwrap();
+    assert_eq!(rust.path, dir.join("src/app.rs"));
+    assert_eq!(rust.position, Position::new(11, 4));
+    assert_eq!(
+        rust.label,
+        "src/app.rs:12:5: error[E0308]: mismatched types"
+    );
+
+    let gcc = parse("src/ui.rs:3:1: warning: unused").unwrap();
+    assert_eq!(gcc.position, Position::new(2, 0));
+    assert_eq!(gcc.label, "src/ui.rs:3:1: warning: unused");
+    let python = parse("  File \"src/lib.rs\", line 7, in <module>").unwrap();
+    assert_eq!(python.position, Position::new(6, 0));
+
+    assert_eq!(parse("src/missing.rs:1:1: error"), None);
+    assert_eq!(parse("12:30:45 started"), None);
+    assert_eq!(parse("see https://example.com:80/x"), None);
+}
+
+#[test]
+fn test_make_targets() {
+    let makefile = "\
+CC := cc
+FLAGS = -O2
+.PHONY: all test
+all: build
+build test: src/*.c
+\t$(CC) -o x $^
+%.o: %.c
+# lint: not a target
+";
+    assert_eq!(make_targets(makefile), ["all", "build", "test"]);
+}
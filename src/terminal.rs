@@ -0,0 +1,237 @@
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, IsTerminal, Stdout, Write},
+    panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Result;
+use crossterm::{
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use ratatui::{backend::CrosstermBackend, Terminal};
+#[cfg(unix)]
+use signal_hook::consts::{SIGCONT, SIGTSTP};
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+pub type Term = Terminal<CrosstermBackend<Output>>;
+
+/// The controlling terminal, for drawing on when stdout is taken.
+#[cfg(unix)]
+const TTY: &str = "/dev/tty";
+#[cfg(not(unix))]
+const TTY: &str = "CONOUT$";
+
+/// Whether the TUI draws on [`TTY`] rather than stdout.
+static ON_TTY: AtomicBool = AtomicBool::new(false);
+
+/// Where the TUI is drawn.
+pub enum Output {
+    Stdout(Stdout),
+    Tty(File),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(out) => out.write(buf),
+            Output::Tty(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(out) => out.flush(),
+            Output::Tty(out) => out.flush(),
+        }
+    }
+}
+
+/// Draws the TUI on the controlling terminal from now on, leaving stdout
+/// free for `--stdout` to print to.
+pub fn draw_on_tty() {
+    ON_TTY.store(true, Ordering::Relaxed);
+}
+
+fn output() -> io::Result<Output> {
+    match ON_TTY.load(Ordering::Relaxed) {
+        true => Ok(Output::Tty(OpenOptions::new().write(true).open(TTY)?)),
+        false => Ok(Output::Stdout(io::stdout())),
+    }
+}
+
+/// Why the TUI can't run here, if it can't: output is piped or redirected,
+/// input isn't a terminal, or the terminal can't handle escape codes. With
+/// `stdin_taken`, keys are read from the controlling terminal instead.
+pub fn unsupported(stdin_taken: bool) -> Option<&'static str> {
+    let drawable = match ON_TTY.load(Ordering::Relaxed) {
+        true => OpenOptions::new()
+            .write(true)
+            .open(TTY)
+            .is_ok_and(|tty| tty.is_terminal()),
+        false => io::stdout().is_terminal(),
+    };
+    if !drawable {
+        Some("output is not a terminal")
+    } else if !stdin_taken && !io::stdin().is_terminal() {
+        Some("input is not a terminal")
+    } else if env::var("TERM").is_ok_and(|t| t == "dumb") {
+        Some("TERM is dumb")
+    } else {
+        None
+    }
+}
+
+/// Whether [`setup`] turned on the kitty keyboard protocol, which
+/// [`restore`] then turns off.
+static ENHANCED_KEYS: AtomicBool = AtomicBool::new(false);
+
+/// Whether wyv takes the mouse, leaving the terminal's own selection and
+/// middle-click paste without it.
+static MOUSE: AtomicBool = AtomicBool::new(true);
+
+/// Switches the terminal into raw mode on the alternate screen. With
+/// `enhanced_keys`, terminals that speak the kitty keyboard protocol are
+/// asked to use it: keys such as `ctrl-i` and `tab` then arrive apart, with
+/// releases and super, hyper and meta. Other terminals carry on as before.
+/// Without `mouse`, mouse capture is never turned on.
+pub fn setup(enhanced_keys: bool, mouse: bool) -> Result<Term> {
+    MOUSE.store(mouse, Ordering::Relaxed);
+    enter(enhanced_keys)?;
+    Ok(Terminal::new(CrosstermBackend::new(output()?))?)
+}
+
+fn enter(enhanced_keys: bool) -> Result<()> {
+    enable_raw_mode()?;
+    let mut out = output()?;
+    execute!(out, EnterAlternateScreen)?;
+    if MOUSE.load(Ordering::Relaxed) {
+        execute!(out, EnableMouseCapture)?;
+    }
+    // Asking the terminal what it supports goes through stdout, so isn't
+    // done while that's taken.
+    let ask = enhanced_keys && !ON_TTY.load(Ordering::Relaxed);
+    if ask && supports_keyboard_enhancement().unwrap_or(false) {
+        let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+            | KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+        execute!(out, PushKeyboardEnhancementFlags(flags))?;
+        ENHANCED_KEYS.store(true, Ordering::Relaxed);
+        tracing::debug!("kitty keyboard protocol on");
+    }
+    Ok(())
+}
+
+/// Undoes [`setup`]. Safe to call more than once, and from a panic hook.
+pub fn restore() -> Result<()> {
+    if ENHANCED_KEYS.swap(false, Ordering::Relaxed) {
+        execute!(output()?, PopKeyboardEnhancementFlags)?;
+    }
+    disable_raw_mode()?;
+    execute!(
+        output()?,
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        crossterm::cursor::Show
+    )?;
+    Ok(())
+}
+
+/// Takes the mouse or gives it back to the terminal, if that changed.
+pub fn capture_mouse(mouse: bool) -> Result<()> {
+    match (MOUSE.swap(mouse, Ordering::Relaxed), mouse) {
+        (false, true) => execute!(output()?, EnableMouseCapture)?,
+        (true, false) => execute!(output()?, DisableMouseCapture)?,
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Restores the terminal before the default hook prints the panic message,
+/// so the message lands in the user's normal screen rather than the
+/// alternate one.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        default_hook(info);
+    }));
+}
+
+/// Returns a flag that is raised when SIGINT or SIGTERM arrives. The main loop
+/// polls it and shuts down through the normal restore path.
+pub fn termination_flag() -> Result<Arc<AtomicBool>> {
+    let flag = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(&flag))?;
+    }
+    Ok(flag)
+}
+
+pub fn terminated(flag: &AtomicBool) -> bool {
+    flag.load(Ordering::Relaxed)
+}
+
+/// Returns flags raised when SIGTSTP and SIGCONT arrive. With the terminal
+/// raw, ctrl-z comes in as a key rather than SIGTSTP, which only arrives
+/// when sent, e.g. by `kill -TSTP`; either way the main loop calls
+/// [`suspend`]. SIGCONT after a stop wyv couldn't catch, SIGSTOP, asks for
+/// a full redraw.
+#[cfg(unix)]
+pub fn job_control_flags() -> Result<(Arc<AtomicBool>, Arc<AtomicBool>)> {
+    let (stop, cont) = Default::default();
+    signal_hook::flag::register(SIGTSTP, Arc::clone(&stop))?;
+    signal_hook::flag::register(SIGCONT, Arc::clone(&cont))?;
+    Ok((stop, cont))
+}
+
+#[cfg(not(unix))]
+pub fn job_control_flags() -> Result<(Arc<AtomicBool>, Arc<AtomicBool>)> {
+    Ok(Default::default())
+}
+
+/// Lowers `flag`, saying whether it was raised.
+pub fn take(flag: &AtomicBool) -> bool {
+    flag.swap(false, Ordering::Relaxed)
+}
+
+/// Hands the terminal back to the shell and stops the process. Once
+/// continued, e.g. by `fg`, takes the terminal again and clears it so the
+/// next frame is drawn in full.
+#[cfg(unix)]
+pub fn suspend(terminal: &mut Term) -> Result<()> {
+    tracing::debug!("suspended");
+    outside(terminal, || {
+        signal_hook::low_level::emulate_default_handler(SIGTSTP)
+    })??;
+    tracing::debug!("continued");
+    Ok(())
+}
+
+/// Runs `f` with the terminal handed back to the shell, e.g. for a password
+/// prompt, then takes it again and clears it so the next frame is drawn in
+/// full.
+pub fn outside<T>(terminal: &mut Term, f: impl FnOnce() -> T) -> Result<T> {
+    let enhanced_keys = ENHANCED_KEYS.load(Ordering::Relaxed);
+    restore()?;
+    let result = f();
+    enter(enhanced_keys)?;
+    terminal.clear()?;
+    Ok(result)
+}
+
+#[cfg(not(unix))]
+pub fn suspend(_terminal: &mut Term) -> Result<()> {
+    anyhow::bail!("suspending is only supported on unix")
+}
@@ -0,0 +1,194 @@
+//! `wyv --tutor`: a lesson file to practise on, with a task at the end of
+//! each lesson that wyv watches for. Whether the tutor has been suggested
+//! or finished is kept in the state directory, so that it's only offered
+//! once.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{App, Mode};
+
+/// What the copy of the lessons is called.
+pub const LESSONS_FILE: &str = "tutor.txt";
+/// Where [`Progress`] is kept, beside the lessons.
+pub const PROGRESS_FILE: &str = "tutor.json";
+
+/// The lessons, copied out for each run of the tutor.
+pub const LESSONS: &str = include_str!("../assets/tutor/tutor.txt");
+
+/// The line lesson 3 asks to have fixed, as it reads once it is.
+const FIXED: &str = "---> The cow jumped over the moon.";
+
+/// What each lesson asks for, in order, with its title.
+const TASKS: [(&str, Task); 5] = [
+    ("the tree", Task::MoveInTree),
+    ("insert mode", Task::Insert),
+    ("editing", Task::Fix),
+    ("saving", Task::Save),
+    ("opening files", Task::OpenAndReturn),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Task {
+    MoveInTree,
+    Insert,
+    Fix,
+    Save,
+    OpenAndReturn,
+}
+
+/// A run of the tutor, on the copy of the lessons at `path`.
+#[derive(Debug)]
+pub struct Tutor {
+    path: PathBuf,
+    lesson: usize,
+    /// Where the tree's selection was when the tutor started.
+    tree_start: usize,
+    /// Whether another file has been opened since lesson 5 started.
+    left: bool,
+    store: TutorStore,
+}
+
+impl Tutor {
+    pub fn new(path: &Path, tree_start: usize, store: TutorStore) -> Self {
+        Tutor {
+            path: path.to_owned(),
+            lesson: 0,
+            tree_start,
+            left: false,
+            store,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.lesson == TASKS.len()
+    }
+
+    /// Moves on past the lessons whose tasks `app` shows done, returning
+    /// what to tell the user if any were.
+    pub fn check(&mut self, app: &App) -> Option<String> {
+        let start = self.lesson;
+        while let Some(&(_, task)) = TASKS.get(self.lesson) {
+            if !self.done(task, app) {
+                break;
+            }
+            self.lesson += 1;
+        }
+        if self.lesson == start {
+            return None;
+        }
+        if let Some((title, _)) = TASKS.get(self.lesson) {
+            return Some(format!(
+                "Lesson {} done; next, lesson {}: {}",
+                self.lesson,
+                self.lesson + 1,
+                title
+            ));
+        }
+        if let Err(e) = self.store.finish() {
+            tracing::warn!(error = %e, "could not note the tutor finished");
+        }
+        Some("That's the tutor done. :q quits".to_string())
+    }
+
+    fn done(&mut self, task: Task, app: &App) -> bool {
+        let tutor = app
+            .buffers
+            .iter()
+            .find(|b| b.path() == Some(self.path.as_path()));
+        let in_front = app.buffer().and_then(|b| b.path()) == Some(self.path.as_path());
+        let fixed = tutor.is_some_and(|b| b.text().lines().any(|l| l.to_string().trim() == FIXED));
+        match task {
+            Task::MoveInTree => app.tree.selected() != self.tree_start,
+            Task::Insert => in_front && app.mode == Mode::Insert,
+            Task::Fix => fixed,
+            Task::Save => fixed && tutor.is_some_and(|b| !b.is_dirty()),
+            Task::OpenAndReturn => {
+                self.left |= app.buffer().is_some_and(|b| b.path().is_some()) && !in_front;
+                self.left && in_front
+            }
+        }
+    }
+}
+
+/// Whether the tutor has been offered and whether it has been finished.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub suggested: bool,
+    pub finished: bool,
+}
+
+/// The file the tutor's [`Progress`] is kept in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TutorStore {
+    path: PathBuf,
+}
+
+impl TutorStore {
+    pub fn new(path: PathBuf) -> Self {
+        TutorStore { path }
+    }
+
+    pub fn load(&self) -> Progress {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Whether to offer the tutor: only the first time this is asked, and
+    /// not once it's been finished.
+    pub fn suggest(&self) -> Result<bool> {
+        let mut progress = self.load();
+        if progress.suggested || progress.finished {
+            return Ok(false);
+        }
+        progress.suggested = true;
+        self.save(&progress)?;
+        Ok(true)
+    }
+
+    pub fn finish(&self) -> Result<()> {
+        let progress = Progress {
+            finished: true,
+            ..self.load()
+        };
+        self.save(&progress)
+    }
+
+    fn save(&self, progress: &Progress) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(progress)?)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_tutor_is_suggested_once() {
+    let dir = std::env::temp_dir().join(format!("wyv-tutor-test-{}", std::process::id()));
+    let store = TutorStore::new(dir.join(PROGRESS_FILE));
+    assert!(store.suggest().unwrap());
+    assert!(!store.suggest().unwrap());
+    store.finish().unwrap();
+    assert_eq!(
+        store.load(),
+        Progress {
+            suggested: true,
+            finished: true
+        }
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    // Finishing it without being asked counts too.
+    store.finish().unwrap();
+    assert!(!store.suggest().unwrap());
+    fs::remove_dir_all(dir).unwrap();
+    assert!(LESSONS.contains(&FIXED.replace("over", "ovr")));
+}
@@ -1,60 +1,1421 @@
-use std::{io::Stdout, path::Path};
-
-use anyhow::Result;
-use tui::{
-    backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Style},
-    text::{Span, Spans},
-    widgets::{List, ListItem, Paragraph, Tabs, Wrap, Block, Borders},
-    Terminal,
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ratatui::{
+    backend::Backend,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, LineGauge, List, ListItem, ListState, Paragraph, Tabs, Wrap},
+    Frame, Terminal,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    app::{orphan_warning, App, Mode, Panel},
+    error::{Error, Result},
+    follow::Level,
+    git::BlameLine,
+    layers::Layer,
+    widgets::{
+        data_tree::DataTree,
+        diff::DiffView,
+        editor::EditorView,
+        file_tree::FileTreeView,
+        git_status::GitStatusView,
+        hex::{HexEditor, HexView},
+        locations::LocationsView,
+        merge::MergeView,
+        popup_menu::{self, PopupMenu},
+        preview::PreviewView,
+        progress::{Gauge, Spinner},
+        search::{ReplaceView, SearchView},
+        select_list::{SelectList, SelectListView},
+        symbol_picker::SymbolPickerView,
+        table::{Table, Width},
+        terminal::TerminalView,
+        text_input::TextInputView,
+        tree_view::TreeView,
+    },
 };
 
-use crate::widgets::file_tree::{FileTree, FileTreeState};
+/// How long a background job runs before the status bar shows it; most
+/// are done well before.
+const SHOW_JOBS_AFTER: Duration = Duration::from_millis(500);
+
+pub fn draw<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
+    terminal
+        .draw(|f| draw_layers(f, app))
+        .map_err(Error::Draw)?;
+
+    Ok(())
+}
+
+/// Each layer over the ones before it, noting where the mouse can reach
+/// them. The last to put the cursor somewhere decides where it is.
+fn draw_layers(f: &mut Frame, app: &mut App) {
+    app.layers.clear();
+    app.layers.push(Layer::Base, f.area());
+    let (area, cursor) = draw_base(f, app);
+    for layer in Layer::ALL {
+        let drawn = match layer {
+            Layer::Base => None,
+            Layer::Float => cursor.and_then(|at| draw_floats(f, app, at, area)),
+            Layer::Dialog => draw_dialogs(f, app, area),
+            Layer::Toast => {
+                if app.show_perf {
+                    draw_perf(f, app, area);
+                }
+                None
+            }
+        };
+        if let Some(drawn) = drawn {
+            app.layers.push(layer, drawn);
+        }
+    }
+}
+
+/// The panes, laid out afresh from the screen's size, and the bar. Returns
+/// the area over the panes that floats and dialogs go in, and where the
+/// editor's cursor is, if it's in view.
+fn draw_base(f: &mut Frame, app: &mut App) -> (Rect, Option<(u16, u16)>) {
+    let theme = app.config.theme.clone();
+    let plain = app.config.editor.screen_reader;
+    // Laid out afresh from the screen's size each frame, so a resize
+    // only takes drawing again. The bar gets the last row, if any, and
+    // for screen readers the announcements the one above it.
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(plain as u16),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(f.area());
+    let (cut_size, bar_area) = (rows[0], rows[2]);
+    if plain {
+        let announcement = app.announcement.as_deref().unwrap_or_default();
+        f.render_widget(Paragraph::new(announcement).style(theme.status()), rows[1]);
+    }
 
-pub fn draw(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
-    terminal.draw(|f| {
-        let mut cut_size = f.size();
-        cut_size.height -= 1;
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(15), Constraint::Percentage(85)].as_ref())
+        .split(cut_size);
 
-        let chunks = Layout::default()
+    let panel_height = match app.panel {
+        Some(_) => chunks[1].height / 3,
+        None => 0,
+    };
+    let main = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(1),
+                Constraint::Min(0),
+                Constraint::Length(panel_height),
+            ]
+            .as_ref(),
+        )
+        .split(chunks[1]);
+    if let Some(buffer) = app.buffer_mut() {
+        buffer.scroll_to_cursor(main[1].height as usize);
+    }
+    app.refresh_outline();
+    app.refresh_preview();
+    app.fetch_metadata(chunks[0].height as usize);
+
+    let titles: Vec<Line> = app.buffers.iter().map(|b| Line::from(b.title())).collect();
+
+    let file_tree = FileTreeView::new(&app.tree)
+        .style(theme.base())
+        .highlight_style(theme.selection())
+        .mark_style(theme.accent())
+        .metadata(&app.metadata)
+        .git(&app.badges)
+        .plain(plain);
+    // Detail columns age, so with them the tree is drawn again as often
+    // as their metadata is looked at again, and once it's been fetched.
+    let ages = match app.tree.options().details.is_empty() {
+        true => None,
+        false => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|t| t.as_secs() / 2),
+    };
+    let key = (
+        app.tree.root(),
+        app.tree.generation(),
+        app.badges.generation(),
+        app.metadata.generation(),
+        [theme.base(), theme.selection(), theme.accent()],
+        ages,
+        plain,
+    );
+    app.tree_pane
+        .render(key, file_tree, chunks[0], f.buffer_mut());
+
+    let tabs = Tabs::new(titles)
+        .select(app.active.unwrap_or_default())
+        .style(theme.base())
+        .highlight_style(theme.accent())
+        .divider(match plain {
+            true => "|",
+            false => ratatui::symbols::line::VERTICAL,
+        });
+    f.render_widget(tabs, main[0]);
+
+    let mut editor_area = main[1];
+    let mut terminal_area = None;
+    if app.panel == Some(Panel::Terminal) {
+        terminal_area = Some((main[2], Borders::TOP));
+    }
+    if app.terminal_split && app.pty.is_some() {
+        let columns = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(15), Constraint::Percentage(85)].as_ref())
-            .split(cut_size);
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(main[1]);
+        editor_area = columns[0];
+        terminal_area = Some((columns[1], Borders::TOP | Borders::LEFT));
+    }
+    // The shell learns the pane's size as it is laid out.
+    if let (Some((area, borders)), Some(pty)) = (terminal_area, &mut app.pty) {
+        let inner = Block::default().borders(borders).inner(area);
+        if let Err(e) = pty.resize((inner.height, inner.width)) {
+            tracing::warn!(error = %e, "could not resize the terminal");
+        }
+    }
 
-        let titles = ["Tab1", "Tab2", "Tab3", "Tab4"]
+    if let (Mode::Blame, Some(blame)) = (app.mode, app.active_blame()) {
+        let width = blame
             .iter()
-            .cloned()
-            .map(Spans::from)
-            .collect();
-
-        let file_tree = FileTree::new(&Path::new(".")).unwrap();
-        f.render_stateful_widget(file_tree, chunks[0], &mut FileTreeState::default());
-
-        let tabs = Tabs::new(titles)
-            .style(Style::default().fg(Color::White))
-            .highlight_style(Style::default().fg(Color::LightBlue))
-            .divider(tui::symbols::line::VERTICAL);
-        f.render_widget(tabs, chunks[1]);
-
-        let text = vec![Spans::from(Span::styled(
-            "Second line",
-            Style::default().fg(Color::Red),
-        ))];
-        let bar = Paragraph::new(text)
-            .style(Style::default().fg(Color::White).bg(Color::DarkGray))
-            .alignment(Alignment::Center)
-            .wrap(Wrap { trim: true });
-        f.render_widget(
-            bar,
-            tui::layout::Rect {
-                x: cut_size.x,
-                y: cut_size.height,
-                width: cut_size.width,
-                height: 1,
-            },
+            .map(|l| l.describe().width())
+            .max()
+            .unwrap_or(0) as u16;
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(width + 1), Constraint::Min(0)].as_ref())
+            .split(editor_area);
+        draw_blame(f, app, blame, columns[0]);
+        editor_area = columns[1];
+    }
+
+    if app.outline.is_some() {
+        let width = (editor_area.width / 4).max(24).min(editor_area.width / 2);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(width)].as_ref())
+            .split(editor_area);
+        draw_outline(f, app, columns[1]);
+        editor_area = columns[0];
+    }
+
+    if app.preview.is_some() {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(editor_area);
+        draw_preview(f, app, columns[1]);
+        editor_area = columns[0];
+    }
+
+    if let (Mode::Hex, Some(hex)) = (app.mode, &mut app.hex) {
+        hex.scroll_to_cursor(editor_area.height.saturating_sub(2) as usize);
+    }
+    let scroll = app.buffer().map_or(0, |b| b.view.offset());
+    let misspellings = app.misspellings(scroll..scroll + editor_area.height as usize);
+    let levels = app.log_levels(scroll..scroll + editor_area.height as usize);
+    let mut cursor = None;
+    if let (Mode::Merge, Some((_, merge))) = (app.mode, &app.merge) {
+        let view = MergeView::new(merge)
+            .style(theme.base())
+            .border_style(theme.accent())
+            .highlight_style(theme.selection());
+        f.render_widget(view, editor_area);
+    } else if let (Mode::Structure, Some(tree)) = (app.mode, &app.structure) {
+        draw_structure(f, app, tree, editor_area);
+    } else if let (Mode::Hex, Some(hex)) = (app.mode, &app.hex) {
+        draw_hex(f, app, hex, editor_area);
+    } else if let (Mode::Diff, Some(diff)) = (app.mode, &app.diff) {
+        let mut view = DiffView::new(diff)
+            .style(theme.base())
+            .border_style(theme.accent())
+            .gutter_style(theme.gutter())
+            .added_style(theme.added())
+            .removed_style(theme.removed());
+        if app.review.as_ref().is_some_and(|r| r.open.is_some()) {
+            view = view.highlight_style(theme.selection());
+        }
+        f.render_widget(view, editor_area);
+    } else if let Some(buffer) = app.buffer() {
+        let mut editor = EditorView::new(buffer)
+            .style(theme.base())
+            .gutter_style(theme.gutter())
+            .annotation_style(theme.gutter())
+            .line_numbers(app.config.editor.line_numbers);
+        let line = buffer.cursor().line;
+        let blame = app.active_blame().and_then(|b| b.get(line));
+        if let (true, Some(blame)) = (app.blame_inline, blame) {
+            editor = editor.annotate(line, format!("{} {}", blame.describe(), blame.summary));
+        }
+        editor = editor.mark_style(theme.misspelled());
+        for m in misspellings {
+            editor = editor.mark(m.line, m.start, m.end);
+        }
+        for (line, level) in levels {
+            let style = match level {
+                Level::Error => theme.log_error(),
+                Level::Warning => theme.log_warning(),
+            };
+            editor = editor.line_style(line, style);
+        }
+        let mut diagnostics: Vec<_> = match buffer.path() {
+            Some(path) => app.diagnostics_for(path).collect(),
+            None => Vec::new(),
+        };
+        // The worst problem on a line gives it its sign.
+        diagnostics.sort_by_key(|d| d.severity);
+        for diagnostic in diagnostics {
+            let sign = diagnostic.severity_name().chars().next().unwrap_or('E');
+            let style = theme.diagnostic(diagnostic.severity);
+            editor = editor.sign(diagnostic.start.0, sign.to_ascii_uppercase(), style);
+        }
+        cursor = editor.cursor_position(editor_area);
+        f.render_widget(editor, editor_area);
+        if let Some(at) = cursor {
+            f.set_cursor_position(at);
+        }
+    }
+
+    if let (Some((area, borders)), true) = (terminal_area, app.terminal_split) {
+        draw_terminal(f, app, area, borders);
+    }
+    if let Some(panel) = app.panel {
+        draw_panel(f, app, panel, main[2]);
+    }
+
+    let bar = match app.mode {
+        Mode::Command => {
+            let prompt = match &app.history_search {
+                Some(search) => {
+                    let found = search.found.and_then(|i| app.command_history.get(i));
+                    let failed = match found.is_none() && !search.query.is_empty() {
+                        true => "failed ",
+                        false => "",
+                    };
+                    let prompt = format!(
+                        "({}reverse-i-search)`{}': {}",
+                        failed,
+                        search.query,
+                        found.unwrap_or_default()
+                    );
+                    let x = bar_area.x + prompt.width() as u16;
+                    let x = x.min(bar_area.right().saturating_sub(1));
+                    f.set_cursor_position((x, bar_area.y));
+                    prompt
+                }
+                None => {
+                    let input = TextInputView::new(&app.command_line)
+                        .prompt(":")
+                        .style(theme.status());
+                    f.set_cursor_position(input.cursor(bar_area));
+                    f.render_widget(input, bar_area);
+                    // Drawn already; the bar below only styles the row.
+                    String::new()
+                }
+            };
+            // Completion candidates, shown beside what's being typed.
+            if let Some(status) = &app.status {
+                let hint = Paragraph::new(status.as_str())
+                    .style(theme.status())
+                    .alignment(Alignment::Right);
+                f.render_widget(hint, bar_area);
+            }
+            Paragraph::new(prompt).style(theme.status())
+        }
+        Mode::Insert => {
+            let text = app.status.as_deref().unwrap_or("-- INSERT --");
+            Paragraph::new(text).style(theme.status())
+        }
+        Mode::ConfirmQuit => Paragraph::new("Unsaved changes: [s]ave all, [d]iscard, [c]ancel")
+            .style(theme.status())
+            .alignment(Alignment::Center),
+        Mode::ConfirmRestore => {
+            Paragraph::new("wyv didn't exit cleanly last time: [r]estore, [d]iscard")
+                .style(theme.status())
+                .alignment(Alignment::Center)
+        }
+        Mode::ConfirmTrust => {
+            Paragraph::new("The project config wants to run programs: [t]rust, [i]gnore")
+                .style(theme.status())
+                .alignment(Alignment::Center)
+        }
+        Mode::Blame => Paragraph::new("Blame: j/k move, enter shows the commit, esc closes")
+            .style(theme.status())
+            .alignment(Alignment::Center),
+        Mode::GitStatus => Paragraph::new(
+            "Git: [s]tage, [u]nstage, enter shows hunks, [c]ommit, [r]efresh, esc closes",
+        )
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::Merge => Paragraph::new(
+            "Merge: j/k pick a conflict, take [o]urs, [t]heirs or [b]oth, [s]ave, esc closes",
+        )
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::Diff if app.review.as_ref().is_some_and(|r| r.open.is_some()) => {
+            let text = app.status.as_deref().unwrap_or(
+                "Review: j/k scroll, [c] notes the top line, [n]ext or [p]revious change, esc goes back",
+            );
+            Paragraph::new(text)
+                .style(theme.status())
+                .alignment(Alignment::Center)
+        }
+        Mode::Diff => Paragraph::new(
+            "Diff: j/k scroll, [n]ext or [p]revious change, [u]nified or side by side, esc closes",
+        )
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::Review => {
+            let text = app.status.as_deref().unwrap_or(
+                "Review: j/k move, enter shows the changes, [w]rites the notes to review.txt, esc closes",
+            );
+            Paragraph::new(text)
+                .style(theme.status())
+                .alignment(Alignment::Center)
+        }
+        Mode::Branches => Paragraph::new(
+            "Branches: enter checks out, / filters, [n]ew from current, [d]elete, esc closes",
+        )
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::Recent => Paragraph::new("Recent files: j/k move, / filters, enter opens, esc closes")
+            .style(theme.status())
+            .alignment(Alignment::Center),
+        Mode::Projects => Paragraph::new("Projects: j/k move, / filters, enter switches, esc closes")
+            .style(theme.status())
+            .alignment(Alignment::Center),
+        Mode::Locations => Paragraph::new("Locations: j/k move, h/l scroll, enter jumps, esc closes")
+            .style(theme.status())
+            .alignment(Alignment::Center),
+        Mode::Rename => Paragraph::new("Rename: [a]pply the edits, [c]ancel")
+            .style(theme.status())
+            .alignment(Alignment::Center),
+        Mode::Terminal => {
+            let text = app.status.as_deref().unwrap_or(
+                "Terminal: ctrl-\\ returns to the editor, shift-pageup scrolls back, ctrl-t hides",
+            );
+            Paragraph::new(text)
+                .style(theme.status())
+                .alignment(Alignment::Center)
+        }
+        Mode::TerminalCopy => Paragraph::new(
+            "Copy: j/k move, [v] starts a selection, [y]anks it, [p]astes, esc leaves",
         )
-    })?;
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::Search => {
+            let replacing = app.search.as_ref().is_some_and(|s| s.replacement.is_some());
+            let text = match (replacing, app.status.as_deref()) {
+                (_, Some(status)) => status,
+                (true, None) => "Replace: type the replacement ($1 for groups), enter previews it, esc stops",
+                (false, None) => "Search: type a pattern, enter jumps, ctrl-q keeps the results, ctrl-r replaces, esc closes",
+            };
+            Paragraph::new(text)
+                .style(theme.status())
+                .alignment(Alignment::Center)
+        }
+        Mode::Replace => Paragraph::new(
+            "Replace: j/k move, space leaves a change out, [t]oggles all, [a]pplies, esc goes back",
+        )
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::Symbols => Paragraph::new(
+            "Symbols: type to narrow, up/down move, enter jumps, esc closes",
+        )
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::Structure => {
+            let path = app
+                .structure
+                .as_ref()
+                .and_then(|t| t.selected_node())
+                .map_or("", |n| n.path.as_str());
+            let hints = "j/k move, l/h expand/collapse, enter toggles or jumps, g jumps, q closes";
+            Paragraph::new(format!("{}  {}", path, hints)).style(theme.status())
+        }
+        Mode::Follow => {
+            let title = app.buffer().map(|b| b.title()).unwrap_or_default();
+            let text = match (&app.status, app.follow.as_ref().is_some_and(|f| f.paused)) {
+                (Some(status), _) => status.clone(),
+                (None, false) => format!(
+                    "Following {}: space pauses, j/k scroll, q stops",
+                    title
+                ),
+                (None, true) => format!(
+                    "Paused on {}: space or G follows again, j/k scroll, q stops",
+                    title
+                ),
+            };
+            Paragraph::new(text)
+                .style(theme.status())
+                .alignment(Alignment::Center)
+        }
+        Mode::Hex => match app.hex.as_ref().and_then(|h| h.query.as_ref()) {
+            Some(query) => {
+                let input = TextInputView::new(query).prompt("/").style(theme.status());
+                f.set_cursor_position(input.cursor(bar_area));
+                f.render_widget(input, bar_area);
+                Paragraph::default().style(theme.status())
+            }
+            None if app.hex.as_ref().is_some_and(|h| h.editing) => {
+                Paragraph::new("-- HEX EDIT -- type hex digits over the bytes, esc stops")
+                    .style(theme.status())
+            }
+            None => Paragraph::new(
+                "Hex: hjkl move, / searches (de ad or \"text\"), n next, [i] edits, [w]rites, q closes",
+            )
+            .style(theme.status())
+            .alignment(Alignment::Center),
+        },
+        Mode::Spell => Paragraph::new(
+            "Spelling: j/k move, enter replaces, [a]dds to the project's words, esc closes",
+        )
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::Outline => Paragraph::new(
+            "Outline: j/k move, enter jumps, esc returns to the editor, [q] closes",
+        )
+        .style(theme.status())
+        .alignment(Alignment::Center),
+        Mode::GitLog => {
+            let text = match app.git_log.as_ref().is_some_and(|l| l.filtering) {
+                true => "Filter: type to narrow, enter keeps it, esc clears it",
+                false => "Git log: j/k move, / filters, enter shows the commit, esc closes",
+            };
+            Paragraph::new(text)
+                .style(theme.status())
+                .alignment(Alignment::Center)
+        }
+        Mode::Normal => {
+            // Until something else is said, an orphaned buffer's warning
+            // stays up while it's the one in view.
+            let orphaned = app.buffer().filter(|b| b.is_orphaned());
+            let text = match (&app.status, orphaned) {
+                (Some(status), _) => Line::from(Span::raw(status.as_str())),
+                (None, Some(buffer)) => {
+                    Line::from(Span::styled(orphan_warning(buffer), theme.log_warning()))
+                }
+                (None, None) => Line::default(),
+            };
+            Paragraph::new(text)
+                .style(theme.status())
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+        }
+    };
+    f.render_widget(bar, bar_area);
 
-    Ok(())
+    let loading = app
+        .buffer()
+        .and_then(|b| Some((b.title(), b.loading_progress()?)));
+    let quiet = app.mode == Mode::Normal && app.status.is_none() && loading.is_none();
+    if let (Mode::Normal, None, Some((title, (read, size)))) = (app.mode, &app.status, loading) {
+        let ratio = (read as f64 / size.max(1) as f64).min(1.0);
+        let gauge = LineGauge::default()
+            .ratio(ratio)
+            .label(format!(
+                "Loading {} {:.0}% (large file: no language server, blame or spelling) ",
+                title,
+                ratio * 100.0
+            ))
+            .style(theme.status())
+            .filled_style(theme.accent());
+        f.render_widget(gauge, bar_area);
+    }
+
+    if let (Mode::Normal, Some(ssh)) = (app.mode, &app.ssh) {
+        let left = Paragraph::new(ssh.indicator()).style(theme.status());
+        f.render_widget(left, bar_area);
+    }
+    let statusline = app.scripts.as_ref().and_then(|s| s.statusline());
+    let mut jobs_area = bar_area;
+    if let (Mode::Normal, Some(text)) = (app.mode, statusline) {
+        jobs_area.width = jobs_area.width.saturating_sub(text.width() as u16 + 1);
+        let right = Paragraph::new(text)
+            .style(theme.status())
+            .alignment(Alignment::Right);
+        f.render_widget(right, bar_area);
+    }
+    if quiet {
+        draw_jobs(f, app, jobs_area);
+    }
+
+    (cut_size, cursor)
+}
+
+/// The windows by the editor's cursor at `at`: signature help and the
+/// completion menu while typing, the spelling suggestions and hover.
+/// Returns where the menu went, if one is open.
+fn draw_floats(f: &mut Frame, app: &App, at: (u16, u16), area: Rect) -> Option<Rect> {
+    match app.mode {
+        Mode::Insert => {
+            draw_signature(f, app, at, area);
+            draw_completion(f, app, at, area)
+        }
+        Mode::Spell => draw_suggestions(f, app, at, area),
+        Mode::Normal => {
+            draw_hover(f, app, at, area);
+            None
+        }
+        _ => None,
+    }
+}
+
+/// The dialog or overlay the mode has up, if any, centred over `area`.
+/// Returns where it went.
+fn draw_dialogs(f: &mut Frame, app: &App, area: Rect) -> Option<Rect> {
+    match app.mode {
+        Mode::ConfirmQuit => {
+            let items = app.dirty_buffers().map(|b| b.title()).collect();
+            Some(draw_dialog(f, app, " Unsaved buffers ", items, None, area))
+        }
+        Mode::ConfirmRestore => {
+            let snapshot = app.pending_restore.as_ref()?;
+            let mut items: Vec<String> = snapshot
+                .buffers
+                .iter()
+                .filter(|path| path.is_file())
+                .map(|path| {
+                    let line = snapshot.recent.position(path).map_or(0, |p| p.line);
+                    let active = match snapshot.active.as_ref() == Some(path) {
+                        true => "  (active)",
+                        false => "",
+                    };
+                    format!("{}:{}{}", app.diff_title(path), line + 1, active)
+                })
+                .collect();
+            let layout = &snapshot.layout;
+            let panes = [
+                (layout.panel.is_some(), "panel"),
+                (layout.outline, "outline"),
+                (layout.preview, "preview"),
+            ];
+            let panes: Vec<&str> = panes
+                .iter()
+                .filter(|(shown, _)| *shown)
+                .map(|(_, name)| *name)
+                .collect();
+            if !panes.is_empty() {
+                items.push(format!("with the {} shown", panes.join(", ")));
+            }
+            Some(draw_dialog(
+                f,
+                app,
+                " Restore previous session? ",
+                items,
+                None,
+                area,
+            ))
+        }
+        Mode::ConfirmTrust => {
+            let items = app
+                .untrusted
+                .iter()
+                .map(|p| format!("{}: {} {}", p.name, p.command, p.args.join(" ")))
+                .collect();
+            Some(draw_dialog(
+                f,
+                app,
+                " Untrusted project plugins ",
+                items,
+                None,
+                area,
+            ))
+        }
+        Mode::Branches => {
+            let picker = app.branches.as_ref()?;
+            Some(draw_picker(f, app, "Branches", picker.list(), area))
+        }
+        Mode::Recent => {
+            let picker = app.recent_picker.as_ref()?;
+            Some(draw_picker(f, app, "Recent files", picker.list(), area))
+        }
+        Mode::Projects => {
+            let picker = app.project_picker.as_ref()?;
+            Some(draw_picker(f, app, "Projects", picker.list(), area))
+        }
+        Mode::Review => {
+            let review = app.review.as_ref()?;
+            let items = review
+                .files()
+                .iter()
+                .map(|file| match review.note_count(file) {
+                    0 => format!("{} {}", file.change, file.path),
+                    1 => format!("{} {}  (1 note)", file.change, file.path),
+                    n => format!("{} {}  ({} notes)", file.change, file.path, n),
+                })
+                .collect();
+            let title = format!(" Changes against {} ", review.base());
+            let selected = Some(review.selected());
+            Some(draw_dialog(f, app, &title, items, selected, area))
+        }
+        Mode::Rename => {
+            let (_, preview) = app.rename.as_ref()?;
+            Some(draw_dialog(f, app, " Rename ", preview.clone(), None, area))
+        }
+        Mode::Search => draw_search(f, app, area),
+        Mode::Replace => draw_replace(f, app, area),
+        Mode::Symbols => draw_symbols(f, app, area),
+        Mode::Normal
+        | Mode::Command
+        | Mode::Insert
+        | Mode::Blame
+        | Mode::GitStatus
+        | Mode::GitLog
+        | Mode::Merge
+        | Mode::Diff
+        | Mode::Locations
+        | Mode::Outline
+        | Mode::Hex
+        | Mode::Spell
+        | Mode::Structure
+        | Mode::Follow
+        | Mode::Terminal
+        | Mode::TerminalCopy => None,
+    }
+}
+
+/// The longest-running background job at the right of `area`, once it
+/// has run long enough to be worth a mention: a gauge if its total is
+/// known, else a spinner.
+fn draw_jobs(f: &mut Frame, app: &App, area: Rect) {
+    let mut slow = app
+        .jobs
+        .active()
+        .filter(|(_, job)| job.elapsed() >= SHOW_JOBS_AFTER);
+    let (_, job) = match slow.next() {
+        Some(job) => job,
+        None => return,
+    };
+    let label = match slow.count() {
+        0 => job.name.clone(),
+        more => format!("{} (+{})", job.name, more),
+    };
+    let theme = &app.config.theme;
+    let plain = app.config.editor.screen_reader;
+    let place = |width: u16| {
+        let width = width.min(area.width);
+        Rect::new(area.right() - width, area.y, width, 1)
+    };
+    match job.total {
+        Some(total) => {
+            let gauge = Gauge::new(job.done as f64 / total.max(1) as f64)
+                .label(&label)
+                .plain(plain)
+                .style(theme.status())
+                .filled_style(theme.accent());
+            let area = place(gauge.width());
+            f.render_widget(gauge, area);
+        }
+        None => {
+            let spinner = Spinner::new(job.elapsed())
+                .label(&label)
+                .plain(plain)
+                .style(theme.status());
+            let area = place(spinner.width());
+            f.render_widget(spinner, area);
+        }
+    }
+}
+
+/// Frame stats in the top right corner of `area`.
+fn draw_perf(f: &mut Frame, app: &App, area: Rect) {
+    let lines = app.perf.lines();
+    let width = (lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 2).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let overlay = Rect {
+        x: area.right() - width,
+        y: area.y,
+        width,
+        height,
+    };
+    let items: Vec<ListItem> = lines.into_iter().map(ListItem::new).collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(app.config.theme.accent())
+        .style(app.config.theme.base())
+        .title(" Perf ");
+    f.render_widget(Clear, overlay);
+    f.render_widget(List::new(items).block(block), overlay);
+}
+
+/// Blame for the lines of the active buffer shown beside it in `area`, with
+/// the cursor line highlighted.
+fn draw_blame(f: &mut Frame, app: &App, blame: &[BlameLine], area: Rect) {
+    let theme = &app.config.theme;
+    let (scroll, cursor) = match app.buffer() {
+        Some(buffer) => (buffer.view.offset(), buffer.cursor().line),
+        None => return,
+    };
+    let items: Vec<ListItem> = blame
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(area.height as usize)
+        .map(|(idx, line)| {
+            let style = match idx == cursor {
+                true => theme.selection(),
+                false => theme.gutter(),
+            };
+            ListItem::new(line.describe()).style(style)
+        })
+        .collect();
+    f.render_widget(List::new(items).style(theme.gutter()), area);
+}
+
+/// The completion menu below the cursor at `(x, y)`, or above it if there
+/// is no room, lined up with the word being completed, and the selected
+/// item's documentation beside it. Returns where the menu went.
+fn draw_completion(f: &mut Frame, app: &App, (x, y): (u16, u16), area: Rect) -> Option<Rect> {
+    let (completion, buffer) = match (&app.completion, app.buffer()) {
+        (Some(completion), Some(buffer)) => (completion, buffer),
+        _ => return None,
+    };
+    let theme = &app.config.theme;
+    let items = completion.visible();
+    let menu = PopupMenu::new(&items, completion.state())
+        .tag_width(4)
+        .style(theme.base())
+        .highlight_style(theme.selection())
+        .tag_style(theme.accent())
+        .border_style(theme.accent());
+    // The kind column and border sit left of the word.
+    let start = buffer.display_col(completion.start());
+    let typed = buffer.display_col(buffer.cursor()).saturating_sub(start) as u16;
+    let left = x.saturating_sub(typed + menu.label_x());
+    let area_menu = popup_menu::place((left, y), menu.size(), area);
+    f.render_widget(menu, area_menu);
+
+    let docs = completion.documentation()?;
+    let doc_width = 50.min(area.width / 2);
+    let doc_x = match area_menu.right() + doc_width <= area.right() {
+        true => area_menu.right(),
+        false if area_menu.x >= area.x + doc_width => area_menu.x - doc_width,
+        false => return Some(area_menu),
+    };
+    let doc_height = (docs.lines().count() as u16 + 2)
+        .clamp(3, 12)
+        .min(area.height);
+    let doc_y = area_menu.y.min(area.bottom().saturating_sub(doc_height));
+    let preview = Rect::new(doc_x, doc_y, doc_width, doc_height);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base());
+    f.render_widget(Clear, preview);
+    f.render_widget(
+        Paragraph::new(docs).block(block).wrap(Wrap { trim: false }),
+        preview,
+    );
+    Some(area_menu)
+}
+
+/// The spelling suggestions for the misspelled word at the cursor, at
+/// `(x, y)`, in a menu below it. Returns where the menu went.
+fn draw_suggestions(f: &mut Frame, app: &App, (x, y): (u16, u16), area: Rect) -> Option<Rect> {
+    let suggestions = app.suggestions.as_ref()?;
+    let theme = &app.config.theme;
+    let title = format!(" Suggestions for `{}` ", suggestions.misspelling.word);
+    let menu = PopupMenu::new(&suggestions.words, suggestions.state())
+        .title(&title)
+        .empty("No suggestions")
+        .style(theme.base())
+        .highlight_style(theme.selection())
+        .border_style(theme.accent());
+    let left = x.saturating_sub(menu.label_x());
+    let area_menu = popup_menu::place((left, y), menu.size(), area);
+    f.render_widget(menu, area_menu);
+    Some(area_menu)
+}
+
+/// A `width` by `height` window by the cursor at `(x, y)`: above its line
+/// with `above` if there is room, else below, and the other way round.
+fn float_at((x, y): (u16, u16), (width, height): (u16, u16), above: bool, area: Rect) -> Rect {
+    let (width, height) = (width.min(area.width), height.min(area.height));
+    let fits_above = y >= area.y + height;
+    let fits_below = y + 1 + height <= area.bottom();
+    let top = match (above && fits_above) || !fits_below {
+        true => y.saturating_sub(height).max(area.y),
+        false => y + 1,
+    };
+    Rect::new(x.min(area.right() - width), top, width, height)
+}
+
+/// How many rows `text` takes wrapped to `width` columns.
+fn wrapped_height(text: &str, width: u16) -> u16 {
+    let width = width.max(1) as usize;
+    let rows: usize = text.lines().map(|l| l.width().max(1).div_ceil(width)).sum();
+    rows as u16
+}
+
+/// The hover documentation, below the cursor.
+fn draw_hover(f: &mut Frame, app: &App, cursor: (u16, u16), area: Rect) {
+    let text = match &app.hover {
+        Some(text) => text,
+        None => return,
+    };
+    let theme = &app.config.theme;
+    let width = text.lines().map(|l| l.width()).max().unwrap_or(0) as u16;
+    let width = (width + 2).clamp(20, 72);
+    let height = (wrapped_height(text, width - 2) + 2).min(16);
+    let window = float_at(cursor, (width, height), false, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base());
+    f.render_widget(Clear, window);
+    f.render_widget(
+        Paragraph::new(text.as_str())
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        window,
+    );
+}
+
+/// The signature of the call being typed, above the cursor so that the
+/// completion menu can go below, with the active parameter highlighted.
+fn draw_signature(f: &mut Frame, app: &App, cursor: (u16, u16), area: Rect) {
+    let help = match &app.signature {
+        Some(help) => help,
+        None => return,
+    };
+    let theme = &app.config.theme;
+    let (start, end) = help.active.unwrap_or_default();
+    let part = |from: usize, to: usize| -> String {
+        help.label
+            .chars()
+            .skip(from)
+            .take(to.saturating_sub(from))
+            .collect()
+    };
+    let mut lines = vec![Line::from(vec![
+        Span::raw(part(0, start)),
+        Span::styled(part(start, end), theme.accent()),
+        Span::raw(part(end, usize::MAX)),
+    ])];
+    let docs = help.documentation.as_deref().unwrap_or_default();
+    lines.extend(docs.lines().map(Line::from));
+
+    let width = lines.iter().map(Line::width).max().unwrap_or(0) as u16;
+    let width = (width + 2).clamp(20, 72);
+    let text = std::iter::once(help.label.as_str())
+        .chain(docs.lines())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let height = (wrapped_height(&text, width - 2) + 2).min(8);
+    let window = float_at(cursor, (width, height), true, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base());
+    f.render_widget(Clear, window);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        window,
+    );
+}
+
+/// Lists `lines` in a box centred over `area`, highlighting the `selected`
+/// one. Returns where the box went.
+fn draw_dialog(
+    f: &mut Frame,
+    app: &App,
+    title: &str,
+    lines: Vec<String>,
+    selected: Option<usize>,
+    area: Rect,
+) -> Rect {
+    let theme = &app.config.theme;
+    let items: Vec<ListItem> = lines.into_iter().map(ListItem::new).collect();
+    let width = (area.width / 2).max(30).min(area.width);
+    let height = (items.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(title);
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(theme.selection());
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(
+        list,
+        popup,
+        &mut ListState::default().with_selected(selected),
+    );
+    popup
+}
+
+/// A picker's list in a dialog, titled with its filter once there is one.
+fn draw_picker<T>(f: &mut Frame, app: &App, title: &str, list: &SelectList<T>, area: Rect) -> Rect {
+    let theme = &app.config.theme;
+    let width = (area.width / 2).max(30).min(area.width);
+    let height = (list.visible_len().max(1) as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let title = match (list.filtering, list.filter()) {
+        (false, "") => format!(" {} ", title),
+        (_, filter) => format!(" {} /{} ", title, filter),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(title);
+    let inner = block.inner(popup);
+    f.render_widget(Clear, popup);
+    f.render_widget(block, popup);
+    let view = SelectListView::new(list)
+        .style(theme.base())
+        .highlight_style(theme.selection());
+    f.render_widget(view, inner);
+    popup
+}
+
+/// The project search overlay: the pattern being typed above the results.
+fn draw_search(f: &mut Frame, app: &App, area: Rect) -> Option<Rect> {
+    let search = match &app.search {
+        Some(search) => search,
+        None => return None,
+    };
+    let theme = &app.config.theme;
+    let popup = overlay(area);
+    let count = search.match_count();
+    let title = match (search.searching(), search.truncated()) {
+        (true, _) => format!(" Search ({} so far) ", count),
+        (false, true) => format!(" Search ({}, stopped early) ", count),
+        (false, false) if search.query.is_empty() => " Search ".to_string(),
+        (false, false) => format!(" Search ({} in {} files) ", count, search.files().len()),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(title);
+    let inner = block.inner(popup);
+    f.render_widget(Clear, popup);
+    f.render_widget(block, popup);
+    if let Some(job) = search.job.and_then(|id| app.jobs.get(id)) {
+        let spinner = Spinner::new(job.elapsed())
+            .label("searching")
+            .plain(app.config.editor.screen_reader)
+            .style(theme.accent());
+        let width = spinner.width().min(popup.width.saturating_sub(4));
+        f.render_widget(
+            spinner,
+            Rect::new(popup.x + 2, popup.bottom() - 1, width, 1),
+        );
+    }
+    let mut prompts = vec![TextInputView::new(&search.query).prompt("> ")];
+    if let Some(replacement) = &search.replacement {
+        prompts.push(TextInputView::new(replacement).prompt("→ "));
+    }
+    let rows = prompts.len() as u16;
+    if inner.height <= rows {
+        return Some(popup);
+    }
+
+    // The cursor goes in the last prompt, which is being typed.
+    for (i, prompt) in prompts.into_iter().enumerate() {
+        let row = Rect::new(inner.x, inner.y + i as u16, inner.width, 1);
+        let prompt = prompt.style(theme.base());
+        f.set_cursor_position(prompt.cursor(row));
+        f.render_widget(prompt, row);
+    }
+
+    let results = Rect::new(inner.x, inner.y + rows, inner.width, inner.height - rows);
+    let view = SearchView::new(search)
+        .style(theme.base())
+        .highlight_style(theme.selection())
+        .match_style(theme.accent())
+        .file_style(theme.accent())
+        .context_style(theme.gutter());
+    f.render_widget(view, results);
+    Some(popup)
+}
+
+/// The changes a replace across files would make, over the search overlay.
+fn draw_replace(f: &mut Frame, app: &App, area: Rect) -> Option<Rect> {
+    let replace = match &app.replace {
+        Some(replace) => replace,
+        None => return None,
+    };
+    let theme = &app.config.theme;
+    let popup = overlay(area);
+    let title = format!(
+        " Replace {} with {} ({} of {} changes) ",
+        replace.pattern,
+        replace.replacement,
+        replace.included_count(),
+        replace.count()
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(title);
+    let inner = block.inner(popup);
+    f.render_widget(Clear, popup);
+    f.render_widget(block, popup);
+    let view = ReplaceView::new(replace)
+        .style(theme.base())
+        .highlight_style(theme.selection())
+        .file_style(theme.accent())
+        .added_style(theme.added())
+        .removed_style(theme.removed())
+        .excluded_style(theme.gutter());
+    f.render_widget(view, inner);
+    Some(popup)
+}
+
+/// The outline sidebar beside the editor.
+fn draw_outline(f: &mut Frame, app: &App, area: Rect) {
+    let outline = match &app.outline {
+        Some(outline) => outline,
+        None => return,
+    };
+    let theme = &app.config.theme;
+    let block = Block::default()
+        .borders(Borders::LEFT)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(" Outline ");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let highlight = match app.mode {
+        Mode::Outline => theme.selection(),
+        _ => theme.accent(),
+    };
+    let view = TreeView::new(outline.nodes(), outline.state())
+        .style(theme.base())
+        .highlight_style(highlight)
+        .detail_style(theme.gutter())
+        .plain(app.config.editor.screen_reader)
+        .empty("No symbols");
+    f.render_widget(view, inner);
+}
+
+/// The structural viewer, over the editor.
+fn draw_structure(f: &mut Frame, app: &App, tree: &DataTree, area: Rect) {
+    let theme = &app.config.theme;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(format!(" {} structure ", app.diff_title(tree.source())));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let view = TreeView::new(tree.roots(), tree.state())
+        .indent(app.config.tree.indent)
+        .style(theme.base())
+        .highlight_style(theme.selection())
+        .plain(app.config.editor.screen_reader);
+    f.render_widget(view, inner);
+}
+
+/// The hex view of a binary file, titled with the offset of the byte at
+/// the cursor.
+fn draw_hex(f: &mut Frame, app: &App, hex: &HexEditor, area: Rect) {
+    let theme = &app.config.theme;
+    let cursor = hex.cursor();
+    let title = format!(
+        " {}{} at 0x{:x} ({} of {} bytes) ",
+        app.diff_title(hex.path()),
+        if hex.is_dirty() { " [+]" } else { "" },
+        cursor,
+        cursor,
+        hex.bytes().len()
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(title);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let view = HexView::new(hex)
+        .style(theme.base())
+        .offset_style(theme.gutter())
+        .highlight_style(theme.selection());
+    f.render_widget(view, inner);
+}
+
+/// The markdown preview beside the editor, scrolled along with it.
+fn draw_preview(f: &mut Frame, app: &App, area: Rect) {
+    let preview = match &app.preview {
+        Some(preview) => preview,
+        None => return,
+    };
+    let theme = &app.config.theme;
+    let block = Block::default()
+        .borders(Borders::LEFT)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(" Preview ");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let scroll = app.buffer().map_or(0, |b| b.view.offset());
+    let view = PreviewView::new(preview)
+        .scroll(scroll)
+        .style(theme.base())
+        .heading_style(theme.accent())
+        .code_style(theme.gutter())
+        .link_style(theme.accent())
+        .marker_style(theme.gutter())
+        .keyword_style(theme.accent())
+        .string_style(theme.added())
+        .comment_style(theme.gutter());
+    f.render_widget(view, inner);
+}
+
+/// The symbol picker, with the query typed so far above the symbols.
+fn draw_symbols(f: &mut Frame, app: &App, area: Rect) -> Option<Rect> {
+    let picker = match &app.symbols {
+        Some(picker) => picker,
+        None => return None,
+    };
+    let theme = &app.config.theme;
+    let popup = overlay(area);
+    let scope = match picker.workspace {
+        true => "Workspace symbols",
+        false => "Symbols",
+    };
+    let title = match picker.indexing() {
+        true => format!(" {} (looking) ", scope),
+        false => format!(" {} ({}) ", scope, picker.len()),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(title);
+    let inner = block.inner(popup);
+    f.render_widget(Clear, popup);
+    f.render_widget(block, popup);
+    if inner.height < 2 {
+        return Some(popup);
+    }
+
+    let prompt = format!("> {}", picker.query());
+    let x = inner.x + prompt.width() as u16;
+    f.render_widget(
+        Paragraph::new(prompt).style(theme.base()),
+        Rect::new(inner.x, inner.y, inner.width, 1),
+    );
+    f.set_cursor_position((x.min(inner.right().saturating_sub(1)), inner.y));
+
+    let results = Rect::new(inner.x, inner.y + 1, inner.width, inner.height - 1);
+    let view = SymbolPickerView::new(picker)
+        .style(theme.base())
+        .highlight_style(theme.selection())
+        .kind_style(theme.accent())
+        .path_style(theme.gutter());
+    f.render_widget(view, results);
+    Some(popup)
+}
+
+/// The area of a large overlay centered in `area`.
+fn overlay(area: Rect) -> Rect {
+    let width = (area.width * 4 / 5).max(40).min(area.width);
+    let height = (area.height * 4 / 5).max(10).min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn draw_panel(f: &mut Frame, app: &App, panel: Panel, area: Rect) {
+    let theme = &app.config.theme;
+    let block = Block::default()
+        .borders(Borders::TOP)
+        .border_style(theme.accent())
+        .style(theme.base());
+
+    match panel {
+        Panel::Log => {
+            let height = block.inner(area).height as usize;
+            let lines = match &app.logger {
+                Some(logger) => logger.recent().last(height),
+                None => vec!["Logging is disabled".to_string()],
+            };
+            let items: Vec<ListItem> = lines.into_iter().map(ListItem::new).collect();
+            f.render_widget(List::new(items).block(block.title(" Log ")), area);
+        }
+        Panel::Jobs => {
+            let rows: Vec<Vec<String>> = app
+                .jobs
+                .active()
+                .map(|(id, job)| {
+                    let progress = match job.total {
+                        Some(total) => format!("{}/{}", job.done, total),
+                        None => job.done.to_string(),
+                    };
+                    let state = if job.cancelled() { "cancelling" } else { "" };
+                    vec![
+                        format!("#{}", id),
+                        job.name.to_string(),
+                        progress,
+                        format!("{:.1}s", job.elapsed().as_secs_f32()),
+                        state.to_string(),
+                    ]
+                })
+                .collect();
+            let widths = [
+                Width::Auto,
+                Width::Weight(1),
+                Width::Auto,
+                Width::Auto,
+                Width::Auto,
+            ];
+            let inner = block.inner(area);
+            f.render_widget(block.title(" Jobs "), area);
+            let table = Table::new(&rows, &widths)
+                .header(&["Id", "Job", "Done", "Time", ""])
+                .header_style(theme.accent())
+                .style(theme.base())
+                .empty("No running jobs");
+            f.render_widget(table, inner);
+        }
+        Panel::Plugins => {
+            let mut items = Vec::new();
+            for (name, lines) in app.plugins.panels() {
+                items.push(ListItem::new(Span::styled(
+                    name.to_string(),
+                    theme.accent(),
+                )));
+                items.extend(lines.iter().map(|l| ListItem::new(l.as_str())));
+            }
+            if items.is_empty() {
+                items.push(ListItem::new("No plugin output"));
+            }
+            f.render_widget(List::new(items).block(block.title(" Plugins ")), area);
+        }
+        Panel::Git => {
+            let inner = block.inner(area);
+            f.render_widget(block.title(" Git "), area);
+            if let Some(status) = &app.git_status {
+                let view = GitStatusView::new(status)
+                    .style(theme.base())
+                    .highlight_style(theme.selection())
+                    .staged_style(theme.accent());
+                f.render_widget(view, inner);
+            }
+        }
+        Panel::GitLog => {
+            let log = match &app.git_log {
+                Some(log) => log,
+                None => return,
+            };
+            let title = match (log.filtering, log.filter()) {
+                (false, "") => format!(" {} ", log.title()),
+                (_, filter) => format!(" {} /{} ", log.title(), filter),
+            };
+            let inner = block.inner(area);
+            f.render_widget(block.title(title), area);
+            let commits = log.visible();
+            let view = TreeView::new(&commits, log.state())
+                .style(theme.base())
+                .highlight_style(theme.selection())
+                .empty("No matching commits");
+            f.render_widget(view, inner);
+        }
+        Panel::Locations => {
+            let locations = match &app.locations {
+                Some(locations) => locations,
+                None => return,
+            };
+            let title = format!(" {} ({}) ", locations.title(), locations.entries().len());
+            let inner = block.inner(area);
+            f.render_widget(block.title(title), area);
+            if let Some((header, rows)) = locations.table() {
+                let mut widths = vec![Width::Auto; header.len()];
+                if let Some(last) = widths.last_mut() {
+                    *last = Width::Weight(1);
+                }
+                let table = Table::new(rows, &widths)
+                    .header(header)
+                    .select(Some(locations.selected_index()))
+                    .scroll_x(locations.scroll_x())
+                    .style(theme.base())
+                    .header_style(theme.accent())
+                    .highlight_style(theme.selection());
+                return f.render_widget(table, inner);
+            }
+            let view = LocationsView::new(locations)
+                .style(theme.base())
+                .highlight_style(theme.selection());
+            f.render_widget(view, inner);
+        }
+        Panel::Tasks => {
+            let run = match &app.task_run {
+                Some(run) => run,
+                None => return,
+            };
+            let height = block.inner(area).height as usize;
+            let lines = run.lines();
+            let items: Vec<ListItem> = lines
+                .iter()
+                .skip(lines.len().saturating_sub(height))
+                .map(|l| ListItem::new(l.as_str()))
+                .collect();
+            let title = format!(" {} ({}) ", run.name, run.state());
+            f.render_widget(List::new(items).block(block.title(title)), area);
+        }
+        Panel::Terminal => draw_terminal(f, app, area, Borders::TOP),
+    }
+}
+
+/// The terminal pane, with the shell's cursor while it has focus.
+fn draw_terminal(f: &mut Frame, app: &App, area: Rect, borders: Borders) {
+    let pty = match &app.pty {
+        Some(pty) => pty,
+        None => return,
+    };
+    let theme = &app.config.theme;
+    let title = match app.mode {
+        Mode::TerminalCopy => " Terminal (copy) ",
+        _ => " Terminal ",
+    };
+    let block = Block::default()
+        .borders(borders)
+        .border_style(theme.accent())
+        .style(theme.base())
+        .title(title);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let view = TerminalView::new(pty)
+        .copy_mode(app.copy_mode)
+        .style(theme.base())
+        .highlight_style(theme.selection())
+        .colors(!theme.monochrome);
+    f.render_widget(view, inner);
+    if let (Mode::Terminal, Some((row, col))) = (app.mode, pty.cursor()) {
+        f.set_cursor_position((inner.x + col, inner.y + row));
+    }
 }
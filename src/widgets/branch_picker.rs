@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use super::select_list::SelectList;
+use crate::git::{self, Branch};
+
+/// The local branches of a repository, with one selected, for the branch
+/// overlay.
+#[derive(Debug)]
+pub struct BranchPicker {
+    root: PathBuf,
+    list: SelectList<Branch>,
+}
+
+impl BranchPicker {
+    /// Branches of the repository containing `dir`, with the current one
+    /// selected.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let root = git::repo_root(dir)?;
+        let mut list = SelectList::new(git::branches(&root)?, label);
+        list.select_where(|b| b.current);
+        Ok(BranchPicker { root, list })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn list(&self) -> &SelectList<Branch> {
+        &self.list
+    }
+
+    pub fn list_mut(&mut self) -> &mut SelectList<Branch> {
+        &mut self.list
+    }
+
+    pub fn branches(&self) -> &[Branch] {
+        self.list.items()
+    }
+
+    pub fn selected_branch(&self) -> Option<&Branch> {
+        self.list.selected_item()
+    }
+
+    pub fn refresh(&mut self) -> Result<()> {
+        self.list.set_items(git::branches(&self.root)?, label);
+        Ok(())
+    }
+
+    /// Deletes the selected branch unless it is checked out or unmerged.
+    pub fn delete_selected(&mut self) -> Result<String> {
+        let branch = match self.selected_branch() {
+            Some(branch) => branch.clone(),
+            None => bail!("no branch selected"),
+        };
+        if branch.current {
+            bail!("can't delete the checked out branch");
+        }
+        git::delete_branch(&self.root, &branch.name)?;
+        self.refresh()?;
+        Ok(branch.name)
+    }
+}
+
+/// A branch as the overlay lists it, the checked out one starred.
+fn label(branch: &Branch) -> String {
+    let current = if branch.current { "*" } else { " " };
+    format!("{} {}", current, branch.name)
+}
@@ -0,0 +1,106 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{KeyEvent, MouseEvent};
+use ratatui::layout::Rect;
+
+use crate::{
+    buffer::Position,
+    fuzzy,
+    lsp::{self, CompletionItem},
+    widgets::popup_menu::{MenuItem, MenuKey, MenuState},
+};
+
+/// Completions a language server offered for the word at `start`, narrowed
+/// down to those matching what has been typed since.
+#[derive(Debug)]
+pub struct Completion {
+    path: PathBuf,
+    /// Where the text an item replaces begins.
+    start: Position,
+    items: Vec<CompletionItem>,
+    filter: String,
+    /// Which of the matching items is selected.
+    state: MenuState,
+}
+
+impl Completion {
+    pub fn new(path: &Path, start: Position, items: Vec<CompletionItem>) -> Self {
+        Completion {
+            path: path.to_owned(),
+            start,
+            items,
+            filter: String::new(),
+            state: MenuState::default(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    pub fn set_filter(&mut self, filter: &str) {
+        if self.filter != filter {
+            self.filter = filter.to_string();
+            self.state.select(0);
+        }
+    }
+
+    /// The items matching the filter, in the server's order.
+    pub fn visible(&self) -> Vec<&CompletionItem> {
+        self.items
+            .iter()
+            .filter(|i| fuzzy::matches(&self.filter, &i.filter_text))
+            .collect()
+    }
+
+    pub fn selected(&self) -> Option<&CompletionItem> {
+        self.visible().get(self.state.selected()).copied()
+    }
+
+    pub fn state(&self) -> &MenuState {
+        &self.state
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> MenuKey {
+        let len = self.visible().len();
+        self.state.handle_key(key, len)
+    }
+
+    /// Handles `mouse` for the menu as drawn in `area`.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent, area: Rect) -> MenuKey {
+        let len = self.visible().len();
+        self.state.handle_mouse(mouse, area, len)
+    }
+
+    /// The selected item's signature and documentation, for the preview
+    /// beside the menu.
+    pub fn documentation(&self) -> Option<String> {
+        let item = self.selected()?;
+        let parts: Vec<&str> = [item.detail.as_deref(), item.documentation.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        Some(parts.join("\n\n")).filter(|d| !d.is_empty())
+    }
+}
+
+impl MenuItem for &CompletionItem {
+    /// The label, and the first line of any detail.
+    fn label(&self) -> Cow<'_, str> {
+        match self.detail.as_deref().and_then(|d| d.lines().next()) {
+            Some(detail) => format!("{}  {}", self.label, detail).into(),
+            None => self.label.as_str().into(),
+        }
+    }
+
+    fn tag(&self) -> &str {
+        lsp::kind_icon(self.kind)
+    }
+}
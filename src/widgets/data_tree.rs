@@ -0,0 +1,138 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use super::tree_view::{TreeItem, TreeState};
+use crate::data::DataNode;
+
+/// The structural viewer: a JSON or YAML document as a tree of keys and
+/// values, expanded and collapsed like the file tree.
+#[derive(Debug)]
+pub struct DataTree {
+    /// The file the document is from.
+    source: PathBuf,
+    root: DataNode,
+    state: TreeState,
+}
+
+impl DataTree {
+    /// A tree of `root`, parsed from `source`, with its top level shown.
+    pub fn new(source: &Path, root: DataNode) -> Self {
+        let mut state = TreeState::default();
+        state.expand(&root.path);
+        DataTree {
+            source: source.to_owned(),
+            root,
+            state,
+        }
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// The document's root, as the only root of a
+    /// [`TreeView`](super::tree_view::TreeView).
+    pub fn roots(&self) -> &[DataNode] {
+        std::slice::from_ref(&self.root)
+    }
+
+    pub fn state(&self) -> &TreeState {
+        &self.state
+    }
+
+    pub fn selected(&self) -> usize {
+        self.state.selected()
+    }
+
+    pub fn selected_node(&self) -> Option<&DataNode> {
+        self.state.selected_item(self.roots())
+    }
+
+    pub fn select_next(&mut self) {
+        self.state.select_next(std::slice::from_ref(&self.root));
+    }
+
+    pub fn select_prev(&mut self) {
+        self.state.select_prev();
+    }
+
+    pub fn expand_selected(&mut self) {
+        self.state.expand_selected(std::slice::from_ref(&self.root));
+    }
+
+    /// Collapses the selected node, or moves the selection to its parent
+    /// when it is already collapsed.
+    pub fn collapse_selected(&mut self) {
+        self.state
+            .collapse_selected(std::slice::from_ref(&self.root));
+    }
+
+    pub fn toggle_selected(&mut self) {
+        self.state.toggle_selected(std::slice::from_ref(&self.root));
+    }
+}
+
+impl TreeItem for DataNode {
+    fn id(&self) -> &str {
+        &self.path
+    }
+
+    fn label(&self) -> Cow<'_, str> {
+        Cow::Owned(DataNode::label(self))
+    }
+
+    fn children(&self) -> &[Self] {
+        DataNode::children(self)
+    }
+}
+
+#[test]
+fn test_data_tree_expands_and_collapses() {
+    let text = "{\"a\": {\"b\": [1, 2]}, \"c\": 3}";
+    let root = crate::data::parse(Path::new("x.json"), text).unwrap();
+    let mut tree = DataTree::new(Path::new("x.json"), root);
+    let labels = |tree: &DataTree| -> Vec<String> {
+        tree.state
+            .rows(tree.roots(), usize::MAX)
+            .iter()
+            .map(|(depth, node)| format!("{}{}", "  ".repeat(*depth), node.label()))
+            .collect()
+    };
+    assert_eq!(labels(&tree), ["{2}", "  a: {1}", "  c: 3"]);
+
+    tree.select_next();
+    tree.toggle_selected();
+    tree.select_next();
+    tree.expand_selected();
+    assert_eq!(
+        labels(&tree),
+        [
+            "{2}",
+            "  a: {1}",
+            "    b: [2]",
+            "      0: 1",
+            "      1: 2",
+            "  c: 3"
+        ]
+    );
+    tree.select_next();
+    assert_eq!(tree.selected_node().unwrap().path, "$.a.b[0]");
+
+    // Collapsing a leaf moves to its parent, then collapses that.
+    tree.collapse_selected();
+    assert_eq!(tree.selected_node().unwrap().path, "$.a.b");
+    tree.collapse_selected();
+    assert_eq!(labels(&tree), ["{2}", "  a: {1}", "    b: [2]", "  c: 3"]);
+
+    use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+
+    use super::tree_view::TreeView;
+
+    let area = Rect::new(0, 0, 20, 4);
+    let mut buf = Buffer::empty(area);
+    TreeView::new(tree.roots(), tree.state()).render(area, &mut buf);
+    let row: String = (0..20).map(|x| buf[(x, 2)].symbol()).collect();
+    assert_eq!(row.trim_end(), "    ▸ b: [2]");
+}
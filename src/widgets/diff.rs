@@ -0,0 +1,182 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Widget},
+};
+
+use crate::diff::{Diff, DiffLayout, DiffLine};
+
+/// Renders a [`Diff`] from its scroll position, either as two panes that
+/// scroll together or as one unified listing. Words that differ within a
+/// changed line are shown reversed.
+pub struct DiffView<'a> {
+    diff: &'a Diff,
+    style: Style,
+    border_style: Style,
+    gutter_style: Style,
+    added_style: Style,
+    removed_style: Style,
+    highlight_style: Option<Style>,
+}
+
+impl<'a> DiffView<'a> {
+    pub fn new(diff: &'a Diff) -> Self {
+        DiffView {
+            diff,
+            style: Style::default(),
+            border_style: Style::default(),
+            gutter_style: Style::default(),
+            added_style: Style::default(),
+            removed_style: Style::default(),
+            highlight_style: None,
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn border_style(mut self, style: Style) -> Self {
+        self.border_style = style;
+        self
+    }
+
+    /// Style of the line numbers.
+    pub fn gutter_style(mut self, style: Style) -> Self {
+        self.gutter_style = style;
+        self
+    }
+
+    pub fn added_style(mut self, style: Style) -> Self {
+        self.added_style = style;
+        self
+    }
+
+    pub fn removed_style(mut self, style: Style) -> Self {
+        self.removed_style = style;
+        self
+    }
+
+    /// Highlights the top row, e.g. as the line a review note goes on.
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = Some(style);
+        self
+    }
+
+    fn block(&self, title: String) -> Block<'static> {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(self.border_style)
+            .title(title)
+    }
+
+    fn highlight_top(&self, inner: Rect, buf: &mut Buffer) {
+        if let (Some(style), true) = (self.highlight_style, inner.height > 0) {
+            buf.set_style(Rect::new(inner.x, inner.y, inner.width, 1), style);
+        }
+    }
+
+    /// Draws `line` at row `y` of `area` after a gutter of `width` digits.
+    fn render_line(
+        &self,
+        mark: Option<char>,
+        line: &DiffLine,
+        style: Style,
+        width: usize,
+        (area, y): (Rect, u16),
+        buf: &mut Buffer,
+    ) {
+        let mut gutter = format!("{:>width$} ", line.number, width = width);
+        if let Some(mark) = mark {
+            gutter.push(mark);
+        }
+        buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+        let (mut x, _) =
+            buf.set_stringn(area.x, y, &gutter, area.width as usize, self.gutter_style);
+        for (text, changed) in &line.segments {
+            let right = area.x + area.width;
+            if x >= right {
+                break;
+            }
+            let style = match changed {
+                true => style.add_modifier(Modifier::REVERSED),
+                false => style,
+            };
+            x = buf.set_stringn(x, y, text, (right - x) as usize, style).0;
+        }
+    }
+}
+
+impl Widget for DiffView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let rows = self.diff.rows();
+        let width = rows
+            .iter()
+            .flat_map(|r| r.left.iter().chain(r.right.iter()))
+            .map(|l| l.number)
+            .max()
+            .unwrap_or(0)
+            .to_string()
+            .len();
+        let scroll = self.diff.scroll();
+
+        match self.diff.layout {
+            DiffLayout::SideBySide => {
+                let panes = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                    .split(area);
+                let sides = [
+                    (&self.diff.left_title, self.removed_style, panes[0]),
+                    (&self.diff.right_title, self.added_style, panes[1]),
+                ];
+                for (side, (title, changed_style, pane)) in sides.into_iter().enumerate() {
+                    let block = self.block(format!(" {} ", title));
+                    let inner = block.inner(pane);
+                    block.render(pane, buf);
+                    let visible = rows.iter().skip(scroll).take(inner.height as usize);
+                    for (i, row) in visible.enumerate() {
+                        let line = match side {
+                            0 => &row.left,
+                            _ => &row.right,
+                        };
+                        let style = match row.changed {
+                            true => changed_style,
+                            false => self.style,
+                        };
+                        if let Some(line) = line {
+                            let at = (inner, inner.y + i as u16);
+                            self.render_line(None, line, style, width, at, buf);
+                        }
+                    }
+                    self.highlight_top(inner, buf);
+                }
+            }
+            DiffLayout::Unified => {
+                let title = format!(" {} → {} ", self.diff.left_title, self.diff.right_title);
+                let block = self.block(title);
+                let inner = block.inner(area);
+                block.render(area, buf);
+                let lines = self.diff.unified();
+                let visible = lines.iter().skip(scroll).take(inner.height as usize);
+                for (i, (mark, line)) in visible.enumerate() {
+                    let style = match mark {
+                        '-' => self.removed_style,
+                        '+' => self.added_style,
+                        _ => self.style,
+                    };
+                    let at = (inner, inner.y + i as u16);
+                    self.render_line(Some(*mark), line, style, width, at, buf);
+                }
+                self.highlight_top(inner, buf);
+            }
+        }
+    }
+}
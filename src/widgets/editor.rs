@@ -0,0 +1,285 @@
+use std::fmt::Write;
+
+use ratatui::{buffer::Buffer as TermBuffer, layout::Rect, style::Style, widgets::Widget};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::buffer::{self, Buffer, Position};
+
+/// Renders a [`Buffer`] starting at its scroll line, scrolled sideways as
+/// far as it takes to show the cursor, with an optional line number gutter, signs beside some lines, virtual text after the end of
+/// some lines, marked stretches of text, e.g. misspelled words, and lines
+/// in their own style.
+pub struct EditorView<'a> {
+    buffer: &'a Buffer,
+    style: Style,
+    gutter_style: Style,
+    line_numbers: bool,
+    annotations: Vec<(usize, String)>,
+    annotation_style: Style,
+    /// Line and char range of each marked stretch.
+    marks: Vec<(usize, usize, usize)>,
+    mark_style: Style,
+    line_styles: Vec<(usize, Style)>,
+    signs: Vec<(usize, char, Style)>,
+}
+
+impl<'a> EditorView<'a> {
+    pub fn new(buffer: &'a Buffer) -> Self {
+        EditorView {
+            buffer,
+            style: Style::default(),
+            gutter_style: Style::default(),
+            line_numbers: true,
+            annotations: Vec::new(),
+            annotation_style: Style::default(),
+            marks: Vec::new(),
+            mark_style: Style::default(),
+            line_styles: Vec::new(),
+            signs: Vec::new(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn gutter_style(mut self, style: Style) -> Self {
+        self.gutter_style = style;
+        self
+    }
+
+    pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Shows `text` after the end of line `line`, e.g. blame for it.
+    pub fn annotate(mut self, line: usize, text: String) -> Self {
+        self.annotations.push((line, text));
+        self
+    }
+
+    pub fn annotation_style(mut self, style: Style) -> Self {
+        self.annotation_style = style;
+        self
+    }
+
+    /// Marks chars `start..end` of line `line`.
+    pub fn mark(mut self, line: usize, start: usize, end: usize) -> Self {
+        self.marks.push((line, start, end));
+        self
+    }
+
+    pub fn mark_style(mut self, style: Style) -> Self {
+        self.mark_style = style;
+        self
+    }
+
+    /// Draws the text of line `line` in `style`, e.g. errors in a log.
+    pub fn line_style(mut self, line: usize, style: Style) -> Self {
+        self.line_styles.push((line, style));
+        self
+    }
+
+    /// Shows `sign` in `style` in the gutter beside line `line`, e.g. `E`
+    /// for an error there. The first sign given for a line wins.
+    pub fn sign(mut self, line: usize, sign: char, style: Style) -> Self {
+        self.signs.push((line, sign, style));
+        self
+    }
+
+    /// Width of the gutter: a column for signs if there are any, then the
+    /// line numbers, each followed by a space.
+    pub fn gutter_width(&self) -> u16 {
+        let signs = if self.signs.is_empty() { 0 } else { 2 };
+        if !self.line_numbers {
+            return signs;
+        }
+        let digits = self
+            .buffer
+            .line_count()
+            .checked_ilog10()
+            .map_or(1, |d| d + 1);
+        signs + digits as u16 + 1
+    }
+
+    /// Screen position of the buffer cursor when rendered into `area`, if it
+    /// is visible.
+    pub fn cursor_position(&self, area: Rect) -> Option<(u16, u16)> {
+        let cursor = self.buffer.cursor();
+        let row = cursor.line.checked_sub(self.buffer.view.offset())?;
+        let col = self
+            .buffer
+            .display_col(cursor)
+            .saturating_sub(self.hscroll(area));
+        let col = col + self.gutter_width() as usize;
+        if row >= area.height as usize || col >= area.width as usize {
+            return None;
+        }
+        Some((area.x + col as u16, area.y + row as u16))
+    }
+
+    /// Columns of text scrolled out of view on the left in `area`: none,
+    /// unless the glyph under the cursor would end past the right edge.
+    fn hscroll(&self, area: Rect) -> usize {
+        let text_width = area.width.saturating_sub(self.gutter_width()) as usize;
+        let cursor = self.buffer.cursor();
+        let start = self.buffer.display_col(cursor);
+        let end = self.buffer.display_col(self.buffer.next_glyph(cursor));
+        // At the end of the line, the cursor takes a column of its own.
+        (start + (end - start).max(1)).saturating_sub(text_width)
+    }
+}
+
+impl Widget for EditorView<'_> {
+    fn render(self, area: Rect, buf: &mut TermBuffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let gutter = self.gutter_width().min(area.width);
+        let hscroll = self.hscroll(area);
+        // Reused from line to line, so drawing allocates about the same
+        // however many lines are shown.
+        let mut number = String::new();
+        for row in 0..area.height {
+            let idx = self.buffer.view.offset() + row as usize;
+            if idx >= self.buffer.line_count() {
+                break;
+            }
+
+            let y = area.y + row;
+            if gutter > 0 {
+                number.clear();
+                let _ = match self.line_numbers {
+                    true => write!(number, "{:>1$} ", idx + 1, gutter as usize - 1),
+                    false => write!(number, "{:1$}", "", gutter as usize),
+                };
+                buf.set_stringn(area.x, y, &number, gutter as usize, self.gutter_style);
+            }
+            if let Some((_, sign, style)) = self.signs.iter().find(|(line, _, _)| *line == idx) {
+                let mut utf8 = [0; 4];
+                let sign = sign.encode_utf8(&mut utf8);
+                buf.set_stringn(area.x, y, sign, gutter as usize, *style);
+            }
+            let style = self
+                .line_styles
+                .iter()
+                .filter(|(line, _)| *line == idx)
+                .fold(self.style, |style, (_, s)| style.patch(*s));
+            // The rope's chunks are drawn one after another rather than
+            // copied out into a line first. Glyphs scrolled off to the left
+            // are skipped, leaving a blank for any cut in two by the edge.
+            let mut x = area.x + gutter;
+            let mut skip = hscroll;
+            buffer::each_chunk(self.buffer.line(idx), |mut chunk| {
+                while skip > 0 && !chunk.is_empty() {
+                    let glyph = chunk.graphemes(true).next().unwrap_or(chunk);
+                    let width = buffer::glyph_width(glyph);
+                    x += width.saturating_sub(skip) as u16;
+                    skip = skip.saturating_sub(width);
+                    chunk = &chunk[glyph.len()..];
+                }
+                if x < area.right() {
+                    (x, _) = buf.set_stringn(x, y, chunk, (area.right() - x) as usize, style);
+                }
+            });
+            let x = x.min(area.right());
+            let text_width = (area.width - gutter) as usize;
+            for &(_, start, end) in self.marks.iter().filter(|(line, _, _)| *line == idx) {
+                let column = |col| {
+                    let col = self.buffer.display_col(Position::new(idx, col));
+                    col.saturating_sub(hscroll).min(text_width) as u16
+                };
+                let (start, end) = (column(start), column(end));
+                let marked = Rect::new(area.x + gutter + start, y, end - start, 1);
+                buf.set_style(marked, self.mark_style);
+            }
+
+            for (_, text) in self.annotations.iter().filter(|(line, _)| *line == idx) {
+                // Kept two columns clear of the text.
+                let x = x + 2;
+                if x < area.right() {
+                    let width = (area.right() - x) as usize;
+                    buf.set_stringn(x, y, text, width, self.annotation_style);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_annotation_follows_line_text() {
+    let buffer = Buffer::from_text("fn main() {}\nsecond\n");
+    let area = Rect::new(0, 0, 30, 2);
+    let mut buf = TermBuffer::empty(area);
+    EditorView::new(&buffer)
+        .line_numbers(false)
+        .annotate(0, "1a2b3c4d Ada".to_string())
+        .render(area, &mut buf);
+    let row: String = (0..30).map(|x| buf[(x, 0)].symbol()).collect();
+    assert_eq!(row.trim_end(), "fn main() {}  1a2b3c4d Ada");
+}
+
+#[test]
+fn test_marks_style_their_chars() {
+    use ratatui::style::Modifier;
+
+    let buffer = Buffer::from_text("a wrod here\n");
+    let area = Rect::new(0, 0, 8, 1);
+    let mut buf = TermBuffer::empty(area);
+    EditorView::new(&buffer)
+        .line_numbers(false)
+        .mark(0, 2, 6)
+        .mark(0, 7, 11)
+        .mark_style(Style::default().add_modifier(Modifier::UNDERLINED))
+        .render(area, &mut buf);
+    let underlined: String = (0..8)
+        .map(|x| &buf[(x, 0)])
+        .filter(|cell| cell.modifier.contains(Modifier::UNDERLINED))
+        .map(|cell| cell.symbol())
+        .collect();
+    assert_eq!(underlined, "wrodh");
+}
+
+#[test]
+fn test_signs_widen_the_gutter() {
+    let buffer = Buffer::from_text("let x = 1;\nlet y;\n");
+    let area = Rect::new(0, 0, 16, 2);
+    let mut buf = TermBuffer::empty(area);
+    let red = Style::default().fg(ratatui::style::Color::Red);
+    let view = EditorView::new(&buffer)
+        .sign(1, 'E', red)
+        .sign(1, 'W', Style::default());
+    assert_eq!(view.gutter_width(), 4);
+    view.render(area, &mut buf);
+    let row = |y| (0..16).map(|x| buf[(x, y)].symbol()).collect::<String>();
+    assert_eq!(row(0).trim_end(), "  1 let x = 1;");
+    assert_eq!(row(1).trim_end(), "E 2 let y;");
+    assert_eq!(buf[(0, 1)].fg, ratatui::style::Color::Red);
+}
+
+#[test]
+fn test_wide_text_scrolls_sideways_to_the_cursor() {
+    let mut buffer = Buffer::from_text("漢字漢字漢字\n");
+    buffer.goto(Position::new(0, usize::MAX));
+    let row = |width| {
+        let area = Rect::new(0, 0, width, 1);
+        let mut buf = TermBuffer::empty(area);
+        let view = EditorView::new(&buffer).line_numbers(false);
+        let cursor = view.cursor_position(area);
+        view.render(area, &mut buf);
+        let text: String = (0..width).map(|x| buf[(x, 0)].symbol()).collect();
+        (text, cursor)
+    };
+    // The line's 12 columns and the cursor after them don't fit in 5, so
+    // the first four glyphs are scrolled out of view. The cell under each
+    // wide glyph's second column is left blank.
+    assert_eq!(row(5), ("漢 字  ".to_string(), Some((4, 0))));
+    // With 6, the edge cuts a glyph in two, leaving a blank for its half.
+    assert_eq!(row(6), (" 漢 字  ".to_string(), Some((5, 0))));
+    assert_eq!(row(20).1, Some((12, 0)));
+}
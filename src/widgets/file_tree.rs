@@ -1,175 +1,1168 @@
-use std::{collections::HashSet, fs, io, path::Path};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Write as _,
+    fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use tui::widgets::StatefulWidget;
+
+use super::{
+    tree_view::{Marker, Row},
+    viewport::Viewport,
+};
+use crate::{
+    badges::GitBadges,
+    error::{Error, Result},
+    metadata::{FileInfo, HumanAge, HumanSize, MetadataCache},
+    paths,
+    session::lenient,
+    sort::SortOptions,
+};
 
 const NAME_SEP: &str = "/";
+/// Files that can be marked at once: the two sides of a comparison.
+const MAX_MARKS: usize = 2;
+/// Entries listed per directory by default.
+pub const MAX_ENTRIES: usize = 5000;
+/// Columns left for names before detail columns are dropped.
+const MIN_NAME_WIDTH: u16 = 12;
+/// Spaces to pad detail columns with, as wide as the widest one and the
+/// space before it.
+const BLANK: &str = "     ";
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// The version of the format [`FileTree`]s are saved in. Saved trees
+/// without one predate it and are read as version 1, the first.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Saved as a [`SavedTree`], tagged with [`FORMAT_VERSION`].
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(try_from = "SavedTree")]
 pub struct FileTree {
     file_root: Box<Path>,
-    root_node: FileNode,
+    /// `file_root` with links resolved, as node paths are built from.
+    canonical_root: PathBuf,
+    /// Every node, each directory's children stored together. The root is
+    /// the first.
+    nodes: Vec<Entry>,
     state: FileTreeState,
+    options: TreeOptions,
+    /// The expanded part of the tree in display order, rebuilt only when a
+    /// directory is expanded or collapsed or the nodes change.
+    rows: Vec<u32>,
+    /// The labels of the shown "more" rows, built with `rows` rather than
+    /// on every draw.
+    more_labels: HashMap<u32, Box<str>>,
+    /// Goes up with every change to what the tree shows.
+    generation: u64,
+}
+
+/// A node of a [`FileTree`]: only its own name, with its path built from
+/// its parents' when asked for.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    name: Box<str>,
+    parent: Option<u32>,
+    /// 1 for the root, 2 for its children and so on. Set when the arena is
+    /// built rather than saved, as it follows from `parent`.
+    #[serde(skip)]
+    depth: u16,
+    kind: EntryKind,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum EntryKind {
+    Directory(Range<u32>),
+    File,
+    Link(Box<Path>),
+    More(usize),
+}
+
+impl Serialize for FileTree {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Saving<'a> {
+            version: u32,
+            file_root: &'a Path,
+            nodes: &'a [Entry],
+            state: &'a FileTreeState,
+            options: &'a TreeOptions,
+        }
+        let saving = Saving {
+            version: FORMAT_VERSION,
+            file_root: &self.file_root,
+            nodes: &self.nodes,
+            state: &self.state,
+            options: &self.options,
+        };
+        saving.serialize(serializer)
+    }
+}
+
+/// A [`FileTree`] as saved, without the rows that are rebuilt from it.
+/// Parts that no longer read, e.g. after a change to their types, fall back
+/// to their defaults instead of failing the whole tree.
+#[derive(Deserialize)]
+struct SavedTree {
+    #[serde(default = "first_version")]
+    version: u32,
+    file_root: Box<Path>,
+    #[serde(default, deserialize_with = "lenient")]
+    nodes: Vec<Entry>,
+    #[serde(default, deserialize_with = "lenient")]
+    state: FileTreeState,
+    #[serde(default, deserialize_with = "lenient")]
+    options: TreeOptions,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+impl TryFrom<SavedTree> for FileTree {
+    type Error = Error;
+
+    fn try_from(saved: SavedTree) -> Result<Self> {
+        // Each version that changes the format reads the one before it here.
+        if saved.version > FORMAT_VERSION {
+            return Err(Error::NewerFormat {
+                found: saved.version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        // Without its nodes, or with ones that don't hang together, the
+        // tree starts from the root alone, filled in by the next scan.
+        let nodes = match with_depths(saved.nodes) {
+            Some(nodes) => nodes,
+            None => {
+                let name = saved.file_root.file_name().unwrap_or_default();
+                let root = FileNode::Directory(name.to_string_lossy().into_owned(), Vec::new());
+                arena(root)
+            }
+        };
+        let mut tree = FileTree {
+            canonical_root: paths::normalize(&saved.file_root),
+            file_root: saved.file_root,
+            nodes,
+            state: saved.state,
+            options: saved.options,
+            rows: Vec::new(),
+            more_labels: HashMap::new(),
+            generation: 0,
+        };
+        tree.flatten();
+        Ok(tree)
+    }
+}
+
+/// Stores a scanned tree breadth first, so that each directory's children
+/// get consecutive ids.
+fn arena(root: FileNode) -> Vec<Entry> {
+    let mut nodes = Vec::new();
+    let mut queue = VecDeque::from([(root, None, 1)]);
+    let mut next = 1;
+    while let Some((node, parent, depth)) = queue.pop_front() {
+        let id = nodes.len() as u32;
+        let (name, kind) = match node {
+            FileNode::Directory(name, children) => {
+                let range = next..next + children.len() as u32;
+                next = range.end;
+                let children = children.into_iter();
+                queue.extend(children.map(|child| (child, Some(id), depth + 1)));
+                (name, EntryKind::Directory(range))
+            }
+            FileNode::File(name) => (name, EntryKind::File),
+            FileNode::Link(name, target) => (name, EntryKind::Link(target)),
+            FileNode::More(count) => (String::new(), EntryKind::More(count)),
+        };
+        nodes.push(Entry {
+            name: name.into_boxed_str(),
+            parent,
+            depth,
+            kind,
+        });
+    }
+    nodes
+}
+
+/// Sets the depth of each of `nodes`, as loaded, or gives `None` if they
+/// aren't a tree: there are none, or some parent doesn't come before its
+/// children, or some directory's children aren't the nodes that name it
+/// as their parent.
+fn with_depths(mut nodes: Vec<Entry>) -> Option<Vec<Entry>> {
+    let len = nodes.len() as u32;
+    for id in 0..nodes.len() {
+        nodes[id].depth = match (id, nodes[id].parent) {
+            (0, None) => 1,
+            (_, Some(parent)) if (parent as usize) < id => nodes[parent as usize].depth + 1,
+            _ => return None,
+        };
+        if let EntryKind::Directory(children) = &nodes[id].kind {
+            let fits = children.start > id as u32 && children.end <= len;
+            let fits = fits
+                && children
+                    .clone()
+                    .all(|child| nodes[child as usize].parent == Some(id as u32));
+            if !fits && !children.is_empty() {
+                return None;
+            }
+        }
+    }
+    (!nodes.is_empty()).then_some(nodes)
+}
+
+/// Takes the arena apart into a scanned tree again, with the node `id`
+/// replaced by `graft`.
+fn unarena(nodes: Vec<Entry>, id: u32, graft: FileNode) -> FileNode {
+    fn build(
+        nodes: &mut [Option<Entry>],
+        id: u32,
+        graft: &mut (u32, Option<FileNode>),
+    ) -> FileNode {
+        if id == graft.0 {
+            if let Some(node) = graft.1.take() {
+                return node;
+            }
+        }
+        let entry = nodes[id as usize].take().expect("each node has one parent");
+        let name = entry.name.into_string();
+        match entry.kind {
+            EntryKind::Directory(children) => {
+                let children = children.map(|child| build(nodes, child, graft)).collect();
+                FileNode::Directory(name, children)
+            }
+            EntryKind::File => FileNode::File(name),
+            EntryKind::Link(target) => FileNode::Link(name, target),
+            EntryKind::More(count) => FileNode::More(count),
+        }
+    }
+    let mut nodes: Vec<Option<Entry>> = nodes.into_iter().map(Some).collect();
+    build(&mut nodes, 0, &mut (id, Some(graft)))
 }
 
-#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+/// A node of a [`FileTree`], borrowed from it.
+#[derive(Debug, Clone, Copy)]
+pub struct NodeRef<'a> {
+    tree: &'a FileTree,
+    id: u32,
+}
+
+impl<'a> NodeRef<'a> {
+    fn entry(&self) -> &'a Entry {
+        &self.tree.nodes[self.id as usize]
+    }
+
+    pub fn name(&self) -> &'a str {
+        &self.entry().name
+    }
+
+    /// The path from the tree's root directory, starting with its name and
+    /// separated by `/`.
+    pub fn path(&self) -> String {
+        self.tree.path(self.id)
+    }
+
+    pub fn has_children(&self) -> bool {
+        matches!(self.entry().kind, EntryKind::Directory(_))
+    }
+
+    /// The entries of a directory as scanned so far; none for a file.
+    pub fn children(&self) -> impl Iterator<Item = NodeRef<'a>> {
+        let ids = match &self.entry().kind {
+            EntryKind::Directory(children) => children.clone(),
+            _ => 0..0,
+        };
+        let tree = self.tree;
+        ids.map(move |id| tree.node(id))
+    }
+
+    /// How many entries of its directory this node stands in for, if it's
+    /// the row left in their place.
+    pub fn more(&self) -> Option<usize> {
+        match self.entry().kind {
+            EntryKind::More(count) => Some(count),
+            _ => None,
+        }
+    }
+
+    /// 1 for the root, 2 for its children and so on.
+    pub fn depth(&self) -> u16 {
+        self.entry().depth
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FileTreeState {
     expanded_nodes: HashSet<String>,
+    selected: usize,
+    /// Paths of the marked files, oldest first.
+    marked: Vec<String>,
+    /// Directories whose entries were all loaded, past `max_entries`.
+    loaded: HashSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TreeOptions {
+    pub show_hidden: bool,
+    pub indent: u16,
+    /// Names to leave out, with `*` and `?` wildcards, e.g. `target` or
+    /// `*.log`.
+    pub ignore: Vec<String>,
+    /// Entries listed per directory; the rest wait for `L` to load them.
+    /// 0 lists them all.
+    pub max_entries: usize,
+    /// Columns shown after each name.
+    pub details: Vec<Detail>,
+    /// The order of each directory's entries, and of search results.
+    pub sort: SortOptions,
+}
+
+/// A column of file details in the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Detail {
+    /// Files' sizes, e.g. `4.2K`.
+    Size,
+    /// How long ago entries were modified, e.g. `3d`.
+    Modified,
+    /// Git's status letter for changed files, e.g. `M`, and `•` beside
+    /// directories with changes in them.
+    Git,
+}
+
+impl Detail {
+    fn width(self) -> u16 {
+        match self {
+            Detail::Size => 4,
+            Detail::Modified => 3,
+            Detail::Git => 1,
+        }
+    }
+
+    fn write_text(self, info: &FileInfo, now: SystemTime, out: &mut String) {
+        let _ = match (self, info.modified) {
+            (Detail::Size, _) if info.is_dir => Ok(()),
+            (Detail::Size, _) => write!(out, "{}", HumanSize(info.len)),
+            (Detail::Modified, Some(time)) => write!(out, "{}", HumanAge { time, now }),
+            (Detail::Modified, None) | (Detail::Git, _) => Ok(()),
+        };
+    }
+}
+
+impl Default for TreeOptions {
+    fn default() -> Self {
+        TreeOptions {
+            show_hidden: false,
+            indent: 2,
+            ignore: Vec::new(),
+            max_entries: MAX_ENTRIES,
+            details: Vec::new(),
+            sort: SortOptions::default(),
+        }
+    }
+}
+
+impl TreeOptions {
+    /// Whether an entry called `name` is left out of the tree.
+    pub fn hides(&self, name: &str) -> bool {
+        (!self.show_hidden && name.starts_with('.'))
+            || self.ignore.iter().any(|pattern| glob_match(pattern, name))
+    }
+}
+
+/// Matches `name` against a pattern where `*` is any run of characters and
+/// `?` any single one.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*` if the rest fails to match.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
 impl FileTree {
     pub fn new(open: &Path) -> anyhow::Result<Self> {
-        let root_node = FileNode::new_from_path(open);
-        match root_node {
-            Ok(root_node) => Ok(FileTree {
-                file_root: Box::from(open.to_owned()),
-                root_node,
-                state: Default::default(),
-            }),
-            Err(e) => Err(e.into()),
+        FileTree::with_options(open, TreeOptions::default())
+    }
+
+    pub fn with_options(open: &Path, options: TreeOptions) -> anyhow::Result<Self> {
+        let root_node = FileNode::new_from_path_with(open, &options)?;
+        Ok(FileTree::from_root_node(open, root_node, options))
+    }
+
+    /// A tree of only `open`'s own entries, which is quick to scan however
+    /// big the directory is. Its subdirectories look empty until
+    /// [`set_root_node`](Self::set_root_node) is given a full scan.
+    pub fn shallow(open: &Path, options: TreeOptions) -> anyhow::Result<Self> {
+        let root_node = FileNode::scan_shallow(open, &options)?;
+        Ok(FileTree::from_root_node(open, root_node, options))
+    }
+
+    fn from_root_node(open: &Path, root_node: FileNode, options: TreeOptions) -> Self {
+        let mut state = FileTreeState::default();
+        state.expanded_nodes.insert(root_node.name().to_string());
+
+        let mut tree = FileTree {
+            canonical_root: paths::normalize(open),
+            file_root: Box::from(open.to_owned()),
+            nodes: arena(root_node),
+            state,
+            options,
+            rows: Vec::new(),
+            more_labels: HashMap::new(),
+            generation: 0,
+        };
+        tree.flatten();
+        tree
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.file_root
+    }
+
+    /// The root with links resolved, as node paths start with.
+    pub fn canonical_root(&self) -> &Path {
+        &self.canonical_root
+    }
+
+    /// Filesystem path of a node in this tree.
+    pub fn node_path(&self, node: NodeRef) -> PathBuf {
+        let mut path = PathBuf::new();
+        self.write_node_path(node.id, &mut path);
+        path
+    }
+
+    /// Puts node `id`'s filesystem path in `out`, reusing its memory.
+    fn write_node_path(&self, id: u32, out: &mut PathBuf) {
+        out.clone_from(&self.canonical_root);
+        // The root's own name is already the last part of the root path.
+        self.each_name(id, &mut |depth, name| {
+            if depth > 0 {
+                out.push(name);
+            }
+        });
+    }
+
+    /// Calls `f` with the depth and name of node `id` and each of its
+    /// parents, root first.
+    fn each_name(&self, id: u32, f: &mut impl FnMut(usize, &str)) -> usize {
+        let entry = &self.nodes[id as usize];
+        let depth = match entry.parent {
+            Some(parent) => self.each_name(parent, f) + 1,
+            None => 0,
+        };
+        f(depth, &entry.name);
+        depth
+    }
+
+    /// The node for the file at `path`, however it's spelled, if the tree
+    /// has one.
+    pub fn find_path(&self, path: &Path) -> Option<NodeRef<'_>> {
+        let path = paths::normalize(path);
+        let relative = path.strip_prefix(&self.canonical_root).ok()?;
+        let mut spelled = self.nodes.first()?.name.to_string();
+        for name in relative.iter() {
+            spelled.push_str(NAME_SEP);
+            spelled.push_str(name.to_str()?);
         }
+        self.find(&spelled).map(|id| self.node(id))
+    }
+
+    fn path(&self, id: u32) -> String {
+        let mut path = String::new();
+        self.write_path(id, &mut path);
+        path
+    }
+
+    /// Puts node `id`'s path, as [`NodeRef::path`] spells it, in `out`,
+    /// reusing its memory.
+    fn write_path(&self, id: u32, out: &mut String) {
+        out.clear();
+        self.each_name(id, &mut |depth, name| {
+            if depth > 0 {
+                out.push_str(NAME_SEP);
+            }
+            out.push_str(name);
+        });
+    }
+
+    fn node(&self, id: u32) -> NodeRef<'_> {
+        NodeRef { tree: self, id }
     }
 
     pub fn state(&mut self) -> &mut FileTreeState {
+        self.generation += 1;
         &mut self.state
     }
 
-    fn to_list_with_limit<'a>(self: &'a Self, limit: u16) -> Vec<&'a FileNode> {
-        let mut i = 0;
-        let mut nodes: Vec<&'a FileNode> = Vec::new();
-        nodes.push(&self.root_node);
-        while i < limit && (i as usize) < nodes.len() {
-            let next = nodes[i as usize];
-            if next.has_children() && self.state.expanded_nodes.contains(next.path()) {
-                match next {
-                    FileNode::Directory(_, c) => {
-                        for n in c {
-                            nodes.push(&n);
-                        }
-                    }
-                    _ => (),
-                };
+    /// The expanded directories, selection and marks, for saving.
+    pub fn snapshot(&self) -> FileTreeState {
+        self.state.clone()
+    }
+
+    /// Takes on `state`, saved earlier from a tree of the same root.
+    pub fn set_state(&mut self, state: FileTreeState) {
+        self.state = state;
+        self.flatten();
+        self.clamp_selection();
+    }
+
+    /// A number that changes whenever what the tree shows may have, so
+    /// that it's only drawn again then.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Whether switching to `options` changes which files are listed, so the
+    /// tree has to be rescanned.
+    pub fn needs_rescan(&self, options: &TreeOptions) -> bool {
+        options.show_hidden != self.options.show_hidden
+            || options.ignore != self.options.ignore
+            || options.max_entries != self.options.max_entries
+    }
+
+    /// Applies new options. Ones that change which files are listed only take
+    /// effect after a [`rescan`](Self::rescan) or
+    /// [`set_root_node`](Self::set_root_node).
+    pub fn set_options(&mut self, options: TreeOptions) {
+        self.generation += 1;
+        self.options = options;
+    }
+
+    /// Rebuilds the nodes from the filesystem. Expanded directories are kept.
+    pub fn rescan(&mut self) -> anyhow::Result<()> {
+        let root_node = FileNode::new_from_path_with(&self.file_root, &self.options)?;
+        self.set_root_node(root_node);
+        Ok(())
+    }
+
+    /// Replaces the nodes with ones scanned elsewhere, e.g. on a background
+    /// thread. Expanded directories are kept, and directories loaded in
+    /// full are loaded again.
+    pub fn set_root_node(&mut self, root_node: FileNode) {
+        self.nodes = arena(root_node);
+        let loaded: Vec<String> = self.state.loaded.iter().cloned().collect();
+        for path in loaded {
+            let id = self.find(&path).filter(|&id| self.is_cut_short(id));
+            if let Some(id) = id {
+                if let Err(e) = self.load_in_full(id) {
+                    tracing::warn!(path, error = %e, "could not load directory in full");
+                    self.state.loaded.remove(&path);
+                }
+            }
+        }
+        self.flatten();
+        self.clamp_selection();
+    }
+
+    /// The node at `path`, as [`NodeRef::path`] spells it.
+    fn find(&self, path: &str) -> Option<u32> {
+        let mut names = path.split(NAME_SEP);
+        if names.next()? != &*self.nodes.first()?.name {
+            return None;
+        }
+        names.try_fold(0, |id, name| match &self.nodes[id as usize].kind {
+            EntryKind::Directory(children) => children
+                .clone()
+                .find(|&child| &*self.nodes[child as usize].name == name),
+            _ => None,
+        })
+    }
+
+    /// Whether directory `id` has entries left out by `max_entries`.
+    fn is_cut_short(&self, id: u32) -> bool {
+        match &self.nodes[id as usize].kind {
+            EntryKind::Directory(children) => children
+                .clone()
+                .any(|child| matches!(self.nodes[child as usize].kind, EntryKind::More(_))),
+            _ => false,
+        }
+    }
+
+    /// Rescans directory `id` with all of its entries.
+    fn load_in_full(&mut self, id: u32) -> Result<()> {
+        let path = self.node_path(self.node(id));
+        let node = FileNode::scan_in_full(&path, &self.options)?;
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = arena(unarena(nodes, id, node));
+        Ok(())
+    }
+
+    /// Loads the entries the selected row stands in for, if it's a
+    /// directory's “more” row. Returns how many there were.
+    pub fn load_selected(&mut self) -> anyhow::Result<Option<usize>> {
+        let node = match self.selected_node() {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+        let (count, parent) = match (node.more(), node.entry().parent) {
+            (Some(count), Some(parent)) => (count, parent),
+            _ => return Ok(None),
+        };
+        let path = self.path(parent);
+        self.load_in_full(parent)?;
+        self.state.loaded.insert(path);
+        self.flatten();
+        Ok(Some(count))
+    }
+
+    pub fn selected(&self) -> usize {
+        self.state.selected
+    }
+
+    pub fn selected_node(&self) -> Option<NodeRef<'_>> {
+        let id = *self.rows.get(self.state.selected)?;
+        Some(self.node(id))
+    }
+
+    pub fn select_next(&mut self) {
+        self.generation += 1;
+        if self.state.selected + 1 < self.rows.len() {
+            self.state.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.generation += 1;
+        self.state.selected = self.state.selected.saturating_sub(1);
+    }
+
+    /// Expands the selected directory.
+    pub fn expand_selected(&mut self) {
+        let path = self
+            .selected_node()
+            .filter(|n| n.has_children())
+            .map(|n| n.path());
+        if let Some(path) = path {
+            if self.state.expanded_nodes.insert(path) {
+                self.flatten();
+            }
+        }
+    }
+
+    /// Collapses the selected directory, or moves the selection to the parent
+    /// directory when it is already collapsed.
+    pub fn collapse_selected(&mut self) {
+        let (path, parent) = match self.selected_node() {
+            Some(node) => (node.path(), node.entry().parent),
+            None => return,
+        };
+
+        if self.state.expanded_nodes.remove(&path) {
+            self.flatten();
+            return;
+        }
+
+        if let Some(parent) = parent {
+            self.generation += 1;
+            let above = &self.rows[..self.state.selected];
+            if let Some(i) = above.iter().rposition(|&id| id == parent) {
+                self.state.selected = i;
+            }
+        }
+    }
+
+    /// Expands every directory in the tree.
+    pub fn expand_all(&mut self) {
+        for (id, entry) in self.nodes.iter().enumerate() {
+            if let EntryKind::Directory(_) = entry.kind {
+                self.state.expanded_nodes.insert(self.path(id as u32));
             }
+        }
+        self.flatten();
+    }
+
+    pub fn toggle_selected(&mut self) {
+        let expanded = self
+            .selected_node()
+            .map(|n| self.state.expanded_nodes.contains(&n.path()));
+        match expanded {
+            Some(true) => self.collapse_selected(),
+            Some(false) => self.expand_selected(),
+            None => (),
+        }
+    }
 
-            i += 1;
+    /// Marks the selected file, or unmarks it if it is marked. Marking a
+    /// third file unmarks the oldest. Returns whether it is now marked.
+    pub fn toggle_mark_selected(&mut self) -> bool {
+        let file = |n: &NodeRef| !n.has_children() && n.more().is_none();
+        let path = match self.selected_node().filter(file) {
+            Some(node) => node.path(),
+            None => return false,
+        };
+        self.generation += 1;
+        if let Some(i) = self.state.marked.iter().position(|p| *p == path) {
+            self.state.marked.remove(i);
+            return false;
         }
+        if self.state.marked.len() == MAX_MARKS {
+            self.state.marked.remove(0);
+        }
+        self.state.marked.push(path);
+        true
+    }
+
+    /// Filesystem paths of the marked files, oldest first.
+    pub fn marked(&self) -> Vec<PathBuf> {
+        let root = &self.canonical_root;
+        let parent = root.parent().unwrap_or(root);
+        self.state.marked.iter().map(|p| parent.join(p)).collect()
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.generation += 1;
+        self.state.marked.clear();
+    }
+
+    fn clamp_selection(&mut self) {
+        self.generation += 1;
+        let len = self.rows.len();
+        self.state.selected = self.state.selected.min(len.saturating_sub(1));
+    }
+
+    /// Rebuilds the rows from the nodes and the expanded directories.
+    fn flatten(&mut self) {
+        self.generation += 1;
+        self.rows.clear();
+        self.more_labels.clear();
+        if self.nodes.is_empty() {
+            return;
+        }
+        // Directories' paths, for looking them up in the expanded ones, are
+        // built as the walk goes down.
+        let mut stack = vec![(0, self.nodes[0].name.to_string())];
+        while let Some((id, path)) = stack.pop() {
+            self.rows.push(id);
+
+            match &self.nodes[id as usize].kind {
+                EntryKind::More(count) => {
+                    let label = format!("{} more (press L to load)", thousands(*count));
+                    self.more_labels.insert(id, label.into());
+                }
+                EntryKind::Directory(children) if self.state.expanded_nodes.contains(&path) => {
+                    stack.extend(children.clone().rev().map(|child| {
+                        let name = &self.nodes[child as usize].name;
+                        (child, format!("{}{}{}", path, NAME_SEP, name))
+                    }));
+                }
+                _ => (),
+            }
+        }
+    }
 
-        nodes
+    /// How many nodes are shown: the expanded part of the tree.
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
     }
+
+    /// The nodes shown on rows `range`, in display order, without walking
+    /// the rest of the tree.
+    pub fn visible(&self, range: Range<usize>) -> impl DoubleEndedIterator<Item = NodeRef<'_>> {
+        let end = range.end.min(self.rows.len());
+        let start = range.start.min(end);
+        self.rows[start..end].iter().map(|&id| self.node(id))
+    }
+
+    /// The first row shown in a view `height` rows tall: the view scrolls
+    /// just far enough to show the selected node.
+    pub fn scroll_offset(&self, height: usize) -> usize {
+        Viewport::showing(self.state.selected, height).offset()
+    }
+
+    /// The first `limit` nodes shown, in display order.
+    pub fn to_list_with_limit(&self, limit: usize) -> Vec<NodeRef<'_>> {
+        self.visible(0..limit).collect()
+    }
+}
+
+/// Renders a [`FileTree`], scrolled so that the selected node is visible.
+pub struct FileTreeView<'a> {
+    tree: &'a FileTree,
+    style: Style,
+    highlight_style: Style,
+    mark_style: Style,
+    metadata: Option<&'a MetadataCache>,
+    git: Option<&'a GitBadges>,
+    plain: bool,
 }
 
-impl StatefulWidget for FileTree {
-    type State = FileTreeState;
+impl<'a> FileTreeView<'a> {
+    pub fn new(tree: &'a FileTree) -> Self {
+        FileTreeView {
+            tree,
+            style: Style::default(),
+            highlight_style: Style::default(),
+            mark_style: Style::default(),
+            metadata: None,
+            git: None,
+            plain: false,
+        }
+    }
+
+    /// Draws the markers before names and the `… more` rows in plain
+    /// ASCII, which screen readers read out as they are.
+    pub fn plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    /// Style of marked files, patched over the others.
+    pub fn mark_style(mut self, style: Style) -> Self {
+        self.mark_style = style;
+        self
+    }
+
+    /// Where the [`details`](TreeOptions::details) columns get their
+    /// metadata; without it they aren't shown.
+    pub fn metadata(mut self, metadata: &'a MetadataCache) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Where the `git` column gets its letters.
+    pub fn git(mut self, badges: &'a GitBadges) -> Self {
+        self.git = Some(badges);
+        self
+    }
+}
 
-    fn render(
-        self,
-        area: tui::layout::Rect,
-        buf: &mut tui::buffer::Buffer,
-        state: &mut Self::State,
-    ) {
+impl Widget for FileTreeView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
         if area.width < 1 || area.height < 1 {
             return;
         }
+        buf.set_style(area, self.style);
 
-        let h = area.height;
-        let w = area.width;
-        let list = self.to_list_with_limit(h);
+        // Detail columns go at the right edge, each after a space, if
+        // there's room left for names.
+        let details = match self.metadata {
+            Some(_) => self.tree.options.details.as_slice(),
+            None => &[],
+        };
+        let mut details_width = details.iter().fold(0u16, |sum, d| {
+            sum.saturating_add(d.width().saturating_add(1))
+        });
+        if details_width.saturating_add(MIN_NAME_WIDTH) > area.width {
+            details_width = 0;
+        }
+        let now = SystemTime::now();
+        // Reused from row to row, so drawing allocates about the same
+        // however many rows there are.
+        let (mut path, mut file, mut text) = (String::new(), PathBuf::new(), String::new());
 
-        for i in 0..h {
-            if usize::from(i) >= list.len() { break; }
-            let start_idx = (i as u64) * (w as u64);
-            let node = list[i as usize];
-            let indent = node.depth();
-            let name = node.name();
+        let h = area.height as usize;
+        let selected = self.tree.state.selected;
+        let offset = self.tree.scroll_offset(h);
+        for (i, node) in (offset..).zip(self.tree.visible(offset..offset + h)) {
+            let y = area.y + (i - offset) as u16;
+            // Paths are built only for what needs one: directories, and
+            // files while some are marked.
+            let has_path = node.has_children() || !self.tree.state.marked.is_empty();
+            if has_path {
+                self.tree.write_path(node.id, &mut path);
+            }
+            let marked = has_path && self.tree.state.marked.contains(&path);
+            let mut style = if i == selected {
+                self.highlight_style
+            } else {
+                self.style
+            };
+            if marked {
+                style = style.patch(self.mark_style);
+            }
+            let label = match self.tree.more_labels.get(&node.id) {
+                Some(label) => label,
+                None => node.name(),
+            };
+            let marker = match node.has_children() {
+                true => match self.tree.state.expanded_nodes.contains(&path) {
+                    true => Marker::Expanded,
+                    false => Marker::Collapsed,
+                },
+                false if node.more().is_some() => Marker::More,
+                false if marked => Marker::Marked,
+                false => Marker::Leaf,
+            };
+            let row = Row {
+                depth: (node.depth() - 1).into(),
+                marker,
+                label,
+                style,
+                selected: i == selected,
+            };
+            let line = Rect::new(area.x, y, area.width, 1);
+            row.render(
+                line,
+                buf,
+                self.tree.options.indent,
+                details_width,
+                self.plain,
+            );
 
-            for iw in 0..w {
-                if iw < indent { continue; }
-                if iw + indent > name.len() as u16 { break; }
-                let fx = area.x + iw;
-                let fy = area.y + i;
-                let cell = &mut buf.content[((fx * fy) as u64 + start_idx) as usize];
-                cell.symbol.push_str("X");
+            if let (Some(metadata), true) = (self.metadata, details_width > 0) {
+                let (info, badge) = match node.more() {
+                    Some(_) => (None, None),
+                    None => {
+                        self.tree.write_node_path(node.id, &mut file);
+                        let stats = details.iter().any(|&d| d != Detail::Git);
+                        let info = stats.then(|| metadata.peek(&file)).flatten();
+                        (info, self.git.and_then(|git| git.badge(&file)))
+                    }
+                };
+                let mut x = area.right() - details_width;
+                for &detail in details {
+                    text.clear();
+                    match (detail, &info, badge) {
+                        (Detail::Git, _, Some(badge)) => text.push(badge),
+                        (_, Some(info), _) => detail.write_text(info, now, &mut text),
+                        _ => (),
+                    }
+                    let width = detail.width() as usize;
+                    let pad = (width + 1).saturating_sub(text.chars().count());
+                    buf.set_stringn(x, y, &BLANK[..pad], pad, style);
+                    buf.set_stringn(x + pad as u16, y, &text, width + 1 - pad, style);
+                    x += detail.width() + 1;
+                }
             }
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+/// `12431` as `12,431`.
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Renders a tree directly with default styles; use [`FileTreeView`] to
+/// style it.
+impl Widget for &FileTree {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        FileTreeView::new(self).render(area, buf);
+    }
+}
+
+/// A scanned directory tree, each node holding only its own name. A
+/// [`FileTree`] stores it more compactly once it's built.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileNode {
     Directory(String, Vec<FileNode>),
     File(String),
     Link(String, Box<Path>),
+    /// Stands in for the entries of a directory past
+    /// [`max_entries`](TreeOptions::max_entries), with how many there are.
+    More(usize),
 }
 
 impl FileNode {
-    pub fn new_from_path(path: &Path) -> Result<FileNode, io::Error> {
-        let path = fs::canonicalize(path)?;
-        FileNode::new_recursive(&path, None)
+    pub fn new_from_path(path: &Path) -> Result<FileNode> {
+        let options = TreeOptions {
+            show_hidden: true,
+            ..Default::default()
+        };
+        FileNode::new_from_path_with(path, &options)
     }
 
-    fn new_recursive(path: &Path, parent: Option<&str>) -> Result<FileNode, io::Error> {
-        let path_name = path.file_name();
-        if path_name.is_none() || path_name.unwrap().to_str().is_none() {
-            return Err(io::Error::new(io::ErrorKind::Other, ""));
-        }
+    pub fn new_from_path_with(path: &Path, options: &TreeOptions) -> Result<FileNode> {
+        FileNode::scan(path, options, &|_| true)
+    }
 
-        let mut path_name = path_name.unwrap().to_str().unwrap().to_string();
-        if parent.is_some() {
-            path_name = parent.unwrap().to_string() + NAME_SEP + &path_name;
+    /// Like [`new_from_path_with`](Self::new_from_path_with), but calls `visit`
+    /// for every entry. Returning `false` from it aborts the scan with an
+    /// [`Error::Cancelled`]. Directories are read in
+    /// parallel, so `visit` is called from several threads at once.
+    pub fn scan(
+        path: &Path,
+        options: &TreeOptions,
+        visit: &(dyn Fn(&Path) -> bool + Sync),
+    ) -> Result<FileNode> {
+        let path = fs::canonicalize(path).map_err(Error::io("open", path))?;
+        FileNode::new_recursive(&path, options, options.max_entries, usize::MAX, visit)
+    }
+
+    /// Scans only `path`'s own entries. The directories among them are
+    /// listed without children, to be filled in by a full scan later.
+    pub fn scan_shallow(path: &Path, options: &TreeOptions) -> Result<FileNode> {
+        let path = fs::canonicalize(path).map_err(Error::io("open", path))?;
+        FileNode::new_recursive(&path, options, options.max_entries, 1, &|_| true)
+    }
+
+    /// Scans `path` with all of its entries, however many there are. The
+    /// directories under it are still cut short at `max_entries`.
+    pub fn scan_in_full(path: &Path, options: &TreeOptions) -> Result<FileNode> {
+        let path = fs::canonicalize(path).map_err(Error::io("open", path))?;
+        FileNode::new_recursive(&path, options, 0, usize::MAX, &|_| true)
+    }
+
+    fn new_recursive(
+        path: &Path,
+        options: &TreeOptions,
+        max_entries: usize,
+        levels: usize,
+        visit: &(dyn Fn(&Path) -> bool + Sync),
+    ) -> Result<FileNode> {
+        if !visit(path) {
+            return Err(Error::Cancelled);
         }
 
+        let path_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => return Err(Error::NotUtf8 { path: path.into() }),
+        };
+
         if path.is_file() {
             Ok(FileNode::File(path_name))
+        } else if path.is_dir() && levels == 0 {
+            Ok(FileNode::Directory(path_name, Vec::new()))
         } else if path.is_dir() {
-            let mut nodes = fs::read_dir(path)?
-                .map(|d| FileNode::new_recursive(&d?.path(), Some(&path_name)))
-                .filter(|pr| pr.is_ok())
-                .map(|pr| pr.unwrap())
-                .collect::<Vec<FileNode>>();
-            nodes.sort();
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)
+                .map_err(Error::io("read directory", path))?
+                .flatten()
+                .filter(|entry| !options.hides(&entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .collect();
+            // Past the limit, the first entries by name are listed and the
+            // rest only counted, rather than scanning all of them.
+            let more = match max_entries {
+                0 => 0,
+                max => entries.len().saturating_sub(max),
+            };
+            if more > 0 {
+                entries.sort_by(|a, b| {
+                    let (a, b) = (
+                        a.file_name().unwrap_or_default(),
+                        b.file_name().unwrap_or_default(),
+                    );
+                    options
+                        .sort
+                        .compare(&a.to_string_lossy(), &b.to_string_lossy())
+                });
+                entries.truncate(max_entries);
+            }
+            // Subdirectories are scanned by rayon's work-stealing pool, so a
+            // few huge ones don't hold up the rest.
+            let scanned: Vec<_> = entries
+                .par_iter()
+                .map(|entry| {
+                    FileNode::new_recursive(entry, options, options.max_entries, levels - 1, visit)
+                })
+                .collect();
+            let mut nodes = Vec::with_capacity(scanned.len());
+            for node in scanned {
+                match node {
+                    Ok(node) => nodes.push(node),
+                    Err(Error::Cancelled) => return Err(Error::Cancelled),
+                    // Unreadable entries are left out rather than failing the scan.
+                    Err(_) => (),
+                }
+            }
+            nodes.sort_by(|a, b| a.compare(b, &options.sort));
+            if more > 0 {
+                nodes.push(FileNode::More(more));
+            }
 
             Ok(FileNode::Directory(path_name, nodes))
         } else if path.is_symlink() {
-            Ok(FileNode::Link(
-                path_name,
-                Box::from(fs::read_link(path)?.as_path()),
-            ))
+            let target = fs::read_link(path).map_err(Error::io("read link", path))?;
+            Ok(FileNode::Link(path_name, target.into_boxed_path()))
         } else {
-            Err(io::Error::new(io::ErrorKind::Other, ""))
-        }
-    }
-
-    pub fn path(&self) -> &str {
-        match self {
-            FileNode::Directory(full_name, _)
-            | FileNode::File(full_name)
-            | FileNode::Link(full_name, _) => full_name,
+            Err(Error::Unsupported { path: path.into() })
         }
     }
 
     pub fn name(&self) -> &str {
         match self {
-            FileNode::Directory(full_name, _)
-            | FileNode::File(full_name)
-            | FileNode::Link(full_name, _) => {
-                full_name.rsplit(NAME_SEP).next().unwrap_or(full_name)
-            }
+            FileNode::Directory(name, _) | FileNode::File(name) | FileNode::Link(name, _) => name,
+            FileNode::More(_) => "",
         }
     }
 
     pub fn has_children(&self) -> bool {
-        match self {
-            &FileNode::Directory(_, _) => true,
-            _ => false,
-        }
+        matches!(self, FileNode::Directory(_, _))
     }
 
-    pub fn depth(&self) -> u16 {
-        match self {
-            FileNode::Directory(path, _) | FileNode::File(path) | FileNode::Link(path, _) => {
-                path.split(NAME_SEP).count().try_into().unwrap()
+    /// Directories first, then files, then links, each in `sort`'s order
+    /// by name, and the row standing in for the rest of a directory last.
+    pub fn compare(&self, other: &FileNode, sort: &SortOptions) -> std::cmp::Ordering {
+        let rank = |node: &FileNode| match node {
+            FileNode::Directory(_, _) => 0,
+            FileNode::File(_) => 1,
+            FileNode::Link(_, _) => 2,
+            FileNode::More(_) => 3,
+        };
+        rank(self)
+            .cmp(&rank(other))
+            .then_with(|| sort.compare(self.name(), other.name()))
+    }
+
+    /// Writes the node and everything under it as an indented listing, with
+    /// `/` after directories and `-> target` after links.
+    pub fn write_text(&self, out: &mut dyn io::Write, indent: u16) -> io::Result<()> {
+        let mut stack = vec![(self, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            let pad = " ".repeat(usize::from(indent) * depth);
+            match node {
+                FileNode::Directory(_, children) => {
+                    writeln!(out, "{}{}/", pad, node.name())?;
+                    stack.extend(children.iter().rev().map(|child| (child, depth + 1)));
+                }
+                FileNode::File(_) => writeln!(out, "{}{}", pad, node.name())?,
+                FileNode::Link(_, target) => {
+                    writeln!(out, "{}{} -> {}", pad, node.name(), target.display())?
+                }
+                FileNode::More(count) => writeln!(out, "{}… {} more", pad, thousands(*count))?,
             }
         }
+        Ok(())
     }
 }
 
@@ -178,12 +1171,43 @@ fn test_node_build() {
     let n = FileNode::new_from_path(Path::new("./src/widgets")).unwrap();
     match n {
         FileNode::Directory(_, contents) => {
-            assert!(contents.contains(&FileNode::File("widgets/file_tree.rs".to_string())))
+            assert!(contents.contains(&FileNode::File("file_tree.rs".to_string())))
         }
         _ => panic!(),
     }
 }
 
+#[test]
+fn test_scan_is_sorted_and_cancellable() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let options = TreeOptions::default();
+    match FileNode::scan(Path::new("./src"), &options, &|_| true).unwrap() {
+        FileNode::Directory(_, contents) => {
+            let sorted = |pair: &[FileNode]| pair[0].compare(&pair[1], &options.sort).is_lt();
+            assert!(contents.windows(2).all(sorted));
+        }
+        _ => panic!(),
+    }
+
+    // Numbers in names sort by value, and case is folded.
+    let dir = std::env::temp_dir().join(format!("wyv-tree-sort-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join("Sub")).unwrap();
+    for name in ["file10", "file2", "File1", "a"] {
+        fs::write(dir.join(name), "").unwrap();
+    }
+    let tree = FileTree::new(&dir).unwrap();
+    let names: Vec<_> = tree.visible(1..6).map(|node| node.name()).collect();
+    assert_eq!(names, ["Sub", "a", "File1", "file2", "file10"]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let visited = AtomicUsize::new(0);
+    let result = FileNode::scan(Path::new("./src"), &options, &|_| {
+        visited.fetch_add(1, Ordering::Relaxed) < 5
+    });
+    assert!(matches!(result, Err(Error::Cancelled)));
+}
+
 #[test]
 fn test_tree_save_and_load() {
     let n = FileTree::new(Path::new("./src/widgets")).unwrap();
@@ -191,3 +1215,349 @@ fn test_tree_save_and_load() {
     let sn = serde_json::from_str::<FileTree>(&s).unwrap();
     assert_eq!(n, sn)
 }
+
+#[test]
+fn test_depth_is_counted_from_the_root_whatever_its_path() {
+    let mut tree = FileTree::new(Path::new("./src/../src/widgets")).unwrap();
+    tree.expand_all();
+    let root = tree.to_list_with_limit(1)[0];
+    assert_eq!(root.depth(), 1);
+    assert!(tree.row_count() > 1);
+    for node in tree.visible(0..tree.row_count()) {
+        let names = node.path().split(NAME_SEP).count();
+        assert_eq!(usize::from(node.depth()), names, "{}", node.path());
+    }
+
+    // Depths aren't saved, but set again on loading.
+    let saved = serde_json::to_string(&tree).unwrap();
+    let loaded = serde_json::from_str::<FileTree>(&saved).unwrap();
+    let depths = |tree: &FileTree| {
+        tree.visible(0..tree.row_count())
+            .map(|n| n.depth())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(depths(&loaded), depths(&tree));
+}
+
+#[test]
+fn test_saved_trees_are_versioned_and_read_leniently() {
+    let tree = FileTree::new(Path::new("./src/widgets")).unwrap();
+    let mut saved = serde_json::to_value(&tree).unwrap();
+    assert_eq!(saved["version"], FORMAT_VERSION);
+
+    // Trees saved before the version tag read as the first version.
+    saved.as_object_mut().unwrap().remove("version");
+    let loaded: FileTree = serde_json::from_value(saved.clone()).unwrap();
+    assert_eq!(loaded, tree);
+
+    // Nodes that no longer read leave the root to be scanned again, and
+    // the rest of the tree as it was.
+    saved["nodes"] = serde_json::json!([{ "name": 3 }]);
+    saved["state"]["selected"] = serde_json::json!(2);
+    let loaded: FileTree = serde_json::from_value(saved.clone()).unwrap();
+    assert_eq!(loaded.row_count(), 1);
+    assert_eq!(loaded.to_list_with_limit(1)[0].name(), "widgets");
+    assert_eq!(loaded.state.selected, 2);
+
+    // So do nodes that point outside the tree or before their parents.
+    let mut nodes = serde_json::to_value(&tree.nodes).unwrap();
+    nodes[0]["kind"]["Directory"]["end"] = serde_json::json!(10_000);
+    saved["nodes"] = nodes.clone();
+    let loaded: FileTree = serde_json::from_value(saved.clone()).unwrap();
+    assert_eq!(loaded.row_count(), 1);
+    nodes[0]["kind"] = serde_json::json!("File");
+    nodes[1]["parent"] = serde_json::json!(5);
+    saved["nodes"] = nodes;
+    let loaded: FileTree = serde_json::from_value(saved.clone()).unwrap();
+    assert_eq!(loaded.row_count(), 1);
+
+    saved["version"] = serde_json::json!(FORMAT_VERSION + 1);
+    let e = serde_json::from_value::<FileTree>(saved).unwrap_err();
+    assert!(e.to_string().starts_with("saved by a newer wyv"), "{}", e);
+}
+
+#[test]
+fn test_navigation_follows_display_order() {
+    let mut tree = FileTree::new(Path::new("./src")).unwrap();
+    assert_eq!(tree.selected_node().unwrap().name(), "src");
+
+    let first_child = tree.to_list_with_limit(2)[1].name().to_string();
+    tree.select_next();
+    assert_eq!(tree.selected_node().unwrap().name(), first_child);
+
+    // Expanding `widgets` puts its children directly below it.
+    while tree.selected_node().unwrap().name() != "widgets" {
+        tree.select_next();
+    }
+    let widgets_idx = tree.selected();
+    tree.expand_selected();
+    tree.select_next();
+    assert!(tree.selected_node().unwrap().path().ends_with(".rs"));
+
+    // Collapsing a file moves up to its directory, then collapses that.
+    tree.collapse_selected();
+    assert_eq!(tree.selected(), widgets_idx);
+    tree.collapse_selected();
+    tree.select_next();
+    assert!(!tree.selected_node().unwrap().path().contains("widgets/"));
+}
+
+#[test]
+fn test_ignore_patterns_hide_entries() {
+    let options = TreeOptions {
+        ignore: vec!["*_tree.rs".to_string(), "mo?.rs".to_string()],
+        ..Default::default()
+    };
+    assert!(options.hides(".git"));
+    assert!(options.hides("file_tree.rs"));
+    assert!(!options.hides("editor.rs"));
+    assert!(!glob_match("*.rs", "rs"));
+    assert!(glob_match("a*b*c", "aXbYbc"));
+
+    match FileNode::new_from_path_with(Path::new("./src/widgets"), &options).unwrap() {
+        FileNode::Directory(_, contents) => {
+            let names: Vec<&str> = contents.iter().map(|n| n.name()).collect();
+            assert!(names.contains(&"editor.rs"));
+            assert!(!names.contains(&"file_tree.rs") && !names.contains(&"mod.rs"));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn test_marks_keep_the_last_two_files() {
+    let mut tree = FileTree::new(Path::new("./src/widgets")).unwrap();
+    assert!(!tree.toggle_mark_selected());
+    tree.expand_selected();
+    let mut marked = Vec::new();
+    for _ in 0..3 {
+        tree.select_next();
+        assert!(tree.toggle_mark_selected());
+        marked.push(tree.node_path(tree.selected_node().unwrap()));
+    }
+    assert_eq!(tree.marked(), &marked[1..]);
+    assert!(!tree.toggle_mark_selected());
+    assert_eq!(tree.marked(), &marked[1..2]);
+
+    let area = Rect::new(0, 0, 30, 8);
+    let mut buf = Buffer::empty(area);
+    FileTreeView::new(&tree).render(area, &mut buf);
+    let row: String = (0..30).map(|x| buf[(x, 2)].symbol()).collect();
+    assert!(row.starts_with("  ● "));
+    tree.clear_marks();
+    assert!(tree.marked().is_empty());
+}
+
+#[test]
+fn test_huge_indents_push_names_off_the_edge() {
+    let mut tree = FileTree::new(Path::new("./src")).unwrap();
+    tree.options.indent = u16::MAX;
+    tree.expand_selected();
+    tree.select_next();
+    tree.expand_selected();
+    let area = Rect::new(0, 0, 20, 6);
+    let mut buf = Buffer::empty(area);
+    FileTreeView::new(&tree).render(area, &mut buf);
+    // Nested rows are left blank rather than overflowing.
+    let row: String = (0..20).map(|x| buf[(x, 1)].symbol()).collect();
+    assert_eq!(row.trim(), "");
+}
+
+#[test]
+fn test_expand_all_lists_every_node() {
+    let mut tree = FileTree::new(Path::new("./src")).unwrap();
+    tree.expand_all();
+    let all = tree.to_list_with_limit(usize::MAX);
+    assert!(all.iter().any(|n| n.path() == "src/widgets/file_tree.rs"));
+    assert_eq!(tree.to_list_with_limit(3).len(), 3);
+}
+
+#[test]
+fn test_rows_follow_expanding_and_collapsing() {
+    let mut tree = FileTree::new(Path::new("./src")).unwrap();
+    let top = tree.row_count();
+    while tree.selected_node().unwrap().name() != "widgets" {
+        tree.select_next();
+    }
+    let widgets = tree.selected();
+    tree.expand_selected();
+    let children = tree.row_count() - top;
+    assert!(children > 0);
+
+    let window: Vec<String> = tree
+        .visible(widgets..widgets + 2)
+        .map(|n| n.path())
+        .collect();
+    assert_eq!(window.len(), 2);
+    assert!(window[1].starts_with(&window[0]));
+    assert_eq!(tree.visible(tree.row_count() - 1..usize::MAX).count(), 1);
+    assert_eq!(tree.visible(usize::MAX - 1..usize::MAX).count(), 0);
+
+    // Rescanning keeps the expanded directories, and loading rebuilds the
+    // rows from them.
+    tree.rescan().unwrap();
+    assert_eq!(tree.row_count(), top + children);
+    let saved = serde_json::to_string(&tree).unwrap();
+    let loaded = serde_json::from_str::<FileTree>(&saved).unwrap();
+    let paths = |tree: &FileTree| {
+        let rows = tree.visible(0..usize::MAX);
+        rows.map(|n| n.path()).collect::<Vec<_>>()
+    };
+    assert_eq!(paths(&loaded), paths(&tree));
+
+    tree.collapse_selected();
+    assert_eq!(tree.row_count(), top);
+}
+
+#[test]
+fn test_write_text_indents_children() {
+    let node = FileNode::Directory(
+        "src".to_string(),
+        vec![
+            FileNode::Directory(
+                "widgets".to_string(),
+                vec![FileNode::File("mod.rs".to_string())],
+            ),
+            FileNode::File("lib.rs".to_string()),
+        ],
+    );
+    let mut out = Vec::new();
+    node.write_text(&mut out, 2).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "src/\n  widgets/\n    mod.rs\n  lib.rs\n"
+    );
+}
+
+#[test]
+fn test_big_directories_are_cut_short() {
+    let dir = std::env::temp_dir().join(format!("wyv-tree-more-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    for i in 0..6 {
+        fs::write(dir.join(format!("f{}.txt", i)), "").unwrap();
+    }
+    let options = TreeOptions {
+        max_entries: 3,
+        ..Default::default()
+    };
+    let mut tree = FileTree::with_options(&dir, options).unwrap();
+    // The root, the first three entries by name and the row for the rest.
+    assert_eq!(tree.row_count(), 5);
+    let last = tree.to_list_with_limit(5)[4];
+    assert_eq!(last.more(), Some(4));
+
+    let area = Rect::new(0, 0, 40, 5);
+    let mut buf = Buffer::empty(area);
+    FileTreeView::new(&tree).render(area, &mut buf);
+    let row: String = (0..40).map(|x| buf[(x, 4)].symbol()).collect();
+    assert_eq!(row.trim_end(), "    … 4 more (press L to load)");
+    FileTreeView::new(&tree).plain(true).render(area, &mut buf);
+    let row: String = (0..40).map(|x| buf[(x, 4)].symbol()).collect();
+    assert_eq!(row.trim_end(), "    ... 4 more (press L to load)");
+
+    for _ in 0..4 {
+        tree.select_next();
+    }
+    assert!(!tree.toggle_mark_selected());
+    assert_eq!(tree.load_selected().unwrap(), Some(4));
+    assert_eq!(tree.row_count(), 8);
+    assert_eq!(tree.selected_node().unwrap().name(), "f2.txt");
+    tree.rescan().unwrap();
+    assert_eq!(tree.row_count(), 8);
+
+    assert_eq!(thousands(12431), "12,431");
+    assert_eq!(thousands(100), "100");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_detail_columns_follow_the_names() {
+    let dir = std::env::temp_dir().join(format!("wyv-tree-details-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("big.bin"), vec![0; 5000]).unwrap();
+    let options = TreeOptions {
+        details: vec![Detail::Size, Detail::Modified],
+        ..Default::default()
+    };
+    let tree = FileTree::with_options(&dir, options).unwrap();
+    let metadata = MetadataCache::default();
+
+    let area = Rect::new(0, 0, 24, 2);
+    let row = |buf: &Buffer, y| {
+        (0..buf.area.width)
+            .map(|x| buf[(x, y)].symbol())
+            .collect::<String>()
+    };
+    // Drawing only shows what's been fetched.
+    let mut buf = Buffer::empty(area);
+    FileTreeView::new(&tree)
+        .metadata(&metadata)
+        .render(area, &mut buf);
+    assert_eq!(row(&buf, 1).trim_end(), "    big.bin");
+    let paths = tree.visible(0..2).map(|node| tree.node_path(node));
+    metadata.fetch(&metadata.queue(paths));
+
+    let mut buf = Buffer::empty(area);
+    FileTreeView::new(&tree)
+        .metadata(&metadata)
+        .render(area, &mut buf);
+    assert!(row(&buf, 0).ends_with("      now"));
+    assert_eq!(row(&buf, 1), "    big.bin     4.9K now");
+
+    // Too narrow for the columns and a name, they're left out.
+    let area = Rect::new(0, 0, 16, 2);
+    let mut buf = Buffer::empty(area);
+    FileTreeView::new(&tree)
+        .metadata(&metadata)
+        .render(area, &mut buf);
+    assert_eq!(row(&buf, 1).trim_end(), "    big.bin");
+
+    // However many are configured, adding up their widths doesn't
+    // overflow.
+    let options = TreeOptions {
+        details: vec![Detail::Size; 20_000],
+        ..Default::default()
+    };
+    let tree = FileTree::with_options(&dir, options).unwrap();
+    let mut buf = Buffer::empty(area);
+    FileTreeView::new(&tree)
+        .metadata(&metadata)
+        .render(area, &mut buf);
+    assert_eq!(row(&buf, 1).trim_end(), "    big.bin");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_shallow_tree_fills_in_from_a_full_scan() {
+    let dir = std::env::temp_dir().join(format!("wyv-tree-shallow-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join("sub/deeper")).unwrap();
+    fs::write(dir.join("sub/inner.txt"), "").unwrap();
+    fs::write(dir.join("top.txt"), "").unwrap();
+
+    let mut tree = FileTree::shallow(&dir, TreeOptions::default()).unwrap();
+    assert_eq!(tree.row_count(), 3);
+    tree.select_next();
+    assert_eq!(tree.selected_node().unwrap().name(), "sub");
+    tree.expand_selected();
+    // Nothing under `sub` has been read yet.
+    assert_eq!(tree.row_count(), 3);
+
+    let full = FileNode::new_from_path_with(&dir, tree.options()).unwrap();
+    tree.set_root_node(full);
+    assert_eq!(tree.row_count(), 5);
+    assert_eq!(tree.selected_node().unwrap().name(), "sub");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_paths_find_the_same_node_however_spelled() {
+    let tree = FileTree::new(Path::new("./src/../src")).unwrap();
+    let node = tree
+        .find_path(Path::new("src/widgets/file_tree.rs"))
+        .unwrap();
+    assert_eq!(node.path(), "src/widgets/file_tree.rs");
+    let same = tree.find_path(Path::new("./src/../src/widgets/./file_tree.rs"));
+    assert_eq!(same.map(|n| n.path()), Some(node.path()));
+    assert_eq!(tree.find_path(Path::new("src")).unwrap().path(), "src");
+    assert!(tree.find_path(Path::new("Cargo.toml")).is_none());
+}
@@ -0,0 +1,139 @@
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+use super::tree_view::{TreeItem, TreeState};
+use crate::{
+    fuzzy,
+    git::{self, Commit},
+};
+
+/// How many commits the log reads.
+const LIMIT: usize = 1000;
+
+/// Commits of a repository, or of one file in it, narrowed down by a fuzzy
+/// filter, with one selected.
+#[derive(Debug)]
+pub struct GitLog {
+    root: PathBuf,
+    /// The file whose history this is, relative to `root`.
+    path: Option<String>,
+    commits: Vec<Commit>,
+    filter: String,
+    /// Whether keys go to the filter rather than moving the selection.
+    pub filtering: bool,
+    /// Which of the filtered commits is selected.
+    state: TreeState,
+}
+
+impl GitLog {
+    /// The log of the repository containing `dir`.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let root = git::repo_root(dir)?;
+        let commits = git::log(&root, None, LIMIT)?;
+        Ok(GitLog::new(root, None, commits))
+    }
+
+    /// The commits that touched the file at `path`.
+    pub fn file_history(path: &Path) -> Result<Self> {
+        let path = fs::canonicalize(path)?;
+        let root = git::repo_root(path.parent().unwrap_or(&path))?;
+        let relative = path
+            .strip_prefix(&root)
+            .map_err(|_| anyhow!("{} is outside the repository", path.display()))?
+            .to_string_lossy()
+            .into_owned();
+        let commits = git::log(&root, Some(&relative), LIMIT)?;
+        Ok(GitLog::new(root, Some(relative), commits))
+    }
+
+    fn new(root: PathBuf, path: Option<String>, commits: Vec<Commit>) -> Self {
+        GitLog {
+            root,
+            path,
+            commits,
+            filter: String::new(),
+            filtering: false,
+            state: TreeState::default(),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match &self.path {
+            Some(path) => format!("History of {}", path),
+            None => "Git log".to_string(),
+        }
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn push_filter(&mut self, c: char) {
+        self.filter.push(c);
+        self.state.select(0);
+    }
+
+    pub fn pop_filter(&mut self) {
+        self.filter.pop();
+        self.state.select(0);
+    }
+
+    /// The commits matching the filter, newest first.
+    pub fn visible(&self) -> Vec<&Commit> {
+        matching(&self.commits, &self.filter)
+    }
+
+    pub fn state(&self) -> &TreeState {
+        &self.state
+    }
+
+    pub fn selected(&self) -> Option<&Commit> {
+        self.visible().get(self.state.selected()).copied()
+    }
+
+    pub fn select_next(&mut self) {
+        let visible = matching(&self.commits, &self.filter);
+        self.state.select_next(&visible);
+    }
+
+    pub fn select_prev(&mut self) {
+        self.state.select_prev();
+    }
+
+    /// A buffer name and the details of the selected commit, with only the
+    /// diff of this log's file if it has one.
+    pub fn show_selected(&self) -> Result<(String, String)> {
+        let commit = self
+            .selected()
+            .ok_or_else(|| anyhow!("no commit selected"))?;
+        let text = git::show(&self.root, &commit.hash, self.path.as_deref())?;
+        let name = match &self.path {
+            Some(path) => format!("commit {} {}", commit.short_hash(), path),
+            None => format!("commit {}", commit.short_hash()),
+        };
+        Ok((name, text))
+    }
+}
+
+fn matching<'c>(commits: &'c [Commit], filter: &str) -> Vec<&'c Commit> {
+    commits
+        .iter()
+        .filter(|c| fuzzy::matches(filter, &c.describe()))
+        .collect()
+}
+
+/// Commits are listed flat, each a root of its own.
+impl TreeItem for &Commit {
+    fn id(&self) -> &str {
+        &self.hash
+    }
+
+    fn label(&self) -> Cow<'_, str> {
+        Cow::Owned(self.describe())
+    }
+}
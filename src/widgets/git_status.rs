@@ -0,0 +1,232 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+use super::viewport::Viewport;
+use crate::git::{self, Hunk, StatusEntry};
+
+/// The changed files of a repository, one of which can be expanded to show
+/// its hunks, with a selected row to stage or unstage.
+#[derive(Debug)]
+pub struct GitStatus {
+    root: PathBuf,
+    entries: Vec<StatusEntry>,
+    /// The expanded entry's path, with its hunks and whether each is staged.
+    expanded: Option<(String, Vec<(Hunk, bool)>)>,
+    selected: usize,
+}
+
+/// A line of the status list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Row<'a> {
+    Entry(&'a StatusEntry),
+    Hunk(&'a Hunk, bool),
+}
+
+impl GitStatus {
+    /// Status of the repository containing `dir`.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let root = git::repo_root(dir)?;
+        let entries = git::status(&root)?;
+        Ok(GitStatus {
+            root,
+            entries,
+            expanded: None,
+            selected: 0,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn entries(&self) -> &[StatusEntry] {
+        &self.entries
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Reads the status again, keeping the expanded file and the selection
+    /// where they still exist.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.entries = git::status(&self.root)?;
+        let expanded = self.expanded.take().map(|(path, _)| path);
+        if let Some(path) = expanded.filter(|p| self.entries.iter().any(|e| &e.path == p)) {
+            let hunks = self.load_hunks(&path)?;
+            self.expanded = Some((path, hunks));
+        }
+        self.selected = self.selected.min(self.rows().len().saturating_sub(1));
+        Ok(())
+    }
+
+    /// The rows in display order: each entry, followed by its hunks if it is
+    /// the expanded one.
+    pub fn rows(&self) -> Vec<Row<'_>> {
+        let mut rows = Vec::new();
+        for entry in &self.entries {
+            rows.push(Row::Entry(entry));
+            match &self.expanded {
+                Some((path, hunks)) if *path == entry.path => {
+                    rows.extend(hunks.iter().map(|(hunk, staged)| Row::Hunk(hunk, *staged)))
+                }
+                _ => (),
+            }
+        }
+        rows
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.rows().len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Shows or hides the hunks of the selected file.
+    pub fn toggle_expanded(&mut self) -> Result<()> {
+        let path = match self.rows().get(self.selected) {
+            Some(Row::Entry(entry)) => entry.path.clone(),
+            _ => return Ok(()),
+        };
+        if self.expanded.as_ref().is_some_and(|(p, _)| *p == path) {
+            self.expanded = None;
+        } else {
+            let hunks = self.load_hunks(&path)?;
+            self.expanded = Some((path, hunks));
+        }
+        Ok(())
+    }
+
+    fn load_hunks(&self, path: &str) -> Result<Vec<(Hunk, bool)>> {
+        let staged = git::hunks(&self.root, path, true)?;
+        let unstaged = git::hunks(&self.root, path, false)?;
+        Ok(staged
+            .into_iter()
+            .map(|h| (h, true))
+            .chain(unstaged.into_iter().map(|h| (h, false)))
+            .collect())
+    }
+
+    /// Stages the selected file or hunk.
+    pub fn stage(&mut self) -> Result<()> {
+        match self.rows().get(self.selected) {
+            Some(Row::Entry(entry)) => git::stage(&self.root, &entry.path)?,
+            Some(Row::Hunk(hunk, false)) => git::apply_to_index(&self.root, hunk, false)?,
+            Some(Row::Hunk(_, true)) => bail!("hunk is already staged"),
+            None => return Ok(()),
+        }
+        self.refresh()
+    }
+
+    /// Unstages the selected file or hunk.
+    pub fn unstage(&mut self) -> Result<()> {
+        match self.rows().get(self.selected) {
+            Some(Row::Entry(entry)) => git::unstage(&self.root, &entry.path)?,
+            Some(Row::Hunk(hunk, true)) => git::apply_to_index(&self.root, hunk, true)?,
+            Some(Row::Hunk(_, false)) => bail!("hunk is not staged"),
+            None => return Ok(()),
+        }
+        self.refresh()
+    }
+
+    /// Commits the staged changes, returning git's summary of the commit.
+    pub fn commit(&mut self, message: &str) -> Result<String> {
+        if message.trim().is_empty() {
+            bail!("empty commit message");
+        }
+        if !self.entries.iter().any(StatusEntry::is_staged) {
+            bail!("nothing staged to commit");
+        }
+        let summary = git::commit(&self.root, message)?;
+        self.refresh()?;
+        Ok(summary)
+    }
+}
+
+/// Renders a [`GitStatus`], scrolled so that the selected row is visible.
+pub struct GitStatusView<'a> {
+    status: &'a GitStatus,
+    style: Style,
+    highlight_style: Style,
+    staged_style: Style,
+}
+
+impl<'a> GitStatusView<'a> {
+    pub fn new(status: &'a GitStatus) -> Self {
+        GitStatusView {
+            status,
+            style: Style::default(),
+            highlight_style: Style::default(),
+            staged_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    /// Style of staged files and hunks.
+    pub fn staged_style(mut self, style: Style) -> Self {
+        self.staged_style = style;
+        self
+    }
+}
+
+impl Widget for GitStatusView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let rows = self.status.rows();
+        if rows.is_empty() {
+            buf.set_stringn(
+                area.x,
+                area.y,
+                "Nothing to commit, working tree clean",
+                area.width as usize,
+                self.style,
+            );
+            return;
+        }
+
+        let h = area.height as usize;
+        let selected = self.status.selected;
+        let offset = Viewport::showing(selected, h).offset();
+        for (i, row) in rows.iter().enumerate().skip(offset).take(h) {
+            let y = area.y + (i - offset) as u16;
+            let (label, staged) = match row {
+                Row::Entry(entry) => (
+                    format!("{}{} {}", entry.staged, entry.unstaged, entry.path),
+                    entry.is_staged(),
+                ),
+                Row::Hunk(hunk, staged) => {
+                    let state = if *staged { "staged" } else { "unstaged" };
+                    (format!("    {} ({})", hunk.header, state), *staged)
+                }
+            };
+            let style = match (i == selected, staged) {
+                (true, _) => self.highlight_style,
+                (false, true) => self.staged_style,
+                (false, false) => self.style,
+            };
+            if i == selected {
+                buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+            }
+            buf.set_stringn(area.x, y, label, area.width as usize, style);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+use super::{text_input::TextInput, viewport::Viewport};
+
+/// Bytes shown on each row.
+pub const ROW_BYTES: usize = 16;
+/// How much of a file is looked at to tell whether it is binary.
+const SNIFF_BYTES: usize = 8192;
+
+/// Whether `bytes`, the start of a file, look like binary rather than text:
+/// they hold a NUL byte or aren't UTF-8.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if head.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => false,
+        // A char cut off where the sniffed bytes end doesn't count.
+        Err(e) => e.error_len().is_some() || head.len() == bytes.len(),
+    }
+}
+
+/// Whether the file at `path` looks binary, judging by its start.
+pub fn is_binary_file(path: &Path) -> bool {
+    let mut head = Vec::new();
+    let read = fs::File::open(path)
+        .and_then(|file| file.take(SNIFF_BYTES as u64 + 1).read_to_end(&mut head));
+    read.is_ok() && is_binary(&head)
+}
+
+/// Parses a byte pattern to search for: hex pairs, spaces ignored, such as
+/// `de ad be ef`, or text in double quotes.
+pub fn parse_pattern(pattern: &str) -> Result<Vec<u8>> {
+    let pattern = pattern.trim();
+    if let Some(text) = pattern.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+        return Ok(text.as_bytes().to_vec());
+    }
+    let digits: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || digits.len() % 2 == 1 {
+        bail!("`{}` isn't a list of hex bytes", pattern);
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16)
+                .map_err(|_| anyhow::anyhow!("`{}` isn't a hex byte", pair))
+        })
+        .collect()
+}
+
+/// A binary file open in the hex view, with the byte under the cursor and
+/// any bytes changed but not yet written.
+#[derive(Debug)]
+pub struct HexEditor {
+    path: PathBuf,
+    bytes: Vec<u8>,
+    cursor: usize,
+    /// The rows shown.
+    view: Viewport,
+    /// Typing hex digits overwrites bytes.
+    pub editing: bool,
+    /// The high half of a byte typed while editing, before its low half.
+    nibble: Option<u8>,
+    dirty: bool,
+    /// The pattern being typed after `/`, while it is.
+    pub query: Option<TextInput>,
+    /// The last pattern searched for.
+    pattern: Vec<u8>,
+}
+
+impl HexEditor {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(HexEditor::new(path, fs::read(path)?))
+    }
+
+    pub fn new(path: &Path, bytes: Vec<u8>) -> Self {
+        HexEditor {
+            path: path.to_owned(),
+            bytes,
+            cursor: 0,
+            view: Viewport::default(),
+            editing: false,
+            nibble: None,
+            dirty: false,
+            query: None,
+            pattern: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Moves the cursor `delta` bytes, staying within the file.
+    pub fn move_by(&mut self, delta: isize) {
+        let last = self.bytes.len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+        self.nibble = None;
+    }
+
+    pub fn goto(&mut self, offset: usize) {
+        self.cursor = offset.min(self.bytes.len().saturating_sub(1));
+        self.nibble = None;
+    }
+
+    /// Scrolls so that the cursor's row is among the `height` rows shown.
+    pub fn scroll_to_cursor(&mut self, height: usize) {
+        let rows = self.bytes.len().div_ceil(ROW_BYTES);
+        self.view.resize(height, rows);
+        self.view.scroll_into_view(self.cursor / ROW_BYTES, rows);
+    }
+
+    /// Types a hex digit over the byte at the cursor, its high half first,
+    /// moving on once both are typed.
+    pub fn type_digit(&mut self, digit: char) -> Result<()> {
+        let value = match digit.to_digit(16) {
+            Some(value) => value as u8,
+            None => bail!("`{}` isn't a hex digit", digit),
+        };
+        let byte = match self.bytes.get_mut(self.cursor) {
+            Some(byte) => byte,
+            None => bail!("the file is empty"),
+        };
+        self.dirty = true;
+        match self.nibble.take() {
+            None => {
+                *byte = value << 4 | (*byte & 0x0f);
+                self.nibble = Some(value);
+            }
+            Some(_) => {
+                *byte = (*byte & 0xf0) | value;
+                if self.cursor + 1 < self.bytes.len() {
+                    self.cursor += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves to the next occurrence of `pattern` after the cursor, going
+    /// round to the start. Returns whether there was one.
+    pub fn search(&mut self, pattern: Vec<u8>) -> bool {
+        self.pattern = pattern;
+        self.search_next()
+    }
+
+    /// Moves to the next occurrence of the last pattern searched for.
+    pub fn search_next(&mut self) -> bool {
+        let pattern = &self.pattern;
+        if pattern.is_empty() || pattern.len() > self.bytes.len() {
+            return false;
+        }
+        let starts = self.bytes.len() - pattern.len() + 1;
+        let found = (1..=starts)
+            .map(|i| (self.cursor + i) % starts)
+            .find(|&i| self.bytes[i..].starts_with(pattern));
+        if let Some(offset) = found {
+            self.goto(offset);
+        }
+        found.is_some()
+    }
+
+    /// Writes the bytes back to the file.
+    pub fn save(&mut self) -> Result<()> {
+        fs::write(&self.path, &self.bytes)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Renders a [`HexEditor`] as rows of an offset, sixteen bytes in hex and
+/// the same bytes as ASCII, with the cursor's byte highlighted in both.
+pub struct HexView<'a> {
+    hex: &'a HexEditor,
+    style: Style,
+    offset_style: Style,
+    highlight_style: Style,
+}
+
+impl<'a> HexView<'a> {
+    pub fn new(hex: &'a HexEditor) -> Self {
+        HexView {
+            hex,
+            style: Style::default(),
+            offset_style: Style::default(),
+            highlight_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn offset_style(mut self, style: Style) -> Self {
+        self.offset_style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+}
+
+impl Widget for HexView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let hex = self.hex;
+        if hex.bytes.is_empty() {
+            buf.set_stringn(
+                area.x,
+                area.y,
+                "Empty file",
+                area.width as usize,
+                self.style,
+            );
+            return;
+        }
+        // Offsets, then the hex bytes in two groups of eight, then ASCII.
+        let hex_x = |i: usize| 10 + i * 3 + usize::from(i >= 8);
+        let ascii_x = hex_x(ROW_BYTES) + 1;
+        let rows = hex
+            .bytes
+            .chunks(ROW_BYTES)
+            .enumerate()
+            .skip(hex.view.offset());
+        for (y, (row, bytes)) in rows.take(area.height as usize).enumerate() {
+            let y = area.y + y as u16;
+            let offset = format!("{:08x}", row * ROW_BYTES);
+            let mut cells = vec![(0, offset, self.offset_style)];
+            for (i, byte) in bytes.iter().enumerate() {
+                let style = match row * ROW_BYTES + i == hex.cursor {
+                    true => self.highlight_style,
+                    false => self.style,
+                };
+                let ascii = match byte.is_ascii_graphic() || *byte == b' ' {
+                    true => *byte as char,
+                    false => '.',
+                };
+                cells.push((hex_x(i), format!("{:02x}", byte), style));
+                cells.push((ascii_x + i, ascii.to_string(), style));
+            }
+            for (x, text, style) in cells {
+                if x < area.width as usize {
+                    let width = area.width as usize - x;
+                    buf.set_stringn(area.x + x as u16, y, text, width, style);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_is_binary() {
+    assert!(!is_binary(b"plain text\n"));
+    assert!(!is_binary("caf\u{e9}".as_bytes()));
+    assert!(is_binary(b"\x7fELF\x02\x01\x01\0\0"));
+    assert!(is_binary(b"\xff\xfe latin-1"));
+    // A char cut in two at the end of what's sniffed is still text.
+    let mut long = vec![b'a'; SNIFF_BYTES - 1];
+    long.extend("\u{e9}".as_bytes());
+    assert!(!is_binary(&long));
+}
+
+#[test]
+fn test_hex_editor_searches_and_edits() {
+    let mut hex = HexEditor::new(Path::new("a.bin"), b"\0\x01abc\xde\xad\xbe\xefabc".to_vec());
+    assert_eq!(parse_pattern("de AD").unwrap(), [0xde, 0xad]);
+    assert_eq!(parse_pattern("\"abc\"").unwrap(), b"abc");
+    assert!(parse_pattern("d").is_err());
+    assert!(parse_pattern("zz").is_err());
+
+    assert!(hex.search(parse_pattern("\"abc\"").unwrap()));
+    assert_eq!(hex.cursor(), 2);
+    assert!(hex.search_next());
+    assert_eq!(hex.cursor(), 9);
+    // Round to the start again.
+    assert!(hex.search_next());
+    assert_eq!(hex.cursor(), 2);
+    assert!(!hex.search(vec![0x42]));
+
+    hex.goto(0);
+    hex.type_digit('4').unwrap();
+    assert_eq!(hex.bytes()[0], 0x40);
+    hex.type_digit('2').unwrap();
+    assert_eq!(hex.bytes()[0], 0x42);
+    assert_eq!(hex.cursor(), 1);
+    assert!(hex.is_dirty());
+    assert!(hex.type_digit('x').is_err());
+
+    let area = Rect::new(0, 0, 80, 1);
+    let mut buf = Buffer::empty(area);
+    HexView::new(&hex).render(area, &mut buf);
+    let row: String = (0..80).map(|x| buf[(x, 0)].symbol()).collect();
+    assert_eq!(
+        row.trim_end(),
+        "00000000  42 01 61 62 63 de ad be  ef 61 62 63              B.abc....abc"
+    );
+}
@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+use super::viewport::Viewport;
+use crate::buffer::Position;
+
+/// A place in a file, with the text the list shows for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub position: Position,
+    pub label: String,
+}
+
+/// Places to visit, such as the references to a symbol, with one selected.
+#[derive(Debug, Clone)]
+pub struct Locations {
+    title: String,
+    entries: Vec<Entry>,
+    selected: usize,
+    /// Column titles and each entry's cells, to show in a table rather
+    /// than the labels.
+    table: Option<(&'static [&'static str], Vec<Vec<String>>)>,
+    /// Cells of the table scrolled out of view on the left.
+    scroll_x: u16,
+}
+
+impl Locations {
+    pub fn new(title: &str, entries: Vec<Entry>) -> Self {
+        Locations {
+            title: title.to_string(),
+            entries,
+            selected: 0,
+            table: None,
+            scroll_x: 0,
+        }
+    }
+
+    /// Shows `rows`, the cells of each entry in turn, in columns under
+    /// `header`.
+    pub fn with_table(mut self, header: &'static [&'static str], rows: Vec<Vec<String>>) -> Self {
+        self.table = Some((header, rows));
+        self
+    }
+
+    pub fn table(&self) -> Option<(&'static [&'static str], &[Vec<String>])> {
+        self.table
+            .as_ref()
+            .map(|(header, rows)| (*header, rows.as_slice()))
+    }
+
+    pub fn scroll_x(&self) -> u16 {
+        self.scroll_x
+    }
+
+    /// Scrolls the table sideways by `cells`, left when negative.
+    pub fn scroll_by(&mut self, cells: i32) {
+        self.scroll_x = (self.scroll_x as i32 + cells).clamp(0, u16::MAX as i32) as u16;
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// Renders the entries of a [`Locations`], scrolled so that the selected
+/// one is visible.
+pub struct LocationsView<'a> {
+    locations: &'a Locations,
+    style: Style,
+    highlight_style: Style,
+}
+
+impl<'a> LocationsView<'a> {
+    pub fn new(locations: &'a Locations) -> Self {
+        LocationsView {
+            locations,
+            style: Style::default(),
+            highlight_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+}
+
+impl Widget for LocationsView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let h = area.height as usize;
+        let selected = self.locations.selected;
+        let offset = Viewport::showing(selected, h).offset();
+        let entries = self.locations.entries.iter().enumerate();
+        for (i, entry) in entries.skip(offset).take(h) {
+            let y = area.y + (i - offset) as u16;
+            let style = match i == selected {
+                true => self.highlight_style,
+                false => self.style,
+            };
+            buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+            buf.set_stringn(area.x, y, &entry.label, area.width as usize, style);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    widgets::{Block, Borders, Widget},
+};
+
+use crate::merge::{Merge, Side};
+
+/// Renders a [`Merge`]: both sides of the selected conflict next to each
+/// other above the merged result, which is scrolled to that conflict.
+pub struct MergeView<'a> {
+    merge: &'a Merge,
+    style: Style,
+    border_style: Style,
+    highlight_style: Style,
+}
+
+impl<'a> MergeView<'a> {
+    pub fn new(merge: &'a Merge) -> Self {
+        MergeView {
+            merge,
+            style: Style::default(),
+            border_style: Style::default(),
+            highlight_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn border_style(mut self, style: Style) -> Self {
+        self.border_style = style;
+        self
+    }
+
+    /// Style of the selected conflict in the result, and of the side it was
+    /// resolved to.
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    fn block(&self, title: String) -> Block<'static> {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(self.border_style)
+            .title(title)
+    }
+}
+
+fn render_lines<'l>(lines: impl Iterator<Item = (&'l str, Style)>, area: Rect, buf: &mut Buffer) {
+    for (row, (line, style)) in lines.take(area.height as usize).enumerate() {
+        let y = area.y + row as u16;
+        buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+        buf.set_stringn(area.x, y, line, area.width as usize, style);
+    }
+}
+
+impl Widget for MergeView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+            .split(area);
+        let sides = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(rows[0]);
+
+        let total = self.merge.conflicts().count();
+        if let Some(conflict) = self.merge.selected_conflict() {
+            let panes = [
+                (Side::Ours, "Ours", &conflict.ours_label, &conflict.ours),
+                (
+                    Side::Theirs,
+                    "Theirs",
+                    &conflict.theirs_label,
+                    &conflict.theirs,
+                ),
+            ];
+            for ((side, name, label, lines), pane) in panes.into_iter().zip(sides.iter()) {
+                let taken = match conflict.resolution {
+                    Some(Side::Both) => true,
+                    resolution => resolution == Some(side),
+                };
+                let title = format!(
+                    " {} ({}) {}/{}{} ",
+                    name,
+                    label,
+                    self.merge.selected() + 1,
+                    total,
+                    if taken { ", taken" } else { "" }
+                );
+                let block = self.block(title);
+                let inner = block.inner(*pane);
+                block.render(*pane, buf);
+                let style = if taken {
+                    self.highlight_style
+                } else {
+                    self.style
+                };
+                render_lines(lines.iter().map(|l| (l.as_str(), style)), inner, buf);
+            }
+        }
+
+        let block = self.block(format!(" Result, {} unresolved ", self.merge.unresolved()));
+        let inner = block.inner(rows[1]);
+        block.render(rows[1], buf);
+        let lines = self.merge.result_lines();
+        let selected = Some(self.merge.selected());
+        let first = lines.iter().position(|(_, c)| *c == selected).unwrap_or(0);
+        // A couple of lines of context above the conflict.
+        let scroll = first.saturating_sub(2);
+        let lines = lines.iter().skip(scroll).map(|(line, conflict)| {
+            let style = match *conflict == selected {
+                true => self.highlight_style,
+                false => self.style,
+            };
+            (line.as_str(), style)
+        });
+        render_lines(lines, inner, buf);
+    }
+}
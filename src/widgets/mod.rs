@@ -1 +1,26 @@
+pub mod branch_picker;
+pub mod completion;
+pub mod data_tree;
+pub mod diff;
+pub mod editor;
 pub mod file_tree;
+pub mod git_log;
+pub mod git_status;
+pub mod hex;
+pub mod locations;
+pub mod merge;
+pub mod outline;
+pub mod pane_cache;
+pub mod popup_menu;
+pub mod preview;
+pub mod progress;
+pub mod project_picker;
+pub mod recent_picker;
+pub mod search;
+pub mod select_list;
+pub mod symbol_picker;
+pub mod table;
+pub mod terminal;
+pub mod text_input;
+pub mod tree_view;
+pub mod viewport;
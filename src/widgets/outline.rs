@@ -0,0 +1,254 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use ropey::Rope;
+
+use super::tree_view::{TreeItem, TreeState};
+use crate::symbols::{Scanner, Symbol};
+
+/// A symbol of the outline and how deep it is nested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub depth: usize,
+    pub symbol: Symbol,
+}
+
+/// A symbol of the outline with the ones nested in it, as a
+/// [`TreeView`](super::tree_view::TreeView) shows them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineNode {
+    /// Where the symbol is among the outline's items.
+    id: String,
+    pub symbol: Symbol,
+    pub children: Vec<OutlineNode>,
+}
+
+impl TreeItem for OutlineNode {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn label(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.symbol.name)
+    }
+
+    fn children(&self) -> &[Self] {
+        &self.children
+    }
+
+    fn detail(&self) -> Option<&str> {
+        Some(&self.symbol.kind)
+    }
+}
+
+/// The outline sidebar: the symbol tree of the focused buffer, with the
+/// symbol around the cursor, or the one picked, selected.
+#[derive(Debug, Default)]
+pub struct Outline {
+    /// The file the outline is of, and its text when it was worked out.
+    source: Option<(PathBuf, Rope)>,
+    items: Vec<OutlineItem>,
+    /// The items nested, all expanded, so that rows and items line up.
+    nodes: Vec<OutlineNode>,
+    state: TreeState,
+    /// Finds the symbols when no language server gives them.
+    scanner: Scanner,
+}
+
+impl Outline {
+    /// Whether the outline was worked out from `text` of `path`.
+    pub fn is_current(&self, path: Option<&Path>, text: Option<&Rope>) -> bool {
+        match (&self.source, path, text) {
+            (Some((source, old)), Some(path), Some(text)) => source == path && old == text,
+            (None, None, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Notes the text the outline is now being worked out from, clearing
+    /// it if that's of another file.
+    pub fn set_source(&mut self, source: Option<(PathBuf, Rope)>) {
+        let same_file = match (&self.source, &source) {
+            (Some((old, _)), Some((new, _))) => old == new,
+            _ => false,
+        };
+        if !same_file {
+            self.items.clear();
+            self.nodes.clear();
+            self.state = TreeState::default();
+        }
+        self.source = source;
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.source.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// The text the outline is being worked out from.
+    pub fn text(&self) -> Option<&Rope> {
+        self.source.as_ref().map(|(_, text)| text)
+    }
+
+    /// Works the outline out from the source's text with patterns.
+    pub fn scan(&mut self) {
+        let items = match &self.source {
+            Some((path, text)) => nest(self.scanner.scan(path, &text.to_string()), text),
+            None => Vec::new(),
+        };
+        self.set_items(items);
+    }
+
+    pub fn set_items(&mut self, items: Vec<OutlineItem>) {
+        self.nodes = tree(&items);
+        for id in 0..items.len() {
+            self.state.expand(&id.to_string());
+        }
+        self.items = items;
+        self.state.clamp(&self.nodes);
+    }
+
+    pub fn items(&self) -> &[OutlineItem] {
+        &self.items
+    }
+
+    pub fn nodes(&self) -> &[OutlineNode] {
+        &self.nodes
+    }
+
+    pub fn state(&self) -> &TreeState {
+        &self.state
+    }
+
+    pub fn selected_item(&self) -> Option<&OutlineItem> {
+        self.items.get(self.state.selected())
+    }
+
+    pub fn select_next(&mut self) {
+        self.state.select_next(&self.nodes);
+    }
+
+    pub fn select_prev(&mut self) {
+        self.state.select_prev();
+    }
+
+    /// Selects the last symbol starting at or above `line`, the one the
+    /// cursor is in.
+    pub fn follow(&mut self, line: usize) {
+        let after = self
+            .items
+            .partition_point(|item| item.symbol.position.line <= line);
+        self.state.select(after.saturating_sub(1));
+    }
+}
+
+/// `items`, which are in order, each under the last one less deep.
+fn tree(items: &[OutlineItem]) -> Vec<OutlineNode> {
+    fn close(open: &mut Vec<(usize, OutlineNode)>, roots: &mut Vec<OutlineNode>) {
+        if let Some((_, node)) = open.pop() {
+            match open.last_mut() {
+                Some((_, parent)) => parent.children.push(node),
+                None => roots.push(node),
+            }
+        }
+    }
+
+    let (mut roots, mut open) = (Vec::new(), Vec::new());
+    for (i, item) in items.iter().enumerate() {
+        while open.last().is_some_and(|(depth, _)| *depth >= item.depth) {
+            close(&mut open, &mut roots);
+        }
+        let node = OutlineNode {
+            id: i.to_string(),
+            symbol: item.symbol.clone(),
+            children: Vec::new(),
+        };
+        open.push((item.depth, node));
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut roots);
+    }
+    roots
+}
+
+/// Nests `symbols`, which are in order, by how far their lines in `text`
+/// are indented: each is inside the last one indented less.
+fn nest(symbols: Vec<Symbol>, text: &Rope) -> Vec<OutlineItem> {
+    let mut open: Vec<usize> = Vec::new();
+    symbols
+        .into_iter()
+        .map(|symbol| {
+            let line = symbol.position.line;
+            let indent = match line < text.len_lines() {
+                true => text
+                    .line(line)
+                    .chars()
+                    .take_while(|c| *c == ' ' || *c == '\t')
+                    .count(),
+                false => 0,
+            };
+            while open.last().is_some_and(|&outer| outer >= indent) {
+                open.pop();
+            }
+            let depth = open.len();
+            open.push(indent);
+            OutlineItem { depth, symbol }
+        })
+        .collect()
+}
+
+#[test]
+fn test_outline_nests_and_follows_the_cursor() {
+    let text = "\
+struct Tree;
+
+impl Tree {
+    fn render(&self) {
+        fn inner() {}
+    }
+
+    fn walk(&self) {}
+}
+
+fn main() {}
+";
+    let path = Path::new("a.rs");
+    let rope = Rope::from_str(text);
+    let mut outline = Outline::default();
+    outline.set_source(Some((path.to_owned(), rope.clone())));
+    assert!(outline.is_current(Some(path), Some(&rope)));
+    assert!(!outline.is_current(Some(path), Some(&Rope::from_str("fn x() {}"))));
+    outline.scan();
+    let items = outline.items();
+    let depths: Vec<(&str, usize)> = items
+        .iter()
+        .map(|i| (i.symbol.name.as_str(), i.depth))
+        .collect();
+    assert_eq!(
+        depths,
+        [
+            ("Tree", 0),
+            ("Tree", 0),
+            ("render", 1),
+            ("inner", 2),
+            ("walk", 1),
+            ("main", 0),
+        ]
+    );
+
+    outline.follow(5);
+    assert_eq!(outline.selected_item().unwrap().symbol.name, "inner");
+    outline.follow(8);
+    assert_eq!(outline.selected_item().unwrap().symbol.name, "walk");
+    outline.select_next();
+    assert_eq!(outline.selected_item().unwrap().symbol.name, "main");
+
+    let names = |nodes: &[OutlineNode]| -> Vec<String> {
+        nodes.iter().map(|n| n.symbol.name.clone()).collect()
+    };
+    assert_eq!(names(outline.nodes()), ["Tree", "Tree", "main"]);
+    assert_eq!(names(&outline.nodes()[1].children), ["render", "walk"]);
+    let rows = outline.state().rows(outline.nodes(), usize::MAX);
+    assert_eq!(rows.len(), outline.items().len());
+}
@@ -0,0 +1,71 @@
+//! Reusing a pane's drawing from the last frame while nothing it shows has
+//! changed, instead of laying its widget out again.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+
+/// The last drawing of a pane and a hash of what it was drawn from.
+#[derive(Debug, Default)]
+pub struct PaneCache {
+    key: Option<u64>,
+    drawn: Buffer,
+    /// How many frames reused the drawing.
+    hits: u64,
+}
+
+impl PaneCache {
+    /// Draws `widget` into `area` of `buf`. `key` stands for everything the
+    /// widget shows; while it and `area` stay the same, the last drawing is
+    /// copied instead and `widget` isn't rendered at all.
+    pub fn render(&mut self, key: impl Hash, widget: impl Widget, area: Rect, buf: &mut Buffer) {
+        let mut hasher = DefaultHasher::new();
+        (key, area).hash(&mut hasher);
+        let key = hasher.finish();
+        if self.key == Some(key) {
+            self.hits += 1;
+        } else {
+            self.drawn = Buffer::empty(area);
+            widget.render(area, &mut self.drawn);
+            self.key = Some(key);
+        }
+        buf.merge(&self.drawn);
+    }
+
+    /// Forgets the drawing, so the next frame renders the widget again.
+    pub fn invalidate(&mut self) {
+        self.key = None;
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+}
+
+#[test]
+fn test_unchanged_panes_are_copied() {
+    use ratatui::widgets::Paragraph;
+
+    let screen = Rect::new(0, 0, 10, 2);
+    let area = Rect::new(2, 1, 5, 1);
+    let mut cache = PaneCache::default();
+    let mut buf = Buffer::empty(screen);
+    cache.render(1, Paragraph::new("hello"), area, &mut buf);
+    assert_eq!(cache.hits(), 0);
+
+    // The same key draws the old text even if the widget changed.
+    let mut buf = Buffer::empty(screen);
+    cache.render(1, Paragraph::new("world"), area, &mut buf);
+    assert_eq!(cache.hits(), 1);
+    assert_eq!(buf, Buffer::with_lines(["          ", "  hello   "]));
+    assert_eq!(buf.area, screen);
+
+    cache.render(2, Paragraph::new("world"), area, &mut buf);
+    assert_eq!(buf, Buffer::with_lines(["          ", "  world   "]));
+    cache.invalidate();
+    cache.render(2, Paragraph::new("again"), area, &mut buf);
+    assert_eq!(cache.hits(), 1);
+}
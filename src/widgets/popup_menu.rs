@@ -0,0 +1,316 @@
+//! Menus that pop up by the cursor: completions and spelling suggestions.
+//! A menu goes below the row it's anchored to, or above it when there's
+//! no room, and scrolls once it's longer than it can be tall. Items are
+//! picked with the arrows or ctrl-n/p and enter, or clicked on, and the
+//! wheel moves through them.
+
+use std::borrow::Cow;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Position, Rect},
+    style::Style,
+    widgets::{Block, Borders, Clear, Widget},
+};
+use unicode_width::UnicodeWidthStr;
+
+use super::viewport::Viewport;
+
+/// How many items a menu shows at once.
+const MAX_HEIGHT: usize = 10;
+/// How wide a menu gets, borders aside.
+const MAX_WIDTH: usize = 60;
+
+/// Something a [`PopupMenu`] can list.
+pub trait MenuItem {
+    fn label(&self) -> Cow<'_, str>;
+
+    /// A short tag drawn in a column of its own before the label, e.g. the
+    /// kind of a completion.
+    fn tag(&self) -> &str {
+        ""
+    }
+}
+
+impl MenuItem for String {
+    fn label(&self) -> Cow<'_, str> {
+        self.as_str().into()
+    }
+}
+
+/// What a key or a click did to a menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuKey {
+    /// Moved the selection.
+    Moved,
+    /// Enter, or a click on an item: the selected item was picked.
+    Picked,
+    /// Esc, or a click outside the menu.
+    Closed,
+    /// Not one of the menu's keys.
+    Ignored,
+}
+
+/// Which item of a menu is selected. Moving off either end wraps round.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MenuState {
+    selected: usize,
+}
+
+impl MenuState {
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index;
+    }
+
+    pub fn select_next(&mut self, len: usize) {
+        if len > 0 {
+            self.selected = (self.selected + 1) % len;
+        }
+    }
+
+    pub fn select_prev(&mut self, len: usize) {
+        if len > 0 {
+            self.selected = (self.selected + len - 1) % len;
+        }
+    }
+
+    /// Handles the keys of a menu of `len` items.
+    pub fn handle_key(&mut self, key: KeyEvent, len: usize) -> MenuKey {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Down => self.select_next(len),
+            KeyCode::Char('n') if ctrl => self.select_next(len),
+            KeyCode::Up => self.select_prev(len),
+            KeyCode::Char('p') if ctrl => self.select_prev(len),
+            KeyCode::Enter => return MenuKey::Picked,
+            KeyCode::Esc => return MenuKey::Closed,
+            _ => return MenuKey::Ignored,
+        }
+        MenuKey::Moved
+    }
+
+    /// Handles `mouse` for a menu of `len` items drawn in `area`, borders
+    /// and all.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent, area: Rect, len: usize) -> MenuKey {
+        let inner = inner(area);
+        let at = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.select_next(len),
+            MouseEventKind::ScrollUp => self.select_prev(len),
+            MouseEventKind::Down(MouseButton::Left) if inner.contains(at) => {
+                let offset = offset(self.selected, inner.height);
+                let row = offset + (mouse.row - inner.y) as usize;
+                if row >= len {
+                    return MenuKey::Ignored;
+                }
+                self.selected = row;
+                return MenuKey::Picked;
+            }
+            MouseEventKind::Down(_) if !area.contains(at) => return MenuKey::Closed,
+            _ => return MenuKey::Ignored,
+        }
+        MenuKey::Moved
+    }
+}
+
+/// The first row shown of a menu `height` rows tall, so that `selected` is.
+fn offset(selected: usize, height: u16) -> usize {
+    Viewport::showing(selected, height as usize).offset()
+}
+
+fn inner(area: Rect) -> Rect {
+    Block::default().borders(Borders::ALL).inner(area)
+}
+
+/// Where a menu of `size`, borders and all, goes for something at
+/// `(x, y)`: below its row if there's room, else above, kept inside `area`.
+pub fn place((x, y): (u16, u16), (width, height): (u16, u16), area: Rect) -> Rect {
+    let (width, height) = (width.min(area.width), height.min(area.height));
+    let top = match y + 1 + height <= area.bottom() {
+        true => y + 1,
+        false => y.saturating_sub(height).max(area.y),
+    };
+    let left = x.clamp(area.x, area.right() - width);
+    Rect::new(left, top, width, height)
+}
+
+/// Renders `items` in a bordered box, scrolled so that the selected one is
+/// visible, with their tags in a column before their labels.
+pub struct PopupMenu<'a, T> {
+    items: &'a [T],
+    state: &'a MenuState,
+    title: &'a str,
+    empty: &'a str,
+    tag_width: usize,
+    style: Style,
+    highlight_style: Style,
+    tag_style: Style,
+    border_style: Style,
+}
+
+impl<'a, T: MenuItem> PopupMenu<'a, T> {
+    pub fn new(items: &'a [T], state: &'a MenuState) -> Self {
+        let tag_width = items.iter().map(|i| i.tag().width()).max().unwrap_or(0);
+        PopupMenu {
+            items,
+            state,
+            title: "",
+            empty: "",
+            tag_width,
+            style: Style::default(),
+            highlight_style: Style::default(),
+            tag_style: Style::default(),
+            border_style: Style::default(),
+        }
+    }
+
+    pub fn title(mut self, title: &'a str) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// What to say when there are no items.
+    pub fn empty(mut self, text: &'a str) -> Self {
+        self.empty = text;
+        self
+    }
+
+    /// Widens the tag column to at least `width`.
+    pub fn tag_width(mut self, width: usize) -> Self {
+        self.tag_width = self.tag_width.max(width);
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    /// Style of the tag column on rows that aren't selected.
+    pub fn tag_style(mut self, style: Style) -> Self {
+        self.tag_style = style;
+        self
+    }
+
+    pub fn border_style(mut self, style: Style) -> Self {
+        self.border_style = style;
+        self
+    }
+
+    /// Columns from the menu's left edge to where its labels start.
+    pub fn label_x(&self) -> u16 {
+        1 + self.tag_column() as u16
+    }
+
+    fn tag_column(&self) -> usize {
+        match self.tag_width {
+            0 => 0,
+            width => width + 1,
+        }
+    }
+
+    /// The menu's width and height, borders and all.
+    pub fn size(&self) -> (u16, u16) {
+        let labels = self.items.iter().map(|i| i.label().width());
+        let width = match self.items.is_empty() {
+            true => self.empty.width(),
+            false => self.tag_column() + labels.max().unwrap_or(0),
+        };
+        let width = width.max(self.title.width()).min(MAX_WIDTH);
+        let height = self.items.len().clamp(1, MAX_HEIGHT);
+        (width as u16 + 2, height as u16 + 2)
+    }
+}
+
+impl<T: MenuItem> Widget for PopupMenu<'_, T> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(self.border_style)
+            .style(self.style)
+            .title(self.title);
+        let inner = block.inner(area);
+        Clear.render(area, buf);
+        block.render(area, buf);
+        if inner.width < 1 || inner.height < 1 {
+            return;
+        }
+        let width = inner.width as usize;
+        if self.items.is_empty() {
+            buf.set_stringn(inner.x, inner.y, self.empty, width, self.style);
+            return;
+        }
+
+        let h = inner.height as usize;
+        let selected = self.state.selected;
+        let offset = offset(selected, inner.height);
+        for (i, item) in self.items.iter().enumerate().skip(offset).take(h) {
+            let y = inner.y + (i - offset) as u16;
+            let style = match i == selected {
+                true => self.highlight_style,
+                false => self.style,
+            };
+            buf.set_style(Rect::new(inner.x, y, inner.width, 1), style);
+            let tag_style = match i == selected {
+                true => style,
+                false => self.tag_style,
+            };
+            buf.set_stringn(inner.x, y, item.tag(), width, tag_style);
+            let x = inner.x + self.tag_column() as u16;
+            let room = inner.right().saturating_sub(x) as usize;
+            buf.set_stringn(x, y, item.label(), room, style);
+        }
+    }
+}
+
+#[test]
+fn test_popup_menu_scrolls_and_picks_by_mouse() {
+    let items: Vec<String> = (1..=12).map(|i| format!("item {}", i)).collect();
+    let mut state = MenuState::default();
+    let menu = PopupMenu::new(&items, &state);
+    assert_eq!(menu.size(), (9, 12));
+
+    // No room below the anchor, so the menu goes above it.
+    let screen = Rect::new(0, 0, 40, 20);
+    let area = place((35, 15), (9, 12), screen);
+    assert_eq!(area, Rect::new(31, 3, 9, 12));
+
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    assert_eq!(
+        state.handle_key(key(KeyCode::Up), items.len()),
+        MenuKey::Moved
+    );
+    assert_eq!(state.selected(), 11);
+    let mut buf = Buffer::empty(screen);
+    PopupMenu::new(&items, &state).render(area, &mut buf);
+    let row = |buf: &Buffer, y| -> String { (32..39).map(|x| buf[(x, y)].symbol()).collect() };
+    // Scrolled to show the last of the twelve.
+    assert_eq!(row(&buf, 4), "item 3 ");
+    assert_eq!(row(&buf, 13), "item 12");
+
+    let click = |column, row| MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column,
+        row,
+        modifiers: KeyModifiers::NONE,
+    };
+    assert_eq!(
+        state.handle_mouse(click(33, 5), area, items.len()),
+        MenuKey::Picked
+    );
+    assert_eq!(items[state.selected()], "item 4");
+    assert_eq!(
+        state.handle_mouse(click(0, 0), area, items.len()),
+        MenuKey::Closed
+    );
+}
@@ -0,0 +1,265 @@
+use std::path::{Path, PathBuf};
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+use ropey::Rope;
+use unicode_width::UnicodeWidthStr;
+
+use super::viewport::Viewport;
+use crate::markdown::{self, Line, Piece, Tone};
+
+/// The markdown preview pane: the focused markdown buffer laid out, kept
+/// in step with its text.
+#[derive(Debug, Default)]
+pub struct Preview {
+    /// The file previewed, and its text when it was laid out.
+    source: Option<(PathBuf, Rope)>,
+    lines: Vec<Line>,
+}
+
+impl Preview {
+    /// Whether the preview was laid out from `text` of `path`.
+    pub fn is_current(&self, path: Option<&Path>, text: Option<&Rope>) -> bool {
+        match (&self.source, path, text) {
+            (Some((source, old)), Some(path), Some(text)) => source == path && old == text,
+            (None, None, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Lays out `text` of `path`, or clears the preview if there's none.
+    pub fn set_source(&mut self, source: Option<(PathBuf, Rope)>) {
+        self.lines = match &source {
+            Some((_, text)) => markdown::render(&text.to_string()),
+            None => Vec::new(),
+        };
+        self.source = source;
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.source.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+}
+
+/// Whether `path` is a markdown file, which can be previewed.
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e == "md" || e == "markdown")
+}
+
+/// A row of the pane: the source line it comes from and what's on it.
+type Row<'a> = (usize, Vec<(&'a str, Tone, Style)>);
+
+/// Wraps `line` at word boundaries into rows `width` wide.
+fn wrap(line: &Line, width: usize) -> Vec<Row<'_>> {
+    let prefix = |text| (text, Tone::Marker, Style::default());
+    let first = vec![prefix(line.prefix.as_str())];
+    if !line.wrap {
+        let mut row = first;
+        row.extend(line.pieces.iter().map(|p| piece(p, &p.text)));
+        return vec![(line.source, row)];
+    }
+    let mut rows = vec![(line.source, first)];
+    let mut used = line.prefix.width();
+    for p in &line.pieces {
+        for word in p.text.split_inclusive(' ') {
+            let w = word.trim_end().width();
+            if used + w > width && used > line.hang.width() {
+                rows.push((line.source, vec![prefix(line.hang.as_str())]));
+                used = line.hang.width();
+            }
+            if used == line.hang.width() && rows.len() > 1 && word.trim().is_empty() {
+                continue;
+            }
+            if let Some((_, row)) = rows.last_mut() {
+                row.push(piece(p, word));
+            }
+            used += word.width();
+        }
+    }
+    rows
+}
+
+fn piece<'a>(p: &Piece, text: &'a str) -> (&'a str, Tone, Style) {
+    (text, p.tone, Style::default().add_modifier(p.modifier))
+}
+
+/// Renders a [`Preview`], scrolled to the first line from at or below the
+/// editor's scroll line so that the two move together.
+pub struct PreviewView<'a> {
+    preview: &'a Preview,
+    scroll: usize,
+    style: Style,
+    heading_style: Style,
+    code_style: Style,
+    link_style: Style,
+    marker_style: Style,
+    keyword_style: Style,
+    string_style: Style,
+    comment_style: Style,
+}
+
+impl<'a> PreviewView<'a> {
+    pub fn new(preview: &'a Preview) -> Self {
+        PreviewView {
+            preview,
+            scroll: 0,
+            style: Style::default(),
+            heading_style: Style::default(),
+            code_style: Style::default(),
+            link_style: Style::default(),
+            marker_style: Style::default(),
+            keyword_style: Style::default(),
+            string_style: Style::default(),
+            comment_style: Style::default(),
+        }
+    }
+
+    /// The source line at the top of the editor.
+    pub fn scroll(mut self, line: usize) -> Self {
+        self.scroll = line;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn heading_style(mut self, style: Style) -> Self {
+        self.heading_style = style;
+        self
+    }
+
+    pub fn code_style(mut self, style: Style) -> Self {
+        self.code_style = style;
+        self
+    }
+
+    pub fn link_style(mut self, style: Style) -> Self {
+        self.link_style = style;
+        self
+    }
+
+    pub fn marker_style(mut self, style: Style) -> Self {
+        self.marker_style = style;
+        self
+    }
+
+    pub fn keyword_style(mut self, style: Style) -> Self {
+        self.keyword_style = style;
+        self
+    }
+
+    pub fn string_style(mut self, style: Style) -> Self {
+        self.string_style = style;
+        self
+    }
+
+    pub fn comment_style(mut self, style: Style) -> Self {
+        self.comment_style = style;
+        self
+    }
+
+    fn tone_style(&self, tone: Tone) -> Style {
+        match tone {
+            Tone::Text => self.style,
+            Tone::Heading => self.heading_style,
+            Tone::Code => self.code_style,
+            Tone::Link => self.link_style,
+            Tone::Marker => self.marker_style,
+            Tone::Keyword => self.keyword_style,
+            Tone::String => self.string_style,
+            Tone::Comment => self.comment_style,
+        }
+    }
+}
+
+impl Widget for PreviewView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let preview = self.preview;
+        let message = match (preview.path(), preview.lines.is_empty()) {
+            (None, _) => Some("Not a markdown file"),
+            (Some(_), true) => Some("Nothing to preview"),
+            _ => None,
+        };
+        if let Some(message) = message {
+            buf.set_stringn(area.x, area.y, message, area.width as usize, self.style);
+            return;
+        }
+
+        let width = area.width as usize;
+        let h = area.height as usize;
+        let rows: Vec<Row> = preview.lines.iter().flat_map(|l| wrap(l, width)).collect();
+        let top = rows
+            .iter()
+            .position(|(source, _)| *source >= self.scroll)
+            .unwrap_or(rows.len());
+        let mut view = Viewport::new(h);
+        view.scroll_to(top, rows.len());
+        let offset = view.offset();
+        for (i, (_, row)) in rows.iter().skip(offset).take(h).enumerate() {
+            let y = area.y + i as u16;
+            let mut x = area.x;
+            for (text, tone, modifiers) in row {
+                if x >= area.right() {
+                    break;
+                }
+                let style = self.style.patch(self.tone_style(*tone)).patch(*modifiers);
+                let left = (area.right() - x) as usize;
+                (x, _) = buf.set_stringn(x, y, text, left, style);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_preview_wraps_and_scrolls_with_the_source() {
+    let mut text = String::from("# Notes\n\n- a list item that wraps onto a second row\n");
+    for i in 0..20 {
+        text.push_str(&format!("\nParagraph {}.\n", i));
+    }
+    let mut preview = Preview::default();
+    let path = Path::new("notes.md");
+    let rope = Rope::from_str(&text);
+    preview.set_source(Some((path.to_owned(), rope.clone())));
+    assert!(preview.is_current(Some(path), Some(&rope)));
+
+    let render = |scroll| {
+        let area = Rect::new(0, 0, 24, 4);
+        let mut buf = Buffer::empty(area);
+        PreviewView::new(&preview)
+            .scroll(scroll)
+            .render(area, &mut buf);
+        (0..area.height)
+            .map(|y| {
+                let row: String = (0..area.width).map(|x| buf[(x, y)].symbol()).collect();
+                row.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        render(0),
+        [
+            "Notes",
+            "",
+            "• a list item that wraps",
+            "  onto a second row"
+        ]
+    );
+    // Paragraph 3 is on line 10 of the source.
+    assert_eq!(render(10)[0], "Paragraph 3.");
+    // The last rows stay at the bottom rather than scrolling off.
+    assert_eq!(render(100)[3], "Paragraph 19.");
+
+    assert!(is_markdown(path));
+    assert!(!is_markdown(Path::new("notes.txt")));
+}
@@ -0,0 +1,207 @@
+//! Small progress indicators for work in the background: a spinner for
+//! work of unknown length and a gauge for work with a known total. The
+//! spinner's frame comes from how long the work has run rather than from
+//! how many frames were drawn, so it turns at the same speed however often
+//! the screen is drawn.
+
+use std::time::Duration;
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+use unicode_width::UnicodeWidthStr;
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const PLAIN_SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
+/// How long each frame of the spinner shows.
+const SPINNER_FRAME: Duration = Duration::from_millis(100);
+
+/// Eighths of a cell, for the gauge's partly filled cell.
+const EIGHTHS: [&str; 8] = [" ", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+
+/// The spinner's frame `elapsed` into the work.
+pub fn spinner_frame(elapsed: Duration, plain: bool) -> &'static str {
+    let frames: &[&str] = match plain {
+        true => &PLAIN_SPINNER,
+        false => &SPINNER,
+    };
+    let n = elapsed.as_millis() / SPINNER_FRAME.as_millis();
+    frames[n as usize % frames.len()]
+}
+
+/// A spinner and what it's waiting on.
+pub struct Spinner<'a> {
+    elapsed: Duration,
+    label: &'a str,
+    plain: bool,
+    style: Style,
+}
+
+impl<'a> Spinner<'a> {
+    /// A spinner for work that started `elapsed` ago.
+    pub fn new(elapsed: Duration) -> Self {
+        Spinner {
+            elapsed,
+            label: "",
+            plain: false,
+            style: Style::default(),
+        }
+    }
+
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Turns in ASCII, for screen readers.
+    pub fn plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Cells the spinner takes, label and all.
+    pub fn width(&self) -> u16 {
+        match self.label.is_empty() {
+            true => 1,
+            false => 2 + self.label.width() as u16,
+        }
+    }
+}
+
+impl Widget for Spinner<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        let frame = spinner_frame(self.elapsed, self.plain);
+        let text = match self.label.is_empty() {
+            true => frame.to_string(),
+            false => format!("{} {}", frame, self.label),
+        };
+        buf.set_stringn(area.x, area.y, text, area.width as usize, self.style);
+    }
+}
+
+/// A bar `width` cells wide filled to `ratio`, then the percentage and a
+/// label: `███▌    42% indexing`.
+pub struct Gauge<'a> {
+    ratio: f64,
+    width: u16,
+    label: &'a str,
+    plain: bool,
+    style: Style,
+    filled_style: Style,
+}
+
+impl<'a> Gauge<'a> {
+    pub fn new(ratio: f64) -> Self {
+        Gauge {
+            ratio: ratio.clamp(0.0, 1.0),
+            width: 10,
+            label: "",
+            plain: false,
+            style: Style::default(),
+            filled_style: Style::default(),
+        }
+    }
+
+    /// How many cells the bar itself takes.
+    pub fn bar_width(mut self, width: u16) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Fills the bar with `#`s, for screen readers.
+    pub fn plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn filled_style(mut self, style: Style) -> Self {
+        self.filled_style = style;
+        self
+    }
+
+    fn text(&self) -> String {
+        let percent = format!("{:>3.0}%", self.ratio * 100.0);
+        match self.label.is_empty() {
+            true => percent,
+            false => format!("{} {}", percent, self.label),
+        }
+    }
+
+    /// Cells the gauge takes, label and all.
+    pub fn width(&self) -> u16 {
+        self.width + 1 + self.text().width() as u16
+    }
+
+    /// The bar: whole cells, then the eighths of the one partly filled.
+    fn bar(&self) -> String {
+        let eighths = (self.ratio * self.width as f64 * 8.0).round() as usize;
+        let (full, part) = (eighths / 8, eighths % 8);
+        let mut bar = match self.plain {
+            true => "#".repeat(full),
+            false => "█".repeat(full),
+        };
+        if full < self.width as usize && part > 0 && !self.plain {
+            bar.push_str(EIGHTHS[part]);
+        }
+        bar
+    }
+}
+
+impl Widget for Gauge<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        let bar_width = self.width.min(area.width);
+        let bar = Rect::new(area.x, area.y, bar_width, 1);
+        buf.set_style(bar, self.style);
+        let empty = match self.plain {
+            true => ".",
+            false => " ",
+        };
+        let blank = empty.repeat(bar_width as usize);
+        buf.set_stringn(area.x, area.y, blank, bar_width as usize, self.style);
+        let filled = self.filled_style.patch(self.style);
+        buf.set_stringn(area.x, area.y, self.bar(), bar_width as usize, filled);
+        let x = area.x + bar_width + 1;
+        let room = area.right().saturating_sub(x) as usize;
+        buf.set_stringn(x, area.y, self.text(), room, self.style);
+    }
+}
+
+#[test]
+fn test_spinner_turns_with_time_and_gauge_fills() {
+    // The frame follows the time, not how often it's asked for.
+    let frame = |ms| spinner_frame(Duration::from_millis(ms), false);
+    assert_eq!(frame(0), frame(99));
+    assert_ne!(frame(99), frame(100));
+    assert_eq!(frame(0), frame(1000));
+    assert_eq!(spinner_frame(Duration::from_millis(250), true), "-");
+
+    let area = Rect::new(0, 0, 20, 1);
+    let row = |buf: &Buffer| -> String { (0..20).map(|x| buf[(x, 0)].symbol()).collect() };
+    let mut buf = Buffer::empty(area);
+    let gauge = Gauge::new(0.425).label("scan");
+    assert_eq!(gauge.width(), 20);
+    gauge.render(area, &mut buf);
+    assert_eq!(row(&buf), "████▎       42% scan");
+    let mut buf = Buffer::empty(area);
+    Gauge::new(0.5).plain(true).render(area, &mut buf);
+    assert_eq!(row(&buf).trim_end(), "#####.....  50%");
+}
@@ -0,0 +1,39 @@
+use std::path::{Path, PathBuf};
+
+use super::select_list::SelectList;
+
+/// The workspace roots opened before that are still there, with one
+/// selected, for the project overlay.
+#[derive(Debug)]
+pub struct ProjectPicker {
+    list: SelectList<PathBuf>,
+}
+
+impl ProjectPicker {
+    /// Lists `roots` but for `current`, the one open now.
+    pub fn new(roots: Vec<PathBuf>, current: &Path) -> Self {
+        let roots = roots
+            .into_iter()
+            .filter(|root| root != current && root.is_dir())
+            .collect();
+        ProjectPicker {
+            list: SelectList::new(roots, |root| root.display().to_string()),
+        }
+    }
+
+    pub fn list(&self) -> &SelectList<PathBuf> {
+        &self.list
+    }
+
+    pub fn list_mut(&mut self) -> &mut SelectList<PathBuf> {
+        &mut self.list
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        self.list.items()
+    }
+
+    pub fn selected_root(&self) -> Option<&Path> {
+        self.list.selected_item().map(PathBuf::as_path)
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use super::select_list::SelectList;
+use crate::{jumps::Jump, recent::RecentFiles};
+
+/// The recent files that are still there, with one selected, for the
+/// recent files overlay.
+#[derive(Debug)]
+pub struct RecentPicker {
+    list: SelectList<Jump>,
+}
+
+impl RecentPicker {
+    /// Lists `recent` but for `current`, the file already in front, each
+    /// shown as `label` has it.
+    pub fn new(
+        recent: &RecentFiles,
+        current: Option<&Path>,
+        label: impl Fn(&Jump) -> String,
+    ) -> Self {
+        let files = recent
+            .iter()
+            .filter(|file| Some(file.path.as_path()) != current && file.path.exists())
+            .cloned()
+            .collect();
+        RecentPicker {
+            list: SelectList::new(files, label),
+        }
+    }
+
+    pub fn list(&self) -> &SelectList<Jump> {
+        &self.list
+    }
+
+    pub fn list_mut(&mut self) -> &mut SelectList<Jump> {
+        &mut self.list
+    }
+
+    pub fn files(&self) -> &[Jump] {
+        self.list.items()
+    }
+
+    pub fn selected_file(&self) -> Option<&Jump> {
+        self.list.selected_item()
+    }
+}
@@ -0,0 +1,578 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    buffer::Position,
+    jobs::JobId,
+    search::{Change, FileMatches, SearchLine},
+    widgets::{locations::Entry, text_input::TextInput, viewport::Viewport},
+};
+
+/// The project search overlay: the pattern typed so far and the files
+/// matching it, with one matching line selected.
+#[derive(Debug)]
+pub struct Search {
+    root: PathBuf,
+    pub query: TextInput,
+    /// What to replace the matches with, while it is being typed.
+    pub replacement: Option<TextInput>,
+    files: Vec<FileMatches>,
+    /// Index of the selected line among the matching ones.
+    selected: usize,
+    /// The search still sending results.
+    pub job: Option<JobId>,
+    truncated: bool,
+    error: Option<String>,
+}
+
+/// A line of the overlay: a file's name, a line of it, or a gap between
+/// lines that aren't next to each other.
+enum Row<'a> {
+    File(&'a Path),
+    Line(&'a SearchLine),
+    Gap,
+}
+
+impl Search {
+    pub fn new(root: &Path) -> Self {
+        Search {
+            root: root.to_owned(),
+            query: TextInput::default(),
+            replacement: None,
+            files: Vec::new(),
+            selected: 0,
+            job: None,
+            truncated: false,
+            error: None,
+        }
+    }
+
+    /// Clears the results for a new search, which `job` runs if it is in
+    /// the background.
+    pub fn start(&mut self, job: Option<JobId>) {
+        self.files.clear();
+        self.selected = 0;
+        self.job = job;
+        self.truncated = false;
+        self.error = None;
+    }
+
+    pub fn add(&mut self, file: FileMatches) {
+        self.files.push(file);
+    }
+
+    pub fn finish(&mut self, truncated: bool) {
+        self.job = None;
+        self.truncated = truncated;
+    }
+
+    /// Shows why the pattern can't be searched for instead of results.
+    pub fn fail(&mut self, error: String) {
+        self.start(None);
+        self.error = Some(error);
+    }
+
+    pub fn searching(&self) -> bool {
+        self.job.is_some()
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn files(&self) -> &[FileMatches] {
+        &self.files
+    }
+
+    /// How many lines match.
+    pub fn match_count(&self) -> usize {
+        self.matches().count()
+    }
+
+    fn matches(&self) -> impl Iterator<Item = (&Path, &SearchLine)> {
+        self.files.iter().flat_map(|file| {
+            let lines = file.lines.iter().filter(|l| !l.matches.is_empty());
+            lines.map(move |line| (file.path.as_path(), line))
+        })
+    }
+
+    /// The file and position of the selected match.
+    pub fn selected_match(&self) -> Option<(&Path, Position)> {
+        let (path, line) = self.matches().nth(self.selected)?;
+        let col = line.matches.first().map_or(0, |m| m.0);
+        Some((path, Position::new(line.line, col)))
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.match_count() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// The matching lines as entries for the locations panel.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.matches()
+            .map(|(path, line)| Entry {
+                path: path.to_owned(),
+                position: Position::new(line.line, line.matches.first().map_or(0, |m| m.0)),
+                label: format!(
+                    "{}:{}: {}",
+                    self.relative(path).display(),
+                    line.line + 1,
+                    line.text.trim()
+                ),
+            })
+            .collect()
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    fn rows(&self) -> Vec<Row<'_>> {
+        let mut rows = Vec::new();
+        for file in &self.files {
+            rows.push(Row::File(self.relative(&file.path)));
+            let mut last = None;
+            for line in &file.lines {
+                if last.is_some_and(|last| line.line > last + 1) {
+                    rows.push(Row::Gap);
+                }
+                rows.push(Row::Line(line));
+                last = Some(line.line);
+            }
+        }
+        rows
+    }
+}
+
+/// Renders the results of a [`Search`] grouped by file, scrolled so that
+/// the selected match is visible.
+pub struct SearchView<'a> {
+    search: &'a Search,
+    style: Style,
+    highlight_style: Style,
+    match_style: Style,
+    file_style: Style,
+    context_style: Style,
+}
+
+impl<'a> SearchView<'a> {
+    pub fn new(search: &'a Search) -> Self {
+        SearchView {
+            search,
+            style: Style::default(),
+            highlight_style: Style::default(),
+            match_style: Style::default(),
+            file_style: Style::default(),
+            context_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    /// For the matched text within a line.
+    pub fn match_style(mut self, style: Style) -> Self {
+        self.match_style = style;
+        self
+    }
+
+    /// For the file names heading each group.
+    pub fn file_style(mut self, style: Style) -> Self {
+        self.file_style = style;
+        self
+    }
+
+    /// For the lines shown around the matches.
+    pub fn context_style(mut self, style: Style) -> Self {
+        self.context_style = style;
+        self
+    }
+}
+
+impl Widget for SearchView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let message = match self.search.error() {
+            Some(error) => Some(error),
+            None if self.search.files.is_empty() && !self.search.searching() => {
+                Some(match self.search.query.is_empty() {
+                    true => "Type a pattern to search the project",
+                    false => "No matches",
+                })
+            }
+            None => None,
+        };
+        if let Some(message) = message {
+            buf.set_stringn(area.x, area.y, message, area.width as usize, self.style);
+            return;
+        }
+
+        let rows = self.search.rows();
+        let mut selected_row = None;
+        let mut seen = 0;
+        for (i, row) in rows.iter().enumerate() {
+            if let Row::Line(line) = row {
+                if !line.matches.is_empty() {
+                    if seen == self.search.selected {
+                        selected_row = Some(i);
+                        break;
+                    }
+                    seen += 1;
+                }
+            }
+        }
+
+        let h = area.height as usize;
+        let offset = selected_row.map_or(0, |s| Viewport::showing(s, h).offset());
+        for (i, row) in rows.iter().enumerate().skip(offset).take(h) {
+            let y = area.y + (i - offset) as u16;
+            let width = area.width as usize;
+            match row {
+                Row::File(path) => {
+                    let name = path.display().to_string();
+                    buf.set_stringn(area.x, y, name, width, self.file_style);
+                }
+                Row::Gap => {
+                    buf.set_stringn(area.x, y, "   --", width, self.context_style);
+                }
+                Row::Line(line) => {
+                    let is_match = !line.matches.is_empty();
+                    let style = match (selected_row == Some(i), is_match) {
+                        (true, _) => self.highlight_style,
+                        (false, true) => self.style,
+                        (false, false) => self.context_style,
+                    };
+                    if selected_row == Some(i) {
+                        buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+                    }
+                    let separator = if is_match { ':' } else { '-' };
+                    let prefix = format!("{:>5}{} ", line.line + 1, separator);
+                    let text: String = line
+                        .text
+                        .chars()
+                        .map(|c| if c == '\t' { ' ' } else { c })
+                        .collect();
+                    let (x, _) = buf.set_stringn(area.x, y, &prefix, width, style);
+                    buf.set_stringn(x, y, &text, area.right().saturating_sub(x) as usize, style);
+
+                    // Where each char starts on screen, and where the last
+                    // one ends.
+                    let mut columns = vec![x];
+                    for c in text.chars() {
+                        let last = columns[columns.len() - 1];
+                        columns.push(last.saturating_add(c.width().unwrap_or(0) as u16));
+                    }
+                    for &(start, end) in &line.matches {
+                        let (from, to) = (columns[start], columns[end]);
+                        let to = to.min(area.right());
+                        if from < to {
+                            let match_style = style.patch(self.match_style);
+                            buf.set_style(Rect::new(from, y, to - from, 1), match_style);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The changes a replace across files would make, each of which can be
+/// left out before they are made.
+#[derive(Debug)]
+pub struct Replace {
+    root: PathBuf,
+    pub pattern: String,
+    pub replacement: String,
+    files: Vec<(PathBuf, Vec<Change>)>,
+    /// Changes left out, by their index among all of them.
+    excluded: HashSet<usize>,
+    selected: usize,
+}
+
+impl Replace {
+    pub fn new(
+        root: &Path,
+        pattern: &str,
+        replacement: &str,
+        files: Vec<(PathBuf, Vec<Change>)>,
+    ) -> Self {
+        Replace {
+            root: root.to_owned(),
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            files,
+            excluded: HashSet::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn files(&self) -> &[(PathBuf, Vec<Change>)] {
+        &self.files
+    }
+
+    pub fn count(&self) -> usize {
+        self.files.iter().map(|(_, changes)| changes.len()).sum()
+    }
+
+    /// How many changes will be made.
+    pub fn included_count(&self) -> usize {
+        self.count() - self.excluded.len()
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.count() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Leaves the selected change out, or puts it back.
+    pub fn toggle_selected(&mut self) {
+        if !self.excluded.remove(&self.selected) {
+            self.excluded.insert(self.selected);
+        }
+    }
+
+    /// Leaves every change out, or puts them all back if they already are.
+    pub fn toggle_all(&mut self) {
+        match self.excluded.len() == self.count() {
+            true => self.excluded.clear(),
+            false => self.excluded = (0..self.count()).collect(),
+        }
+    }
+
+    /// The changes to make, by file, leaving out files with none.
+    pub fn included(&self) -> Vec<(PathBuf, Vec<Change>)> {
+        let mut index = 0;
+        let mut included = Vec::new();
+        for (path, changes) in &self.files {
+            let kept: Vec<Change> = changes
+                .iter()
+                .filter(|_| {
+                    index += 1;
+                    !self.excluded.contains(&(index - 1))
+                })
+                .cloned()
+                .collect();
+            if !kept.is_empty() {
+                included.push((path.clone(), kept));
+            }
+        }
+        included
+    }
+}
+
+/// Renders the changes of a [`Replace`] as a diff grouped by file, each
+/// line before and after, scrolled so that the selected one is visible.
+pub struct ReplaceView<'a> {
+    replace: &'a Replace,
+    style: Style,
+    highlight_style: Style,
+    file_style: Style,
+    added_style: Style,
+    removed_style: Style,
+    excluded_style: Style,
+}
+
+impl<'a> ReplaceView<'a> {
+    pub fn new(replace: &'a Replace) -> Self {
+        ReplaceView {
+            replace,
+            style: Style::default(),
+            highlight_style: Style::default(),
+            file_style: Style::default(),
+            added_style: Style::default(),
+            removed_style: Style::default(),
+            excluded_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    /// For the file names heading each group.
+    pub fn file_style(mut self, style: Style) -> Self {
+        self.file_style = style;
+        self
+    }
+
+    pub fn added_style(mut self, style: Style) -> Self {
+        self.added_style = style;
+        self
+    }
+
+    pub fn removed_style(mut self, style: Style) -> Self {
+        self.removed_style = style;
+        self
+    }
+
+    /// For changes that have been left out.
+    pub fn excluded_style(mut self, style: Style) -> Self {
+        self.excluded_style = style;
+        self
+    }
+}
+
+impl Widget for ReplaceView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        // A heading per file, then two lines per change.
+        let mut rows = Vec::new();
+        let mut index = 0;
+        for (path, changes) in &self.replace.files {
+            let relative = path.strip_prefix(&self.replace.root).unwrap_or(path);
+            rows.push((None, format!("{} ({})", relative.display(), changes.len())));
+            for change in changes {
+                let mark = match self.replace.excluded.contains(&index) {
+                    true => "[ ]",
+                    false => "[x]",
+                };
+                let before = format!("{} {:>5}- {}", mark, change.line + 1, change.text);
+                let after = format!("    {:>5}+ {}", change.line + 1, change.replaced());
+                rows.push((Some((index, false)), before));
+                rows.push((Some((index, true)), after));
+                index += 1;
+            }
+        }
+
+        let selected_row = rows
+            .iter()
+            .position(|(change, _)| *change == Some((self.replace.selected, false)))
+            .unwrap_or(0);
+        let h = area.height as usize;
+        // Both lines of the selected change show.
+        let offset = Viewport::showing(selected_row + 1, h).offset();
+        for (i, (change, text)) in rows.iter().enumerate().skip(offset).take(h) {
+            let y = area.y + (i - offset) as u16;
+            let style = match change {
+                None => self.file_style,
+                Some((index, _)) if self.replace.excluded.contains(index) => self.excluded_style,
+                Some((_, true)) => self.added_style,
+                Some((_, false)) => self.removed_style,
+            };
+            let style = match change.is_some_and(|(index, _)| index == self.replace.selected) {
+                true => style.patch(self.highlight_style),
+                false => style,
+            };
+            buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+            let text: String = text
+                .chars()
+                .map(|c| if c == '\t' { ' ' } else { c })
+                .collect();
+            buf.set_stringn(area.x, y, text, area.width as usize, style);
+        }
+    }
+}
+
+#[test]
+fn test_search_selection_and_entries() {
+    let root = Path::new("/project");
+    let line = |line, matches: Vec<(usize, usize)>| SearchLine {
+        line,
+        text: format!("line {}", line),
+        matches,
+    };
+    let mut search = Search::new(root);
+    search.start(Some(1));
+    search.add(FileMatches {
+        path: root.join("a.rs"),
+        lines: vec![line(0, vec![(0, 4)]), line(1, vec![])],
+    });
+    search.add(FileMatches {
+        path: root.join("src/b.rs"),
+        lines: vec![line(4, vec![(5, 6)]), line(9, vec![(0, 4)])],
+    });
+    assert!(search.searching());
+    search.finish(false);
+    assert!(!search.searching());
+
+    assert_eq!(search.match_count(), 3);
+    assert_eq!(search.rows().len(), 7);
+    search.select_next();
+    search.select_next();
+    search.select_next();
+    assert_eq!(
+        search.selected_match(),
+        Some((root.join("src/b.rs").as_path(), Position::new(9, 0)))
+    );
+    search.select_prev();
+    assert_eq!(search.selected_match().unwrap().1, Position::new(4, 5));
+
+    let labels: Vec<String> = search.entries().into_iter().map(|e| e.label).collect();
+    assert_eq!(
+        labels,
+        [
+            "a.rs:1: line 0",
+            "src/b.rs:5: line 4",
+            "src/b.rs:10: line 9"
+        ]
+    );
+}
+
+#[test]
+fn test_replace_leaves_out_excluded_changes() {
+    let change = |line| Change {
+        line,
+        text: "old".to_string(),
+        range: (0, 3),
+        replacement: "new".to_string(),
+    };
+    let files = vec![
+        (PathBuf::from("a"), vec![change(0), change(3)]),
+        (PathBuf::from("b"), vec![change(1)]),
+    ];
+    let mut replace = Replace::new(Path::new(""), "old", "new", files);
+    assert_eq!(replace.count(), 3);
+    replace.select_next();
+    replace.select_next();
+    replace.toggle_selected();
+    assert_eq!(replace.included_count(), 2);
+    let included = replace.included();
+    assert_eq!(included.len(), 1);
+    assert_eq!(included[0].1, [change(0), change(3)]);
+
+    replace.toggle_all();
+    assert!(replace.included().is_empty());
+    replace.toggle_all();
+    assert_eq!(replace.included_count(), 3);
+}
@@ -0,0 +1,262 @@
+//! The list behind the pickers: items shown a line each, narrowed down by
+//! a fuzzy filter, with one selected, and the keys that move through them
+//! the same in every picker.
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+use super::viewport::Viewport;
+use crate::fuzzy;
+
+/// What a key did to a [`SelectList`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListKey {
+    /// Moved the selection or changed the filter.
+    Handled,
+    /// Enter: the selected item was picked.
+    Picked,
+    /// `q` or esc: the picker should close.
+    Closed,
+    /// Not one of the list's keys, so the picker's own.
+    Ignored,
+}
+
+/// Items with the lines they're shown as, the filter typed after `/` and
+/// the selected one among those that match it.
+#[derive(Debug)]
+pub struct SelectList<T> {
+    items: Vec<T>,
+    labels: Vec<String>,
+    filter: String,
+    /// Whether keys go to the filter rather than moving the selection.
+    pub filtering: bool,
+    /// Indexes of the items matching the filter.
+    matches: Vec<usize>,
+    /// Index into `matches`.
+    selected: usize,
+}
+
+impl<T> SelectList<T> {
+    /// Lists `items`, each shown as `label` has it.
+    pub fn new(items: Vec<T>, label: impl Fn(&T) -> String) -> Self {
+        let mut list = SelectList {
+            items: Vec::new(),
+            labels: Vec::new(),
+            filter: String::new(),
+            filtering: false,
+            matches: Vec::new(),
+            selected: 0,
+        };
+        list.set_items(items, label);
+        list
+    }
+
+    /// Swaps the items for `items`, keeping the filter and as much of the
+    /// selection as there's room for.
+    pub fn set_items(&mut self, items: Vec<T>, label: impl Fn(&T) -> String) {
+        self.labels = items.iter().map(label).collect();
+        self.items = items;
+        self.refilter();
+        self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The lines of the items matching the filter, in order.
+    pub fn visible(&self) -> impl Iterator<Item = &str> {
+        self.matches.iter().map(|&i| self.labels[i].as_str())
+    }
+
+    pub fn visible_len(&self) -> usize {
+        self.matches.len()
+    }
+
+    /// Which of the visible items is selected.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_item(&self) -> Option<&T> {
+        self.matches.get(self.selected).map(|&i| &self.items[i])
+    }
+
+    /// Selects the first item `pick` is true of, if it's visible.
+    pub fn select_where(&mut self, pick: impl Fn(&T) -> bool) {
+        if let Some(row) = self.matches.iter().position(|&i| pick(&self.items[i])) {
+            self.selected = row;
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_first(&mut self) {
+        self.selected = 0;
+    }
+
+    pub fn select_last(&mut self) {
+        self.selected = self.matches.len().saturating_sub(1);
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn push_filter(&mut self, c: char) {
+        self.filter.push(c);
+        self.refilter();
+        self.selected = 0;
+    }
+
+    pub fn pop_filter(&mut self) {
+        self.filter.pop();
+        self.refilter();
+        self.selected = 0;
+    }
+
+    fn refilter(&mut self) {
+        self.matches = (0..self.items.len())
+            .filter(|&i| fuzzy::matches(&self.filter, &self.labels[i]))
+            .collect();
+    }
+
+    /// Handles the keys every picker shares: j/k and the arrows move, g
+    /// and G or home and end go to either end, `/` starts a filter, enter
+    /// picks and `q` or esc closes. While filtering, keys are typed into
+    /// the filter; esc drops it and enter goes back to moving.
+    pub fn handle_key(&mut self, key: KeyEvent) -> ListKey {
+        if self.filtering {
+            match key.code {
+                KeyCode::Char(c) => self.push_filter(c),
+                KeyCode::Backspace => self.pop_filter(),
+                KeyCode::Down => self.select_next(),
+                KeyCode::Up => self.select_prev(),
+                KeyCode::Enter => self.filtering = false,
+                KeyCode::Esc => {
+                    self.filtering = false;
+                    self.filter.clear();
+                    self.refilter();
+                }
+                _ => return ListKey::Ignored,
+            }
+            return ListKey::Handled;
+        }
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => self.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => self.select_prev(),
+            KeyCode::Char('g') | KeyCode::Home => self.select_first(),
+            KeyCode::Char('G') | KeyCode::End => self.select_last(),
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Enter => return ListKey::Picked,
+            KeyCode::Char('q') | KeyCode::Esc => return ListKey::Closed,
+            _ => return ListKey::Ignored,
+        }
+        ListKey::Handled
+    }
+}
+
+/// Renders the visible lines of a [`SelectList`], scrolled so that the
+/// selected one is in view.
+pub struct SelectListView<'a, T> {
+    list: &'a SelectList<T>,
+    style: Style,
+    highlight_style: Style,
+}
+
+impl<'a, T> SelectListView<'a, T> {
+    pub fn new(list: &'a SelectList<T>) -> Self {
+        SelectListView {
+            list,
+            style: Style::default(),
+            highlight_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+}
+
+impl<T> Widget for SelectListView<'_, T> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+        let width = area.width as usize;
+        if self.list.matches.is_empty() {
+            buf.set_stringn(area.x, area.y, "No matches", width, self.style);
+            return;
+        }
+
+        let h = area.height as usize;
+        let selected = self.list.selected;
+        let offset = Viewport::showing(selected, h).offset();
+        for (i, line) in self.list.visible().enumerate().skip(offset).take(h) {
+            let y = area.y + (i - offset) as u16;
+            let style = match i == selected {
+                true => self.highlight_style,
+                false => self.style,
+            };
+            if i == selected {
+                buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+            }
+            buf.set_stringn(area.x, y, line, width, style);
+        }
+    }
+}
+
+#[test]
+fn test_select_list_filters_and_moves() {
+    use crossterm::event::KeyModifiers;
+
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    let names = ["main", "feature/tree", "feature/list", "fix"];
+    let mut list = SelectList::new(names.to_vec(), |name| name.to_string());
+    assert_eq!(list.handle_key(key(KeyCode::Char('G'))), ListKey::Handled);
+    assert_eq!(list.selected_item(), Some(&"fix"));
+
+    list.handle_key(key(KeyCode::Char('/')));
+    for c in "feat".chars() {
+        list.handle_key(key(KeyCode::Char(c)));
+    }
+    assert_eq!(list.visible().collect::<Vec<_>>(), names[1..3]);
+    list.handle_key(key(KeyCode::Enter));
+    assert!(!list.filtering);
+    list.handle_key(key(KeyCode::Char('j')));
+    assert_eq!(list.selected_item(), Some(&"feature/list"));
+    assert_eq!(list.handle_key(key(KeyCode::Enter)), ListKey::Picked);
+    assert_eq!(list.handle_key(key(KeyCode::Char('d'))), ListKey::Ignored);
+
+    // Fewer items keep the selection in range.
+    list.set_items(vec!["feature/tree"], |name| name.to_string());
+    assert_eq!(list.selected_item(), Some(&"feature/tree"));
+
+    let area = Rect::new(0, 0, 12, 1);
+    let mut buf = Buffer::empty(area);
+    list.pop_filter();
+    list.push_filter('z');
+    SelectListView::new(&list).render(area, &mut buf);
+    let row: String = (0..12).map(|x| buf[(x, 0)].symbol()).collect();
+    assert_eq!(row.trim_end(), "No matches");
+    assert_eq!(list.handle_key(key(KeyCode::Esc)), ListKey::Closed);
+}
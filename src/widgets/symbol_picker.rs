@@ -0,0 +1,252 @@
+use std::path::{Path, PathBuf};
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+use super::viewport::Viewport;
+use crate::{fuzzy, jobs::JobId, symbols::Symbol};
+
+/// The symbol overlay: the symbols of a file or of the whole workspace,
+/// narrowed down by a fuzzy query, with one selected.
+#[derive(Debug)]
+pub struct SymbolPicker {
+    root: PathBuf,
+    /// Lists every file's symbols rather than one file's.
+    pub workspace: bool,
+    symbols: Vec<Symbol>,
+    query: String,
+    /// Indices of the symbols matching the query, best match first.
+    matching: Vec<usize>,
+    /// Index into `matching`.
+    selected: usize,
+    /// The job still gathering the workspace's symbols.
+    pub job: Option<JobId>,
+}
+
+impl SymbolPicker {
+    pub fn new(root: &Path, workspace: bool, symbols: Vec<Symbol>) -> Self {
+        let mut picker = SymbolPicker {
+            root: root.to_owned(),
+            workspace,
+            symbols: Vec::new(),
+            query: String::new(),
+            matching: Vec::new(),
+            selected: 0,
+            job: None,
+        };
+        picker.set_symbols(symbols);
+        picker
+    }
+
+    /// Replaces the symbols listed, once a job has gathered them.
+    pub fn set_symbols(&mut self, symbols: Vec<Symbol>) {
+        self.symbols = symbols;
+        self.job = None;
+        self.refilter();
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn push_query(&mut self, c: char) {
+        self.query.push(c);
+        self.refilter();
+    }
+
+    pub fn pop_query(&mut self) {
+        self.query.pop();
+        self.refilter();
+    }
+
+    /// Ranks the symbols by how well their names match the query, keeping
+    /// their order in the file when it is empty.
+    fn refilter(&mut self) {
+        let mut scored: Vec<(usize, usize)> = self
+            .symbols
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some((fuzzy::score(&self.query, &s.name)?, i)))
+            .collect();
+        if !self.query.is_empty() {
+            let symbols = &self.symbols;
+            scored.sort_by_key(|&(score, i)| (std::cmp::Reverse(score), symbols[i].name.len(), i));
+        }
+        self.matching = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    pub fn indexing(&self) -> bool {
+        self.job.is_some()
+    }
+
+    /// How many symbols there are in all.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// The symbols matching the query, best first.
+    pub fn matching(&self) -> impl Iterator<Item = &Symbol> {
+        self.matching.iter().map(|&i| &self.symbols[i])
+    }
+
+    pub fn selected_symbol(&self) -> Option<&Symbol> {
+        let idx = *self.matching.get(self.selected)?;
+        self.symbols.get(idx)
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matching.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    fn describe(&self, symbol: &Symbol) -> Option<String> {
+        if !self.workspace {
+            return None;
+        }
+        let path = symbol.path.strip_prefix(&self.root).unwrap_or(&symbol.path);
+        Some(format!("{}:{}", path.display(), symbol.position.line + 1))
+    }
+}
+
+/// Renders the matching symbols of a [`SymbolPicker`], each with its kind
+/// and, for the workspace, where it is, scrolled so that the selected one
+/// is visible.
+pub struct SymbolPickerView<'a> {
+    picker: &'a SymbolPicker,
+    style: Style,
+    highlight_style: Style,
+    kind_style: Style,
+    path_style: Style,
+}
+
+impl<'a> SymbolPickerView<'a> {
+    pub fn new(picker: &'a SymbolPicker) -> Self {
+        SymbolPickerView {
+            picker,
+            style: Style::default(),
+            highlight_style: Style::default(),
+            kind_style: Style::default(),
+            path_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    pub fn kind_style(mut self, style: Style) -> Self {
+        self.kind_style = style;
+        self
+    }
+
+    pub fn path_style(mut self, style: Style) -> Self {
+        self.path_style = style;
+        self
+    }
+}
+
+impl Widget for SymbolPickerView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let picker = self.picker;
+        if picker.matching.is_empty() {
+            let message = match (picker.indexing(), picker.is_empty()) {
+                (true, _) => "Looking for symbols",
+                (false, true) => "No symbols",
+                (false, false) => "No matching symbols",
+            };
+            buf.set_stringn(area.x, area.y, message, area.width as usize, self.style);
+            return;
+        }
+
+        let width = area.width as usize;
+        let h = area.height as usize;
+        let selected = picker.selected;
+        let offset = Viewport::showing(selected, h).offset();
+        for (i, symbol) in picker.matching().enumerate().skip(offset).take(h) {
+            let y = area.y + (i - offset) as u16;
+            let highlight = |style: Style| match i == selected {
+                true => style.patch(self.highlight_style),
+                false => style,
+            };
+            if i == selected {
+                buf.set_style(Rect::new(area.x, y, area.width, 1), self.highlight_style);
+            }
+            let kind = format!("{:<9} ", symbol.kind);
+            let (x, _) = buf.set_stringn(area.x, y, &kind, width, highlight(self.kind_style));
+            let used = (x - area.x) as usize;
+            let (x, _) = buf.set_stringn(
+                x,
+                y,
+                &symbol.name,
+                width.saturating_sub(used),
+                highlight(self.style),
+            );
+            if let Some(location) = picker.describe(symbol) {
+                let used = (x - area.x) as usize + 2;
+                buf.set_stringn(
+                    x + 2,
+                    y,
+                    location,
+                    width.saturating_sub(used),
+                    highlight(self.path_style),
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_symbol_picker_ranks_matches() {
+    use crate::buffer::Position;
+
+    let symbol = |name: &str, line| Symbol {
+        name: name.to_string(),
+        kind: "function".to_string(),
+        path: PathBuf::from("/p/src/a.rs"),
+        position: Position::new(line, 0),
+    };
+    let symbols = vec![
+        symbol("tangle", 0),
+        symbol("git_log", 1),
+        symbol("draw", 2),
+        symbol("log", 3),
+    ];
+    let mut picker = SymbolPicker::new(Path::new("/p"), true, symbols);
+    let names = |picker: &SymbolPicker| -> Vec<String> {
+        picker.matching().map(|s| s.name.clone()).collect()
+    };
+    assert_eq!(names(&picker), ["tangle", "git_log", "draw", "log"]);
+
+    picker.push_query('g');
+    picker.push_query('l');
+    assert_eq!(names(&picker), ["git_log", "tangle"]);
+    picker.select_next();
+    picker.select_next();
+    assert_eq!(picker.selected_symbol().unwrap().name, "tangle");
+    assert_eq!(picker.describe(&symbol("x", 9)).unwrap(), "src/a.rs:10");
+
+    picker.pop_query();
+    picker.pop_query();
+    picker.push_query('z');
+    assert!(picker.selected_symbol().is_none());
+}
@@ -0,0 +1,257 @@
+//! Rows of cells lined up in columns under a header, for the jobs panel
+//! and the diagnostics list. A column is a fixed number of cells wide, as
+//! wide as its widest cell, or a weighted share of the room left over.
+//! Rows wider than the area scroll sideways.
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::viewport::Viewport;
+
+/// How wide a [`Table`] column is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    /// Exactly this many cells.
+    Fixed(u16),
+    /// As wide as the widest of its cells and its title.
+    Auto,
+    /// A share of what the other columns leave, by weight. Never narrower
+    /// than its title.
+    Weight(u16),
+}
+
+/// Cells between columns.
+const GAP: u16 = 1;
+
+/// The widths of columns `widths` takes, with `header` over `rows`, in
+/// `room` cells.
+pub fn layout(widths: &[Width], header: &[&str], rows: &[Vec<String>], room: u16) -> Vec<u16> {
+    let title = |i: usize| header.get(i).map_or(0, |t| t.width()) as u16;
+    let widest = |i: usize| {
+        let cells = rows
+            .iter()
+            .filter_map(|r| r.get(i))
+            .map(|c| c.width() as u16);
+        cells.max().unwrap_or(0).max(title(i))
+    };
+    let mut out: Vec<u16> = widths
+        .iter()
+        .enumerate()
+        .map(|(i, width)| match width {
+            Width::Fixed(n) => *n,
+            Width::Auto => widest(i),
+            Width::Weight(_) => title(i),
+        })
+        .collect();
+    let gaps = GAP * widths.len().saturating_sub(1) as u16;
+    let used = out.iter().sum::<u16>() + gaps;
+    let weights: u16 = widths
+        .iter()
+        .map(|w| match w {
+            Width::Weight(n) => *n,
+            _ => 0,
+        })
+        .sum();
+    if weights == 0 {
+        return out;
+    }
+    // What's left goes to the weighted columns, in whole cells, the last
+    // taking any remainder.
+    let left = room.saturating_sub(used);
+    let mut given = 0;
+    let last = widths.iter().rposition(|w| matches!(w, Width::Weight(_)));
+    for (i, width) in widths.iter().enumerate() {
+        if let Width::Weight(n) = width {
+            let share = match Some(i) == last {
+                true => left - given,
+                false => left * n / weights,
+            };
+            given += share;
+            out[i] += share;
+        }
+    }
+    out
+}
+
+/// `text` cut or padded to `width` cells.
+fn fit(text: &str, width: u16) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0) as u16;
+        if used + w > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.extend(std::iter::repeat_n(' ', (width - used) as usize));
+    out
+}
+
+/// `line` with its first `skip` cells dropped.
+fn skip_cells(line: &str, skip: u16) -> &str {
+    let mut skipped = 0;
+    for (i, c) in line.char_indices() {
+        if skipped >= skip {
+            return &line[i..];
+        }
+        skipped += c.width().unwrap_or(0) as u16;
+    }
+    ""
+}
+
+/// Renders rows of cells in columns, with an optional header and selected
+/// row, scrolled down to keep the selection visible and sideways by
+/// [`scroll_x`](Self::scroll_x).
+pub struct Table<'a> {
+    rows: &'a [Vec<String>],
+    widths: &'a [Width],
+    header: &'a [&'a str],
+    selected: Option<usize>,
+    scroll_x: u16,
+    empty: &'a str,
+    style: Style,
+    header_style: Style,
+    highlight_style: Style,
+}
+
+impl<'a> Table<'a> {
+    pub fn new(rows: &'a [Vec<String>], widths: &'a [Width]) -> Self {
+        Table {
+            rows,
+            widths,
+            header: &[],
+            selected: None,
+            scroll_x: 0,
+            empty: "",
+            style: Style::default(),
+            header_style: Style::default(),
+            highlight_style: Style::default(),
+        }
+    }
+
+    /// Titles the columns, in a row of their own above the rest.
+    pub fn header(mut self, header: &'a [&'a str]) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn select(mut self, selected: Option<usize>) -> Self {
+        self.selected = selected;
+        self
+    }
+
+    /// Cells scrolled out of view on the left. Stops at the last column.
+    pub fn scroll_x(mut self, scroll: u16) -> Self {
+        self.scroll_x = scroll;
+        self
+    }
+
+    /// What to say when there are no rows.
+    pub fn empty(mut self, text: &'a str) -> Self {
+        self.empty = text;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn header_style(mut self, style: Style) -> Self {
+        self.header_style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    /// `cells` in the columns `widths` lays out, scrolled.
+    fn line<'s>(&self, cells: impl Iterator<Item = &'s str>, widths: &[u16]) -> String {
+        let cells: Vec<String> = cells.zip(widths).map(|(c, &w)| fit(c, w)).collect();
+        let line = cells.join(&" ".repeat(GAP as usize));
+        skip_cells(&line, self.scroll_x).to_string()
+    }
+}
+
+impl Widget for Table<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+        let width = area.width as usize;
+        let widths = layout(self.widths, self.header, self.rows, area.width);
+        // Scrolling stops with the last column at the left edge.
+        let last = widths.iter().rev().skip(1).map(|w| w + GAP).sum::<u16>();
+        let table = Table {
+            scroll_x: self.scroll_x.min(last),
+            ..self
+        };
+
+        let mut y = area.y;
+        if !table.header.is_empty() {
+            let line = table.line(table.header.iter().copied(), &widths);
+            buf.set_stringn(area.x, y, line, width, table.header_style);
+            y += 1;
+        }
+        if table.rows.is_empty() && y < area.bottom() {
+            buf.set_stringn(area.x, y, table.empty, width, table.style);
+            return;
+        }
+
+        let h = area.bottom().saturating_sub(y) as usize;
+        let selected = table.selected.unwrap_or(0);
+        let offset = Viewport::showing(selected, h).offset();
+        for (i, row) in table.rows.iter().enumerate().skip(offset).take(h) {
+            let y = y + (i - offset) as u16;
+            let style = match table.selected == Some(i) {
+                true => table.highlight_style,
+                false => table.style,
+            };
+            buf.set_style(Rect::new(area.x, y, area.width, 1), style);
+            let line = table.line(row.iter().map(String::as_str), &widths);
+            buf.set_stringn(area.x, y, line, width, style);
+        }
+    }
+}
+
+#[test]
+fn test_table_lays_out_and_scrolls_columns() {
+    let widths = [
+        Width::Fixed(3),
+        Width::Auto,
+        Width::Weight(1),
+        Width::Weight(2),
+    ];
+    let cells = |cells: [&str; 4]| cells.map(String::from).to_vec();
+    let rows = vec![
+        cells(["1", "index", "a", "b"]),
+        cells(["22", "grep", "c", "d"]),
+    ];
+    let header = ["#", "job", "x", "y"];
+    // 3 + 5 + 3 gaps leave 21 cells, split a third and two thirds.
+    assert_eq!(layout(&widths, &header, &rows, 32), [3, 5, 7, 14]);
+    // With no room to spare, weighted columns keep their titles.
+    assert_eq!(layout(&widths, &header, &rows, 10), [3, 5, 1, 1]);
+
+    let area = Rect::new(0, 0, 12, 3);
+    let row = |buf: &Buffer, y| -> String { (0..12).map(|x| buf[(x, y)].symbol()).collect() };
+    let mut buf = Buffer::empty(area);
+    Table::new(&rows, &widths)
+        .header(&header)
+        .select(Some(1))
+        .render(area, &mut buf);
+    assert_eq!(row(&buf, 0), "#   job   x ");
+    assert_eq!(row(&buf, 2), "22  grep  c ");
+
+    let mut buf = Buffer::empty(area);
+    Table::new(&rows, &widths)
+        .header(&header)
+        .scroll_x(4)
+        .render(area, &mut buf);
+    assert_eq!(row(&buf, 1), "index a b   ");
+}
@@ -0,0 +1,110 @@
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+use crate::pty::Pty;
+
+/// The cursor and selection of copy mode, as lines counted from the top of
+/// the live screen. Lines of history above it are negative.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CopyMode {
+    pub cursor: isize,
+    /// Where the selection started, while there is one.
+    pub anchor: Option<isize>,
+}
+
+impl CopyMode {
+    /// The first and last selected lines; just the cursor's without a
+    /// selection.
+    pub fn selection(&self) -> (isize, isize) {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        (anchor.min(self.cursor), anchor.max(self.cursor))
+    }
+}
+
+/// Renders the screen of a [`Pty`] as it is scrolled, with the lines
+/// selected in copy mode highlighted.
+pub struct TerminalView<'a> {
+    pty: &'a Pty,
+    copy: Option<CopyMode>,
+    style: Style,
+    highlight_style: Style,
+    colors: bool,
+}
+
+impl<'a> TerminalView<'a> {
+    pub fn new(pty: &'a Pty) -> Self {
+        TerminalView {
+            pty,
+            copy: None,
+            style: Style::default(),
+            highlight_style: Style::default(),
+            colors: true,
+        }
+    }
+
+    pub fn copy_mode(mut self, copy: Option<CopyMode>) -> Self {
+        self.copy = copy;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    /// Whether to show the colours programs ask for, or only their bold,
+    /// underline and the like.
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+}
+
+impl Widget for TerminalView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+
+        let (rows, cols) = self.pty.size();
+        let scrollback = self.pty.scrollback() as isize;
+        for row in 0..rows.min(area.height) {
+            let y = area.y + row;
+            for col in 0..cols.min(area.width) {
+                if let Some((text, mut style)) = self.pty.cell(row, col) {
+                    if !self.colors {
+                        (style.fg, style.bg) = (None, None);
+                    }
+                    buf[(area.x + col, y)]
+                        .set_symbol(&text)
+                        .set_style(self.style.patch(style));
+                }
+            }
+            let line = row as isize - scrollback;
+            if let Some(copy) = self.copy {
+                let (first, last) = copy.selection();
+                if (first..=last).contains(&line) {
+                    buf.set_style(Rect::new(area.x, y, area.width, 1), self.highlight_style);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_copy_mode_selection() {
+    let mut copy = CopyMode {
+        cursor: 3,
+        anchor: None,
+    };
+    assert_eq!(copy.selection(), (3, 3));
+    copy.anchor = Some(5);
+    assert_eq!(copy.selection(), (3, 5));
+    copy.cursor = -2;
+    assert_eq!(copy.selection(), (-2, 5));
+}
@@ -0,0 +1,392 @@
+//! A line of text being typed: the command line, the search overlay's
+//! pattern and replacement, and the prompts of the dialogs. The keys are
+//! the shell's: the arrows, ctrl-a/e and home/end move, alt-b/f and
+//! ctrl-arrows move by words, and what ctrl-w, alt-d, ctrl-k and ctrl-u
+//! cut goes on a kill ring that ctrl-y pastes from and alt-y cycles.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Position, Rect},
+    style::Style,
+    widgets::Widget,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Cuts kept on the kill ring; older ones are dropped.
+const KILL_RING: usize = 16;
+
+/// What a key did to a [`TextInput`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKey {
+    /// The text changed.
+    Edited,
+    /// Only the cursor moved.
+    Moved,
+    /// Not an editing key, so the prompt's own.
+    Ignored,
+}
+
+/// The text typed, with a cursor in it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TextInput {
+    text: String,
+    /// Byte offset into `text`.
+    cursor: usize,
+    /// Cuts, the latest last.
+    kills: Vec<String>,
+    /// Where the last paste went and which cut it was, while alt-y can
+    /// still swap it for an older one.
+    yanked: Option<(usize, usize)>,
+}
+
+impl TextInput {
+    pub fn new(text: &str) -> Self {
+        TextInput {
+            text: text.to_string(),
+            cursor: text.len(),
+            ..TextInput::default()
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Replaces the text, with the cursor at its end.
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = text.len();
+        self.yanked = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.set("");
+    }
+
+    /// Empties the input, returning what was in it.
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        self.yanked = None;
+        std::mem::take(&mut self.text)
+    }
+
+    /// Byte offset of the cursor.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    /// Columns of the text before the cursor.
+    pub fn cursor_width(&self) -> usize {
+        self.text[..self.cursor].width()
+    }
+
+    fn prev_char(&self) -> usize {
+        let before = &self.text[..self.cursor];
+        before.char_indices().last().map_or(0, |(i, _)| i)
+    }
+
+    fn next_char(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        after
+            .chars()
+            .next()
+            .map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    /// Where the word before the cursor starts, past any gap before it.
+    fn word_start(&self) -> usize {
+        let before = &self.text[..self.cursor];
+        let word = before.trim_end_matches(|c| !is_word(c));
+        word.trim_end_matches(is_word).len()
+    }
+
+    /// Where the word after the cursor ends, past any gap before it.
+    fn word_end(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let word = after.trim_start_matches(|c| !is_word(c));
+        self.text.len() - word.trim_start_matches(is_word).len()
+    }
+
+    /// Cuts `start..end` onto the kill ring.
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.kills.push(self.text.drain(start..end).collect());
+        if self.kills.len() > KILL_RING {
+            self.kills.remove(0);
+        }
+        self.cursor = start;
+    }
+
+    /// Pastes the `back`th latest cut at the cursor.
+    fn yank(&mut self, back: usize) {
+        let index = match self.kills.len().checked_sub(back + 1) {
+            Some(index) => index,
+            None => return,
+        };
+        let start = self.cursor;
+        let cut = self.kills[index].clone();
+        self.insert_str(&cut);
+        self.yanked = Some((start, back));
+    }
+
+    /// Swaps the text just pasted for the cut before it.
+    fn yank_older(&mut self) {
+        let (start, back) = match self.yanked {
+            Some(yanked) if !self.kills.is_empty() => yanked,
+            _ => return,
+        };
+        self.text.drain(start..self.cursor);
+        self.cursor = start;
+        self.yank((back + 1) % self.kills.len());
+    }
+
+    /// Handles the editing keys. Enter, esc, tab and the up and down
+    /// arrows are left to the prompt, as are other keys with ctrl or alt.
+    pub fn handle_key(&mut self, key: KeyEvent) -> InputKey {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let yanked = self.yanked.take();
+        let len = self.text.len();
+        let cursor = self.cursor;
+        match key.code {
+            KeyCode::Left if ctrl => self.cursor = self.word_start(),
+            KeyCode::Right if ctrl => self.cursor = self.word_end(),
+            KeyCode::Char('b') if alt => self.cursor = self.word_start(),
+            KeyCode::Char('f') if alt => self.cursor = self.word_end(),
+            KeyCode::Left => self.cursor = self.prev_char(),
+            KeyCode::Right => self.cursor = self.next_char(),
+            KeyCode::Char('b') if ctrl => self.cursor = self.prev_char(),
+            KeyCode::Char('f') if ctrl => self.cursor = self.next_char(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            KeyCode::Char('e') if ctrl => self.cursor = len,
+            KeyCode::Backspace if alt || ctrl => self.kill(self.word_start(), cursor),
+            KeyCode::Char('w') if ctrl => self.kill(self.word_start(), cursor),
+            KeyCode::Char('d') if alt => self.kill(cursor, self.word_end()),
+            KeyCode::Char('k') if ctrl => self.kill(cursor, len),
+            KeyCode::Char('u') if ctrl => self.kill(0, cursor),
+            KeyCode::Char('y') if ctrl => self.yank(0),
+            KeyCode::Char('y') if alt => {
+                self.yanked = yanked;
+                self.yank_older();
+            }
+            KeyCode::Backspace => {
+                let start = self.prev_char();
+                self.text.drain(start..cursor);
+                self.cursor = start;
+            }
+            KeyCode::Delete => {
+                let end = self.next_char();
+                self.text.drain(cursor..end);
+            }
+            KeyCode::Char('d') if ctrl => {
+                let end = self.next_char();
+                self.text.drain(cursor..end);
+            }
+            KeyCode::Char(c) if !ctrl && !alt => self.insert(c),
+            _ => {
+                self.yanked = yanked;
+                return InputKey::Ignored;
+            }
+        }
+        match self.text.len() != len || self.yanked.is_some() {
+            true => InputKey::Edited,
+            false => InputKey::Moved,
+        }
+    }
+
+    /// Completes the word before the cursor as far as the candidates
+    /// `complete` gives for the text before it agree, returning them.
+    pub fn complete(&mut self, complete: impl FnOnce(&str) -> Vec<String>) -> Vec<String> {
+        let candidates = complete(&self.text[..self.cursor]);
+        let first = match candidates.first() {
+            Some(first) => first,
+            None => return candidates,
+        };
+        let common = candidates.iter().fold(first.as_str(), |common, c| {
+            let len = common
+                .char_indices()
+                .zip(c.chars())
+                .take_while(|((_, a), b)| a == b)
+                .last()
+                .map_or(0, |((i, a), _)| i + a.len_utf8());
+            &common[..len]
+        });
+        let start = self.text[..self.cursor].rfind(' ').map_or(0, |i| i + 1);
+        let common = common.to_string();
+        self.text.replace_range(start..self.cursor, &common);
+        self.cursor = start + common.len();
+        candidates
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Draws a [`TextInput`] after its prompt, scrolled sideways to keep the
+/// cursor in view. Only the cells written to are drawn over.
+pub struct TextInputView<'a> {
+    input: &'a TextInput,
+    prompt: &'a str,
+    style: Style,
+}
+
+impl<'a> TextInputView<'a> {
+    pub fn new(input: &'a TextInput) -> Self {
+        TextInputView {
+            input,
+            prompt: "",
+            style: Style::default(),
+        }
+    }
+
+    pub fn prompt(mut self, prompt: &'a str) -> Self {
+        self.prompt = prompt;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Columns of the text scrolled out of view on the left.
+    fn scroll(&self, area: Rect) -> usize {
+        let room = (area.width as usize).saturating_sub(self.prompt.width() + 1);
+        self.input.cursor_width().saturating_sub(room)
+    }
+
+    /// Where the terminal's cursor goes when the view is drawn in `area`.
+    pub fn cursor(&self, area: Rect) -> Position {
+        let x = self.prompt.width() + self.input.cursor_width() - self.scroll(area);
+        let x = area.x + (x as u16).min(area.width.saturating_sub(1));
+        Position::new(x, area.y)
+    }
+}
+
+impl Widget for TextInputView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        let width = area.width as usize;
+        let (x, _) = buf.set_stringn(area.x, area.y, self.prompt, width, self.style);
+        // Written a char at a time into the cells, so drawing doesn't
+        // allocate.
+        let (mut x, mut skip, mut last) = (x, self.scroll(area), None);
+        for c in self.input.text().chars().filter(|c| !c.is_control()) {
+            let w = c.width().unwrap_or(0) as u16;
+            if skip > 0 {
+                skip = skip.saturating_sub(w.into());
+                continue;
+            }
+            if w == 0 {
+                // Combining marks join the char before them.
+                if let Some(last) = last {
+                    let cell = &mut buf[(last, area.y)];
+                    let mut symbol = cell.symbol().to_owned();
+                    symbol.push(c);
+                    cell.set_symbol(&symbol);
+                }
+                continue;
+            }
+            if x.saturating_add(w) > area.right() {
+                break;
+            }
+            buf[(x, area.y)].set_char(c).set_style(self.style);
+            // The cell a wide char covers is left blank.
+            for covered in x + 1..x + w {
+                buf[(covered, area.y)].reset();
+            }
+            last = Some(x);
+            x += w;
+        }
+    }
+}
+
+#[test]
+fn test_text_input_edits_by_words_and_yanks() {
+    let key = |code, modifiers| KeyEvent::new(code, modifiers);
+    let (none, ctrl, alt) = (KeyModifiers::NONE, KeyModifiers::CONTROL, KeyModifiers::ALT);
+    let mut input = TextInput::new("set tree.sort name");
+    assert_eq!(
+        input.handle_key(key(KeyCode::Char('w'), ctrl)),
+        InputKey::Edited
+    );
+    assert_eq!(input.text(), "set tree.sort ");
+    input.handle_key(key(KeyCode::Char('b'), alt));
+    assert_eq!(&input.text()[input.cursor()..], "sort ");
+    assert_eq!(input.handle_key(key(KeyCode::Left, none)), InputKey::Moved);
+    input.handle_key(key(KeyCode::Char('u'), ctrl));
+    assert_eq!(input.text(), ".sort ");
+    input.handle_key(key(KeyCode::Char('e'), ctrl));
+    input.handle_key(key(KeyCode::Char('y'), ctrl));
+    assert_eq!(input.text(), ".sort set tree");
+    // Alt-y swaps the paste for the cut before it.
+    input.handle_key(key(KeyCode::Char('y'), alt));
+    assert_eq!(input.text(), ".sort name");
+    assert_eq!(
+        input.handle_key(key(KeyCode::Enter, none)),
+        InputKey::Ignored
+    );
+
+    let mut input = TextInput::new("set tree.s");
+    let candidates = input.complete(|typed| {
+        assert_eq!(typed, "set tree.s");
+        vec!["tree.show_hidden".to_string(), "tree.sort".to_string()]
+    });
+    assert_eq!(candidates.len(), 2);
+    assert_eq!(input.text(), "set tree.s");
+    input.handle_key(key(KeyCode::Char('o'), none));
+    input.complete(|_| vec!["tree.sort".to_string()]);
+    assert_eq!(input.text(), "set tree.sort");
+
+    let input = TextInput::new("hunter2");
+    let area = Rect::new(0, 0, 8, 1);
+    let mut buf = Buffer::empty(area);
+    let view = TextInputView::new(&input).prompt("> ");
+    assert_eq!(view.cursor(area), Position::new(7, 0));
+    view.render(area, &mut buf);
+    let row: String = (0..8).map(|x| buf[(x, 0)].symbol()).collect();
+    // Scrolled so the cursor, after the last of the seven, stays in view.
+    assert_eq!(row, "> nter2 ");
+}
+
+#[test]
+fn test_text_input_draws_wide_and_combining_chars() {
+    let mut input = TextInput::default();
+    "e\u{301}漢字ab".chars().for_each(|c| input.insert(c));
+    let area = Rect::new(0, 0, 8, 1);
+    let row = |input: &TextInput| {
+        let mut buf = Buffer::empty(area);
+        TextInputView::new(input)
+            .prompt("> ")
+            .render(area, &mut buf);
+        (0..8).map(|x| buf[(x, 0)].symbol()).collect::<String>()
+    };
+    // Scrolled past the accented e and the first wide char.
+    assert_eq!(row(&input), "> 字 ab  ");
+    input.handle_key(KeyEvent::new(KeyCode::Home, KeyModifiers::NONE));
+    // The mark stays on its e, and the wide chars' second columns are
+    // blank.
+    assert_eq!(row(&input), "> e\u{301}漢 字 a");
+}
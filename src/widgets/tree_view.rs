@@ -0,0 +1,375 @@
+//! Trees drawn a row per item, indented by depth, with a marker on the
+//! items that have children: the selection, expansion and scrolling the
+//! structural viewer, the outline and the git log share. The file tree
+//! keeps its own, stored compactly and loaded a directory at a time, but
+//! draws each of its rows as a [`Row`] the same way.
+
+use std::{borrow::Cow, collections::HashSet};
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+use super::viewport::Viewport;
+
+/// Something a [`TreeView`] can show.
+pub trait TreeItem: Sized {
+    /// Names the item among all of the tree's, to remember it expanded by.
+    fn id(&self) -> &str;
+
+    fn label(&self) -> Cow<'_, str>;
+
+    fn children(&self) -> &[Self] {
+        &[]
+    }
+
+    /// Drawn after the label, in the view's detail style.
+    fn detail(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Which items of a tree are expanded, and which row is selected.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TreeState {
+    expanded: HashSet<String>,
+    selected: usize,
+}
+
+impl TreeState {
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Selects `row` as it is; [`clamp`](Self::clamp) keeps it in the tree.
+    pub fn select(&mut self, row: usize) {
+        self.selected = row;
+    }
+
+    pub fn is_expanded(&self, id: &str) -> bool {
+        self.expanded.contains(id)
+    }
+
+    pub fn expand(&mut self, id: &str) {
+        self.expanded.insert(id.to_string());
+    }
+
+    pub fn collapse(&mut self, id: &str) {
+        self.expanded.remove(id);
+    }
+
+    /// The rows shown, in order, each item with its depth: `roots`, with
+    /// the children of the expanded ones under them. Stops once `limit`
+    /// rows have been collected.
+    pub fn rows<'t, T: TreeItem>(&self, roots: &'t [T], limit: usize) -> Vec<(usize, &'t T)> {
+        let mut rows = Vec::new();
+        let mut stack: Vec<(usize, &T)> = roots.iter().rev().map(|r| (0, r)).collect();
+        while rows.len() < limit {
+            let (depth, next) = match stack.pop() {
+                Some(next) => next,
+                None => break,
+            };
+            rows.push((depth, next));
+            if self.expanded.contains(next.id()) {
+                stack.extend(next.children().iter().rev().map(|c| (depth + 1, c)));
+            }
+        }
+        rows
+    }
+
+    pub fn selected_item<'t, T: TreeItem>(&self, roots: &'t [T]) -> Option<&'t T> {
+        let rows = self.rows(roots, self.selected + 1);
+        rows.get(self.selected).map(|&(_, item)| item)
+    }
+
+    pub fn select_next<T: TreeItem>(&mut self, roots: &[T]) {
+        if self.selected + 1 < self.rows(roots, self.selected + 2).len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Keeps the selection on a row of `roots`, e.g. once they've shrunk.
+    pub fn clamp<T: TreeItem>(&mut self, roots: &[T]) {
+        let len = self.rows(roots, self.selected + 1).len();
+        self.selected = self.selected.min(len.saturating_sub(1));
+    }
+
+    pub fn expand_selected<T: TreeItem>(&mut self, roots: &[T]) {
+        if let Some(item) = self.selected_item(roots) {
+            if !item.children().is_empty() {
+                self.expanded.insert(item.id().to_string());
+            }
+        }
+    }
+
+    /// Collapses the selected item, or moves the selection to its parent
+    /// when it is already collapsed.
+    pub fn collapse_selected<T: TreeItem>(&mut self, roots: &[T]) {
+        let rows = self.rows(roots, self.selected + 1);
+        let (depth, item) = match rows.get(self.selected) {
+            Some(&row) => row,
+            None => return,
+        };
+        if let Some(parent) = rows[..self.selected].iter().rposition(|(d, _)| *d < depth) {
+            if !self.expanded.contains(item.id()) {
+                self.selected = parent;
+            }
+        }
+        self.expanded.remove(item.id());
+    }
+
+    pub fn toggle_selected<T: TreeItem>(&mut self, roots: &[T]) {
+        let expanded = self.selected_item(roots).map(|i| self.is_expanded(i.id()));
+        match expanded {
+            Some(true) => self.collapse_selected(roots),
+            Some(false) => self.expand_selected(roots),
+            None => (),
+        }
+    }
+}
+
+/// What's drawn before a row's label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// Nothing, in a tree where nothing has children.
+    None,
+    /// Blank, so the label lines up with those that have a marker.
+    Leaf,
+    Expanded,
+    /// An item with children that aren't shown.
+    Collapsed,
+    /// A file marked for comparing.
+    Marked,
+    /// A row standing in for entries that aren't loaded.
+    More,
+}
+
+impl Marker {
+    /// The marker with a space after it, in plain ASCII for screen readers
+    /// if `plain`.
+    pub fn symbol(self, plain: bool) -> &'static str {
+        match (self, plain) {
+            (Marker::None, _) => "",
+            (Marker::Leaf, _) => "  ",
+            (Marker::Expanded, false) => "▾ ",
+            (Marker::Expanded, true) => "- ",
+            (Marker::Collapsed, false) => "▸ ",
+            (Marker::Collapsed, true) => "+ ",
+            (Marker::Marked, false) => "● ",
+            (Marker::Marked, true) => "* ",
+            (Marker::More, false) => "  … ",
+            (Marker::More, true) => "  ... ",
+        }
+    }
+}
+
+/// One row of a tree: highlighted all the way across when it's selected,
+/// and its label after a marker, indented by depth.
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    pub depth: usize,
+    pub marker: Marker,
+    pub label: &'a str,
+    pub style: Style,
+    pub selected: bool,
+}
+
+impl Row<'_> {
+    /// Draws the row on `line`, each level of depth indented `indent`
+    /// columns, keeping the label out of the last `reserved` columns for
+    /// the caller to fill. Returns the column after the label, or `None`
+    /// when the indent leaves no room for it.
+    pub fn render(
+        self,
+        line: Rect,
+        buf: &mut Buffer,
+        indent: u16,
+        reserved: u16,
+        plain: bool,
+    ) -> Option<u16> {
+        if self.selected {
+            buf.set_style(line, self.style);
+        }
+        let width = line.width.saturating_sub(reserved);
+        let depth = u16::try_from(self.depth).unwrap_or(u16::MAX);
+        let indent = depth.saturating_mul(indent);
+        if indent >= width {
+            return None;
+        }
+        let (x, room) = (line.x + indent, (width - indent) as usize);
+        let marker = self.marker.symbol(plain);
+        let (after, _) = buf.set_stringn(x, line.y, marker, room, self.style);
+        let room = room.saturating_sub((after - x) as usize);
+        let (end, _) = buf.set_stringn(after, line.y, self.label, room, self.style);
+        Some(end)
+    }
+}
+
+/// Renders `roots` as their [`TreeState`] has them, scrolled so that the
+/// selected row is visible. Items are marked expanded or collapsed when
+/// any root has children.
+pub struct TreeView<'a, T> {
+    roots: &'a [T],
+    state: &'a TreeState,
+    indent: u16,
+    style: Style,
+    highlight_style: Style,
+    detail_style: Style,
+    plain: bool,
+    empty: &'a str,
+}
+
+impl<'a, T: TreeItem> TreeView<'a, T> {
+    pub fn new(roots: &'a [T], state: &'a TreeState) -> Self {
+        TreeView {
+            roots,
+            state,
+            indent: 2,
+            style: Style::default(),
+            highlight_style: Style::default(),
+            detail_style: Style::default(),
+            plain: false,
+            empty: "",
+        }
+    }
+
+    /// Columns each level of nesting is indented by.
+    pub fn indent(mut self, indent: u16) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    pub fn detail_style(mut self, style: Style) -> Self {
+        self.detail_style = style;
+        self
+    }
+
+    /// Draws the markers in plain ASCII, for screen readers.
+    pub fn plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    /// What to say when there's nothing to show.
+    pub fn empty(mut self, text: &'a str) -> Self {
+        self.empty = text;
+        self
+    }
+}
+
+impl<T: TreeItem> Widget for TreeView<'_, T> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width < 1 || area.height < 1 {
+            return;
+        }
+        buf.set_style(area, self.style);
+        if self.roots.is_empty() {
+            buf.set_stringn(area.x, area.y, self.empty, area.width as usize, self.style);
+            return;
+        }
+
+        let nested = self.roots.iter().any(|r| !r.children().is_empty());
+        let h = area.height as usize;
+        let selected = self.state.selected;
+        let offset = Viewport::showing(selected, h).offset();
+        let rows = self.state.rows(self.roots, offset + h);
+        for (i, (depth, item)) in rows.into_iter().enumerate().skip(offset) {
+            let y = area.y + (i - offset) as u16;
+            let style = match i == selected {
+                true => self.highlight_style,
+                false => self.style,
+            };
+            let marker = match (nested, item.children().is_empty()) {
+                (false, _) => Marker::None,
+                (true, true) => Marker::Leaf,
+                (true, false) if self.state.is_expanded(item.id()) => Marker::Expanded,
+                (true, false) => Marker::Collapsed,
+            };
+            let label = item.label();
+            let row = Row {
+                depth,
+                marker,
+                label: &label,
+                style,
+                selected: i == selected,
+            };
+            let line = Rect::new(area.x, y, area.width, 1);
+            let x = match row.render(line, buf, self.indent, 0, self.plain) {
+                Some(x) => x,
+                None => continue,
+            };
+            if let Some(detail) = item.detail() {
+                let room = area.right().saturating_sub(x + 1) as usize;
+                buf.set_stringn(x + 1, y, detail, room, self.detail_style.patch(style));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tree_view_walks_and_scrolls_nested_items() {
+    let text = "{\"a\": {\"x\": 1, \"y\": 2}, \"b\": 3}";
+    let root = crate::data::parse(std::path::Path::new("t.json"), text).unwrap();
+    let roots = root.children();
+    let mut state = TreeState::default();
+    let ids = |state: &TreeState| -> Vec<String> {
+        let rows = state.rows(roots, usize::MAX);
+        rows.iter().map(|(_, n)| n.id().to_string()).collect()
+    };
+    assert_eq!(ids(&state), ["$.a", "$.b"]);
+    state.toggle_selected(roots);
+    assert_eq!(ids(&state), ["$.a", "$.a.x", "$.a.y", "$.b"]);
+    state.select_next(roots);
+    state.select_next(roots);
+    assert_eq!(state.selected_item(roots).unwrap().id(), "$.a.y");
+
+    let area = Rect::new(0, 0, 10, 2);
+    let mut buf = Buffer::empty(area);
+    TreeView::new(roots, &state).render(area, &mut buf);
+    let row = |buf: &Buffer, y| -> String { (0..10).map(|x| buf[(x, y)].symbol()).collect() };
+    // Scrolled down a row to keep y in view.
+    assert_eq!(row(&buf, 0).trim_end(), "    x: 1");
+    assert_eq!(row(&buf, 1).trim_end(), "    y: 2");
+
+    state.collapse_selected(roots);
+    assert_eq!(state.selected_item(roots).unwrap().id(), "$.a");
+    state.collapse_selected(roots);
+    assert_eq!(ids(&state), ["$.a", "$.b"]);
+    let mut buf = Buffer::empty(area);
+    TreeView::new(roots, &state)
+        .plain(true)
+        .render(area, &mut buf);
+    assert_eq!(row(&buf, 0).trim_end(), "+ a: {2}");
+
+    state.select(5);
+    state.clamp(roots);
+    assert_eq!(state.selected(), 1);
+
+    // Rows as the file tree draws them, names clear of its detail columns.
+    let mut buf = Buffer::empty(area);
+    let row = Row {
+        depth: 1,
+        marker: Marker::Marked,
+        label: "main.rs",
+        style: Style::default(),
+        selected: false,
+    };
+    assert_eq!(row.render(area, &mut buf, 2, 3, true), Some(7));
+    assert_eq!(
+        (0..10).map(|x| buf[(x, 0)].symbol()).collect::<String>(),
+        "  * mai   "
+    );
+    assert_eq!(row.render(area, &mut buf, 7, 3, true), None);
+}
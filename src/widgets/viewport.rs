@@ -0,0 +1,173 @@
+//! Which rows of something longer than its view are in view: the first
+//! one shown and how many fit. The editor, hex view and diff keep one
+//! between frames, told their height as they're drawn; lists drawn afresh
+//! each frame make one on the spot to show their selection.
+
+use std::ops::Range;
+
+/// How [`Viewport::scroll_into_view`] brings a row into view.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Scroll only as far as it takes, so the row ends up on the edge it
+    /// came in from.
+    #[default]
+    Follow,
+    /// Put a row that's out of view in the middle, once, then go back to
+    /// following. For jumps, so what's around the place landed on shows.
+    Center,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    offset: usize,
+    height: usize,
+    policy: Policy,
+    past_end: bool,
+}
+
+impl Viewport {
+    pub fn new(height: usize) -> Self {
+        Viewport {
+            height,
+            ..Viewport::default()
+        }
+    }
+
+    /// A view `height` rows tall scrolled down from the top just far
+    /// enough to show `row`.
+    pub fn showing(row: usize, height: usize) -> Self {
+        Viewport {
+            offset: (row + 1).saturating_sub(height),
+            height,
+            ..Viewport::default()
+        }
+    }
+
+    /// Lets the last row scroll up to the top, leaving the rest of the
+    /// view blank, so that any row can be the first shown.
+    pub fn past_end(mut self, past_end: bool) -> Self {
+        self.past_end = past_end;
+        self
+    }
+
+    /// The first row shown.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The rows shown, some perhaps past the end.
+    pub fn rows(&self) -> Range<usize> {
+        self.offset..self.offset + self.height
+    }
+
+    pub fn contains(&self, row: usize) -> bool {
+        self.rows().contains(&row)
+    }
+
+    /// How the next [`scroll_into_view`](Self::scroll_into_view) goes.
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = policy;
+    }
+
+    /// The furthest down it scrolls over `len` rows.
+    fn max_offset(&self, len: usize) -> usize {
+        match self.past_end {
+            true => len.saturating_sub(1),
+            false => len.saturating_sub(self.height),
+        }
+    }
+
+    /// Scrolls to show `offset` first, or as near as `len` rows allow.
+    pub fn scroll_to(&mut self, offset: usize, len: usize) {
+        self.offset = offset.min(self.max_offset(len));
+    }
+
+    pub fn scroll_by(&mut self, rows: isize, len: usize) {
+        self.scroll_to(self.offset.saturating_add_signed(rows), len);
+    }
+
+    /// Makes the view `height` rows tall, over `len` rows now, as when the
+    /// terminal's resized or the text shrinks. The first row stays where
+    /// it is unless that would leave rows blank at the end.
+    pub fn resize(&mut self, height: usize, len: usize) {
+        self.height = height;
+        self.scroll_to(self.offset, len);
+    }
+
+    /// Scrolls, as the policy has it, so that `row` of `len` is in view.
+    /// A view with no height has nowhere to show it, and doesn't move.
+    pub fn scroll_into_view(&mut self, row: usize, len: usize) {
+        if self.height == 0 {
+            return;
+        }
+        let offset = match self.policy {
+            _ if self.contains(row) => self.offset,
+            Policy::Center => row.saturating_sub(self.height / 2),
+            Policy::Follow if row < self.offset => row,
+            Policy::Follow => row + 1 - self.height,
+        };
+        self.policy = Policy::Follow;
+        self.scroll_to(offset, len);
+    }
+}
+
+#[test]
+fn test_viewport_follows_and_centers() {
+    let mut view = Viewport::new(10);
+    view.scroll_into_view(25, 100);
+    assert_eq!(view.rows(), 16..26);
+    view.scroll_into_view(20, 100);
+    assert_eq!(view.offset(), 16);
+    view.scroll_into_view(3, 100);
+    assert_eq!(view.offset(), 3);
+
+    // Centred once, then following again.
+    view.set_policy(Policy::Center);
+    view.scroll_into_view(50, 100);
+    assert_eq!(view.rows(), 45..55);
+    view.scroll_into_view(56, 100);
+    assert_eq!(view.offset(), 47);
+    // Near the end there's only so far to go.
+    view.set_policy(Policy::Center);
+    view.scroll_into_view(98, 100);
+    assert_eq!(view.offset(), 90);
+
+    assert_eq!(Viewport::showing(4, 5).offset(), 0);
+    assert_eq!(Viewport::showing(12, 5).rows(), 8..13);
+    assert_eq!(Viewport::showing(3, 0).offset(), 4);
+}
+
+#[test]
+fn test_viewport_keeps_in_bounds_as_things_shrink() {
+    // Text cut short under the view pulls it back up.
+    let mut view = Viewport::new(10);
+    view.scroll_to(40, 50);
+    assert_eq!(view.offset(), 40);
+    view.resize(10, 45);
+    assert_eq!(view.offset(), 35);
+    view.resize(10, 4);
+    assert_eq!(view.offset(), 0);
+
+    // A taller terminal shows more above rather than blank rows below,
+    // and a shorter one keeps the first row where it was.
+    view.scroll_to(90, 100);
+    view.resize(20, 100);
+    assert_eq!(view.rows(), 80..100);
+    view.resize(5, 100);
+    assert_eq!(view.offset(), 80);
+    // Shrunk to nothing, it stays put until there's room again.
+    view.resize(0, 100);
+    view.scroll_into_view(10, 100);
+    assert_eq!(view.offset(), 80);
+
+    let mut view = Viewport::new(10).past_end(true);
+    view.scroll_by(200, 50);
+    assert_eq!(view.offset(), 49);
+    view.scroll_by(-60, 50);
+    assert_eq!(view.offset(), 0);
+    assert_eq!(Viewport::default().past_end(true).max_offset(0), 0);
+}
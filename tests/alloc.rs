@@ -0,0 +1,68 @@
+//! Counts the allocations made while drawing, so that per-row allocations
+//! don't creep back into the render path. A test binary of its own, as the
+//! counting allocator replaces the global one.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    path::Path,
+};
+
+use ratatui::{buffer::Buffer as TermBuffer, layout::Rect, widgets::Widget};
+use wyv::{
+    buffer::Buffer,
+    widgets::{editor::EditorView, file_tree::FileTree, file_tree::FileTreeView},
+};
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Allocations made on this thread by drawing `widget` into an `area`
+/// `height` rows tall.
+fn allocations(height: u16, widget: impl Widget) -> usize {
+    let area = Rect::new(0, 0, 60, height);
+    let mut buf = TermBuffer::empty(area);
+    let before = ALLOCATIONS.with(Cell::get);
+    widget.render(area, &mut buf);
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_drawing_allocates_the_same_for_any_number_of_rows() {
+    let mut tree = FileTree::new(Path::new("./src")).unwrap();
+    tree.expand_all();
+    assert!(tree.row_count() > 50);
+    let few = allocations(5, FileTreeView::new(&tree));
+    let many = allocations(50, FileTreeView::new(&tree));
+    assert_eq!(few, many);
+    assert!(many < 8, "drawing the tree made {} allocations", many);
+
+    let text = "let line = 1; // a line of code\n".repeat(200);
+    let buffer = Buffer::from_text(&text);
+    let few = allocations(5, EditorView::new(&buffer));
+    let many = allocations(50, EditorView::new(&buffer));
+    assert_eq!(few, many);
+    assert!(many < 4, "drawing the editor made {} allocations", many);
+}
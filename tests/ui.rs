@@ -0,0 +1,2443 @@
+use std::{collections::HashMap, fs, path::Path, time::Instant};
+#[cfg(feature = "git")]
+use std::{path::PathBuf, process::Command};
+
+use ratatui::style::Color;
+use serde_json::json;
+use wyv::{
+    app::{Mode, Panel},
+    buffer::Position,
+    harness::Harness,
+    layers::Layer,
+    remote::Request,
+    runtime::Message,
+};
+
+#[test]
+fn test_j_moves_tree_selection_down() {
+    let mut h = Harness::new(Path::new("./src"), 80, 20).unwrap();
+    assert_eq!(h.app.tree.selected(), 0);
+
+    h.keys("jj").unwrap();
+    assert_eq!(h.app.tree.selected(), 2);
+    h.keys("k").unwrap();
+    assert_eq!(h.app.tree.selected(), 1);
+}
+
+#[test]
+fn test_enter_opens_file_into_editor() {
+    let mut h = Harness::new(Path::new("./src"), 100, 20).unwrap();
+    while !h
+        .app
+        .tree
+        .selected_node()
+        .unwrap()
+        .path()
+        .ends_with("lib.rs")
+    {
+        h.keys("j").unwrap();
+    }
+
+    h.keys("<enter>").unwrap();
+    assert_eq!(h.app.buffers.len(), 1);
+    assert!(h.screen_contains("lib.rs").unwrap());
+    assert!(h.screen_contains("pub mod app;").unwrap());
+}
+
+#[test]
+fn test_open_command_takes_a_position() {
+    let mut h = Harness::new(Path::new("./src"), 100, 20).unwrap();
+    h.keys(":open widgets/editor.rs:5:3<enter>").unwrap();
+    let buffer = h.app.buffer().unwrap();
+    assert!(buffer.path().unwrap().ends_with("widgets/editor.rs"));
+    assert_eq!(buffer.cursor(), Position::new(4, 2));
+
+    h.keys(":open<enter>").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("open needs a file, e.g. src/main.rs:12")
+    );
+}
+
+#[test]
+fn test_log_command_toggles_panel() {
+    let mut h = Harness::new(Path::new("./src"), 80, 24).unwrap();
+    h.keys(":log<enter>").unwrap();
+    assert_eq!(h.app.panel, Some(Panel::Log));
+    assert!(h.screen_contains(" Log ").unwrap());
+
+    h.keys(":log<enter>").unwrap();
+    assert_eq!(h.app.panel, None);
+}
+
+#[test]
+fn test_unknown_command_reports_in_status_bar() {
+    let mut h = Harness::new(Path::new("./src"), 80, 10).unwrap();
+    h.keys(":frobnicate<enter>").unwrap();
+    let screen = h.screen().unwrap();
+    assert!(screen
+        .last()
+        .unwrap()
+        .contains("unknown command `frobnicate`"));
+}
+
+#[test]
+fn test_remote_requests_drive_app() {
+    let mut h = Harness::new(Path::new("./src"), 80, 20).unwrap();
+    let request = |method: &str, params| Request {
+        method: method.to_string(),
+        params,
+    };
+
+    h.app
+        .handle_remote(&request("keys", json!({ "keys": "jj" })))
+        .unwrap();
+    h.app
+        .handle_remote(&request("open", json!({ "files": ["./src/lib.rs:3"] })))
+        .unwrap();
+    let state = h.app.handle_remote(&request("state", json!(null))).unwrap();
+    assert_eq!(
+        state["buffers"][0]["cursor"],
+        json!({ "line": 2, "col": 0 })
+    );
+    assert_eq!(state["active"], json!(0));
+    let selected = h.app.tree.node_path(h.app.tree.selected_node().unwrap());
+    assert_eq!(state["selected"], json!(selected));
+
+    let error = h
+        .app
+        .handle_remote(&request("command", json!({ "line": "frobnicate" })))
+        .unwrap_err();
+    assert_eq!(error.message, "unknown command `frobnicate`");
+    assert!(h.app.handle_remote(&request("nope", json!(null))).is_err());
+}
+
+#[test]
+fn test_quit_with_dirty_buffer_asks_first() {
+    let mut h = Harness::new(Path::new("./src"), 80, 20).unwrap();
+    h.app.open(Path::new("./src/lib.rs"), None).unwrap();
+    h.app.buffer_mut().unwrap().insert("// edit\n").unwrap();
+
+    h.keys("<ctrl-c>").unwrap();
+    assert!(!h.app.exit);
+    assert!(h.screen_contains("Unsaved buffers").unwrap());
+    assert!(h.screen_contains("lib.rs +").unwrap());
+
+    h.keys("c").unwrap();
+    assert!(!h.app.exit);
+    assert!(!h.screen_contains("Unsaved buffers").unwrap());
+
+    h.keys("<ctrl-c>d").unwrap();
+    assert!(h.app.exit);
+
+    h.app.exit = false;
+    h.keys(":q!<enter>").unwrap();
+    assert!(h.app.exit);
+}
+
+#[test]
+fn test_ctrl_z_and_suspend_ask_the_main_loop_to_stop() {
+    let mut h = Harness::new(Path::new("./src"), 80, 20).unwrap();
+    h.keys("<ctrl-z>").unwrap();
+    assert!(h.app.suspend);
+    assert!(!h.app.exit);
+
+    h.app.suspend = false;
+    h.keys(":suspend<enter>").unwrap();
+    assert!(h.app.suspend);
+}
+
+#[test]
+fn test_set_changes_options_with_completion() {
+    let mut h = Harness::new(Path::new("./src"), 100, 10).unwrap();
+    assert!(h.app.config.editor.line_numbers);
+
+    h.keys(":set nonumbers<enter>").unwrap();
+    assert!(!h.app.config.editor.line_numbers);
+    h.keys(":set? numbers<enter>").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("editor.line_numbers = false (set)")
+    );
+
+    h.keys(":set editor.tab_w<tab>=2<enter>").unwrap();
+    assert_eq!(h.app.config.editor.tab_width, 2);
+    h.keys(":set tree.<tab>").unwrap();
+    assert_eq!(h.app.command_line.text(), "set tree.");
+    assert!(h.screen_contains("tree.show_hidden").unwrap());
+    h.keys("<esc>:set editor.tab_width=x<enter>").unwrap();
+    assert!(h.app.status.as_deref().unwrap().contains("invalid value"));
+}
+
+/// A fresh git repository in a temporary directory with `files` committed
+/// by "Test Author", who also makes any commits the test does.
+#[cfg(feature = "git")]
+fn git_repo(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wyv-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        fs::write(dir.join(file), contents).unwrap();
+    }
+    git(&dir, &["init", "-q"]);
+    git(&dir, &["config", "user.name", "Test Author"]);
+    git(&dir, &["config", "user.email", "test@example.com"]);
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Initial commit"]);
+    dir
+}
+
+#[cfg(feature = "git")]
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "git {:?}", args);
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_blame_inline_and_pane() {
+    let dir = git_repo("blame", &[("notes.txt", "first\nsecond\n")]);
+    let mut h = Harness::new(&dir, 120, 12).unwrap();
+    h.app
+        .open(&dir.join("notes.txt"), Some(Position::new(1, 0)))
+        .unwrap();
+
+    h.keys("b").unwrap();
+    assert!(h.app.blame_inline);
+    assert!(h
+        .screen()
+        .unwrap()
+        .iter()
+        .any(|row| row.contains("second  ") && row.contains("Test Author")));
+
+    h.keys(":blame<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Blame);
+    let rows = h.screen().unwrap();
+    assert_eq!(rows.iter().filter(|r| r.contains("Test Author")).count(), 2);
+
+    h.keys("k<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    let title = h.app.buffer().unwrap().title();
+    assert!(title.starts_with("commit "), "{}", title);
+    assert!(h.screen_contains("Initial commit").unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_tree_shows_git_badges() {
+    let dir = git_repo("badges", &[("a.txt", "a\n"), ("b.txt", "b\n")]);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/new.txt"), "new\n").unwrap();
+    fs::write(dir.join("b.txt"), "changed\n").unwrap();
+    let mut config = wyv::config::Config::default();
+    config.tree.details = vec![wyv::widgets::file_tree::Detail::Git];
+
+    let app = wyv::app::App::new(&dir, config).unwrap();
+    let mut h = Harness::with_app(app, 100, 10).unwrap();
+    h.app.refresh_badges();
+    // The tree takes the first 15 columns, the badges the last of them.
+    let badge = |h: &mut Harness, name: &str| {
+        let rows = h.screen().unwrap();
+        let tree: Vec<String> = rows.iter().map(|r| r.chars().take(15).collect()).collect();
+        let row = tree.iter().find(|r| r.contains(name)).unwrap();
+        row.chars().nth(14).unwrap_or(' ')
+    };
+    assert_eq!(badge(&mut h, "b.txt"), 'M');
+    // Git names untracked directories rather than the files in them.
+    assert_eq!(badge(&mut h, "sub"), '?');
+    assert_eq!(badge(&mut h, "a.txt"), ' ');
+
+    // Saving asks git about the saved file alone.
+    h.app.open(&dir.join("a.txt"), None).unwrap();
+    h.keys("ix<esc>:w<enter>").unwrap();
+    h.app.refresh_badges();
+    assert_eq!(badge(&mut h, "a.txt"), 'M');
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_git_panel_stages_hunks_and_commits() {
+    let lines: Vec<String> = (1..=20).map(|i| format!("line {}", i)).collect();
+    let dir = git_repo("status", &[("notes.txt", &(lines.join("\n") + "\n"))]);
+    let mut edited = lines.clone();
+    edited[0] = "line one".to_string();
+    edited[19] = "line twenty".to_string();
+    fs::write(dir.join("notes.txt"), edited.join("\n") + "\n").unwrap();
+    fs::write(dir.join("new.txt"), "new\n").unwrap();
+
+    let mut h = Harness::new(&dir, 100, 30).unwrap();
+    h.keys(":git<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::GitStatus);
+    assert!(h.screen_contains(" M notes.txt").unwrap());
+    assert!(h.screen_contains("?? new.txt").unwrap());
+
+    // Stage only the first of the two hunks.
+    h.keys("<enter>js").unwrap();
+    assert_eq!(h.app.status, None);
+    assert!(h.screen_contains("@@ -1,4 +1,4 @@ (staged)").unwrap());
+    assert!(h.screen_contains("(unstaged)").unwrap());
+
+    h.keys("cFix the first line<enter>").unwrap();
+    let status = h.app.status.clone().unwrap();
+    assert!(status.contains("Fix the first line"), "{}", status);
+    assert_eq!(h.app.mode, Mode::GitStatus);
+    // The second hunk is left over, unstaged.
+    assert!(h.screen_contains(" M notes.txt").unwrap());
+    assert!(h
+        .screen_contains("@@ -17,4 +17,4 @@ line 16 (unstaged)")
+        .unwrap());
+    assert!(!h.screen_contains("(staged)").unwrap());
+
+    h.keys(":commit nothing staged<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("nothing staged to commit"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_git_log_filters_and_file_history() {
+    let dir = git_repo("log", &[("a.txt", "a\n"), ("b.txt", "b\n")]);
+    fs::write(dir.join("a.txt"), "a, tweaked\n").unwrap();
+    git(&dir, &["commit", "-q", "-am", "Tweak a"]);
+
+    let mut h = Harness::new(&dir, 100, 30).unwrap();
+    h.keys(":gitlog<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::GitLog);
+    assert!(h.screen_contains("Test Author Tweak a").unwrap());
+    assert!(h.screen_contains("Test Author Initial commit").unwrap());
+
+    h.keys("/initl<enter>").unwrap();
+    assert!(h.screen_contains("Git log /initl").unwrap());
+    assert!(!h.screen_contains("Tweak a").unwrap());
+    h.keys("<enter>").unwrap();
+    assert!(h.app.buffer().unwrap().title().starts_with("commit "));
+    assert!(h.screen_contains("Initial commit").unwrap());
+
+    h.keys("<esc>").unwrap();
+    h.app.open(&dir.join("b.txt"), None).unwrap();
+    h.keys(":history<enter>").unwrap();
+    let log = h.app.git_log.as_ref().unwrap();
+    assert_eq!(log.title(), "History of b.txt");
+    let subjects: Vec<&str> = log.visible().iter().map(|c| c.subject.as_str()).collect();
+    assert_eq!(subjects, ["Initial commit"]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_branch_switch_guards_dirty_buffers() {
+    let dir = git_repo("branches", &[("a.txt", "a\n")]);
+    git(&dir, &["switch", "-q", "-c", "feature"]);
+    fs::write(dir.join("a.txt"), "a on feature\n").unwrap();
+    git(&dir, &["commit", "-q", "-am", "Change a"]);
+    git(&dir, &["switch", "-q", "-"]);
+
+    let mut h = Harness::new(&dir, 100, 30).unwrap();
+    h.app.open(&dir.join("a.txt"), None).unwrap();
+    h.app.buffer_mut().unwrap().insert("unsaved ").unwrap();
+
+    h.keys(":branches<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Branches);
+    assert!(h.screen_contains("Branches").unwrap());
+    assert!(h.screen_contains("  feature").unwrap());
+    // The current branch comes after `feature` and is selected.
+    h.keys("k<enter>").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("a.txt + differ on feature; save or discard the changes first")
+    );
+    assert_eq!(h.app.mode, Mode::Branches);
+
+    h.app.buffer_mut().unwrap().reload().unwrap();
+    h.keys("<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("Switched to feature"));
+    assert!(h.screen_contains("a on feature").unwrap());
+
+    h.keys(":branch topic<enter>:branches<enter>").unwrap();
+    assert!(h.screen_contains("* topic").unwrap());
+    h.keys("kkd").unwrap();
+    assert!(h
+        .app
+        .status
+        .as_deref()
+        .unwrap()
+        .starts_with("Deleted branch "));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_project_search_jumps_and_keeps_results() {
+    let dir = std::env::temp_dir().join(format!("wyv-search-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("a.txt"), "alpha\nneedle one\nomega\n").unwrap();
+    fs::write(dir.join("src/b.txt"), "x\ny\nz\nthe Needle\n").unwrap();
+
+    let mut h = Harness::new(&dir, 100, 30).unwrap();
+    h.keys("<ctrl-f>").unwrap();
+    assert_eq!(h.app.mode, Mode::Search);
+    assert!(h
+        .screen_contains("Type a pattern to search the project")
+        .unwrap());
+
+    h.keys("needle").unwrap();
+    assert!(h.screen_contains(" Search (2 in 2 files) ").unwrap());
+    assert!(h.screen_contains("> needle").unwrap());
+    assert!(h.screen_contains("src/b.txt").unwrap());
+    assert!(h.screen_contains("    2: needle one").unwrap());
+    assert!(h.screen_contains("    3- omega").unwrap());
+
+    h.keys("<down><enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.path(), Some(dir.join("src/b.txt").as_path()));
+    assert_eq!(buffer.cursor(), Position::new(3, 4));
+
+    // The overlay opens again as it was left; capitals make it match case.
+    h.keys("<ctrl-f><backspace><backspace><backspace><backspace><backspace><backspace>Needle")
+        .unwrap();
+    assert!(h.screen_contains(" Search (1 in 1 files) ").unwrap());
+    h.keys("(").unwrap();
+    assert!(h.screen_contains("invalid pattern `Needle(`").unwrap());
+    h.keys("<backspace><ctrl-q>").unwrap();
+    assert_eq!(h.app.mode, Mode::Locations);
+    assert!(h.screen_contains(" Search: Needle (1) ").unwrap());
+    assert!(h.screen_contains("src/b.txt:4: the Needle").unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_symbol_pickers_jump_to_definitions() {
+    let dir = std::env::temp_dir().join(format!("wyv-symbols-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("src/tree.rs"),
+        "pub struct Tree;\n\nimpl Tree {\n    pub fn render(&self) {}\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("src/main.py"),
+        "import os\n\ndef run_tree():\n    pass\n",
+    )
+    .unwrap();
+
+    let mut h = Harness::new(&dir, 100, 30).unwrap();
+    h.app.open(&dir.join("src/tree.rs"), None).unwrap();
+    h.keys("s").unwrap();
+    assert_eq!(h.app.mode, Mode::Symbols);
+    assert!(h.screen_contains(" Symbols (3) ").unwrap());
+    assert!(h.screen_contains("impl      Tree").unwrap());
+    h.keys("rndr<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(3, 11));
+
+    h.keys("Stree").unwrap();
+    assert!(h.screen_contains(" Workspace symbols (4) ").unwrap());
+    assert!(h.screen_contains("src/main.py:3").unwrap());
+    h.keys("<backspace><backspace><backspace><backspace>run<enter>")
+        .unwrap();
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.path(), Some(dir.join("src/main.py").as_path()));
+    assert_eq!(buffer.cursor(), Position::new(2, 4));
+
+    fs::write(dir.join("notes.txt"), "fn not_code\n").unwrap();
+    h.app.open(&dir.join("notes.txt"), None).unwrap();
+    h.keys("s").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("no symbols found in notes.txt")
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_outline_follows_edits_and_jumps() {
+    let dir = std::env::temp_dir().join(format!("wyv-outline-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("tree.rs"),
+        "struct Tree;\n\nimpl Tree {\n    fn render(&self) {}\n}\n",
+    )
+    .unwrap();
+
+    let mut h = Harness::new(&dir, 120, 20).unwrap();
+    h.app.open(&dir.join("tree.rs"), None).unwrap();
+    h.keys("o").unwrap();
+    assert_eq!(h.app.mode, Mode::Outline);
+    assert!(h.screen_contains("│ Outline").unwrap());
+    assert!(h.screen_contains("  render function").unwrap());
+
+    h.keys("jj<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(3, 7));
+
+    // Typing updates it, and the symbol the cursor is in is selected.
+    h.keys("i<end><enter>fn added() {}<esc>").unwrap();
+    assert!(h.screen_contains("added function").unwrap());
+    let outline = h.app.outline.as_ref().unwrap();
+    assert_eq!(outline.selected_item().unwrap().symbol.name, "added");
+
+    h.keys(":outline<enter>").unwrap();
+    assert!(h.app.outline.is_none());
+    assert!(!h.screen_contains("│ Outline").unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_structure_of_yaml_jumps_to_source() {
+    let dir = std::env::temp_dir().join(format!("wyv-structure-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("ci.yml"),
+        "name: build\njobs:\n  test:\n    runs-on: linux\n",
+    )
+    .unwrap();
+    fs::write(dir.join("bad.json"), "{\"a\": }").unwrap();
+
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    h.app.open(&dir.join("bad.json"), None).unwrap();
+    h.keys("T").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("bad.json: line 1, column 7: expected a value")
+    );
+
+    h.app.open(&dir.join("ci.yml"), None).unwrap();
+    h.keys("T").unwrap();
+    assert_eq!(h.app.mode, Mode::Structure);
+    assert!(h.screen_contains(" ci.yml structure ").unwrap());
+    assert!(h.screen_contains("  ▸ jobs: {1}").unwrap());
+
+    h.keys("jj<enter>j<enter>j").unwrap();
+    assert!(h.screen_contains("      runs-on: linux").unwrap());
+    assert!(h.screen_contains("$.jobs.test.runs-on  j/k move").unwrap());
+    h.keys("<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(3, 13));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_following_a_log_sticks_to_its_end() {
+    let dir = std::env::temp_dir().join(format!("wyv-follow-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("server.log");
+    let mut text: String = (0..30).map(|i| format!("INFO request {}\n", i)).collect();
+    fs::write(&log, &text).unwrap();
+
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    h.keys(":follow server.log<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Follow);
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(29, 0));
+    assert!(h.screen_contains("INFO request 29").unwrap());
+    assert!(h
+        .screen_contains("Following server.log: space pauses")
+        .unwrap());
+
+    text.push_str("ERROR disk full\nWARN retrying\n");
+    fs::write(&log, &text).unwrap();
+    h.app.handle_message(Message::FileChanged(log.clone()));
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(31, 0));
+    let buffer = h.render().unwrap().clone();
+    // The color of the first char of `text` on screen.
+    let color = |text: &str| {
+        let width = buffer.area.width as usize;
+        let rows: Vec<String> = buffer
+            .content
+            .chunks(width)
+            .map(|row| row.iter().map(|c| c.symbol()).collect())
+            .collect();
+        let (y, x) = rows
+            .iter()
+            .enumerate()
+            .find_map(|(y, row)| row.find(text).map(|x| (y, row[..x].chars().count())))
+            .unwrap();
+        buffer.content[y * width + x].fg
+    };
+    assert_eq!(color("ERROR disk full"), Color::Red);
+    assert_eq!(color("WARN retrying"), Color::Yellow);
+
+    // Scrolling up pauses; new lines then leave the cursor where it is.
+    h.keys("k").unwrap();
+    assert!(h.screen_contains("Paused on server.log").unwrap());
+    text.push_str("INFO recovered\n");
+    fs::write(&log, &text).unwrap();
+    h.app.handle_message(Message::FileChanged(log.clone()));
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(30, 0));
+    assert_eq!(h.app.buffer().unwrap().line_count(), 34);
+    h.keys("G").unwrap();
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(32, 0));
+
+    h.keys("q").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert!(h.app.follow.is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Stands in for `sftp -b -`, running the batch's `ls`, `get` and `put` on
+/// this machine as if it were the host.
+#[cfg(all(unix, feature = "ssh"))]
+const FAKE_SFTP: &str = r#"
+while IFS= read -r line; do
+    echo "sftp> $line"
+    eval "set -- ${line#-}"
+    case $1 in
+    ls) LC_ALL=C ls -la "$3" ;;
+    get | put) cp "$2" "$3" || exit 1 ;;
+    esac
+done
+"#;
+
+#[test]
+#[cfg(all(unix, feature = "ssh"))]
+fn test_remote_tree_over_sftp() {
+    let dir = std::env::temp_dir().join(format!("wyv-ssh-{}", std::process::id()));
+    let host = dir.join("host");
+    fs::create_dir_all(host.join("src")).unwrap();
+    fs::write(host.join("notes.txt"), "remote notes\n").unwrap();
+    fs::write(host.join("src/main.rs"), "fn main() {}\n").unwrap();
+
+    let ssh = wyv::ssh::SshOptions {
+        sftp: "sh".to_string(),
+        args: vec!["-c".to_string(), FAKE_SFTP.to_string(), "sftp".to_string()],
+        mirror: Some(dir.join("mirror")),
+    };
+    let config = wyv::config::Config {
+        ssh,
+        ..Default::default()
+    };
+
+    let app = wyv::app::App::new(&dir, config).unwrap();
+    let mut h = Harness::with_app(app, 100, 20).unwrap();
+    let url = format!("ssh://ada@box{}", host.display());
+    h.keys(&format!(":ssh {}<enter>", url)).unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("Connected to box"));
+    let screen = h.screen().unwrap();
+    assert!(screen.iter().any(|row| row.contains("notes.txt")));
+    assert!(screen.last().unwrap().starts_with("ssh: box"));
+
+    // The mirror holds stand-ins until a file is opened.
+    let mirror = h.app.ssh.as_ref().unwrap().mirror().to_owned();
+    assert_eq!(fs::read_to_string(mirror.join("src/main.rs")).unwrap(), "");
+    h.app.open(&mirror.join("notes.txt"), None).unwrap();
+    let text = |h: &Harness| h.app.buffer().unwrap().text().to_string();
+    assert_eq!(text(&h), "remote notes\n");
+
+    h.keys("iedited <esc>:w<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("Put notes.txt on box"));
+    assert_eq!(
+        fs::read_to_string(host.join("notes.txt")).unwrap(),
+        "edited remote notes\n"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_large_files_are_streamed_in() {
+    let dir = std::env::temp_dir().join(format!("wyv-large-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let text = "a line of a big log\n".repeat(50);
+    fs::write(dir.join("big.log"), &text).unwrap();
+    let mut config = wyv::config::Config::default();
+    config.editor.large_file = 100;
+
+    let app = wyv::app::App::new(&dir, config).unwrap();
+    let mut h = Harness::with_app(app, 100, 20).unwrap();
+    h.app
+        .open(&dir.join("big.log"), Some(Position::new(20, 2)))
+        .unwrap();
+    let buffer = h.app.buffer().unwrap();
+    assert!(buffer.is_large() && !buffer.readonly());
+    assert_eq!(buffer.text().to_string(), text);
+    assert_eq!(buffer.cursor(), Position::new(20, 2));
+
+    // While a file is still coming in, the bar shows how far it has got.
+    let mut loading = wyv::buffer::Buffer::loading(&dir.join("huge.log"), 400);
+    loading.append_loaded(&text[..100]);
+    h.app.buffers.push(loading);
+    h.app.active = Some(1);
+    assert!(h.screen_contains("Loading huge.log 25%").unwrap());
+    h.keys("ix<esc>").unwrap();
+    assert_eq!(h.app.buffer().unwrap().text().len_bytes(), 100);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_failed_scans_are_reported_in_the_status_line() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-scan-error-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut h = Harness::new(&dir, 100, 10).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let options = h.app.tree.options().clone();
+    let result = wyv::widgets::file_tree::FileNode::scan(&dir, &options, &|_| true);
+    h.app.handle_message(Message::TreeScanned {
+        root: h.app.tree.root().to_path_buf(),
+        result,
+    });
+    let status = h.app.status.clone().unwrap();
+    assert!(status.starts_with("can't open "), "{}", status);
+    assert!(h.screen_contains("can't open").unwrap());
+}
+
+#[test]
+fn test_metadata_is_fetched_for_the_rows_in_view() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-metadata-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..100 {
+        fs::write(dir.join(format!("f{:03}.txt", i)), "x").unwrap();
+    }
+    let dir = dir.canonicalize().unwrap();
+    let mut config = wyv::config::Config::default();
+    config.tree.details = vec![wyv::widgets::file_tree::Detail::Size];
+    let app = wyv::app::App::new(&dir, config).unwrap();
+    let mut h = Harness::with_app(app, 100, 10).unwrap();
+
+    h.screen().unwrap();
+    let near = dir.join("f010.txt");
+    let far = dir.join("f090.txt");
+    assert!(h.app.metadata.peek(&near).is_some());
+    assert!(h.app.metadata.peek(&far).is_none());
+
+    // Scrolling to the end fetches what comes into view.
+    for _ in 0..100 {
+        h.app.tree.select_next();
+    }
+    h.screen().unwrap();
+    assert!(h.app.metadata.peek(&far).is_some());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_binary_files_open_in_the_hex_view() {
+    let dir = std::env::temp_dir().join(format!("wyv-hex-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut bytes = b"\x7fELF\0\0".to_vec();
+    bytes.extend([0xaa; 40]);
+    bytes.extend(b"marker");
+    fs::write(dir.join("a.bin"), &bytes).unwrap();
+
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    h.app.open(&dir.join("a.bin"), None).unwrap();
+    assert_eq!(h.app.mode, Mode::Hex);
+    assert!(h.app.buffers.is_empty());
+    assert!(h
+        .screen_contains("00000000  7f 45 4c 46 00 00 aa aa  aa aa")
+        .unwrap());
+    assert!(h.screen_contains(".ELF..").unwrap());
+
+    h.keys("/\"marker\"<enter>").unwrap();
+    assert_eq!(h.app.hex.as_ref().unwrap().cursor(), 46);
+    assert!(h
+        .screen_contains(" a.bin at 0x2e (46 of 52 bytes) ")
+        .unwrap());
+    h.keys("/ff<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("ff not found"));
+
+    // Overwrite the `m` of the marker and write the file.
+    h.keys("i4d<esc>").unwrap();
+    assert!(h.screen_contains(" a.bin [+] at 0x2f").unwrap());
+    h.keys("q").unwrap();
+    assert_eq!(h.app.mode, Mode::Hex);
+    h.keys("wq").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert!(fs::read(dir.join("a.bin")).unwrap().ends_with(b"Marker"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_markdown_preview_follows_the_buffer() {
+    let dir = std::env::temp_dir().join(format!("wyv-preview-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("README.md"), "# Title\n\n- **one**\n").unwrap();
+    fs::write(dir.join("main.py"), "print(1)\n").unwrap();
+
+    let mut h = Harness::new(&dir, 120, 20).unwrap();
+    h.app.open(&dir.join("main.py"), None).unwrap();
+    h.keys("P").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("main.py isn't markdown"));
+
+    h.app.open(&dir.join("README.md"), None).unwrap();
+    h.keys("P").unwrap();
+    assert!(h.screen_contains("│ Preview").unwrap());
+    assert!(h.screen_contains("│• one").unwrap());
+    assert!(h.screen_contains("│Title").unwrap());
+
+    // Edits show up as they're typed.
+    h.keys("i<down><down><end><enter>- two<esc>").unwrap();
+    assert!(h.screen_contains("│• two").unwrap());
+
+    h.keys(":preview<enter>").unwrap();
+    assert!(h.app.preview.is_none());
+    assert!(!h.screen_contains("│ Preview").unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_spelling_is_underlined_and_corrected() {
+    use crossterm::event::{Event, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+    use ratatui::style::Modifier;
+
+    let dir = std::env::temp_dir().join(format!("wyv-spell-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("notes.md"), "A wrod and a frobnicator.\n").unwrap();
+
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    h.app.open(&dir.join("notes.md"), None).unwrap();
+    let underlined = |h: &mut Harness| -> String {
+        let screen = h.render().unwrap();
+        let area = screen.area;
+        (0..area.height)
+            .flat_map(|y| (0..area.width).map(move |x| (x, y)))
+            .map(|pos| &screen[pos])
+            .filter(|cell| cell.modifier.contains(Modifier::UNDERLINED))
+            .map(|cell| cell.symbol().to_string())
+            .collect()
+    };
+    assert_eq!(underlined(&mut h), "");
+    h.keys(":set spell true<enter>").unwrap();
+    assert_eq!(underlined(&mut h), "wrodfrobnicator");
+
+    h.keys("z").unwrap();
+    assert_eq!(h.app.mode, Mode::Spell);
+    assert!(h.screen_contains(" Suggestions for `wrod` ").unwrap());
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(0, 2));
+    // The suggestions pop up below the word; a click takes one.
+    let menu = h.app.layers.areas(Layer::Float).next().unwrap();
+    assert_eq!(menu.y, 2);
+    h.app.handle_event(Event::Mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column: menu.x + 1,
+        row: menu.y + 1,
+        modifiers: KeyModifiers::NONE,
+    }));
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert_eq!(
+        h.app.buffer().unwrap().line(0).to_string(),
+        "A word and a frobnicator."
+    );
+
+    // Added words are kept in the project's list.
+    h.keys("z").unwrap();
+    assert!(h
+        .screen_contains(" Suggestions for `frobnicator` ")
+        .unwrap());
+    h.keys("a").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert_eq!(underlined(&mut h), "");
+    assert_eq!(
+        fs::read_to_string(dir.join(".wyv-words")).unwrap(),
+        "frobnicator\n"
+    );
+    h.keys("z").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("no misspelled words after the cursor")
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_replace_across_files_with_preview() {
+    let dir = std::env::temp_dir().join(format!("wyv-replace-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "foo1 foo2\n").unwrap();
+    fs::write(dir.join("b.txt"), "x\nfoo3\n").unwrap();
+
+    let mut h = Harness::new(&dir, 100, 30).unwrap();
+    h.app.open(&dir.join("a.txt"), None).unwrap();
+    h.keys(r"<ctrl-f>foo(\d)<ctrl-r>bar$1<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Replace);
+    assert!(h
+        .screen_contains(" Replace foo(\\d) with bar$1 (3 of 3 changes) ")
+        .unwrap());
+    assert!(h.screen_contains("[x]     1- foo1 foo2").unwrap());
+    assert!(h.screen_contains("        1+ foo1 bar2").unwrap());
+    assert!(h.screen_contains("        2+ bar3").unwrap());
+
+    // Leave out the second change in a.txt.
+    h.keys("j ").unwrap();
+    assert!(h.screen_contains("[ ]     1- foo1 foo2").unwrap());
+    assert!(h.screen_contains("(2 of 3 changes)").unwrap());
+    h.keys("a").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("Replaced 2 matches in 2 files")
+    );
+
+    // The open file changes in its buffer, the other on disk.
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.text().to_string(), "bar1 foo2\n");
+    assert!(buffer.is_dirty());
+    assert_eq!(
+        fs::read_to_string(dir.join("a.txt")).unwrap(),
+        "foo1 foo2\n"
+    );
+    assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "x\nbar3\n");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Takes write permission away from `path`, a file or a directory, and
+/// says whether that stops this user writing it; it doesn't for root.
+#[cfg(unix)]
+fn make_read_only(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = if path.is_dir() { 0o555 } else { 0o444 };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    match path.is_dir() {
+        true => {
+            let probe = path.join(".probe");
+            let written = fs::write(&probe, "").is_ok();
+            let _ = fs::remove_file(probe);
+            !written
+        }
+        false => fs::OpenOptions::new().write(true).open(path).is_err(),
+    }
+}
+
+#[cfg(unix)]
+fn make_writable(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_saving_a_read_only_file_points_at_sudowrite() {
+    let dir = std::env::temp_dir().join(format!("wyv-ro-save-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.conf");
+    fs::write(&path, "old\n").unwrap();
+    let mut h = Harness::new(&dir, 100, 12).unwrap();
+    h.app.open(&path, None).unwrap();
+    if make_read_only(&path) {
+        h.keys("inew <esc>:w<enter>").unwrap();
+        let status = h.app.status.clone().unwrap_or_default();
+        assert!(status.contains(":sudowrite saves it"), "{}", status);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_save_as_into_a_read_only_directory_fails_first() {
+    let dir = std::env::temp_dir().join(format!("wyv-ro-saveas-{}", std::process::id()));
+    fs::create_dir_all(dir.join("locked")).unwrap();
+    let mut h = Harness::new(&dir, 100, 12).unwrap();
+    if make_read_only(&dir.join("locked")) {
+        h.keys(":new<enter>itext<esc>:saveas locked/a.txt<enter>")
+            .unwrap();
+        let status = h.app.status.clone().unwrap_or_default();
+        assert!(status.starts_with("locked/a.txt: "), "{}", status);
+        assert!(h.app.buffer().unwrap().path().is_none());
+    }
+    make_writable(&dir.join("locked"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_replace_leaves_every_file_when_one_is_read_only() {
+    let dir = std::env::temp_dir().join(format!("wyv-ro-replace-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "foo\n").unwrap();
+    fs::write(dir.join("b.txt"), "foo\n").unwrap();
+    let mut h = Harness::new(&dir, 100, 30).unwrap();
+    if make_read_only(&dir.join("b.txt")) {
+        h.keys("<ctrl-f>foo<ctrl-r>bar<enter>a").unwrap();
+        assert_eq!(
+            h.app.status.as_deref(),
+            Some("b.txt: Permission denied (os error 13); nothing was replaced")
+        );
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "foo\n");
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_location_list_steps_through_entries() {
+    use wyv::lsp::Diagnostic;
+
+    let dir = std::env::temp_dir().join(format!("wyv-locations-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "one todo\ntwo\ntodo three\n").unwrap();
+    fs::write(dir.join("b.txt"), "todo four\n").unwrap();
+
+    let mut h = Harness::new(&dir, 100, 30).unwrap();
+    h.keys("]").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("the location list is empty"));
+
+    h.keys("<ctrl-f>todo<ctrl-q><esc>").unwrap();
+    h.keys("]").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("(1 of 3) a.txt:1: one todo"));
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(0, 4));
+    h.keys("]]").unwrap();
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.path(), Some(dir.join("b.txt").as_path()));
+    h.keys("]").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("already at the last location")
+    );
+    h.keys("[").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("(2 of 3) a.txt:3: todo three")
+    );
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(2, 0));
+
+    // Diagnostics feed the same list.
+    h.app.diagnostics.insert(
+        dir.join("a.txt"),
+        vec![Diagnostic {
+            start: (1, 1),
+            severity: 2,
+            message: "odd word\nmore detail".to_string(),
+        }],
+    );
+    h.keys(":diagnostics<enter>").unwrap();
+    assert!(h.screen_contains(" Diagnostics (1) ").unwrap());
+    assert!(h.screen_contains("File  Line Severity Message").unwrap());
+    assert!(h.screen_contains("a.txt 2:2  warning  odd word").unwrap());
+    h.keys("<esc>]").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("(1 of 1) a.txt:2:2: warning: odd word")
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_linting_on_save_fills_the_gutter() {
+    let dir = std::env::temp_dir().join(format!("wyv-lint-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("run.sh"), "set -e\necho $1\n").unwrap();
+
+    // Stands in for shellcheck, in the default regex format.
+    let linter = wyv::lint::LinterConfig {
+        command: "sh".to_string(),
+        args: ["-c", FAKE_LINTER, "lint", "{path}"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect(),
+        format: wyv::lint::LintFormat::Regex,
+        pattern: wyv::lint::default_pattern(),
+    };
+    let config = wyv::config::Config {
+        linters: HashMap::from([("sh".to_string(), linter)]).into(),
+        editor: wyv::config::EditorOptions {
+            lint_on_save: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let app = wyv::app::App::new(&dir, config).unwrap();
+    let mut h = Harness::with_app(app, 80, 10).unwrap();
+    h.app.open(&dir.join("run.sh"), None).unwrap();
+    h.keys(":w<enter>").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("run.sh: 1 problem, :diagnostics shows it")
+    );
+    assert!(h.screen_contains("W 2 echo $1").unwrap());
+
+    h.keys(":diagnostics<enter><esc>]").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("(1 of 1) run.sh:2:6: warning: quote $1")
+    );
+
+    // Fixing the file clears its lints.
+    fs::write(dir.join("run.sh"), "set -e\necho \"$1\"\n").unwrap();
+    h.app.lint(&dir.join("run.sh")).unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("run.sh: no problems"));
+    assert!(h.app.lints.is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Complains about each unquoted `$1` in the file it's given.
+const FAKE_LINTER: &str = r#"grep -n 'echo \$1' "$1" | while IFS=: read -r line _; do
+    echo "$(basename "$1"):$line:6: warning: quote \$1"
+done"#;
+
+#[test]
+fn test_merge_view_resolves_conflicts() {
+    let path = std::env::temp_dir().join(format!("wyv-merge-{}.txt", std::process::id()));
+    std::fs::write(
+        &path,
+        "top\n<<<<<<< HEAD\nours 1\n=======\ntheirs 1\n>>>>>>> feature\nmiddle\n\
+         <<<<<<< HEAD\nours 2\n=======\ntheirs 2\n>>>>>>> feature\n",
+    )
+    .unwrap();
+    let mut h = Harness::new(Path::new("./src"), 100, 30).unwrap();
+    h.app.open(&path, None).unwrap();
+    assert!(h
+        .app
+        .status
+        .as_deref()
+        .unwrap()
+        .contains("has merge conflicts"));
+
+    h.keys(":merge<enter>").unwrap();
+    assert!(h.screen_contains("Ours (HEAD) 1/2").unwrap());
+    assert!(h.screen_contains("Theirs (feature) 1/2").unwrap());
+    assert!(h.screen_contains("Result, 2 unresolved").unwrap());
+
+    h.keys("t").unwrap();
+    assert!(h.screen_contains("Ours (HEAD) 2/2").unwrap());
+    h.keys("s").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("Saved with 1 conflicts left"));
+    h.keys("os").unwrap();
+    assert!(h.app.merge.is_none());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "top\ntheirs 1\nmiddle\nours 2\n"
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_diff_view_against_head_and_between_files() {
+    let dir = git_repo(
+        "diff",
+        &[("a.txt", "one\ntwo\nthree\n"), ("b.txt", "one\nthree\n")],
+    );
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    h.app.open(&dir.join("a.txt"), None).unwrap();
+    h.app
+        .buffer_mut()
+        .unwrap()
+        .set_text("one\n2\nthree\nfour\n")
+        .unwrap();
+
+    h.keys(":diff<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Diff);
+    assert!(h.screen_contains("a.txt (HEAD)").unwrap());
+    let rows = h.screen().unwrap();
+    assert!(rows
+        .iter()
+        .any(|r| r.contains("2 two") && r.contains("2 2")));
+    assert!(rows.iter().any(|r| r.contains("4 four")));
+
+    h.keys("u").unwrap();
+    let rows = h.screen().unwrap();
+    let at = |text: &str| rows.iter().position(|r| r.contains(text)).unwrap();
+    assert!(at("2 -two") < at("2 +2"));
+    assert!(at("2 +2") < at("3  three"));
+    h.keys("<esc>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+
+    // Open buffers are compared as edited.
+    h.keys(":diff a.txt b.txt<enter>u").unwrap();
+    assert!(h.screen_contains("a.txt → b.txt").unwrap());
+    assert!(h.screen_contains("-2").unwrap());
+    assert!(h.screen_contains("-four").unwrap());
+    h.keys("<esc>:diff a.txt a.txt<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("No differences"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_review_notes_changes_against_a_base() {
+    let dir = git_repo(
+        "review",
+        &[("a.txt", "one\ntwo\nthree\n"), ("c.txt", "gone\n")],
+    );
+    git(&dir, &["branch", "base"]);
+    git(&dir, &["switch", "-q", "-c", "feature"]);
+    fs::write(dir.join("a.txt"), "one\n2\nthree\n").unwrap();
+    fs::write(dir.join("b.txt"), "new\n").unwrap();
+    git(&dir, &["rm", "-q", "c.txt"]);
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "Feature"]);
+
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    h.keys(":review base<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Review);
+    assert!(h.screen_contains(" Changes against base ").unwrap());
+    assert!(h.screen_contains("A b.txt").unwrap());
+
+    h.keys("<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Diff);
+    assert!(h.screen_contains("a.txt (base)").unwrap());
+    h.keys("jcwhy 2?<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("Noted line 2 of a.txt"));
+    assert_eq!(h.app.mode, Mode::Diff);
+    h.keys("<esc>").unwrap();
+    assert_eq!(h.app.mode, Mode::Review);
+    assert!(h.screen_contains("M a.txt  (1 note)").unwrap());
+
+    h.keys("jj<enter>ckeep this<enter><esc>w").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("Wrote 2 notes to review.txt"));
+    let report = fs::read_to_string(dir.join("review.txt")).unwrap();
+    assert!(report.starts_with("Review of feature against base (merge base "));
+    assert!(
+        report.ends_with("\n\na.txt\n  line 2: why 2?\n\nc.txt\n  line 1 (removed): keep this\n")
+    );
+
+    // The review is kept until another one starts.
+    h.keys("<esc>:review<enter>").unwrap();
+    assert!(h.screen_contains("D c.txt  (1 note)").unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_compare_marked_files_outside_a_repo() {
+    let dir = std::env::temp_dir().join(format!("wyv-compare-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.conf"), "port = 80\nhost = a\n").unwrap();
+    fs::write(dir.join("b.conf"), "port = 8080\nhost = a\n").unwrap();
+    fs::write(dir.join("c.conf"), "port = 80\n").unwrap();
+
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    h.keys("=").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("mark two files with m to compare them")
+    );
+    h.keys("jm").unwrap();
+    assert!(h.screen_contains("● a.conf").unwrap());
+    assert!(h
+        .app
+        .status
+        .as_deref()
+        .unwrap()
+        .starts_with("Marked a.conf; mark or select"));
+
+    // One mark compares with the selected file.
+    h.keys("j=").unwrap();
+    assert_eq!(h.app.mode, Mode::Diff);
+    assert!(h.screen_contains("┌ a.conf ─").unwrap());
+    assert!(h.screen_contains("┌ b.conf ─").unwrap());
+    assert!(h.screen_contains("port = 8080").unwrap());
+    h.keys("<esc>").unwrap();
+    assert!(!h.screen_contains("●").unwrap());
+
+    // Two marks compare with each other, wherever the selection is.
+    h.keys("mjmk=").unwrap();
+    assert!(h.screen_contains("┌ b.conf ─").unwrap());
+    assert!(h.screen_contains("┌ c.conf ─").unwrap());
+    h.keys("<esc>:compare a.conf a.conf<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("No differences"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// A language server in sh that answers `initialize` and offers the same
+/// three completions every time.
+#[cfg(all(unix, feature = "lsp"))]
+const FAKE_SERVER: &str = r#"
+reply() { printf 'Content-Length: %s\r\n\r\n%s' "${#1}" "$1"; }
+while :; do
+    len=
+    while IFS= read -r line; do
+        line=$(printf '%s' "$line" | tr -d '\r')
+        [ -z "$line" ] && break
+        case $line in Content-Length:*) len=${line#*: } ;; esac
+    done
+    [ -n "$len" ] || exit 0
+    body=$(dd bs=1 count="$len" 2>/dev/null)
+    id=$(printf '%s' "$body" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    case $body in
+    *'"method":"initialize"'*)
+        reply '{"jsonrpc":"2.0","id":'"$id"',"result":{"capabilities":{"completionProvider":{"triggerCharacters":["."]},"signatureHelpProvider":{"triggerCharacters":["("]},"documentFormattingProvider":true}}}' ;;
+    *'"method":"textDocument/completion"'*)
+        reply '{"jsonrpc":"2.0","id":'"$id"',"result":[{"label":"println","kind":3,"detail":"macro","documentation":"Prints to stdout.","insertText":"println!(${1:fmt})","insertTextFormat":2},{"label":"print","kind":3},{"label":"vec","kind":3}]}' ;;
+    *'"method":"textDocument/hover"'*)
+        reply '{"jsonrpc":"2.0","id":'"$id"',"result":{"contents":{"kind":"markdown","value":"```rust\nfn greet(name: &str, loud: bool)\n```\nSays hello."}}}' ;;
+    *'"method":"textDocument/signatureHelp"'*)
+        reply '{"jsonrpc":"2.0","id":'"$id"',"result":{"signatures":[{"label":"greet(name: &str, loud: bool)","documentation":"Says hello.","parameters":[{"label":"name: &str"},{"label":[18,28]}]}],"activeParameter":1}}' ;;
+    *'"method":"textDocument/formatting"'*)
+        reply '{"jsonrpc":"2.0","id":'"$id"',"result":[{"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":0}},"newText":"    "}]}' ;;
+    *'"method":"textDocument/definition"'*)
+        reply '{"jsonrpc":"2.0","id":'"$id"',"result":{"uri":"file://'"$1"'/lib.rs","range":{"start":{"line":0,"character":7},"end":{"line":0,"character":12}}}}' ;;
+    *'"method":"textDocument/references"'*)
+        reply '{"jsonrpc":"2.0","id":'"$id"',"result":[{"uri":"file://'"$1"'/main.rs","range":{"start":{"line":1,"character":4},"end":{"line":1,"character":9}}},{"uri":"file://'"$1"'/lib.rs","range":{"start":{"line":0,"character":7},"end":{"line":0,"character":12}}}]}' ;;
+    *'"method":"textDocument/rename"'*)
+        edit() { printf '"file://%s/%s":[{"range":{"start":{"line":%s,"character":%s},"end":{"line":%s,"character":%s}},"newText":"hello"}]' "$1" "$2" "$3" "$4" "$3" "$5"; }
+        reply '{"jsonrpc":"2.0","id":'"$id"',"result":{"changes":{'"$(edit "$1" main.rs 1 4 9),$(edit "$1" other.rs 0 11 16)"'}}}' ;;
+    esac
+done
+"#;
+
+/// Starts the fake server for files in `dir`, which it is told about.
+#[cfg(all(unix, feature = "lsp"))]
+fn start_fake_server(h: &mut Harness, runtime: &wyv::runtime::Runtime, dir: &Path) {
+    h.app.spawner = Some(runtime.spawner());
+    h.app.config.lsp = vec![wyv::lsp::LspServerConfig {
+        name: "fake".to_string(),
+        command: "sh".to_string(),
+        args: vec![
+            "-c".to_string(),
+            FAKE_SERVER.to_string(),
+            "fake".to_string(),
+            dir.display().to_string(),
+        ],
+        extensions: vec!["rs".to_string()],
+        language_id: None,
+    }];
+    h.app.start_language_servers();
+}
+
+/// Handles the runtime's messages until `done` holds for the app.
+#[cfg(unix)]
+fn wait_for(
+    h: &mut Harness,
+    runtime: &mut wyv::runtime::Runtime,
+    done: impl Fn(&wyv::app::App) -> bool,
+) {
+    use std::time::{Duration, Instant};
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done(&h.app) {
+        assert!(Instant::now() < deadline, "timed out waiting for messages");
+        for message in runtime.drain() {
+            h.app.handle_message(message);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[cfg(all(unix, feature = "lsp"))]
+#[test]
+fn test_lsp_completion_menu() {
+    use wyv::runtime::Runtime;
+
+    let dir = std::env::temp_dir().join(format!("wyv-lsp-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.rs");
+    fs::write(&path, "fn main() {\n    \n}\n").unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    start_fake_server(&mut h, &runtime, &dir);
+    h.app.open(&path, Some(Position::new(1, 4))).unwrap();
+
+    h.keys("ip").unwrap();
+    wait_for(&mut h, &mut runtime, |app| app.completion.is_some());
+    assert!(h.screen_contains("fn   print").unwrap());
+    assert!(h.screen_contains("fn   println  macro").unwrap());
+    assert!(!h.screen_contains("vec").unwrap());
+
+    // Typing narrows the menu down; the preview follows the selection.
+    h.keys("rintl").unwrap();
+    assert!(!h.screen_contains("fn   print ").unwrap());
+    assert!(h.screen_contains("Prints to stdout.").unwrap());
+
+    h.keys("<tab>").unwrap();
+    assert!(h.app.completion.is_none());
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.line(1).to_string(), "    println!(fmt)");
+    assert_eq!(buffer.cursor(), Position::new(1, 13));
+    h.keys("<esc>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(all(unix, feature = "lsp"))]
+#[test]
+fn test_lsp_definition_references_rename() {
+    use wyv::runtime::Runtime;
+
+    let dir = std::env::temp_dir().join(format!("wyv-lsp-nav-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (main, lib, other) = (
+        dir.join("main.rs"),
+        dir.join("lib.rs"),
+        dir.join("other.rs"),
+    );
+    fs::write(&main, "fn main() {\n    greet();\n}\n").unwrap();
+    fs::write(&lib, "pub fn greet() {}\n").unwrap();
+    fs::write(&other, "use crate::greet;\n").unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    start_fake_server(&mut h, &runtime, &dir);
+    h.app.open(&main, Some(Position::new(1, 4))).unwrap();
+
+    // Going to the definition opens the other file; ctrl-o comes back.
+    h.keys("g").unwrap();
+    wait_for(&mut h, &mut runtime, |app| {
+        app.buffer().and_then(|b| b.path()) == Some(lib.as_path())
+    });
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(0, 7));
+    h.keys("<ctrl-o>").unwrap();
+    assert_eq!(h.app.buffer().unwrap().path(), Some(main.as_path()));
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(1, 4));
+
+    h.keys("r").unwrap();
+    wait_for(&mut h, &mut runtime, |app| app.mode == Mode::Locations);
+    assert!(h.screen_contains("References (2)").unwrap());
+    assert!(h.screen_contains("main.rs:2:5  greet();").unwrap());
+    assert!(h.screen_contains("lib.rs:1:8  pub fn greet() {}").unwrap());
+    h.keys("j<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert_eq!(h.app.buffer().unwrap().path(), Some(lib.as_path()));
+    h.keys("<ctrl-o>").unwrap();
+
+    // Renaming previews every edit before making any.
+    h.keys(":rename hello<enter>").unwrap();
+    wait_for(&mut h, &mut runtime, |app| app.mode == Mode::Rename);
+    assert!(h.screen_contains("2:5  greet → hello").unwrap());
+    assert!(h.screen_contains("other.rs (1 edits)").unwrap());
+    h.keys("a").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("Renamed 2 occurrences in 2 files")
+    );
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.line(1).to_string(), "    hello();");
+    assert!(buffer.is_dirty());
+    assert_eq!(fs::read_to_string(&other).unwrap(), "use crate::hello;\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(all(unix, feature = "lsp"))]
+#[test]
+fn test_rename_touches_nothing_when_a_file_is_read_only() {
+    use wyv::runtime::Runtime;
+
+    let dir = std::env::temp_dir().join(format!("wyv-ro-rename-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (main, other) = (dir.join("main.rs"), dir.join("other.rs"));
+    fs::write(&main, "fn main() {\n    greet();\n}\n").unwrap();
+    fs::write(&other, "use crate::greet;\n").unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    start_fake_server(&mut h, &runtime, &dir);
+    h.app.open(&main, Some(Position::new(1, 4))).unwrap();
+    if make_read_only(&other) {
+        h.keys(":rename hello<enter>").unwrap();
+        wait_for(&mut h, &mut runtime, |app| app.mode == Mode::Rename);
+        h.keys("a").unwrap();
+        assert_eq!(
+            h.app.status.as_deref(),
+            Some("other.rs: Permission denied (os error 13); nothing was renamed")
+        );
+        assert_eq!(h.app.buffer().unwrap().line(1).to_string(), "    greet();");
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(all(unix, feature = "lsp"))]
+#[test]
+fn test_switching_projects_leaves_the_new_servers_alone() {
+    use std::time::{Duration, Instant};
+    use wyv::runtime::Runtime;
+
+    let base = std::env::temp_dir().join(format!("wyv-lsp-switch-{}", std::process::id()));
+    let (old, new) = (base.join("old"), base.join("new"));
+    for dir in [&old, &new] {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {\n    \n}\n").unwrap();
+    }
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(&old, 100, 20).unwrap();
+    start_fake_server(&mut h, &runtime, &old);
+    h.app.open(&old.join("main.rs"), None).unwrap();
+
+    // The old server is stopped as the new project's starts, at the same
+    // index; its reader still says it exited, and that's about the old one.
+    h.app.switch_project(&new).unwrap();
+    h.app
+        .open(&new.join("main.rs"), Some(Position::new(1, 4)))
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut exited = false;
+    while !exited {
+        assert!(Instant::now() < deadline, "the old server never exited");
+        for message in runtime.drain() {
+            exited |= matches!(message, Message::LspExited { .. });
+            h.app.handle_message(message);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert!(!h
+        .app
+        .status
+        .as_deref()
+        .unwrap_or_default()
+        .contains("exited"));
+    h.keys("ip").unwrap();
+    wait_for(&mut h, &mut runtime, |app| app.completion.is_some());
+    fs::remove_dir_all(base).unwrap();
+}
+
+#[cfg(all(unix, feature = "lsp"))]
+#[test]
+fn test_lsp_hover_and_signature_help() {
+    use wyv::runtime::Runtime;
+
+    let dir = std::env::temp_dir().join(format!("wyv-lsp-hover-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.rs");
+    fs::write(&path, "fn main() {\n    greet\n}\n").unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    start_fake_server(&mut h, &runtime, &dir);
+    h.app.open(&path, Some(Position::new(1, 4))).unwrap();
+
+    h.keys("K").unwrap();
+    wait_for(&mut h, &mut runtime, |app| app.hover.is_some());
+    assert!(h
+        .screen_contains("fn greet(name: &str, loud: bool)")
+        .unwrap());
+    assert!(h.screen_contains("Says hello.").unwrap());
+    assert!(!h.screen_contains("```").unwrap());
+    h.keys("<esc>").unwrap();
+    assert!(!h.screen_contains("Says hello.").unwrap());
+
+    // Opening the call's parentheses shows its signature.
+    h.app.buffer_mut().unwrap().goto(Position::new(1, 9));
+    h.keys("i(").unwrap();
+    wait_for(&mut h, &mut runtime, |app| app.signature.is_some());
+    let screen = h.screen().unwrap();
+    let row = screen
+        .iter()
+        .position(|l| l.contains("greet(name: &str, loud: bool)"))
+        .unwrap();
+    let col = screen[row].split("loud").next().unwrap().chars().count() as u16;
+    let accent = h.app.config.theme.accent();
+    let buffer = h.render().unwrap();
+    assert_eq!(buffer[(col, row as u16)].fg, accent.fg.unwrap());
+    assert_ne!(buffer[(col - 2, row as u16)].fg, accent.fg.unwrap());
+
+    h.keys("<esc>").unwrap();
+    assert!(h.app.signature.is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(all(unix, feature = "lsp"))]
+#[test]
+fn test_format_with_formatter_and_language_server() {
+    use std::collections::HashMap;
+    use wyv::{format::FormatterConfig, runtime::Runtime};
+
+    let dir = std::env::temp_dir().join(format!("wyv-format-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (notes, main) = (dir.join("notes.txt"), dir.join("main.rs"));
+    fs::write(&notes, "one  two\nthree   four\n").unwrap();
+    fs::write(&main, "fn main() {\ngreet();\n}\n").unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    start_fake_server(&mut h, &runtime, &dir);
+    let formatter = |command: &str, args: &[&str]| FormatterConfig {
+        command: command.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+    };
+    h.app.config.formatters = HashMap::from([
+        ("txt".to_string(), formatter("tr", &["-s", " "])),
+        ("rs".to_string(), formatter("", &[])),
+    ])
+    .into();
+    h.app.config.editor.format_on_save = true;
+
+    // An external formatter runs before the file is written.
+    h.app.open(&notes, Some(Position::new(1, 8))).unwrap();
+    h.keys(":w<enter>").unwrap();
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "one two\nthree four\n");
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(1, 8));
+
+    // Without one, the language server formats; on save, the file is
+    // written once its edits are in.
+    h.app.open(&main, Some(Position::new(1, 3))).unwrap();
+    h.keys(":format<enter>").unwrap();
+    wait_for(&mut h, &mut runtime, |app| app.buffer().unwrap().is_dirty());
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.line(1).to_string(), "    greet();");
+    assert_eq!(buffer.cursor(), Position::new(1, 7));
+    h.keys(":w<enter>").unwrap();
+    wait_for(&mut h, &mut runtime, |app| {
+        !app.buffer().unwrap().is_dirty()
+    });
+    assert_eq!(
+        fs::read_to_string(&main).unwrap(),
+        "fn main() {\n        greet();\n}\n"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// The terminal pane's screen as lines of text.
+#[cfg(all(unix, feature = "terminal"))]
+fn terminal_screen(app: &wyv::app::App) -> String {
+    let pty = app.pty.as_ref().unwrap();
+    let (rows, cols) = pty.size();
+    (0..rows)
+        .map(|row| {
+            let cells = (0..cols).filter_map(|col| pty.cell(row, col));
+            cells.map(|(text, _)| text).collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_long_running_jobs_show_in_the_status_bar() {
+    use std::time::Duration;
+    use wyv::runtime::Runtime;
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(Path::new("./src"), 80, 10).unwrap();
+    let spawner = runtime.spawner();
+    let id = h.app.jobs.spawn(&spawner, "index", |ctx| {
+        ctx.progress(5, Some(10));
+        while !ctx.is_cancelled() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        Ok(())
+    });
+    wait_for(&mut h, &mut runtime, |app| {
+        app.jobs.get(id).is_some_and(|job| job.total.is_some())
+    });
+    // Not at first: most jobs are over before they'd be worth showing.
+    assert!(!h.screen_contains("index").unwrap());
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(h.screen_contains(" 50% index").unwrap());
+
+    h.app.jobs.cancel(id);
+    wait_for(&mut h, &mut runtime, |app| app.jobs.is_empty());
+    assert!(!h.screen_contains("index").unwrap());
+}
+
+#[cfg(all(unix, feature = "terminal"))]
+#[test]
+fn test_terminal_pane_with_copy_mode() {
+    use wyv::runtime::Runtime;
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(Path::new("./src"), 100, 30).unwrap();
+    h.app.spawner = Some(runtime.spawner());
+    h.app.config.terminal.shell = Some("sh".to_string());
+
+    h.keys("<ctrl-t>").unwrap();
+    assert_eq!(h.app.mode, Mode::Terminal);
+    assert_eq!(h.app.panel, Some(Panel::Terminal));
+    h.render().unwrap();
+    assert_eq!(h.app.pty.as_ref().unwrap().size(), (8, 85));
+
+    // The output differs from the command echoed as it is typed.
+    h.keys("printf 'one%s\\n' - - -<enter>").unwrap();
+    wait_for(&mut h, &mut runtime, |app| {
+        terminal_screen(app).matches("one-").count() == 3
+    });
+    assert!(h.screen_contains("one-").unwrap());
+    assert!(h.screen_contains(" Terminal ").unwrap());
+
+    // Copy mode starts a page up, here on the command at the top, and
+    // yanks the selected lines.
+    h.keys("<shift-pageup>").unwrap();
+    assert_eq!(h.app.mode, Mode::TerminalCopy);
+    assert_eq!(h.app.copy_mode.unwrap().cursor, 0);
+    h.keys("<esc>").unwrap();
+    assert_eq!(h.app.mode, Mode::Terminal);
+    h.keys("<shift-pageup>jvjjy").unwrap();
+    assert_eq!(h.app.register.as_deref(), Some("one-\none-\none-"));
+    assert_eq!(h.app.status.as_deref(), Some("Copied 3 lines"));
+
+    // Out to the editor with the pane still open, back in, then hidden.
+    h.keys("<ctrl-\\>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert!(h.app.terminal_visible());
+    h.keys("<ctrl-t>").unwrap();
+    assert_eq!(h.app.mode, Mode::Terminal);
+    h.keys("<ctrl-t>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert!(!h.screen_contains(" Terminal ").unwrap());
+
+    // The same shell shows up beside the editor, until it exits.
+    h.app.config.terminal.position = wyv::pty::TerminalPosition::Right;
+    h.keys("<ctrl-t>").unwrap();
+    assert!(h.app.terminal_split);
+    h.render().unwrap();
+    assert_eq!(h.app.pty.as_ref().unwrap().size().0, 27);
+    h.keys("exit<enter>").unwrap();
+    wait_for(&mut h, &mut runtime, |app| app.pty.is_none());
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert!(!h.app.terminal_visible());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_task_output_and_problems() {
+    use wyv::{runtime::Runtime, tasks::TaskConfig};
+
+    let dir = std::env::temp_dir().join(format!("wyv-tasks-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.rs"), "fn main() {\n    let x = 1;\n}\n").unwrap();
+    fs::write(dir.join("Makefile"), "all:\n\techo hi\n").unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(&dir, 100, 24).unwrap();
+    h.app.spawner = Some(runtime.spawner());
+    h.app.config.tasks = vec![TaskConfig {
+        name: "check".to_string(),
+        command: "echo checking; echo 'a.rs:2:9: warning: unused' >&2; exit 1".to_string(),
+        cwd: None,
+    }];
+    h.keys(":task<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("Tasks: check make:all"));
+
+    h.keys(":task check<enter>").unwrap();
+    assert_eq!(h.app.panel, Some(Panel::Tasks));
+    wait_for(&mut h, &mut runtime, |app| {
+        app.task_run.as_ref().is_some_and(|r| !r.running())
+    });
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("check failed (exit 1); 1 problem, :problems shows it")
+    );
+    assert!(h.screen_contains(" check (exit 1) ").unwrap());
+    assert!(h.screen_contains("checking").unwrap());
+
+    h.keys(":problems<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Locations);
+    assert!(h.screen_contains("a.rs:2:9: warning: unused").unwrap());
+    h.keys("<enter>").unwrap();
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.path(), Some(dir.join("a.rs").as_path()));
+    assert_eq!(buffer.cursor(), Position::new(1, 8));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_shell_commands_and_filters() {
+    use wyv::runtime::Runtime;
+
+    let path = std::env::temp_dir().join(format!("wyv-filter-{}.txt", std::process::id()));
+    fs::write(&path, "cherry\nbanana\napple\n").unwrap();
+    let mut runtime = Runtime::new().unwrap();
+    let mut h = Harness::new(Path::new("./src"), 100, 24).unwrap();
+    h.app.spawner = Some(runtime.spawner());
+    h.app.open(&path, None).unwrap();
+
+    h.keys(":%!sort<enter>").unwrap();
+    let text = |h: &Harness| h.app.buffer().unwrap().text().to_string();
+    assert_eq!(text(&h), "apple\nbanana\ncherry\n");
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("Filtered 3 lines through sort")
+    );
+    h.keys(":2,3!tr a-z A-Z<enter>").unwrap();
+    assert_eq!(text(&h), "apple\nBANANA\nCHERRY\n");
+    h.keys(":.!exit 1<enter>").unwrap();
+    assert_eq!(
+        h.app.status.as_deref(),
+        Some("exit 1 failed (exit status: 1)")
+    );
+    assert_eq!(text(&h), "apple\nBANANA\nCHERRY\n");
+
+    h.keys(":!echo from the shell<enter>").unwrap();
+    assert_eq!(h.app.panel, Some(Panel::Tasks));
+    wait_for(&mut h, &mut runtime, |app| {
+        app.task_run.as_ref().is_some_and(|r| !r.running())
+    });
+    assert!(h
+        .screen_contains(" !echo from the shell (exit 0) ")
+        .unwrap());
+    assert!(h.screen_contains("from the shell").unwrap());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_tree_pane_is_redrawn_only_when_it_changes() {
+    let mut h = Harness::new(Path::new("./src"), 80, 20).unwrap();
+    h.render().unwrap();
+    h.render().unwrap();
+    assert_eq!(h.app.tree_pane.hits(), 1);
+
+    h.keys("j").unwrap();
+    let screen = h.screen().unwrap();
+    assert_eq!(h.app.tree_pane.hits(), 1);
+    let selected = h.app.tree.selected_node().unwrap().name().to_string();
+    assert!(screen[1].contains(&selected));
+    h.render().unwrap();
+    assert_eq!(h.app.tree_pane.hits(), 2);
+}
+
+#[test]
+fn test_degenerate_screen_sizes_draw_without_panicking() {
+    let sizes = [(0, 0), (1, 1), (2, 2), (1, 40), (1000, 3), (3, 1), (80, 2)];
+    // Each step leaves something else on screen: panels, sidebars and
+    // overlays are all laid out from what's left.
+    let steps = [
+        ":log<enter>",
+        ":outline<enter><esc>",
+        ":perf<enter>",
+        ":search<enter>fn",
+        "<esc>:symbols<enter>",
+        "<esc>:hex<enter>",
+        "q:",
+    ];
+    let mut h = Harness::new(Path::new("./src"), 80, 20).unwrap();
+    h.app.open(Path::new("./src/lib.rs"), None).unwrap();
+    for step in steps {
+        h.keys(step).unwrap();
+        for (width, height) in sizes {
+            h.resize(width, height);
+            h.screen().unwrap();
+        }
+        h.resize(80, 20);
+        h.screen().unwrap();
+    }
+    let rows = h.screen().unwrap();
+    assert_eq!(rows.len(), 20);
+    assert!(rows[19].starts_with(':'), "{:?}", rows[19]);
+}
+
+#[test]
+fn test_insert_mode_moves_and_deletes_whole_glyphs() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-glyphs-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("emoji.txt"), "ab\n").unwrap();
+    let mut h = Harness::new(&dir, 40, 10).unwrap();
+    h.app.open(&dir.join("emoji.txt"), None).unwrap();
+
+    // A family emoji is five chars, but one glyph two columns wide.
+    h.keys("i<right>👨\u{200d}👩\u{200d}👧<left>").unwrap();
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(0, 1));
+    let (x, _) = h.cursor().unwrap();
+    h.keys("<right>").unwrap();
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(0, 6));
+    assert_eq!(h.cursor().unwrap().0, x + 2);
+
+    h.keys("<backspace>").unwrap();
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.text().to_string(), "ab\n");
+    assert_eq!(buffer.cursor(), Position::new(0, 1));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_buffers_whose_directory_goes_are_orphaned_and_saved_elsewhere() {
+    let root = std::env::temp_dir().join(format!("wyv-ui-orphan-{}", std::process::id()));
+    let dir = root.join("gone");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("notes.txt"), "notes\n").unwrap();
+    let mut h = Harness::new(&root, 100, 10).unwrap();
+    h.app.open(&dir.join("notes.txt"), None).unwrap();
+    h.keys("ix<esc>").unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+    h.app.check_files(Instant::now());
+    assert!(h.app.buffer().unwrap().is_orphaned());
+    assert!(h
+        .screen_contains("notes.txt + was deleted or moved")
+        .unwrap());
+
+    h.keys(":w<enter>").unwrap();
+    assert!(h.screen_contains(":saveas <path> to save it").unwrap());
+    assert!(!dir.exists());
+
+    h.keys(":saveas kept.txt<enter>").unwrap();
+    let buffer = h.app.buffer().unwrap();
+    assert!(!buffer.is_orphaned() && !buffer.is_dirty());
+    assert_eq!(
+        fs::read_to_string(root.join("kept.txt")).unwrap(),
+        "xnotes\n"
+    );
+    assert!(h.screen_contains("kept.txt").unwrap());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_one_file_spelled_two_ways_opens_one_buffer() {
+    let mut h = Harness::new(Path::new("./src"), 80, 10).unwrap();
+    h.app.open(Path::new("./src/../src/lib.rs"), None).unwrap();
+    h.app.open(Path::new("src/lib.rs"), None).unwrap();
+    h.keys(":open ./widgets/../lib.rs<enter>").unwrap();
+    assert_eq!(h.app.buffers.len(), 1);
+    let path = h.app.buffer().unwrap().path().unwrap();
+    assert!(path.is_absolute() && path.ends_with("src/lib.rs"));
+    assert_eq!(h.app.buffer_index(Path::new("src/lib.rs")), Some(0));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sudowrite_saves_through_the_helper_outside_the_ui() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-sudowrite-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("hosts"), "127.0.0.1\n").unwrap();
+    let mut config = wyv::config::Config::default();
+    // `env tee -- FILE` stands in for `sudo tee -- FILE`.
+    config.editor.elevate = "env".to_string();
+    let app = wyv::app::App::new(&dir, config).unwrap();
+    let mut h = Harness::with_app(app, 100, 10).unwrap();
+    h.app.open(&dir.join("hosts"), None).unwrap();
+    h.keys("i# <esc>:sudowrite<enter>").unwrap();
+
+    // The main loop saves it once the terminal is handed back.
+    assert_eq!(h.app.elevated_save, Some(0));
+    assert!(h.app.buffer().unwrap().is_dirty());
+    let idx = h.app.elevated_save.take().unwrap();
+    h.app.save_elevated(idx).unwrap();
+    assert!(!h.app.buffer().unwrap().is_dirty());
+    assert_eq!(
+        fs::read_to_string(dir.join("hosts")).unwrap(),
+        "# 127.0.0.1\n"
+    );
+    assert!(h.screen_contains("Saved hosts through env").unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_marks_follow_edits_and_global_ones_cross_files() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-marks-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (main, lib) = (dir.join("main.rs"), dir.join("lib.rs"));
+    fs::write(&main, "fn main() {\n    run();\n}\n").unwrap();
+    fs::write(&lib, "pub fn run() {}\n").unwrap();
+    let mut h = Harness::new(&dir, 100, 12).unwrap();
+
+    h.app.open(&main, Some(Position::new(1, 4))).unwrap();
+    h.keys("Ma").unwrap();
+    assert!(h.screen_contains("Set mark a").unwrap());
+    h.app.buffer_mut().unwrap().goto(Position::new(0, 0));
+    h.keys("i// entry\n<esc>'a").unwrap();
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(2, 4));
+
+    h.app.open(&lib, Some(Position::new(0, 7))).unwrap();
+    h.keys("MR'a").unwrap();
+    assert!(h.screen_contains("mark a isn't set").unwrap());
+    h.app.open(&main, None).unwrap();
+    h.keys("'R").unwrap();
+    assert_eq!(h.app.buffer().unwrap().path(), Some(lib.as_path()));
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(0, 7));
+    h.keys("'1").unwrap();
+    assert!(h.screen_contains("marks are named a to z").unwrap());
+
+    h.app.open(&main, None).unwrap();
+    h.keys(":marks<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Locations);
+    assert!(h.screen_contains("a  main.rs:3:5  run();").unwrap());
+    assert!(h.screen_contains("R  lib.rs:1:8  pub fn run() {}").unwrap());
+    h.keys("<esc>:delmarks a R<enter>").unwrap();
+    assert!(h.screen_contains("Deleted 2 marks").unwrap());
+
+    // Marks come back with the session, in files opened later too.
+    h.keys(":mark b<enter>:w<enter>").unwrap();
+    let session = h.app.session();
+    let mut h = Harness::new(&dir, 100, 12).unwrap();
+    h.app.restore_session(session);
+    h.app.open(&main, None).unwrap();
+    h.keys("'b").unwrap();
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(2, 4));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_jumps_land_mid_view_and_moves_follow() {
+    let mut h = Harness::new(Path::new("./src"), 80, 20).unwrap();
+    h.app.open(Path::new("./src/lib.rs"), None).unwrap();
+    h.app
+        .open(Path::new("./src/app.rs"), Some(Position::new(2000, 0)))
+        .unwrap();
+    h.render().unwrap();
+    let view = h.app.buffer().unwrap().view;
+    assert_eq!(view.offset(), 2000 - view.height() / 2);
+
+    // Moving off the bottom scrolls only as far as it takes.
+    let below = view.rows().end - 2000;
+    h.keys(&format!("i{}<esc>", "<down>".repeat(below)))
+        .unwrap();
+    h.render().unwrap();
+    let view = h.app.buffer().unwrap().view;
+    assert_eq!(view.rows().end, 2000 + below + 1);
+}
+
+#[test]
+fn test_layers_stack_toasts_over_dialogs_over_panes() {
+    use crossterm::event::{Event, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+    use ratatui::layout::Position;
+
+    let mut h = Harness::new(Path::new("./src"), 80, 16).unwrap();
+    h.app.open(Path::new("./src/lib.rs"), None).unwrap();
+    h.app.open(Path::new("./src/ui.rs"), None).unwrap();
+    h.keys(":perf<enter>R").unwrap();
+    assert_eq!(h.app.mode, Mode::Recent);
+    assert!(h.screen_contains(" Perf ").unwrap());
+    assert!(h.screen_contains("Recent files").unwrap());
+    let dialog = h.app.layers.areas(Layer::Dialog).next().unwrap();
+
+    // A dialog keeps the mouse, even off it: nothing underneath sees it.
+    assert_eq!(
+        h.app.layers.route(Position::new(0, 0)),
+        Some((Layer::Dialog, dialog))
+    );
+    h.app.handle_event(Event::Mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column: 0,
+        row: 0,
+        modifiers: KeyModifiers::NONE,
+    }));
+    assert_eq!(h.app.mode, Mode::Recent);
+    h.keys("<esc>").unwrap();
+    h.render().unwrap();
+    assert!(!h.app.layers.is_up(Layer::Dialog));
+}
+
+#[test]
+fn test_recent_files_reopen_where_they_were_left() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-recent-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+    fs::write(&a, "one\ntwo\nthree\n").unwrap();
+    fs::write(&b, "four\n").unwrap();
+    let mut h = Harness::new(&dir, 80, 12).unwrap();
+    h.app.open(&a, Some(Position::new(2, 1))).unwrap();
+    h.app.open(&b, None).unwrap();
+    h.keys("R").unwrap();
+    assert_eq!(h.app.mode, Mode::Recent);
+    assert!(h.screen_contains("a.txt:3").unwrap());
+    assert_eq!(h.app.recent_picker.as_ref().unwrap().files().len(), 1);
+    h.keys("<esc>").unwrap();
+
+    // The next run lists both, the last one opened first.
+    let session = h.app.session();
+    let mut h = Harness::new(&dir, 80, 12).unwrap();
+    h.app.restore_session(session);
+    h.keys(":recent<enter>").unwrap();
+    assert!(h.screen_contains("Recent files").unwrap());
+    assert_eq!(h.app.recent_picker.as_ref().unwrap().files()[0].path, b);
+    // `/` narrows the list down; enter goes back to moving through it.
+    h.keys("/a.t").unwrap();
+    assert!(h.screen_contains("Recent files /a.t").unwrap());
+    let picker = h.app.recent_picker.as_ref().unwrap();
+    assert_eq!(picker.list().visible_len(), 1);
+    h.keys("<enter><enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert_eq!(h.app.buffer().unwrap().path(), Some(a.as_path()));
+    assert_eq!(h.app.buffer().unwrap().cursor(), Position::new(2, 1));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_switching_projects_swaps_the_tree_and_session() {
+    use wyv::{projects::ProjectStore, session::SessionStore};
+
+    let dir = std::env::temp_dir().join(format!("wyv-ui-projects-{}", std::process::id()));
+    let (web, api, state) = (dir.join("web"), dir.join("api"), dir.join("state"));
+    fs::create_dir_all(&web).unwrap();
+    fs::create_dir_all(&api).unwrap();
+    fs::write(web.join("index.html"), "<p>hi</p>\n").unwrap();
+    fs::write(api.join("server.py"), "print('up')\n").unwrap();
+    let mut h = Harness::new(&web, 100, 14).unwrap();
+    h.app.sessions = Some(SessionStore::new(state.join("sessions.json")));
+    h.app.projects = Some(ProjectStore::new(state.join("projects.json")));
+    h.app.load_session();
+    h.app.open(&web.join("index.html"), None).unwrap();
+    h.keys(":log<enter>").unwrap();
+
+    h.keys(":project ../api<enter>").unwrap();
+    assert!(h.screen_contains("Switched to").unwrap());
+    assert!(h.screen_contains("server.py").unwrap());
+    assert!(h.app.buffers.is_empty());
+    assert_eq!(h.app.panel, None);
+
+    // Unsaved changes would be lost, so they hold the switch up.
+    h.app.open(&api.join("server.py"), None).unwrap();
+    h.keys("i#<esc>:projects<enter>").unwrap();
+    assert!(h.screen_contains("web").unwrap());
+    h.keys("<enter>").unwrap();
+    assert!(h.screen_contains("a buffer has unsaved changes").unwrap());
+    h.keys(":w<enter>:projects<enter><enter>").unwrap();
+    assert_eq!(h.app.tree.root(), web.canonicalize().unwrap());
+    let buffer = h.app.buffer().unwrap();
+    assert!(buffer.path().unwrap().ends_with("web/index.html"));
+    assert_eq!(h.app.panel, Some(Panel::Log));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_scratch_buffers_take_notes_and_join_the_tree_once_saved() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-scratch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    let mut h = Harness::new(&dir, 80, 12).unwrap();
+
+    h.keys(":new<enter>").unwrap();
+    assert!(h.screen_contains("scratch 1: :saveas <path>").unwrap());
+    h.keys("ibuy milk<esc>:w<enter>").unwrap();
+    assert!(h.screen_contains(":saveas <path> to save it").unwrap());
+    assert!(!h.screen_contains("todo.txt").unwrap());
+    h.keys(":saveas todo.txt<enter>").unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("todo.txt")).unwrap(),
+        "buy milk"
+    );
+    assert!(h.screen_contains("todo.txt").unwrap());
+    assert!(h.app.tree.find_path(&dir.join("todo.txt")).is_some());
+    // The saved one's name isn't given out again.
+    h.keys(":new<enter>").unwrap();
+    assert_eq!(h.app.buffer().unwrap().title(), "scratch 2");
+
+    #[cfg(unix)]
+    {
+        h.keys(":new !echo built<enter>").unwrap();
+        let buffer = h.app.buffer().unwrap();
+        assert_eq!(buffer.title(), "scratch 3");
+        assert_eq!(buffer.text().to_string(), "built\n");
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_save_as_completes_directories_and_makes_new_ones() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-saveas-{}", std::process::id()));
+    fs::create_dir_all(dir.join("notes")).unwrap();
+    fs::write(dir.join("notes/old.txt"), "").unwrap();
+    let mut h = Harness::new(&dir, 80, 12).unwrap();
+    h.keys(":new<enter>idone<esc>W").unwrap();
+    assert_eq!(h.app.command_line.text(), "saveas ");
+    h.keys("n<tab>").unwrap();
+    assert_eq!(h.app.command_line.text(), "saveas notes/");
+    h.keys("2026/today.txt<enter>").unwrap();
+    assert!(h
+        .screen_contains("notes/2026 doesn't exist; :saveas! creates it")
+        .unwrap());
+
+    h.keys(":saveas! notes/2026/today.txt<enter>").unwrap();
+    let saved = dir.join("notes/2026/today.txt");
+    assert_eq!(fs::read_to_string(&saved).unwrap(), "done");
+    assert_eq!(h.app.buffer().unwrap().title(), "today.txt");
+    // Saving it elsewhere starts the prompt in its directory.
+    h.keys("W").unwrap();
+    assert_eq!(h.app.command_line.text(), "saveas notes/2026/");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_save_as_keeps_off_other_files_and_buffers() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-saveas-over-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("taken.txt"), "keep").unwrap();
+    fs::write(dir.join("open.txt"), "open").unwrap();
+    let mut h = Harness::new(&dir, 80, 12).unwrap();
+    h.app.open(&dir.join("open.txt"), None).unwrap();
+    h.keys(":new<enter>imine<esc>:saveas taken.txt<enter>")
+        .unwrap();
+    assert!(h
+        .screen_contains("taken.txt exists; :saveas! saves over it")
+        .unwrap());
+    assert_eq!(fs::read_to_string(dir.join("taken.txt")).unwrap(), "keep");
+
+    // Not even with `!` does it take a file another buffer has.
+    h.keys(":saveas! open.txt<enter>").unwrap();
+    assert!(h
+        .screen_contains("open.txt is open in another buffer")
+        .unwrap());
+    assert_eq!(fs::read_to_string(dir.join("open.txt")).unwrap(), "open");
+
+    h.keys(":saveas my notes.txt<enter>").unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("my notes.txt")).unwrap(),
+        "mine"
+    );
+    h.keys(":saveas! taken.txt<enter>").unwrap();
+    assert_eq!(fs::read_to_string(dir.join("taken.txt")).unwrap(), "mine");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_command_line_edits_where_the_cursor_is() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-input-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut h = Harness::new(&dir, 60, 10).unwrap();
+    h.keys(":set editor.wap<left><left>r").unwrap();
+    assert_eq!(h.app.command_line.text(), "set editor.wrap");
+    assert!(h.screen_contains(":set editor.wrap").unwrap());
+    // What ctrl-w cuts, ctrl-y puts back.
+    h.keys("<ctrl-e><ctrl-w><ctrl-a><ctrl-y>.").unwrap();
+    assert_eq!(h.app.command_line.text(), "wrap.set editor.");
+    h.keys("<esc>").unwrap();
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_command_line_recalls_and_searches_its_history() {
+    use wyv::history::HistoryStore;
+
+    let dir = std::env::temp_dir().join(format!("wyv-ui-history-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let store = HistoryStore::new(dir.join("state/history.json"));
+    let mut h = Harness::new(&dir, 100, 12).unwrap();
+    h.app.history_store = Some(store.clone());
+    h.keys(":set editor.wrap<enter>:perf<enter>:set editor.tab_width 8<enter>")
+        .unwrap();
+
+    // Up and Down step through the lines that start with what was typed.
+    h.keys(":se<up>").unwrap();
+    assert_eq!(h.app.command_line.text(), "set editor.tab_width 8");
+    h.keys("<up>").unwrap();
+    assert_eq!(h.app.command_line.text(), "set editor.wrap");
+    h.keys("<down><down>").unwrap();
+    assert_eq!(h.app.command_line.text(), "se");
+    h.keys("<esc>").unwrap();
+
+    // The history outlives the run.
+    let mut h = Harness::new(&dir, 100, 12).unwrap();
+    h.app.command_history = store.load();
+    h.keys(":<ctrl-r>wr").unwrap();
+    assert!(h
+        .screen_contains("(reverse-i-search)`wr': set editor.wrap")
+        .unwrap());
+    h.keys("<ctrl-r>").unwrap();
+    assert!(h.screen_contains("set editor.wrap").unwrap());
+    h.keys("x").unwrap();
+    assert!(h.screen_contains("(failed reverse-i-search)`wrx'").unwrap());
+    h.keys("<backspace><enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    assert!(h.screen_contains("editor.wrap = true").unwrap());
+
+    // Tab completes command names as well as their arguments.
+    h.keys(":proj<tab>").unwrap();
+    assert_eq!(h.app.command_line.text(), "project");
+    assert!(h.screen_contains("project projects").unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_stdin_opens_read_only_unless_printed_back_out() {
+    let dir = std::env::temp_dir().join(format!("wyv-ui-stdin-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut h = Harness::new(&dir, 80, 12).unwrap();
+    h.app.open_stdin("+added\n-removed\n");
+    assert!(h.screen_contains("stdin").unwrap());
+    assert!(h.app.buffer().unwrap().readonly());
+
+    // With --stdout, the buffer is what gets printed, so it can be edited
+    // and left without saving.
+    let mut h = Harness::new(&dir, 80, 12).unwrap();
+    h.app.to_stdout = true;
+    h.app.open_stdin("b\na\n");
+    h.keys("iz<esc>:q<enter>").unwrap();
+    assert!(h.app.exit);
+    assert_eq!(h.app.buffer().unwrap().text().to_string(), "zb\na\n");
+
+    // Other buffers still ask.
+    let mut h = Harness::new(&dir, 80, 12).unwrap();
+    h.app.to_stdout = true;
+    h.keys(":new<enter>inote<esc>").unwrap();
+    h.app.open_stdin("");
+    h.keys(":q<enter>").unwrap();
+    assert_eq!(h.app.mode, Mode::ConfirmQuit);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_tutor_notices_each_lesson_done() {
+    use wyv::tutor::{TutorStore, PROGRESS_FILE};
+
+    let dir = std::env::temp_dir().join(format!("wyv-ui-tutor-{}", std::process::id()));
+    let state = dir.join("state");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("other.txt"), "hi\n").unwrap();
+    let mut h = Harness::new(&dir, 100, 20).unwrap();
+    h.app.start_tutor(&state).unwrap();
+    assert!(h.screen_contains("Welcome to wyv").unwrap());
+
+    h.keys("j").unwrap();
+    assert!(h
+        .screen_contains("Lesson 1 done; next, lesson 2: insert mode")
+        .unwrap());
+    h.keys("i").unwrap();
+    assert!(h.screen_contains("next, lesson 3: editing").unwrap());
+    let line = h
+        .app
+        .buffer()
+        .unwrap()
+        .text()
+        .lines()
+        .position(|l| l.to_string().starts_with("--->"))
+        .unwrap();
+    h.app
+        .buffer_mut()
+        .unwrap()
+        .goto(Position::new(line, "---> The cow jumped ov".len()));
+    h.keys("e").unwrap();
+    assert!(h.screen_contains("next, lesson 4: saving").unwrap());
+    h.keys("<esc>:w<enter>").unwrap();
+    assert!(h.screen_contains("next, lesson 5: opening files").unwrap());
+
+    h.keys(":open other.txt<enter><ctrl-o>").unwrap();
+    assert!(h.screen_contains("That's the tutor done").unwrap());
+    assert!(h.app.tutor.is_none());
+    // Once finished, it isn't offered again.
+    assert!(!TutorStore::new(state.join(PROGRESS_FILE))
+        .suggest()
+        .unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_keymap_profiles_import_over_your_bindings_and_export() {
+    use std::collections::HashMap;
+    use wyv::config::{Action, Keymap};
+
+    let dir = std::env::temp_dir().join(format!("wyv-ui-keymaps-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut h = Harness::new(&dir, 100, 12).unwrap();
+    h.app.config.keymap = Keymap::from(HashMap::from([("ctrl-s".to_string(), Action::Outline)]));
+    h.keys(":keymap import emacs<enter>").unwrap();
+    assert!(h
+        .screen_contains("1 of your bindings differ from it")
+        .unwrap());
+    assert_eq!(h.app.buffer().unwrap().title(), "keymap conflicts");
+    let report = h.app.buffer().unwrap().text().to_string();
+    assert!(report.contains("ctrl-s") && report.contains("yours: outline"));
+    assert!(report.contains("emacs: search"));
+
+    let key = |name| wyv::config::parse_key(name).unwrap();
+    assert_eq!(
+        h.app.config.keymap.action(&key("alt-x")),
+        Some(Action::CommandMode)
+    );
+    assert_eq!(
+        h.app.config.keymap.action(&key("ctrl-s")),
+        Some(Action::Outline)
+    );
+
+    // What's exported imports again as it was.
+    h.keys(":keymap export mine.toml<enter>").unwrap();
+    let exported = fs::read_to_string(dir.join("mine.toml")).unwrap();
+    assert!(exported.contains("alt-x = \"command_mode\""));
+    h.app.config.keymap = Keymap::default();
+    h.keys(":keymap import mine.toml<enter>").unwrap();
+    assert!(h.screen_contains("Took on the mine.toml keymap").unwrap());
+    assert_eq!(
+        h.app.config.keymap.action(&key("ctrl-s")),
+        Some(Action::Outline)
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_session_snapshots_are_offered_after_a_run_cut_short() {
+    use std::time::Instant;
+    use wyv::session::SessionStore;
+
+    let dir = std::env::temp_dir().join(format!("wyv-ui-autosave-{}", std::process::id()));
+    let (work, state) = (dir.join("work"), dir.join("state"));
+    fs::create_dir_all(work.join("docs")).unwrap();
+    fs::write(work.join("docs/a.txt"), "one\ntwo\nthree\n").unwrap();
+    let store = SessionStore::new(state.join("sessions.json"));
+    let mut h = Harness::new(&work, 80, 14).unwrap();
+    h.app.sessions = Some(store.clone());
+    h.keys("jl").unwrap();
+    h.app
+        .open(&work.join("docs/a.txt"), Some(Position::new(2, 0)))
+        .unwrap();
+    h.keys(":outline<enter>").unwrap();
+    h.app.autosave_session(Instant::now());
+    // The run ends without saving its session, as a crash would.
+    let expanded = h.app.tree.row_count();
+    drop(h);
+
+    let mut h = Harness::new(&work, 80, 14).unwrap();
+    h.app.sessions = Some(store.clone());
+    h.app.offer_restore();
+    assert_eq!(h.app.mode, Mode::ConfirmRestore);
+    assert!(h.screen_contains("Restore previous session?").unwrap());
+    assert!(h.screen_contains("docs/a.txt:3  (active)").unwrap());
+    assert!(h.screen_contains("with the outline shown").unwrap());
+    h.keys("r").unwrap();
+    assert_eq!(h.app.mode, Mode::Normal);
+    let buffer = h.app.buffer().unwrap();
+    assert_eq!(buffer.path(), Some(work.join("docs/a.txt").as_path()));
+    assert_eq!(buffer.cursor(), Position::new(2, 0));
+    assert_eq!(h.app.tree.row_count(), expanded);
+
+    // A clean exit leaves nothing to restore.
+    h.app.save_session().unwrap();
+    let mut h = Harness::new(&work, 80, 14).unwrap();
+    h.app.sessions = Some(store);
+    h.app.offer_restore();
+    assert_eq!(h.app.mode, Mode::Normal);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_screen_reader_mode_announces_changes_in_plain_text() {
+    let mut h = Harness::new(Path::new("./src"), 100, 12).unwrap();
+    h.keys(":set screenreader<enter>").unwrap();
+    assert!(h.app.config.editor.screen_reader);
+    let screen = h.screen().unwrap().join("\n");
+    assert!(screen.contains("+ widgets"));
+    assert!(!screen.contains('▸') && !screen.contains('│'));
+
+    h.keys(":open main.rs<enter>").unwrap();
+    assert_eq!(h.app.announcement.as_deref(), Some("Opened main.rs"));
+    // The announcement has a line of its own, above the status bar.
+    let rows = h.screen().unwrap();
+    assert!(rows[10].starts_with("Opened main.rs"));
+    h.keys(":").unwrap();
+    assert!(h.screen_contains("command mode").unwrap());
+}
+
+#[test]
+fn test_monochrome_draws_with_attributes_only() {
+    let mut config = wyv::config::Config::default();
+    config.theme = wyv::config::Theme::monochrome();
+    let app = wyv::app::App::new(Path::new("./src"), config).unwrap();
+    let mut h = Harness::with_app(app, 80, 10).unwrap();
+    h.keys(":open main.rs<enter>").unwrap();
+    let buf = h.render().unwrap();
+    assert!(buf
+        .content()
+        .iter()
+        .all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset));
+    // The status bar and the tree's selection stand out reversed.
+    let reversed = ratatui::style::Modifier::REVERSED;
+    assert!(buf[(0, 9)].modifier.contains(reversed));
+    assert!(buf[(0, 0)].modifier.contains(reversed));
+}
+
+#[test]
+fn test_mouse_capture_can_be_turned_off() {
+    let mut h = Harness::new(Path::new("./src"), 80, 10).unwrap();
+    assert!(h.app.config.editor.mouse);
+    h.keys(":set nomouse<enter>").unwrap();
+    assert!(!h.app.config.editor.mouse);
+    h.keys(":set? mouse<enter>").unwrap();
+    assert_eq!(h.app.status.as_deref(), Some("editor.mouse = false (set)"));
+}
+
+#[test]
+fn test_crash_reports_leave_out_what_was_typed() {
+    let dir = std::env::temp_dir().join(format!("wyv-crash-typing-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "\n").unwrap();
+    let mut h = Harness::new(&dir, 80, 20).unwrap();
+    h.app.open(&dir.join("a.txt"), None).unwrap();
+    h.keys("ihunter2<esc>:echo swordfish<esc>").unwrap();
+
+    let report = wyv::crash::write_report(&dir.join("crashes"), "test", "").unwrap();
+    let report = fs::read_to_string(report).unwrap();
+    // Keys are noted one at a time, so look for any single character noted
+    // outside Normal mode.
+    let events = report.split("Last events").nth(1).unwrap();
+    let typed = events.lines().filter(|l| l.starts_with("  key")).find(|l| {
+        let last = l.rsplit(' ').next().unwrap_or_default();
+        last.chars().count() == 1 && !l.contains("Normal")
+    });
+    assert_eq!(typed, None);
+    assert!(report.contains("key in Insert: esc"));
+    assert!(report.contains("key in Normal: CommandMode"));
+    fs::remove_dir_all(dir).unwrap();
+}